@@ -0,0 +1,48 @@
+use std::path::{Path, PathBuf};
+
+use agentfs_sdk::{agentfs_dir, AgentFS, AgentFSOptions};
+use anyhow::{Context, Result as AnyhowResult};
+
+use crate::cmd::init::open_agentfs;
+
+pub async fn handle_backup_command(id_or_path: String, dest: PathBuf) -> AnyhowResult<()> {
+    let options = AgentFSOptions::resolve(&id_or_path)?;
+    eprintln!("Using agent: {}", id_or_path);
+
+    let (_, agentfs) = open_agentfs(options).await?;
+    agentfs
+        .backup_to(&dest)
+        .await
+        .context("Failed to back up database")?;
+
+    eprintln!("Backup written to {}", dest.display());
+    Ok(())
+}
+
+/// Clone an agent, or restore one from a backup file.
+///
+/// `src` is treated as an agent ID if `.agentfs/{src}.db` exists, and as a
+/// backup file path otherwise.
+pub async fn handle_clone_command(src: String, dst: String, force: bool) -> AnyhowResult<()> {
+    let base_dir = agentfs_dir();
+    let is_agent =
+        AgentFSOptions::validate_agent_id(&src) && base_dir.join(format!("{}.db", src)).exists();
+
+    if is_agent {
+        AgentFS::clone_agent(&src, &dst, base_dir, force)
+            .await
+            .context("Failed to clone agent")?;
+        eprintln!("Cloned agent '{}' to '{}'", src, dst);
+    } else {
+        let path = Path::new(&src);
+        if !path.is_file() {
+            anyhow::bail!("Agent or backup file '{}' not found", src);
+        }
+        AgentFS::restore_from(path, &dst, base_dir, force)
+            .await
+            .context("Failed to restore agent")?;
+        eprintln!("Restored agent '{}' from {}", dst, path.display());
+    }
+
+    Ok(())
+}
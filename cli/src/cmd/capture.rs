@@ -0,0 +1,196 @@
+//! Capture of a sandboxed command's stdout and stderr into the agent filesystem.
+//!
+//! Each captured stream is redirected to a pipe. A thread copies everything
+//! read from the pipe to the terminal, as the command would have written it,
+//! and to a spool file on disk. Once the command has exited, the spool is
+//! appended to the requested file in the agent filesystem in fixed-size
+//! blocks, so output of any size is never held in memory as a whole.
+//!
+//! Spooling (rather than writing to the filesystem while the command runs)
+//! keeps the capture off the database while the sandbox itself is using it.
+
+use super::run::CaptureOptions;
+use agentfs_sdk::filesystem::AgentFS;
+use anyhow::{bail, Context, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::thread::JoinHandle;
+
+/// Size of the blocks read from the pipe and appended to the filesystem
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// One captured stream
+struct Tee {
+    /// Destination file in the agent filesystem
+    path: String,
+    /// Descriptor the stream replaces in the child (1 or 2)
+    target_fd: RawFd,
+    /// Write end of the pipe, until handed to the child or closed
+    write_end: Option<OwnedFd>,
+    /// Thread copying the pipe to the terminal and the spool
+    thread: JoinHandle<std::io::Result<File>>,
+}
+
+impl Tee {
+    fn start(path: &str, target_fd: RawFd) -> Result<Self> {
+        if !path.starts_with('/') {
+            bail!("Capture path '{}' must be absolute", path);
+        }
+
+        let mut fds = [0; 2];
+        // SAFETY: pipe2 writes two valid descriptors into `fds` on success.
+        // O_CLOEXEC keeps the read end out of the child; dup2 onto the target
+        // descriptor clears the flag on the child's copy of the write end.
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to create capture pipe");
+        }
+        // SAFETY: both descriptors were just created and are owned by nobody else
+        let (read_end, write_end) =
+            unsafe { (File::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+
+        let spool = tempfile::tempfile().context("Failed to create capture spool file")?;
+        let thread = std::thread::spawn(move || copy_stream(read_end, spool, target_fd));
+
+        Ok(Self {
+            path: path.to_string(),
+            target_fd,
+            write_end: Some(write_end),
+            thread,
+        })
+    }
+
+    /// Wait for the stream to close and append its output to the filesystem
+    async fn save(self, fs: &AgentFS) -> Result<()> {
+        drop(self.write_end);
+        let mut spool = tokio::task::spawn_blocking(move || self.thread.join())
+            .await?
+            .map_err(|_| anyhow::anyhow!("Capture thread panicked"))?
+            .context("Failed to capture output")?;
+        spool.seek(SeekFrom::Start(0))?;
+
+        if let Some((parent, _)) = self.path.rsplit_once('/') {
+            if !parent.is_empty() {
+                fs.create_dir_all(parent).await?;
+            }
+        }
+        // Create the file even if the command printed nothing
+        fs.append_file(&self.path, &[]).await?;
+
+        let mut block = vec![0u8; BLOCK_SIZE];
+        loop {
+            let n = spool.read(&mut block)?;
+            if n == 0 {
+                break;
+            }
+            fs.append_file(&self.path, &block[..n]).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Copy everything read from the pipe to `target_fd` and the spool
+///
+/// Errors writing to the terminal (e.g. a closed pipe downstream) are ignored
+/// so that the capture itself stays complete.
+fn copy_stream(mut pipe: File, mut spool: File, target_fd: RawFd) -> std::io::Result<File> {
+    // SAFETY: the terminal descriptor belongs to this process for its whole
+    // lifetime; ManuallyDrop keeps it open when the File goes away.
+    let mut terminal = std::mem::ManuallyDrop::new(unsafe { File::from_raw_fd(target_fd) });
+    let mut block = vec![0u8; BLOCK_SIZE];
+    loop {
+        let n = match pipe.read(&mut block) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let _ = terminal.write_all(&block[..n]);
+        spool.write_all(&block[..n])?;
+    }
+    Ok(spool)
+}
+
+/// The streams captured for one `run` invocation
+pub struct Capture {
+    stdout: Option<Tee>,
+    stderr: Option<Tee>,
+}
+
+impl Capture {
+    /// Start capturing the streams selected in `options`
+    pub fn start(options: &CaptureOptions) -> Result<Self> {
+        Ok(Self {
+            stdout: options
+                .stdout
+                .as_deref()
+                .map(|path| Tee::start(path, libc::STDOUT_FILENO))
+                .transpose()?,
+            stderr: options
+                .stderr
+                .as_deref()
+                .map(|path| Tee::start(path, libc::STDERR_FILENO))
+                .transpose()?,
+        })
+    }
+
+    fn tees(&self) -> impl Iterator<Item = &Tee> {
+        self.stdout.iter().chain(self.stderr.iter())
+    }
+
+    /// Redirect this process's stdout/stderr into the capture pipes
+    ///
+    /// Meant to be called in a forked child right before exec.
+    pub fn redirect_child(&self) -> std::io::Result<()> {
+        for tee in self.tees() {
+            if let Some(write_end) = &tee.write_end {
+                // SAFETY: both descriptors are valid for the duration of the call
+                if unsafe { libc::dup2(write_end.as_raw_fd(), tee.target_fd) } < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Hand the write end of the stdout pipe to a command being spawned
+    pub fn take_stdout(&mut self) -> Option<OwnedFd> {
+        self.stdout.as_mut()?.write_end.take()
+    }
+
+    /// Hand the write end of the stderr pipe to a command being spawned
+    pub fn take_stderr(&mut self) -> Option<OwnedFd> {
+        self.stderr.as_mut()?.write_end.take()
+    }
+
+    /// Close this process's copies of the write ends
+    ///
+    /// Must be called once the child holds its own copies, otherwise the
+    /// capture never sees end-of-file.
+    pub fn close_write_ends(&mut self) {
+        for tee in [&mut self.stdout, &mut self.stderr].into_iter().flatten() {
+            tee.write_end = None;
+        }
+    }
+
+    /// Wait for the command's output to end and append it to the filesystem
+    pub async fn save(self, fs: &AgentFS) -> Result<()> {
+        for tee in [self.stdout, self.stderr].into_iter().flatten() {
+            let path = tee.path.clone();
+            tee.save(fs)
+                .await
+                .with_context(|| format!("Failed to save captured output to {}", path))?;
+        }
+        Ok(())
+    }
+
+    /// Like [`Capture::save`], for callers that are not async
+    ///
+    /// Runs on a separate thread with its own runtime, so it can also be used
+    /// from code that is itself running on a runtime thread.
+    pub fn save_blocking(self, fs: AgentFS) -> Result<()> {
+        std::thread::spawn(move || crate::get_runtime().block_on(self.save(&fs)))
+            .join()
+            .map_err(|_| anyhow::anyhow!("Capture thread panicked"))?
+    }
+}
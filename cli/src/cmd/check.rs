@@ -0,0 +1,116 @@
+pub use agentfs_sdk::RepairOptions;
+
+use agentfs_sdk::{AgentFSOptions, CheckReport, RepairReport};
+use anyhow::Result as AnyhowResult;
+
+use crate::cmd::init::open_agentfs;
+
+/// Check an agent database and print what was found, repairing it first if
+/// `repair` is given
+///
+/// Returns whether the database is healthy.
+pub async fn handle_check_command(
+    stdout: &mut impl std::io::Write,
+    id_or_path: String,
+    json: bool,
+    repair: Option<RepairOptions>,
+) -> AnyhowResult<bool> {
+    let options = AgentFSOptions::resolve(&id_or_path)?;
+    let (_, agentfs) = open_agentfs(options).await?;
+    let repaired = match repair {
+        Some(options) => Some(agentfs.repair(options).await?),
+        None => None,
+    };
+    let report = agentfs.check().await?;
+
+    if json {
+        let value = match &repaired {
+            Some(repaired) => serde_json::json!({ "repair": repaired, "check": report }),
+            None => serde_json::to_value(&report)?,
+        };
+        writeln!(stdout, "{}", serde_json::to_string_pretty(&value)?)?;
+    } else {
+        if let Some(repaired) = &repaired {
+            write_repair(stdout, repaired)?;
+        }
+        write_report(stdout, &report)?;
+    }
+    Ok(report.is_ok())
+}
+
+fn write_repair(stdout: &mut impl std::io::Write, report: &RepairReport) -> AnyhowResult<()> {
+    for dentry in &report.removed_dentries {
+        writeln!(
+            stdout,
+            "removed entry '{}' in directory {} naming missing inode {}",
+            dentry.name, dentry.parent_ino, dentry.ino
+        )?;
+    }
+    if report.removed_data_blocks > 0 {
+        writeln!(
+            stdout,
+            "removed {} data blocks of missing inodes",
+            report.removed_data_blocks
+        )?;
+    }
+    if report.removed_symlinks > 0 {
+        writeln!(
+            stdout,
+            "removed {} symlink targets of missing inodes",
+            report.removed_symlinks
+        )?;
+    }
+    for ino in &report.recovered_inodes {
+        writeln!(stdout, "linked inode {} as /lost+found/#{}", ino, ino)?;
+    }
+    writeln!(stdout, "{} repairs made", report.changes())?;
+    Ok(())
+}
+
+fn write_report(stdout: &mut impl std::io::Write, report: &CheckReport) -> AnyhowResult<()> {
+    writeln!(
+        stdout,
+        "{} inodes, {} directory entries, {} data blocks",
+        report.inodes, report.dentries, report.data_blocks
+    )?;
+    for message in &report.integrity_errors {
+        writeln!(stdout, "integrity: {}", message)?;
+    }
+    for ino in &report.orphaned_inodes {
+        writeln!(stdout, "orphaned inode {}", ino)?;
+    }
+    for dentry in &report.dangling_dentries {
+        writeln!(
+            stdout,
+            "entry '{}' in directory {} names missing inode {}",
+            dentry.name, dentry.parent_ino, dentry.ino
+        )?;
+    }
+    for dentry in &report.misplaced_dentries {
+        writeln!(
+            stdout,
+            "entry '{}' (inode {}) is under {}, which is missing or not a directory",
+            dentry.name, dentry.ino, dentry.parent_ino
+        )?;
+    }
+    for ino in &report.orphaned_data {
+        writeln!(stdout, "data blocks left for missing inode {}", ino)?;
+    }
+    for ino in &report.orphaned_symlinks {
+        writeln!(stdout, "symlink target left for missing inode {}", ino)?;
+    }
+    for mismatch in &report.link_count_mismatches {
+        writeln!(
+            stdout,
+            "inode {} has link count {} but {} directory entries",
+            mismatch.ino, mismatch.nlink, mismatch.dentries
+        )?;
+    }
+
+    if report.is_ok() {
+        writeln!(stdout, "ok")?;
+    } else {
+        writeln!(stdout, "{} problems found", report.problems())?;
+    }
+    Ok(())
+}
@@ -1,21 +1,116 @@
 use std::collections::VecDeque;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context as TaskContext, Poll};
+use std::time::{Duration, UNIX_EPOCH};
 
-use agentfs_sdk::AgentFSOptions;
+use agentfs_sdk::filesystem::mode::format_mode;
+use agentfs_sdk::filesystem::{self, DirEntry, FileType, Stats};
+use agentfs_sdk::{AgentFSOptions, ExportOptions, ImportOptions};
 use anyhow::{Context, Result as AnyhowResult};
+use chrono::TimeZone;
+use tokio::io::AsyncWrite;
 use turso::Value;
 
 use crate::cmd::init::open_agentfs;
 
 const ROOT_INO: i64 = 1;
-const S_IFMT: u32 = 0o170000;
-const S_IFDIR: u32 = 0o040000;
-const S_IFREG: u32 = 0o100000;
-const S_IFLNK: u32 = 0o120000;
+
+/// Order of the entries of each directory listed by `fs ls`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LsSort {
+    #[default]
+    Name,
+    /// Largest first
+    Size,
+    /// Most recently modified first
+    Mtime,
+}
+
+impl std::str::FromStr for LsSort {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "name" => Ok(LsSort::Name),
+            "size" => Ok(LsSort::Size),
+            "mtime" => Ok(LsSort::Mtime),
+            _ => anyhow::bail!("Invalid sort order: {}", s),
+        }
+    }
+}
+
+/// Which entries `fs ls` prints, and in what order
+#[derive(Debug, Clone, Default)]
+pub struct LsOptions {
+    /// Print at most this many entries (default: all)
+    pub limit: Option<usize>,
+    /// Skip this many entries before printing
+    pub offset: usize,
+    pub sort: LsSort,
+    /// Reverse the order within each directory
+    pub reverse: bool,
+}
+
+/// Entries fetched per query when a directory is listed in name order
+const LS_PAGE_SIZE: usize = 1000;
+
+struct LsEntry {
+    name: String,
+    ino: i64,
+    mode: u32,
+    size: i64,
+    mtime: i64,
+}
+
+/// Entries of a directory that sort after `after` by name, at most `limit` of them
+async fn ls_entries(
+    conn: &turso::Connection,
+    parent_ino: i64,
+    after: Option<&str>,
+    limit: Option<usize>,
+) -> AnyhowResult<Vec<LsEntry>> {
+    let limit = limit.map_or(i64::MAX, |limit| i64::try_from(limit).unwrap_or(i64::MAX));
+    let mut rows = conn
+        .query(
+            "SELECT d.name, d.ino, i.mode, i.size, i.mtime FROM fs_dentry d
+             JOIN fs_inode i ON d.ino = i.ino
+             WHERE d.parent_ino = ? AND d.name > ?
+             ORDER BY d.name
+             LIMIT ?",
+            (parent_ino, after.unwrap_or(""), limit),
+        )
+        .await
+        .context("Failed to query directory entries")?;
+
+    let integer = |row: &turso::Row, col: usize| {
+        row.get_value(col)
+            .ok()
+            .and_then(|v| v.as_integer().copied())
+            .unwrap_or(0)
+    };
+    let mut entries = Vec::new();
+    while let Some(row) = rows.next().await.context("Failed to fetch row")? {
+        let name = match row.get_value(0) {
+            Ok(Value::Text(name)) => name,
+            _ => String::new(),
+        };
+        entries.push(LsEntry {
+            name,
+            ino: integer(&row, 1),
+            mode: integer(&row, 2) as u32,
+            size: integer(&row, 3),
+            mtime: integer(&row, 4),
+        });
+    }
+    Ok(entries)
+}
 
 pub async fn ls_filesystem(
     stdout: &mut impl std::io::Write,
     id_or_path: String,
     path: &str,
+    listing: &LsOptions,
 ) -> AnyhowResult<()> {
     let options = AgentFSOptions::resolve(&id_or_path)?;
     eprintln!("Using agent: {}", id_or_path);
@@ -26,92 +121,427 @@ pub async fn ls_filesystem(
     if path != "/" {
         anyhow::bail!("Only root directory (/) is currently supported");
     }
+    if listing.limit == Some(0) {
+        return Ok(());
+    }
+
+    // In name order a directory is read a page at a time, so a limited
+    // listing stops reading once it has printed enough. Other orders need
+    // every entry of a directory before the first one can be printed.
+    let paged = listing.sort == LsSort::Name && !listing.reverse;
+    let mut skip = listing.offset;
+    let mut remaining = listing.limit;
 
     let mut queue: VecDeque<(i64, String)> = VecDeque::new();
     queue.push_back((ROOT_INO, String::new()));
 
     while let Some((parent_ino, prefix)) = queue.pop_front() {
-        let query = format!(
-            "SELECT d.name, d.ino, i.mode FROM fs_dentry d
-             JOIN fs_inode i ON d.ino = i.ino
-             WHERE d.parent_ino = {}
-             ORDER BY d.name",
-            parent_ino
-        );
+        let mut after: Option<String> = None;
+        loop {
+            let entries = if paged {
+                ls_entries(&conn, parent_ino, after.as_deref(), Some(LS_PAGE_SIZE)).await?
+            } else {
+                let mut entries = ls_entries(&conn, parent_ino, None, None).await?;
+                // Stable sorts, so ties stay in name order
+                match listing.sort {
+                    LsSort::Name => {}
+                    LsSort::Size => entries.sort_by(|a, b| b.size.cmp(&a.size)),
+                    LsSort::Mtime => entries.sort_by(|a, b| b.mtime.cmp(&a.mtime)),
+                }
+                if listing.reverse {
+                    entries.reverse();
+                }
+                entries
+            };
+            let last_page = !paged || entries.len() < LS_PAGE_SIZE;
+            after = entries.last().map(|entry| entry.name.clone());
+
+            for entry in entries {
+                let is_dir = FileType::from_mode(entry.mode) == Some(FileType::Directory);
+                let full_path = if prefix.is_empty() {
+                    entry.name
+                } else {
+                    format!("{}/{}", prefix, entry.name)
+                };
+                if is_dir {
+                    queue.push_back((entry.ino, full_path.clone()));
+                }
 
-        let mut rows = conn
-            .query(&query, ())
-            .await
-            .context("Failed to query directory entries")?;
-
-        let mut entries = Vec::new();
-        while let Some(row) = rows.next().await.context("Failed to fetch row")? {
-            let name: String = row
-                .get_value(0)
-                .ok()
-                .and_then(|v| {
-                    if let Value::Text(s) = v {
-                        Some(s.clone())
-                    } else {
-                        None
+                if skip > 0 {
+                    skip -= 1;
+                    continue;
+                }
+                let type_char = if is_dir { 'd' } else { 'f' };
+                stdout
+                    .write_fmt(format_args!("{} {}\n", type_char, full_path))
+                    .context("Failed to write to stdout")?;
+                if let Some(remaining) = remaining.as_mut() {
+                    *remaining -= 1;
+                    if *remaining == 0 {
+                        return Ok(());
                     }
-                })
-                .unwrap_or_default();
+                }
+            }
 
-            let ino: i64 = row
-                .get_value(1)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0);
+            if last_page {
+                break;
+            }
+        }
+    }
 
-            let mode: u32 = row
-                .get_value(2)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .unwrap_or(0) as u32;
+    Ok(())
+}
 
-            entries.push((name, ino, mode));
-        }
+pub async fn tree_filesystem(
+    stdout: &mut impl std::io::Write,
+    id_or_path: String,
+    path: &str,
+    depth: Option<usize>,
+    all: bool,
+) -> AnyhowResult<()> {
+    let options = AgentFSOptions::resolve(&id_or_path)?;
+    let (_, agentfs) = open_agentfs(options).await?;
+    let fs = &agentfs.fs;
 
-        for (name, ino, mode) in entries {
-            let is_dir = mode & S_IFMT == S_IFDIR;
-            let type_char = if is_dir { 'd' } else { 'f' };
-            let full_path = if prefix.is_empty() {
-                name.clone()
-            } else {
-                format!("{}/{}", prefix, name)
-            };
+    let root = match path.trim_end_matches('/') {
+        "" => "/".to_string(),
+        trimmed => trimmed.to_string(),
+    };
+    match fs.stat(&root).await? {
+        Some(stats) if stats.is_directory() => {}
+        Some(_) => anyhow::bail!("Not a directory: {}", path),
+        None => anyhow::bail!("Directory not found: {}", path),
+    }
+
+    /// A directory whose entries are being printed
+    struct Level {
+        path: String,
+        prefix: String,
+        entries: std::vec::IntoIter<DirEntry>,
+    }
 
-            stdout
-                .write_fmt(format_args!("{} {}\n", type_char, full_path))
-                .context("Failed to write to stdout")?;
+    let list = |path: String| async move {
+        let entries = fs.readdir_plus(&path).await?.unwrap_or_default();
+        let entries: Vec<DirEntry> = entries
+            .into_iter()
+            .filter(|entry| all || !entry.name.starts_with('.'))
+            .collect();
+        AnyhowResult::Ok(entries.into_iter())
+    };
+
+    writeln!(stdout, "{}", root)?;
+    let mut stack = vec![Level {
+        entries: list(root.clone()).await?,
+        path: root,
+        prefix: String::new(),
+    }];
+    let (mut dirs, mut files) = (0, 0);
+
+    while let Some(level) = stack.last_mut() {
+        let Some(entry) = level.entries.next() else {
+            stack.pop();
+            continue;
+        };
+        let last = level.entries.len() == 0;
+        let child_path = if level.path == "/" {
+            format!("/{}", entry.name)
+        } else {
+            format!("{}/{}", level.path, entry.name)
+        };
+        let child_prefix = format!("{}{}", level.prefix, if last { "    " } else { "│   " });
+        let branch = if last { "└── " } else { "├── " };
+        write!(stdout, "{}{}", level.prefix, branch)?;
 
-            if is_dir {
-                queue.push_back((ino, full_path));
+        if entry.stats.is_directory() {
+            writeln!(stdout, "{}/", entry.name)?;
+            dirs += 1;
+            if depth.is_none_or(|depth| stack.len() < depth) {
+                stack.push(Level {
+                    entries: list(child_path.clone()).await?,
+                    path: child_path,
+                    prefix: child_prefix,
+                });
             }
+        } else if entry.stats.is_symlink() {
+            let target = fs.readlink(&child_path).await?.unwrap_or_default();
+            writeln!(stdout, "{} -> {}", entry.name, target)?;
+            files += 1;
+        } else {
+            writeln!(stdout, "{}", entry.name)?;
+            files += 1;
         }
     }
 
+    writeln!(
+        stdout,
+        "\n{} {}, {} {}",
+        dirs,
+        if dirs == 1 {
+            "directory"
+        } else {
+            "directories"
+        },
+        files,
+        if files == 1 { "file" } else { "files" }
+    )?;
     Ok(())
 }
 
+/// Adapts a blocking writer such as stdout for the SDK's async streaming reads
+struct BlockingWriter<'a, W>(&'a mut W);
+
+impl<W: std::io::Write + Send> AsyncWrite for BlockingWriter<'_, W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Poll::Ready(self.0.write(buf))
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        _cx: &mut TaskContext<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Poll::Ready(self.0.flush())
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// How much of a file `fs cat` looks at to tell binary files from text.
+/// Like grep, a NUL byte in there means binary.
+const BINARY_SNIFF_LEN: usize = 8000;
+
+/// Holds back the start of a file until it is known not to be binary
+struct TextOnlyWriter<'a, W> {
+    out: &'a mut W,
+    /// Bytes written so far, until enough have been seen to decide
+    head: Option<Vec<u8>>,
+    binary: bool,
+}
+
+impl<'a, W: std::io::Write> TextOnlyWriter<'a, W> {
+    fn new(out: &'a mut W) -> Self {
+        Self {
+            out,
+            head: Some(Vec::new()),
+            binary: false,
+        }
+    }
+
+    fn release_head(&mut self) -> std::io::Result<()> {
+        let Some(head) = self.head.take() else {
+            return Ok(());
+        };
+        if head[..head.len().min(BINARY_SNIFF_LEN)].contains(&0) {
+            self.binary = true;
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "binary file",
+            ));
+        }
+        self.out.write_all(&head)
+    }
+}
+
+impl<W: std::io::Write + Send> AsyncWrite for TextOnlyWriter<'_, W> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut TaskContext<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let Some(head) = self.head.as_mut() else {
+            return Poll::Ready(self.out.write(buf));
+        };
+        head.extend_from_slice(buf);
+        if head.len() >= BINARY_SNIFF_LEN {
+            if let Err(e) = self.release_head() {
+                return Poll::Ready(Err(e));
+            }
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(
+        mut self: Pin<&mut Self>,
+        _cx: &mut TaskContext<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Poll::Ready(self.release_head().and_then(|()| self.out.flush()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<std::io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Print a file to `stdout`
+///
+/// Unless `allow_binary` is set, nothing is printed for files that look
+/// binary, to keep them from garbling the terminal.
 pub async fn cat_filesystem(
-    stdout: &mut impl std::io::Write,
+    stdout: &mut (impl std::io::Write + Send),
     id_or_path: String,
     path: &str,
+    allow_binary: bool,
 ) -> AnyhowResult<()> {
     let options = AgentFSOptions::resolve(&id_or_path)?;
     let (_, agentfs) = open_agentfs(options).await?;
 
-    match agentfs.fs.read_file(path).await? {
-        Some(file) => {
-            stdout.write_all(&file)?;
-            Ok(())
+    // Blocks go straight to stdout, so huge files aren't held in memory
+    let found = if allow_binary {
+        agentfs
+            .fs
+            .read_file_to(path, &mut BlockingWriter(stdout))
+            .await?
+    } else {
+        let mut writer = TextOnlyWriter::new(stdout);
+        match agentfs.fs.read_file_to(path, &mut writer).await {
+            Err(_) if writer.binary => {
+                anyhow::bail!("{} is a binary file; use --force to print it anyway", path)
+            }
+            result => result?,
         }
+    };
+    match found {
+        Some(_) => Ok(()),
         None => anyhow::bail!("File not found: {}", path),
     }
 }
 
+pub async fn stat_filesystem(
+    stdout: &mut impl std::io::Write,
+    id_or_path: String,
+    path: &str,
+    dereference: bool,
+    json: bool,
+) -> AnyhowResult<()> {
+    let options = AgentFSOptions::resolve(&id_or_path)?;
+    let (_, agentfs) = open_agentfs(options).await?;
+
+    let stats = if dereference {
+        agentfs.fs.stat(path).await?
+    } else {
+        agentfs.fs.lstat(path).await?
+    };
+    let Some(stats) = stats else {
+        anyhow::bail!("File not found: {}", path);
+    };
+
+    if json {
+        let value = serde_json::json!({
+            "path": path,
+            "type": stats.file_type(),
+            "mode": format!("{:04o}", stats.permissions()),
+            "size": stats.size,
+            "nlink": stats.nlink,
+            "ino": stats.ino,
+            "uid": stats.uid,
+            "gid": stats.gid,
+            "atime": stats.atime,
+            "mtime": stats.mtime,
+            "ctime": stats.ctime,
+        });
+        writeln!(stdout, "{}", serde_json::to_string_pretty(&value)?)?;
+    } else {
+        write_stats(stdout, path, &stats)?;
+    }
+    Ok(())
+}
+
+/// Print how a file's blocks are stored, one block per line
+pub async fn blocks_filesystem(
+    stdout: &mut impl std::io::Write,
+    id_or_path: String,
+    path: &str,
+) -> AnyhowResult<()> {
+    let options = AgentFSOptions::resolve(&id_or_path)?;
+    let (_, agentfs) = open_agentfs(options).await?;
+
+    let blocks = agentfs
+        .fs
+        .block_map(path)
+        .await
+        .with_context(|| format!("Failed to read blocks of {}", path))?;
+    let size = match agentfs.fs.stat(path).await? {
+        Some(stats) => stats.size as u64,
+        None => anyhow::bail!("File not found: {}", path),
+    };
+
+    writeln!(
+        stdout,
+        "{:>8} {:>12} {:>8} {:>8}  COMPRESSED",
+        "CHUNK", "OFFSET", "LENGTH", "STORED"
+    )?;
+    let mut next_chunk = 0;
+    let mut holes = 0;
+    for block in &blocks {
+        holes += block.chunk_index - next_chunk;
+        next_chunk = block.chunk_index + 1;
+        writeln!(
+            stdout,
+            "{:>8} {:>12} {:>8} {:>8}  {}",
+            block.chunk_index,
+            block.offset,
+            block.length,
+            block.stored_length,
+            if block.compressed { "yes" } else { "no" }
+        )?;
+    }
+
+    let data: u64 = blocks.iter().map(|block| block.length).sum();
+    let stored: u64 = blocks.iter().map(|block| block.stored_length).sum();
+    writeln!(
+        stdout,
+        "blocks: {}, holes: {}, data: {} bytes, stored: {} bytes, file size: {}",
+        blocks.len(),
+        holes,
+        data,
+        stored,
+        size
+    )?;
+    // Truncate trims the blocks to the new size, so data past it means a bug
+    if blocks
+        .last()
+        .is_some_and(|last| last.offset + last.length > size)
+    {
+        writeln!(stdout, "warning: blocks extend past the end of the file")?;
+    }
+    Ok(())
+}
+
+/// Write file metadata in a `stat(1)`-like layout
+fn write_stats(stdout: &mut impl std::io::Write, path: &str, stats: &Stats) -> AnyhowResult<()> {
+    writeln!(stdout, "  File: {}", path)?;
+    writeln!(stdout, "  Type: {}", stats.file_type())?;
+    writeln!(
+        stdout,
+        "  Mode: {:04o} ({})",
+        stats.permissions(),
+        format_mode(stats.mode)
+    )?;
+    writeln!(stdout, "  Size: {}", stats.size)?;
+    writeln!(stdout, " Links: {}", stats.nlink)?;
+    writeln!(stdout, " Inode: {}", stats.ino)?;
+    writeln!(stdout, "   Uid: {}", stats.uid)?;
+    writeln!(stdout, "   Gid: {}", stats.gid)?;
+    writeln!(stdout, "Access: {}", format_timestamp(stats.atime))?;
+    writeln!(stdout, "Modify: {}", format_timestamp(stats.mtime))?;
+    writeln!(stdout, "Change: {}", format_timestamp(stats.ctime))?;
+    Ok(())
+}
+
+/// Format timestamp as YYYY-MM-DD HH:MM:SS (UTC)
+fn format_timestamp(timestamp: i64) -> String {
+    chrono::Utc
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| format!("Invalid timestamp: {}", timestamp))
+}
+
 pub async fn write_filesystem(id_or_path: String, path: &str, content: &str) -> AnyhowResult<()> {
     let options = AgentFSOptions::resolve(&id_or_path)?;
     let (_, agentfs) = open_agentfs(options).await?;
@@ -132,6 +562,223 @@ pub async fn write_filesystem(id_or_path: String, path: &str, content: &str) ->
     Ok(())
 }
 
+/// Remove every file matching `pattern`, and matching directories if `recursive`
+pub async fn rm_filesystem(
+    stdout: &mut impl std::io::Write,
+    id_or_path: String,
+    pattern: &str,
+    recursive: bool,
+) -> AnyhowResult<()> {
+    let options = AgentFSOptions::resolve(&id_or_path)?;
+    let (_, agentfs) = open_agentfs(options).await?;
+
+    let removed = agentfs
+        .fs
+        .remove_glob(pattern, recursive)
+        .await
+        .with_context(|| format!("Failed to remove {}", pattern))?;
+    writeln!(stdout, "Removed {} paths matching {}", removed, pattern)?;
+    Ok(())
+}
+
+/// Copy a host directory tree into the agent filesystem at `dst`
+pub async fn import_filesystem(
+    stdout: &mut impl std::io::Write,
+    id_or_path: String,
+    host_dir: &Path,
+    dst: &str,
+    preserve_mode: bool,
+) -> AnyhowResult<()> {
+    let options = AgentFSOptions::resolve(&id_or_path)?;
+    let (_, agentfs) = open_agentfs(options).await?;
+
+    let summary = agentfs
+        .fs
+        .import_dir_with(host_dir, dst, ImportOptions { preserve_mode })
+        .await
+        .with_context(|| format!("Failed to import {}", host_dir.display()))?;
+    writeln!(
+        stdout,
+        "Imported {} files ({} bytes), {} directories and {} symlinks into {}",
+        summary.files, summary.bytes, summary.directories, summary.symlinks, dst
+    )?;
+    Ok(())
+}
+
+/// Copy the directory tree at `src` out of the agent filesystem into `host_dir`
+pub async fn export_filesystem(
+    stdout: &mut impl std::io::Write,
+    id_or_path: String,
+    src: &str,
+    host_dir: &Path,
+    dereference: bool,
+) -> AnyhowResult<()> {
+    let options = AgentFSOptions::resolve(&id_or_path)?;
+    let (_, agentfs) = open_agentfs(options).await?;
+
+    let options = ExportOptions {
+        follow_symlinks: dereference,
+    };
+    let summary = agentfs
+        .fs
+        .export_dir_with(src, host_dir, options)
+        .await
+        .with_context(|| format!("Failed to export {}", src))?;
+    writeln!(
+        stdout,
+        "Exported {} files ({} bytes), {} directories and {} symlinks to {}",
+        summary.files,
+        summary.bytes,
+        summary.directories,
+        summary.symlinks,
+        host_dir.display()
+    )?;
+    if summary.skipped > 0 {
+        writeln!(
+            stdout,
+            "Skipped {} dangling or looping symlinks",
+            summary.skipped
+        )?;
+    }
+    Ok(())
+}
+
+/// Split a `mv` operand into the agent it names and the path inside it
+///
+/// `ID:/path` names a path in another agent, where `ID` is an agent ID or a
+/// database path. Anything else is a path in the default agent.
+fn parse_agent_path<'a>(default_id: &'a str, spec: &'a str) -> (&'a str, &'a str) {
+    match spec.find(":/") {
+        Some(i) if i > 0 => (&spec[..i], &spec[i + 1..]),
+        _ => (default_id, spec),
+    }
+}
+
+/// Counts of what [`copy_tree`] copied
+#[derive(Debug, Default)]
+struct CopySummary {
+    files: u64,
+    bytes: u64,
+    directories: u64,
+    symlinks: u64,
+}
+
+/// Copy the file, symlink or directory tree at `src` in `from` to `dst` in `to`
+///
+/// Permission bits are kept. Returns what was copied and the source paths in
+/// the order they were visited, parents before children.
+async fn copy_tree(
+    from: &filesystem::AgentFS,
+    src: &str,
+    to: &filesystem::AgentFS,
+    dst: &str,
+) -> AnyhowResult<(CopySummary, Vec<String>)> {
+    let mut summary = CopySummary::default();
+    let mut visited = Vec::new();
+    let mut stack = vec![(src.to_string(), dst.to_string())];
+
+    while let Some((src, dst)) = stack.pop() {
+        let Some(stats) = from.lstat(&src).await? else {
+            anyhow::bail!("File not found: {}", src);
+        };
+        if stats.is_directory() {
+            to.mkdir(&dst).await?;
+            let mut names = from.readdir(&src).await?.unwrap_or_default();
+            // Popped from the stack in name order
+            names.sort_unstable_by(|a, b| b.cmp(a));
+            for name in names {
+                stack.push((format!("{}/{}", src, name), format!("{}/{}", dst, name)));
+            }
+            summary.directories += 1;
+        } else if stats.is_symlink() {
+            let target = from.readlink(&src).await?.unwrap_or_default();
+            to.symlink(&target, &dst).await?;
+            summary.symlinks += 1;
+        } else {
+            let data = from.read_file(&src).await?.unwrap_or_default();
+            to.write_file(&dst, &data).await?;
+            summary.files += 1;
+            summary.bytes += data.len() as u64;
+        }
+        if !stats.is_symlink() {
+            to.chmod(&dst, stats.permissions()).await?;
+        }
+        visited.push(src);
+    }
+    Ok((summary, visited))
+}
+
+/// Move or rename `from` to `to`
+///
+/// Within one agent this is a rename. Either operand may name a path in
+/// another agent as `ID:/path`, in which case the tree is copied between the
+/// two databases and the source removed afterwards, unless `keep` is set.
+pub async fn mv_filesystem(
+    stdout: &mut impl std::io::Write,
+    id_or_path: String,
+    from: &str,
+    to: &str,
+    keep: bool,
+) -> AnyhowResult<()> {
+    let (src_id, src_path) = parse_agent_path(&id_or_path, from);
+    let (dst_id, dst_path) = parse_agent_path(&id_or_path, to);
+    let src_options = AgentFSOptions::resolve(src_id)?;
+    let dst_options = AgentFSOptions::resolve(dst_id)?;
+    let same_agent = src_options.db_path()? == dst_options.db_path()?;
+
+    let (_, src_agent) = open_agentfs(src_options).await?;
+    if same_agent && !keep {
+        src_agent
+            .fs
+            .rename(src_path, dst_path)
+            .await
+            .with_context(|| format!("Failed to move {} to {}", from, to))?;
+        return Ok(());
+    }
+    let dst_agent = match same_agent {
+        true => None,
+        false => Some(open_agentfs(dst_options).await?.1),
+    };
+    let dst_fs = dst_agent.as_ref().map_or(&src_agent.fs, |agent| &agent.fs);
+
+    if same_agent && dst_path.starts_with(&format!("{}/", src_path.trim_end_matches('/'))) {
+        anyhow::bail!("Cannot copy {} into itself", from);
+    }
+    if dst_fs.lstat(dst_path).await?.is_some() {
+        anyhow::bail!("Destination already exists: {}", to);
+    }
+    if let Some((parent, _)) = dst_path.trim_end_matches('/').rsplit_once('/') {
+        if !parent.is_empty() {
+            dst_fs.create_dir_all(parent).await?;
+        }
+    }
+    let (summary, visited) = copy_tree(&src_agent.fs, src_path, dst_fs, dst_path)
+        .await
+        .with_context(|| format!("Failed to copy {} to {}", from, to))?;
+    if !keep {
+        // Children were visited after their parents, so remove them first
+        for path in visited.iter().rev() {
+            src_agent
+                .fs
+                .remove(path)
+                .await
+                .with_context(|| format!("Failed to remove {}", path))?;
+        }
+    }
+    writeln!(
+        stdout,
+        "{} {} files ({} bytes), {} directories and {} symlinks from {} to {}",
+        if keep { "Copied" } else { "Moved" },
+        summary.files,
+        summary.bytes,
+        summary.directories,
+        summary.symlinks,
+        from,
+        to
+    )?;
+    Ok(())
+}
+
 /// Represents a change type in the overlay filesystem
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum ChangeType {
@@ -152,11 +799,11 @@ impl std::fmt::Display for ChangeType {
 
 /// Get file type character
 fn file_type_char(mode: u32) -> char {
-    match mode & S_IFMT {
-        S_IFDIR => 'd',
-        S_IFLNK => 'l',
-        S_IFREG => 'f',
-        _ => '?',
+    match FileType::from_mode(mode) {
+        Some(FileType::Directory) => 'd',
+        Some(FileType::Symlink) => 'l',
+        Some(FileType::File) => 'f',
+        None => '?',
     }
 }
 
@@ -241,13 +888,78 @@ pub async fn diff_filesystem(id_or_path: String) -> AnyhowResult<()> {
     Ok(())
 }
 
+pub async fn log_filesystem(
+    stdout: &mut impl std::io::Write,
+    id_or_path: String,
+    since: Option<i64>,
+    json: bool,
+) -> AnyhowResult<()> {
+    let options = AgentFSOptions::resolve(&id_or_path)?;
+    let (_, agentfs) = open_agentfs(options).await?;
+
+    if !agentfs.fs.journal_enabled() {
+        anyhow::bail!("Journaling is not enabled for this agent (create it with `init --journal`)");
+    }
+
+    let since = match since {
+        Some(seconds) if seconds >= 0 => UNIX_EPOCH + Duration::from_secs(seconds as u64),
+        Some(seconds) => UNIX_EPOCH - Duration::from_secs(seconds.unsigned_abs()),
+        None => UNIX_EPOCH,
+    };
+    let entries = agentfs.fs.journal(since).await?;
+
+    if json {
+        let values: Vec<_> = entries
+            .iter()
+            .map(|entry| {
+                serde_json::json!({
+                    "id": entry.id,
+                    "timestamp": entry.timestamp,
+                    "op": entry.op.as_str(),
+                    "path": entry.path,
+                    "target": entry.target,
+                    "size": entry.size,
+                    "sha256": entry.hash,
+                })
+            })
+            .collect();
+        writeln!(stdout, "{}", serde_json::to_string_pretty(&values)?)?;
+        return Ok(());
+    }
+
+    for entry in entries {
+        write!(
+            stdout,
+            "{}  {:<6}  {}",
+            format_timestamp(entry.timestamp),
+            entry.op.as_str(),
+            entry.path
+        )?;
+        if let Some(target) = &entry.target {
+            write!(stdout, " -> {}", target)?;
+        }
+        if let Some(size) = entry.size {
+            write!(stdout, "  {} bytes", size)?;
+        }
+        if let Some(hash) = &entry.hash {
+            write!(stdout, "  sha256:{}", hash)?;
+        }
+        writeln!(stdout)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use agentfs_sdk::{AgentFS, AgentFSOptions};
     use tempfile::NamedTempFile;
 
     use crate::cmd::fs::cat_filesystem;
-    use crate::cmd::fs::ls_filesystem;
+    use crate::cmd::fs::{
+        blocks_filesystem, export_filesystem, format_mode, import_filesystem, log_filesystem,
+        mv_filesystem, parse_agent_path, rm_filesystem, stat_filesystem, tree_filesystem,
+    };
+    use crate::cmd::fs::{ls_filesystem, LsOptions, LsSort};
 
     async fn agentfs() -> (AgentFS, String, NamedTempFile) {
         let file = NamedTempFile::new().unwrap();
@@ -262,7 +974,9 @@ mod tests {
     pub async fn cat_file_not_found() {
         let (_agentfs, path, _file) = agentfs().await;
         let mut buf = Vec::new();
-        let err = cat_filesystem(&mut buf, path, "test.md").await.unwrap_err();
+        let err = cat_filesystem(&mut buf, path, "test.md", true)
+            .await
+            .unwrap_err();
         assert!(err.to_string().contains("File not found"));
     }
 
@@ -272,7 +986,9 @@ mod tests {
         let content = b"hello, agentfs";
         agentfs.fs.write_file("test.md", content).await.unwrap();
         let mut buf = Vec::new();
-        cat_filesystem(&mut buf, path, "test.md").await.unwrap();
+        cat_filesystem(&mut buf, path, "test.md", true)
+            .await
+            .unwrap();
         assert_eq!(buf, content);
     }
 
@@ -282,15 +998,346 @@ mod tests {
         let content = vec![100u8; 4 * 1024 * 1024];
         agentfs.fs.write_file("test.md", &content).await.unwrap();
         let mut buf = Vec::new();
-        cat_filesystem(&mut buf, path, "test.md").await.unwrap();
+        cat_filesystem(&mut buf, path, "test.md", true)
+            .await
+            .unwrap();
         assert_eq!(buf, content);
     }
 
+    #[tokio::test]
+    pub async fn cat_binary_file() {
+        let (agentfs, path, _file) = agentfs().await;
+        let binary = b"\x7fELF\x02\x01\x01\x00\x00\x00";
+        agentfs.fs.write_file("prog", binary).await.unwrap();
+        let mut buf = Vec::new();
+        let err = cat_filesystem(&mut buf, path.clone(), "prog", false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("binary file"));
+        assert!(buf.is_empty());
+
+        cat_filesystem(&mut buf, path.clone(), "prog", true)
+            .await
+            .unwrap();
+        assert_eq!(buf, binary);
+
+        // Text longer than the sniffed prefix still comes through whole
+        let text = "line\n".repeat(4096);
+        agentfs
+            .fs
+            .write_file("log.txt", text.as_bytes())
+            .await
+            .unwrap();
+        let mut buf = Vec::new();
+        cat_filesystem(&mut buf, path, "log.txt", false)
+            .await
+            .unwrap();
+        assert_eq!(buf, text.as_bytes());
+    }
+
+    #[tokio::test]
+    pub async fn cat_file_with_sql_metacharacters() {
+        let (agentfs, path, _file) = agentfs().await;
+        let name = "it's; DROP TABLE fs_dentry; --.md";
+        agentfs.fs.write_file(name, b"quoted").await.unwrap();
+        agentfs.fs.write_file("other.md", b"other").await.unwrap();
+        let mut buf = Vec::new();
+        cat_filesystem(&mut buf, path.clone(), name, true)
+            .await
+            .unwrap();
+        assert_eq!(buf, b"quoted");
+
+        let mut buf = Vec::new();
+        ls_filesystem(&mut buf, path, "/", &LsOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            buf,
+            b"f it's; DROP TABLE fs_dentry; --.md
+f other.md
+"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn stat_file() {
+        let (agentfs, path, _file) = agentfs().await;
+        agentfs.fs.write_file("test.md", b"hello").await.unwrap();
+        let mut buf = Vec::new();
+        stat_filesystem(&mut buf, path, "test.md", false, false)
+            .await
+            .unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert!(out.contains("  Type: file\n"));
+        assert!(out.contains("  Mode: 0644 (-rw-r--r--)\n"));
+        assert!(out.contains("  Size: 5\n"));
+        assert!(out.contains(" Links: 1\n"));
+    }
+
+    #[tokio::test]
+    pub async fn stat_dir_json() {
+        let (agentfs, path, _file) = agentfs().await;
+        agentfs.fs.mkdir("a").await.unwrap();
+        let mut buf = Vec::new();
+        stat_filesystem(&mut buf, path, "a", false, true)
+            .await
+            .unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&buf).unwrap();
+        assert_eq!(value["type"], "directory");
+        assert_eq!(value["mode"], "0755");
+        assert_eq!(value["path"], "a");
+    }
+
+    #[tokio::test]
+    pub async fn stat_not_found() {
+        let (_agentfs, path, _file) = agentfs().await;
+        let mut buf = Vec::new();
+        let err = stat_filesystem(&mut buf, path, "missing", false, false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("File not found"));
+    }
+
+    #[tokio::test]
+    pub async fn tree_nested() {
+        let (agentfs, path, _file) = agentfs().await;
+        agentfs.fs.mkdir("a").await.unwrap();
+        agentfs.fs.mkdir("a/b").await.unwrap();
+        agentfs.fs.write_file("a/b/1.md", b"1").await.unwrap();
+        agentfs.fs.write_file("a/2.md", b"2").await.unwrap();
+        agentfs.fs.write_file(".hidden", b"h").await.unwrap();
+        agentfs.fs.write_file("3.md", b"3").await.unwrap();
+        let mut buf = Vec::new();
+        tree_filesystem(&mut buf, path, "/", None, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "/
+├── 3.md
+└── a/
+    ├── 2.md
+    └── b/
+        └── 1.md
+
+2 directories, 3 files
+"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn tree_depth_and_all() {
+        let (agentfs, path, _file) = agentfs().await;
+        agentfs.fs.mkdir("a").await.unwrap();
+        agentfs.fs.write_file("a/1.md", b"1").await.unwrap();
+        agentfs.fs.write_file(".hidden", b"h").await.unwrap();
+        let mut buf = Vec::new();
+        tree_filesystem(&mut buf, path, "/", Some(1), true)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "/
+├── .hidden
+└── a/
+
+1 directory, 1 file
+"
+        );
+    }
+
+    #[tokio::test]
+    pub async fn import_host_dir() {
+        let (agentfs, path, _file) = agentfs().await;
+        let host = tempfile::tempdir().unwrap();
+        std::fs::create_dir(host.path().join("sub")).unwrap();
+        std::fs::write(host.path().join("a.txt"), b"hello").unwrap();
+        std::fs::write(host.path().join("sub/b.txt"), b"world!").unwrap();
+
+        let mut buf = Vec::new();
+        import_filesystem(&mut buf, path, host.path(), "/seed", false)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "Imported 2 files (11 bytes), 1 directories and 0 symlinks into /seed\n"
+        );
+        assert_eq!(
+            agentfs.fs.read_file("/seed/sub/b.txt").await.unwrap(),
+            Some(b"world!".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    pub async fn export_to_host_dir() {
+        let (agentfs, path, _file) = agentfs().await;
+        agentfs.fs.mkdir("/out").await.unwrap();
+        agentfs.fs.mkdir("/out/sub").await.unwrap();
+        agentfs
+            .fs
+            .write_file("/out/sub/report.md", b"done")
+            .await
+            .unwrap();
+
+        let host = tempfile::tempdir().unwrap();
+        let dest = host.path().join("artifacts");
+        let mut buf = Vec::new();
+        export_filesystem(&mut buf, path, "/out", &dest, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            format!(
+                "Exported 1 files (4 bytes), 1 directories and 0 symlinks to {}\n",
+                dest.display()
+            )
+        );
+        assert_eq!(std::fs::read(dest.join("sub/report.md")).unwrap(), b"done");
+    }
+
+    #[test]
+    fn parse_mv_operands() {
+        assert_eq!(parse_agent_path("a", "/x/y"), ("a", "/x/y"));
+        assert_eq!(parse_agent_path("a", "b:/x"), ("b", "/x"));
+        assert_eq!(parse_agent_path("a", "/tmp/b.db:/x"), ("/tmp/b.db", "/x"));
+        assert_eq!(parse_agent_path("a", ":/x"), ("a", ":/x"));
+    }
+
+    #[tokio::test]
+    pub async fn blocks_of_sparse_file() {
+        let (agentfs, path, _file) = agentfs().await;
+        let chunk_size = agentfs.fs.chunk_size() as u64;
+        agentfs.fs.write_file("/a.bin", b"head").await.unwrap();
+        agentfs
+            .fs
+            .pwrite("/a.bin", chunk_size * 2, b"tail")
+            .await
+            .unwrap();
+
+        let mut buf = Vec::new();
+        blocks_filesystem(&mut buf, path.clone(), "/a.bin")
+            .await
+            .unwrap();
+        let output = String::from_utf8(buf).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(
+            lines[2].split_whitespace().collect::<Vec<_>>(),
+            ["2", &(chunk_size * 2).to_string(), "4", "4", "no"]
+        );
+        assert_eq!(
+            lines[3],
+            format!(
+                "blocks: 2, holes: 1, data: 8 bytes, stored: 8 bytes, file size: {}",
+                chunk_size * 2 + 4
+            )
+        );
+
+        let mut buf = Vec::new();
+        let err = blocks_filesystem(&mut buf, path, "/missing")
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("/missing"));
+    }
+
+    #[tokio::test]
+    pub async fn rm_glob() {
+        let (agentfs, path, _file) = agentfs().await;
+        agentfs.fs.mkdir("/tmp").await.unwrap();
+        agentfs.fs.mkdir("/tmp/cache.log").await.unwrap();
+        agentfs.fs.write_file("/tmp/a.log", b"a").await.unwrap();
+        agentfs.fs.write_file("/tmp/b.txt", b"b").await.unwrap();
+
+        let mut buf = Vec::new();
+        rm_filesystem(&mut buf, path.clone(), "/tmp/*.log", false)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "Removed 1 paths matching /tmp/*.log\n"
+        );
+        assert!(agentfs.fs.stat("/tmp/a.log").await.unwrap().is_none());
+        assert!(agentfs.fs.stat("/tmp/b.txt").await.unwrap().is_some());
+        assert!(agentfs.fs.stat("/tmp/cache.log").await.unwrap().is_some());
+
+        let mut buf = Vec::new();
+        rm_filesystem(&mut buf, path, "/tmp/*.log", true)
+            .await
+            .unwrap();
+        assert!(agentfs.fs.stat("/tmp/cache.log").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    pub async fn mv_within_agent() {
+        let (agentfs, path, _file) = agentfs().await;
+        agentfs.fs.write_file("/a.md", b"hello").await.unwrap();
+        let mut buf = Vec::new();
+        mv_filesystem(&mut buf, path, "/a.md", "/b.md", false)
+            .await
+            .unwrap();
+        assert!(buf.is_empty());
+        assert!(agentfs.fs.stat("/a.md").await.unwrap().is_none());
+        assert_eq!(
+            agentfs.fs.read_file("/b.md").await.unwrap(),
+            Some(b"hello".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    pub async fn mv_across_agents() {
+        let (src, src_path, _src_file) = agentfs().await;
+        let (dst, dst_path, _dst_file) = agentfs().await;
+        src.fs.mkdir("/docs").await.unwrap();
+        src.fs.write_file("/docs/a.md", b"hello").await.unwrap();
+        src.fs.chmod("/docs/a.md", 0o600).await.unwrap();
+        src.fs.symlink("a.md", "/docs/link").await.unwrap();
+
+        let to = format!("{}:/archive/docs", dst_path);
+        let mut buf = Vec::new();
+        mv_filesystem(&mut buf, src_path.clone(), "/docs", &to, false)
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            format!(
+                "Moved 1 files (5 bytes), 1 directories and 1 symlinks from /docs to {}\n",
+                to
+            )
+        );
+        assert!(src.fs.stat("/docs").await.unwrap().is_none());
+        assert_eq!(
+            dst.fs.read_file("/archive/docs/a.md").await.unwrap(),
+            Some(b"hello".to_vec())
+        );
+        let stats = dst.fs.stat("/archive/docs/a.md").await.unwrap().unwrap();
+        assert_eq!(stats.permissions(), 0o600);
+        assert_eq!(
+            dst.fs.readlink("/archive/docs/link").await.unwrap(),
+            Some("a.md".to_string())
+        );
+
+        // Copying back with --keep leaves the source alone
+        let mut buf = Vec::new();
+        mv_filesystem(&mut buf, src_path, &to, "/restored", true)
+            .await
+            .unwrap();
+        assert!(dst.fs.stat("/archive/docs/a.md").await.unwrap().is_some());
+        assert!(src.fs.stat("/restored/a.md").await.unwrap().is_some());
+    }
+
+    #[test]
+    fn format_mode_symlink() {
+        assert_eq!(format_mode(0o120777), "lrwxrwxrwx");
+        assert_eq!(format_mode(0o040750), "drwxr-x---");
+    }
+
     #[tokio::test]
     pub async fn ls_empty() {
         let (_agentfs, path, _file) = agentfs().await;
         let mut buf = Vec::new();
-        ls_filesystem(&mut buf, path, "/").await.unwrap();
+        ls_filesystem(&mut buf, path, "/", &LsOptions::default())
+            .await
+            .unwrap();
         assert_eq!(buf, b"");
     }
 
@@ -302,7 +1349,9 @@ mod tests {
         let big = vec![100u8; 1024 * 1024];
         agentfs.fs.write_file("3.md", &big).await.unwrap();
         let mut buf = Vec::new();
-        ls_filesystem(&mut buf, path, "/").await.unwrap();
+        ls_filesystem(&mut buf, path, "/", &LsOptions::default())
+            .await
+            .unwrap();
         assert_eq!(
             buf,
             b"f 1.md
@@ -312,6 +1361,62 @@ f 3.md
         );
     }
 
+    #[tokio::test]
+    pub async fn ls_limit_and_offset() {
+        let (agentfs, path, _file) = agentfs().await;
+        agentfs.fs.mkdir("a").await.unwrap();
+        agentfs.fs.write_file("a/1.md", b"1").await.unwrap();
+        agentfs.fs.write_file("a/2.md", b"2").await.unwrap();
+        agentfs.fs.write_file("b.md", b"b").await.unwrap();
+
+        let mut buf = Vec::new();
+        let listing = LsOptions {
+            limit: Some(2),
+            offset: 1,
+            ..LsOptions::default()
+        };
+        ls_filesystem(&mut buf, path.clone(), "/", &listing)
+            .await
+            .unwrap();
+        assert_eq!(buf, b"f b.md\nf a/1.md\n");
+
+        // Skipped directories are still descended into
+        let mut buf = Vec::new();
+        let listing = LsOptions {
+            offset: 3,
+            ..LsOptions::default()
+        };
+        ls_filesystem(&mut buf, path, "/", &listing).await.unwrap();
+        assert_eq!(buf, b"f a/2.md\n");
+    }
+
+    #[tokio::test]
+    pub async fn ls_sorted() {
+        let (agentfs, path, _file) = agentfs().await;
+        agentfs.fs.write_file("small.md", b"1").await.unwrap();
+        agentfs.fs.write_file("big.md", b"1111").await.unwrap();
+        agentfs.fs.write_file("medium.md", b"11").await.unwrap();
+
+        let mut buf = Vec::new();
+        let listing = LsOptions {
+            sort: LsSort::Size,
+            ..LsOptions::default()
+        };
+        ls_filesystem(&mut buf, path.clone(), "/", &listing)
+            .await
+            .unwrap();
+        assert_eq!(buf, b"f big.md\nf medium.md\nf small.md\n");
+
+        let mut buf = Vec::new();
+        let listing = LsOptions {
+            limit: Some(2),
+            reverse: true,
+            ..LsOptions::default()
+        };
+        ls_filesystem(&mut buf, path, "/", &listing).await.unwrap();
+        assert_eq!(buf, b"f small.md\nf medium.md\n");
+    }
+
     #[tokio::test]
     pub async fn ls_dirs() {
         let (agentfs, path, _file) = agentfs().await;
@@ -325,7 +1430,9 @@ f 3.md
         let big = vec![100u8; 1024 * 1024];
         agentfs.fs.write_file("d/e/3.md", &big).await.unwrap();
         let mut buf = Vec::new();
-        ls_filesystem(&mut buf, path, "/").await.unwrap();
+        ls_filesystem(&mut buf, path, "/", &LsOptions::default())
+            .await
+            .unwrap();
         assert_eq!(
             buf,
             b"d a
@@ -339,4 +1446,37 @@ f d/e/3.md
 "
         );
     }
+
+    #[tokio::test]
+    pub async fn log_journal() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap().to_string();
+        let mut buf = Vec::new();
+        {
+            let agentfs = AgentFS::open(AgentFSOptions::with_path(path.clone()))
+                .await
+                .unwrap();
+            agentfs.fs.mkdir("/docs").await.unwrap();
+        }
+        let err = log_filesystem(&mut buf, path.clone(), None, false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not enabled"));
+
+        let agentfs = AgentFS::open(AgentFSOptions::with_path(path.clone()).with_journal())
+            .await
+            .unwrap();
+        agentfs.fs.write_file("/docs/a.md", b"hello").await.unwrap();
+        agentfs.fs.rename("/docs/a.md", "/docs/b.md").await.unwrap();
+
+        log_filesystem(&mut buf, path, None, false).await.unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        let lines: Vec<_> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(
+            "write   /docs/a.md  5 bytes  \
+             sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        ));
+        assert!(lines[1].ends_with("rename  /docs/a.md -> /docs/b.md"));
+    }
 }
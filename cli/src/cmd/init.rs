@@ -1,8 +1,11 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
 use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 use agentfs_sdk::{agentfs_dir, AgentFS, AgentFSOptions, OverlayFS};
 use anyhow::{Context, Result as AnyhowResult};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Utc};
 use turso::sync::{PartialBootstrapStrategy, PartialSyncOpts};
 
 use crate::parser::SyncCommandOptions;
@@ -32,6 +35,29 @@ pub async fn open_agentfs(
     Ok((Some(db), agent))
 }
 
+/// Shut down an agent opened with [`open_agentfs`]
+///
+/// Synced databases are pushed to the remote first when `push` is set, then
+/// checkpointed through the sync engine; local databases are checkpointed by
+/// [`AgentFS::close`].
+pub async fn close_agentfs(
+    db: Option<turso::sync::Database>,
+    agent: AgentFS,
+    push: bool,
+) -> AnyhowResult<()> {
+    match db {
+        Some(db) => {
+            if push {
+                db.push().await.context("Failed to push to remote")?;
+            }
+            db.checkpoint().await?;
+            drop(agent);
+            Ok(())
+        }
+        None => agent.close().await,
+    }
+}
+
 pub async fn create_agentfs(
     options: AgentFSOptions,
     sync_options: SyncCommandOptions,
@@ -83,20 +109,57 @@ pub async fn create_agentfs(
     }
 }
 
+/// Template for generated agent IDs, see [`generate_agent_id`]
+pub const DEFAULT_ID_FORMAT: &str = "agent-%Y%m%d-%H%M%S-{rand}";
+
+/// Generate an agent ID from a template
+///
+/// The template is a `strftime` format, expanded with the UTC time `now`, in
+/// which `{rand}` stands for `suffix`. Agents created within the same second
+/// only get distinct IDs if the template has `{rand}` or sub-second fields.
+pub fn generate_agent_id(template: &str, now: DateTime<Utc>, suffix: &str) -> AnyhowResult<String> {
+    let expanded = template.replace("{rand}", suffix);
+    let items: Vec<Item> = StrftimeItems::new(&expanded).collect();
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        anyhow::bail!("Invalid ID format '{}'", template);
+    }
+
+    let id = now.format_with_items(items.into_iter()).to_string();
+    if !AgentFSOptions::validate_agent_id(&id) {
+        anyhow::bail!(
+            "ID format '{}' generates invalid agent ID '{}'. Agent IDs must contain only alphanumeric characters, hyphens, and underscores.",
+            template,
+            id
+        );
+    }
+    Ok(id)
+}
+
+/// Six random hex digits for the `{rand}` placeholder of ID templates
+fn random_suffix() -> String {
+    // RandomState is seeded randomly, which is all the randomness needed here
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(std::process::id());
+    format!("{:06x}", hasher.finish() & 0xff_ffff)
+}
+
 pub async fn init_database(
     id: Option<String>,
+    id_format: Option<String>,
     sync_options: SyncCommandOptions,
     force: bool,
     base: Option<PathBuf>,
+    journal: bool,
 ) -> AnyhowResult<()> {
     // Generate ID if not provided
-    let id = id.unwrap_or_else(|| {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        format!("agent-{}", timestamp)
-    });
+    let id = match id {
+        Some(id) => id,
+        None => generate_agent_id(
+            id_format.as_deref().unwrap_or(DEFAULT_ID_FORMAT),
+            Utc::now(),
+            &random_suffix(),
+        )?,
+    };
 
     // Validate agent ID for safety
     if !AgentFSOptions::validate_agent_id(&id) {
@@ -144,7 +207,10 @@ pub async fn init_database(
 
     // Use the SDK to initialize the database - this ensures consistency
     // The SDK will create .agentfs directory and database file
-    let (synced_db, agent) = create_agentfs(open_options, sync_options).await?;
+    let (synced_db, mut agent) = create_agentfs(open_options, sync_options).await?;
+    if journal {
+        agent.fs.enable_journal().await?;
+    }
 
     // If base is provided, initialize the overlay schema using the SDK
     if let Some(base_path) = base {
@@ -155,7 +221,7 @@ pub async fn init_database(
             .to_string();
 
         // Use SDK's OverlayFS::init_schema to ensure schema consistency
-        OverlayFS::init_schema(&agent.get_connection(), &base_path_str)
+        OverlayFS::init_schema(agent.get_connection().as_ref(), &base_path_str)
             .await
             .context("Failed to initialize overlay schema")?;
 
@@ -177,3 +243,35 @@ pub async fn init_database(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_generate_agent_id() {
+        let now = Utc.with_ymd_and_hms(2026, 3, 14, 15, 9, 26).unwrap();
+        assert_eq!(
+            generate_agent_id(DEFAULT_ID_FORMAT, now, "0a1b2c").unwrap(),
+            "agent-20260314-150926-0a1b2c"
+        );
+        assert_eq!(
+            generate_agent_id("build_%s", now, "ffffff").unwrap(),
+            format!("build_{}", now.timestamp())
+        );
+
+        // Every ID that comes out is usable as a database name
+        let err = generate_agent_id("agent-%Y/%m", now, "0").unwrap_err();
+        assert!(err.to_string().contains("invalid agent ID"));
+        assert!(generate_agent_id("agent-%Q", now, "0").is_err());
+    }
+
+    #[test]
+    fn test_random_suffix() {
+        let suffix = random_suffix();
+        assert_eq!(suffix.len(), 6);
+        assert!(suffix.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(random_suffix(), random_suffix());
+    }
+}
@@ -1,9 +1,14 @@
+pub mod backup;
+pub mod check;
 pub mod completions;
 pub mod fs;
 pub mod init;
 pub mod mcp_server;
+pub mod record;
+pub mod shell;
 pub mod sync;
 pub mod timeline;
+pub mod tools;
 
 #[cfg(target_os = "linux")]
 pub mod mount;
@@ -13,9 +18,16 @@ pub mod mount;
 
 mod run;
 
+// Output capture for `run` (Unix sandboxes only)
+#[cfg(all(unix, feature = "sandbox"))]
+pub(crate) mod capture;
+
 // Standalone NFS server command (Unix only)
 #[cfg(unix)]
 pub mod nfs;
 
 pub use mount::{mount, MountArgs};
-pub use run::handle_run_command;
+pub use run::{
+    handle_run_command, CaptureOptions, DenyOptions, FakeTimeOptions, FaultOptions, PtraceOptions,
+    StraceOptions,
+};
@@ -0,0 +1,152 @@
+//! Recording of `agentfs run` invocations in an agent's tool-call history.
+
+use std::path::Path;
+
+use agentfs_sdk::{AgentFS, AgentFSOptions};
+use anyhow::{Context, Result};
+use serde_json::json;
+
+use super::init::{close_agentfs, open_agentfs};
+use super::CaptureOptions;
+
+/// Name of the tool calls created for `agentfs run`
+pub const RUN_TOOL_NAME: &str = "agentfs run";
+
+/// A pending tool call for one `agentfs run` invocation
+///
+/// Created with [`RunRecorder::start`] before the command is launched and
+/// completed with [`RunRecorder::finish`] once it has exited.
+pub struct RunRecorder {
+    db: Option<turso::sync::Database>,
+    agent: AgentFS,
+    id: i64,
+    capture: CaptureOptions,
+}
+
+impl RunRecorder {
+    /// Open the agent and start a tool call with the command as its input
+    pub async fn start(
+        id_or_path: &str,
+        command: &Path,
+        args: &[String],
+        capture: &CaptureOptions,
+    ) -> Result<Self> {
+        let options = AgentFSOptions::resolve(id_or_path)?;
+        let (db, agent) = open_agentfs(options).await?;
+
+        let cwd = std::env::current_dir().context("Failed to get current directory")?;
+        let parameters = json!({
+            "command": command.to_string_lossy(),
+            "args": args,
+            "cwd": cwd.to_string_lossy(),
+        });
+        let id = agent
+            .tools
+            .start(RUN_TOOL_NAME, Some(parameters))
+            .await
+            .context("Failed to record run")?;
+
+        Ok(Self {
+            db,
+            agent,
+            id,
+            capture: capture.clone(),
+        })
+    }
+
+    /// Complete the tool call with the command's exit code
+    ///
+    /// A zero exit code records a success whose result holds the exit code and
+    /// where the output was captured; anything else records an error.
+    pub async fn finish(self, exit_code: i32) -> Result<()> {
+        let tools = &self.agent.tools;
+        if exit_code == 0 {
+            let result = json!({
+                "exit_code": exit_code,
+                "stdout": self.capture.stdout,
+                "stderr": self.capture.stderr,
+            });
+            tools.success(self.id, Some(result)).await?;
+        } else {
+            tools.error(self.id, &self.error_message(exit_code)).await?;
+        }
+        close_agentfs(self.db, self.agent, false).await
+    }
+
+    /// Like [`RunRecorder::finish`], for callers that are not async
+    ///
+    /// Runs on a separate thread with its own runtime, so it can also be used
+    /// from code that is itself running on a runtime thread.
+    pub fn finish_blocking(self, exit_code: i32) -> Result<()> {
+        std::thread::spawn(move || crate::get_runtime().block_on(self.finish(exit_code)))
+            .join()
+            .map_err(|_| anyhow::anyhow!("Recording thread panicked"))?
+    }
+
+    fn error_message(&self, exit_code: i32) -> String {
+        let mut message = format!("exited with code {}", exit_code);
+        if let Some(path) = &self.capture.stdout {
+            message.push_str(&format!("; stdout captured to {}", path));
+        }
+        if let Some(path) = &self.capture.stderr {
+            message.push_str(&format!("; stderr captured to {}", path));
+        }
+        message
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentfs_sdk::ToolCallStatus;
+    use tempfile::NamedTempFile;
+
+    #[tokio::test]
+    async fn test_record_run() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        drop(
+            AgentFS::open(AgentFSOptions::with_path(path))
+                .await
+                .unwrap(),
+        );
+
+        let capture = CaptureOptions {
+            stdout: Some("/logs/out.txt".to_string()),
+            stderr: None,
+        };
+        let args = vec!["-c".to_string(), "true".to_string()];
+        let recorder = RunRecorder::start(path, Path::new("/bin/sh"), &args, &capture)
+            .await
+            .unwrap();
+        recorder.finish(0).await.unwrap();
+        let recorder = RunRecorder::start(path, Path::new("/bin/false"), &[], &capture)
+            .await
+            .unwrap();
+        recorder.finish(1).await.unwrap();
+
+        let agent = AgentFS::open(AgentFSOptions::with_path(path))
+            .await
+            .unwrap();
+        let calls = agent.tools.recent(None).await.unwrap();
+        assert_eq!(calls.len(), 2);
+        let ok = calls
+            .iter()
+            .find(|c| c.status == ToolCallStatus::Success)
+            .unwrap();
+        assert_eq!(ok.name, RUN_TOOL_NAME);
+        assert_eq!(
+            ok.parameters.as_ref().unwrap()["args"],
+            json!(["-c", "true"])
+        );
+        assert_eq!(ok.result.as_ref().unwrap()["stdout"], "/logs/out.txt");
+        let failed = calls
+            .iter()
+            .find(|c| c.status == ToolCallStatus::Error)
+            .unwrap();
+        assert_eq!(
+            failed.error.as_deref(),
+            Some("exited with code 1; stdout captured to /logs/out.txt")
+        );
+    }
+}
@@ -4,8 +4,10 @@
 //! - Linux: FUSE + namespace sandbox (or experimental ptrace)
 //! - Darwin: NFS + sandbox-exec
 
+use super::record::RunRecorder;
 use anyhow::Result;
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
 
 #[cfg_attr(all(target_os = "linux", feature = "sandbox"), path = "run_linux.rs")]
 #[cfg_attr(all(target_os = "macos", feature = "sandbox"), path = "run_darwin.rs")]
@@ -16,21 +18,123 @@ use std::path::PathBuf;
 #[cfg_attr(not(feature = "sandbox"), path = "run_not_supported.rs")]
 mod sys;
 
+/// Strace-like output options for the experimental sandbox.
+#[derive(Debug, Clone, Default)]
+pub struct StraceOptions {
+    /// Log intercepted system calls to stderr
+    pub enabled: bool,
+    /// Print aggregated per-syscall statistics at exit
+    pub summary: bool,
+    /// Only log these system calls (all if empty)
+    pub filter: Vec<String>,
+    /// Never log these system calls
+    pub exclude: Vec<String>,
+}
+
+/// Deterministic clock served to the sandboxed process.
+#[derive(Debug, Clone, Copy)]
+pub struct FakeTimeOptions {
+    /// Wall-clock time reported by the first time syscall
+    pub start: SystemTime,
+    /// Amount the clock advances on every time syscall
+    pub step: Duration,
+}
+
+/// Syscall delays and failures injected into the sandboxed process.
+#[derive(Debug, Clone, Default)]
+pub struct FaultOptions {
+    /// `SYSCALL=DURATION` rules adding latency
+    pub delays: Vec<String>,
+    /// `SYSCALL=ERRNO[:PROBABILITY]` rules failing syscalls
+    pub failures: Vec<String>,
+    /// Seed for the failure draws (random if unset)
+    pub seed: Option<u64>,
+}
+
+impl FaultOptions {
+    /// Check if any delay or failure was requested
+    pub fn is_empty(&self) -> bool {
+        self.delays.is_empty() && self.failures.is_empty()
+    }
+}
+
+/// Paths hidden from the sandboxed process.
+#[derive(Debug, Clone, Default)]
+pub struct DenyOptions {
+    /// Absolute paths hidden along with everything below them
+    pub paths: Vec<PathBuf>,
+    /// Glob patterns of hidden paths
+    pub globs: Vec<String>,
+}
+
+impl DenyOptions {
+    /// Check if any path is hidden
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty() && self.globs.is_empty()
+    }
+}
+
+/// Options only honored by the experimental ptrace-based sandbox.
+#[derive(Debug, Clone, Default)]
+pub struct PtraceOptions {
+    /// Strace-like syscall logging
+    pub strace: StraceOptions,
+    /// Print each process's FD table when it exits
+    pub dump_fds_on_exit: bool,
+    /// Print the mount table before the command starts
+    pub print_mounts: bool,
+    /// Mount a minimal /dev and /proc
+    pub std_devices: bool,
+    /// Fake clock, if time syscalls should not reach the host
+    pub fake_time: Option<FakeTimeOptions>,
+    /// Fault injection for chaos testing
+    pub faults: FaultOptions,
+    /// Paths the sandboxed process cannot see
+    pub deny: DenyOptions,
+    /// Working directory to start the command in, as seen in the sandbox
+    pub cwd: Option<PathBuf>,
+}
+
+/// Files in the agent filesystem that the command's output is copied to.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureOptions {
+    /// Destination of the command's stdout
+    pub stdout: Option<String>,
+    /// Destination of the command's stderr
+    pub stderr: Option<String>,
+}
+
+impl CaptureOptions {
+    /// Check if any stream is captured
+    pub fn is_empty(&self) -> bool {
+        self.stdout.is_none() && self.stderr.is_none()
+    }
+}
+
 /// Handle the `run` command, dispatching to the platform-specific implementation.
+#[allow(clippy::too_many_arguments)]
 pub async fn handle_run_command(
     allow: Vec<PathBuf>,
     no_default_allows: bool,
     experimental_sandbox: bool,
-    strace: bool,
+    ptrace: PtraceOptions,
+    capture: CaptureOptions,
+    record: Option<String>,
     session: Option<String>,
     command: PathBuf,
     args: Vec<String>,
 ) -> Result<()> {
+    let recorder = match record {
+        Some(id_or_path) => Some(RunRecorder::start(&id_or_path, &command, &args, &capture).await?),
+        None => None,
+    };
     sys::run(
         allow,
         no_default_allows,
         experimental_sandbox,
-        strace,
+        ptrace,
+        capture,
+        recorder,
         session,
         command,
         args,
@@ -17,6 +17,7 @@ use std::process::Command;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+use crate::cmd::{capture::Capture, record::RunRecorder};
 use crate::nfs::AgentNFS;
 
 #[cfg(target_os = "macos")]
@@ -25,12 +26,25 @@ use crate::sandbox::darwin::{generate_sandbox_profile, SandboxConfig};
 /// Default NFS port to try (use a high port to avoid needing root)
 const DEFAULT_NFS_PORT: u32 = 11111;
 
+/// Exit code of a finished command, 128 + the signal number if it was killed
+fn exit_code(status: std::process::ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+
+    match status.signal() {
+        Some(signal) => 128 + signal,
+        None => status.code().unwrap_or(1),
+    }
+}
+
 /// Run the command in a Darwin sandbox.
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     allow: Vec<PathBuf>,
     no_default_allows: bool,
     _experimental_sandbox: bool,
-    _strace: bool,
+    _ptrace: super::PtraceOptions,
+    capture: super::CaptureOptions,
+    recorder: Option<RunRecorder>,
     session_id: Option<String>,
     command: PathBuf,
     args: Vec<String>,
@@ -53,6 +67,9 @@ pub async fn run(
     // Create overlay filesystem with CWD as base
     let base_str = cwd.to_string_lossy().to_string();
     let hostfs = HostFS::new(&base_str).context("Failed to create HostFS")?;
+    // Captured output goes to the delta layer, which shares its connection
+    // with the overlay
+    let capture_fs = agentfs.fs.clone();
     let overlay = OverlayFS::new(Arc::new(hostfs), agentfs.fs);
 
     // Initialize the overlay (copies directory structure)
@@ -94,7 +111,8 @@ pub async fn run(
     print_welcome_banner(&session);
 
     // Run the command
-    let exit_code = run_command_in_mount(&session, command, args)?;
+    let mut capture = Capture::start(&capture)?;
+    let exit_code = run_command_in_mount(&session, command, args, &mut capture)?;
 
     // Unmount
     unmount(&session.mountpoint)?;
@@ -102,6 +120,16 @@ pub async fn run(
     // Stop the server
     server_handle.abort();
 
+    // Save captured output now that nothing else is using the delta layer
+    if let Err(e) = capture.save(&capture_fs).await {
+        eprintln!("Warning: {:#}", e);
+    }
+    if let Some(recorder) = recorder {
+        if let Err(e) = recorder.finish(exit_code).await {
+            eprintln!("Warning: Failed to record run: {:#}", e);
+        }
+    }
+
     // Clean up mountpoint directory (but keep the delta database)
     if let Err(e) = std::fs::remove_dir(&session.mountpoint) {
         eprintln!(
@@ -302,7 +330,12 @@ fn mount_nfs(port: u32, mountpoint: &Path) -> Result<()> {
 /// The mountpoint overlays CWD, and additional paths in HOME are made writable
 /// through the allow_paths configuration.
 #[cfg(target_os = "macos")]
-fn run_command_in_mount(session: &RunSession, command: PathBuf, args: Vec<String>) -> Result<i32> {
+fn run_command_in_mount(
+    session: &RunSession,
+    command: PathBuf,
+    args: Vec<String>,
+    capture: &mut Capture,
+) -> Result<i32> {
     // Generate the Sandbox profile
     let config = SandboxConfig {
         mountpoint: session.mountpoint.clone(),
@@ -327,11 +360,18 @@ fn run_command_in_mount(session: &RunSession, command: PathBuf, args: Vec<String
         // Zsh: use custom ZDOTDIR to override prompt
         .env("ZDOTDIR", session.run_dir.join("zsh"));
 
+    if let Some(stdout) = capture.take_stdout() {
+        cmd.stdout(stdout);
+    }
+    if let Some(stderr) = capture.take_stderr() {
+        cmd.stderr(stderr);
+    }
+
     let status = cmd
         .status()
         .with_context(|| format!("Failed to execute command: {}", command.display()))?;
 
-    Ok(status.code().unwrap_or(1))
+    Ok(exit_code(status))
 }
 
 /// Run a command with the working directory set to the mounted filesystem (Linux).
@@ -339,7 +379,12 @@ fn run_command_in_mount(session: &RunSession, command: PathBuf, args: Vec<String
 /// On Linux, the command runs without additional sandboxing (NFS provides
 /// copy-on-write for the working directory).
 #[cfg(target_os = "linux")]
-fn run_command_in_mount(session: &RunSession, command: PathBuf, args: Vec<String>) -> Result<i32> {
+fn run_command_in_mount(
+    session: &RunSession,
+    command: PathBuf,
+    args: Vec<String>,
+    capture: &mut Capture,
+) -> Result<i32> {
     let mut cmd = Command::new(&command);
     cmd.args(&args)
         .current_dir(&session.mountpoint)
@@ -349,11 +394,18 @@ fn run_command_in_mount(session: &RunSession, command: PathBuf, args: Vec<String
         // Zsh: use custom ZDOTDIR to override prompt
         .env("ZDOTDIR", session.run_dir.join("zsh"));
 
+    if let Some(stdout) = capture.take_stdout() {
+        cmd.stdout(stdout);
+    }
+    if let Some(stderr) = capture.take_stderr() {
+        cmd.stderr(stderr);
+    }
+
     let status = cmd
         .status()
         .with_context(|| format!("Failed to execute command: {}", command.display()))?;
 
-    Ok(status.code().unwrap_or(1))
+    Ok(exit_code(status))
 }
 
 /// Unmount the NFS filesystem (macOS version).
@@ -3,15 +3,19 @@
 //! Dispatches to either the FUSE+namespace sandbox (default) or the experimental
 //! ptrace-based sandbox based on command-line flags.
 
+use crate::cmd::record::RunRecorder;
 use anyhow::Result;
 use std::path::PathBuf;
 
 /// Run the command in a Linux sandbox.
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     allow: Vec<PathBuf>,
     no_default_allows: bool,
     experimental_sandbox: bool,
-    strace: bool,
+    ptrace: super::PtraceOptions,
+    capture: super::CaptureOptions,
+    recorder: Option<RunRecorder>,
     session: Option<String>,
     command: PathBuf,
     args: Vec<String>,
@@ -23,12 +27,54 @@ pub async fn run(
         if session.is_some() {
             eprintln!("Warning: --session is not supported with --experimental-sandbox, ignoring");
         }
-        crate::sandbox::linux_ptrace::run_cmd(strace, command, args).await;
+        crate::sandbox::linux_ptrace::run_cmd(ptrace, capture, recorder, command, args).await?;
     } else {
-        if strace {
-            eprintln!("Warning: --strace is only supported with --experimental-sandbox, ignoring");
+        if ptrace.strace.enabled || ptrace.strace.summary {
+            eprintln!("Warning: --strace and --strace-summary are only supported with --experimental-sandbox, ignoring");
         }
-        crate::sandbox::linux::run_cmd(allow, no_default_allows, session, command, args).await?;
+        if ptrace.dump_fds_on_exit {
+            eprintln!(
+                "Warning: --dump-fds-on-exit is only supported with --experimental-sandbox, ignoring"
+            );
+        }
+        if ptrace.print_mounts {
+            eprintln!(
+                "Warning: --print-mounts is only supported with --experimental-sandbox, ignoring"
+            );
+        }
+        if ptrace.std_devices {
+            eprintln!(
+                "Warning: --with-std-devices is only supported with --experimental-sandbox, ignoring"
+            );
+        }
+        if ptrace.fake_time.is_some() {
+            eprintln!(
+                "Warning: --fake-time is only supported with --experimental-sandbox, ignoring"
+            );
+        }
+        if !ptrace.faults.is_empty() {
+            eprintln!(
+                "Warning: --delay and --fail are only supported with --experimental-sandbox, ignoring"
+            );
+        }
+        if !ptrace.deny.is_empty() {
+            eprintln!(
+                "Warning: --deny-path and --deny-glob are only supported with --experimental-sandbox, ignoring"
+            );
+        }
+        if ptrace.cwd.is_some() {
+            eprintln!("Warning: --cwd is only supported with --experimental-sandbox, ignoring");
+        }
+        crate::sandbox::linux::run_cmd(
+            allow,
+            no_default_allows,
+            capture,
+            recorder,
+            session,
+            command,
+            args,
+        )
+        .await?;
     }
     Ok(())
 }
@@ -10,7 +10,9 @@ pub async fn run(
     _allow: Vec<PathBuf>,
     _no_default_allows: bool,
     _experimental_sandbox: bool,
-    _strace: bool,
+    _ptrace: super::PtraceOptions,
+    _capture: super::CaptureOptions,
+    _recorder: Option<crate::cmd::record::RunRecorder>,
     _session: Option<String>,
     _command: PathBuf,
     _args: Vec<String>,
@@ -0,0 +1,266 @@
+use agentfs_sdk::{AgentFS, AgentFSOptions};
+use anyhow::{Context, Result as AnyhowResult};
+use std::io::{BufRead, Write};
+
+use crate::cmd::init::{close_agentfs, open_agentfs};
+use crate::cmd::timeline::format_table;
+
+const HELP: &str = "\
+Commands:
+  ls [PATH]              List a directory (directories end with /)
+  cd [PATH]              Change the working directory (default: /)
+  pwd                    Print the working directory
+  cat PATH               Print a file
+  put LOCAL [PATH]       Upload a host file (default: its file name in the working directory)
+  rm PATH                Remove a file or empty directory
+  kv get KEY             Print a value from the key-value store
+  kv set KEY VALUE       Store a value (parsed as JSON, or as a string if that fails)
+  tools list [LIMIT]     Show recent tool calls
+  help                   Show this help
+  exit                   Leave the shell
+";
+
+/// Open an agent and run an interactive shell over stdin/stdout
+///
+/// The agent is checkpointed and closed when the shell exits; with `push`, a
+/// synced agent is pushed to its remote first.
+pub async fn handle_shell_command(id_or_path: String, push: bool) -> AnyhowResult<()> {
+    let options = AgentFSOptions::resolve(&id_or_path)?;
+    let (db, agent) = open_agentfs(options).await?;
+    if push && db.is_none() {
+        anyhow::bail!("db is not connected to the remote");
+    }
+    eprintln!("Using agent: {} (type `help` for commands)", id_or_path);
+
+    let stdin = std::io::stdin();
+    Shell::new(&agent)
+        .run(&mut stdin.lock(), &mut std::io::stdout(), true)
+        .await?;
+    close_agentfs(db, agent, push).await
+}
+
+/// An interactive session over an open agent, with a virtual working directory
+pub struct Shell<'a> {
+    agent: &'a AgentFS,
+    cwd: String,
+}
+
+impl<'a> Shell<'a> {
+    pub fn new(agent: &'a AgentFS) -> Self {
+        Self {
+            agent,
+            cwd: "/".to_string(),
+        }
+    }
+
+    /// Read and execute commands until `exit` or end of input
+    ///
+    /// Errors from individual commands are reported and the session continues.
+    pub async fn run(
+        &mut self,
+        input: &mut impl BufRead,
+        stdout: &mut impl Write,
+        prompt: bool,
+    ) -> AnyhowResult<()> {
+        let mut line = String::new();
+        loop {
+            if prompt {
+                write!(stdout, "agentfs:{}> ", self.cwd)?;
+                stdout.flush()?;
+            }
+
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                if prompt {
+                    writeln!(stdout)?;
+                }
+                return Ok(());
+            }
+
+            match self.execute(line.trim(), stdout).await {
+                Ok(true) => {}
+                Ok(false) => return Ok(()),
+                Err(e) => writeln!(stdout, "Error: {}", e)?,
+            }
+        }
+    }
+
+    /// Execute a single command line
+    ///
+    /// Returns `false` if the session should end.
+    pub async fn execute(&mut self, line: &str, stdout: &mut impl Write) -> AnyhowResult<bool> {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        let fs = &self.agent.fs;
+
+        match args.as_slice() {
+            [] => {}
+            ["exit"] | ["quit"] => return Ok(false),
+            ["help"] => write!(stdout, "{}", HELP)?,
+            ["pwd"] => writeln!(stdout, "{}", self.cwd)?,
+            ["ls"] | ["ls", _] => {
+                let path = self.resolve(args.get(1).copied().unwrap_or("."));
+                let entries = fs
+                    .readdir_plus(&path)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("No such directory: {}", path))?;
+                for entry in entries {
+                    let suffix = if entry.stats.is_directory() { "/" } else { "" };
+                    writeln!(stdout, "{}{}", entry.name, suffix)?;
+                }
+            }
+            ["cd"] | ["cd", _] => {
+                let path = self.resolve(args.get(1).copied().unwrap_or("/"));
+                match fs.stat(&path).await? {
+                    Some(stats) if stats.is_directory() => self.cwd = path,
+                    Some(_) => anyhow::bail!("Not a directory: {}", path),
+                    None => anyhow::bail!("No such directory: {}", path),
+                }
+            }
+            ["cat", path] => {
+                let path = self.resolve(path);
+                let data = fs
+                    .read_file(&path)
+                    .await?
+                    .ok_or_else(|| anyhow::anyhow!("File not found: {}", path))?;
+                stdout.write_all(&data)?;
+            }
+            ["put", local] | ["put", local, _] => {
+                let dest = match args.get(2) {
+                    Some(dest) => self.resolve(dest),
+                    None => {
+                        let name = std::path::Path::new(local)
+                            .file_name()
+                            .and_then(|name| name.to_str())
+                            .ok_or_else(|| anyhow::anyhow!("Invalid file name: {}", local))?;
+                        self.resolve(name)
+                    }
+                };
+                let file = tokio::fs::File::open(local)
+                    .await
+                    .with_context(|| format!("Failed to open {}", local))?;
+                let written = fs.write_file_from(&dest, file).await?;
+                writeln!(stdout, "Wrote {} bytes to {}", written, dest)?;
+            }
+            ["rm", path] => fs.remove(&self.resolve(path)).await?,
+            ["kv", "get", key] => match self.agent.kv.get::<serde_json::Value>(key).await? {
+                Some(value) => writeln!(stdout, "{}", value)?,
+                None => anyhow::bail!("Key not found: {}", key),
+            },
+            ["kv", "set", key, ..] => {
+                let raw = line
+                    .splitn(4, char::is_whitespace)
+                    .nth(3)
+                    .map(str::trim)
+                    .filter(|raw| !raw.is_empty())
+                    .ok_or_else(|| anyhow::anyhow!("Usage: kv set KEY VALUE"))?;
+                let value = serde_json::from_str(raw)
+                    .unwrap_or_else(|_| serde_json::Value::String(raw.to_string()));
+                self.agent.kv.set(key, &value).await?;
+            }
+            ["tools", "list"] | ["tools", "list", _] => {
+                let limit = match args.get(2) {
+                    Some(limit) => limit
+                        .parse()
+                        .with_context(|| format!("Invalid limit: {}", limit))?,
+                    None => 20,
+                };
+                let calls = self.agent.tools.recent(Some(limit)).await?;
+                format_table(stdout, &calls)?;
+            }
+            [command, ..] => {
+                anyhow::bail!("Unknown command or arguments: {} (try `help`)", command)
+            }
+        }
+        Ok(true)
+    }
+
+    /// Resolve a path against the working directory
+    ///
+    /// `.` and `..` are applied lexically and clamped at the root.
+    fn resolve(&self, path: &str) -> String {
+        let mut components: Vec<&str> = if path.starts_with('/') {
+            Vec::new()
+        } else {
+            self.cwd.split('/').filter(|c| !c.is_empty()).collect()
+        };
+        for component in path.split('/') {
+            match component {
+                "" | "." => {}
+                ".." => {
+                    components.pop();
+                }
+                component => components.push(component),
+            }
+        }
+        format!("/{}", components.join("/"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    async fn run(agent: &AgentFS, script: &str) -> String {
+        let mut shell = Shell::new(agent);
+        let mut out = Vec::new();
+        shell
+            .run(&mut script.as_bytes(), &mut out, false)
+            .await
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_shell_navigation() {
+        let file = NamedTempFile::new().unwrap();
+        let agent = AgentFS::open(AgentFSOptions::with_path(file.path().to_str().unwrap()))
+            .await
+            .unwrap();
+        agent.fs.mkdir("/docs").await.unwrap();
+        agent
+            .fs
+            .write_file("/docs/readme.md", b"hello\n")
+            .await
+            .unwrap();
+
+        let out = run(
+            &agent,
+            "ls\ncd docs\npwd\ncat readme.md\ncd ..\npwd\ncd nope\n",
+        )
+        .await;
+        assert_eq!(
+            out,
+            "docs/\n/docs\nhello\n/\nError: No such directory: /nope\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shell_put_rm_and_kv() {
+        let file = NamedTempFile::new().unwrap();
+        let agent = AgentFS::open(AgentFSOptions::with_path(file.path().to_str().unwrap()))
+            .await
+            .unwrap();
+        let local = NamedTempFile::new().unwrap();
+        std::fs::write(local.path(), b"uploaded").unwrap();
+
+        let script = format!(
+            "put {} /up.txt\ncat /up.txt\nrm /up.txt\nkv set greeting hello world\nkv get greeting\nexit\nls\n",
+            local.path().display()
+        );
+        let out = run(&agent, &script).await;
+        assert_eq!(out, "Wrote 8 bytes to /up.txt\nuploaded\"hello world\"\n");
+        assert!(agent.fs.stat("/up.txt").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let mut shell = Shell::new(&agent);
+        shell.cwd = "/a/b".to_string();
+        assert_eq!(shell.resolve("c"), "/a/b/c");
+        assert_eq!(shell.resolve("../c/./d"), "/a/c/d");
+        assert_eq!(shell.resolve("../../../.."), "/");
+        assert_eq!(shell.resolve("/x/"), "/x");
+    }
+}
@@ -1,18 +1,214 @@
-use agentfs_sdk::AgentFSOptions;
+use std::future::Future;
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use agentfs_sdk::{AgentFS, AgentFSOptions, ChangeKey, Conflict, ConflictPolicy, ValueFormat};
 use anyhow::anyhow;
+use tokio_util::sync::CancellationToken;
+use turso::Value;
 
 use crate::cmd::init::open_agentfs;
 
-pub async fn handle_pull_command(id_or_path: String) -> anyhow::Result<()> {
-    let options = AgentFSOptions::resolve(&id_or_path)?;
+/// How often a running pull or push reports a heartbeat
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Progress of a pull or push
+///
+/// turso doesn't expose progress from inside a sync, so this is coarse: a start
+/// event, periodic heartbeats while the transfer runs, and the number of bytes
+/// moved once it is done.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncProgress {
+    Started,
+    Heartbeat {
+        elapsed: Duration,
+    },
+    Finished {
+        elapsed: Duration,
+        /// Bytes transferred over the network, if the sync stats report them
+        bytes: Option<u64>,
+    },
+}
+
+/// Pull remote changes, reporting progress to `cb`
+///
+/// Cancelling `token` aborts the pull and returns an error.
+pub async fn pull_with_progress(
+    db: &turso::sync::Database,
+    cb: impl Fn(SyncProgress),
+    token: &CancellationToken,
+) -> anyhow::Result<()> {
+    let start = Instant::now();
+    let before = network_bytes(db, "network_received_bytes").await;
+    track_progress(db.pull(), &cb, token, HEARTBEAT_INTERVAL).await?;
+    let after = network_bytes(db, "network_received_bytes").await;
+    cb(SyncProgress::Finished {
+        elapsed: start.elapsed(),
+        bytes: before
+            .zip(after)
+            .map(|(before, after)| after.saturating_sub(before)),
+    });
+    Ok(())
+}
+
+/// Push local changes, reporting progress to `cb`
+///
+/// Cancelling `token` aborts the push and returns an error.
+pub async fn push_with_progress(
+    db: &turso::sync::Database,
+    cb: impl Fn(SyncProgress),
+    token: &CancellationToken,
+) -> anyhow::Result<()> {
+    let start = Instant::now();
+    let before = network_bytes(db, "network_sent_bytes").await;
+    track_progress(db.push(), &cb, token, HEARTBEAT_INTERVAL).await?;
+    let after = network_bytes(db, "network_sent_bytes").await;
+    cb(SyncProgress::Finished {
+        elapsed: start.elapsed(),
+        bytes: before
+            .zip(after)
+            .map(|(before, after)| after.saturating_sub(before)),
+    });
+    Ok(())
+}
+
+/// Drive `op` to completion, emitting `Started` and a heartbeat every `interval`
+///
+/// The final `Finished` event is left to the caller, which knows how many
+/// bytes were moved.
+async fn track_progress<T, E>(
+    op: impl Future<Output = Result<T, E>>,
+    cb: &impl Fn(SyncProgress),
+    token: &CancellationToken,
+    interval: Duration,
+) -> anyhow::Result<T>
+where
+    E: Into<anyhow::Error>,
+{
+    let start = Instant::now();
+    cb(SyncProgress::Started);
+
+    let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+    tokio::pin!(op);
+    loop {
+        tokio::select! {
+            result = &mut op => return result.map_err(Into::into),
+            _ = token.cancelled() => anyhow::bail!("Sync cancelled"),
+            _ = ticker.tick() => cb(SyncProgress::Heartbeat { elapsed: start.elapsed() }),
+        }
+    }
+}
+
+/// Read a network counter from the sync stats
+async fn network_bytes(db: &turso::sync::Database, field: &str) -> Option<u64> {
+    let stats = serde_json::to_value(db.stats().await.ok()?).ok()?;
+    stats.get(field)?.as_u64()
+}
+
+/// Render sync progress as a single, updating line on stderr
+fn render_progress(verb: &'static str) -> impl Fn(SyncProgress) {
+    move |progress| {
+        let mut stderr = std::io::stderr();
+        let _ = match progress {
+            SyncProgress::Started => write!(stderr, "{}...", verb),
+            SyncProgress::Heartbeat { elapsed } => {
+                write!(stderr, "\r{}... {}s", verb, elapsed.as_secs())
+            }
+            SyncProgress::Finished { bytes, .. } => match bytes {
+                Some(bytes) => writeln!(stderr, "\r{}... done ({} bytes)", verb, bytes),
+                None => writeln!(stderr, "\r{}... done", verb),
+            },
+        };
+        let _ = stderr.flush();
+    }
+}
+
+/// A token that is cancelled when the user presses Ctrl+C
+fn cancel_on_ctrl_c() -> CancellationToken {
+    let token = CancellationToken::new();
+    let child = token.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            child.cancel();
+        }
+    });
+    token
+}
+
+fn parse_conflict_policy(value: &str) -> anyhow::Result<ConflictPolicy> {
+    match value {
+        "last-writer-wins" => Ok(ConflictPolicy::LastWriterWins),
+        "prefer-local" => Ok(ConflictPolicy::PreferLocal),
+        "prefer-remote" => Ok(ConflictPolicy::PreferRemote),
+        "manual" => Ok(ConflictPolicy::Manual),
+        _ => Err(anyhow!("Unknown conflict policy: {}", value)),
+    }
+}
+
+pub async fn handle_pull_command(id_or_path: String, conflicts: String) -> anyhow::Result<()> {
+    let policy = parse_conflict_policy(&conflicts)?;
+    let options = AgentFSOptions::resolve(&id_or_path)?.with_sync_conflict_policy(policy);
     eprintln!("Using agent: {}", id_or_path);
 
-    let (db, _) = open_agentfs(options).await?;
+    // Local changes are everything written since the previous pull started
+    let marker = format!("{}-last-pull", options.db_path()?);
+    let since = std::fs::read_to_string(&marker)
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(0);
+    let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+
+    let (db, agent) = open_agentfs(options).await?;
     let Some(db) = db else {
         return Err(anyhow!("db is not connected to the remote"));
     };
-    db.pull().await?;
+    let local = agent.changes_since(since).await?;
+    pull_with_progress(&db, render_progress("Pulling"), &cancel_on_ctrl_c()).await?;
+    let conflicts = agent.reconcile(policy, &local).await?;
+    std::fs::write(&marker, started.to_string())?;
+
     eprintln!("Remote data pulled to local db successfully");
+    report_conflicts(&agent, &conflicts).await
+}
+
+/// Print conflicts left for the user and keep local file versions as `<path>.local`
+async fn report_conflicts(agent: &AgentFS, conflicts: &[Conflict]) -> anyhow::Result<()> {
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    eprintln!(
+        "{} conflict(s) left unresolved, the pulled values were kept:",
+        conflicts.len()
+    );
+    for conflict in conflicts {
+        match &conflict.key {
+            ChangeKey::Kv(key) => {
+                let local = &conflict.local;
+                let value = local.value.as_deref().unwrap_or_default();
+                match local.format {
+                    Some(ValueFormat::Json) | None => eprintln!(
+                        "  kv {}: local value was {}",
+                        key,
+                        String::from_utf8_lossy(value)
+                    ),
+                    Some(format) => eprintln!(
+                        "  kv {}: local value was {} bytes of {:?}",
+                        key,
+                        value.len(),
+                        format
+                    ),
+                }
+            }
+            ChangeKey::File(path) => match &conflict.local.value {
+                Some(data) => {
+                    let copy = format!("{}.local", path);
+                    agent.fs.write_file(&copy, data).await?;
+                    eprintln!("  file {}: local version saved as {}", path, copy);
+                }
+                None => eprintln!("  file {}", path),
+            },
+        }
+    }
     Ok(())
 }
 
@@ -24,7 +220,7 @@ pub async fn handle_push_command(id_or_path: String) -> anyhow::Result<()> {
     let Some(db) = db else {
         return Err(anyhow!("db is not connected to the remote"));
     };
-    db.push().await?;
+    push_with_progress(&db, render_progress("Pushing"), &cancel_on_ctrl_c()).await?;
     eprintln!("Local data pushed to remote db successfully");
     Ok(())
 }
@@ -33,12 +229,17 @@ pub async fn handle_checkpoint_command(id_or_path: String) -> anyhow::Result<()>
     let options = AgentFSOptions::resolve(&id_or_path)?;
     eprintln!("Using agent: {}", id_or_path);
 
-    let (db, _) = open_agentfs(options).await?;
-    let Some(db) = db else {
-        return Err(anyhow!("db is not connected to the remote"));
-    };
-    db.checkpoint().await?;
-    eprintln!("Local db checkpoined successfully");
+    let (db, agent) = open_agentfs(options).await?;
+    match db {
+        Some(db) => db.checkpoint().await?,
+        None => {
+            // Local-only database: fold the WAL back into the main file
+            let conn = agent.get_connection();
+            let mut rows = conn.query("PRAGMA wal_checkpoint(TRUNCATE)", ()).await?;
+            while rows.next().await?.is_some() {}
+        }
+    }
+    eprintln!("Local db checkpointed successfully");
     Ok(())
 }
 
@@ -49,11 +250,134 @@ pub async fn handle_stats_command(
     let options = AgentFSOptions::resolve(&id_or_path)?;
     eprintln!("Using agent: {}", id_or_path);
 
-    let (db, _) = open_agentfs(options).await?;
-    let Some(db) = db else {
-        return Err(anyhow!("db is not connected to the remote"));
+    let db_path = options.db_path()?;
+    let (db, agent) = open_agentfs(options).await?;
+    let mut stats = match db {
+        Some(db) => serde_json::to_value(db.stats().await?)?,
+        None => local_stats(&agent, &db_path).await?,
     };
-    let stats = db.stats().await?;
+    // Storage stats say little about what the agent holds, so count that too
+    if let Some(stats) = stats.as_object_mut() {
+        stats.insert(
+            "summary".to_string(),
+            serde_json::to_value(agent.summary().await?)?,
+        );
+    }
     stdout.write_all(serde_json::to_string(&stats)?.as_bytes())?;
     Ok(())
 }
+
+/// Stats for a database that isn't connected to a remote: file sizes and
+/// row counts of every table.
+async fn local_stats(agent: &AgentFS, db_path: &str) -> anyhow::Result<serde_json::Value> {
+    let conn = agent.get_connection();
+
+    let mut names = Vec::new();
+    let mut rows = conn
+        .query(
+            "SELECT name FROM sqlite_schema WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+            (),
+        )
+        .await?;
+    while let Some(row) = rows.next().await? {
+        if let Ok(Value::Text(name)) = row.get_value(0) {
+            names.push(name);
+        }
+    }
+
+    let mut tables = serde_json::Map::new();
+    for name in names {
+        let sql = format!("SELECT COUNT(*) FROM \"{}\"", name.replace('"', "\"\""));
+        let mut rows = conn.query(&sql, ()).await?;
+        let count = match rows.next().await? {
+            Some(row) => row.get_value(0)?.as_integer().copied().unwrap_or(0),
+            None => 0,
+        };
+        tables.insert(name, count.into());
+    }
+
+    let file_size = |path: &str| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    Ok(serde_json::json!({
+        "file_size": file_size(db_path),
+        "wal_size": file_size(&format!("{db_path}-wal")),
+        "tables": tables,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use agentfs_sdk::{AgentFS, AgentFSOptions};
+    use tempfile::tempdir;
+
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use tokio_util::sync::CancellationToken;
+
+    use crate::cmd::sync::{
+        handle_checkpoint_command, handle_stats_command, track_progress, SyncProgress,
+    };
+
+    #[tokio::test]
+    async fn test_track_progress_heartbeats() {
+        let events = Mutex::new(Vec::new());
+        let token = CancellationToken::new();
+        let op = async {
+            tokio::time::sleep(Duration::from_millis(60)).await;
+            Ok::<_, anyhow::Error>(42)
+        };
+        let result = track_progress(
+            op,
+            &|p| events.lock().unwrap().push(p),
+            &token,
+            Duration::from_millis(10),
+        )
+        .await
+        .unwrap();
+        assert_eq!(result, 42);
+
+        let events = events.into_inner().unwrap();
+        assert_eq!(events[0], SyncProgress::Started);
+        assert!(events.len() > 1);
+        assert!(events[1..]
+            .iter()
+            .all(|p| matches!(p, SyncProgress::Heartbeat { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_track_progress_cancelled() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let op = std::future::pending::<anyhow::Result<()>>();
+        let err = track_progress(op, &|_| {}, &token, Duration::from_secs(1))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Sync cancelled");
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_and_stats_local_db() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("local.db");
+        let path = path.to_str().unwrap().to_string();
+
+        let agentfs = AgentFS::open(AgentFSOptions::with_path(path.clone()))
+            .await
+            .unwrap();
+        agentfs.kv.set("key", &"value").await.unwrap();
+        agentfs.fs.write_file("/a.txt", b"hello").await.unwrap();
+        drop(agentfs);
+
+        handle_checkpoint_command(path.clone()).await.unwrap();
+
+        let mut stdout = Vec::new();
+        handle_stats_command(&mut stdout, path).await.unwrap();
+        let stats: serde_json::Value = serde_json::from_slice(&stdout).unwrap();
+        assert_eq!(stats["tables"]["kv_store"], 1);
+        assert!(stats["file_size"].as_u64().unwrap() > 0);
+        assert_eq!(stats["summary"]["kv_keys"], 1);
+        assert_eq!(stats["summary"]["files"], 1);
+        assert_eq!(stats["summary"]["file_bytes"], 5);
+        assert_eq!(stats["summary"]["tool_calls"]["pending"], 0);
+    }
+}
@@ -93,7 +93,7 @@ fn format_timestamp(timestamp: i64) -> String {
 }
 
 /// Format tool calls in table format
-fn format_table(stdout: &mut impl Write, calls: &[ToolCall]) -> AnyhowResult<()> {
+pub(crate) fn format_table(stdout: &mut impl Write, calls: &[ToolCall]) -> AnyhowResult<()> {
     if calls.is_empty() {
         writeln!(stdout, "No tool calls found")?;
         return Ok(());
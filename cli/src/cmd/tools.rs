@@ -0,0 +1,60 @@
+use agentfs_sdk::{toolcalls::to_otlp_json, AgentFSOptions};
+use anyhow::{Context, Result as AnyhowResult};
+use std::io::Write;
+
+use crate::cmd::init::open_agentfs;
+
+/// Export the tool call audit log as OpenTelemetry spans
+///
+/// `otlp-json` writes an OTLP/JSON trace export request that can be posted to a
+/// collector; `json` writes the spans as a plain JSON array.
+pub async fn handle_export_command(
+    stdout: &mut impl Write,
+    id_or_path: &str,
+    format: &str,
+) -> AnyhowResult<()> {
+    let options = AgentFSOptions::resolve(id_or_path)?;
+    let (_, agentfs) = open_agentfs(options).await?;
+
+    let spans = agentfs
+        .tools
+        .export_spans()
+        .await
+        .context("Failed to export tool calls")?;
+
+    let json = match format {
+        "otlp-json" => to_otlp_json(&spans, id_or_path),
+        "json" => serde_json::to_value(&spans)?,
+        _ => anyhow::bail!("Invalid format: {}", format),
+    };
+    writeln!(stdout, "{}", serde_json::to_string(&json)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use agentfs_sdk::AgentFS;
+    use tempfile::NamedTempFile;
+
+    #[tokio::test]
+    async fn test_export_otlp_json() {
+        let file = NamedTempFile::new().unwrap();
+        let path = file.path().to_str().unwrap();
+        let agentfs = AgentFS::open(AgentFSOptions::with_path(path))
+            .await
+            .unwrap();
+        let id = agentfs.tools.start("search", None).await.unwrap();
+        agentfs.tools.success(id, None).await.unwrap();
+
+        let mut output = Vec::new();
+        handle_export_command(&mut output, path, "otlp-json")
+            .await
+            .unwrap();
+
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        let spans = &json["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(spans[0]["name"], "search");
+        assert_eq!(spans[0]["status"]["code"], 1);
+    }
+}
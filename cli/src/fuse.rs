@@ -1,4 +1,4 @@
-use agentfs_sdk::{BoxedFile, FileSystem, FsError, Stats};
+use agentfs_sdk::{filesystem::mode, BoxedFile, FileSystem, FsError, Stats};
 use fuser::{
     consts::{
         FUSE_ASYNC_READ, FUSE_CACHE_SYMLINKS, FUSE_NO_OPENDIR_SUPPORT, FUSE_PARALLEL_DIROPS,
@@ -174,17 +174,17 @@ impl Filesystem for AgentFSFuse {
         }
     }
 
-    /// Sets file attributes, handling truncate and chmod operations.
+    /// Sets file attributes, handling truncate, chmod and chown operations.
     ///
-    /// Currently `size` changes (truncate) and `mode` changes (chmod) are supported.
-    /// Other attribute changes (uid, gid, timestamps) are accepted but ignored.
+    /// Currently `size` changes (truncate), `mode` changes (chmod) and `uid`/`gid`
+    /// changes (chown) are supported. Timestamp changes are accepted but ignored.
     fn setattr(
         &mut self,
         _req: &Request,
         ino: u64,
         mode: Option<u32>,
-        _uid: Option<u32>,
-        _gid: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
         size: Option<u64>,
         _atime: Option<fuser::TimeOrNow>,
         _mtime: Option<fuser::TimeOrNow>,
@@ -214,6 +214,24 @@ impl Filesystem for AgentFSFuse {
             }
         }
 
+        // Handle chown
+        if uid.is_some() || gid.is_some() {
+            let Some(path) = self.path_cache.lock().get(&ino).cloned() else {
+                reply.error(libc::ENOENT);
+                return;
+            };
+
+            let fs = self.fs.clone();
+            let result = self
+                .runtime
+                .block_on(async move { fs.chown(&path, uid, gid).await });
+
+            if let Err(e) = result {
+                reply.error(error_to_errno(&e));
+                return;
+            }
+        }
+
         // Handle truncate
         if let Some(new_size) = size {
             let result = if let Some(fh) = fh {
@@ -351,13 +369,7 @@ impl Filesystem for AgentFSFuse {
                 format!("{}/{}", path, entry.name)
             };
 
-            let kind = if entry.stats.is_directory() {
-                FileType::Directory
-            } else if entry.stats.is_symlink() {
-                FileType::Symlink
-            } else {
-                FileType::RegularFile
-            };
+            let kind = file_kind(&entry.stats);
 
             self.add_path(entry.stats.ino as u64, entry_path);
             all_entries.push((entry.stats.ino as u64, kind, entry.name.as_str()));
@@ -1238,14 +1250,6 @@ impl AgentFSFuse {
 /// The uid and gid parameters override the stored values to ensure proper
 /// file ownership reporting (avoids "dubious ownership" errors from git).
 fn fillattr(stats: &Stats, uid: u32, gid: u32) -> FileAttr {
-    let kind = if stats.is_directory() {
-        FileType::Directory
-    } else if stats.is_symlink() {
-        FileType::Symlink
-    } else {
-        FileType::RegularFile
-    };
-
     FileAttr {
         ino: stats.ino as u64,
         size: stats.size as u64,
@@ -1254,7 +1258,7 @@ fn fillattr(stats: &Stats, uid: u32, gid: u32) -> FileAttr {
         mtime: UNIX_EPOCH + Duration::from_secs(stats.mtime as u64),
         ctime: UNIX_EPOCH + Duration::from_secs(stats.ctime as u64),
         crtime: UNIX_EPOCH,
-        kind,
+        kind: file_kind(stats),
         perm: (stats.mode & 0o777) as u16,
         nlink: stats.nlink,
         uid,
@@ -1265,6 +1269,15 @@ fn fillattr(stats: &Stats, uid: u32, gid: u32) -> FileAttr {
     }
 }
 
+/// FUSE file type of an entry; types AgentFS doesn't store show as regular files
+fn file_kind(stats: &Stats) -> FileType {
+    match stats.kind() {
+        Some(mode::FileType::Directory) => FileType::Directory,
+        Some(mode::FileType::Symlink) => FileType::Symlink,
+        Some(mode::FileType::File) | None => FileType::RegularFile,
+    }
+}
+
 pub fn mount(
     fs: Arc<dyn FileSystem>,
     opts: FuseMountOptions,
@@ -1,10 +1,11 @@
 use agentfs::{
     cmd::{self, completions::handle_completions},
     get_runtime,
-    parser::{Args, Command, FsCommand, ServeCommand, SyncCommand},
+    parser::{Args, Command, FsCommand, ServeCommand, SyncCommand, ToolsCommand},
 };
 use clap::{CommandFactory, Parser};
 use clap_complete::CompleteEnv;
+use std::io::IsTerminal;
 use tracing_subscriber::prelude::*;
 
 fn main() {
@@ -24,12 +25,16 @@ fn main() {
     match args.command {
         Command::Init {
             id,
+            id_format,
             force,
             base,
+            journal,
             sync,
         } => {
             let rt = get_runtime();
-            if let Err(e) = rt.block_on(cmd::init::init_database(id, sync, force, base)) {
+            if let Err(e) = rt.block_on(cmd::init::init_database(
+                id, id_format, sync, force, base, journal,
+            )) {
                 eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
@@ -38,9 +43,9 @@ fn main() {
             id_or_path,
             command,
         } => match command {
-            SyncCommand::Pull => {
+            SyncCommand::Pull { conflicts } => {
                 let rt = get_runtime();
-                if let Err(e) = rt.block_on(cmd::sync::handle_pull_command(id_or_path)) {
+                if let Err(e) = rt.block_on(cmd::sync::handle_pull_command(id_or_path, conflicts)) {
                     eprintln!("Error: {}", e);
                     std::process::exit(1);
                 }
@@ -75,17 +80,65 @@ fn main() {
             no_default_allows,
             experimental_sandbox,
             strace,
+            strace_summary,
+            strace_filter,
+            strace_exclude,
+            dump_fds_on_exit,
+            print_mounts,
+            with_std_devices,
+            fake_time,
+            fake_time_step,
+            delay,
+            fail,
+            seed,
+            deny_path,
+            deny_glob,
+            cwd,
+            capture_stdout,
+            capture_stderr,
+            record,
+            id,
             session,
             command,
             args,
         } => {
             let command = command.unwrap_or_else(default_shell);
+            let ptrace = cmd::PtraceOptions {
+                strace: cmd::StraceOptions {
+                    enabled: strace,
+                    summary: strace_summary,
+                    filter: strace_filter,
+                    exclude: strace_exclude,
+                },
+                dump_fds_on_exit,
+                print_mounts,
+                std_devices: with_std_devices,
+                fake_time: fake_time.map(|start| cmd::FakeTimeOptions {
+                    start,
+                    step: std::time::Duration::from_millis(fake_time_step),
+                }),
+                faults: cmd::FaultOptions {
+                    delays: delay,
+                    failures: fail,
+                    seed,
+                },
+                deny: cmd::DenyOptions {
+                    paths: deny_path,
+                    globs: deny_glob,
+                },
+                cwd,
+            };
             let rt = get_runtime();
             if let Err(e) = rt.block_on(cmd::handle_run_command(
                 allow,
                 no_default_allows,
                 experimental_sandbox,
-                strace,
+                ptrace,
+                cmd::CaptureOptions {
+                    stdout: capture_stdout,
+                    stderr: capture_stderr,
+                },
+                id.filter(|_| record),
                 session,
                 command,
                 args,
@@ -125,6 +178,45 @@ fn main() {
                 std::process::exit(1);
             }
         },
+        Command::Backup { id_or_path, dest } => {
+            let rt = get_runtime();
+            if let Err(e) = rt.block_on(cmd::backup::handle_backup_command(id_or_path, dest)) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::Clone { src, dst, force } => {
+            let rt = get_runtime();
+            if let Err(e) = rt.block_on(cmd::backup::handle_clone_command(src, dst, force)) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::Check {
+            id_or_path,
+            json,
+            repair,
+            lost_found,
+        } => {
+            let rt = get_runtime();
+            let repair = repair.then_some(cmd::check::RepairOptions {
+                apply: true,
+                lost_and_found: lost_found,
+            });
+            match rt.block_on(cmd::check::handle_check_command(
+                &mut std::io::stdout(),
+                id_or_path,
+                json,
+                repair,
+            )) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
         Command::Diff { id_or_path } => {
             let rt = get_runtime();
             if let Err(e) = rt.block_on(cmd::fs::diff_filesystem(id_or_path)) {
@@ -155,27 +247,99 @@ fn main() {
                 std::process::exit(1);
             }
         }
+        Command::Tools { command } => match command {
+            ToolsCommand::Export { id_or_path, format } => {
+                let rt = get_runtime();
+                if let Err(e) = rt.block_on(cmd::tools::handle_export_command(
+                    &mut std::io::stdout(),
+                    &id_or_path,
+                    &format,
+                )) {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        },
         Command::Fs {
             command,
             id_or_path,
         } => {
             let rt = get_runtime();
             match command {
-                FsCommand::Ls { fs_path } => {
+                FsCommand::Ls {
+                    fs_path,
+                    limit,
+                    offset,
+                    sort,
+                    reverse,
+                } => {
+                    let listing = cmd::fs::LsOptions {
+                        limit,
+                        offset,
+                        // Validated by clap
+                        sort: sort.parse().unwrap_or_default(),
+                        reverse,
+                    };
                     if let Err(e) = rt.block_on(cmd::fs::ls_filesystem(
                         &mut std::io::stdout(),
                         id_or_path,
                         &fs_path,
+                        &listing,
+                    )) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                FsCommand::Tree {
+                    fs_path,
+                    depth,
+                    all,
+                } => {
+                    if let Err(e) = rt.block_on(cmd::fs::tree_filesystem(
+                        &mut std::io::stdout(),
+                        id_or_path,
+                        &fs_path,
+                        depth,
+                        all,
                     )) {
                         eprintln!("Error: {}", e);
                         std::process::exit(1);
                     }
                 }
-                FsCommand::Cat { file_path } => {
+                FsCommand::Cat { file_path, force } => {
+                    // Binary output only garbles a terminal; pipes get it unchanged
+                    let allow_binary = force || !std::io::stdout().is_terminal();
                     if let Err(e) = rt.block_on(cmd::fs::cat_filesystem(
                         &mut std::io::stdout(),
                         id_or_path,
                         &file_path,
+                        allow_binary,
+                    )) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                FsCommand::Blocks { file_path } => {
+                    if let Err(e) = rt.block_on(cmd::fs::blocks_filesystem(
+                        &mut std::io::stdout(),
+                        id_or_path,
+                        &file_path,
+                    )) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                FsCommand::Stat {
+                    file_path,
+                    dereference,
+                    json,
+                } => {
+                    if let Err(e) = rt.block_on(cmd::fs::stat_filesystem(
+                        &mut std::io::stdout(),
+                        id_or_path,
+                        &file_path,
+                        dereference,
+                        json,
                     )) {
                         eprintln!("Error: {}", e);
                         std::process::exit(1);
@@ -189,6 +353,79 @@ fn main() {
                         std::process::exit(1);
                     }
                 }
+                FsCommand::Rm { pattern, recursive } => {
+                    if let Err(e) = rt.block_on(cmd::fs::rm_filesystem(
+                        &mut std::io::stdout(),
+                        id_or_path,
+                        &pattern,
+                        recursive,
+                    )) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                FsCommand::Mv { from, to, keep } => {
+                    if let Err(e) = rt.block_on(cmd::fs::mv_filesystem(
+                        &mut std::io::stdout(),
+                        id_or_path,
+                        &from,
+                        &to,
+                        keep,
+                    )) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                FsCommand::Import {
+                    host_dir,
+                    dst,
+                    preserve_mode,
+                } => {
+                    if let Err(e) = rt.block_on(cmd::fs::import_filesystem(
+                        &mut std::io::stdout(),
+                        id_or_path,
+                        &host_dir,
+                        &dst,
+                        preserve_mode,
+                    )) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                FsCommand::Export {
+                    src,
+                    host_dir,
+                    dereference,
+                } => {
+                    if let Err(e) = rt.block_on(cmd::fs::export_filesystem(
+                        &mut std::io::stdout(),
+                        id_or_path,
+                        &src,
+                        &host_dir,
+                        dereference,
+                    )) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+                FsCommand::Log { since, json } => {
+                    if let Err(e) = rt.block_on(cmd::fs::log_filesystem(
+                        &mut std::io::stdout(),
+                        id_or_path,
+                        since,
+                        json,
+                    )) {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        Command::Shell { id_or_path, push } => {
+            let rt = get_runtime();
+            if let Err(e) = rt.block_on(cmd::shell::handle_shell_command(id_or_path, push)) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
             }
         }
         Command::Completions { command } => handle_completions(command),
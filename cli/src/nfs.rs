@@ -7,7 +7,7 @@
 use std::collections::HashMap;
 use std::sync::Arc;
 
-use agentfs_sdk::{FileSystem, Stats, S_IFDIR, S_IFLNK, S_IFMT, S_IFREG};
+use agentfs_sdk::{FileSystem, FileType, Stats};
 use async_trait::async_trait;
 use nfsserve::nfs::{
     fattr3, fileid3, filename3, ftype3, nfspath3, nfsstat3, nfstime3, sattr3, specdata3,
@@ -95,16 +95,15 @@ impl AgentNFS {
 
     /// Convert AgentFS Stats to NFS fattr3.
     fn stats_to_fattr(&self, stats: &Stats, ino: fileid3) -> fattr3 {
-        let ftype = match stats.mode & S_IFMT {
-            S_IFREG => ftype3::NF3REG,
-            S_IFDIR => ftype3::NF3DIR,
-            S_IFLNK => ftype3::NF3LNK,
-            _ => ftype3::NF3REG,
+        let ftype = match stats.kind() {
+            Some(FileType::Directory) => ftype3::NF3DIR,
+            Some(FileType::Symlink) => ftype3::NF3LNK,
+            Some(FileType::File) | None => ftype3::NF3REG,
         };
 
         fattr3 {
             ftype,
-            mode: stats.mode & 0o7777,
+            mode: stats.permissions(),
             nlink: stats.nlink,
             uid: self.uid,
             gid: self.gid,
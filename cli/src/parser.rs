@@ -1,10 +1,11 @@
 use crate::cmd::completions::Shell;
 use agentfs_sdk::agentfs_dir;
-use clap::{Parser, Subcommand};
+use clap::{ArgGroup, Parser, Subcommand};
 use clap_complete::{
     engine::ValueCompleter, ArgValueCompleter, CompletionCandidate, PathCompleter,
 };
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 #[derive(Parser, Debug)]
 #[command(name = "agentfs")]
@@ -41,6 +42,11 @@ pub enum Command {
         /// Agent identifier (if not provided, generates a unique one)
         id: Option<String>,
 
+        /// Template for the generated ID: a strftime format (UTC) where `{rand}`
+        /// is a random hex suffix [default: agent-%Y%m%d-%H%M%S-{rand}]
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "id")]
+        id_format: Option<String>,
+
         /// Overwrite existing file if it exists
         #[arg(long)]
         force: bool,
@@ -49,6 +55,10 @@ pub enum Command {
         #[arg(long)]
         base: Option<PathBuf>,
 
+        /// Keep an audit journal of filesystem changes (see `fs <ID> log`)
+        #[arg(long)]
+        journal: bool,
+
         #[command(flatten)]
         sync: SyncCommandOptions,
     },
@@ -70,11 +80,21 @@ pub enum Command {
         #[command(subcommand)]
         command: FsCommand,
     },
+    /// Interactive shell over an agent (ls, cd, cat, put, rm, kv, tools)
+    Shell {
+        /// Agent ID or database path
+        #[arg(add = ArgValueCompleter::new(id_or_path_completer))]
+        id_or_path: String,
+        /// Push local changes to the remote when the shell exits (synced agents only)
+        #[arg(long)]
+        push: bool,
+    },
     /// Run a command in the sandboxed environment.
     ///
     /// By default, uses FUSE+overlay with Linux user and mount namespaces for isolation.
     /// The overlay uses the host filesystem as a read-only base and stores
     /// all changes in an AgentFS-backed delta layer.
+    #[command(group = ArgGroup::new("strace_mode").multiple(true))]
     Run {
         /// Allow write access to additional directories (can be specified multiple times)
         #[arg(long = "allow", value_name = "PATH")]
@@ -90,9 +110,122 @@ pub enum Command {
 
         /// Enable strace-like output for system calls
         /// Only used with --experimental-sandbox
-        #[arg(long = "strace")]
+        #[arg(long = "strace", group = "strace_mode")]
         strace: bool,
 
+        /// Print per-syscall counts, total time and errors at exit (like `strace -c`)
+        /// Only used with --experimental-sandbox
+        #[arg(long = "strace-summary", group = "strace_mode")]
+        strace_summary: bool,
+
+        /// Only show these system calls in strace output (comma-separated, e.g. openat,read,write,close)
+        #[arg(
+            long = "strace-filter",
+            value_name = "SYSCALLS",
+            value_delimiter = ',',
+            requires = "strace_mode"
+        )]
+        strace_filter: Vec<String>,
+
+        /// Hide these system calls from strace output (comma-separated)
+        #[arg(
+            long = "strace-exclude",
+            value_name = "SYSCALLS",
+            value_delimiter = ',',
+            requires = "strace_mode"
+        )]
+        strace_exclude: Vec<String>,
+
+        /// Print each process's file descriptor table (virtual FD, kind, kernel FD,
+        /// flags and path) when it exits
+        /// Only used with --experimental-sandbox
+        #[arg(long = "dump-fds-on-exit")]
+        dump_fds_on_exit: bool,
+
+        /// Print the sandbox's mount table (path, type, source, ro/rw) before
+        /// running the command
+        /// Only used with --experimental-sandbox
+        #[arg(long = "print-mounts")]
+        print_mounts: bool,
+
+        /// Provide a minimal /dev (null, zero, full, random, urandom, tty) and
+        /// a /proc limited to /proc/self, served by the sandbox
+        /// Only used with --experimental-sandbox
+        #[arg(long = "with-std-devices")]
+        with_std_devices: bool,
+
+        /// Serve a fixed wall-clock time to clock_gettime, gettimeofday and time
+        /// (RFC 3339 / ISO 8601, e.g. 2024-01-01T00:00:00Z)
+        /// Only used with --experimental-sandbox
+        #[arg(long = "fake-time", value_name = "TIME", value_parser = parse_fake_time)]
+        fake_time: Option<SystemTime>,
+
+        /// Advance the fake clock by this many milliseconds on every time syscall
+        #[arg(
+            long = "fake-time-step",
+            value_name = "MS",
+            default_value_t = 0,
+            requires = "fake_time"
+        )]
+        fake_time_step: u64,
+
+        /// Add latency to a system call, e.g. `open=5ms` (can be specified multiple times)
+        /// Only used with --experimental-sandbox
+        #[arg(long = "delay", value_name = "SYSCALL=DURATION")]
+        delay: Vec<String>,
+
+        /// Fail a system call with an errno, optionally with a probability,
+        /// e.g. `read=EIO:0.01` (can be specified multiple times)
+        /// Only used with --experimental-sandbox
+        #[arg(long = "fail", value_name = "SYSCALL=ERRNO[:PROBABILITY]")]
+        fail: Vec<String>,
+
+        /// Seed for --fail, making the injected failures reproducible
+        #[arg(long = "seed", value_name = "SEED")]
+        seed: Option<u64>,
+
+        /// Hide a path (and everything below it) from the command, which sees
+        /// ENOENT instead (can be specified multiple times)
+        /// Only used with --experimental-sandbox
+        #[arg(long = "deny-path", value_name = "PATH")]
+        deny_path: Vec<PathBuf>,
+
+        /// Hide every path matching a glob, e.g. `**/.ssh/**`; `*` and `?` match
+        /// within one path component and `**` across any number
+        /// (can be specified multiple times)
+        /// Only used with --experimental-sandbox
+        #[arg(long = "deny-glob", value_name = "PATTERN")]
+        deny_glob: Vec<String>,
+
+        /// Start the command in this directory, an absolute path as the sandbox
+        /// sees it (e.g. a directory inside /agent) instead of the current one
+        /// Only used with --experimental-sandbox
+        #[arg(long = "cwd", visible_alias = "working-dir", value_name = "PATH")]
+        cwd: Option<PathBuf>,
+
+        /// Copy the command's stdout into this file in the agent filesystem,
+        /// in addition to printing it (appends if the file exists)
+        #[arg(long = "capture-stdout", value_name = "PATH")]
+        capture_stdout: Option<String>,
+
+        /// Copy the command's stderr into this file in the agent filesystem,
+        /// in addition to printing it (appends if the file exists)
+        #[arg(long = "capture-stderr", value_name = "PATH")]
+        capture_stderr: Option<String>,
+
+        /// Record this run in the tool-call history of the agent given by --id
+        #[arg(long = "record", requires = "id")]
+        record: bool,
+
+        /// Agent ID or database path that --record writes to
+        #[arg(
+            long = "id",
+            value_name = "ID_OR_PATH",
+            requires = "record",
+            add = ArgValueCompleter::new(id_or_path_completer)
+        )]
+        id: Option<String>,
+
         /// Session identifier for sharing delta layer across multiple runs.
         /// If not provided, a unique session ID is generated for each run.
         /// Use the same session ID to share the delta layer between runs.
@@ -136,6 +269,48 @@ pub enum Command {
         #[arg(long)]
         gid: Option<u32>,
     },
+    /// Write a point-in-time copy of an agent database to a file
+    Backup {
+        /// Agent ID or database path
+        #[arg(value_name = "ID_OR_PATH", add = ArgValueCompleter::new(id_or_path_completer))]
+        id_or_path: String,
+
+        /// Destination file for the backup
+        #[arg(value_name = "DEST", add = ArgValueCompleter::new(PathCompleter::file()))]
+        dest: PathBuf,
+    },
+    /// Create a new agent from an existing agent or a backup file
+    Clone {
+        /// Source agent ID or backup file path
+        #[arg(value_name = "SRC", add = ArgValueCompleter::new(id_or_path_completer))]
+        src: String,
+
+        /// Destination agent ID
+        #[arg(value_name = "DST")]
+        dst: String,
+
+        /// Overwrite the destination agent if it exists
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check an agent database for corruption
+    Check {
+        /// Agent ID or database path
+        #[arg(value_name = "ID_OR_PATH", add = ArgValueCompleter::new(id_or_path_completer))]
+        id_or_path: String,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Fix dangling entries and leftover data before checking
+        #[arg(long)]
+        repair: bool,
+
+        /// With --repair, link unreferenced inodes into /lost+found
+        #[arg(long, requires = "repair")]
+        lost_found: bool,
+    },
     /// Show differences between base filesystem and delta (overlay mode only)
     Diff {
         /// Agent ID or database path
@@ -164,6 +339,11 @@ pub enum Command {
         #[arg(long, default_value = "table", value_parser = ["table", "json"])]
         format: String,
     },
+    /// Tool call audit log operations
+    Tools {
+        #[command(subcommand)]
+        command: ToolsCommand,
+    },
     /// Start an NFS server to export an AgentFS filesystem over the network
     /// (deprecated: use `agentfs serve nfs` instead)
     #[cfg(unix)]
@@ -209,11 +389,63 @@ pub enum FsCommand {
         /// Path to list (default: /)
         #[arg(default_value = "/")]
         fs_path: String,
+
+        /// Print at most this many entries
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+
+        /// Skip this many entries before printing
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        offset: usize,
+
+        /// Order of the entries in each directory
+        #[arg(long, default_value = "name", value_parser = ["name", "size", "mtime"])]
+        sort: String,
+
+        /// Reverse the order within each directory
+        #[arg(short, long)]
+        reverse: bool,
+    },
+    /// Display the directory hierarchy as an indented tree
+    Tree {
+        /// Directory to start from (default: /)
+        #[arg(default_value = "/")]
+        fs_path: String,
+
+        /// Descend at most this many levels
+        #[arg(long, value_name = "N")]
+        depth: Option<usize>,
+
+        /// Include entries whose names start with a dot
+        #[arg(short, long)]
+        all: bool,
     },
     /// Display file contents
     Cat {
         /// Path to the file in the filesystem
         file_path: String,
+
+        /// Print binary files to a terminal too
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Display file metadata (type, mode, size, link count, timestamps)
+    Stat {
+        /// Path to the file in the filesystem
+        file_path: String,
+
+        /// Follow symbolic links
+        #[arg(short = 'L', long)]
+        dereference: bool,
+
+        /// Print metadata as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show how a file's blocks are stored (offset, length, stored size, compression)
+    Blocks {
+        /// Path to the file in the filesystem
+        file_path: String,
     },
     /// Write file content
     Write {
@@ -223,20 +455,104 @@ pub enum FsCommand {
         /// Content of the file
         content: String,
     },
+    /// Remove every file matching a glob pattern, e.g. '/tmp/*.log'
+    ///
+    /// `*` and `?` match within one path component and `**` matches any number
+    /// of components. Everything is removed in one transaction: if any removal
+    /// fails, nothing is removed.
+    Rm {
+        /// Absolute glob pattern (quote it so the shell leaves it alone)
+        pattern: String,
+
+        /// Also remove matching directories, with everything in them
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// Move or rename a file or directory, within the agent or to another one
+    ///
+    /// Either path may be written as `ID:/path` to name a path in another
+    /// agent; the tree is then copied across and removed from the source.
+    Mv {
+        /// Path to move, or `ID:/path` in another agent
+        from: String,
+
+        /// New path, or `ID:/path` in another agent
+        to: String,
+
+        /// Copy instead of moving, leaving the source in place
+        #[arg(long)]
+        keep: bool,
+    },
+    /// Copy a host directory tree into the filesystem
+    Import {
+        /// Directory on the host to copy from
+        host_dir: PathBuf,
+
+        /// Destination directory in the filesystem (created if missing)
+        dst: String,
+
+        /// Keep the permission bits of host files and directories
+        #[arg(long)]
+        preserve_mode: bool,
+    },
+    /// Copy a directory tree from the filesystem out to the host
+    Export {
+        /// Directory in the filesystem to copy from
+        src: String,
+
+        /// Destination directory on the host (created if missing)
+        host_dir: PathBuf,
+
+        /// Write what symlinks point to instead of recreating the symlinks
+        #[arg(short = 'L', long)]
+        dereference: bool,
+    },
+    /// Show the audit journal of filesystem changes (needs `init --journal`)
+    Log {
+        /// Only show changes made at or after this Unix timestamp
+        #[arg(long, value_name = "SECONDS")]
+        since: Option<i64>,
+
+        /// Print entries as JSON
+        #[arg(long)]
+        json: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
 pub enum SyncCommand {
     /// Pull remote changes (only of agentfs was initialized with remote sync)
-    Pull,
+    Pull {
+        /// How to resolve kv entries and files changed both locally and remotely
+        #[arg(
+            long,
+            default_value = "last-writer-wins",
+            value_parser = ["last-writer-wins", "prefer-local", "prefer-remote", "manual"]
+        )]
+        conflicts: String,
+    },
     /// Push remote changes (only of agentfs was initialized with remote sync)
     Push,
-    /// Print synced database stats
+    /// Print database stats (sync stats for synced dbs, table sizes otherwise)
     Stats,
-    /// Checkpoint local synced db
+    /// Checkpoint the local db
     Checkpoint,
 }
 
+#[derive(Subcommand, Debug)]
+pub enum ToolsCommand {
+    /// Export tool calls as OpenTelemetry spans
+    Export {
+        /// Agent ID or database path
+        #[arg(value_name = "ID_OR_PATH", add = ArgValueCompleter::new(id_or_path_completer))]
+        id_or_path: String,
+
+        /// Output format
+        #[arg(long, default_value = "otlp-json", value_parser = ["otlp-json", "json"])]
+        format: String,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 pub enum ServeCommand {
     /// Start an NFS server to export an AgentFS filesystem over the network
@@ -334,3 +650,45 @@ fn id_or_path_completer(current: &std::ffi::OsStr) -> Vec<CompletionCandidate> {
 
     completions
 }
+
+/// Parse a `--fake-time` value.
+///
+/// Accepts RFC 3339 timestamps, as well as a date-time or date without an
+/// offset, which are interpreted as UTC.
+fn parse_fake_time(value: &str) -> Result<SystemTime, String> {
+    use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.into());
+    }
+    if let Ok(dt) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Ok(dt.and_utc().into());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        if let Some(dt) = date.and_hms_opt(0, 0, 0) {
+            return Ok(dt.and_utc().into());
+        }
+    }
+    Err(format!(
+        "invalid time '{value}', expected ISO 8601 such as 2024-01-01T00:00:00Z"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_parse_fake_time() {
+        let expected = UNIX_EPOCH + Duration::from_secs(1_704_067_200);
+        assert_eq!(parse_fake_time("2024-01-01T00:00:00Z").unwrap(), expected);
+        assert_eq!(
+            parse_fake_time("2024-01-01T01:00:00+01:00").unwrap(),
+            expected
+        );
+        assert_eq!(parse_fake_time("2024-01-01T00:00:00").unwrap(), expected);
+        assert_eq!(parse_fake_time("2024-01-01").unwrap(), expected);
+        assert!(parse_fake_time("yesterday").is_err());
+    }
+}
@@ -15,7 +15,8 @@
 //! The HostFS base layer then accesses files through `/proc/self/fd/N`,
 //! bypassing the FUSE mount entirely.
 
-use agentfs_sdk::{AgentFS, AgentFSOptions, FileSystem, HostFS, OverlayFS};
+use crate::cmd::{capture::Capture, record::RunRecorder, CaptureOptions};
+use agentfs_sdk::{filesystem, AgentFS, AgentFSOptions, FileSystem, HostFS, OverlayFS};
 use anyhow::{bail, Context, Result};
 use std::{
     cmp::Reverse,
@@ -142,6 +143,8 @@ fn install_signal_handlers() {
 pub async fn run_cmd(
     allow: Vec<PathBuf>,
     no_default_allows: bool,
+    capture: CaptureOptions,
+    recorder: Option<RunRecorder>,
     session_id: Option<String>,
     command: PathBuf,
     args: Vec<String>,
@@ -158,6 +161,9 @@ pub async fn run_cmd(
     if is_mountpoint(&session.fuse_mountpoint) {
         eprintln!("Joining existing session: {}", session.run_id);
         eprintln!();
+        if !capture.is_empty() {
+            eprintln!("Warning: --capture-stdout and --capture-stderr are not supported when joining an existing session, ignoring");
+        }
         return run_in_existing_session(
             &cwd,
             &session.fuse_mountpoint,
@@ -165,6 +171,7 @@ pub async fn run_cmd(
             command,
             args,
             &session.run_id,
+            recorder,
         );
     }
 
@@ -195,6 +202,9 @@ pub async fn run_cmd(
     };
 
     let base = Arc::new(hostfs);
+    // Captured output goes to the delta layer, which shares its connection
+    // with the overlay
+    let capture_fs = agentfs.fs.clone();
     let overlay = OverlayFS::new(base, agentfs.fs);
 
     let cwd_str = cwd
@@ -239,6 +249,8 @@ pub async fn run_cmd(
     // The parent needs to write uid_map/gid_map for the child after unshare.
     let (pipe_to_child, pipe_to_parent) = create_sync_pipes()?;
 
+    let mut capture = Capture::start(&capture)?;
+
     // SAFETY: fork() is safe when called from a single-threaded context before
     // the child performs any async-signal-unsafe operations. Our child immediately
     // closes unused fds and calls exec after namespace setup.
@@ -257,6 +269,9 @@ pub async fn run_cmd(
 
         // Close the fd in child - we don't need it (parent keeps it for FUSE)
         drop(cwd_fd);
+        if let Err(e) = capture.redirect_child() {
+            child_exit(&format!("Failed to redirect output for capture: {}", e));
+        }
         run_child(
             &cwd,
             &session.fuse_mountpoint,
@@ -273,6 +288,7 @@ pub async fn run_cmd(
             libc::close(pipe_to_child[0]); // Close read end
             libc::close(pipe_to_parent[1]); // Close write end
         }
+        capture.close_write_ends();
 
         // Wait for child to signal it has called unshare
         if !wait_for_pipe_signal(pipe_to_parent[0]) {
@@ -298,6 +314,9 @@ pub async fn run_cmd(
             &session.fuse_mountpoint,
             fuse_handle,
             &session.db_path,
+            capture,
+            capture_fs,
+            recorder,
         );
     }
 }
@@ -313,6 +332,7 @@ fn run_in_existing_session(
     command: PathBuf,
     args: Vec<String>,
     session_id: &str,
+    recorder: Option<RunRecorder>,
 ) -> Result<()> {
     // SAFETY: getuid/getgid are always safe
     let uid = unsafe { libc::getuid() };
@@ -375,6 +395,7 @@ fn run_in_existing_session(
         // Wait for child to exit (don't unmount or cleanup - the original session owns that)
         // Retry on EINTR (signal interruption)
         let exit_code = wait_for_child(child_pid);
+        finish_recording(recorder, exit_code);
 
         std::process::exit(exit_code);
     }
@@ -885,12 +906,16 @@ fn unmount_fuse(mountpoint: &Path) -> bool {
 /// a lazy unmount (fusermount -uz) which safely detaches the filesystem even
 /// while the FUSE thread may still be processing requests. The thread will
 /// terminate naturally when the mount is gone.
+#[allow(clippy::too_many_arguments)]
 fn run_parent(
     child_pid: i32,
     cwd_fd: std::fs::File,
     fuse_mountpoint: &Path,
     _fuse_handle: std::thread::JoinHandle<anyhow::Result<()>>,
     db_path: &Path,
+    capture: Capture,
+    capture_fs: filesystem::AgentFS,
+    recorder: Option<RunRecorder>,
 ) -> ! {
     // Store child PID and install signal handlers before waiting
     CHILD_PID.store(child_pid, Ordering::SeqCst);
@@ -915,9 +940,16 @@ fn run_parent(
             "You may need to manually unmount with: fusermount -uz {}",
             fuse_mountpoint.display()
         );
+        finish_recording(recorder, exit_code);
         std::process::exit(exit_code);
     }
 
+    // Save captured output now that nothing else is using the delta layer
+    if let Err(e) = capture.save_blocking(capture_fs) {
+        eprintln!("Warning: {:#}", e);
+    }
+    finish_recording(recorder, exit_code);
+
     // Clean up the FUSE mountpoint directory (but keep the delta database)
     if let Err(e) = std::fs::remove_dir_all(fuse_mountpoint) {
         eprintln!(
@@ -937,6 +969,15 @@ fn run_parent(
     std::process::exit(exit_code);
 }
 
+/// Complete the `--record` tool call, if any, with the command's exit code
+fn finish_recording(recorder: Option<RunRecorder>, exit_code: i32) {
+    if let Some(recorder) = recorder {
+        if let Err(e) = recorder.finish_blocking(exit_code) {
+            eprintln!("Warning: Failed to record run: {:#}", e);
+        }
+    }
+}
+
 /// Wait for a path to become a mountpoint
 fn wait_for_mount(path: &Path, timeout: std::time::Duration) -> bool {
     let start = std::time::Instant::now();
@@ -3,20 +3,63 @@
 //! This module provides syscall interception via ptrace for filesystem
 //! virtualization. This is experimental and requires root or CAP_SYS_PTRACE.
 
+use crate::cmd::{
+    capture::Capture, record::RunRecorder, CaptureOptions, DenyOptions, FaultOptions, PtraceOptions,
+};
 use agentfs_sandbox::{
-    init_fd_tables, init_mount_table, init_strace, MountTable, Sandbox, SqliteVfs,
+    strace_summary, write_strace_summary, DenyList, FaultPlan, Sandbox, SandboxConfig, SqliteVfs,
+    StraceFilter, SyscallDelay, SyscallFault,
 };
-use reverie_process::Command;
-use reverie_ptrace::TracerBuilder;
-use std::{path::PathBuf, sync::Arc};
+use agentfs_sdk::filesystem::AgentFS;
+use anyhow::{anyhow, Context, Result};
+use reverie_process::ExitStatus;
+use std::{fs::File, path::PathBuf, sync::Arc};
+
+/// Parse the `--delay` and `--fail` rules into a fault plan
+fn fault_plan(faults: FaultOptions) -> Result<FaultPlan> {
+    let delays = faults
+        .delays
+        .iter()
+        .map(|rule| rule.parse::<SyscallDelay>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("Invalid --delay: {}", e))?;
+    let failures = faults
+        .failures
+        .iter()
+        .map(|rule| rule.parse::<SyscallFault>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow!("Invalid --fail: {}", e))?;
+    Ok(FaultPlan::new(delays, failures, faults.seed))
+}
+
+/// Build the deny list from `--deny-path` and `--deny-glob`
+fn deny_list(deny: DenyOptions) -> Result<DenyList> {
+    DenyList::new(deny.paths, deny.globs)
+        .map_err(|e| anyhow!("Invalid --deny-path or --deny-glob: {}", e))
+}
+
+/// Exit code of the sandboxed command, 128 + the signal number if it was killed
+fn exit_code(status: &ExitStatus) -> i32 {
+    match *status {
+        ExitStatus::Exited(code) => code,
+        ExitStatus::Signaled(signal, _) => 128 + signal as i32,
+    }
+}
 
 /// Run a command using the experimental ptrace-based syscall interception sandbox.
-pub async fn run_cmd(strace: bool, command: PathBuf, args: Vec<String>) {
+pub async fn run_cmd(
+    ptrace: PtraceOptions,
+    capture: CaptureOptions,
+    recorder: Option<RunRecorder>,
+    command: PathBuf,
+    args: Vec<String>,
+) -> Result<()> {
+    let faults = fault_plan(ptrace.faults)?;
+    let deny = deny_list(ptrace.deny)?;
+
     eprintln!("Welcome to AgentFS!");
     eprintln!();
 
-    let mut mount_table = MountTable::new();
-
     // Default mount: agent.db at /agent
     let db_path = PathBuf::from("agent.db");
     let mount_point = PathBuf::from("/agent");
@@ -29,22 +72,51 @@ pub async fn run_cmd(strace: bool, command: PathBuf, args: Vec<String>) {
     );
     eprintln!();
 
-    let vfs = SqliteVfs::new(&db_path, mount_point.clone())
+    // Keep a handle on the filesystem to save captured output into
+    let fs = AgentFS::new(db_path.to_str().context("Invalid database path")?)
         .await
-        .expect("Failed to create AgentFS VFS");
-    mount_table.add_mount(mount_point, Arc::new(vfs));
-
-    init_mount_table(mount_table);
-    init_fd_tables();
-    init_strace(strace);
+        .context("Failed to create AgentFS VFS")?;
+    let vfs = SqliteVfs::from_fs(Arc::new(fs.clone()), mount_point.clone()).with_source(&db_path);
+    let mut capture = Capture::start(&capture)?;
 
-    let mut cmd = Command::new(command);
-    for arg in args {
-        cmd.arg(arg);
+    let strace = ptrace.strace;
+    let mut config = SandboxConfig::new(command)
+        .args(args)
+        .mount_vfs(mount_point, Arc::new(vfs))
+        .strace(strace.enabled)
+        .strace_summary(strace.summary)
+        .strace_filter(StraceFilter::new(strace.filter, strace.exclude))
+        .dump_fds_on_exit(ptrace.dump_fds_on_exit)
+        .print_mounts(ptrace.print_mounts)
+        .std_devices(ptrace.std_devices)
+        .faults(faults)
+        .deny(deny);
+    if let Some(fake_time) = ptrace.fake_time {
+        config = config.fake_time(fake_time.start, fake_time.step);
+    }
+    if let Some(cwd) = ptrace.cwd {
+        config = config.current_dir(cwd);
+    }
+    if let Some(stdout) = capture.take_stdout() {
+        config = config.stdout(File::from(stdout));
+    }
+    if let Some(stderr) = capture.take_stderr() {
+        config = config.stderr(File::from(stderr));
     }
 
-    let tracer = TracerBuilder::<Sandbox>::new(cmd).spawn().await.unwrap();
+    let status = Sandbox::run(config).await?;
+    if let Err(e) = capture.save(&fs).await {
+        eprintln!("Warning: {:#}", e);
+    }
+    if let Some(recorder) = recorder {
+        if let Err(e) = recorder.finish(exit_code(&status)).await {
+            eprintln!("Warning: Failed to record run: {:#}", e);
+        }
+    }
 
-    let (status, _) = tracer.wait().await.unwrap();
+    if strace.summary {
+        let _ = write_strace_summary(&mut std::io::stderr(), &strace_summary());
+    }
+    // Exit the way the command did, so a signal death is reported as such
     status.raise_or_exit()
 }
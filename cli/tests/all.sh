@@ -11,6 +11,7 @@ DIR="$(dirname "$0")"
 
 # 2. ptrace-based sandbox (--experimental-sandbox)
 "$DIR/test-run-experimental-syscalls.sh"
+"$DIR/test-run-exit-code.sh"
 
 # 3. FUSE overlay (agentfs run) - tests copy-on-write
 "$DIR/test-run-syscalls.sh" || true  # Requires user namespaces (may fail in CI)
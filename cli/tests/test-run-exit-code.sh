@@ -0,0 +1,36 @@
+#!/bin/sh
+#
+# Test that agentfs run exits with the sandboxed command's exit code.
+#
+set -e
+
+echo -n "TEST run exit code... "
+
+TEST_DB="agent.db"
+
+rm -f "$TEST_DB" "${TEST_DB}-wal" "${TEST_DB}-shm"
+
+# Expect `agentfs run <args>` to exit with code $1
+expect_exit() {
+    expected="$1"
+    shift
+    set +e
+    output=$(cargo run -- run "$@" 2>&1)
+    code=$?
+    set -e
+    if [ "$code" -ne "$expected" ]; then
+        echo "FAILED: 'run $*' exited with $code, expected $expected"
+        echo "Output was: $output"
+        exit 1
+    fi
+}
+
+expect_exit 0 --experimental-sandbox /bin/sh -c 'exit 0'
+expect_exit 42 --experimental-sandbox /bin/sh -c 'exit 42'
+
+# Commands killed by a signal exit with 128 + the signal number
+expect_exit 143 --experimental-sandbox /bin/sh -c 'kill -TERM $$'
+
+rm -f "$TEST_DB" "${TEST_DB}-wal" "${TEST_DB}-shm"
+
+echo "OK"
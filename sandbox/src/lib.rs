@@ -5,12 +5,20 @@ pub mod syscall;
 pub mod vfs;
 
 #[cfg(target_os = "linux")]
-pub use sandbox::{init_fd_tables, init_mount_table, init_strace, Sandbox};
+pub use sandbox::{
+    init_deny_list, init_dump_fds_on_exit, init_faults, init_fd_tables, init_fd_tables_with,
+    init_mount_table, init_strace, init_strace_filter, init_strace_summary, init_virtual_cwd,
+    strace_summary, write_fd_dump, write_strace_summary, DenyList, FaultPlan, Sandbox,
+    SandboxConfig, StraceFilter, SyscallDelay, SyscallFault, SyscallStats,
+};
 pub use vfs::{
     bind::BindVfs,
-    mount::{MountConfig, MountTable, MountType},
+    fdtable::{FdEntry, FdInfo, FdKind},
+    mount::{MountConfig, MountInfo, MountTable, MountType},
     Vfs, VfsError, VfsResult,
 };
 
 #[cfg(target_os = "linux")]
-pub use vfs::sqlite::SqliteVfs;
+pub use syscall::time::init_fake_time;
+#[cfg(target_os = "linux")]
+pub use vfs::{dev::DevVfs, proc::ProcVfs, sqlite::SqliteVfs};
@@ -0,0 +1,172 @@
+//! Paths hidden from the sandboxed process.
+//!
+//! The deny list is checked before mount translation, so a denied path
+//! disappears whether it lives on the host, under a bind mount or in a
+//! virtual filesystem. Path-based syscalls that name a denied path fail with
+//! `ENOENT`.
+
+use crate::syscall::normalize_path;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Global deny list, set when `--deny-path` or `--deny-glob` is used
+static DENY_LIST: OnceLock<DenyList> = OnceLock::new();
+
+/// Paths and glob patterns the sandboxed process cannot see
+///
+/// A denied path hides everything below it as well. Glob patterns are
+/// matched against whole absolute paths, component by component: `*` and `?`
+/// match within a single component and `**` matches any number of
+/// components, including none.
+#[derive(Debug, Clone, Default)]
+pub struct DenyList {
+    paths: Vec<PathBuf>,
+    globs: Vec<Vec<String>>,
+}
+
+impl DenyList {
+    /// Create a deny list from absolute paths and absolute glob patterns
+    pub fn new(paths: Vec<PathBuf>, globs: Vec<String>) -> Result<Self, String> {
+        let paths = paths
+            .into_iter()
+            .map(|path| {
+                if path.is_absolute() {
+                    Ok(normalize_path(&path))
+                } else {
+                    Err(format!("deny path '{}' must be absolute", path.display()))
+                }
+            })
+            .collect::<Result<_, _>>()?;
+        let globs = globs
+            .into_iter()
+            .map(|glob| {
+                // `**/x` is shorthand for `/**/x`
+                let anchored = if glob.starts_with("**") {
+                    format!("/{}", glob)
+                } else {
+                    glob.clone()
+                };
+                if !anchored.starts_with('/') {
+                    return Err(format!(
+                        "deny glob '{}' must be absolute or start with '**'",
+                        glob
+                    ));
+                }
+                Ok(anchored
+                    .split('/')
+                    .filter(|part| !part.is_empty() && *part != ".")
+                    .map(str::to_string)
+                    .collect())
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { paths, globs })
+    }
+
+    /// Check if the list denies nothing
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty() && self.globs.is_empty()
+    }
+
+    /// Check if an absolute path is denied
+    pub fn is_denied(&self, path: &Path) -> bool {
+        let path = normalize_path(path);
+        if self.paths.iter().any(|denied| path.starts_with(denied)) {
+            return true;
+        }
+        if self.globs.is_empty() {
+            return false;
+        }
+        let components: Vec<&str> = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => name.to_str(),
+                _ => None,
+            })
+            .collect();
+        // A glob also hides everything below a match, like a denied path
+        self.globs
+            .iter()
+            .any(|glob| (0..=components.len()).any(|len| glob_match(glob, &components[..len])))
+    }
+}
+
+/// Match path components against glob components
+fn glob_match(glob: &[String], path: &[&str]) -> bool {
+    match glob.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| glob_match(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                component_match(first.as_bytes(), name.as_bytes()) && glob_match(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Match one path component against a pattern with `*` and `?` wildcards
+fn component_match(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|skip| component_match(rest, &name[skip..])),
+        Some((b'?', rest)) => !name.is_empty() && component_match(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && component_match(rest, &name[1..]),
+    }
+}
+
+/// Initialize the deny list
+///
+/// This must be called before spawning the traced process. Without it, no
+/// paths are hidden.
+pub fn init_deny_list(list: DenyList) {
+    DENY_LIST.set(list).expect("Deny list already initialized");
+}
+
+/// Get the deny list, if any paths are denied
+pub(crate) fn deny_list() -> Option<&'static DenyList> {
+    DENY_LIST.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deny_paths() {
+        let list = DenyList::new(
+            vec!["/etc/shadow".into(), "/home/user/secrets/".into()],
+            vec![],
+        )
+        .unwrap();
+        assert!(list.is_denied(Path::new("/etc/shadow")));
+        assert!(list.is_denied(Path::new("/etc/../etc/shadow")));
+        assert!(list.is_denied(Path::new("/home/user/secrets/key.pem")));
+        assert!(!list.is_denied(Path::new("/etc/shadow-")));
+        assert!(!list.is_denied(Path::new("/etc/passwd")));
+        assert!(!list.is_denied(Path::new("/home/user")));
+
+        assert!(DenyList::new(vec!["etc/shadow".into()], vec![]).is_err());
+    }
+
+    #[test]
+    fn test_deny_globs() {
+        let list = DenyList::new(
+            vec![],
+            vec!["**/.ssh/**".to_string(), "/tmp/*.key".to_string()],
+        )
+        .unwrap();
+        assert!(list.is_denied(Path::new("/home/user/.ssh")));
+        assert!(list.is_denied(Path::new("/home/user/.ssh/id_ed25519")));
+        assert!(list.is_denied(Path::new("/.ssh/config")));
+        assert!(list.is_denied(Path::new("/tmp/server.key")));
+        // Everything below a match is hidden too
+        assert!(list.is_denied(Path::new("/tmp/a.key/inner")));
+        assert!(!list.is_denied(Path::new("/tmp/dir/server.key")));
+        assert!(!list.is_denied(Path::new("/home/user/.sshrc")));
+        assert!(!list.is_denied(Path::new("/tmp/server.pem")));
+
+        assert!(DenyList::new(vec![], vec!["*.key".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,314 @@
+//! Syscall fault injection for chaos testing.
+//!
+//! Rules are keyed by syscall name, like the strace filter, and are applied
+//! before a syscall is dispatched: delays add latency, failures make the
+//! syscall return an error without running it.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    OnceLock,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Global fault plan, set when `--delay` or `--fail` is used
+static FAULT_PLAN: OnceLock<FaultPlan> = OnceLock::new();
+
+/// Error numbers that can be injected by name
+const ERRNO_NAMES: &[(&str, i32)] = &[
+    ("EPERM", libc::EPERM),
+    ("ENOENT", libc::ENOENT),
+    ("EINTR", libc::EINTR),
+    ("EIO", libc::EIO),
+    ("EBADF", libc::EBADF),
+    ("EAGAIN", libc::EAGAIN),
+    ("ENOMEM", libc::ENOMEM),
+    ("EACCES", libc::EACCES),
+    ("EBUSY", libc::EBUSY),
+    ("EEXIST", libc::EEXIST),
+    ("ENOTDIR", libc::ENOTDIR),
+    ("EISDIR", libc::EISDIR),
+    ("EINVAL", libc::EINVAL),
+    ("EMFILE", libc::EMFILE),
+    ("EFBIG", libc::EFBIG),
+    ("ENOSPC", libc::ENOSPC),
+    ("EROFS", libc::EROFS),
+    ("EPIPE", libc::EPIPE),
+    ("ENAMETOOLONG", libc::ENAMETOOLONG),
+    ("ENOTEMPTY", libc::ENOTEMPTY),
+    ("ECONNRESET", libc::ECONNRESET),
+    ("ETIMEDOUT", libc::ETIMEDOUT),
+    ("ECONNREFUSED", libc::ECONNREFUSED),
+    ("EDQUOT", libc::EDQUOT),
+];
+
+/// Latency added to every call of a syscall, parsed from `SYSCALL=DURATION`
+///
+/// The duration takes a `us`, `ms` or `s` suffix; a bare number is in
+/// milliseconds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyscallDelay {
+    pub syscall: String,
+    pub delay: Duration,
+}
+
+impl FromStr for SyscallDelay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (syscall, delay) = split_rule(s, "SYSCALL=DURATION")?;
+        Ok(Self {
+            syscall,
+            delay: parse_duration(delay)?,
+        })
+    }
+}
+
+/// Synthetic error returned instead of running a syscall, parsed from
+/// `SYSCALL=ERRNO[:PROBABILITY]`
+///
+/// The errno is a name such as `EIO` or a number. The probability is between
+/// 0 and 1 and defaults to 1, i.e. every call fails.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyscallFault {
+    pub syscall: String,
+    pub errno: i32,
+    pub probability: f64,
+}
+
+impl FromStr for SyscallFault {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (syscall, rest) = split_rule(s, "SYSCALL=ERRNO[:PROBABILITY]")?;
+        let (errno, probability) = match rest.split_once(':') {
+            Some((errno, probability)) => {
+                let probability: f64 = probability
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("invalid probability '{}'", probability))?;
+                if !(0.0..=1.0).contains(&probability) {
+                    return Err(format!(
+                        "probability {} is not between 0 and 1",
+                        probability
+                    ));
+                }
+                (errno, probability)
+            }
+            None => (rest, 1.0),
+        };
+        Ok(Self {
+            syscall,
+            errno: parse_errno(errno)?,
+            probability,
+        })
+    }
+}
+
+/// Split a `name=value` rule, normalizing the syscall name
+fn split_rule<'a>(s: &'a str, syntax: &str) -> Result<(String, &'a str), String> {
+    let (syscall, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid rule '{}', expected {}", s, syntax))?;
+    let syscall = syscall.trim().to_ascii_lowercase();
+    if syscall.is_empty() {
+        return Err(format!("missing syscall name in '{}'", s));
+    }
+    Ok((syscall, value.trim()))
+}
+
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(idx) => s.split_at(idx),
+        None => (s, "ms"),
+    };
+    let value: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration '{}'", s))?;
+    match unit {
+        "us" => Ok(Duration::from_micros(value)),
+        "ms" => Ok(Duration::from_millis(value)),
+        "s" => Ok(Duration::from_secs(value)),
+        _ => Err(format!("invalid duration '{}', expected e.g. 5ms", s)),
+    }
+}
+
+fn parse_errno(s: &str) -> Result<i32, String> {
+    let s = s.trim();
+    if let Ok(errno) = s.parse::<i32>() {
+        if errno > 0 {
+            return Ok(errno);
+        }
+    }
+    ERRNO_NAMES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(s))
+        .map(|(_, errno)| *errno)
+        .ok_or_else(|| format!("unknown errno '{}'", s))
+}
+
+/// Symbolic name of an error number, if it is one of the known names
+pub(crate) fn errno_name(errno: i32) -> Option<&'static str> {
+    ERRNO_NAMES
+        .iter()
+        .find(|(_, value)| *value == errno)
+        .map(|(name, _)| *name)
+}
+
+/// A failure rule together with the number of times it has been consulted
+#[derive(Debug)]
+struct FaultRule {
+    errno: i32,
+    probability: f64,
+    draws: AtomicU64,
+}
+
+/// Delays and failures to inject into the sandboxed process's syscalls
+///
+/// Whether a call fails depends only on the seed, the syscall name and how
+/// many calls of that syscall came before it, so a run with a fixed seed
+/// fails the same calls every time.
+#[derive(Debug)]
+pub struct FaultPlan {
+    delays: HashMap<String, Duration>,
+    faults: HashMap<String, FaultRule>,
+    seed: u64,
+}
+
+impl FaultPlan {
+    /// Create a plan from the given rules
+    ///
+    /// Later rules for the same syscall replace earlier ones. Without a seed,
+    /// one is derived from the current time.
+    pub fn new(delays: Vec<SyscallDelay>, faults: Vec<SyscallFault>, seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since| since.as_nanos() as u64)
+                .unwrap_or_default()
+        });
+        Self {
+            delays: delays
+                .into_iter()
+                .map(|rule| (rule.syscall, rule.delay))
+                .collect(),
+            faults: faults
+                .into_iter()
+                .map(|rule| {
+                    let fault = FaultRule {
+                        errno: rule.errno,
+                        probability: rule.probability,
+                        draws: AtomicU64::new(0),
+                    };
+                    (rule.syscall, fault)
+                })
+                .collect(),
+            seed,
+        }
+    }
+
+    /// Check if the plan injects anything at all
+    pub fn is_empty(&self) -> bool {
+        self.delays.is_empty() && self.faults.is_empty()
+    }
+
+    /// Latency to add before running the syscall
+    pub(crate) fn delay(&self, syscall: &str) -> Option<Duration> {
+        self.delays.get(syscall).copied()
+    }
+
+    /// Error to fail this call of the syscall with, if any
+    pub(crate) fn injected_errno(&self, syscall: &str) -> Option<i32> {
+        let rule = self.faults.get(syscall)?;
+        let draw = rule.draws.fetch_add(1, Ordering::Relaxed);
+        let sample = mix(self.seed ^ name_hash(syscall) ^ mix(draw));
+        // Top 53 bits give a uniform float in [0, 1)
+        let uniform = (sample >> 11) as f64 / (1u64 << 53) as f64;
+        (uniform < rule.probability).then_some(rule.errno)
+    }
+}
+
+/// SplitMix64 finalizer
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// FNV-1a hash of a syscall name, stable across runs
+fn name_hash(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Initialize fault injection
+///
+/// This must be called before spawning the traced process. Without it, no
+/// faults are injected.
+pub fn init_faults(plan: FaultPlan) {
+    FAULT_PLAN
+        .set(plan)
+        .expect("Fault plan already initialized");
+}
+
+/// Get the fault plan, if fault injection is enabled
+pub(crate) fn fault_plan() -> Option<&'static FaultPlan> {
+    FAULT_PLAN.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rules() {
+        let delay: SyscallDelay = "open=5ms".parse().unwrap();
+        assert_eq!(delay.syscall, "open");
+        assert_eq!(delay.delay, Duration::from_millis(5));
+        assert_eq!(
+            "Read=250us".parse::<SyscallDelay>().unwrap().delay,
+            Duration::from_micros(250)
+        );
+        assert_eq!(
+            "read=2".parse::<SyscallDelay>().unwrap().delay,
+            Duration::from_millis(2)
+        );
+        assert!("open".parse::<SyscallDelay>().is_err());
+        assert!("open=5min".parse::<SyscallDelay>().is_err());
+
+        let fault: SyscallFault = "read=EIO:0.01".parse().unwrap();
+        assert_eq!(fault.syscall, "read");
+        assert_eq!(fault.errno, libc::EIO);
+        assert_eq!(fault.probability, 0.01);
+        let fault: SyscallFault = "write=28".parse().unwrap();
+        assert_eq!(fault.errno, libc::ENOSPC);
+        assert_eq!(fault.probability, 1.0);
+        assert!("read=EWHAT".parse::<SyscallFault>().is_err());
+        assert!("read=EIO:1.5".parse::<SyscallFault>().is_err());
+    }
+
+    #[test]
+    fn test_injection_is_deterministic_with_seed() {
+        let plan = || {
+            let fault = "read=EIO:0.5".parse().unwrap();
+            FaultPlan::new(Vec::new(), vec![fault], Some(42))
+        };
+        let draws = |plan: &FaultPlan| -> Vec<Option<i32>> {
+            (0..64).map(|_| plan.injected_errno("read")).collect()
+        };
+
+        let first = draws(&plan());
+        assert_eq!(first, draws(&plan()));
+        // Roughly half of the calls fail, and only with the configured errno
+        let failures = first.iter().flatten().count();
+        assert!((16..48).contains(&failures), "{} failures", failures);
+        assert!(first.iter().flatten().all(|errno| *errno == libc::EIO));
+
+        // Syscalls without a rule are left alone
+        assert_eq!(plan().injected_errno("write"), None);
+        assert_eq!(plan().delay("read"), None);
+    }
+}
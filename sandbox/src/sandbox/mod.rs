@@ -1,23 +1,114 @@
+mod deny;
+mod fault;
+mod run;
+mod strace;
+mod summary;
+
+pub(crate) use deny::deny_list;
+pub use deny::{init_deny_list, DenyList};
+pub use fault::{init_faults, FaultPlan, SyscallDelay, SyscallFault};
+pub use run::SandboxConfig;
+pub use summary::{init_strace_summary, strace_summary, write_strace_summary, SyscallStats};
+
+use strace::{format_result, format_syscall, is_error};
+
 use crate::{
     syscall,
-    vfs::{fdtable::FdTable, mount::MountTable},
+    vfs::{
+        fdtable::{FdEntry, FdInfo, FdTable},
+        lock::LockTable,
+        mount::MountTable,
+        VfsResult,
+    },
 };
-use reverie::{syscalls::Syscall, Error, Guest, Tool};
-use std::collections::HashMap;
+use reverie::{
+    syscalls::{Syscall, SyscallInfo},
+    Error, ExitStatus, GlobalRPC, Guest, Pid, Tool,
+};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::PathBuf;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Mutex, OnceLock,
 };
+use std::time::{Duration, Instant};
 
 /// Global mount table shared across all threads
 static MOUNT_TABLE: OnceLock<MountTable> = OnceLock::new();
 
 /// Global FD tables, one per process (keyed by pid)
-static FD_TABLES: OnceLock<Mutex<HashMap<i32, FdTable>>> = OnceLock::new();
+static FD_TABLES: OnceLock<FdTables> = OnceLock::new();
+
+/// Per-process FD tables and the table a process without one starts from
+struct FdTables {
+    tables: Mutex<HashMap<i32, FdTable>>,
+    initial: FdTable,
+}
+
+/// Global virtual working directories, one per process (keyed by pid)
+///
+/// A process only has an entry while its working directory is inside a mount.
+static VIRTUAL_CWDS: OnceLock<Mutex<HashMap<i32, PathBuf>>> = OnceLock::new();
+
+/// Global advisory locks held on virtual files
+static LOCK_TABLE: OnceLock<LockTable> = OnceLock::new();
+
+/// Virtual working directory the traced process starts in, until its first syscall
+static INITIAL_CWD: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Set while `INITIAL_CWD` still has to be applied
+static INITIAL_CWD_PENDING: AtomicBool = AtomicBool::new(false);
 
 /// Global flag to enable strace-like output
 static STRACE_ENABLED: AtomicBool = AtomicBool::new(false);
 
+/// Global flag to print each process's FD table when it exits
+static DUMP_FDS_ON_EXIT: AtomicBool = AtomicBool::new(false);
+
+/// Global filter selecting which syscalls strace-like output is emitted for
+static STRACE_FILTER: OnceLock<StraceFilter> = OnceLock::new();
+
+/// Selects which syscalls are logged and summarized in strace mode, by name
+///
+/// Names are matched exactly against the kernel syscall name (e.g. `openat`,
+/// not `open`, on architectures without a plain `open`).
+#[derive(Debug, Clone, Default)]
+pub struct StraceFilter {
+    include: Option<HashSet<String>>,
+    exclude: HashSet<String>,
+}
+
+impl StraceFilter {
+    /// Create a filter that logs only `include` (all syscalls if empty),
+    /// minus anything in `exclude`
+    pub fn new(include: Vec<String>, exclude: Vec<String>) -> Self {
+        let normalize = |names: Vec<String>| -> HashSet<String> {
+            names
+                .into_iter()
+                .map(|name| name.trim().to_ascii_lowercase())
+                .filter(|name| !name.is_empty())
+                .collect()
+        };
+        let include = normalize(include);
+        Self {
+            include: (!include.is_empty()).then_some(include),
+            exclude: normalize(exclude),
+        }
+    }
+
+    /// Check if a syscall with the given name should be logged
+    pub fn matches(&self, name: &str) -> bool {
+        if self.exclude.contains(name) {
+            return false;
+        }
+        match &self.include {
+            Some(include) => include.contains(name),
+            None => true,
+        }
+    }
+}
+
 /// Initialize the global mount table
 ///
 /// This must be called before spawning the traced process.
@@ -36,9 +127,28 @@ fn get_mount_table() -> &'static MountTable {
 ///
 /// This must be called before spawning the traced process.
 pub fn init_fd_tables() {
-    FD_TABLES
-        .set(Mutex::new(HashMap::new()))
-        .expect("FD tables already initialized");
+    set_fd_tables(FdTable::new());
+}
+
+/// Initialize the global FD tables with extra entries at fixed virtual FDs
+///
+/// This must be called before spawning the traced process, instead of
+/// [`init_fd_tables`]. The traced process starts with the standard FDs plus
+/// `preopened`; its children inherit them through fork as usual. Passthrough
+/// entries must name kernel FDs the traced process actually inherits.
+pub fn init_fd_tables_with(preopened: Vec<(i32, FdEntry)>) -> VfsResult<()> {
+    set_fd_tables(FdTable::with_preopened(preopened)?);
+    Ok(())
+}
+
+fn set_fd_tables(initial: FdTable) {
+    let tables = FdTables {
+        tables: Mutex::new(HashMap::new()),
+        initial,
+    };
+    if FD_TABLES.set(tables).is_err() {
+        panic!("FD tables already initialized");
+    }
 }
 
 /// Initialize strace mode
@@ -48,36 +158,146 @@ pub fn init_strace(enabled: bool) {
     STRACE_ENABLED.store(enabled, Ordering::Relaxed);
 }
 
+/// Initialize the strace syscall filter
+///
+/// This must be called before spawning the traced process. Without it, every
+/// syscall is logged and summarized.
+pub fn init_strace_filter(filter: StraceFilter) {
+    STRACE_FILTER
+        .set(filter)
+        .expect("Strace filter already initialized");
+}
+
+/// Initialize the virtual working directory of the traced process
+///
+/// This must be called before spawning the traced process. `cwd` is a path
+/// inside a mount; it becomes the working directory of the first process to
+/// make a syscall, which is the traced process itself, and is inherited by
+/// its children as if it had called `chdir`.
+pub fn init_virtual_cwd(cwd: PathBuf) {
+    *INITIAL_CWD.lock().unwrap() = Some(cwd);
+    INITIAL_CWD_PENDING.store(true, Ordering::SeqCst);
+}
+
+/// Apply the initial virtual working directory to `pid`, if still pending
+fn claim_initial_cwd(pid: i32) {
+    if INITIAL_CWD_PENDING.swap(false, Ordering::SeqCst) {
+        let cwd = INITIAL_CWD.lock().unwrap().take();
+        set_virtual_cwd(pid, cwd);
+    }
+}
+
+/// Initialize FD table dumps on exit
+///
+/// This must be called before spawning the traced process. When enabled,
+/// every process's FD table is printed to stderr as it calls `exit_group`.
+pub fn init_dump_fds_on_exit(enabled: bool) {
+    DUMP_FDS_ON_EXIT.store(enabled, Ordering::Relaxed);
+}
+
+/// Write an FD table snapshot in a human-readable format
+pub fn write_fd_dump<W: Write>(out: &mut W, pid: i32, fds: &[FdInfo]) -> io::Result<()> {
+    writeln!(out, "[{}] fd table at exit ({} open):", pid, fds.len())?;
+    writeln!(
+        out,
+        "{:>6}  {:<11}  {:>6}  {:>10}  path",
+        "fd", "kind", "kernel", "flags"
+    )?;
+    for fd in fds {
+        let kernel_fd = fd.kernel_fd.map_or("-".to_string(), |fd| fd.to_string());
+        let path = fd
+            .path
+            .as_ref()
+            .map_or("-".into(), |path| path.display().to_string());
+        writeln!(
+            out,
+            "{:>6}  {:<11}  {:>6}  {:>#10o}  {}",
+            fd.vfd,
+            fd.kind.as_str(),
+            kernel_fd,
+            fd.flags,
+            path
+        )?;
+    }
+    Ok(())
+}
+
 /// Check if strace is enabled
 fn is_strace_enabled() -> bool {
     STRACE_ENABLED.load(Ordering::Relaxed)
 }
 
+/// Check if a syscall passes the strace filter
+fn is_selected(syscall: &Syscall) -> bool {
+    STRACE_FILTER
+        .get()
+        .is_none_or(|filter| filter.matches(syscall.name()))
+}
+
+/// Check if a passthrough syscall must be tail-injected
+///
+/// These never return to the tracer (or replace the process image), so they
+/// cannot be timed with a regular inject in summary mode.
+fn needs_tail_inject(syscall: &Syscall) -> bool {
+    matches!(
+        syscall.name(),
+        "exit"
+            | "exit_group"
+            | "execve"
+            | "execveat"
+            | "clone"
+            | "clone3"
+            | "fork"
+            | "vfork"
+            | "rt_sigreturn"
+    )
+}
+
 /// Get or create an FD table for a specific process
 fn get_fd_table(pid: i32) -> FdTable {
-    let tables = FD_TABLES.get().expect("FD tables not initialized");
-    let mut tables = tables.lock().unwrap();
+    let fd_tables = FD_TABLES.get().expect("FD tables not initialized");
+    let mut tables = fd_tables.tables.lock().unwrap();
 
-    tables.entry(pid).or_default().clone()
+    tables
+        .entry(pid)
+        .or_insert_with(|| fd_tables.initial.deep_clone())
+        .clone()
 }
 
 /// Insert an FD table for a specific process (used for fork/clone)
 pub(crate) fn insert_fd_table(pid: i32, fd_table: FdTable) {
-    let tables = FD_TABLES.get().expect("FD tables not initialized");
-    let mut tables = tables.lock().unwrap();
+    let fd_tables = FD_TABLES.get().expect("FD tables not initialized");
+    let mut tables = fd_tables.tables.lock().unwrap();
 
     tables.insert(pid, fd_table);
 }
 
-/// Format a syscall for strace-like output
-fn format_syscall(syscall: &Syscall) -> String {
-    // Using the Debug implementation as a starting point
-    format!("{:?}", syscall)
+/// Get the virtual working directory of a process, if it is inside a mount
+pub(crate) fn virtual_cwd(pid: i32) -> Option<PathBuf> {
+    let cwds = VIRTUAL_CWDS.get_or_init(|| Mutex::new(HashMap::new()));
+    cwds.lock().unwrap().get(&pid).cloned()
+}
+
+/// Set (or clear) the virtual working directory of a process
+pub(crate) fn set_virtual_cwd(pid: i32, cwd: Option<PathBuf>) {
+    let cwds = VIRTUAL_CWDS.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut cwds = cwds.lock().unwrap();
+
+    match cwd {
+        Some(cwd) => cwds.insert(pid, cwd),
+        None => cwds.remove(&pid),
+    };
+}
+
+/// Get the advisory locks held on virtual files
+pub(crate) fn lock_table() -> &'static LockTable {
+    LOCK_TABLE.get_or_init(LockTable::new)
 }
 
-/// Format a syscall result for strace-like output
-fn format_result(value: i64) -> String {
-    format!("{}", value)
+/// Release what a process held once it is gone
+fn release_process_state(pid: i32) {
+    // Record locks belong to the process, so they go away with it
+    lock_table().release_process(pid);
 }
 
 /// The Sandbox tool
@@ -99,32 +319,159 @@ impl Tool for Sandbox {
     ) -> Result<i64, Error> {
         let mount_table = get_mount_table();
         let pid = guest.pid().as_raw();
+        claim_initial_cwd(pid);
         let fd_table = get_fd_table(pid);
+        let selected = is_selected(&syscall);
+        let traced = selected && is_strace_enabled();
+        let summarized = selected && summary::is_summary_enabled();
+        let name = syscall.name();
+        let started = Instant::now();
 
-        if is_strace_enabled() {
+        if traced {
             eprintln!("[{}] {}", pid, format_syscall(&syscall));
         }
 
+        if name == "exit_group" {
+            if DUMP_FDS_ON_EXIT.load(Ordering::Relaxed) {
+                let _ = write_fd_dump(&mut io::stderr(), pid, &fd_table.snapshot_debug());
+            }
+            release_process_state(pid);
+        }
+
+        if let Some(plan) = fault::fault_plan() {
+            if let Some(delay) = plan.delay(name) {
+                tokio::time::sleep(delay).await;
+            }
+            // Syscalls that never return can't be failed meaningfully
+            if !needs_tail_inject(&syscall) {
+                if let Some(errno) = plan.injected_errno(name) {
+                    if traced {
+                        eprintln!("[{}] = {} (injected)", pid, format_result(-errno as i64));
+                    }
+                    if summarized {
+                        summary::record_syscall(name, started.elapsed(), true);
+                    }
+                    return Ok(-errno as i64);
+                }
+            }
+        }
+
         let result = match syscall::dispatch_syscall(guest, syscall, mount_table, &fd_table).await {
             Ok(syscall::SyscallResult::Value(value)) => {
-                if is_strace_enabled() {
+                if traced {
                     eprintln!("[{}] = {}", pid, format_result(value));
                 }
+                if summarized {
+                    // Emulated syscalls report failures as negated errnos
+                    summary::record_syscall(name, started.elapsed(), is_error(value));
+                }
                 Ok(value)
             }
-            Ok(syscall::SyscallResult::Syscall(syscall)) => guest.tail_inject(syscall).await,
+            Ok(syscall::SyscallResult::Syscall(syscall)) => {
+                if summarized && !needs_tail_inject(&syscall) {
+                    let result = guest.inject(syscall).await.map_err(Error::from);
+                    summary::record_syscall(name, started.elapsed(), result.is_err());
+                    result
+                } else {
+                    if summarized {
+                        summary::record_syscall(name, Duration::ZERO, false);
+                    }
+                    guest.tail_inject(syscall).await
+                }
+            }
             Err(e) => {
-                if is_strace_enabled() {
+                if traced {
                     if let Error::Errno(errno) = &e {
                         eprintln!("[{}] = -1 {}", pid, errno);
                     } else {
                         eprintln!("[{}] = error: {:?}", pid, e);
                     }
                 }
+                if summarized {
+                    summary::record_syscall(name, started.elapsed(), true);
+                }
                 Err(e)
             }
         };
 
         result
     }
+
+    async fn on_exit_process<G: GlobalRPC<Self::GlobalState>>(
+        self,
+        pid: Pid,
+        _global_state: &G,
+        _exit_status: ExitStatus,
+    ) -> Result<(), Error> {
+        // Processes killed by a signal, or whose last thread calls `exit`,
+        // never go through `exit_group`
+        release_process_state(pid.as_raw());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_fd_dump() {
+        let fds = FdTable::new().snapshot_debug();
+        let mut out = Vec::new();
+        write_fd_dump(&mut out, 42, &fds).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines[0], "[42] fd table at exit (3 open):");
+        assert_eq!(lines.len(), 5);
+        assert!(lines[2].trim_start().starts_with("0  passthrough"));
+        assert!(lines[4].ends_with("  -"));
+    }
+
+    #[tokio::test]
+    async fn test_exited_process_releases_locks() {
+        use crate::vfs::lock::{LockKind, LockOwner, LockRange};
+
+        let file = (u64::MAX, 419);
+        let (holder, waiter) = (LockOwner::Process(-419), LockOwner::Process(-420));
+        lock_table()
+            .try_lock(file, holder, LockKind::Exclusive, LockRange::WHOLE, None)
+            .unwrap();
+
+        let waiting = tokio::spawn(async move {
+            lock_table()
+                .lock(file, waiter, LockKind::Exclusive, LockRange::WHOLE, None)
+                .await
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+
+        // However the holder died, its locks go with it
+        release_process_state(-419);
+        waiting.await.unwrap();
+        release_process_state(-420);
+    }
+
+    #[test]
+    fn test_strace_filter_matches() {
+        let all = StraceFilter::default();
+        assert!(all.matches("openat"));
+
+        let include =
+            StraceFilter::new(vec!["openat".to_string(), " Read ".to_string()], Vec::new());
+        assert!(include.matches("openat"));
+        assert!(include.matches("read"));
+        assert!(!include.matches("mmap"));
+
+        let exclude = StraceFilter::new(Vec::new(), vec!["mmap".to_string()]);
+        assert!(exclude.matches("openat"));
+        assert!(!exclude.matches("mmap"));
+
+        let both = StraceFilter::new(
+            vec!["openat".to_string(), "close".to_string()],
+            vec!["close".to_string()],
+        );
+        assert!(both.matches("openat"));
+        assert!(!both.matches("close"));
+    }
 }
@@ -0,0 +1,605 @@
+//! Programmatic entry point for running a command in the sandbox.
+
+use super::{
+    init_deny_list, init_dump_fds_on_exit, init_faults, init_fd_tables_with, init_mount_table,
+    init_strace, init_strace_filter, init_strace_summary, init_virtual_cwd, DenyList, FaultPlan,
+    Sandbox, StraceFilter,
+};
+use crate::{
+    syscall::{normalize_path, time::init_fake_time},
+    vfs::{
+        bind::BindVfs,
+        dev::DevVfs,
+        fdtable::FdEntry,
+        mount::{MountConfig, MountTable, MountType},
+        proc::ProcVfs,
+        sqlite::SqliteVfs,
+        Vfs,
+    },
+};
+use anyhow::{bail, Context, Result};
+use reverie_process::{Command, ExitStatus, Stdio};
+use reverie_ptrace::TracerBuilder;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::{Duration, SystemTime};
+
+/// Set once a sandbox has been started, since its state is process-global
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// A filesystem to mount in the sandbox
+enum Mount {
+    /// Built from a mount specification when the sandbox starts
+    Config(MountConfig),
+    /// An already constructed VFS
+    Vfs(PathBuf, Arc<dyn Vfs>),
+}
+
+/// Everything needed to run a command in the sandbox
+///
+/// ```ignore
+/// let config = SandboxConfig::new("/bin/sh")
+///     .arg("-c")
+///     .arg("ls /agent")
+///     .mount(MountConfig::sqlite("agent.db", "/agent"))
+///     .env_clear()
+///     .env("PATH", "/usr/bin:/bin");
+/// let status = Sandbox::run(config).await?;
+/// ```
+pub struct SandboxConfig {
+    command: PathBuf,
+    args: Vec<OsString>,
+    mounts: Vec<Mount>,
+    strace: bool,
+    strace_summary: bool,
+    strace_filter: StraceFilter,
+    dump_fds_on_exit: bool,
+    print_mounts: bool,
+    fake_time: Option<(SystemTime, Duration)>,
+    faults: Option<FaultPlan>,
+    deny: Option<DenyList>,
+    symlink_root: Option<PathBuf>,
+    preopened: Vec<(i32, FdEntry)>,
+    env_clear: bool,
+    env_remove: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    current_dir: Option<PathBuf>,
+    stdout: Option<Stdio>,
+    stderr: Option<Stdio>,
+}
+
+impl SandboxConfig {
+    /// Create a configuration that runs `command` with no mounts
+    pub fn new(command: impl Into<PathBuf>) -> Self {
+        Self {
+            command: command.into(),
+            args: Vec::new(),
+            mounts: Vec::new(),
+            strace: false,
+            strace_summary: false,
+            strace_filter: StraceFilter::default(),
+            dump_fds_on_exit: false,
+            print_mounts: false,
+            fake_time: None,
+            faults: None,
+            deny: None,
+            symlink_root: None,
+            preopened: Vec::new(),
+            env_clear: false,
+            env_remove: Vec::new(),
+            envs: Vec::new(),
+            current_dir: None,
+            stdout: None,
+            stderr: None,
+        }
+    }
+
+    /// Append an argument to the command
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Append several arguments to the command
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Mount a filesystem described by a mount specification
+    ///
+    /// Sqlite databases are opened when the sandbox starts. Each sqlite mount
+    /// gets its own connection and device number, so several can be mounted
+    /// side by side, e.g. `/cache` from one agent and `/work` from another.
+    pub fn mount(mut self, mount: MountConfig) -> Self {
+        self.mounts.push(Mount::Config(mount));
+        self
+    }
+
+    /// Mount an already constructed VFS at `path`
+    pub fn mount_vfs(mut self, path: impl Into<PathBuf>, vfs: Arc<dyn Vfs>) -> Self {
+        self.mounts.push(Mount::Vfs(path.into(), vfs));
+        self
+    }
+
+    /// Mount a minimal virtual `/dev` and a skeletal `/proc`
+    ///
+    /// Many programs need `/dev/null` or `/proc/self` to run at all, which
+    /// are missing when a virtual filesystem is mounted over the root.
+    pub fn std_devices(mut self, enabled: bool) -> Self {
+        if enabled {
+            self.mounts.push(Mount::Config(MountConfig::dev("/dev")));
+            self.mounts.push(Mount::Config(MountConfig::proc("/proc")));
+        }
+        self
+    }
+
+    /// Print every intercepted syscall to stderr
+    pub fn strace(mut self, enabled: bool) -> Self {
+        self.strace = enabled;
+        self
+    }
+
+    /// Collect per-syscall counts and timings
+    ///
+    /// The summary is not printed; read it with [`super::strace_summary`]
+    /// once the command has exited.
+    pub fn strace_summary(mut self, enabled: bool) -> Self {
+        self.strace_summary = enabled;
+        self
+    }
+
+    /// Restrict strace output and the summary to the selected syscalls
+    pub fn strace_filter(mut self, filter: StraceFilter) -> Self {
+        self.strace_filter = filter;
+        self
+    }
+
+    /// Print each process's FD table to stderr when it exits
+    pub fn dump_fds_on_exit(mut self, enabled: bool) -> Self {
+        self.dump_fds_on_exit = enabled;
+        self
+    }
+
+    /// Print the resolved mount table to stderr before the command starts
+    ///
+    /// Each line is a [`MountInfo`](crate::vfs::mount::MountInfo), in the
+    /// order paths are matched against the mounts.
+    pub fn print_mounts(mut self, enabled: bool) -> Self {
+        self.print_mounts = enabled;
+        self
+    }
+
+    /// Replace the clocks with a fake one starting at `start` and advancing
+    /// by `step` on every read
+    pub fn fake_time(mut self, start: SystemTime, step: Duration) -> Self {
+        self.fake_time = Some((start, step));
+        self
+    }
+
+    /// Inject syscall delays and failures
+    pub fn faults(mut self, plan: FaultPlan) -> Self {
+        self.faults = Some(plan);
+        self
+    }
+
+    /// Hide paths from the command
+    pub fn deny(mut self, list: DenyList) -> Self {
+        self.deny = Some(list);
+        self
+    }
+
+    /// Refuse bind mounts whose source escapes `root`, and keep symlinks in
+    /// bind mounts from leading out of them
+    ///
+    /// Bind sources are canonicalized, so a source that is (or goes through)
+    /// a symlink to somewhere outside `root` fails the sandbox start. Inside
+    /// each bind mount, symlinks are resolved against the sandbox view; see
+    /// [`BindVfs::with_confined_symlinks`]. Mounts added with
+    /// [`SandboxConfig::mount_vfs`] are used as they are.
+    pub fn no_symlink_escape(mut self, root: impl Into<PathBuf>) -> Self {
+        self.symlink_root = Some(root.into());
+        self
+    }
+
+    /// Start the command with `entry` already open at virtual FD `vfd`
+    ///
+    /// A passthrough entry's kernel FD must be inherited by the command, i.e.
+    /// open without `O_CLOEXEC` in this process.
+    pub fn preopen(mut self, vfd: i32, entry: FdEntry) -> Self {
+        self.preopened.push((vfd, entry));
+        self
+    }
+
+    /// Start the command with an empty environment
+    ///
+    /// Variables set with [`Self::env`] are still passed.
+    pub fn env_clear(mut self) -> Self {
+        self.env_clear = true;
+        self
+    }
+
+    /// Remove a variable from the inherited environment
+    pub fn env_remove(mut self, key: impl Into<OsString>) -> Self {
+        self.env_remove.push(key.into());
+        self
+    }
+
+    /// Set an environment variable for the command
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+
+    /// Set the working directory of the command
+    ///
+    /// `dir` is an absolute path as the command sees it. Inside a mount the
+    /// command starts in that directory of the mount, and relative paths
+    /// resolve from it even if the mount is virtual and has no host directory
+    /// to change into. `Sandbox::run` fails if `dir` is not a directory.
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Send the command's stdout somewhere other than this process's stdout
+    pub fn stdout(mut self, stdout: impl Into<Stdio>) -> Self {
+        self.stdout = Some(stdout.into());
+        self
+    }
+
+    /// Send the command's stderr somewhere other than this process's stderr
+    pub fn stderr(mut self, stderr: impl Into<Stdio>) -> Self {
+        self.stderr = Some(stderr.into());
+        self
+    }
+
+    /// Build the mount table, opening every configured filesystem
+    async fn mount_table(mounts: Vec<Mount>, symlink_root: Option<&Path>) -> Result<MountTable> {
+        let symlink_root = symlink_root
+            .map(|root| {
+                std::fs::canonicalize(root)
+                    .with_context(|| format!("Failed to canonicalize {}", root.display()))
+            })
+            .transpose()?;
+        let mut table = MountTable::new();
+        for mount in mounts {
+            let (path, vfs): (PathBuf, Arc<dyn Vfs>) = match mount {
+                Mount::Vfs(path, vfs) => (path, vfs),
+                Mount::Config(config) => {
+                    let read_only = config.read_only;
+                    let vfs: Arc<dyn Vfs> = match config.mount_type {
+                        MountType::Bind { src } => {
+                            let real = std::fs::canonicalize(&src).with_context(|| {
+                                format!("Failed to canonicalize {}", src.display())
+                            })?;
+                            if let Some(root) = &symlink_root {
+                                if !real.starts_with(root) {
+                                    bail!(
+                                        "Bind source '{}' resolves to '{}', outside '{}'",
+                                        src.display(),
+                                        real.display(),
+                                        root.display()
+                                    );
+                                }
+                            }
+                            Arc::new(
+                                BindVfs::new(real, config.dst.clone())
+                                    .with_owner(config.uid_map, config.gid_map)
+                                    .with_confined_symlinks(symlink_root.is_some())
+                                    .with_read_only(read_only),
+                            )
+                        }
+                        MountType::Sqlite { src } => Arc::new(
+                            SqliteVfs::new(&src, config.dst.clone())
+                                .await
+                                .with_context(|| format!("Failed to open {}", src.display()))?
+                                .with_read_only(read_only),
+                        ),
+                        MountType::Tmpfs { size } => Arc::new(
+                            SqliteVfs::in_memory(config.dst.clone())
+                                .await
+                                .context("Failed to create tmpfs")?
+                                .with_capacity(size)
+                                .with_read_only(read_only),
+                        ),
+                        _ if read_only => {
+                            bail!("Mount '{}' cannot be read-only", config.dst.display())
+                        }
+                        MountType::Dev => Arc::new(DevVfs::new(config.dst.clone())),
+                        MountType::Proc => Arc::new(ProcVfs::new(config.dst.clone())),
+                    };
+                    (config.dst, vfs)
+                }
+            };
+            if !path.is_absolute() {
+                bail!("Mount point '{}' must be absolute", path.display());
+            }
+            table.add_mount(path, vfs);
+        }
+        Ok(table)
+    }
+
+    /// Find where the command starts when its working directory is `dir`
+    ///
+    /// Returns the host directory to spawn the command in and, if `dir` is
+    /// inside a mount, the virtual working directory to track. The host
+    /// directory is `None` for virtual mounts, leaving the kernel's working
+    /// directory as it is.
+    async fn working_dir(
+        dir: &Path,
+        table: &MountTable,
+    ) -> Result<(Option<PathBuf>, Option<PathBuf>)> {
+        if !dir.is_absolute() {
+            bail!("Working directory '{}' must be absolute", dir.display());
+        }
+        let dir = normalize_path(dir);
+        let Some((vfs, translated)) = table.resolve(&dir) else {
+            if !dir.is_dir() {
+                bail!("Working directory '{}' is not a directory", dir.display());
+            }
+            return Ok((Some(dir), None));
+        };
+
+        let host_dir = if vfs.is_virtual() {
+            let stat = vfs
+                .stat(&dir)
+                .await
+                .with_context(|| format!("Working directory '{}'", dir.display()))?;
+            if stat.st_mode & libc::S_IFMT != libc::S_IFDIR {
+                bail!("Working directory '{}' is not a directory", dir.display());
+            }
+            None
+        } else {
+            if !translated.is_dir() {
+                bail!("Working directory '{}' is not a directory", dir.display());
+            }
+            Some(translated)
+        };
+        Ok((host_dir, Some(dir)))
+    }
+
+    /// Build the command to spawn under the tracer, starting in `current_dir`
+    fn command(&mut self, current_dir: Option<&Path>) -> Command {
+        let mut cmd = Command::new(&self.command);
+        cmd.args(&self.args);
+        if self.env_clear {
+            cmd.env_clear();
+        }
+        for key in &self.env_remove {
+            cmd.env_remove(key);
+        }
+        for (key, value) in &self.envs {
+            cmd.env(key, value);
+        }
+        if let Some(dir) = current_dir {
+            cmd.current_dir(dir);
+        }
+        if let Some(stdout) = self.stdout.take() {
+            cmd.stdout(stdout);
+        }
+        if let Some(stderr) = self.stderr.take() {
+            cmd.stderr(stderr);
+        }
+        cmd
+    }
+}
+
+impl Sandbox {
+    /// Run a command in the sandbox and wait for it to exit
+    ///
+    /// The sandbox keeps its state in process globals, so it can only be run
+    /// once per process; later calls fail.
+    pub async fn run(mut config: SandboxConfig) -> Result<ExitStatus> {
+        if STARTED.swap(true, Ordering::SeqCst) {
+            bail!("A sandbox has already been run in this process");
+        }
+
+        let mounts = std::mem::take(&mut config.mounts);
+        let mount_table =
+            SandboxConfig::mount_table(mounts, config.symlink_root.as_deref()).await?;
+        let (host_dir, virtual_cwd) = match &config.current_dir {
+            Some(dir) => SandboxConfig::working_dir(dir, &mount_table).await?,
+            None => (None, None),
+        };
+        let cmd = config.command(host_dir.as_deref());
+
+        if config.print_mounts {
+            for mount in mount_table.describe() {
+                eprintln!("mount: {}", mount);
+            }
+        }
+        init_mount_table(mount_table);
+        if let Some(cwd) = virtual_cwd {
+            init_virtual_cwd(cwd);
+        }
+        init_fd_tables_with(config.preopened).context("Invalid preopened FDs")?;
+        init_strace(config.strace);
+        init_strace_summary(config.strace_summary);
+        init_strace_filter(config.strace_filter);
+        init_dump_fds_on_exit(config.dump_fds_on_exit);
+        if let Some((start, step)) = config.fake_time {
+            init_fake_time(start, step);
+        }
+        if let Some(faults) = config.faults.filter(|plan| !plan.is_empty()) {
+            init_faults(faults);
+        }
+        if let Some(deny) = config.deny.filter(|list| !list.is_empty()) {
+            init_deny_list(deny);
+        }
+
+        let tracer = TracerBuilder::<Sandbox>::new(cmd)
+            .spawn()
+            .await
+            .context("Failed to spawn sandboxed command")?;
+        let (status, _) = tracer
+            .wait()
+            .await
+            .context("Failed to wait for sandboxed command")?;
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mount_table_from_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SandboxConfig::new("/bin/true")
+            .mount(
+                format!("type=bind,src={},dst=/host", dir.path().display())
+                    .parse()
+                    .unwrap(),
+            )
+            .mount(
+                format!(
+                    "type=sqlite,src={}/agent.db,dst=/agent",
+                    dir.path().display()
+                )
+                .parse()
+                .unwrap(),
+            );
+
+        let table = SandboxConfig::mount_table(config.mounts, None)
+            .await
+            .unwrap();
+        assert_eq!(table.mounts().len(), 2);
+        assert!(table.resolve(&PathBuf::from("/agent/file")).is_some());
+        assert!(table.resolve(&PathBuf::from("/host/file")).is_some());
+        assert!(table.resolve(&PathBuf::from("/etc/passwd")).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_mount_table_from_builders() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SandboxConfig::new("/bin/true")
+            .mount(MountConfig::bind(dir.path(), "/host").read_only())
+            .mount(MountConfig::sqlite(dir.path().join("agent.db"), "/agent"))
+            .mount(MountConfig::tmpfs("/scratch", Some(1 << 20)));
+
+        let table = SandboxConfig::mount_table(config.mounts, None)
+            .await
+            .unwrap();
+        let mounts = table.describe();
+        let kind = |dst: &str| {
+            let mount = mounts
+                .iter()
+                .find(|mount| mount.dst == Path::new(dst))
+                .unwrap();
+            (mount.kind.as_str(), mount.read_only)
+        };
+        assert_eq!(kind("/host"), ("bind", true));
+        assert_eq!(kind("/agent"), ("sqlite", false));
+        assert_eq!(kind("/scratch"), ("tmpfs", false));
+        assert!(table.has_read_only());
+        assert!(table.is_read_only(Path::new("/host/file")));
+        assert!(!table.is_read_only(Path::new("/agent/file")));
+
+        let config = SandboxConfig::new("/bin/true").mount(MountConfig::dev("/dev").read_only());
+        assert!(SandboxConfig::mount_table(config.mounts, None)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bind_source_escaping_symlink_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let outside = dir.path().join("outside");
+        std::fs::create_dir_all(root.join("inside")).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+
+        let mounts = |src: &Path| {
+            SandboxConfig::new("/bin/true")
+                .mount(
+                    format!("type=bind,src={},dst=/data", src.display())
+                        .parse()
+                        .unwrap(),
+                )
+                .mounts
+        };
+
+        let table = SandboxConfig::mount_table(mounts(&root.join("inside")), Some(&root))
+            .await
+            .unwrap();
+        assert!(table.checks_paths());
+
+        // The source is canonicalized when parsed, so the symlink is already resolved
+        let err = SandboxConfig::mount_table(mounts(&root.join("link")), Some(&root))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("outside"));
+
+        let table = SandboxConfig::mount_table(mounts(&root.join("link")), None)
+            .await
+            .unwrap();
+        assert!(!table.checks_paths());
+    }
+
+    #[tokio::test]
+    async fn test_working_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("file"), b"").unwrap();
+        let db = dir.path().join("agent.db");
+        let fs = agentfs_sdk::filesystem::AgentFS::new(db.to_str().unwrap())
+            .await
+            .unwrap();
+        fs.mkdir("/project").await.unwrap();
+        fs.write_file("/notes.txt", b"").await.unwrap();
+
+        let config = SandboxConfig::new("/bin/true")
+            .mount(
+                format!("type=bind,src={},dst=/host", dir.path().display())
+                    .parse()
+                    .unwrap(),
+            )
+            .mount_vfs(
+                "/agent",
+                Arc::new(SqliteVfs::from_fs(Arc::new(fs), "/agent".into())),
+            );
+        let table = SandboxConfig::mount_table(config.mounts, None)
+            .await
+            .unwrap();
+        let working_dir = |path: &'static str| SandboxConfig::working_dir(Path::new(path), &table);
+
+        // Virtual mounts keep the kernel's working directory
+        let (host, cwd) = working_dir("/agent/project/../project").await.unwrap();
+        assert_eq!(host, None);
+        assert_eq!(cwd, Some(PathBuf::from("/agent/project")));
+
+        let (host, cwd) = working_dir("/host/src").await.unwrap();
+        assert_eq!(host, Some(dir.path().join("src")));
+        assert_eq!(cwd, Some(PathBuf::from("/host/src")));
+
+        let (host, cwd) = working_dir("/").await.unwrap();
+        assert_eq!(host, Some(PathBuf::from("/")));
+        assert_eq!(cwd, None);
+
+        assert!(working_dir("/agent/notes.txt").await.is_err());
+        assert!(working_dir("/agent/missing").await.is_err());
+        assert!(working_dir("/host/file").await.is_err());
+        assert!(working_dir("relative").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_relative_mount_point_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = Arc::new(BindVfs::new(dir.path().to_path_buf(), "relative".into()));
+        let config = SandboxConfig::new("/bin/true").mount_vfs("relative", vfs);
+        assert!(SandboxConfig::mount_table(config.mounts, None)
+            .await
+            .is_err());
+    }
+}
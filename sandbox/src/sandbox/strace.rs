@@ -0,0 +1,255 @@
+//! Rendering of syscalls and their results for strace-like output.
+//!
+//! Flag arguments of the common file and memory syscalls are decoded into
+//! their symbolic names, and error returns are shown as `-1 ERRNO`, the way
+//! `strace` prints them. Other syscalls fall back to their `Debug` output.
+
+use super::fault::errno_name;
+use reverie::syscalls::Syscall;
+use std::fmt;
+
+/// Access mode of `open` flags, which is a value rather than a bit set
+const OPEN_ACCESS_MODES: &[(&str, i32)] = &[
+    ("O_RDONLY", libc::O_RDONLY),
+    ("O_WRONLY", libc::O_WRONLY),
+    ("O_RDWR", libc::O_RDWR),
+];
+
+/// `open` flag bits, with multi-bit flags before the flags they contain
+const OPEN_FLAGS: &[(&str, i32)] = &[
+    ("O_CREAT", libc::O_CREAT),
+    ("O_EXCL", libc::O_EXCL),
+    ("O_NOCTTY", libc::O_NOCTTY),
+    ("O_TRUNC", libc::O_TRUNC),
+    ("O_APPEND", libc::O_APPEND),
+    ("O_NONBLOCK", libc::O_NONBLOCK),
+    ("O_SYNC", libc::O_SYNC),
+    ("O_DSYNC", libc::O_DSYNC),
+    ("O_DIRECT", libc::O_DIRECT),
+    ("O_LARGEFILE", libc::O_LARGEFILE),
+    ("O_TMPFILE", libc::O_TMPFILE),
+    ("O_DIRECTORY", libc::O_DIRECTORY),
+    ("O_NOFOLLOW", libc::O_NOFOLLOW),
+    ("O_NOATIME", libc::O_NOATIME),
+    ("O_CLOEXEC", libc::O_CLOEXEC),
+    ("O_PATH", libc::O_PATH),
+];
+
+/// `mmap` protection bits
+const PROT_FLAGS: &[(&str, i32)] = &[
+    ("PROT_READ", libc::PROT_READ),
+    ("PROT_WRITE", libc::PROT_WRITE),
+    ("PROT_EXEC", libc::PROT_EXEC),
+];
+
+/// `mmap` flag bits, with multi-bit flags before the flags they contain
+const MAP_FLAGS: &[(&str, i32)] = &[
+    ("MAP_SHARED_VALIDATE", libc::MAP_SHARED_VALIDATE),
+    ("MAP_SHARED", libc::MAP_SHARED),
+    ("MAP_PRIVATE", libc::MAP_PRIVATE),
+    ("MAP_FIXED_NOREPLACE", libc::MAP_FIXED_NOREPLACE),
+    ("MAP_FIXED", libc::MAP_FIXED),
+    ("MAP_ANONYMOUS", libc::MAP_ANONYMOUS),
+    ("MAP_GROWSDOWN", libc::MAP_GROWSDOWN),
+    ("MAP_DENYWRITE", libc::MAP_DENYWRITE),
+    ("MAP_EXECUTABLE", libc::MAP_EXECUTABLE),
+    ("MAP_LOCKED", libc::MAP_LOCKED),
+    ("MAP_NORESERVE", libc::MAP_NORESERVE),
+    ("MAP_POPULATE", libc::MAP_POPULATE),
+    ("MAP_NONBLOCK", libc::MAP_NONBLOCK),
+    ("MAP_STACK", libc::MAP_STACK),
+    ("MAP_HUGETLB", libc::MAP_HUGETLB),
+];
+
+/// Largest error number the kernel returns; smaller negative results are errors
+const MAX_ERRNO: i64 = 4095;
+
+/// Format a syscall for strace-like output
+pub(crate) fn format_syscall(syscall: &Syscall) -> String {
+    match syscall {
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Open(args) => {
+            let flags = args.flags().bits();
+            format!(
+                "open({}, {}{})",
+                pointer(args.path()),
+                open_flags(flags),
+                mode(flags, args.mode().map(|m| m.bits()))
+            )
+        }
+        Syscall::Openat(args) => {
+            let flags = args.flags().bits();
+            format!(
+                "openat({}, {}, {}{})",
+                dirfd(args.dirfd()),
+                pointer(args.path()),
+                open_flags(flags),
+                mode(flags, args.mode().map(|m| m.bits()))
+            )
+        }
+        Syscall::Mmap(args) => format!(
+            "mmap({}, {}, {}, {}, {}, {:#x})",
+            pointer(args.addr()),
+            args.len(),
+            prot_flags(args.prot().bits()),
+            map_flags(args.flags().bits()),
+            args.fd(),
+            args.offset()
+        ),
+        _ => format!("{:?}", syscall),
+    }
+}
+
+/// Whether a raw syscall return value is a negated errno
+pub(crate) fn is_error(value: i64) -> bool {
+    (-MAX_ERRNO..0).contains(&value)
+}
+
+/// Format a syscall result for strace-like output
+///
+/// Error returns are shown as `-1` followed by the error name.
+pub(crate) fn format_result(value: i64) -> String {
+    if is_error(value) {
+        let errno = -value as i32;
+        return match errno_name(errno) {
+            Some(name) => format!("-1 {}", name),
+            None => format!("-1 errno {}", errno),
+        };
+    }
+    format!("{}", value)
+}
+
+/// Format a directory file descriptor, naming `AT_FDCWD`
+fn dirfd(fd: i32) -> String {
+    if fd == libc::AT_FDCWD {
+        "AT_FDCWD".to_string()
+    } else {
+        fd.to_string()
+    }
+}
+
+/// Format an optional guest pointer, showing a missing one as `NULL`
+fn pointer<P: fmt::Debug>(ptr: Option<P>) -> String {
+    match ptr {
+        Some(ptr) => format!("{:?}", ptr),
+        None => "NULL".to_string(),
+    }
+}
+
+/// Format the `mode` argument of `open`, which only matters when creating
+fn mode(flags: i32, mode: Option<u32>) -> String {
+    let creates = flags & libc::O_CREAT != 0 || flags & libc::O_TMPFILE == libc::O_TMPFILE;
+    match mode {
+        Some(mode) if creates => format!(", {:#o}", mode),
+        _ => String::new(),
+    }
+}
+
+fn open_flags(flags: i32) -> String {
+    let access = flags & libc::O_ACCMODE;
+    let mut names = match OPEN_ACCESS_MODES.iter().find(|(_, mode)| *mode == access) {
+        Some((name, _)) => vec![name.to_string()],
+        None => vec![format!("{:#o}", access)],
+    };
+    let rest = flag_names(flags & !libc::O_ACCMODE, OPEN_FLAGS);
+    if rest != "0" {
+        names.push(rest);
+    }
+    names.join("|")
+}
+
+fn prot_flags(prot: i32) -> String {
+    if prot == libc::PROT_NONE {
+        return "PROT_NONE".to_string();
+    }
+    flag_names(prot, PROT_FLAGS)
+}
+
+fn map_flags(flags: i32) -> String {
+    flag_names(flags, MAP_FLAGS)
+}
+
+/// Join the names of the flags set in `bits` with `|`
+///
+/// Bits without a name are shown as a trailing hex value, and no bits at all
+/// as `0`.
+fn flag_names(bits: i32, names: &[(&str, i32)]) -> String {
+    let mut rest = bits;
+    let mut parts = Vec::new();
+    for (name, flag) in names {
+        if *flag != 0 && rest & flag == *flag {
+            parts.push(name.to_string());
+            rest &= !flag;
+        }
+    }
+    if rest != 0 || parts.is_empty() {
+        parts.push(format!("{:#x}", rest));
+    }
+    parts.join("|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_open_flags() {
+        assert_eq!(open_flags(libc::O_RDONLY), "O_RDONLY");
+        assert_eq!(
+            open_flags(libc::O_RDWR | libc::O_CREAT | libc::O_CLOEXEC),
+            "O_RDWR|O_CREAT|O_CLOEXEC"
+        );
+        assert_eq!(
+            open_flags(libc::O_WRONLY | libc::O_TMPFILE),
+            "O_WRONLY|O_TMPFILE"
+        );
+        assert_eq!(
+            open_flags(libc::O_RDONLY | libc::O_DIRECTORY),
+            "O_RDONLY|O_DIRECTORY"
+        );
+        assert_eq!(mode(libc::O_CREAT, Some(0o644)), ", 0o644");
+        assert_eq!(mode(libc::O_RDONLY, Some(0o644)), "");
+        assert_eq!(dirfd(libc::AT_FDCWD), "AT_FDCWD");
+        assert_eq!(dirfd(3), "3");
+    }
+
+    #[test]
+    fn test_mmap_flags() {
+        assert_eq!(prot_flags(libc::PROT_NONE), "PROT_NONE");
+        assert_eq!(
+            prot_flags(libc::PROT_READ | libc::PROT_WRITE),
+            "PROT_READ|PROT_WRITE"
+        );
+        assert_eq!(
+            map_flags(libc::MAP_PRIVATE | libc::MAP_ANONYMOUS),
+            "MAP_PRIVATE|MAP_ANONYMOUS"
+        );
+        assert_eq!(map_flags(libc::MAP_SHARED_VALIDATE), "MAP_SHARED_VALIDATE");
+        assert_eq!(
+            flag_names(libc::PROT_READ | 0x100, PROT_FLAGS),
+            "PROT_READ|0x100"
+        );
+    }
+
+    #[test]
+    fn test_format_result() {
+        assert_eq!(format_result(3), "3");
+        assert_eq!(format_result(0), "0");
+        assert_eq!(format_result(-(libc::EACCES as i64)), "-1 EACCES");
+        assert_eq!(format_result(-(libc::ENOENT as i64)), "-1 ENOENT");
+        assert_eq!(
+            format_result(-(libc::ELOOP as i64)),
+            format!("-1 errno {}", libc::ELOOP)
+        );
+    }
+
+    #[test]
+    fn test_is_error() {
+        assert!(is_error(-(libc::ENOENT as i64)));
+        assert!(is_error(-4095));
+        assert!(!is_error(0));
+        assert!(!is_error(3));
+        // Large negative values are results, e.g. addresses, not errors
+        assert!(!is_error(-4096));
+    }
+}
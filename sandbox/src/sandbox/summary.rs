@@ -0,0 +1,159 @@
+//! Per-syscall counters for `strace -c` style summaries.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex, OnceLock,
+};
+use std::time::Duration;
+
+/// Global flag to enable syscall summary collection
+static SUMMARY_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Global per-syscall counters, keyed by syscall name
+static SUMMARY_STATS: OnceLock<Mutex<HashMap<&'static str, SyscallStats>>> = OnceLock::new();
+
+/// Aggregated statistics for a single syscall
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyscallStats {
+    /// Number of times the syscall was made
+    pub calls: u64,
+    /// Number of calls that returned an error
+    pub errors: u64,
+    /// Total time spent handling the syscall
+    pub total_time: Duration,
+}
+
+/// Initialize summary mode
+///
+/// This must be called before spawning the traced process.
+pub fn init_strace_summary(enabled: bool) {
+    SUMMARY_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Check if summary mode is enabled
+pub(crate) fn is_summary_enabled() -> bool {
+    SUMMARY_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Record one completed syscall
+pub(crate) fn record_syscall(name: &'static str, elapsed: Duration, failed: bool) {
+    let stats = SUMMARY_STATS.get_or_init(|| Mutex::new(HashMap::new()));
+    let mut stats = stats.lock().unwrap();
+    let entry = stats.entry(name).or_default();
+    entry.calls += 1;
+    entry.total_time += elapsed;
+    if failed {
+        entry.errors += 1;
+    }
+}
+
+/// Snapshot of the collected counters, most expensive syscalls first
+pub fn strace_summary() -> Vec<(&'static str, SyscallStats)> {
+    let Some(stats) = SUMMARY_STATS.get() else {
+        return Vec::new();
+    };
+    let mut summary: Vec<_> = stats
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(name, stats)| (*name, *stats))
+        .collect();
+    summary.sort_by(|(a_name, a), (b_name, b)| {
+        b.total_time
+            .cmp(&a.total_time)
+            .then(b.calls.cmp(&a.calls))
+            .then(a_name.cmp(b_name))
+    });
+    summary
+}
+
+/// Write a summary table with one row per syscall and a trailing total row
+pub fn write_strace_summary<W: Write>(
+    out: &mut W,
+    summary: &[(&str, SyscallStats)],
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:>10} {:>10} {:>14} {:>10}  syscall",
+        "calls", "errors", "total (usecs)", "usecs/call"
+    )?;
+    writeln!(
+        out,
+        "{:->10} {:->10} {:->14} {:->10}  {:->16}",
+        "", "", "", "", ""
+    )?;
+
+    let mut total = SyscallStats::default();
+    for (name, stats) in summary {
+        write_row(out, name, stats)?;
+        total.calls += stats.calls;
+        total.errors += stats.errors;
+        total.total_time += stats.total_time;
+    }
+
+    writeln!(
+        out,
+        "{:->10} {:->10} {:->14} {:->10}  {:->16}",
+        "", "", "", "", ""
+    )?;
+    write_row(out, "total", &total)
+}
+
+fn write_row<W: Write>(out: &mut W, name: &str, stats: &SyscallStats) -> io::Result<()> {
+    let usecs = stats.total_time.as_micros();
+    let per_call = usecs.checked_div(stats.calls as u128).unwrap_or(0);
+    writeln!(
+        out,
+        "{:>10} {:>10} {:>14} {:>10}  {}",
+        stats.calls, stats.errors, usecs, per_call, name
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_strace_summary() {
+        let summary = vec![
+            (
+                "openat",
+                SyscallStats {
+                    calls: 4,
+                    errors: 1,
+                    total_time: Duration::from_micros(200),
+                },
+            ),
+            (
+                "read",
+                SyscallStats {
+                    calls: 10,
+                    errors: 0,
+                    total_time: Duration::from_micros(50),
+                },
+            ),
+        ];
+
+        let mut out = Vec::new();
+        write_strace_summary(&mut out, &summary).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert_eq!(lines.len(), 6);
+        assert!(lines[0].ends_with("syscall"));
+        assert_eq!(
+            lines[2].split_whitespace().collect::<Vec<_>>(),
+            ["4", "1", "200", "50", "openat"]
+        );
+        assert_eq!(
+            lines[3].split_whitespace().collect::<Vec<_>>(),
+            ["10", "0", "50", "5", "read"]
+        );
+        assert_eq!(
+            lines[5].split_whitespace().collect::<Vec<_>>(),
+            ["14", "1", "250", "17", "total"]
+        );
+    }
+}
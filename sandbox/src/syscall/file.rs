@@ -1,16 +1,20 @@
 use crate::{
-    sandbox::Sandbox,
-    syscall::translate_path,
+    sandbox::{self, Sandbox},
+    syscall::{absolute_path, normalize_path, resolve_at_cwd, translate_path, translate_path_at},
     vfs::{
         fdtable::{FdEntry, FdTable},
+        file::BoxedFileOps,
+        lock::LockOwner,
         mount::MountTable,
+        DirEntry,
     },
 };
 use reverie::{
-    syscalls::{MemoryAccess, ReadAddr, Syscall},
+    syscalls::{MemoryAccess, PathPtr, ReadAddr, Syscall},
     Error, Guest, Stack,
 };
 use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStringExt;
 
 /// The `openat` system call.
 ///
@@ -32,6 +36,10 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
         // Handle dirfd resolution for relative paths
         let dirfd = args.dirfd();
         let kernel_dirfd = if dirfd == libc::AT_FDCWD {
+            // Relative paths follow the virtual working directory, if there is one
+            if let Some(resolved) = resolve_at_cwd(guest.pid().as_raw(), dirfd, &path) {
+                path = resolved;
+            }
             dirfd
         } else if path.is_relative() {
             // For relative paths, resolve against dirfd
@@ -63,7 +71,9 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
             // Check if this is a virtual VFS (like SQLite)
             if vfs.is_virtual() {
                 // For virtual VFS, open the file directly without going to the kernel
-                let mode = args.mode().map(|m| m.bits()).unwrap_or(0o644);
+                // The kernel would apply the process's umask to the mode of a new file
+                let mode = args.mode().map(|m| m.bits()).unwrap_or(0o644)
+                    & !process_umask(guest.pid().as_raw());
                 match vfs.open(&path, args.flags().bits(), mode).await {
                     Ok(file_ops) => {
                         // Store the path with the FD entry for directories
@@ -76,18 +86,13 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
                         return Ok(Some(virtual_fd as i64));
                     }
                     Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -e.to_errno() as i64;
                         return Ok(Some(errno));
                     }
                 }
             } else {
                 // For passthrough VFS, translate the path and call the kernel
-                let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
+                let new_path_addr = translate_path_at(guest, dirfd, path_addr, mount_table).await?;
 
                 let new_syscall = reverie::syscalls::Openat::new()
                     .with_dirfd(kernel_dirfd)
@@ -112,9 +117,11 @@ pub async fn handle_openat<T: Guest<Sandbox>>(
             }
         } else {
             // No mount point matches - pass through to kernel with original path
+            // (made absolute if it was resolved against the virtual working directory)
+            let new_path_addr = translate_path_at(guest, dirfd, path_addr, mount_table).await?;
             let new_syscall = reverie::syscalls::Openat::new()
                 .with_dirfd(kernel_dirfd)
-                .with_path(Some(path_addr))
+                .with_path(new_path_addr.or(Some(path_addr)))
                 .with_flags(args.flags())
                 .with_mode(args.mode());
 
@@ -179,12 +186,7 @@ pub async fn handle_read<T: Guest<Sandbox>>(
                         return Ok(crate::syscall::SyscallResult::Value(n as i64));
                     }
                     Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -e.to_errno() as i64;
                         return Ok(crate::syscall::SyscallResult::Value(errno));
                     }
                 }
@@ -237,12 +239,7 @@ pub async fn handle_write<T: Guest<Sandbox>>(
                         return Ok(crate::syscall::SyscallResult::Value(n as i64));
                     }
                     Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -e.to_errno() as i64;
                         return Ok(crate::syscall::SyscallResult::Value(errno));
                     }
                 }
@@ -259,7 +256,7 @@ pub async fn handle_write<T: Guest<Sandbox>>(
 /// This intercepts `close` system calls, translates virtual FDs to kernel FDs,
 /// and cleans up the FD mapping.
 pub async fn handle_close<T: Guest<Sandbox>>(
-    _guest: &mut T,
+    guest: &mut T,
     syscall: Syscall,
     args: &reverie::syscalls::Close,
     fd_table: &FdTable,
@@ -278,9 +275,15 @@ pub async fn handle_close<T: Guest<Sandbox>>(
                 )));
             }
             FdEntry::Virtual { file_ops, .. } => {
-                // Virtualized file - just call close on the FileOps
-                file_ops.close().await.ok();
-                return Ok(crate::syscall::SyscallResult::Value(0)); // Success
+                release_locks(guest.pid().as_raw(), &file_ops).await;
+                // Virtualized file - close the FileOps, which writes back
+                // buffered data. Like close(2), the FD is gone even if that
+                // fails, e.g. with EFBIG for a file over the size limit.
+                let result = match file_ops.close().await {
+                    Ok(()) => 0,
+                    Err(e) => -e.to_errno() as i64,
+                };
+                return Ok(crate::syscall::SyscallResult::Value(result));
             }
         }
     }
@@ -289,6 +292,26 @@ pub async fn handle_close<T: Guest<Sandbox>>(
     Ok(crate::syscall::SyscallResult::Syscall(syscall))
 }
 
+/// Release the advisory locks a close drops on a virtual file
+///
+/// Closing any FD of a file releases the process's record locks on it, as
+/// POSIX requires, while `flock` locks last until the open file's last FD is
+/// closed.
+async fn release_locks(pid: i32, file_ops: &BoxedFileOps) {
+    let table = sandbox::lock_table();
+    if table.is_empty() {
+        return;
+    }
+    let Ok(key) = crate::vfs::lock::file_key(file_ops).await else {
+        return;
+    };
+    table.release(key, LockOwner::Process(pid));
+    // The closed entry is already out of the FD table, so one reference means no FD is left
+    if std::sync::Arc::strong_count(file_ops) == 1 {
+        table.release(key, LockOwner::open_file(file_ops));
+    }
+}
+
 /// The `dup` system call.
 ///
 /// This intercepts `dup` system calls and duplicates both the virtual and kernel FDs.
@@ -438,93 +461,83 @@ pub async fn handle_dup3<T: Guest<Sandbox>>(
 ) -> Result<Option<i64>, Error> {
     let old_vfd = args.oldfd();
     let new_vfd = args.newfd();
-    let flags = args.flags();
+    let flags = args.flags().bits();
+
+    // O_CLOEXEC is the only flag dup3 accepts
+    if flags & !libc::O_CLOEXEC != 0 {
+        return Ok(Some(-libc::EINVAL as i64));
+    }
+    let cloexec = flags & libc::O_CLOEXEC != 0;
 
     // Get the entry for the old virtual FD
-    if let Some(old_entry) = fd_table.get(old_vfd) {
-        // Get the entry at new_vfd if it exists (we need to close its kernel FD)
-        let old_new_entry = fd_table.get(new_vfd);
+    let Some(old_entry) = fd_table.get(old_vfd) else {
+        // FD not in table, let the original syscall through
+        return Ok(None);
+    };
+    if old_vfd == new_vfd {
+        return Ok(Some(-libc::EINVAL as i64));
+    }
 
-        match old_entry {
-            FdEntry::Passthrough {
-                kernel_fd: old_kernel_fd,
+    let displaced = match old_entry {
+        FdEntry::Passthrough {
+            kernel_fd: old_kernel_fd,
+            flags,
+            path,
+        } => {
+            // Duplicate to a fresh kernel FD first, then close the old one if needed,
+            // to avoid race conditions. The kernel FD carries O_CLOEXEC as well so
+            // that it is honored when the guest execs.
+            let cmd = if cloexec {
+                reverie::syscalls::FcntlCmd::F_DUPFD_CLOEXEC(0)
+            } else {
+                reverie::syscalls::FcntlCmd::F_DUPFD(0)
+            };
+            let new_kernel_fd = guest
+                .inject(Syscall::Fcntl(
+                    reverie::syscalls::Fcntl::new()
+                        .with_fd(old_kernel_fd)
+                        .with_cmd(cmd),
+                ))
+                .await?;
+
+            if new_kernel_fd < 0 {
+                // Dup failed, return the error
+                return Ok(Some(new_kernel_fd));
+            }
+
+            // Create new passthrough FD entry for the duplicated kernel FD
+            let entry = FdEntry::Passthrough {
+                kernel_fd: new_kernel_fd as i32,
+                flags,
                 path,
-                ..
-            } => {
-                // Allocate a new kernel FD - we need to duplicate to a fresh FD first,
-                // then close the old one if needed, to avoid race conditions
-                let new_kernel_fd = guest
-                    .inject(Syscall::Dup(
-                        reverie::syscalls::Dup::new().with_oldfd(old_kernel_fd),
+            }
+            .with_cloexec(cloexec);
+            fd_table.allocate_at(new_vfd, entry)
+        }
+        FdEntry::Virtual { .. } => match fd_table.duplicate3(old_vfd, new_vfd, cloexec) {
+            Ok(displaced) => displaced,
+            Err(crate::vfs::VfsError::InvalidInput(_)) => return Ok(Some(-libc::EINVAL as i64)),
+            Err(_) => return Ok(Some(-libc::EBADF as i64)),
+        },
+    };
+
+    // Close whatever was previously at new_vfd
+    if let Some(entry) = displaced {
+        match entry {
+            FdEntry::Passthrough { kernel_fd, .. } => {
+                let _ = guest
+                    .inject(Syscall::Close(
+                        reverie::syscalls::Close::new().with_fd(kernel_fd),
                     ))
                     .await?;
-
-                if new_kernel_fd < 0 {
-                    // Dup failed, return the error
-                    return Ok(Some(new_kernel_fd));
-                }
-
-                // Close the old kernel FD at new_vfd if it existed
-                if let Some(old_entry) = old_new_entry {
-                    match old_entry {
-                        FdEntry::Passthrough { kernel_fd, .. } => {
-                            let _ = guest
-                                .inject(Syscall::Close(
-                                    reverie::syscalls::Close::new().with_fd(kernel_fd),
-                                ))
-                                .await?;
-                        }
-                        FdEntry::Virtual { file_ops, .. } => {
-                            file_ops.close().await.ok();
-                        }
-                    }
-                }
-
-                // Note: dup3 flags (O_CLOEXEC) are stored in the FD table and will be
-                // applied later when needed (e.g., on exec). The kernel FD itself doesn't
-                // need the flag since we're virtualizing the behavior.
-
-                // Create new passthrough FD entry for the duplicated kernel FD
-                let entry = FdEntry::Passthrough {
-                    kernel_fd: new_kernel_fd as i32,
-                    flags: flags.bits(),
-                    path,
-                };
-                let _ = fd_table.allocate_at(new_vfd, entry);
             }
-            FdEntry::Virtual { .. } => {
-                // Virtualized file - close old entry at new_vfd if exists, then duplicate
-                if let Some(old_entry) = old_new_entry {
-                    match old_entry {
-                        FdEntry::Virtual { file_ops, .. } => {
-                            file_ops.close().await.ok();
-                        }
-                        FdEntry::Passthrough { kernel_fd, .. } => {
-                            let _ = guest
-                                .inject(Syscall::Close(
-                                    reverie::syscalls::Close::new().with_fd(kernel_fd),
-                                ))
-                                .await?;
-                        }
-                    }
-                }
-                let entry = match old_entry {
-                    FdEntry::Virtual { file_ops, path, .. } => FdEntry::Virtual {
-                        file_ops,
-                        flags: flags.bits(),
-                        path,
-                    },
-                    _ => unreachable!(),
-                };
-                let _ = fd_table.allocate_at(new_vfd, entry);
+            FdEntry::Virtual { file_ops, .. } => {
+                file_ops.close().await.ok();
             }
         }
-
-        Ok(Some(new_vfd as i64))
-    } else {
-        // FD not in table, let the original syscall through
-        Ok(None)
     }
+
+    Ok(Some(new_vfd as i64))
 }
 
 /// The `ioctl` system call.
@@ -562,7 +575,8 @@ pub async fn handle_ioctl<T: Guest<Sandbox>>(
 ///
 /// This intercepts `fcntl` system calls and handles virtual FD operations.
 /// Special handling is needed for F_DUPFD and F_DUPFD_CLOEXEC commands which
-/// duplicate file descriptors.
+/// duplicate file descriptors, for F_GETFL/F_SETFL whose status flags are
+/// tracked in the FD table, and for record locks on virtual files.
 pub async fn handle_fcntl<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Fcntl,
@@ -572,6 +586,29 @@ pub async fn handle_fcntl<T: Guest<Sandbox>>(
 
     let virtual_fd = args.fd();
 
+    // Virtual files have no kernel FD, so status flags are served from the FD table
+    if let Some(FdEntry::Virtual { file_ops, .. }) = fd_table.get(virtual_fd) {
+        match args.cmd() {
+            FcntlCmd::F_GETFL => {
+                let flags = fd_table.status_flags(virtual_fd).unwrap_or(0);
+                return Ok(Some(flags as i64));
+            }
+            FcntlCmd::F_SETFL(flags) => {
+                if let Some(new_flags) = fd_table.set_flags(virtual_fd, flags) {
+                    if file_ops.set_flags(new_flags).is_err() {
+                        return Ok(Some(-libc::EINVAL as i64));
+                    }
+                }
+                return Ok(Some(0));
+            }
+            FcntlCmd::F_GETLK(_) | FcntlCmd::F_SETLK(_) | FcntlCmd::F_SETLKW(_) => {
+                let flags = fd_table.get_flags(virtual_fd).unwrap_or(0);
+                return super::lock::handle_fcntl_lock(guest, args, &file_ops, flags).await;
+            }
+            _ => {}
+        }
+    }
+
     // Translate virtual FD to kernel FD
     if let Some(kernel_fd) = fd_table.translate(virtual_fd) {
         match args.cmd() {
@@ -620,6 +657,19 @@ pub async fn handle_fcntl<T: Guest<Sandbox>>(
                     return Ok(Some(new_kernel_fd));
                 }
             }
+            FcntlCmd::F_SETFL(flags) => {
+                let new_syscall = reverie::syscalls::Fcntl::new()
+                    .with_fd(kernel_fd)
+                    .with_cmd(args.cmd());
+
+                let result = guest.inject(Syscall::Fcntl(new_syscall)).await?;
+
+                // Keep the FD table in sync with the kernel's view of the flags
+                if result >= 0 {
+                    fd_table.set_flags(virtual_fd, flags);
+                }
+                return Ok(Some(result));
+            }
             _ => {
                 // For other fcntl commands, just translate the FD and pass through
                 let new_syscall = reverie::syscalls::Fcntl::new()
@@ -682,6 +732,46 @@ mod fdset {
         (kernel_set, max_kernel_fd + 1)
     }
 
+    /// Collect the virtual FDs in `virt_set` that are ready for `event`
+    ///
+    /// Returns the ready set, the number of ready FDs, and whether `virt_set`
+    /// contained any virtual FD at all.
+    pub fn virtual_ready(
+        virt_set: &libc::fd_set,
+        virt_nfds: i32,
+        fd_table: &FdTable,
+        event: i16,
+    ) -> (libc::fd_set, i64, bool) {
+        let mut ready_set: libc::fd_set = unsafe { MaybeUninit::zeroed().assume_init() };
+        zero(&mut ready_set);
+        let mut ready = 0;
+        let mut any_virtual = false;
+
+        for vfd in 0..virt_nfds {
+            if !is_set(vfd, virt_set) {
+                continue;
+            }
+            if let Some(revents) = virtual_revents(fd_table, vfd, event) {
+                any_virtual = true;
+                if revents & event != 0 {
+                    set(vfd, &mut ready_set);
+                    ready += 1;
+                }
+            }
+        }
+
+        (ready_set, ready, any_virtual)
+    }
+
+    /// Add every FD in `other` to `set`
+    pub fn union(set: &mut libc::fd_set, other: &libc::fd_set, nfds: i32) {
+        for fd in 0..nfds {
+            if is_set(fd, other) {
+                self::set(fd, set);
+            }
+        }
+    }
+
     /// Translate an fd_set from kernel FDs back to virtual FDs
     pub fn translate_to_virtual(
         kernel_set: &libc::fd_set,
@@ -703,10 +793,23 @@ mod fdset {
     }
 }
 
+/// Readiness of a virtual FD for the `poll` events in `events`
+///
+/// Returns `None` if `vfd` is not a virtual FD, in which case the kernel has to
+/// answer for it.
+fn virtual_revents(fd_table: &FdTable, vfd: i32, events: i16) -> Option<i16> {
+    fd_table
+        .get(vfd)?
+        .file_ops()
+        .map(|file_ops| file_ops.poll(events))
+}
+
 /// The `pselect6` system call.
 ///
 /// This intercepts `pselect6` system calls and translates virtual FDs in the fd_sets
 /// to kernel FDs before calling the real syscall, then translates the results back.
+/// Virtual FDs are answered from their file ops; if any of them is ready, the kernel
+/// is only polled for the passthrough FDs instead of blocking.
 pub async fn handle_pselect6<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Pselect6,
@@ -714,155 +817,137 @@ pub async fn handle_pselect6<T: Guest<Sandbox>>(
 ) -> Result<Option<i64>, Error> {
     let virt_nfds = args.nfds();
 
-    // Read the virtual fd_sets from guest memory
-    let virt_readfds = if let Some(addr) = args.readfds() {
-        Some(guest.memory().read_value(addr)?)
-    } else {
-        None
-    };
-
-    let virt_writefds = if let Some(addr) = args.writefds() {
-        Some(guest.memory().read_value(addr)?)
-    } else {
-        None
-    };
-
-    let virt_exceptfds = if let Some(addr) = args.exceptfds() {
-        Some(guest.memory().read_value(addr)?)
-    } else {
-        None
-    };
-
-    // Translate fd_sets from virtual to kernel FDs
-    let (kernel_readfds, max_read) = if let Some(ref vset) = virt_readfds {
-        let (kset, max) = fdset::translate_to_kernel(vset, virt_nfds, fd_table);
-        (Some(kset), max)
-    } else {
-        (None, 0)
-    };
-
-    let (kernel_writefds, max_write) = if let Some(ref vset) = virt_writefds {
-        let (kset, max) = fdset::translate_to_kernel(vset, virt_nfds, fd_table);
-        (Some(kset), max)
-    } else {
-        (None, 0)
-    };
-
-    let (kernel_exceptfds, max_except) = if let Some(ref vset) = virt_exceptfds {
-        let (kset, max) = fdset::translate_to_kernel(vset, virt_nfds, fd_table);
-        (Some(kset), max)
-    } else {
-        (None, 0)
-    };
+    // Read the virtual fd_sets from guest memory, paired with the event each one waits for
+    let mut sets: [(Option<libc::fd_set>, i16); 3] = [
+        (None, libc::POLLIN),
+        (None, libc::POLLOUT),
+        (None, libc::POLLPRI),
+    ];
+    let addrs = [args.readfds(), args.writefds(), args.exceptfds()];
+    for (set, addr) in sets.iter_mut().zip(addrs) {
+        if let Some(addr) = addr {
+            set.0 = Some(guest.memory().read_value(addr)?);
+        }
+    }
 
-    // Calculate the maximum kernel FD + 1
-    let kernel_nfds = max_read.max(max_write).max(max_except);
+    // Translate fd_sets from virtual to kernel FDs, and answer virtual FDs directly
+    let mut kernel_sets: [Option<libc::fd_set>; 3] = [None; 3];
+    let mut ready_sets: [Option<libc::fd_set>; 3] = [None; 3];
+    let mut kernel_nfds = 0;
+    let mut virtual_ready = 0;
+    let mut any_virtual = false;
+    for (i, (vset, event)) in sets.iter().enumerate() {
+        if let Some(vset) = vset {
+            let (kset, max) = fdset::translate_to_kernel(vset, virt_nfds, fd_table);
+            kernel_sets[i] = Some(kset);
+            kernel_nfds = kernel_nfds.max(max);
+
+            let (ready_set, ready, has_virtual) =
+                fdset::virtual_ready(vset, virt_nfds, fd_table, *event);
+            ready_sets[i] = Some(ready_set);
+            virtual_ready += ready;
+            any_virtual |= has_virtual;
+        }
+    }
 
     // If all fd_sets are None or nfds is 0, just pass through
-    if kernel_nfds == 0 {
+    if kernel_nfds == 0 && !any_virtual {
         return Ok(None);
     }
 
-    // Allocate space for kernel fd_sets in guest memory
-    let mut stack = guest.stack().await;
-
-    let kernel_readfds_addr = if kernel_readfds.is_some() {
-        let addr: reverie::syscalls::AddrMut<libc::fd_set> = stack.reserve();
-        Some(addr)
-    } else {
-        None
-    };
-
-    let kernel_writefds_addr = if kernel_writefds.is_some() {
-        let addr: reverie::syscalls::AddrMut<libc::fd_set> = stack.reserve();
-        Some(addr)
-    } else {
-        None
-    };
-
-    let kernel_exceptfds_addr = if kernel_exceptfds.is_some() {
-        let addr: reverie::syscalls::AddrMut<libc::fd_set> = stack.reserve();
-        Some(addr)
+    let result = if kernel_nfds == 0 && virtual_ready > 0 {
+        // Only virtual FDs, and some are ready: nothing to wait for
+        0
     } else {
-        None
-    };
-
-    stack.commit()?;
-
-    // Write kernel fd_sets to guest memory
-    if let (Some(kset), Some(addr)) = (kernel_readfds.as_ref(), kernel_readfds_addr) {
-        guest.memory().write_value(addr, kset)?;
-    }
-    if let (Some(kset), Some(addr)) = (kernel_writefds.as_ref(), kernel_writefds_addr) {
-        guest.memory().write_value(addr, kset)?;
-    }
-    if let (Some(kset), Some(addr)) = (kernel_exceptfds.as_ref(), kernel_exceptfds_addr) {
-        guest.memory().write_value(addr, kset)?;
-    }
-
-    // Create new syscall with translated FDs
-    let new_syscall = reverie::syscalls::Pselect6::new()
-        .with_nfds(kernel_nfds)
-        .with_readfds(kernel_readfds_addr)
-        .with_writefds(kernel_writefds_addr)
-        .with_exceptfds(kernel_exceptfds_addr)
-        .with_timeout(args.timeout())
-        .with_sigmask(args.sigmask());
-
-    // Execute the syscall
-    let result = guest.inject(Syscall::Pselect6(new_syscall)).await?;
-
-    // If the syscall failed or timed out, return early
-    if result <= 0 {
-        return Ok(Some(result));
-    }
-
-    // Read back the kernel fd_sets and translate to virtual FDs
-    if let (Some(addr), Some(_)) = (kernel_readfds_addr, virt_readfds.as_ref()) {
-        let kernel_set: libc::fd_set = guest.memory().read_value(addr)?;
-        let mut virt_set: libc::fd_set = unsafe { MaybeUninit::zeroed().assume_init() };
-        fdset::translate_to_virtual(&kernel_set, kernel_nfds, &mut virt_set, virt_nfds, fd_table);
-
-        // Write back to original guest address
-        if let Some(orig_addr) = args.readfds() {
-            guest.memory().write_value(orig_addr, &virt_set)?;
+        // Allocate space for kernel fd_sets in guest memory
+        let mut stack = guest.stack().await;
+        let mut kernel_addrs: [Option<reverie::syscalls::AddrMut<libc::fd_set>>; 3] = [None; 3];
+        if kernel_nfds > 0 {
+            for (addr, kset) in kernel_addrs.iter_mut().zip(&kernel_sets) {
+                if kset.is_some() {
+                    *addr = Some(stack.reserve());
+                }
+            }
         }
-    }
-
-    if let (Some(addr), Some(_)) = (kernel_writefds_addr, virt_writefds.as_ref()) {
-        let kernel_set: libc::fd_set = guest.memory().read_value(addr)?;
-        let mut virt_set: libc::fd_set = unsafe { MaybeUninit::zeroed().assume_init() };
-        fdset::translate_to_virtual(&kernel_set, kernel_nfds, &mut virt_set, virt_nfds, fd_table);
+        // Poll instead of blocking when virtual FDs are already ready
+        let zero_timeout: Option<reverie::syscalls::AddrMut<libc::timespec>> =
+            (virtual_ready > 0).then(|| stack.reserve());
+        stack.commit()?;
+
+        // Write kernel fd_sets to guest memory
+        for (addr, kset) in kernel_addrs.iter().zip(&kernel_sets) {
+            if let (Some(addr), Some(kset)) = (addr, kset) {
+                guest.memory().write_value(*addr, kset)?;
+            }
+        }
+        let timeout = match zero_timeout {
+            Some(addr) => {
+                let ts = libc::timespec {
+                    tv_sec: 0,
+                    tv_nsec: 0,
+                };
+                guest.memory().write_value(addr, &ts)?;
+                Some(addr.cast())
+            }
+            None => args.timeout(),
+        };
 
-        if let Some(orig_addr) = args.writefds() {
-            guest.memory().write_value(orig_addr, &virt_set)?;
+        // Create new syscall with translated FDs
+        let new_syscall = reverie::syscalls::Pselect6::new()
+            .with_nfds(kernel_nfds)
+            .with_readfds(kernel_addrs[0])
+            .with_writefds(kernel_addrs[1])
+            .with_exceptfds(kernel_addrs[2])
+            .with_timeout(timeout)
+            .with_sigmask(args.sigmask());
+
+        // Execute the syscall
+        let result = guest.inject(Syscall::Pselect6(new_syscall)).await?;
+        if result < 0 {
+            return Ok(Some(result));
         }
-    }
 
-    if let (Some(addr), Some(_)) = (kernel_exceptfds_addr, virt_exceptfds.as_ref()) {
-        let kernel_set: libc::fd_set = guest.memory().read_value(addr)?;
-        let mut virt_set: libc::fd_set = unsafe { MaybeUninit::zeroed().assume_init() };
-        fdset::translate_to_virtual(&kernel_set, kernel_nfds, &mut virt_set, virt_nfds, fd_table);
+        // Read back the kernel fd_sets and translate to virtual FDs
+        for i in 0..3 {
+            if let (Some(addr), Some(ready_set)) = (kernel_addrs[i], ready_sets[i].as_mut()) {
+                let kernel_set: libc::fd_set = guest.memory().read_value(addr)?;
+                let mut virt_set: libc::fd_set = unsafe { MaybeUninit::zeroed().assume_init() };
+                fdset::translate_to_virtual(
+                    &kernel_set,
+                    kernel_nfds,
+                    &mut virt_set,
+                    virt_nfds,
+                    fd_table,
+                );
+                fdset::union(ready_set, &virt_set, virt_nfds);
+            }
+        }
+        result
+    };
 
-        if let Some(orig_addr) = args.exceptfds() {
-            guest.memory().write_value(orig_addr, &virt_set)?;
+    // Write the merged sets back to the original guest addresses
+    for (addr, ready_set) in addrs.iter().zip(&ready_sets) {
+        if let (Some(addr), Some(ready_set)) = (addr, ready_set) {
+            guest.memory().write_value(*addr, ready_set)?;
         }
     }
 
-    Ok(Some(result))
+    Ok(Some(result + virtual_ready))
 }
 
 /// The `poll` system call.
 ///
 /// This intercepts `poll` system calls and translates virtual FDs in the pollfd array
 /// to kernel FDs before calling the real syscall, then translates the results back.
+/// Virtual FDs are answered from their file ops and hidden from the kernel; if any
+/// of them is ready, the kernel is polled without a timeout instead of blocking.
 #[cfg(not(target_arch = "aarch64"))]
 pub async fn handle_poll<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Poll,
     fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
-    use reverie::syscalls::{MemoryAccess, PollFd};
+    use reverie::syscalls::{MemoryAccess, PollFd, PollFlags};
 
     let nfds = args.nfds();
     if nfds == 0 {
@@ -882,6 +967,12 @@ pub async fn handle_poll<T: Guest<Sandbox>>(
         pollfds.push(pollfd);
     }
 
+    let ready_virtual: Vec<Option<i16>> = pollfds
+        .iter()
+        .map(|pollfd| virtual_revents(fd_table, pollfd.fd, pollfd.events.bits()))
+        .collect();
+    let ready_now = ready_virtual.iter().flatten().any(|revents| *revents != 0);
+
     // Allocate space on stack for kernel pollfd array
     let mut stack = guest.stack().await;
     let kernel_fds_addr: reverie::syscalls::AddrMut<PollFd> = stack.reserve();
@@ -895,11 +986,15 @@ pub async fn handle_poll<T: Guest<Sandbox>>(
 
     // Write kernel pollfds to guest memory
     for (i, pollfd) in pollfds.iter().enumerate() {
-        let kernel_fd = fd_table.translate(pollfd.fd).unwrap_or(pollfd.fd);
+        // The kernel ignores negative FDs, which keeps virtual FDs out of its way
+        let kernel_fd = match ready_virtual[i] {
+            Some(_) => -1,
+            None => fd_table.translate(pollfd.fd).unwrap_or(pollfd.fd),
+        };
         let kernel_pollfd = PollFd {
             fd: kernel_fd,
             events: pollfd.events,
-            revents: reverie::syscalls::PollFlags::empty(),
+            revents: PollFlags::empty(),
         };
 
         let offset = i as isize * std::mem::size_of::<PollFd>() as isize;
@@ -914,26 +1009,36 @@ pub async fn handle_poll<T: Guest<Sandbox>>(
     let new_syscall = reverie::syscalls::Poll::new()
         .with_fds(Some(kernel_fds_addr))
         .with_nfds(nfds)
-        .with_timeout(args.timeout());
+        .with_timeout(if ready_now { 0 } else { args.timeout() });
 
     let result = guest.inject(Syscall::Poll(new_syscall)).await?;
 
-    // If the syscall failed or timed out, return early
-    if result <= 0 {
+    // If the syscall failed or nothing is ready, return early
+    if result < 0 || (result == 0 && !ready_now) {
         return Ok(Some(result));
     }
 
-    // Read back the kernel pollfds and translate to virtual FDs
+    // Read back the kernel pollfds, merge in virtual readiness, and write the
+    // revents to the original pollfd array
+    let mut ready = 0;
     for i in 0..nfds {
         let offset = i as isize * std::mem::size_of::<PollFd>() as isize;
-        let kernel_pollfd: PollFd =
-            unsafe { guest.memory().read_value(kernel_fds_addr.offset(offset))? };
+        let revents = match ready_virtual[i as usize] {
+            Some(revents) => PollFlags::from_bits_truncate(revents),
+            None => {
+                let kernel_pollfd: PollFd =
+                    unsafe { guest.memory().read_value(kernel_fds_addr.offset(offset))? };
+                kernel_pollfd.revents
+            }
+        };
+        if !revents.is_empty() {
+            ready += 1;
+        }
 
-        // Write back the revents to the original pollfd array
         let virt_pollfd = PollFd {
             fd: pollfds[i as usize].fd, // Keep the virtual FD
             events: pollfds[i as usize].events,
-            revents: kernel_pollfd.revents,
+            revents,
         };
 
         unsafe {
@@ -943,111 +1048,331 @@ pub async fn handle_poll<T: Guest<Sandbox>>(
         }
     }
 
-    Ok(Some(result))
+    Ok(Some(ready))
 }
 
-/// The `getdents64` system call.
+/// Register a freshly created epoll instance in the FD table
+fn virtualize_epoll_fd(fd_table: &FdTable, kernel_fd: i64) -> i64 {
+    if kernel_fd < 0 {
+        return kernel_fd;
+    }
+    let entry = FdEntry::Passthrough {
+        kernel_fd: kernel_fd as i32,
+        flags: 0,
+        path: None,
+    };
+    fd_table.allocate(entry) as i64
+}
+
+/// The `epoll_create1` system call.
 ///
-/// This intercepts `getdents64` system calls and translates virtual FDs to kernel FDs,
-/// or calls FileOps::getdents() for virtual files.
-pub async fn handle_getdents64<T: Guest<Sandbox>>(
+/// This intercepts `epoll_create1` system calls and virtualizes the returned FD.
+pub async fn handle_epoll_create1<T: Guest<Sandbox>>(
     guest: &mut T,
-    syscall: Syscall,
-    args: &reverie::syscalls::Getdents64,
+    args: &reverie::syscalls::EpollCreate1,
     fd_table: &FdTable,
-) -> Result<crate::syscall::SyscallResult, Error> {
-    let virtual_fd = args.fd() as i32;
-
-    // Get the FD entry
-    if let Some(entry) = fd_table.get(virtual_fd) {
-        match entry {
-            FdEntry::Passthrough { kernel_fd, .. } => {
-                // Passthrough file - rewrite FD and return modified syscall for tail_inject
-                let new_syscall = args.with_fd(kernel_fd as u32);
+) -> Result<Option<i64>, Error> {
+    let kernel_fd = guest.inject(Syscall::EpollCreate1(*args)).await?;
+    Ok(Some(virtualize_epoll_fd(fd_table, kernel_fd)))
+}
 
-                return Ok(crate::syscall::SyscallResult::Syscall(Syscall::Getdents64(
-                    new_syscall,
-                )));
-            }
-            FdEntry::Virtual { file_ops, .. } => {
-                // Virtual file - use FileOps::getdents()
-                match file_ops.getdents().await {
-                    Ok(entries) => {
-                        // Format as linux_dirent64 structures
-                        let dirent_addr = match args.dirent() {
-                            Some(addr) => addr,
-                            None => {
-                                return Ok(crate::syscall::SyscallResult::Value(
-                                    -libc::EFAULT as i64,
-                                ))
-                            }
-                        };
-                        let count = args.count() as usize;
-
-                        let mut buf = Vec::new();
-                        let mut offset = 1i64;
-
-                        for (ino, name, d_type) in entries {
-                            // Calculate record length (aligned to 8 bytes)
-                            let name_len = name.len() + 1; // +1 for null terminator
-                            let reclen = (19 + name_len).div_ceil(8) * 8; // 19 = sizeof(ino + off + reclen + type)
-
-                            if buf.len() + reclen > count {
-                                break; // Not enough space
-                            }
-
-                            // Write linux_dirent64 structure
-                            buf.extend_from_slice(&ino.to_ne_bytes()); // d_ino (u64)
-                            buf.extend_from_slice(&offset.to_ne_bytes()); // d_off (i64)
-                            buf.extend_from_slice(&(reclen as u16).to_ne_bytes()); // d_reclen (u16)
-                            buf.push(d_type); // d_type (u8)
-                            buf.extend_from_slice(name.as_bytes()); // d_name
-                            buf.push(0); // null terminator
-
-                            // Pad to 8-byte alignment
-                            while buf.len() % 8 != 0 {
-                                buf.push(0);
-                            }
-
-                            offset += 1;
-                        }
+/// The `epoll_create` system call.
+///
+/// This intercepts `epoll_create` system calls and virtualizes the returned FD.
+#[cfg(not(target_arch = "aarch64"))]
+pub async fn handle_epoll_create<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::EpollCreate,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let kernel_fd = guest.inject(Syscall::EpollCreate(*args)).await?;
+    Ok(Some(virtualize_epoll_fd(fd_table, kernel_fd)))
+}
 
-                        // Write to guest memory
-                        if !buf.is_empty() {
-                            guest.memory().write_exact(dirent_addr.cast::<u8>(), &buf)?;
-                        }
+/// The `epoll_ctl` system call.
+///
+/// This intercepts `epoll_ctl` system calls and translates both the epoll FD and
+/// the target FD to kernel FDs. Virtual FDs cannot be watched by the kernel, so
+/// they are rejected with `EPERM`, which is what Linux reports for regular files.
+pub async fn handle_epoll_ctl<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::EpollCtl,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let Some(kernel_epfd) = fd_table.translate(args.epfd()) else {
+        // FD not in table, let the original syscall through (will likely fail with EBADF)
+        return Ok(None);
+    };
 
-                        return Ok(crate::syscall::SyscallResult::Value(buf.len() as i64));
-                    }
-                    Err(_) => {
-                        // Not a directory or error
-                        return Ok(crate::syscall::SyscallResult::Value(-libc::ENOTDIR as i64));
-                    }
-                }
-            }
-        }
-    }
+    let kernel_fd = match fd_table.get(args.fd()) {
+        Some(FdEntry::Passthrough { kernel_fd, .. }) => kernel_fd,
+        Some(FdEntry::Virtual { .. }) => return Ok(Some(-(libc::EPERM as i64))),
+        None => args.fd(),
+    };
 
-    // FD not in table, let the original syscall through (will likely fail with EBADF)
-    Ok(crate::syscall::SyscallResult::Syscall(syscall))
+    let new_syscall = args.with_epfd(kernel_epfd).with_fd(kernel_fd);
+    let result = guest.inject(Syscall::EpollCtl(new_syscall)).await?;
+    Ok(Some(result))
 }
 
-/// The `fstat` system call.
+/// The `epoll_wait` system call.
 ///
-/// This intercepts `fstat` system calls and translates virtual FDs to kernel FDs,
-/// or calls FileOps::fstat() for virtual files.
-pub async fn handle_fstat<T: Guest<Sandbox>>(
+/// This intercepts `epoll_wait` system calls and translates the epoll FD to a kernel FD.
+#[cfg(not(target_arch = "aarch64"))]
+pub async fn handle_epoll_wait<T: Guest<Sandbox>>(
     guest: &mut T,
-    syscall: Syscall,
-    args: &reverie::syscalls::Fstat,
+    args: &reverie::syscalls::EpollWait,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    if let Some(kernel_epfd) = fd_table.translate(args.epfd()) {
+        let new_syscall = args.with_epfd(kernel_epfd);
+        let result = guest.inject(Syscall::EpollWait(new_syscall)).await?;
+        return Ok(Some(result));
+    }
+
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    Ok(None)
+}
+
+/// The `epoll_pwait` system call.
+///
+/// This intercepts `epoll_pwait` system calls and translates the epoll FD to a kernel FD.
+pub async fn handle_epoll_pwait<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::EpollPwait,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    if let Some(kernel_epfd) = fd_table.translate(args.epfd()) {
+        let new_syscall = args.with_epfd(kernel_epfd);
+        let result = guest.inject(Syscall::EpollPwait(new_syscall)).await?;
+        return Ok(Some(result));
+    }
+
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    Ok(None)
+}
+
+/// Directory record layout written by `getdents`/`getdents64`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DirentFormat {
+    /// `struct linux_dirent`, with `d_type` stored in the last byte of the record
+    Legacy,
+    /// `struct linux_dirent64`
+    Dirent64,
+}
+
+/// Encode directory entries into `linux_dirent`/`linux_dirent64` records.
+///
+/// `position` is the directory offset of the first entry; each record's
+/// `d_off` is the offset of the entry after it. Stops at the first entry that
+/// does not fit in `count` bytes and returns the encoded bytes together with
+/// the number of entries consumed.
+fn encode_dirents(
+    entries: &[DirEntry],
+    position: i64,
+    count: usize,
+    format: DirentFormat,
+) -> (Vec<u8>, usize) {
+    let mut buf = Vec::new();
+    let mut consumed = 0;
+
+    for entry in entries {
+        // Header: d_ino (u64) + d_off (i64) + d_reclen (u16), plus d_type (u8)
+        // and the NUL-terminated name, padded to 8-byte alignment
+        let reclen = (8 + 8 + 2 + 1 + entry.name.len() + 1).div_ceil(8) * 8;
+        if buf.len() + reclen > count {
+            break; // Not enough space, the caller picks it up on the next call
+        }
+
+        let record_start = buf.len();
+        let next_offset = position + consumed as i64 + 1;
+        buf.extend_from_slice(&entry.ino.to_ne_bytes()); // d_ino
+        buf.extend_from_slice(&next_offset.to_ne_bytes()); // d_off
+        buf.extend_from_slice(&(reclen as u16).to_ne_bytes()); // d_reclen
+        match format {
+            DirentFormat::Dirent64 => {
+                buf.push(entry.d_type); // d_type
+                buf.extend_from_slice(entry.name.as_bytes()); // d_name
+                buf.resize(record_start + reclen, 0); // NUL terminator and padding
+            }
+            DirentFormat::Legacy => {
+                buf.extend_from_slice(entry.name.as_bytes()); // d_name
+                buf.resize(record_start + reclen, 0); // NUL terminator and padding
+                buf[record_start + reclen - 1] = entry.d_type; // d_type
+            }
+        }
+
+        consumed += 1;
+    }
+
+    (buf, consumed)
+}
+
+/// Serve `getdents`/`getdents64` for a virtual directory from `FileOps::getdents()`.
+///
+/// Fills as many records as fit in the caller's buffer and advances the
+/// directory position past them, so repeated calls page through the listing
+/// and return 0 at the end.
+async fn getdents_virtual<T: Guest<Sandbox>>(
+    guest: &mut T,
+    file_ops: &BoxedFileOps,
+    dirent_addr: Option<reverie::syscalls::AddrMut<'_, u8>>,
+    count: usize,
+    format: DirentFormat,
+) -> Result<i64, Error> {
+    let entries = match file_ops.getdents().await {
+        Ok(entries) => entries,
+        Err(e) => return Ok(-e.to_errno() as i64),
+    };
+    let dirent_addr = match dirent_addr {
+        Some(addr) => addr,
+        None => return Ok(-libc::EFAULT as i64),
+    };
+
+    let position = file_ops.seek(0, libc::SEEK_CUR).await.unwrap_or(0);
+    let (buf, consumed) = encode_dirents(&entries, position, count, format);
+
+    if consumed == 0 && !entries.is_empty() {
+        // The next record does not fit in the buffer
+        return Ok(-libc::EINVAL as i64);
+    }
+
+    if !buf.is_empty() {
+        guest.memory().write_exact(dirent_addr, &buf)?;
+        if let Err(e) = file_ops.seek(consumed as i64, libc::SEEK_CUR).await {
+            return Ok(-e.to_errno() as i64);
+        }
+    }
+
+    Ok(buf.len() as i64)
+}
+
+/// The `getdents64` system call.
+///
+/// This intercepts `getdents64` system calls and translates virtual FDs to kernel FDs,
+/// or serves the listing from FileOps::getdents() for virtual directories.
+pub async fn handle_getdents64<T: Guest<Sandbox>>(
+    guest: &mut T,
+    syscall: Syscall,
+    args: &reverie::syscalls::Getdents64,
     fd_table: &FdTable,
 ) -> Result<crate::syscall::SyscallResult, Error> {
-    let virtual_fd = args.fd();
+    let virtual_fd = args.fd() as i32;
 
     // Get the FD entry
     if let Some(entry) = fd_table.get(virtual_fd) {
         match entry {
             FdEntry::Passthrough { kernel_fd, .. } => {
+                // Passthrough file - rewrite FD and return modified syscall for tail_inject
+                let new_syscall = args.with_fd(kernel_fd as u32);
+
+                return Ok(crate::syscall::SyscallResult::Syscall(Syscall::Getdents64(
+                    new_syscall,
+                )));
+            }
+            FdEntry::Virtual { file_ops, .. } => {
+                let result = getdents_virtual(
+                    guest,
+                    &file_ops,
+                    args.dirent().map(|addr| addr.cast::<u8>()),
+                    args.count() as usize,
+                    DirentFormat::Dirent64,
+                )
+                .await?;
+                return Ok(crate::syscall::SyscallResult::Value(result));
+            }
+        }
+    }
+
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    Ok(crate::syscall::SyscallResult::Syscall(syscall))
+}
+
+/// The legacy `getdents` system call.
+///
+/// Same as `getdents64`, but virtual directories are encoded as `struct linux_dirent`.
+#[cfg(not(target_arch = "aarch64"))]
+pub async fn handle_getdents<T: Guest<Sandbox>>(
+    guest: &mut T,
+    syscall: Syscall,
+    args: &reverie::syscalls::Getdents,
+    fd_table: &FdTable,
+) -> Result<crate::syscall::SyscallResult, Error> {
+    let virtual_fd = args.fd() as i32;
+
+    if let Some(entry) = fd_table.get(virtual_fd) {
+        match entry {
+            FdEntry::Passthrough { kernel_fd, .. } => {
+                let new_syscall = args.with_fd(kernel_fd as u32);
+
+                return Ok(crate::syscall::SyscallResult::Syscall(Syscall::Getdents(
+                    new_syscall,
+                )));
+            }
+            FdEntry::Virtual { file_ops, .. } => {
+                let result = getdents_virtual(
+                    guest,
+                    &file_ops,
+                    args.dirent().map(|addr| addr.cast::<u8>()),
+                    args.count() as usize,
+                    DirentFormat::Legacy,
+                )
+                .await?;
+                return Ok(crate::syscall::SyscallResult::Value(result));
+            }
+        }
+    }
+
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    Ok(crate::syscall::SyscallResult::Syscall(syscall))
+}
+
+/// The `fstat` system call.
+///
+/// This intercepts `fstat` system calls and translates virtual FDs to kernel FDs,
+/// or calls FileOps::fstat() for virtual files. Files opened from mounts that
+/// rewrite ownership get their uid/gid patched after the kernel fills the buffer.
+pub async fn handle_fstat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    syscall: Syscall,
+    args: &reverie::syscalls::Fstat,
+    fd_table: &FdTable,
+    mount_table: &MountTable,
+) -> Result<crate::syscall::SyscallResult, Error> {
+    let virtual_fd = args.fd();
+
+    // Get the FD entry
+    if let Some(entry) = fd_table.get(virtual_fd) {
+        match entry {
+            FdEntry::Passthrough {
+                kernel_fd, path, ..
+            } => {
+                let remap_vfs = path
+                    .as_deref()
+                    .and_then(|p| mount_table.resolve(p))
+                    .map(|(vfs, _)| vfs)
+                    .filter(|vfs| vfs.remaps_ownership());
+
+                if let (Some(vfs), Some(stat_addr)) = (remap_vfs, args.stat()) {
+                    let result = guest
+                        .inject(Syscall::Fstat(args.with_fd(kernel_fd)))
+                        .await?;
+                    if result == 0 {
+                        let mut stat_buf: libc::stat = guest.memory().read_value(stat_addr.0)?;
+                        vfs.apply_owner_override(&mut stat_buf);
+                        let stat_bytes: &[u8] = unsafe {
+                            std::slice::from_raw_parts(
+                                &stat_buf as *const _ as *const u8,
+                                std::mem::size_of::<libc::stat>(),
+                            )
+                        };
+                        guest
+                            .memory()
+                            .write_exact(stat_addr.0.cast::<u8>(), stat_bytes)?;
+                    }
+                    return Ok(crate::syscall::SyscallResult::Value(result));
+                }
+
                 // Passthrough file - rewrite FD and return modified syscall for tail_inject
                 let new_syscall = args.with_fd(kernel_fd);
 
@@ -1075,12 +1400,7 @@ pub async fn handle_fstat<T: Guest<Sandbox>>(
                         return Ok(crate::syscall::SyscallResult::Value(0)); // Success
                     }
                     Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -e.to_errno() as i64;
                         return Ok(crate::syscall::SyscallResult::Value(errno));
                     }
                 }
@@ -1111,6 +1431,10 @@ pub async fn handle_fstatat<T: Guest<Sandbox>>(
         // Handle dirfd resolution for relative paths
         let dirfd = args.dirfd();
         let kernel_dirfd = if dirfd == libc::AT_FDCWD {
+            // Relative paths follow the virtual working directory, if there is one
+            if let Some(resolved) = resolve_at_cwd(guest.pid().as_raw(), dirfd, &path) {
+                path = resolved;
+            }
             dirfd
         } else if path.is_relative() {
             // For relative paths, resolve against dirfd
@@ -1141,9 +1465,9 @@ pub async fn handle_fstatat<T: Guest<Sandbox>>(
 
         // Check if this path matches a mount point
         if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
-            // Check if this is a virtual VFS (like SQLite)
-            if vfs.is_virtual() {
-                // For virtual VFS, open the file directly without going to the kernel
+            // Virtual VFS (like SQLite) and mounts that rewrite ownership are
+            // stat'ed through the VFS instead of the kernel
+            if vfs.is_virtual() || vfs.remaps_ownership() {
                 match vfs.stat(&path).await {
                     Ok(stat_buf) => {
                         // Write the stat result to guest memory
@@ -1162,19 +1486,18 @@ pub async fn handle_fstatat<T: Guest<Sandbox>>(
                         return Ok(crate::syscall::SyscallResult::Value(0)); // Success
                     }
                     Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -e.to_errno() as i64;
                         return Ok(crate::syscall::SyscallResult::Value(errno));
                     }
                 }
             }
         }
         // No mount point matches - pass through to kernel with original path
-        let new_syscall = args.with_dirfd(kernel_dirfd).with_path(Some(path_addr));
+        // (made absolute if it was resolved against the virtual working directory)
+        let new_path_addr = translate_path_at(guest, dirfd, path_addr, mount_table).await?;
+        let new_syscall = args
+            .with_dirfd(kernel_dirfd)
+            .with_path(new_path_addr.or(Some(path_addr)));
 
         return Ok(crate::syscall::SyscallResult::Syscall(Syscall::Fstatat(
             new_syscall,
@@ -1277,12 +1600,7 @@ pub async fn handle_lseek<T: Guest<Sandbox>>(
                         return Ok(crate::syscall::SyscallResult::Value(new_offset));
                     }
                     Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -e.to_errno() as i64;
                         return Ok(crate::syscall::SyscallResult::Value(errno));
                     }
                 }
@@ -1328,23 +1646,73 @@ pub async fn handle_mmap<T: Guest<Sandbox>>(
     Ok(None)
 }
 
+/// Answer an access check for a path inside a virtual VFS.
+///
+/// Existence is checked with `Vfs::stat` (or `lstat` when not following
+/// symlinks). `R_OK` and `X_OK` are always granted, `W_OK` is denied on
+/// read-only mounts. Returns `None` if the path is not in a virtual VFS.
+async fn access_virtual<T: Guest<Sandbox>>(
+    guest: &mut T,
+    dirfd: i32,
+    path_addr: reverie::syscalls::PathPtr<'_>,
+    mode: i32,
+    follow_symlinks: bool,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+    let path = resolve_at_cwd(guest.pid().as_raw(), dirfd, &path).unwrap_or(path);
+    let Some((vfs, _)) = mount_table.resolve(&path) else {
+        return Ok(None);
+    };
+    if !vfs.is_virtual() {
+        return Ok(None);
+    }
+
+    if mode & !(libc::F_OK | libc::R_OK | libc::W_OK | libc::X_OK) != 0 {
+        return Ok(Some(-libc::EINVAL as i64));
+    }
+
+    let stat = if follow_symlinks {
+        vfs.stat(&path).await
+    } else {
+        vfs.lstat(&path).await
+    };
+    let errno = match stat {
+        Ok(_) if mode & libc::W_OK != 0 && vfs.is_read_only() => libc::EACCES,
+        Ok(_) => return Ok(Some(0)),
+        Err(e) => e.to_errno(),
+    };
+    Ok(Some(-errno as i64))
+}
+
 /// The `access` system call.
 ///
-/// This intercepts `access` system calls and translates paths according to the mount table.
+/// This intercepts `access` system calls, answers them from the VFS for virtual
+/// mounts, and otherwise translates paths according to the mount table.
 #[cfg(not(target_arch = "aarch64"))]
 pub async fn handle_access<T: Guest<Sandbox>>(
     guest: &mut T,
+    syscall: Syscall,
     args: &reverie::syscalls::Access,
     mount_table: &MountTable,
-) -> Result<Option<Syscall>, Error> {
+) -> Result<crate::syscall::SyscallResult, Error> {
     if let Some(path_addr) = args.path() {
+        let mode = args.mode().bits() as i32;
+        if let Some(result) =
+            access_virtual(guest, libc::AT_FDCWD, path_addr, mode, true, mount_table).await?
+        {
+            return Ok(crate::syscall::SyscallResult::Value(result));
+        }
+
         if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
             let new_syscall = args.with_path(Some(new_path_addr));
 
-            return Ok(Some(Syscall::Access(new_syscall)));
+            return Ok(crate::syscall::SyscallResult::Syscall(Syscall::Access(
+                new_syscall,
+            )));
         }
     }
-    Ok(None)
+    Ok(crate::syscall::SyscallResult::Syscall(syscall))
 }
 
 /// The `faccessat2` system call.
@@ -1365,11 +1733,26 @@ pub async fn handle_faccessat2<T: Guest<Sandbox>>(
     let mode = syscall_args.arg2 as i32;
     let flags = syscall_args.arg3 as i32;
 
+    // Paths in virtual mounts are answered by the VFS
+    let follow_symlinks = flags & libc::AT_SYMLINK_NOFOLLOW == 0;
+    if let Some(result) = access_virtual(
+        guest,
+        dirfd,
+        pathname_addr,
+        mode,
+        follow_symlinks,
+        mount_table,
+    )
+    .await?
+    {
+        return Ok(Some(result));
+    }
+
     // Check if dirfd needs virtualization
     let dirfd_needs_translation = dirfd != libc::AT_FDCWD && fd_table.translate(dirfd).is_some();
 
     // Check if path needs virtualization
-    let translated_path_opt = translate_path(guest, pathname_addr, mount_table).await?;
+    let translated_path_opt = translate_path_at(guest, dirfd, pathname_addr, mount_table).await?;
     let path_needs_translation = translated_path_opt.is_some();
 
     // If nothing needs virtualization, let the original syscall pass through
@@ -1420,11 +1803,19 @@ pub async fn handle_faccessat<T: Guest<Sandbox>>(
     };
     let dirfd = args.dirfd();
 
+    // Paths in virtual mounts are answered by the VFS
+    let mode = args.mode().bits() as i32;
+    if let Some(result) =
+        access_virtual(guest, dirfd, pathname_addr, mode, true, mount_table).await?
+    {
+        return Ok(Some(result));
+    }
+
     // Check if dirfd needs virtualization
     let dirfd_needs_translation = dirfd != libc::AT_FDCWD && fd_table.translate(dirfd).is_some();
 
     // Check if path needs virtualization
-    let translated_path_opt = translate_path(guest, pathname_addr, mount_table).await?;
+    let translated_path_opt = translate_path_at(guest, dirfd, pathname_addr, mount_table).await?;
     let path_needs_translation = translated_path_opt.is_some();
 
     // If nothing needs virtualization, let the original syscall pass through
@@ -1449,15 +1840,46 @@ pub async fn handle_faccessat<T: Guest<Sandbox>>(
     Ok(Some(result))
 }
 
+/// Check if a rename moves an entry to another mount
+///
+/// Each path is resolved from its directory FD as the kernel would look it
+/// up. A path whose base directory can't be determined is left to the kernel.
+fn rename_crosses_mounts<T: Guest<Sandbox>>(
+    guest: &T,
+    old: (i32, Option<PathPtr>),
+    new: (i32, Option<PathPtr>),
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<bool, Error> {
+    let pid = guest.pid().as_raw();
+    let mut paths = Vec::with_capacity(2);
+    for (dirfd, path_addr) in [old, new] {
+        let Some(path_addr) = path_addr else {
+            return Ok(false);
+        };
+        let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+        match absolute_path(pid, dirfd, &path, fd_table) {
+            Some(path) => paths.push(path),
+            None => return Ok(false),
+        }
+    }
+    Ok(!mount_table.same_mount(&paths[0], &paths[1]))
+}
+
 /// The `rename` system call.
 ///
 /// This intercepts `rename` system calls and translates both paths according to the mount table.
+/// Renames between two mounts fail with `EXDEV`, so programs like `mv` fall
+/// back to copying and unlinking.
 #[cfg(not(target_arch = "aarch64"))]
 pub async fn handle_rename<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Rename,
     mount_table: &MountTable,
-) -> Result<Option<Syscall>, Error> {
+    fd_table: &FdTable,
+) -> Result<Option<crate::syscall::SyscallResult>, Error> {
+    use crate::syscall::SyscallResult;
+
     // Only translate if we need to - otherwise pass through unchanged
     let oldpath_needs_translation = args.oldpath().is_some();
     let newpath_needs_translation = args.newpath().is_some();
@@ -1466,6 +1888,13 @@ pub async fn handle_rename<T: Guest<Sandbox>>(
         return Ok(None);
     }
 
+    let cwd = libc::AT_FDCWD;
+    let old = (cwd, args.oldpath());
+    let new = (cwd, args.newpath());
+    if rename_crosses_mounts(guest, old, new, mount_table, fd_table)? {
+        return Ok(Some(SyscallResult::Value(-libc::EXDEV as i64)));
+    }
+
     // Build new syscall with translated paths
     let mut new_syscall = *args;
     let mut modified = false;
@@ -1491,12 +1920,120 @@ pub async fn handle_rename<T: Guest<Sandbox>>(
     }
 
     if modified {
-        Ok(Some(Syscall::Rename(new_syscall)))
+        Ok(Some(SyscallResult::Syscall(Syscall::Rename(new_syscall))))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The `renameat` system call.
+///
+/// Like [`handle_rename`], with both paths looked up from directory FDs,
+/// which are translated to kernel FDs.
+pub async fn handle_renameat<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Renameat,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<crate::syscall::SyscallResult>, Error> {
+    use crate::syscall::SyscallResult;
+
+    let old = (args.olddirfd(), args.oldpath());
+    let new = (args.newdirfd(), args.newpath());
+    if rename_crosses_mounts(guest, old, new, mount_table, fd_table)? {
+        return Ok(Some(SyscallResult::Value(-libc::EXDEV as i64)));
+    }
+
+    let kernel_olddirfd = kernel_dirfd(args.olddirfd(), fd_table);
+    let kernel_newdirfd = kernel_dirfd(args.newdirfd(), fd_table);
+    let mut new_syscall = args
+        .with_olddirfd(kernel_olddirfd)
+        .with_newdirfd(kernel_newdirfd);
+    let mut modified = kernel_olddirfd != args.olddirfd() || kernel_newdirfd != args.newdirfd();
+
+    if let Some(oldpath_addr) = args.oldpath() {
+        if let Some(new_path_addr) =
+            translate_path_at(guest, args.olddirfd(), oldpath_addr, mount_table).await?
+        {
+            new_syscall = new_syscall.with_oldpath(Some(new_path_addr));
+            modified = true;
+        }
+    }
+    if let Some(newpath_addr) = args.newpath() {
+        if let Some(new_path_addr) =
+            translate_path_at(guest, args.newdirfd(), newpath_addr, mount_table).await?
+        {
+            new_syscall = new_syscall.with_newpath(Some(new_path_addr));
+            modified = true;
+        }
+    }
+
+    if modified {
+        Ok(Some(SyscallResult::Syscall(Syscall::Renameat(new_syscall))))
+    } else {
+        Ok(None)
+    }
+}
+
+/// The `renameat2` system call.
+///
+/// Like [`handle_renameat`]; the flags are passed on to the kernel unchanged.
+pub async fn handle_renameat2<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Renameat2,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<crate::syscall::SyscallResult>, Error> {
+    use crate::syscall::SyscallResult;
+
+    let old = (args.olddirfd(), args.oldpath());
+    let new = (args.newdirfd(), args.newpath());
+    if rename_crosses_mounts(guest, old, new, mount_table, fd_table)? {
+        return Ok(Some(SyscallResult::Value(-libc::EXDEV as i64)));
+    }
+
+    let kernel_olddirfd = kernel_dirfd(args.olddirfd(), fd_table);
+    let kernel_newdirfd = kernel_dirfd(args.newdirfd(), fd_table);
+    let mut new_syscall = args
+        .with_olddirfd(kernel_olddirfd)
+        .with_newdirfd(kernel_newdirfd);
+    let mut modified = kernel_olddirfd != args.olddirfd() || kernel_newdirfd != args.newdirfd();
+
+    if let Some(oldpath_addr) = args.oldpath() {
+        if let Some(new_path_addr) =
+            translate_path_at(guest, args.olddirfd(), oldpath_addr, mount_table).await?
+        {
+            new_syscall = new_syscall.with_oldpath(Some(new_path_addr));
+            modified = true;
+        }
+    }
+    if let Some(newpath_addr) = args.newpath() {
+        if let Some(new_path_addr) =
+            translate_path_at(guest, args.newdirfd(), newpath_addr, mount_table).await?
+        {
+            new_syscall = new_syscall.with_newpath(Some(new_path_addr));
+            modified = true;
+        }
+    }
+
+    if modified {
+        Ok(Some(SyscallResult::Syscall(Syscall::Renameat2(
+            new_syscall,
+        ))))
     } else {
         Ok(None)
     }
 }
 
+/// Translate a directory FD argument to the kernel's FD, leaving `AT_FDCWD` alone
+fn kernel_dirfd(dirfd: i32, fd_table: &FdTable) -> i32 {
+    if dirfd == libc::AT_FDCWD {
+        dirfd
+    } else {
+        fd_table.translate(dirfd).unwrap_or(dirfd)
+    }
+}
+
 /// The `unlink` system call.
 ///
 /// This intercepts `unlink` system calls and translates paths according to the mount table.
@@ -1721,20 +2258,123 @@ pub async fn handle_getpeername<T: Guest<Sandbox>>(
 
 /// The `chdir` system call.
 ///
-/// This intercepts `chdir` system calls and translates paths according to the mount table.
+/// This intercepts `chdir` system calls and tracks the virtual working directory.
+/// Directories in a virtual VFS only exist in the VFS, so the kernel's working
+/// directory is left untouched and relative paths are resolved by the sandbox
+/// instead. Other directories are changed into by the kernel (with the path
+/// translated according to the mount table).
 pub async fn handle_chdir<T: Guest<Sandbox>>(
     guest: &mut T,
+    syscall: Syscall,
     args: &reverie::syscalls::Chdir,
     mount_table: &MountTable,
-) -> Result<Option<Syscall>, Error> {
-    if let Some(path_addr) = args.path() {
-        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
-            let new_syscall = args.with_path(Some(new_path_addr));
+) -> Result<crate::syscall::SyscallResult, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(crate::syscall::SyscallResult::Syscall(syscall));
+    };
+    let pid = guest.pid().as_raw();
+    let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+    let path = resolve_at_cwd(pid, libc::AT_FDCWD, &path).unwrap_or(path);
+    let mounted = mount_table.resolve(&path);
+
+    if let Some((vfs, _)) = &mounted {
+        if vfs.is_virtual() {
+            let errno = match vfs.stat(&path).await {
+                Ok(stat) if stat.st_mode & libc::S_IFMT == libc::S_IFDIR => {
+                    sandbox::set_virtual_cwd(pid, Some(normalize_path(&path)));
+                    return Ok(crate::syscall::SyscallResult::Value(0));
+                }
+                Ok(_) => libc::ENOTDIR,
+                Err(e) => e.to_errno(),
+            };
+            return Ok(crate::syscall::SyscallResult::Value(-errno as i64));
+        }
+    }
 
-            return Ok(Some(Syscall::Chdir(new_syscall)));
+    let new_path_addr = translate_path(guest, path_addr, mount_table).await?;
+    let new_syscall = args.with_path(new_path_addr.or(Some(path_addr)));
+    let result = guest.inject(Syscall::Chdir(new_syscall)).await?;
+
+    if result == 0 {
+        // Only directories inside a mount need a virtual working directory;
+        // elsewhere the kernel's working directory is authoritative
+        let cwd = mounted.map(|_| normalize_path(&path));
+        sandbox::set_virtual_cwd(pid, cwd);
+    }
+    Ok(crate::syscall::SyscallResult::Value(result))
+}
+
+/// The `fchdir` system call.
+///
+/// This intercepts `fchdir` system calls, virtualizes the file descriptor, and
+/// tracks the virtual working directory like `chdir`.
+pub async fn handle_fchdir<T: Guest<Sandbox>>(
+    guest: &mut T,
+    syscall: Syscall,
+    args: &reverie::syscalls::Fchdir,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<crate::syscall::SyscallResult, Error> {
+    let pid = guest.pid().as_raw();
+
+    match fd_table.get(args.fd()) {
+        Some(FdEntry::Virtual { file_ops, path, .. }) => {
+            let result = match file_ops.fstat().await {
+                Ok(stat) if stat.st_mode & libc::S_IFMT == libc::S_IFDIR => match path {
+                    Some(path) => {
+                        sandbox::set_virtual_cwd(pid, Some(normalize_path(&path)));
+                        0
+                    }
+                    None => -libc::EBADF as i64,
+                },
+                Ok(_) => -libc::ENOTDIR as i64,
+                Err(e) => -e.to_errno() as i64,
+            };
+            Ok(crate::syscall::SyscallResult::Value(result))
+        }
+        Some(FdEntry::Passthrough {
+            kernel_fd, path, ..
+        }) => {
+            let new_syscall = args.with_fd(kernel_fd);
+            let result = guest.inject(Syscall::Fchdir(new_syscall)).await?;
+
+            if result == 0 {
+                let cwd = path
+                    .filter(|path| mount_table.resolve(path).is_some())
+                    .map(|path| normalize_path(&path));
+                sandbox::set_virtual_cwd(pid, cwd);
+            }
+            Ok(crate::syscall::SyscallResult::Value(result))
         }
+        None => Ok(crate::syscall::SyscallResult::Syscall(syscall)),
     }
-    Ok(None)
+}
+
+/// The `getcwd` system call.
+///
+/// This intercepts `getcwd` system calls and reports the virtual working directory
+/// while it is inside a mount, so the sandboxed process sees the path it changed
+/// into rather than the translated host path.
+pub async fn handle_getcwd<T: Guest<Sandbox>>(
+    guest: &mut T,
+    syscall: Syscall,
+    args: &reverie::syscalls::Getcwd,
+) -> Result<crate::syscall::SyscallResult, Error> {
+    let Some(cwd) = sandbox::virtual_cwd(guest.pid().as_raw()) else {
+        return Ok(crate::syscall::SyscallResult::Syscall(syscall));
+    };
+    let Some(buf_addr) = args.buf() else {
+        return Ok(crate::syscall::SyscallResult::Value(-libc::EFAULT as i64));
+    };
+
+    let mut bytes = cwd.into_os_string().into_vec();
+    bytes.push(0);
+    if bytes.len() > args.size() {
+        return Ok(crate::syscall::SyscallResult::Value(-libc::ERANGE as i64));
+    }
+
+    guest.memory().write_exact(buf_addr.cast::<u8>(), &bytes)?;
+    Ok(crate::syscall::SyscallResult::Value(bytes.len() as i64))
 }
 
 /// The `rmdir` system call.
@@ -1773,7 +2413,7 @@ pub async fn handle_unlinkat<T: Guest<Sandbox>>(
     let dirfd = args.dirfd();
     let flags = args.flags();
 
-    // Only handle AT_FDCWD for now (same as handle_chmod)
+    // Only handle AT_FDCWD for now
     if dirfd != AT_FDCWD {
         return Ok(None);
     }
@@ -1790,51 +2430,240 @@ pub async fn handle_unlinkat<T: Guest<Sandbox>>(
     Ok(None)
 }
 
+/// Map an error from a virtual `chmod` or `chown` to an errno
+/// The umask of a process, from `/proc/<pid>/status`, or the usual 022 if it can't be read
+fn process_umask(pid: i32) -> u32 {
+    std::fs::read_to_string(format!("/proc/{}/status", pid))
+        .ok()
+        .and_then(|status| {
+            let line = status.lines().find(|line| line.starts_with("Umask:"))?;
+            u32::from_str_radix(line["Umask:".len()..].trim(), 8).ok()
+        })
+        .unwrap_or(0o022)
+}
+
+fn metadata_errno(err: crate::vfs::VfsError) -> i64 {
+    match err {
+        // chmod(2) and chown(2) report refused changes as EPERM
+        crate::vfs::VfsError::PermissionDenied => -libc::EPERM as i64,
+        err => -err.to_errno() as i64,
+    }
+}
+
+/// Change the permission bits of `path` if it lives on a virtual mount
+///
+/// Returns `None` if the path belongs to the kernel and the syscall should be
+/// passed through.
+async fn chmod_virtual(path: &std::path::Path, mode: u32, mount_table: &MountTable) -> Option<i64> {
+    let (vfs, _) = mount_table.resolve(path)?;
+    if !vfs.is_virtual() {
+        return None;
+    }
+    Some(match vfs.chmod(path, mode & 0o7777).await {
+        Ok(()) => 0,
+        Err(e) => metadata_errno(e),
+    })
+}
+
+/// Change the ownership of `path` if the mount it lives on handles it
+///
+/// Mounts that rewrite ownership only accept chown to the ownership they
+/// report (a no-op); anything else would expose or change host ids. Virtual
+/// mounts store the new ids. As in `chown(2)`, `u32::MAX` leaves an id
+/// unchanged. Returns `None` if the syscall should be passed through.
+async fn chown_virtual(
+    path: &std::path::Path,
+    owner: u32,
+    group: u32,
+    mount_table: &MountTable,
+) -> Option<i64> {
+    let (vfs, _) = mount_table.resolve(path)?;
+    if vfs.remaps_ownership() {
+        let (uid, gid) = vfs.owner_override();
+        let owner_ok = owner == u32::MAX || uid == Some(owner);
+        let group_ok = group == u32::MAX || gid == Some(group);
+        return Some(if owner_ok && group_ok {
+            0
+        } else {
+            -libc::EPERM as i64
+        });
+    }
+    if !vfs.is_virtual() {
+        return None;
+    }
+    let uid = (owner != u32::MAX).then_some(owner);
+    let gid = (group != u32::MAX).then_some(group);
+    Some(match vfs.chown(path, uid, gid).await {
+        Ok(()) => 0,
+        Err(e) => metadata_errno(e),
+    })
+}
+
+/// The `chmod` system call.
+///
+/// This intercepts `chmod` system calls, changing the mode through the VFS for
+/// virtual mounts and translating paths according to the mount table otherwise.
+#[cfg(not(target_arch = "aarch64"))]
+pub async fn handle_chmod<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Chmod,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    let Some(path_addr) = args.path() else {
+        return Ok(None);
+    };
+
+    let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+    let path = resolve_at_cwd(guest.pid().as_raw(), libc::AT_FDCWD, &path).unwrap_or(path);
+    if let Some(result) = chmod_virtual(&path, args.mode().bits(), mount_table).await {
+        return Ok(Some(result));
+    }
+
+    if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        let new_syscall = Syscall::Chmod(args.with_path(Some(new_path_addr)));
+        return Ok(Some(guest.inject(new_syscall).await?));
+    }
+    Ok(None)
+}
+
 /// The `fchmodat` system call (used for `chmod` on ARM).
 ///
-/// This intercepts `fchmodat` system calls and translates paths according to the mount table.
+/// This intercepts `fchmodat` system calls, changing the mode through the VFS for
+/// virtual mounts, and otherwise translates paths according to the mount table and
+/// virtualizes the dirfd parameter.
 /// Signature: int fchmodat(int dirfd, const char *pathname, mode_t mode, int flags);
-/// Note: On ARM (aarch64), chmod is implemented via fchmodat with AT_FDCWD.
-pub async fn handle_chmod<T: Guest<Sandbox>>(
+pub async fn handle_fchmodat<T: Guest<Sandbox>>(
     guest: &mut T,
-    syscall_args: &reverie::syscalls::SyscallArgs,
+    args: &reverie::syscalls::Fchmodat,
     mount_table: &MountTable,
+    fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
-    use libc::AT_FDCWD;
-    use reverie::syscalls::{AtFlags, Mode, PathPtr, Syscall};
+    let Some(pathname_addr) = args.path() else {
+        return Ok(None);
+    };
+    let dirfd = args.dirfd();
 
-    let dirfd = syscall_args.arg0 as i32;
-    let pathname_addr = match PathPtr::from_ptr(syscall_args.arg1 as _) {
-        Some(ptr) => ptr,
-        None => {
-            return Ok(None);
+    let path: std::path::PathBuf = pathname_addr.read(&guest.memory())?;
+    if let Some(path) = absolute_path(guest.pid().as_raw(), dirfd, &path, fd_table) {
+        if let Some(result) = chmod_virtual(&path, args.mode().bits(), mount_table).await {
+            return Ok(Some(result));
         }
+    }
+
+    // Check if dirfd needs virtualization
+    let dirfd_needs_translation = dirfd != libc::AT_FDCWD && fd_table.translate(dirfd).is_some();
+
+    // Check if path needs virtualization
+    let translated_path_opt = translate_path_at(guest, dirfd, pathname_addr, mount_table).await?;
+
+    // If nothing needs virtualization, let the original syscall pass through
+    if !dirfd_needs_translation && translated_path_opt.is_none() {
+        return Ok(None);
+    }
+
+    let kernel_dirfd = if dirfd == libc::AT_FDCWD {
+        dirfd
+    } else {
+        fd_table.translate(dirfd).unwrap_or(dirfd)
     };
-    let mode = syscall_args.arg2 as u32;
-    let flags = syscall_args.arg3 as i32;
+    let new_path_addr = translated_path_opt.unwrap_or(pathname_addr);
+    let new_syscall =
+        Syscall::Fchmodat(args.with_dirfd(kernel_dirfd).with_path(Some(new_path_addr)));
+
+    Ok(Some(guest.inject(new_syscall).await?))
+}
+
+/// The `fchmodat` system call, when reverie reports it by number only.
+///
+/// Decodes the raw arguments and defers to [`handle_fchmodat`].
+pub async fn handle_fchmodat_raw<T: Guest<Sandbox>>(
+    guest: &mut T,
+    syscall_args: &reverie::syscalls::SyscallArgs,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    use reverie::syscalls::{AtFlags, Mode, PathPtr};
+
+    let args = reverie::syscalls::Fchmodat::new()
+        .with_dirfd(syscall_args.arg0 as i32)
+        .with_path(PathPtr::from_ptr(syscall_args.arg1 as _))
+        .with_mode(Mode::from_bits_truncate(syscall_args.arg2 as u32))
+        .with_flags(AtFlags::from_bits_truncate(syscall_args.arg3 as i32));
+    handle_fchmodat(guest, &args, mount_table, fd_table).await
+}
 
-    if dirfd != libc::AT_FDCWD {
+/// The `fchmod` system call.
+///
+/// This intercepts `fchmod` system calls, changing the mode through the VFS for
+/// virtual files and translating virtual FDs to kernel FDs otherwise.
+pub async fn handle_fchmod<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Fchmod,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    let Some(entry) = fd_table.get(args.fd()) else {
         return Ok(None);
+    };
+
+    match entry {
+        FdEntry::Passthrough { kernel_fd, .. } => {
+            let new_syscall = Syscall::Fchmod(args.with_fd(kernel_fd));
+            Ok(Some(guest.inject(new_syscall).await?))
+        }
+        FdEntry::Virtual { path, .. } => {
+            // Virtual files without a path (e.g. unlinked ones) can't be changed
+            let result = match path {
+                Some(path) => chmod_virtual(&path, args.mode().bits(), mount_table).await,
+                None => None,
+            };
+            Ok(Some(result.unwrap_or(-libc::EPERM as i64)))
+        }
     }
+}
+
+/// The `chown` and `lchown` system calls.
+///
+/// This intercepts `chown` and `lchown` system calls, changing ownership through the
+/// VFS for virtual mounts and translating paths according to the mount table otherwise.
+#[cfg(not(target_arch = "aarch64"))]
+pub async fn handle_chown<T: Guest<Sandbox>>(
+    guest: &mut T,
+    syscall: &Syscall,
+    mount_table: &MountTable,
+) -> Result<Option<i64>, Error> {
+    let (path_addr, owner, group) = match syscall {
+        Syscall::Chown(args) => (args.path(), args.owner(), args.group()),
+        Syscall::Lchown(args) => (args.path(), args.owner(), args.group()),
+        _ => return Ok(None),
+    };
+    let Some(path_addr) = path_addr else {
+        return Ok(None);
+    };
 
-    if let Some(new_path_addr) = translate_path(guest, pathname_addr, mount_table).await? {
-        let new_syscall = reverie::syscalls::Fchmodat::new()
-            .with_dirfd(AT_FDCWD)
-            .with_path(Some(new_path_addr))
-            .with_mode(Mode::from_bits_truncate(mode))
-            .with_flags(AtFlags::from_bits_truncate(flags));
-        let injected = guest.inject(Syscall::Fchmodat(new_syscall)).await?;
+    let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+    let path = resolve_at_cwd(guest.pid().as_raw(), libc::AT_FDCWD, &path).unwrap_or(path);
+    if let Some(result) = chown_virtual(&path, owner, group, mount_table).await {
+        return Ok(Some(result));
+    }
 
-        Ok(Some(injected))
-    } else {
-        Ok(None)
+    if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        let new_syscall = match syscall {
+            Syscall::Chown(args) => Syscall::Chown(args.with_path(Some(new_path_addr))),
+            Syscall::Lchown(args) => Syscall::Lchown(args.with_path(Some(new_path_addr))),
+            _ => return Ok(None),
+        };
+        return Ok(Some(guest.inject(new_syscall).await?));
     }
+    Ok(None)
 }
 
 /// The `fchownat` system call.
 ///
-/// This intercepts `fchownat` system calls, translates paths according to the mount table,
-/// and virtualizes the dirfd parameter.
+/// This intercepts `fchownat` system calls, changing ownership through the VFS for
+/// virtual mounts, and otherwise translates paths according to the mount table and
+/// virtualizes the dirfd parameter.
 pub async fn handle_fchownat<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Fchownat,
@@ -1846,11 +2675,18 @@ pub async fn handle_fchownat<T: Guest<Sandbox>>(
     };
     let dirfd = args.dirfd();
 
+    let path: std::path::PathBuf = pathname_addr.read(&guest.memory())?;
+    if let Some(path) = absolute_path(guest.pid().as_raw(), dirfd, &path, fd_table) {
+        if let Some(result) = chown_virtual(&path, args.owner(), args.group(), mount_table).await {
+            return Ok(Some(result));
+        }
+    }
+
     // Check if dirfd needs virtualization
     let dirfd_needs_translation = dirfd != libc::AT_FDCWD && fd_table.translate(dirfd).is_some();
 
     // Check if path needs virtualization
-    let translated_path_opt = translate_path(guest, pathname_addr, mount_table).await?;
+    let translated_path_opt = translate_path_at(guest, dirfd, pathname_addr, mount_table).await?;
     let path_needs_translation = translated_path_opt.is_some();
 
     // If nothing needs virtualization, let the original syscall pass through
@@ -1874,3 +2710,96 @@ pub async fn handle_fchownat<T: Guest<Sandbox>>(
 
     Ok(Some(result))
 }
+
+/// The `fchown` system call.
+///
+/// This intercepts `fchown` system calls, changing ownership through the VFS for
+/// virtual files and translating virtual FDs to kernel FDs otherwise.
+pub async fn handle_fchown<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Fchown,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    // FD not in table, let the original syscall through (will likely fail with EBADF)
+    let Some(entry) = fd_table.get(args.fd()) else {
+        return Ok(None);
+    };
+
+    let (owner, group) = (args.owner(), args.group());
+    match entry {
+        FdEntry::Passthrough {
+            kernel_fd, path, ..
+        } => {
+            if let Some(path) = path {
+                if let Some(result) = chown_virtual(&path, owner, group, mount_table).await {
+                    return Ok(Some(result));
+                }
+            }
+            let new_syscall = Syscall::Fchown(args.with_fd(kernel_fd));
+            Ok(Some(guest.inject(new_syscall).await?))
+        }
+        FdEntry::Virtual { path, .. } => {
+            // Virtual files without a path (e.g. unlinked ones) can't be changed
+            let result = match path {
+                Some(path) => chown_virtual(&path, owner, group, mount_table).await,
+                None => None,
+            };
+            Ok(Some(result.unwrap_or(-libc::EPERM as i64)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<DirEntry> {
+        vec![
+            DirEntry::new(1, ".", libc::S_IFDIR),
+            DirEntry::new(1, "..", libc::S_IFDIR),
+            DirEntry::new(7, "notes.txt", libc::S_IFREG),
+        ]
+    }
+
+    #[test]
+    fn test_encode_dirents64() {
+        let (buf, consumed) = encode_dirents(&entries(), 0, 4096, DirentFormat::Dirent64);
+        assert_eq!(consumed, 3);
+        // "." and ".." fit in 24 bytes, "notes.txt" needs 32
+        assert_eq!(buf.len(), 24 + 24 + 32);
+
+        let record = &buf[48..];
+        assert_eq!(u64::from_ne_bytes(record[0..8].try_into().unwrap()), 7);
+        assert_eq!(i64::from_ne_bytes(record[8..16].try_into().unwrap()), 3);
+        assert_eq!(u16::from_ne_bytes(record[16..18].try_into().unwrap()), 32);
+        assert_eq!(record[18], libc::DT_REG);
+        assert_eq!(&record[19..29], b"notes.txt\0");
+    }
+
+    #[test]
+    fn test_encode_dirents_legacy() {
+        let (buf, consumed) = encode_dirents(&entries()[2..], 2, 4096, DirentFormat::Legacy);
+        assert_eq!(consumed, 1);
+        assert_eq!(buf.len(), 32);
+        assert_eq!(i64::from_ne_bytes(buf[8..16].try_into().unwrap()), 3);
+        assert_eq!(&buf[18..28], b"notes.txt\0");
+        assert_eq!(buf[31], libc::DT_REG);
+    }
+
+    #[test]
+    fn test_encode_dirents_paginates() {
+        let entries = entries();
+        let (buf, consumed) = encode_dirents(&entries, 0, 60, DirentFormat::Dirent64);
+        assert_eq!(consumed, 2);
+        assert_eq!(buf.len(), 48);
+
+        let (buf, consumed) = encode_dirents(&entries[consumed..], 2, 60, DirentFormat::Dirent64);
+        assert_eq!(consumed, 1);
+        assert_eq!(buf.len(), 32);
+
+        let (buf, consumed) = encode_dirents(&entries, 0, 16, DirentFormat::Dirent64);
+        assert_eq!(consumed, 0);
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+//! Advisory locking (`flock` and `fcntl` record locks) on virtual files
+//!
+//! Locks on passthrough FDs are taken by the kernel as usual. Virtual files have
+//! no kernel file to lock, so their locks are kept in the sandbox's
+//! [`LockTable`](crate::vfs::lock::LockTable), which every traced process shares.
+
+use crate::{
+    sandbox::{self, Sandbox},
+    vfs::{
+        fdtable::{FdEntry, FdTable},
+        file::BoxedFileOps,
+        lock::{self, FileLock, LockKind, LockOwner, LockRange},
+    },
+};
+use reverie::{
+    syscalls::{Addr, AddrMut, MemoryAccess, Syscall},
+    Error, Guest,
+};
+
+/// The `flock` system call.
+///
+/// Locks on virtual files are owned by the open file, so they are shared by
+/// duplicated FDs and released when the last of them is closed.
+pub async fn handle_flock<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Flock,
+    fd_table: &FdTable,
+) -> Result<Option<i64>, Error> {
+    let operation = args.operation();
+    match fd_table.get(args.fd()) {
+        Some(FdEntry::Passthrough { kernel_fd, .. }) => {
+            let new_syscall = args.with_fd(kernel_fd);
+            Ok(Some(guest.inject(Syscall::Flock(new_syscall)).await?))
+        }
+        Some(FdEntry::Virtual { file_ops, .. }) => {
+            let key = match lock::file_key(&file_ops).await {
+                Ok(key) => key,
+                Err(e) => return Ok(Some(-e.to_errno() as i64)),
+            };
+            let table = sandbox::lock_table();
+            let owner = LockOwner::open_file(&file_ops);
+            let kind = match operation & !libc::LOCK_NB {
+                libc::LOCK_SH => LockKind::Shared,
+                libc::LOCK_EX => LockKind::Exclusive,
+                libc::LOCK_UN => {
+                    table.release(key, owner);
+                    return Ok(Some(0));
+                }
+                _ => return Ok(Some(-libc::EINVAL as i64)),
+            };
+
+            if operation & libc::LOCK_NB != 0 {
+                if table
+                    .try_lock(key, owner, kind, LockRange::WHOLE, Some(&file_ops))
+                    .is_err()
+                {
+                    return Ok(Some(-libc::EWOULDBLOCK as i64));
+                }
+            } else {
+                table
+                    .lock(key, owner, kind, LockRange::WHOLE, Some(&file_ops))
+                    .await;
+            }
+            Ok(Some(0))
+        }
+        // FD not in table, let the original syscall through (will likely fail with EBADF)
+        None => Ok(None),
+    }
+}
+
+/// The `F_GETLK`, `F_SETLK` and `F_SETLKW` commands of `fcntl` on a virtual file.
+///
+/// Record locks are owned by the calling process; see
+/// [`handle_fcntl`](super::file::handle_fcntl) for the other commands.
+pub async fn handle_fcntl_lock<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Fcntl,
+    file_ops: &BoxedFileOps,
+    fd_flags: i32,
+) -> Result<Option<i64>, Error> {
+    use reverie::syscalls::FcntlCmd;
+
+    let (addr, wait): (Addr<libc::flock>, _) = match args.cmd() {
+        FcntlCmd::F_GETLK(Some(addr)) => return get_lock(guest, addr, file_ops).await,
+        FcntlCmd::F_SETLK(Some(addr)) => (addr, false),
+        FcntlCmd::F_SETLKW(Some(addr)) => (addr, true),
+        FcntlCmd::F_GETLK(None) | FcntlCmd::F_SETLK(None) | FcntlCmd::F_SETLKW(None) => {
+            return Ok(Some(-libc::EFAULT as i64));
+        }
+        _ => return Ok(None),
+    };
+
+    let request: libc::flock = guest.memory().read_value(addr)?;
+    let range = match lock_range(&request, file_ops).await {
+        Ok(range) => range,
+        Err(errno) => return Ok(Some(-errno as i64)),
+    };
+    let key = match lock::file_key(file_ops).await {
+        Ok(key) => key,
+        Err(e) => return Ok(Some(-e.to_errno() as i64)),
+    };
+    let table = sandbox::lock_table();
+    let owner = LockOwner::Process(guest.pid().as_raw());
+
+    // Like the kernel, only allow read locks on readable FDs and write locks on writable ones
+    let access = fd_flags & libc::O_ACCMODE;
+    let kind = match request.l_type as i32 {
+        libc::F_RDLCK if access != libc::O_WRONLY => LockKind::Shared,
+        libc::F_WRLCK if access != libc::O_RDONLY => LockKind::Exclusive,
+        libc::F_RDLCK | libc::F_WRLCK => return Ok(Some(-libc::EBADF as i64)),
+        libc::F_UNLCK => {
+            table.unlock(key, owner, range);
+            return Ok(Some(0));
+        }
+        _ => return Ok(Some(-libc::EINVAL as i64)),
+    };
+
+    if wait {
+        table.lock(key, owner, kind, range, None).await;
+    } else if table.try_lock(key, owner, kind, range, None).is_err() {
+        return Ok(Some(-libc::EAGAIN as i64));
+    }
+    Ok(Some(0))
+}
+
+/// `F_GETLK`: report a lock that would block the described one, or `F_UNLCK`
+async fn get_lock<T: Guest<Sandbox>>(
+    guest: &mut T,
+    addr: AddrMut<'_, libc::flock>,
+    file_ops: &BoxedFileOps,
+) -> Result<Option<i64>, Error> {
+    let mut request: libc::flock = guest.memory().read_value(addr)?;
+    let range = match lock_range(&request, file_ops).await {
+        Ok(range) => range,
+        Err(errno) => return Ok(Some(-errno as i64)),
+    };
+    let kind = match request.l_type as i32 {
+        libc::F_RDLCK => LockKind::Shared,
+        libc::F_WRLCK => LockKind::Exclusive,
+        _ => return Ok(Some(-libc::EINVAL as i64)),
+    };
+    let key = match lock::file_key(file_ops).await {
+        Ok(key) => key,
+        Err(e) => return Ok(Some(-e.to_errno() as i64)),
+    };
+
+    let owner = LockOwner::Process(guest.pid().as_raw());
+    match sandbox::lock_table().conflict(key, owner, kind, range) {
+        Some(FileLock { owner, kind, range }) => {
+            request.l_type = match kind {
+                LockKind::Shared => libc::F_RDLCK,
+                LockKind::Exclusive => libc::F_WRLCK,
+            } as libc::c_short;
+            request.l_whence = libc::SEEK_SET as libc::c_short;
+            request.l_start = range.start as libc::off_t;
+            request.l_len = if range.end == u64::MAX {
+                0
+            } else {
+                (range.end - range.start) as libc::off_t
+            };
+            request.l_pid = match owner {
+                LockOwner::Process(pid) => pid,
+                LockOwner::OpenFile(_) => -1,
+            };
+        }
+        None => request.l_type = libc::F_UNLCK as libc::c_short,
+    }
+    guest.memory().write_value(addr, &request)?;
+    Ok(Some(0))
+}
+
+/// The byte range described by a `struct flock`, as an errno on failure
+///
+/// `l_start` is relative to `l_whence`, an `l_len` of 0 reaches past the end of
+/// the file however it grows, and a negative `l_len` covers the bytes before
+/// `l_start`.
+async fn lock_range(request: &libc::flock, file_ops: &BoxedFileOps) -> Result<LockRange, i32> {
+    let base = match request.l_whence as i32 {
+        libc::SEEK_SET => 0,
+        libc::SEEK_CUR => file_ops
+            .seek(0, libc::SEEK_CUR)
+            .await
+            .map_err(|e| e.to_errno())?,
+        libc::SEEK_END => file_ops.fstat().await.map_err(|e| e.to_errno())?.st_size,
+        _ => return Err(libc::EINVAL),
+    };
+    let start = base.checked_add(request.l_start).ok_or(libc::EOVERFLOW)?;
+    let (start, end) = match request.l_len {
+        0 => (start, None),
+        len if len > 0 => (start, Some(start.checked_add(len).ok_or(libc::EOVERFLOW)?)),
+        len => (start + len, Some(start)),
+    };
+    if start < 0 {
+        return Err(libc::EINVAL);
+    }
+    Ok(LockRange {
+        start: start as u64,
+        end: end.map_or(u64::MAX, |end| end as u64),
+    })
+}
@@ -1,17 +1,66 @@
 pub mod file;
+pub mod lock;
 pub mod process;
 pub mod stat;
+pub mod time;
 pub mod xattr;
 
 use crate::{
-    sandbox::Sandbox,
-    vfs::{fdtable::FdTable, mount::MountTable},
+    sandbox::{self, DenyList, Sandbox},
+    vfs::{
+        fdtable::{FdEntry, FdTable},
+        mount::MountTable,
+        VfsError,
+    },
 };
 use reverie::{
     syscalls::{MemoryAccess, PathPtr, ReadAddr, Syscall},
     Error, Guest, Stack,
 };
-use std::{ffi::CString, path::PathBuf};
+use std::{
+    ffi::CString,
+    path::{Component, Path, PathBuf},
+};
+
+/// Lexically normalize a path, resolving `.` and `..` components
+pub(crate) fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// Resolve a relative path against the virtual working directory.
+///
+/// Returns `Some(path)` with the absolute path only if `path` is relative, it
+/// is looked up from the current directory (`dirfd` is `AT_FDCWD`), and the
+/// process's working directory is inside a mount. Otherwise the kernel's own
+/// working directory is authoritative and `None` is returned.
+pub(crate) fn resolve_at_cwd(pid: i32, dirfd: i32, path: &Path) -> Option<PathBuf> {
+    if dirfd != libc::AT_FDCWD || path.as_os_str().is_empty() || path.is_absolute() {
+        return None;
+    }
+    let cwd = sandbox::virtual_cwd(pid)?;
+    Some(normalize_path(&cwd.join(path)))
+}
+
+/// Path translation for syscalls that resolve relative paths from the working directory.
+///
+/// See [`translate_path_at`].
+pub(crate) async fn translate_path<'a, T: Guest<Sandbox>>(
+    guest: &'a mut T,
+    path_addr: PathPtr<'a>,
+    mount_table: &MountTable,
+) -> Result<Option<PathPtr<'a>>, Error> {
+    translate_path_at(guest, libc::AT_FDCWD, path_addr, mount_table).await
+}
 
 /// Common path translation logic for syscalls.
 ///
@@ -19,28 +68,35 @@ use std::{ffi::CString, path::PathBuf};
 /// handlers. It performs the following operations:
 ///
 /// 1. Reads the original path from guest memory
-/// 2. Resolves the path through the mount table to get the translated host path
-/// 3. Converts the translated path to a C string
-/// 4. Allocates space on the guest stack for the new path
-/// 5. Writes the translated path to guest memory
-/// 6. Returns the new path address for use in the modified syscall
+/// 2. Resolves relative paths against the virtual working directory
+/// 3. Resolves the path through the mount table to get the translated host path
+/// 4. Converts the translated path to a C string
+/// 5. Allocates space on the guest stack for the new path
+/// 6. Writes the translated path to guest memory
+/// 7. Returns the new path address for use in the modified syscall
 ///
 /// # Arguments
 /// * `guest` - The guest process being traced
+/// * `dirfd` - The directory file descriptor relative paths are looked up from
 /// * `path_addr` - The address of the original path in guest memory
 /// * `mount_table` - The mount table for path resolution
 ///
 /// # Returns
 /// * `Ok(Some(addr))` - Path was successfully translated, returns new address
 /// * `Ok(None)` - Path does not need translation (no matching mount point)
+///
+/// A relative path resolved against a virtual working directory that falls
+/// outside every mount point is rewritten to its absolute form, since the
+/// kernel's working directory does not follow the virtual one.
 /// * `Err(e)` - An error occurred during translation
 ///
 /// # Safety
 /// This function allocates memory on the guest stack and writes to guest memory.
 /// The allocated memory is automatically cleaned up when the syscall returns,
 /// as the guest process unwinds its own stack frame.
-pub(crate) async fn translate_path<'a, T: Guest<Sandbox>>(
+pub(crate) async fn translate_path_at<'a, T: Guest<Sandbox>>(
     guest: &'a mut T,
+    dirfd: i32,
     path_addr: PathPtr<'a>,
     mount_table: &MountTable,
 ) -> Result<Option<PathPtr<'a>>, Error> {
@@ -52,10 +108,16 @@ pub(crate) async fn translate_path<'a, T: Guest<Sandbox>>(
         return Ok(None);
     }
 
+    // Relative paths follow the virtual working directory, if there is one
+    let resolved = resolve_at_cwd(guest.pid().as_raw(), dirfd, &path);
+
     // Resolve through mount table to get the translated host path
-    let (_vfs, translated_path) = match mount_table.resolve(&path) {
-        Some(result) => result,
-        None => return Ok(None), // No mount point matches, use original path
+    let translated_path = match mount_table.resolve(resolved.as_deref().unwrap_or(path.as_path())) {
+        Some((_vfs, translated_path)) => translated_path,
+        None => match resolved {
+            Some(resolved) => resolved,
+            None => return Ok(None), // No mount point matches, use original path
+        },
     };
 
     // Convert translated path to a C string for the syscall
@@ -85,6 +147,244 @@ pub(crate) async fn translate_path<'a, T: Guest<Sandbox>>(
     }))
 }
 
+/// Path arguments of a syscall, with the directory each is looked up from
+fn path_args<'a>(syscall: &'a Syscall) -> Vec<(i32, Option<PathPtr<'a>>)> {
+    let cwd = libc::AT_FDCWD;
+    match syscall {
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Open(args) => vec![(cwd, args.path())],
+        Syscall::Openat(args) => vec![(args.dirfd(), args.path())],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Stat(args) => vec![(cwd, args.path())],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Lstat(args) => vec![(cwd, args.path())],
+        Syscall::Statx(args) => vec![(args.dirfd(), args.path())],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Newfstatat(args) => vec![(args.dirfd(), args.path())],
+        #[cfg(target_arch = "aarch64")]
+        Syscall::Fstatat(args) => vec![(args.dirfd(), args.path())],
+        Syscall::Statfs(args) => vec![(cwd, args.path())],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Readlink(args) => vec![(cwd, args.path())],
+        Syscall::Readlinkat(args) => vec![(args.dirfd(), args.path())],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Symlink(args) => vec![(cwd, args.linkpath())],
+        Syscall::Symlinkat(args) => vec![(args.newdirfd(), args.linkpath())],
+        Syscall::Linkat(args) => vec![
+            (args.olddirfd(), args.oldpath()),
+            (args.newdirfd(), args.newpath()),
+        ],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Access(args) => vec![(cwd, args.path())],
+        Syscall::Faccessat(args) => vec![(args.dirfd(), args.path())],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Rename(args) => vec![(cwd, args.oldpath()), (cwd, args.newpath())],
+        Syscall::Renameat(args) => vec![
+            (args.olddirfd(), args.oldpath()),
+            (args.newdirfd(), args.newpath()),
+        ],
+        Syscall::Renameat2(args) => vec![
+            (args.olddirfd(), args.oldpath()),
+            (args.newdirfd(), args.newpath()),
+        ],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Unlink(args) => vec![(cwd, args.path())],
+        Syscall::Unlinkat(args) => vec![(args.dirfd(), args.path())],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Mkdir(args) => vec![(cwd, args.path())],
+        Syscall::Mkdirat(args) => vec![(args.dirfd(), args.path())],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Rmdir(args) => vec![(cwd, args.path())],
+        Syscall::Chdir(args) => vec![(cwd, args.path())],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Chmod(args) => vec![(cwd, args.path())],
+        Syscall::Fchmodat(args) => vec![(args.dirfd(), args.path())],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Chown(args) => vec![(cwd, args.path())],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Lchown(args) => vec![(cwd, args.path())],
+        Syscall::Fchownat(args) => vec![(args.dirfd(), args.path())],
+        Syscall::Lgetxattr(args) => vec![(cwd, args.path())],
+        Syscall::Llistxattr(args) => vec![(cwd, args.path())],
+        Syscall::Execve(args) => vec![(cwd, args.path())],
+        _ => Vec::new(),
+    }
+}
+
+/// Resolve a path argument to an absolute path, as the kernel would look it up
+///
+/// Relative paths are resolved against the virtual working directory or the
+/// directory file descriptor, falling back to the kernel's view in `/proc`.
+/// Returns `None` if the base directory cannot be determined.
+fn absolute_path(pid: i32, dirfd: i32, path: &Path, fd_table: &FdTable) -> Option<PathBuf> {
+    if path.is_absolute() {
+        return Some(normalize_path(path));
+    }
+    let base = if dirfd == libc::AT_FDCWD {
+        match sandbox::virtual_cwd(pid) {
+            Some(cwd) => cwd,
+            None => std::fs::read_link(format!("/proc/{}/cwd", pid)).ok()?,
+        }
+    } else {
+        let entry = fd_table.get(dirfd);
+        match entry.as_ref().and_then(|entry| entry.path()) {
+            Some(path) => path.clone(),
+            None => {
+                let kernel_fd = entry.and_then(|entry| entry.kernel_fd()).unwrap_or(dirfd);
+                std::fs::read_link(format!("/proc/{}/fd/{}", pid, kernel_fd)).ok()?
+            }
+        }
+    };
+    Some(normalize_path(&base.join(path)))
+}
+
+/// Check if any path argument of a syscall is hidden by the deny list
+fn is_denied<T: Guest<Sandbox>>(
+    guest: &T,
+    syscall: &Syscall,
+    deny_list: &DenyList,
+    fd_table: &FdTable,
+) -> Result<bool, Error> {
+    let pid = guest.pid().as_raw();
+    for (dirfd, path_addr) in path_args(syscall) {
+        let Some(path_addr) = path_addr else {
+            continue;
+        };
+        let path: PathBuf = path_addr.read(&guest.memory())?;
+        if let Some(path) = absolute_path(pid, dirfd, &path, fd_table) {
+            if deny_list.is_denied(&path) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// Check the path arguments of a syscall with the mounts they lie under
+///
+/// Returns the errno to fail the syscall with if a mount refuses a path.
+fn refused_path<T: Guest<Sandbox>>(
+    guest: &T,
+    syscall: &Syscall,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<i32>, Error> {
+    let pid = guest.pid().as_raw();
+    for (dirfd, path_addr) in path_args(syscall) {
+        let Some(path_addr) = path_addr else {
+            continue;
+        };
+        let path: PathBuf = path_addr.read(&guest.memory())?;
+        let Some(path) = absolute_path(pid, dirfd, &path, fd_table) else {
+            continue;
+        };
+        match mount_table.check_path(&path) {
+            Ok(()) => {}
+            Err(err @ (VfsError::SymlinkLoop | VfsError::NotFound)) => {
+                return Ok(Some(err.to_errno()))
+            }
+            // Any other refusal means the mount hides the path
+            Err(_) => return Ok(Some(libc::EACCES)),
+        }
+    }
+    Ok(None)
+}
+
+/// Path arguments a syscall would create, remove or modify
+///
+/// Opens only count when they could write: for writing, creating or
+/// truncating.
+fn modified_paths<'a>(syscall: &'a Syscall) -> Vec<(i32, Option<PathPtr<'a>>)> {
+    let cwd = libc::AT_FDCWD;
+    let writes = |flags: i32| {
+        flags & libc::O_ACCMODE != libc::O_RDONLY || flags & (libc::O_CREAT | libc::O_TRUNC) != 0
+    };
+    match syscall {
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Open(args) if writes(args.flags().bits()) => vec![(cwd, args.path())],
+        Syscall::Openat(args) if writes(args.flags().bits()) => {
+            vec![(args.dirfd(), args.path())]
+        }
+        // Only the new name of a hard link is written to
+        Syscall::Linkat(args) => vec![(args.newdirfd(), args.newpath())],
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Symlink(_)
+        | Syscall::Rename(_)
+        | Syscall::Unlink(_)
+        | Syscall::Mkdir(_)
+        | Syscall::Rmdir(_)
+        | Syscall::Chmod(_)
+        | Syscall::Chown(_)
+        | Syscall::Lchown(_) => path_args(syscall),
+        Syscall::Symlinkat(_)
+        | Syscall::Renameat(_)
+        | Syscall::Renameat2(_)
+        | Syscall::Unlinkat(_)
+        | Syscall::Mkdirat(_)
+        | Syscall::Fchmodat(_)
+        | Syscall::Fchownat(_) => path_args(syscall),
+        Syscall::Truncate(args) => vec![(cwd, args.path())],
+        Syscall::Setxattr(args) => vec![(cwd, args.path())],
+        Syscall::Lsetxattr(args) => vec![(cwd, args.path())],
+        Syscall::Removexattr(args) => vec![(cwd, args.path())],
+        Syscall::Lremovexattr(args) => vec![(cwd, args.path())],
+        Syscall::Utimensat(args) if args.path().is_some() => {
+            vec![(args.dirfd(), args.path())]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// File descriptor whose file a syscall would modify
+fn modified_fd(syscall: &Syscall) -> Option<i32> {
+    match syscall {
+        Syscall::Ftruncate(args) => Some(args.fd()),
+        Syscall::Fchmod(args) => Some(args.fd()),
+        Syscall::Fchown(args) => Some(args.fd()),
+        Syscall::Fsetxattr(args) => Some(args.fd()),
+        Syscall::Fremovexattr(args) => Some(args.fd()),
+        // futimens() is utimensat() on the FD itself
+        Syscall::Utimensat(args) if args.path().is_none() => Some(args.dirfd()),
+        _ => None,
+    }
+}
+
+/// Check if a syscall would modify a path in a read-only mount
+///
+/// Virtual mounts refuse writes themselves; this keeps the kernel from
+/// writing through passthrough mounts such as read-only bind mounts.
+fn modifies_read_only<T: Guest<Sandbox>>(
+    guest: &T,
+    syscall: &Syscall,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<bool, Error> {
+    if let Some(fd) = modified_fd(syscall) {
+        if let Some(FdEntry::Passthrough {
+            path: Some(path), ..
+        }) = fd_table.get(fd)
+        {
+            if mount_table.is_read_only(&path) {
+                return Ok(true);
+            }
+        }
+    }
+
+    let pid = guest.pid().as_raw();
+    for (dirfd, path_addr) in modified_paths(syscall) {
+        let Some(path_addr) = path_addr else {
+            continue;
+        };
+        let path: PathBuf = path_addr.read(&guest.memory())?;
+        if let Some(path) = absolute_path(pid, dirfd, &path, fd_table) {
+            if mount_table.is_read_only(&path) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
 /// System call dispatch.
 ///
 /// This function dispatches a system call to the appropriate handler if the
@@ -99,6 +399,25 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
     mount_table: &MountTable,
     fd_table: &FdTable,
 ) -> Result<SyscallResult, Error> {
+    // Denied paths are hidden before any mount translation
+    if let Some(deny_list) = sandbox::deny_list() {
+        if is_denied(guest, &syscall, deny_list, fd_table)? {
+            return Ok(SyscallResult::Value(-libc::ENOENT as i64));
+        }
+    }
+
+    // Mounts that confine symlinks refuse paths leading out of them
+    if mount_table.checks_paths() {
+        if let Some(errno) = refused_path(guest, &syscall, mount_table, fd_table)? {
+            return Ok(SyscallResult::Value(-errno as i64));
+        }
+    }
+
+    // Read-only mounts refuse changes before the kernel can make them
+    if mount_table.has_read_only() && modifies_read_only(guest, &syscall, mount_table, fd_table)? {
+        return Ok(SyscallResult::Value(-libc::EROFS as i64));
+    }
+
     // FIXME: We need to intercept all system calls that use a path or file descriptor.
     match &syscall {
         Syscall::Openat(args) => {
@@ -197,15 +516,22 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
             }
         }
         Syscall::Statfs(args) => {
-            if let Some(modified) = stat::handle_statfs(guest, args, mount_table).await? {
-                Ok(SyscallResult::Syscall(modified))
+            if let Some(result) = stat::handle_statfs(guest, args, mount_table).await? {
+                Ok(result)
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Fstatfs(args) => {
+            if let Some(result) = stat::handle_fstatfs(guest, args, mount_table, fd_table).await? {
+                Ok(result)
             } else {
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
         #[cfg(not(target_arch = "aarch64"))]
         Syscall::Readlink(args) => {
-            if let Some(result) = stat::handle_readlink(guest, args, mount_table).await? {
+            if let Some(result) = stat::handle_readlink(guest, args, mount_table, fd_table).await? {
                 Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
@@ -271,6 +597,13 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
+        Syscall::Flock(args) => {
+            if let Some(result) = lock::handle_flock(guest, args, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
         Syscall::Pselect6(args) => {
             if let Some(result) = file::handle_pselect6(guest, args, fd_table).await? {
                 Ok(SyscallResult::Value(result))
@@ -286,8 +619,49 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
+        Syscall::EpollCreate1(args) => {
+            if let Some(result) = file::handle_epoll_create1(guest, args, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::EpollCreate(args) => {
+            if let Some(result) = file::handle_epoll_create(guest, args, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::EpollCtl(args) => {
+            if let Some(result) = file::handle_epoll_ctl(guest, args, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::EpollWait(args) => {
+            if let Some(result) = file::handle_epoll_wait(guest, args, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::EpollPwait(args) => {
+            if let Some(result) = file::handle_epoll_pwait(guest, args, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
         Syscall::Getdents64(args) => file::handle_getdents64(guest, syscall, args, fd_table).await,
-        Syscall::Fstat(args) => file::handle_fstat(guest, syscall, args, fd_table).await,
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Getdents(args) => file::handle_getdents(guest, syscall, args, fd_table).await,
+        Syscall::Fstat(args) => {
+            file::handle_fstat(guest, syscall, args, fd_table, mount_table).await
+        }
         #[cfg(target_arch = "aarch64")]
         Syscall::Fstatat(args) => {
             file::handle_fstatat(guest, syscall, args, fd_table, mount_table).await
@@ -395,13 +769,7 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
         Syscall::Madvise(_) => Ok(SyscallResult::Syscall(syscall)),
         // Path-based file operations
         #[cfg(not(target_arch = "aarch64"))]
-        Syscall::Access(args) => {
-            if let Some(modified) = file::handle_access(guest, args, mount_table).await? {
-                Ok(SyscallResult::Syscall(modified))
-            } else {
-                Ok(SyscallResult::Syscall(syscall))
-            }
-        }
+        Syscall::Access(args) => file::handle_access(guest, syscall, args, mount_table).await,
         Syscall::Faccessat(args) => {
             if let Some(result) = file::handle_faccessat(guest, args, mount_table, fd_table).await?
             {
@@ -412,10 +780,21 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
         }
         #[cfg(not(target_arch = "aarch64"))]
         Syscall::Rename(args) => {
-            if let Some(modified) = file::handle_rename(guest, args, mount_table).await? {
-                Ok(SyscallResult::Syscall(modified))
-            } else {
-                Ok(SyscallResult::Syscall(syscall))
+            match file::handle_rename(guest, args, mount_table, fd_table).await? {
+                Some(result) => Ok(result),
+                None => Ok(SyscallResult::Syscall(syscall)),
+            }
+        }
+        Syscall::Renameat(args) => {
+            match file::handle_renameat(guest, args, mount_table, fd_table).await? {
+                Some(result) => Ok(result),
+                None => Ok(SyscallResult::Syscall(syscall)),
+            }
+        }
+        Syscall::Renameat2(args) => {
+            match file::handle_renameat2(guest, args, mount_table, fd_table).await? {
+                Some(result) => Ok(result),
+                None => Ok(SyscallResult::Syscall(syscall)),
             }
         }
         #[cfg(not(target_arch = "aarch64"))]
@@ -426,9 +805,37 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
-        Syscall::Chdir(args) => {
-            if let Some(modified) = file::handle_chdir(guest, args, mount_table).await? {
-                Ok(SyscallResult::Syscall(modified))
+        Syscall::Chdir(args) => file::handle_chdir(guest, syscall, args, mount_table).await,
+        Syscall::Fchdir(args) => {
+            file::handle_fchdir(guest, syscall, args, mount_table, fd_table).await
+        }
+        Syscall::Getcwd(args) => file::handle_getcwd(guest, syscall, args).await,
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Chmod(args) => {
+            if let Some(result) = file::handle_chmod(guest, args, mount_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Fchmodat(args) => {
+            if let Some(result) = file::handle_fchmodat(guest, args, mount_table, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::Fchmod(args) => {
+            if let Some(result) = file::handle_fchmod(guest, args, mount_table, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        #[cfg(not(target_arch = "aarch64"))]
+        Syscall::Chown(_) | Syscall::Lchown(_) => {
+            if let Some(result) = file::handle_chown(guest, &syscall, mount_table).await? {
+                Ok(SyscallResult::Value(result))
             } else {
                 Ok(SyscallResult::Syscall(syscall))
             }
@@ -440,16 +847,41 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
                 Ok(SyscallResult::Syscall(syscall))
             }
         }
+        Syscall::Fchown(args) => {
+            if let Some(result) = file::handle_fchown(guest, args, mount_table, fd_table).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
         // Threading and synchronization - passthrough
         Syscall::SetTidAddress(_) => Ok(SyscallResult::Syscall(syscall)),
         Syscall::SetRobustList(_) => Ok(SyscallResult::Syscall(syscall)),
         Syscall::Futex(_) => Ok(SyscallResult::Syscall(syscall)),
-        // Time - passthrough
+        // Time - served from the fake clock when enabled, otherwise passthrough
         #[cfg(not(target_arch = "aarch64"))]
-        Syscall::Time(_) => Ok(SyscallResult::Syscall(syscall)),
-        Syscall::ClockGettime(_) => Ok(SyscallResult::Syscall(syscall)),
+        Syscall::Time(args) => {
+            if let Some(result) = time::handle_time(guest, args).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
+        Syscall::ClockGettime(args) => {
+            if let Some(result) = time::handle_clock_gettime(guest, args).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
         Syscall::ClockGetres(_) => Ok(SyscallResult::Syscall(syscall)),
-        Syscall::Gettimeofday(_) => Ok(SyscallResult::Syscall(syscall)),
+        Syscall::Gettimeofday(args) => {
+            if let Some(result) = time::handle_gettimeofday(guest, args).await? {
+                Ok(SyscallResult::Value(result))
+            } else {
+                Ok(SyscallResult::Syscall(syscall))
+            }
+        }
         // Random - passthrough
         Syscall::Getrandom(_) => Ok(SyscallResult::Syscall(syscall)),
         // Resource limits - passthrough
@@ -480,7 +912,9 @@ pub async fn dispatch_syscall<T: Guest<Sandbox>>(
                 Sysno::rseq => Ok(SyscallResult::Syscall(syscall)), // rseq - passthrough
                 Sysno::lseek => Ok(SyscallResult::Syscall(syscall)),
                 Sysno::fchmodat => {
-                    if let Some(result) = file::handle_chmod(guest, args, mount_table).await? {
+                    if let Some(result) =
+                        file::handle_fchmodat_raw(guest, args, mount_table, fd_table).await?
+                    {
                         Ok(SyscallResult::Value(result))
                     } else {
                         Ok(SyscallResult::Syscall(syscall))
@@ -515,3 +949,39 @@ pub enum SyscallResult {
     /// Handler modified the syscall, which should be tail-injected
     Syscall(Syscall),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path(Path::new("/agent/./a/../b")),
+            Path::new("/agent/b")
+        );
+        assert_eq!(
+            normalize_path(Path::new("/agent/../../etc")),
+            Path::new("/etc")
+        );
+        assert_eq!(
+            normalize_path(Path::new("/agent/dir/")),
+            Path::new("/agent/dir")
+        );
+    }
+
+    #[test]
+    fn test_modified_fd() {
+        use reverie::syscalls::{Fsetxattr, Ftruncate, Read, Utimensat};
+
+        let ftruncate = Syscall::Ftruncate(Ftruncate::new().with_fd(5));
+        assert_eq!(modified_fd(&ftruncate), Some(5));
+        let fsetxattr = Syscall::Fsetxattr(Fsetxattr::new().with_fd(6));
+        assert_eq!(modified_fd(&fsetxattr), Some(6));
+        // futimens()
+        let utimensat = Syscall::Utimensat(Utimensat::new().with_dirfd(7));
+        assert_eq!(modified_fd(&utimensat), Some(7));
+        assert!(modified_paths(&utimensat).is_empty());
+        assert_eq!(modified_fd(&Syscall::Read(Read::new().with_fd(5))), None);
+    }
+}
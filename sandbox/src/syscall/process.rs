@@ -1,6 +1,11 @@
 use crate::{sandbox, sandbox::Sandbox, vfs::fdtable::FdTable};
 use reverie::{syscalls::Syscall, Error, Guest};
 
+/// Give a newly created child the virtual working directory of its parent
+fn inherit_virtual_cwd<T: Guest<Sandbox>>(guest: &T, child: i32) {
+    sandbox::set_virtual_cwd(child, sandbox::virtual_cwd(guest.pid().as_raw()));
+}
+
 /// The `fork` system call.
 ///
 /// This intercepts `fork` system calls to properly handle FD table inheritance.
@@ -21,6 +26,7 @@ pub async fn handle_fork<T: Guest<Sandbox>>(
         // Create a deep copy of our FD table for the child
         let child_fd_table = parent_fd_table.deep_clone();
         sandbox::insert_fd_table(result as i32, child_fd_table);
+        inherit_virtual_cwd(guest, result as i32);
     }
     // If result == 0, we're in the child - the FD table was already set up by the parent
     // If result < 0, fork failed - no action needed
@@ -52,6 +58,7 @@ pub async fn handle_vfork<T: Guest<Sandbox>>(
         // since the child will exec or exit, and we need independent FD tracking)
         let child_fd_table = parent_fd_table.deep_clone();
         sandbox::insert_fd_table(result as i32, child_fd_table);
+        inherit_virtual_cwd(guest, result as i32);
     }
 
     Ok(Some(result))
@@ -90,6 +97,7 @@ pub async fn handle_clone<T: Guest<Sandbox>>(
             let child_fd_table = parent_fd_table.deep_clone();
             sandbox::insert_fd_table(result as i32, child_fd_table);
         }
+        inherit_virtual_cwd(guest, result as i32);
     }
     // If result == 0, we're in the child - FD table already set up by parent
     // If result < 0, clone failed
@@ -116,6 +124,7 @@ pub async fn handle_clone3<T: Guest<Sandbox>>(
         // TODO: Parse clone_args to check CLONE_FILES flag
         let child_fd_table = parent_fd_table.deep_clone();
         sandbox::insert_fd_table(result as i32, child_fd_table);
+        inherit_virtual_cwd(guest, result as i32);
     }
 
     Ok(Some(result))
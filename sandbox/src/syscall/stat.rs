@@ -1,12 +1,19 @@
 use crate::{
     sandbox::Sandbox,
-    syscall::translate_path,
-    vfs::{fdtable::FdTable, mount::MountTable},
+    syscall::{resolve_at_cwd, translate_path, translate_path_at, SyscallResult},
+    vfs::{
+        fdtable::{FdEntry, FdTable},
+        file::BoxedFileOps,
+        mount::MountTable,
+        VfsResult,
+    },
 };
 use reverie::{
-    syscalls::{MemoryAccess, ReadAddr, Syscall},
+    syscalls::{AddrMut, AtFlags, MemoryAccess, ReadAddr, Syscall},
     Error, Guest, Stack,
 };
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// The `statx` system call.
 ///
@@ -31,18 +38,20 @@ pub async fn handle_statx<T: Guest<Sandbox>>(
     if let Some(path_addr) = args.path() {
         // Read the original path from guest memory
         let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+        let path = resolve_at_cwd(guest.pid().as_raw(), dirfd, &path).unwrap_or(path);
 
         // Check if this path matches a mount point
         if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
-            // Check if this is a virtual VFS (like SQLite)
-            if vfs.is_virtual() {
-                // For virtual VFS, statx is not supported - return ENOSYS
+            // Check if this is a virtual VFS (like SQLite) or one that rewrites ownership
+            if vfs.is_virtual() || vfs.remaps_ownership() {
+                // For these mounts, statx is not supported - return ENOSYS
                 // The caller will fall back to newfstatat
                 return Ok(Some(-libc::ENOSYS as i64));
             }
         }
 
-        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        if let Some(new_path_addr) = translate_path_at(guest, dirfd, path_addr, mount_table).await?
+        {
             let new_syscall = reverie::syscalls::Statx::new()
                 .with_dirfd(kernel_dirfd)
                 .with_path(Some(new_path_addr))
@@ -70,7 +79,6 @@ pub async fn handle_newfstatat<T: Guest<Sandbox>>(
     mount_table: &MountTable,
     fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
-    use reverie::syscalls::AtFlags;
     let dirfd = args.dirfd();
     // AT_FDCWD is -100
     let kernel_dirfd = if dirfd == -100 {
@@ -82,11 +90,13 @@ pub async fn handle_newfstatat<T: Guest<Sandbox>>(
     if let Some(path_addr) = args.path() {
         // Read the original path from guest memory
         let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+        let path = resolve_at_cwd(guest.pid().as_raw(), dirfd, &path).unwrap_or(path);
 
         // Check if this path matches a mount point
         if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
-            // Check if this is a virtual VFS (like SQLite)
-            if vfs.is_virtual() {
+            // Virtual VFS (like SQLite) and mounts that rewrite ownership are
+            // stat'ed through the VFS instead of the kernel
+            if vfs.is_virtual() || vfs.remaps_ownership() {
                 let flags = args.flags();
                 let follow_symlinks = !flags.contains(AtFlags::AT_SYMLINK_NOFOLLOW);
 
@@ -114,19 +124,15 @@ pub async fn handle_newfstatat<T: Guest<Sandbox>>(
                         return Ok(Some(0)); // Success
                     }
                     Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EIO as i64,
-                        };
+                        let errno = -e.to_errno() as i64;
                         return Ok(Some(errno));
                     }
                 }
             }
         }
 
-        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        if let Some(new_path_addr) = translate_path_at(guest, dirfd, path_addr, mount_table).await?
+        {
             let new_syscall = reverie::syscalls::Newfstatat::new()
                 .with_dirfd(kernel_dirfd)
                 .with_path(Some(new_path_addr))
@@ -142,22 +148,104 @@ pub async fn handle_newfstatat<T: Guest<Sandbox>>(
 
 /// The `statfs` system call.
 ///
-/// This intercepts `statfs` system calls and translates paths according to the mount table.
+/// Paths on virtual mounts are answered from [`Vfs::statfs`](crate::vfs::Vfs::statfs);
+/// other paths are translated according to the mount table.
+/// Returns `None` if the original syscall should be used.
 pub async fn handle_statfs<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Statfs,
     mount_table: &MountTable,
-) -> Result<Option<Syscall>, Error> {
+) -> Result<Option<SyscallResult>, Error> {
     if let Some(path_addr) = args.path() {
+        let path: PathBuf = path_addr.read(&guest.memory())?;
+        let path = resolve_at_cwd(guest.pid().as_raw(), libc::AT_FDCWD, &path).unwrap_or(path);
+
+        if let Some((vfs, _)) = mount_table.resolve(&path) {
+            if vfs.is_virtual() {
+                // statfs fails like stat for paths that don't exist
+                let result = match vfs.stat(&path).await {
+                    Ok(_) => write_statfs(guest, vfs.statfs().await, args.buf()).await?,
+                    Err(e) => -e.to_errno() as i64,
+                };
+                return Ok(Some(SyscallResult::Value(result)));
+            }
+        }
+
         if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
             let new_syscall = args.with_path(Some(new_path_addr));
 
-            return Ok(Some(Syscall::Statfs(new_syscall)));
+            return Ok(Some(SyscallResult::Syscall(Syscall::Statfs(new_syscall))));
         }
     }
     Ok(None)
 }
 
+/// The `fstatfs` system call.
+///
+/// Virtual files are answered from the [`Vfs`](crate::vfs::Vfs) of the mount
+/// they were opened from, and passthrough FDs are translated to kernel FDs.
+/// Returns `None` if the original syscall should be used.
+pub async fn handle_fstatfs<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Fstatfs,
+    mount_table: &MountTable,
+    fd_table: &FdTable,
+) -> Result<Option<SyscallResult>, Error> {
+    match fd_table.get(args.fd()) {
+        Some(FdEntry::Passthrough { kernel_fd, .. }) => Ok(Some(SyscallResult::Syscall(
+            Syscall::Fstatfs(args.with_fd(kernel_fd)),
+        ))),
+        Some(FdEntry::Virtual { path, .. }) => {
+            let vfs = path.as_deref().and_then(|path| mount_table.resolve(path));
+            let result = match vfs {
+                Some((vfs, _)) => write_statfs(guest, vfs.statfs().await, args.buf()).await?,
+                // An unlinked O_TMPFILE has no path to find its mount by
+                None => -libc::ENOSYS as i64,
+            };
+            Ok(Some(SyscallResult::Value(result)))
+        }
+        // FD not in table, let the original syscall through (will likely fail with EBADF)
+        None => Ok(None),
+    }
+}
+
+/// Filesystem type reported for virtual mounts, the one FUSE mounts of
+/// AgentFS report too
+const FUSE_SUPER_MAGIC: i64 = 0x65735546;
+
+/// Write the result of [`Vfs::statfs`](crate::vfs::Vfs::statfs) to a guest
+/// `struct statfs`, returning the syscall result
+async fn write_statfs<T: Guest<Sandbox>>(
+    guest: &mut T,
+    result: VfsResult<libc::statvfs>,
+    buf: Option<AddrMut<'_, libc::statfs>>,
+) -> Result<i64, Error> {
+    let stats = match result {
+        Ok(stats) => stats,
+        Err(e) => return Ok(-e.to_errno() as i64),
+    };
+    let Some(buf) = buf else {
+        return Ok(-libc::EFAULT as i64);
+    };
+
+    let mut statfs: libc::statfs = unsafe { std::mem::zeroed() };
+    statfs.f_type = FUSE_SUPER_MAGIC as _;
+    statfs.f_bsize = stats.f_bsize as _;
+    statfs.f_frsize = stats.f_frsize as _;
+    statfs.f_blocks = stats.f_blocks as _;
+    statfs.f_bfree = stats.f_bfree as _;
+    statfs.f_bavail = stats.f_bavail as _;
+    statfs.f_files = stats.f_files as _;
+    statfs.f_ffree = stats.f_ffree as _;
+    statfs.f_namelen = stats.f_namemax as _;
+    statfs.f_flags = (stats.f_flag | ST_VALID) as _;
+    guest.memory().write_value(buf, &statfs)?;
+    Ok(0)
+}
+
+/// `f_flags` bit telling callers the other flags are filled in
+const ST_VALID: libc::c_ulong = 0x0020;
+
 /// The `readlink` system call.
 ///
 /// This intercepts `readlink` system calls and translates paths according to the mount table.
@@ -166,9 +254,27 @@ pub async fn handle_readlink<T: Guest<Sandbox>>(
     guest: &mut T,
     args: &reverie::syscalls::Readlink,
     mount_table: &MountTable,
+    fd_table: &FdTable,
 ) -> Result<Option<i64>, Error> {
     if let Some(path_addr) = args.path() {
         let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+        let path = resolve_at_cwd(guest.pid().as_raw(), libc::AT_FDCWD, &path).unwrap_or(path);
+
+        if let Some(link) = proc_fd_link(&path, guest.pid().as_raw(), fd_table) {
+            let target = match link {
+                Ok(target) => target,
+                Err(errno) => return Ok(Some(errno)),
+            };
+            let target_str = target.to_string_lossy();
+            let target_bytes = target_str.as_bytes();
+            let bytes_to_write = std::cmp::min(target_bytes.len(), args.bufsize());
+            if let Some(buf_addr) = args.buf() {
+                guest
+                    .memory()
+                    .write_exact(buf_addr.cast::<u8>(), &target_bytes[..bytes_to_write])?;
+            }
+            return Ok(Some(bytes_to_write as i64));
+        }
 
         // Check if this path matches a mount point
         if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
@@ -194,12 +300,7 @@ pub async fn handle_readlink<T: Guest<Sandbox>>(
                         return Ok(Some(0));
                     }
                     Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EINVAL as i64,
-                        };
+                        let errno = -e.to_errno() as i64;
                         return Ok(Some(errno));
                     }
                 }
@@ -239,6 +340,23 @@ pub async fn handle_readlinkat<T: Guest<Sandbox>>(
 
     if let Some(path_addr) = args.path() {
         let path: std::path::PathBuf = path_addr.read(&guest.memory())?;
+        let path = resolve_at_cwd(guest.pid().as_raw(), dirfd, &path).unwrap_or(path);
+
+        if let Some(link) = proc_fd_link(&path, guest.pid().as_raw(), fd_table) {
+            let target = match link {
+                Ok(target) => target,
+                Err(errno) => return Ok(Some(errno)),
+            };
+            let target_str = target.to_string_lossy();
+            let target_bytes = target_str.as_bytes();
+            let bytes_to_write = std::cmp::min(target_bytes.len(), args.buf_len());
+            if let Some(buf_addr) = args.buf() {
+                guest
+                    .memory()
+                    .write_exact(buf_addr.cast::<u8>(), &target_bytes[..bytes_to_write])?;
+            }
+            return Ok(Some(bytes_to_write as i64));
+        }
 
         // Check if this path matches a mount point
         if let Some((vfs, _translated_path)) = mount_table.resolve(&path) {
@@ -264,19 +382,15 @@ pub async fn handle_readlinkat<T: Guest<Sandbox>>(
                         return Ok(Some(0));
                     }
                     Err(e) => {
-                        // Map VFS errors to errno
-                        let errno = match e {
-                            crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                            crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                            _ => -libc::EINVAL as i64,
-                        };
+                        let errno = -e.to_errno() as i64;
                         return Ok(Some(errno));
                     }
                 }
             }
         }
 
-        if let Some(new_path_addr) = translate_path(guest, path_addr, mount_table).await? {
+        if let Some(new_path_addr) = translate_path_at(guest, dirfd, path_addr, mount_table).await?
+        {
             let new_syscall = reverie::syscalls::Readlinkat::new()
                 .with_dirfd(kernel_dirfd)
                 .with_path(Some(new_path_addr))
@@ -305,6 +419,8 @@ pub async fn handle_symlink<T: Guest<Sandbox>>(
     // Read the linkpath from guest memory
     if let Some(linkpath_addr) = args.linkpath() {
         let linkpath: std::path::PathBuf = linkpath_addr.read(&guest.memory())?;
+        let linkpath =
+            resolve_at_cwd(guest.pid().as_raw(), libc::AT_FDCWD, &linkpath).unwrap_or(linkpath);
 
         // Read the target from guest memory
         if let Some(target_addr) = args.target() {
@@ -318,13 +434,7 @@ pub async fn handle_symlink<T: Guest<Sandbox>>(
                     match vfs.symlink(&target, &linkpath).await {
                         Ok(()) => return Ok(Some(0)), // Success
                         Err(e) => {
-                            // Map VFS errors to errno
-                            let errno = match e {
-                                crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                                crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                                crate::vfs::VfsError::AlreadyExists => -libc::EEXIST as i64,
-                                _ => -libc::EIO as i64,
-                            };
+                            let errno = -e.to_errno() as i64;
                             return Ok(Some(errno));
                         }
                     }
@@ -370,6 +480,7 @@ pub async fn handle_symlinkat<T: Guest<Sandbox>>(
     // Read linkpath and target from guest memory
     if let Some(linkpath_addr) = args.linkpath() {
         let linkpath: std::path::PathBuf = linkpath_addr.read(&guest.memory())?;
+        let linkpath = resolve_at_cwd(guest.pid().as_raw(), dirfd, &linkpath).unwrap_or(linkpath);
 
         if let Some(target_addr) = args.target() {
             let target: std::path::PathBuf = target_addr.read(&guest.memory())?;
@@ -382,13 +493,7 @@ pub async fn handle_symlinkat<T: Guest<Sandbox>>(
                     match vfs.symlink(&target, &linkpath).await {
                         Ok(()) => return Ok(Some(0)), // Success
                         Err(e) => {
-                            // Map VFS errors to errno
-                            let errno = match e {
-                                crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
-                                crate::vfs::VfsError::PermissionDenied => -libc::EACCES as i64,
-                                crate::vfs::VfsError::AlreadyExists => -libc::EEXIST as i64,
-                                _ => -libc::EIO as i64,
-                            };
+                            let errno = -e.to_errno() as i64;
                             return Ok(Some(errno));
                         }
                     }
@@ -396,7 +501,7 @@ pub async fn handle_symlinkat<T: Guest<Sandbox>>(
             }
 
             if let Some(new_linkpath_addr) =
-                translate_path(guest, linkpath_addr, mount_table).await?
+                translate_path_at(guest, dirfd, linkpath_addr, mount_table).await?
             {
                 let new_syscall = reverie::syscalls::Symlinkat::new()
                     .with_target(args.target())
@@ -411,6 +516,79 @@ pub async fn handle_symlinkat<T: Guest<Sandbox>>(
     Ok(None)
 }
 
+/// Find the FD named by the old side of a `linkat` call, if any
+///
+/// Open files are linked either with `AT_EMPTY_PATH` and an empty path, which
+/// refers to `olddirfd` itself, or with `AT_SYMLINK_FOLLOW` through
+/// `/proc/self/fd/N`.
+fn linkat_source_fd(olddirfd: i32, oldpath: &Path, flags: AtFlags, pid: i32) -> Option<i32> {
+    if oldpath.as_os_str().is_empty() {
+        return flags.contains(AtFlags::AT_EMPTY_PATH).then_some(olddirfd);
+    }
+    if !flags.contains(AtFlags::AT_SYMLINK_FOLLOW) {
+        return None;
+    }
+    proc_self_fd(oldpath, pid)
+}
+
+/// Parse the FD out of a `/proc/self/fd/N` path
+///
+/// `/proc/thread-self/fd/N` and `/proc/<pid>/fd/N` with the caller's own pid
+/// name the same FD.
+fn proc_self_fd(path: &Path, pid: i32) -> Option<i32> {
+    let fd = path
+        .strip_prefix("/proc/self/fd")
+        .or_else(|_| path.strip_prefix("/proc/thread-self/fd"))
+        .or_else(|_| path.strip_prefix(format!("/proc/{}/fd", pid)))
+        .ok()?;
+    fd.to_str()?.parse().ok()
+}
+
+/// Resolve a `/proc/self/fd/N` symlink from the FD table
+///
+/// The kernel's `/proc` knows nothing of virtual FDs and numbers passthrough
+/// FDs differently, so these links are answered here: with the path the FD
+/// was opened with, or for passthrough FDs without one (pipes, sockets) with
+/// the kernel's link for the underlying FD. Returns `None` if `path` isn't
+/// such a link, otherwise the target or a negated errno.
+fn proc_fd_link(path: &Path, pid: i32, fd_table: &FdTable) -> Option<Result<PathBuf, i64>> {
+    let vfd = proc_self_fd(path, pid)?;
+    let Some(entry) = fd_table.get(vfd) else {
+        return Some(Err(-libc::ENOENT as i64));
+    };
+    if let Some(path) = entry.path() {
+        return Some(Ok(path.clone()));
+    }
+    Some(match entry.kernel_fd() {
+        Some(kernel_fd) => std::fs::read_link(format!("/proc/{}/fd/{}", pid, kernel_fd))
+            .map_err(|e| -(e.raw_os_error().unwrap_or(libc::EIO) as i64)),
+        None => Err(-libc::ENOENT as i64),
+    })
+}
+
+/// Link an open virtual file to `newpath`
+///
+/// The file can only be linked within the mount it was opened from.
+async fn link_virtual_fd(
+    file_ops: &BoxedFileOps,
+    fd_path: Option<&PathBuf>,
+    newpath: &Path,
+    mount_table: &MountTable,
+) -> i64 {
+    let source = fd_path.and_then(|path| mount_table.resolve(path));
+    let target = mount_table.resolve(newpath);
+    match (source, target) {
+        (Some((source, _)), Some((target, _))) if Arc::ptr_eq(&source, &target) => {
+            match target.link_fd(file_ops, newpath).await {
+                Ok(()) => 0,
+                Err(crate::vfs::VfsError::PermissionDenied) => -libc::EPERM as i64,
+                Err(e) => -e.to_errno() as i64,
+            }
+        }
+        _ => -libc::EXDEV as i64,
+    }
+}
+
 /// The `linkat` system call.
 ///
 /// This intercepts `linkat` system calls and translates paths according to the mount table
@@ -441,9 +619,24 @@ pub async fn handle_linkat<T: Guest<Sandbox>>(
     // Read oldpath and newpath from guest memory
     if let Some(oldpath_addr) = args.oldpath() {
         let oldpath: std::path::PathBuf = oldpath_addr.read(&guest.memory())?;
+        let oldpath = resolve_at_cwd(guest.pid().as_raw(), olddirfd, &oldpath).unwrap_or(oldpath);
 
         if let Some(newpath_addr) = args.newpath() {
             let newpath: std::path::PathBuf = newpath_addr.read(&guest.memory())?;
+            let newpath =
+                resolve_at_cwd(guest.pid().as_raw(), newdirfd, &newpath).unwrap_or(newpath);
+
+            // Linking an open virtual file (e.g. one created with O_TMPFILE) by its FD
+            let pid = guest.pid().as_raw();
+            if let Some(vfd) = linkat_source_fd(olddirfd, &oldpath, args.flags(), pid) {
+                if let Some(entry) = fd_table.get(vfd) {
+                    if let Some(file_ops) = entry.file_ops() {
+                        let result =
+                            link_virtual_fd(file_ops, entry.path(), &newpath, mount_table).await;
+                        return Ok(Some(result));
+                    }
+                }
+            }
 
             // Check if newpath matches a mount point with virtual VFS
             if let Some((vfs, _translated_path)) = mount_table.resolve(&newpath) {
@@ -453,12 +646,10 @@ pub async fn handle_linkat<T: Guest<Sandbox>>(
                     match vfs.link(&oldpath, &newpath).await {
                         Ok(()) => return Ok(Some(0)), // Success
                         Err(e) => {
-                            // Map VFS errors to errno
+                            // link(2) reports a refused link as EPERM
                             let errno = match e {
-                                crate::vfs::VfsError::NotFound => -libc::ENOENT as i64,
                                 crate::vfs::VfsError::PermissionDenied => -libc::EPERM as i64,
-                                crate::vfs::VfsError::AlreadyExists => -libc::EEXIST as i64,
-                                _ => -libc::EIO as i64,
+                                e => -e.to_errno() as i64,
                             };
                             return Ok(Some(errno));
                         }
@@ -477,12 +668,10 @@ pub async fn handle_linkat<T: Guest<Sandbox>>(
                     // Both paths need translation
                     use std::ffi::CString;
 
-                    let old_cstr =
-                        CString::new(translated_oldpath.to_string_lossy().to_string())
-                            .map_err(|_| reverie::syscalls::Errno::EINVAL)?;
-                    let new_cstr =
-                        CString::new(translated_newpath.to_string_lossy().to_string())
-                            .map_err(|_| reverie::syscalls::Errno::EINVAL)?;
+                    let old_cstr = CString::new(translated_oldpath.to_string_lossy().to_string())
+                        .map_err(|_| reverie::syscalls::Errno::EINVAL)?;
+                    let new_cstr = CString::new(translated_newpath.to_string_lossy().to_string())
+                        .map_err(|_| reverie::syscalls::Errno::EINVAL)?;
 
                     // Allocate space for both paths on guest stack
                     let old_bytes = old_cstr.as_bytes_with_nul();
@@ -498,12 +687,10 @@ pub async fn handle_linkat<T: Guest<Sandbox>>(
                     guest.memory().write_exact(old_byte_addr, old_bytes)?;
                     guest.memory().write_exact(new_byte_addr, new_bytes)?;
 
-                    let new_oldpath_ptr: reverie::syscalls::PathPtr = unsafe {
-                        std::mem::transmute(old_byte_addr)
-                    };
-                    let new_newpath_ptr: reverie::syscalls::PathPtr = unsafe {
-                        std::mem::transmute(new_byte_addr)
-                    };
+                    let new_oldpath_ptr: reverie::syscalls::PathPtr =
+                        unsafe { std::mem::transmute(old_byte_addr) };
+                    let new_newpath_ptr: reverie::syscalls::PathPtr =
+                        unsafe { std::mem::transmute(new_byte_addr) };
 
                     let new_syscall = reverie::syscalls::Linkat::new()
                         .with_olddirfd(kernel_olddirfd)
@@ -517,7 +704,7 @@ pub async fn handle_linkat<T: Guest<Sandbox>>(
                 (Some(_), None) => {
                     // Only oldpath needs translation
                     if let Some(new_oldpath_addr) =
-                        translate_path(guest, oldpath_addr, mount_table).await?
+                        translate_path_at(guest, olddirfd, oldpath_addr, mount_table).await?
                     {
                         let new_syscall = reverie::syscalls::Linkat::new()
                             .with_olddirfd(kernel_olddirfd)
@@ -532,7 +719,7 @@ pub async fn handle_linkat<T: Guest<Sandbox>>(
                 (None, Some(_)) => {
                     // Only newpath needs translation
                     if let Some(new_newpath_addr) =
-                        translate_path(guest, newpath_addr, mount_table).await?
+                        translate_path_at(guest, newdirfd, newpath_addr, mount_table).await?
                     {
                         let new_syscall = reverie::syscalls::Linkat::new()
                             .with_olddirfd(kernel_olddirfd)
@@ -552,3 +739,55 @@ pub async fn handle_linkat<T: Guest<Sandbox>>(
     }
     Ok(None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::fdtable::FdEntry;
+    use std::os::fd::AsRawFd;
+
+    #[test]
+    fn test_proc_fd_link() {
+        let pid = std::process::id() as i32;
+        let dir = tempfile::tempdir().unwrap();
+        let host_file = std::fs::File::create(dir.path().join("host.txt")).unwrap();
+        let host_path = std::fs::canonicalize(dir.path().join("host.txt")).unwrap();
+
+        let fd_table = FdTable::new();
+        let named = fd_table.allocate(FdEntry::Passthrough {
+            kernel_fd: host_file.as_raw_fd(),
+            flags: 0,
+            path: Some(PathBuf::from("/sandbox/file.txt")),
+        });
+        let unnamed = fd_table.allocate(FdEntry::Passthrough {
+            kernel_fd: host_file.as_raw_fd(),
+            flags: 0,
+            path: None,
+        });
+
+        // The path the FD was opened with wins over the kernel's view
+        let link = format!("/proc/self/fd/{}", named);
+        assert_eq!(
+            proc_fd_link(Path::new(&link), pid, &fd_table),
+            Some(Ok(PathBuf::from("/sandbox/file.txt")))
+        );
+
+        // Without one, the link of the underlying kernel FD is used
+        let link = format!("/proc/{}/fd/{}", pid, unnamed);
+        assert_eq!(
+            proc_fd_link(Path::new(&link), pid, &fd_table),
+            Some(Ok(host_path))
+        );
+
+        assert_eq!(
+            proc_fd_link(Path::new("/proc/thread-self/fd/99"), pid, &fd_table),
+            Some(Err(-libc::ENOENT as i64))
+        );
+        assert_eq!(
+            proc_fd_link(Path::new("/proc/self/fdinfo/0"), pid, &fd_table),
+            None
+        );
+        let other = format!("/proc/{}/fd/0", pid + 1);
+        assert_eq!(proc_fd_link(Path::new(&other), pid, &fd_table), None);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::sandbox::Sandbox;
+use reverie::{
+    syscalls::{Errno, MemoryAccess},
+    Error, Guest,
+};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    OnceLock,
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Global fake clock, set when `--fake-time` is used
+static FAKE_CLOCK: OnceLock<FakeClock> = OnceLock::new();
+
+/// A deterministic clock served to the sandboxed process
+///
+/// Every intercepted time syscall advances the clock by `step`, so the n-th
+/// call (counting from zero) observes `start + n * step`. Monotonic clocks
+/// report the elapsed fake time, starting at zero.
+#[derive(Debug)]
+pub struct FakeClock {
+    start_nanos: i128,
+    step_nanos: u64,
+    ticks: AtomicU64,
+}
+
+impl FakeClock {
+    /// Create a clock starting at `start` and advancing by `step` per call
+    pub fn new(start: SystemTime, step: Duration) -> Self {
+        let start_nanos = match start.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_nanos() as i128,
+            Err(e) => -(e.duration().as_nanos() as i128),
+        };
+        Self {
+            start_nanos,
+            step_nanos: step.as_nanos() as u64,
+            ticks: AtomicU64::new(0),
+        }
+    }
+
+    /// Advance the clock, returning the elapsed fake time in nanoseconds
+    fn tick(&self) -> i128 {
+        let ticks = self.ticks.fetch_add(1, Ordering::Relaxed);
+        ticks as i128 * self.step_nanos as i128
+    }
+
+    /// Advance the clock, returning the wall-clock time in nanoseconds since the epoch
+    fn realtime_nanos(&self) -> i128 {
+        self.start_nanos + self.tick()
+    }
+
+    /// Advance the clock, returning the monotonic time in nanoseconds
+    fn monotonic_nanos(&self) -> i128 {
+        self.tick()
+    }
+
+    /// Advance the clock for the given clock ID
+    ///
+    /// Returns `None` for clocks that are not faked (e.g. CPU-time clocks).
+    fn now_nanos(&self, clockid: libc::clockid_t) -> Option<i128> {
+        match clockid {
+            libc::CLOCK_REALTIME | libc::CLOCK_REALTIME_COARSE | libc::CLOCK_TAI => {
+                Some(self.realtime_nanos())
+            }
+            libc::CLOCK_MONOTONIC
+            | libc::CLOCK_MONOTONIC_RAW
+            | libc::CLOCK_MONOTONIC_COARSE
+            | libc::CLOCK_BOOTTIME => Some(self.monotonic_nanos()),
+            _ => None,
+        }
+    }
+}
+
+/// Initialize the fake clock
+///
+/// This must be called before spawning the traced process. Without it, time
+/// syscalls are passed through to the host.
+pub fn init_fake_time(start: SystemTime, step: Duration) {
+    FAKE_CLOCK
+        .set(FakeClock::new(start, step))
+        .expect("Fake clock already initialized");
+}
+
+/// Split nanoseconds since the epoch into whole seconds and the sub-second remainder
+fn split_nanos(nanos: i128) -> (i64, i64) {
+    (
+        nanos.div_euclid(1_000_000_000) as i64,
+        nanos.rem_euclid(1_000_000_000) as i64,
+    )
+}
+
+/// The `clock_gettime` system call.
+///
+/// Serves the fake clock for wall-clock and monotonic clocks.
+/// Returns `Some(result)` if the syscall was handled and the result should be returned directly,
+/// or `None` if the original syscall should be used.
+pub async fn handle_clock_gettime<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::ClockGettime,
+) -> Result<Option<i64>, Error> {
+    let Some(clock) = FAKE_CLOCK.get() else {
+        return Ok(None);
+    };
+    let Some(nanos) = clock.now_nanos(args.clockid()) else {
+        return Ok(None);
+    };
+    let tp = args.tp().ok_or(Error::Errno(Errno::EFAULT))?;
+
+    let (tv_sec, tv_nsec) = split_nanos(nanos);
+    let ts = libc::timespec { tv_sec, tv_nsec };
+    guest
+        .memory()
+        .write_value(tp.cast::<libc::timespec>(), &ts)?;
+
+    Ok(Some(0))
+}
+
+/// The `gettimeofday` system call.
+///
+/// Serves the fake wall-clock time. The timezone, if requested, is reported as UTC.
+pub async fn handle_gettimeofday<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Gettimeofday,
+) -> Result<Option<i64>, Error> {
+    let Some(clock) = FAKE_CLOCK.get() else {
+        return Ok(None);
+    };
+
+    if let Some(tv) = args.tv() {
+        let (tv_sec, nsec) = split_nanos(clock.realtime_nanos());
+        let tv_val = libc::timeval {
+            tv_sec,
+            tv_usec: nsec / 1_000,
+        };
+        guest
+            .memory()
+            .write_value(tv.cast::<libc::timeval>(), &tv_val)?;
+    }
+
+    if let Some(tz) = args.tz() {
+        // struct timezone { int tz_minuteswest; int tz_dsttime; }
+        guest.memory().write_exact(tz.cast::<u8>(), &[0u8; 8])?;
+    }
+
+    Ok(Some(0))
+}
+
+/// The `time` system call.
+///
+/// Serves the fake wall-clock time in whole seconds.
+#[cfg(not(target_arch = "aarch64"))]
+pub async fn handle_time<T: Guest<Sandbox>>(
+    guest: &mut T,
+    args: &reverie::syscalls::Time,
+) -> Result<Option<i64>, Error> {
+    let Some(clock) = FAKE_CLOCK.get() else {
+        return Ok(None);
+    };
+
+    let (secs, _) = split_nanos(clock.realtime_nanos());
+    if let Some(tloc) = args.tloc() {
+        guest.memory().write_value(tloc.cast::<i64>(), &secs)?;
+    }
+
+    Ok(Some(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fake_clock_steps() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let clock = FakeClock::new(start, Duration::from_millis(250));
+
+        assert_eq!(
+            split_nanos(clock.now_nanos(libc::CLOCK_REALTIME).unwrap()),
+            (1_700_000_000, 0)
+        );
+        assert_eq!(
+            split_nanos(clock.now_nanos(libc::CLOCK_REALTIME).unwrap()),
+            (1_700_000_000, 250_000_000)
+        );
+        assert_eq!(
+            split_nanos(clock.now_nanos(libc::CLOCK_MONOTONIC).unwrap()),
+            (0, 500_000_000)
+        );
+        assert!(clock.now_nanos(libc::CLOCK_PROCESS_CPUTIME_ID).is_none());
+    }
+
+    #[test]
+    fn test_fake_clock_before_epoch() {
+        let start = UNIX_EPOCH - Duration::from_millis(1_500);
+        let clock = FakeClock::new(start, Duration::ZERO);
+
+        assert_eq!(split_nanos(clock.realtime_nanos()), (-2, 500_000_000));
+        assert_eq!(split_nanos(clock.realtime_nanos()), (-2, 500_000_000));
+    }
+}
@@ -1,6 +1,12 @@
 use super::{Vfs, VfsError, VfsResult};
+use std::collections::VecDeque;
+use std::ffi::{CString, OsString};
+use std::os::unix::ffi::OsStrExt;
 use std::path::{Path, PathBuf};
 
+/// Most symlinks followed while resolving one path, like the kernel's limit
+const MAX_SYMLINKS: usize = 40;
+
 /// A bind mount VFS that maps a sandbox path to a host directory
 ///
 /// This implements a simple bind mount - it translates paths under
@@ -14,6 +20,14 @@ pub struct BindVfs {
     host_root: PathBuf,
     /// The virtual path as seen by the sandboxed process
     sandbox_root: PathBuf,
+    /// Owner uid reported for every file, hiding the host's real uids
+    uid_map: Option<u32>,
+    /// Owner gid reported for every file, hiding the host's real gids
+    gid_map: Option<u32>,
+    /// Canonical host root when symlinks are confined to the mount
+    confined_root: Option<PathBuf>,
+    /// Refuse syscalls that would modify the mount
+    read_only: bool,
 }
 
 impl BindVfs {
@@ -26,9 +40,52 @@ impl BindVfs {
         Self {
             host_root,
             sandbox_root,
+            uid_map: None,
+            gid_map: None,
+            confined_root: None,
+            read_only: false,
         }
     }
 
+    /// Make the mount read-only
+    ///
+    /// Files are still opened by the kernel, so the syscall layer refuses
+    /// opens for writing and changes to the tree with `EROFS` before they
+    /// reach it.
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Report every file in the mount as owned by the given uid and/or gid
+    pub fn with_owner(mut self, uid: Option<u32>, gid: Option<u32>) -> Self {
+        self.uid_map = uid;
+        self.gid_map = gid;
+        self
+    }
+
+    /// Keep symlinks from leading out of the mount
+    ///
+    /// Symlinks in the directories of a path are resolved against the sandbox
+    /// view: absolute targets are sandbox paths, and a target outside the
+    /// mount fails with `EACCES`. The last component is left for the kernel to
+    /// follow, but only if it resolves to a file inside the mount; otherwise
+    /// the path is refused by [`Vfs::check_path`], even for `lstat` or
+    /// `unlink`.
+    pub fn with_confined_symlinks(mut self, confined: bool) -> Self {
+        self.confined_root = if confined {
+            Some(std::fs::canonicalize(&self.host_root).unwrap_or_else(|_| self.host_root.clone()))
+        } else {
+            None
+        };
+        self
+    }
+
+    /// Check if symlinks are confined to the mount
+    pub fn confines_symlinks(&self) -> bool {
+        self.confined_root.is_some()
+    }
+
     /// Get the host root path
     pub fn host_root(&self) -> &Path {
         &self.host_root
@@ -38,6 +95,96 @@ impl BindVfs {
     pub fn sandbox_root(&self) -> &Path {
         &self.sandbox_root
     }
+
+    /// Resolve symlinks among the directories of `relative` inside the mount
+    ///
+    /// Returns the host path, whose directories contain no symlinks. `..` and
+    /// symlink targets that climb out of the mount fail with `PermissionDenied`.
+    fn resolve_confined(&self, relative: &Path) -> VfsResult<PathBuf> {
+        let mut resolved = PathBuf::new();
+        let mut remaining: VecDeque<OsString> = relative
+            .components()
+            .map(|c| c.as_os_str().to_os_string())
+            .collect();
+        let mut followed = 0;
+
+        while let Some(name) = remaining.pop_front() {
+            if name == "." || name.is_empty() {
+                continue;
+            }
+            if name == ".." {
+                if !resolved.pop() {
+                    return Err(VfsError::PermissionDenied);
+                }
+                continue;
+            }
+            // The last component is left for the kernel (see `check_path`)
+            if remaining.is_empty() {
+                resolved.push(&name);
+                break;
+            }
+
+            let host_path = self.host_root.join(&resolved).join(&name);
+            if !is_symlink(&host_path) {
+                resolved.push(&name);
+                continue;
+            }
+
+            followed += 1;
+            if followed > MAX_SYMLINKS {
+                return Err(VfsError::SymlinkLoop);
+            }
+            let target = std::fs::read_link(&host_path)?;
+            if target.is_absolute() {
+                // Absolute targets name sandbox paths, which must be in the mount
+                let inside = target
+                    .strip_prefix(&self.sandbox_root)
+                    .map_err(|_| VfsError::PermissionDenied)?;
+                resolved = PathBuf::new();
+                for component in inside.components().rev() {
+                    remaining.push_front(component.as_os_str().to_os_string());
+                }
+            } else {
+                for component in target.components().rev() {
+                    remaining.push_front(component.as_os_str().to_os_string());
+                }
+            }
+        }
+
+        if resolved.as_os_str().is_empty() {
+            Ok(self.host_root.clone())
+        } else {
+            Ok(self.host_root.join(resolved))
+        }
+    }
+
+    /// Stat the host file behind a sandbox path and apply the ownership override
+    fn host_stat(&self, path: &Path, follow_symlinks: bool) -> VfsResult<libc::stat> {
+        let host_path = self.translate_path(path)?;
+        let c_path = CString::new(host_path.as_os_str().as_bytes())
+            .map_err(|_| VfsError::InvalidInput("Path contains NUL byte".to_string()))?;
+
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        let ret = unsafe {
+            if follow_symlinks {
+                libc::stat(c_path.as_ptr(), &mut stat)
+            } else {
+                libc::lstat(c_path.as_ptr(), &mut stat)
+            }
+        };
+        if ret != 0 {
+            let err = std::io::Error::last_os_error();
+            return Err(match err.raw_os_error() {
+                Some(libc::ENOENT) => VfsError::NotFound,
+                Some(libc::EACCES) => VfsError::PermissionDenied,
+                Some(libc::ELOOP) => VfsError::SymlinkLoop,
+                _ => VfsError::IoError(err),
+            });
+        }
+
+        self.apply_owner_override(&mut stat);
+        Ok(stat)
+    }
 }
 
 #[async_trait::async_trait]
@@ -61,6 +208,10 @@ impl Vfs for BindVfs {
                 .unwrap_or("")
                 .trim_start_matches('/');
 
+            if self.confined_root.is_some() {
+                return self.resolve_confined(Path::new(relative));
+            }
+
             // Construct the host path
             let host_path = if relative.is_empty() {
                 self.host_root.clone()
@@ -74,10 +225,60 @@ impl Vfs for BindVfs {
         }
     }
 
+    fn kind(&self) -> &'static str {
+        "bind"
+    }
+
+    fn source(&self) -> Option<PathBuf> {
+        Some(self.host_root.clone())
+    }
+
     fn is_virtual(&self) -> bool {
         // Bind mounts are not virtual - they use real kernel file descriptors
         false
     }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn owner_override(&self) -> (Option<u32>, Option<u32>) {
+        (self.uid_map, self.gid_map)
+    }
+
+    fn checks_paths(&self) -> bool {
+        self.confines_symlinks()
+    }
+
+    fn check_path(&self, path: &Path) -> VfsResult<()> {
+        let Some(root) = &self.confined_root else {
+            return Ok(());
+        };
+        let host_path = self.translate_path(path)?;
+        match std::fs::canonicalize(&host_path) {
+            Ok(real) if real.starts_with(root) => Ok(()),
+            Ok(_) => Err(VfsError::PermissionDenied),
+            Err(e) if e.raw_os_error() == Some(libc::ELOOP) => Err(VfsError::SymlinkLoop),
+            // A dangling symlink could still create a file wherever it points
+            Err(_) if is_symlink(&host_path) => Err(VfsError::PermissionDenied),
+            Err(_) => Ok(()),
+        }
+    }
+
+    async fn stat(&self, path: &Path) -> VfsResult<libc::stat> {
+        self.host_stat(path, true)
+    }
+
+    async fn lstat(&self, path: &Path) -> VfsResult<libc::stat> {
+        self.host_stat(path, false)
+    }
+}
+
+/// Check if a host path is a symlink, without following it
+fn is_symlink(path: &Path) -> bool {
+    std::fs::symlink_metadata(path)
+        .map(|meta| meta.file_type().is_symlink())
+        .unwrap_or(false)
 }
 
 #[cfg(test)]
@@ -123,5 +324,78 @@ mod tests {
     fn test_is_not_virtual() {
         let vfs = BindVfs::new(PathBuf::from("/tmp/agent"), PathBuf::from("/agent"));
         assert!(!vfs.is_virtual());
+        assert!(!vfs.remaps_ownership());
+    }
+
+    #[test]
+    fn test_confined_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        std::fs::create_dir_all(root.join("real")).unwrap();
+        std::fs::write(root.join("real/file.txt"), b"data").unwrap();
+        std::fs::write(dir.path().join("secret"), b"host").unwrap();
+        // Absolute targets are sandbox paths, relative ones stay relative
+        std::os::unix::fs::symlink("/data/real", root.join("abs")).unwrap();
+        std::os::unix::fs::symlink("real", root.join("rel")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), root.join("escape")).unwrap();
+        std::os::unix::fs::symlink("../secret", root.join("secret")).unwrap();
+        std::os::unix::fs::symlink("real/file.txt", root.join("ok")).unwrap();
+
+        let vfs = BindVfs::new(root.clone(), PathBuf::from("/data")).with_confined_symlinks(true);
+        assert!(vfs.confines_symlinks());
+
+        assert_eq!(
+            vfs.translate_path(Path::new("/data/abs/file.txt")).unwrap(),
+            root.join("real/file.txt")
+        );
+        assert_eq!(
+            vfs.translate_path(Path::new("/data/rel/file.txt")).unwrap(),
+            root.join("real/file.txt")
+        );
+        assert!(vfs.check_path(Path::new("/data/abs/file.txt")).is_ok());
+        assert!(vfs.check_path(Path::new("/data/ok")).is_ok());
+        assert!(vfs.check_path(Path::new("/data/new.txt")).is_ok());
+
+        // Directories and final components that lead out of the mount are refused
+        assert!(matches!(
+            vfs.translate_path(Path::new("/data/escape/secret")),
+            Err(VfsError::PermissionDenied)
+        ));
+        assert!(matches!(
+            vfs.check_path(Path::new("/data/secret")),
+            Err(VfsError::PermissionDenied)
+        ));
+        assert!(matches!(
+            vfs.translate_path(Path::new("/data/../secret")),
+            Err(VfsError::PermissionDenied)
+        ));
+        assert_eq!(vfs.translate_path(Path::new("/data")).unwrap(), root);
+
+        // Without confinement the kernel follows symlinks as usual
+        let vfs = BindVfs::new(root.clone(), PathBuf::from("/data"));
+        assert_eq!(
+            vfs.translate_path(Path::new("/data/escape/secret"))
+                .unwrap(),
+            root.join("escape/secret")
+        );
+        assert!(vfs.check_path(Path::new("/data/secret")).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_stat_applies_owner_override() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file.txt"), b"data").unwrap();
+
+        let vfs = BindVfs::new(dir.path().to_path_buf(), PathBuf::from("/data"))
+            .with_owner(Some(1000), Some(1000));
+        assert!(vfs.remaps_ownership());
+
+        let stat = vfs.stat(Path::new("/data/file.txt")).await.unwrap();
+        assert_eq!(stat.st_uid, 1000);
+        assert_eq!(stat.st_gid, 1000);
+        assert_eq!(stat.st_size, 4);
+
+        let result = vfs.lstat(Path::new("/data/missing")).await;
+        assert!(matches!(result, Err(VfsError::NotFound)));
     }
 }
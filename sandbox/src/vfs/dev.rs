@@ -0,0 +1,473 @@
+use super::file::{BoxedFileOps, FileOps};
+use super::{DirEntry, Vfs, VfsError, VfsResult};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Inode number of the device directory itself
+const DIR_INO: u64 = 1;
+
+/// A character device served by [`DevVfs`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Device {
+    /// Reads return EOF, writes are discarded
+    Null,
+    /// Reads return zeros, writes are discarded
+    Zero,
+    /// Reads return zeros, writes fail with `ENOSPC`
+    Full,
+    /// Reads return random bytes, writes are discarded
+    Random,
+    /// Same as [`Device::Random`]
+    Urandom,
+    /// The supervisor's controlling terminal
+    Tty,
+}
+
+/// Devices in directory listing order, with their names
+const DEVICES: &[(&str, Device)] = &[
+    ("full", Device::Full),
+    ("null", Device::Null),
+    ("random", Device::Random),
+    ("tty", Device::Tty),
+    ("urandom", Device::Urandom),
+    ("zero", Device::Zero),
+];
+
+impl Device {
+    fn from_name(name: &str) -> Option<Self> {
+        DEVICES
+            .iter()
+            .find(|(device, _)| *device == name)
+            .map(|(_, device)| *device)
+    }
+
+    fn ino(self) -> u64 {
+        DIR_INO + 1 + DEVICES.iter().position(|(_, d)| *d == self).unwrap() as u64
+    }
+
+    /// Device number, matching the real Linux devices
+    fn rdev(self) -> libc::dev_t {
+        match self {
+            Device::Null => libc::makedev(1, 3),
+            Device::Zero => libc::makedev(1, 5),
+            Device::Full => libc::makedev(1, 7),
+            Device::Random => libc::makedev(1, 8),
+            Device::Urandom => libc::makedev(1, 9),
+            Device::Tty => libc::makedev(5, 0),
+        }
+    }
+}
+
+/// A minimal virtual `/dev` with the standard character devices
+///
+/// The mount contains `null`, `zero`, `full`, `random`, `urandom` and `tty`,
+/// all served by the sandbox rather than the host. Nothing can be created in
+/// it, and any other name under the mount point is reported as missing. `tty`
+/// is the sandbox's own controlling terminal, if it has one.
+#[derive(Debug, Clone)]
+pub struct DevVfs {
+    /// The virtual path as seen by the sandboxed process
+    mount_point: PathBuf,
+    /// Time reported for every file, set when the VFS is created
+    created: i64,
+}
+
+impl DevVfs {
+    /// Create a device VFS mounted at `mount_point` (usually `/dev`)
+    pub fn new(mount_point: PathBuf) -> Self {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or(0);
+        Self {
+            mount_point,
+            created,
+        }
+    }
+
+    /// Get the mount point path
+    pub fn mount_point(&self) -> &Path {
+        &self.mount_point
+    }
+
+    /// Look up a sandbox path, returning `None` for the directory itself
+    fn lookup(&self, path: &Path) -> VfsResult<Option<Device>> {
+        let relative = path
+            .strip_prefix(&self.mount_point)
+            .map_err(|_| VfsError::NotFound)?;
+        let name = match relative.to_str() {
+            Some("") => return Ok(None),
+            Some(name) => name.trim_end_matches('/'),
+            None => return Err(VfsError::NotFound),
+        };
+        Device::from_name(name).map(Some).ok_or(VfsError::NotFound)
+    }
+
+    fn device_stat(&self, device: Option<Device>) -> libc::stat {
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        match device {
+            Some(device) => {
+                stat.st_ino = device.ino();
+                stat.st_mode = libc::S_IFCHR | 0o666;
+                stat.st_nlink = 1;
+                stat.st_rdev = device.rdev();
+            }
+            None => {
+                stat.st_ino = DIR_INO;
+                stat.st_mode = libc::S_IFDIR | 0o755;
+                stat.st_nlink = 2;
+            }
+        }
+        stat.st_blksize = 4096;
+        stat.st_atime = self.created;
+        stat.st_mtime = self.created;
+        stat.st_ctime = self.created;
+        stat
+    }
+
+    fn entries(&self) -> Vec<DirEntry> {
+        let mut entries = vec![
+            DirEntry::new(DIR_INO, ".", libc::S_IFDIR),
+            DirEntry::new(DIR_INO, "..", libc::S_IFDIR),
+        ];
+        entries.extend(
+            DEVICES
+                .iter()
+                .map(|(name, device)| DirEntry::new(device.ino(), *name, libc::S_IFCHR)),
+        );
+        entries
+    }
+}
+
+#[async_trait::async_trait]
+impl Vfs for DevVfs {
+    fn translate_path(&self, path: &Path) -> VfsResult<PathBuf> {
+        // Everything under the mount point belongs to us, even missing devices
+        if path.starts_with(&self.mount_point) {
+            Ok(path.to_path_buf())
+        } else {
+            Err(VfsError::NotFound)
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        "dev"
+    }
+
+    fn is_virtual(&self) -> bool {
+        true
+    }
+
+    async fn open(&self, path: &Path, flags: i32, _mode: u32) -> VfsResult<BoxedFileOps> {
+        let device = match self.lookup(path) {
+            Ok(device) => device,
+            // Devices can't be created
+            Err(VfsError::NotFound) if flags & libc::O_CREAT != 0 => {
+                return Err(VfsError::PermissionDenied)
+            }
+            Err(e) => return Err(e),
+        };
+
+        let Some(device) = device else {
+            return Ok(Arc::new(DevDirectoryOps {
+                stat: self.device_stat(None),
+                entries: self.entries(),
+                flags: Mutex::new(flags),
+                position: Mutex::new(0),
+            }));
+        };
+
+        let tty = if device == Device::Tty {
+            let tty = OpenOptions::new()
+                .read(flags & libc::O_ACCMODE != libc::O_WRONLY)
+                .write(flags & libc::O_ACCMODE != libc::O_RDONLY)
+                .open("/dev/tty")?;
+            Some(Mutex::new(tty))
+        } else {
+            None
+        };
+
+        Ok(Arc::new(DeviceOps {
+            device,
+            stat: self.device_stat(Some(device)),
+            tty,
+            flags: Mutex::new(flags),
+        }))
+    }
+
+    async fn stat(&self, path: &Path) -> VfsResult<libc::stat> {
+        let device = self.lookup(path)?;
+        Ok(self.device_stat(device))
+    }
+
+    async fn lstat(&self, path: &Path) -> VfsResult<libc::stat> {
+        self.stat(path).await
+    }
+
+    async fn readdir(&self, path: &Path) -> VfsResult<Vec<DirEntry>> {
+        match self.lookup(path)? {
+            None => Ok(self.entries()),
+            Some(_) => Err(VfsError::NotADirectory),
+        }
+    }
+}
+
+/// An open character device
+struct DeviceOps {
+    device: Device,
+    stat: libc::stat,
+    /// The host terminal, for [`Device::Tty`]
+    tty: Option<Mutex<File>>,
+    flags: Mutex<i32>,
+}
+
+#[async_trait::async_trait]
+impl FileOps for DeviceOps {
+    async fn read(&self, buf: &mut [u8]) -> VfsResult<usize> {
+        match self.device {
+            Device::Null => Ok(0),
+            Device::Zero | Device::Full => {
+                buf.fill(0);
+                Ok(buf.len())
+            }
+            Device::Random | Device::Urandom => {
+                let n = unsafe { libc::getrandom(buf.as_mut_ptr().cast(), buf.len(), 0) };
+                if n < 0 {
+                    return Err(std::io::Error::last_os_error().into());
+                }
+                Ok(n as usize)
+            }
+            Device::Tty => Ok(self.tty.as_ref().unwrap().lock().unwrap().read(buf)?),
+        }
+    }
+
+    async fn write(&self, buf: &[u8]) -> VfsResult<usize> {
+        match self.device {
+            Device::Full => Err(std::io::Error::from_raw_os_error(libc::ENOSPC).into()),
+            Device::Tty => Ok(self.tty.as_ref().unwrap().lock().unwrap().write(buf)?),
+            _ => Ok(buf.len()),
+        }
+    }
+
+    async fn seek(&self, _offset: i64, _whence: i32) -> VfsResult<i64> {
+        // Seeking a character device is a no-op that reports offset 0
+        Ok(0)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        Ok(self.stat)
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        match cmd {
+            libc::F_GETFL => Ok(self.get_flags() as i64),
+            libc::F_SETFL => {
+                self.set_flags(arg as i32)?;
+                Ok(0)
+            }
+            _ => Err(VfsError::Other(format!(
+                "Unsupported fcntl command: {}",
+                cmd
+            ))),
+        }
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        Err(VfsError::Other("ioctl not supported".to_string()))
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+}
+
+/// The device directory, opened for listing
+struct DevDirectoryOps {
+    stat: libc::stat,
+    entries: Vec<DirEntry>,
+    flags: Mutex<i32>,
+    /// Index of the next entry to return
+    position: Mutex<usize>,
+}
+
+#[async_trait::async_trait]
+impl FileOps for DevDirectoryOps {
+    async fn read(&self, _buf: &mut [u8]) -> VfsResult<usize> {
+        Err(VfsError::IsADirectory)
+    }
+
+    async fn write(&self, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::IsADirectory)
+    }
+
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        let mut position = self.position.lock().unwrap();
+        let new_position = match whence {
+            libc::SEEK_SET => offset,
+            libc::SEEK_CUR => *position as i64 + offset,
+            _ => return Err(VfsError::InvalidInput("Invalid whence".to_string())),
+        };
+        if new_position < 0 {
+            return Err(VfsError::InvalidInput("Invalid offset".to_string()));
+        }
+        *position = new_position as usize;
+        Ok(new_position)
+    }
+
+    async fn fstat(&self) -> VfsResult<libc::stat> {
+        Ok(self.stat)
+    }
+
+    async fn fsync(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    async fn fdatasync(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    fn fcntl(&self, cmd: i32, arg: i64) -> VfsResult<i64> {
+        match cmd {
+            libc::F_GETFL => Ok(self.get_flags() as i64),
+            libc::F_SETFL => {
+                self.set_flags(arg as i32)?;
+                Ok(0)
+            }
+            _ => Err(VfsError::Other(format!(
+                "Unsupported fcntl command: {}",
+                cmd
+            ))),
+        }
+    }
+
+    fn ioctl(&self, _request: u64, _arg: u64) -> VfsResult<i64> {
+        Err(VfsError::Other("ioctl not supported".to_string()))
+    }
+
+    fn as_raw_fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    async fn close(&self) -> VfsResult<()> {
+        Ok(())
+    }
+
+    fn get_flags(&self) -> i32 {
+        *self.flags.lock().unwrap()
+    }
+
+    fn set_flags(&self, flags: i32) -> VfsResult<()> {
+        *self.flags.lock().unwrap() = flags;
+        Ok(())
+    }
+
+    async fn getdents(&self) -> VfsResult<Vec<DirEntry>> {
+        let position = *self.position.lock().unwrap();
+        Ok(self.entries.get(position..).unwrap_or_default().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vfs() -> DevVfs {
+        DevVfs::new(PathBuf::from("/dev"))
+    }
+
+    async fn read(vfs: &DevVfs, path: &str, len: usize) -> Vec<u8> {
+        let file = vfs.open(Path::new(path), libc::O_RDONLY, 0).await.unwrap();
+        let mut buf = vec![0xffu8; len];
+        let n = file.read(&mut buf).await.unwrap();
+        buf.truncate(n);
+        buf
+    }
+
+    #[tokio::test]
+    async fn test_device_reads_and_writes() {
+        let vfs = vfs();
+
+        assert!(read(&vfs, "/dev/null", 16).await.is_empty());
+        assert_eq!(read(&vfs, "/dev/zero", 16).await, vec![0u8; 16]);
+        assert_eq!(read(&vfs, "/dev/urandom", 16).await.len(), 16);
+
+        let null = vfs
+            .open(Path::new("/dev/null"), libc::O_WRONLY, 0)
+            .await
+            .unwrap();
+        assert_eq!(null.write(b"discarded").await.unwrap(), 9);
+
+        let full = vfs
+            .open(Path::new("/dev/full"), libc::O_WRONLY, 0)
+            .await
+            .unwrap();
+        match full.write(b"x").await {
+            Err(VfsError::IoError(e)) => assert_eq!(e.raw_os_error(), Some(libc::ENOSPC)),
+            other => panic!("expected ENOSPC, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stat_and_readdir() {
+        let vfs = vfs();
+
+        let stat = vfs.stat(Path::new("/dev/null")).await.unwrap();
+        assert_eq!(stat.st_mode, libc::S_IFCHR | 0o666);
+        assert_eq!(stat.st_rdev, libc::makedev(1, 3));
+        let stat = vfs.stat(Path::new("/dev")).await.unwrap();
+        assert_eq!(stat.st_mode & libc::S_IFMT, libc::S_IFDIR);
+
+        assert!(matches!(
+            vfs.stat(Path::new("/dev/sda")).await,
+            Err(VfsError::NotFound)
+        ));
+        let result = vfs
+            .open(Path::new("/dev/new"), libc::O_WRONLY | libc::O_CREAT, 0o644)
+            .await;
+        assert!(matches!(result, Err(VfsError::PermissionDenied)));
+
+        let names: Vec<_> = vfs
+            .readdir(Path::new("/dev"))
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(
+            names,
+            [".", "..", "full", "null", "random", "tty", "urandom", "zero"]
+        );
+
+        let dir = vfs
+            .open(Path::new("/dev"), libc::O_RDONLY | libc::O_DIRECTORY, 0)
+            .await
+            .unwrap();
+        dir.seek(2, libc::SEEK_SET).await.unwrap();
+        assert_eq!(dir.getdents().await.unwrap()[0].name, "full");
+    }
+}
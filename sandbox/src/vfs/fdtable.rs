@@ -1,5 +1,6 @@
 use super::file::BoxedFileOps;
-use std::collections::{BinaryHeap, HashMap};
+use super::{VfsError, VfsResult};
+use std::collections::{BinaryHeap, HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 
 /// Standard file descriptor constants
@@ -8,6 +9,10 @@ const STDOUT_FILENO: i32 = 1;
 const STDERR_FILENO: i32 = 2;
 const FIRST_USER_FD: i32 = 3;
 
+/// File status flags that can be changed with fcntl F_SETFL on Linux
+const SETFL_MASK: i32 =
+    libc::O_APPEND | libc::O_ASYNC | libc::O_DIRECT | libc::O_NOATIME | libc::O_NONBLOCK;
+
 /// Information about a virtualized file descriptor
 #[derive(Clone)]
 pub enum FdEntry {
@@ -25,6 +30,40 @@ pub enum FdEntry {
     },
 }
 
+/// Kind of an FD table entry, as reported by [`FdTable::snapshot_debug`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FdKind {
+    /// Backed by a kernel FD in the traced process
+    Passthrough,
+    /// Served by a `FileOps` implementation
+    Virtual,
+}
+
+impl FdKind {
+    /// Lowercase name of the kind
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FdKind::Passthrough => "passthrough",
+            FdKind::Virtual => "virtual",
+        }
+    }
+}
+
+/// Point-in-time description of one FD table entry, for debugging
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FdInfo {
+    /// Virtual FD seen by the process
+    pub vfd: i32,
+    /// Whether the FD passes through to the kernel or is virtual
+    pub kind: FdKind,
+    /// Kernel FD for passthrough entries
+    pub kernel_fd: Option<i32>,
+    /// Open flags, including `O_CLOEXEC`
+    pub flags: i32,
+    /// Path the FD was opened with, if known
+    pub path: Option<std::path::PathBuf>,
+}
+
 impl FdEntry {
     /// Get the kernel file descriptor if this is a passthrough file
     pub fn kernel_fd(&self) -> Option<i32> {
@@ -57,6 +96,20 @@ impl FdEntry {
             FdEntry::Virtual { file_ops, .. } => Some(file_ops),
         }
     }
+
+    /// Return this entry with the close-on-exec flag set or cleared
+    pub fn with_cloexec(mut self, cloexec: bool) -> Self {
+        let flags = match &mut self {
+            FdEntry::Passthrough { flags, .. } => flags,
+            FdEntry::Virtual { flags, .. } => flags,
+        };
+        if cloexec {
+            *flags |= libc::O_CLOEXEC;
+        } else {
+            *flags &= !libc::O_CLOEXEC;
+        }
+        self
+    }
 }
 
 /// Inner state of the FD table, protected by a single mutex
@@ -122,6 +175,33 @@ impl FdTable {
         }
     }
 
+    /// Create an FD table with the standard FDs plus entries at fixed virtual FDs
+    ///
+    /// Entries at 0, 1 or 2 replace the standard FDs. Gaps left below the
+    /// preopened FDs stay available to [`FdTable::allocate`], which never hands
+    /// out a preopened FD while it is open. Fails if a virtual FD is negative or
+    /// listed twice.
+    pub fn with_preopened(preopened: Vec<(i32, FdEntry)>) -> VfsResult<Self> {
+        let table = Self::new();
+        let mut seen = HashSet::new();
+        for (vfd, entry) in preopened {
+            if vfd < 0 {
+                return Err(VfsError::InvalidInput(format!(
+                    "preopened fd {} is negative",
+                    vfd
+                )));
+            }
+            if !seen.insert(vfd) {
+                return Err(VfsError::InvalidInput(format!(
+                    "preopened fd {} is listed twice",
+                    vfd
+                )));
+            }
+            table.allocate_at(vfd, entry);
+        }
+        Ok(table)
+    }
+
     /// Create a deep clone of this FD table (for fork/clone syscalls)
     ///
     /// This creates a completely independent copy of the FD table,
@@ -270,6 +350,43 @@ impl FdTable {
         inner.entries.get(&vfd).cloned()
     }
 
+    /// Get the file status flags for a virtual FD (for fcntl F_GETFL)
+    pub fn get_flags(&self, vfd: i32) -> Option<i32> {
+        let inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        inner.entries.get(&vfd).map(|entry| entry.flags())
+    }
+
+    /// Get the access mode and status flags of a virtual FD as F_GETFL reports them
+    ///
+    /// The stored flags also hold the creation flags and `O_CLOEXEC` passed to
+    /// `open`, which Linux never returns from F_GETFL.
+    pub fn status_flags(&self, vfd: i32) -> Option<i32> {
+        self.get_flags(vfd)
+            .map(|flags| flags & (libc::O_ACCMODE | SETFL_MASK))
+    }
+
+    /// Update the file status flags for a virtual FD (for fcntl F_SETFL)
+    ///
+    /// Like F_SETFL, only the settable status flags are changed; the access mode
+    /// and file creation flags are preserved. Returns the resulting flags, or
+    /// None if the VFD doesn't exist.
+    pub fn set_flags(&self, vfd: i32, flags: i32) -> Option<i32> {
+        let mut inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let entry = inner.entries.get_mut(&vfd)?;
+        let stored = match entry {
+            FdEntry::Passthrough { flags, .. } => flags,
+            FdEntry::Virtual { flags, .. } => flags,
+        };
+        *stored = (*stored & !SETFL_MASK) | (flags & SETFL_MASK);
+        Some(*stored)
+    }
+
     /// Deallocate a virtual FD and mark it as available for reuse
     pub fn deallocate(&self, vfd: i32) -> Option<FdEntry> {
         let mut inner = self
@@ -301,6 +418,55 @@ impl FdTable {
         let entry = self.get(old_vfd)?;
         self.allocate_at(new_vfd, entry)
     }
+
+    /// Duplicate a virtual FD to a specific new FD with flags (for dup3 syscall)
+    ///
+    /// Unlike `duplicate_at`, duplicating an FD onto itself is an error, and the
+    /// close-on-exec flag of the new entry is set from `cloexec` rather than
+    /// inherited. Returns the old entry that was at new_vfd if it existed (caller
+    /// should close it).
+    pub fn duplicate3(
+        &self,
+        old_vfd: i32,
+        new_vfd: i32,
+        cloexec: bool,
+    ) -> VfsResult<Option<FdEntry>> {
+        if old_vfd == new_vfd {
+            return Err(VfsError::InvalidInput(format!(
+                "cannot duplicate fd {} onto itself",
+                old_vfd
+            )));
+        }
+        let entry = self.get(old_vfd).ok_or(VfsError::NotFound)?;
+        Ok(self.allocate_at(new_vfd, entry.with_cloexec(cloexec)))
+    }
+
+    /// Describe every entry in the table, ordered by virtual FD
+    ///
+    /// Unlike the `Debug` output, which only has counts, this lists each FD
+    /// so that leaked or misrouted descriptors can be spotted.
+    pub fn snapshot_debug(&self) -> Vec<FdInfo> {
+        let inner = self
+            .inner
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut snapshot: Vec<FdInfo> = inner
+            .entries
+            .iter()
+            .map(|(vfd, entry)| FdInfo {
+                vfd: *vfd,
+                kind: match entry {
+                    FdEntry::Passthrough { .. } => FdKind::Passthrough,
+                    FdEntry::Virtual { .. } => FdKind::Virtual,
+                },
+                kernel_fd: entry.kernel_fd(),
+                flags: entry.flags(),
+                path: entry.path().cloned(),
+            })
+            .collect();
+        snapshot.sort_by_key(|info| info.vfd);
+        snapshot
+    }
 }
 
 impl Default for FdTable {
@@ -409,6 +575,236 @@ mod tests {
         assert!(result.is_none());
         assert_eq!(table.translate(10), Some(100));
     }
+
+    #[test]
+    fn test_duplicate3() {
+        let table = FdTable::new();
+
+        let entry = FdEntry::Passthrough {
+            kernel_fd: 100,
+            flags: libc::O_RDWR | libc::O_CLOEXEC,
+            path: None,
+        };
+        let vfd = table.allocate(entry);
+
+        assert!(table.duplicate3(vfd, 10, false).unwrap().is_none());
+        assert_eq!(table.translate(10), Some(100));
+        assert_eq!(table.get_flags(10), Some(libc::O_RDWR));
+
+        // Replacing an existing FD hands back the displaced entry
+        let displaced = table.duplicate3(vfd, 10, true).unwrap();
+        assert_eq!(displaced.unwrap().flags(), libc::O_RDWR);
+        assert_eq!(table.get_flags(10), Some(libc::O_RDWR | libc::O_CLOEXEC));
+
+        assert!(matches!(
+            table.duplicate3(42, 11, false),
+            Err(VfsError::NotFound)
+        ));
+        assert_eq!(table.translate(11), None);
+    }
+
+    #[test]
+    fn test_with_preopened() {
+        let log = FdEntry::Passthrough {
+            kernel_fd: 40,
+            flags: libc::O_WRONLY,
+            path: Some("/tmp/log".into()),
+        };
+        let table = FdTable::with_preopened(vec![(5, log.clone()), (3, log.clone())]).unwrap();
+        assert_eq!(table.translate(3), Some(40));
+        assert_eq!(table.translate(5), Some(40));
+        assert_eq!(table.translate(1), Some(1));
+
+        // Lowest-available allocation skips the preopened FDs
+        assert_eq!(table.allocate(log.clone()), 4);
+        assert_eq!(table.allocate(log.clone()), 6);
+        // Once closed, a preopened FD is reused like any other
+        table.deallocate(3);
+        assert_eq!(table.allocate(log.clone()), 3);
+
+        assert!(FdTable::with_preopened(vec![(-1, log.clone())]).is_err());
+        assert!(FdTable::with_preopened(vec![(7, log.clone()), (7, log)]).is_err());
+    }
+
+    #[test]
+    fn test_duplicate3_same_fd() {
+        let table = FdTable::new();
+
+        let entry = FdEntry::Passthrough {
+            kernel_fd: 100,
+            flags: libc::O_RDWR,
+            path: None,
+        };
+        let vfd = table.allocate(entry);
+
+        // dup3 rejects oldfd == newfd with EINVAL and leaves the entry untouched
+        assert!(matches!(
+            table.duplicate3(vfd, vfd, true),
+            Err(VfsError::InvalidInput(_))
+        ));
+        assert_eq!(table.translate(vfd), Some(100));
+        assert_eq!(table.get_flags(vfd), Some(libc::O_RDWR));
+    }
+
+    /// Minimal FileOps for exercising virtual FD entries
+    struct NullFileOps;
+
+    #[async_trait::async_trait]
+    impl crate::vfs::file::FileOps for NullFileOps {
+        async fn read(&self, _buf: &mut [u8]) -> crate::vfs::VfsResult<usize> {
+            Ok(0)
+        }
+        async fn write(&self, buf: &[u8]) -> crate::vfs::VfsResult<usize> {
+            Ok(buf.len())
+        }
+        async fn seek(&self, _offset: i64, _whence: i32) -> crate::vfs::VfsResult<i64> {
+            Ok(0)
+        }
+        async fn fstat(&self) -> crate::vfs::VfsResult<libc::stat> {
+            Ok(unsafe { std::mem::zeroed() })
+        }
+        async fn fsync(&self) -> crate::vfs::VfsResult<()> {
+            Ok(())
+        }
+        async fn fdatasync(&self) -> crate::vfs::VfsResult<()> {
+            Ok(())
+        }
+        fn fcntl(&self, _cmd: i32, _arg: i64) -> crate::vfs::VfsResult<i64> {
+            Ok(0)
+        }
+        fn ioctl(&self, _request: u64, _arg: u64) -> crate::vfs::VfsResult<i64> {
+            Ok(0)
+        }
+        fn as_raw_fd(&self) -> Option<std::os::unix::io::RawFd> {
+            None
+        }
+        async fn close(&self) -> crate::vfs::VfsResult<()> {
+            Ok(())
+        }
+        fn get_flags(&self) -> i32 {
+            0
+        }
+        fn set_flags(&self, _flags: i32) -> crate::vfs::VfsResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_set_flags_toggles_nonblock() {
+        let table = FdTable::new();
+
+        let entry = FdEntry::Virtual {
+            file_ops: Arc::new(NullFileOps),
+            flags: libc::O_RDWR,
+            path: None,
+        };
+        let vfd = table.allocate(entry);
+        assert_eq!(table.get_flags(vfd), Some(libc::O_RDWR));
+
+        let flags = table.get_flags(vfd).unwrap();
+        assert_eq!(
+            table.set_flags(vfd, flags | libc::O_NONBLOCK),
+            Some(libc::O_RDWR | libc::O_NONBLOCK)
+        );
+        assert_eq!(table.get_flags(vfd), Some(libc::O_RDWR | libc::O_NONBLOCK));
+
+        let flags = table.get_flags(vfd).unwrap();
+        table.set_flags(vfd, flags & !libc::O_NONBLOCK);
+        assert_eq!(table.get_flags(vfd), Some(libc::O_RDWR));
+    }
+
+    #[test]
+    fn test_virtual_entry_poll_readiness() {
+        let table = FdTable::new();
+
+        let entry = FdEntry::Virtual {
+            file_ops: Arc::new(NullFileOps),
+            flags: libc::O_RDWR,
+            path: None,
+        };
+        let vfd = table.allocate(entry);
+        let entry = table.get(vfd).unwrap();
+        let file_ops = entry.file_ops().unwrap();
+
+        // Regular files are always readable and writable, and never report urgent data
+        assert_eq!(
+            file_ops.poll(libc::POLLIN | libc::POLLOUT | libc::POLLPRI),
+            libc::POLLIN | libc::POLLOUT
+        );
+        assert_eq!(file_ops.poll(libc::POLLPRI), 0);
+    }
+
+    #[test]
+    fn test_snapshot_debug() {
+        let table = FdTable::new();
+        let vfd = table.allocate(FdEntry::Virtual {
+            file_ops: Arc::new(NullFileOps),
+            flags: libc::O_RDONLY | libc::O_CLOEXEC,
+            path: Some("/agent/notes.txt".into()),
+        });
+        table.deallocate(1);
+
+        let snapshot = table.snapshot_debug();
+        let vfds: Vec<i32> = snapshot.iter().map(|info| info.vfd).collect();
+        assert_eq!(vfds, vec![0, 2, vfd]);
+        assert_eq!(snapshot[0].kind, FdKind::Passthrough);
+        assert_eq!(snapshot[0].kernel_fd, Some(0));
+        assert_eq!(
+            snapshot[2],
+            FdInfo {
+                vfd,
+                kind: FdKind::Virtual,
+                kernel_fd: None,
+                flags: libc::O_RDONLY | libc::O_CLOEXEC,
+                path: Some("/agent/notes.txt".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_set_flags_preserves_access_mode() {
+        let table = FdTable::new();
+
+        let entry = FdEntry::Passthrough {
+            kernel_fd: 100,
+            flags: libc::O_WRONLY | libc::O_CLOEXEC,
+            path: None,
+        };
+        let vfd = table.allocate(entry);
+
+        // Access mode and creation flags can't be changed through F_SETFL
+        table.set_flags(vfd, libc::O_RDWR | libc::O_TRUNC | libc::O_APPEND);
+        assert_eq!(
+            table.get_flags(vfd),
+            Some(libc::O_WRONLY | libc::O_CLOEXEC | libc::O_APPEND)
+        );
+
+        assert_eq!(table.get_flags(42), None);
+        assert_eq!(table.set_flags(42, libc::O_NONBLOCK), None);
+    }
+
+    #[test]
+    fn test_status_flags_drop_open_only_flags() {
+        let table = FdTable::new();
+
+        let entry = FdEntry::Virtual {
+            file_ops: Arc::new(NullFileOps),
+            flags: libc::O_WRONLY | libc::O_CREAT | libc::O_CLOEXEC | libc::O_APPEND,
+            path: None,
+        };
+        let vfd = table.allocate(entry);
+        assert_eq!(
+            table.status_flags(vfd),
+            Some(libc::O_WRONLY | libc::O_APPEND)
+        );
+
+        table.set_flags(vfd, libc::O_NONBLOCK);
+        assert_eq!(
+            table.status_flags(vfd),
+            Some(libc::O_WRONLY | libc::O_NONBLOCK)
+        );
+        assert_eq!(table.status_flags(42), None);
+    }
 }
 
 /// Property tests for `FdTable` correctness.
@@ -1,4 +1,4 @@
-use super::VfsResult;
+use super::{DirEntry, VfsResult};
 use async_trait::async_trait;
 use std::os::unix::io::RawFd;
 use std::sync::Arc;
@@ -49,12 +49,32 @@ pub trait FileOps: Send + Sync {
     /// Set flags associated with this file descriptor
     fn set_flags(&self, flags: i32) -> VfsResult<()>;
 
+    /// Report which of the `poll` events in `events` are ready
+    ///
+    /// Used to answer `poll`/`select` for virtual FDs, which the kernel knows
+    /// nothing about. The default matches what Linux reports for regular files:
+    /// always readable and writable, never any other event.
+    fn poll(&self, events: i16) -> i16 {
+        events & (libc::POLLIN | libc::POLLOUT | libc::POLLRDNORM | libc::POLLWRNORM)
+    }
+
+    /// Give this open file the name `path` (for `linkat` on an FD)
+    ///
+    /// `path` is relative to the VFS that opened the file. Files opened with
+    /// `O_TMPFILE` have no name until this is called; for any other file it adds
+    /// a hard link.
+    async fn link(&self, _path: &str) -> VfsResult<()> {
+        Err(super::VfsError::Other("link not supported".to_string()))
+    }
+
     /// Read directory entries (for directories only)
     ///
-    /// This is used to implement getdents64. Returns a vector of (inode, name, type) tuples.
+    /// This is used to implement getdents/getdents64. Returns the entries from
+    /// the current directory position onward without advancing it; callers
+    /// advance past the entries they consumed with `seek(n, SEEK_CUR)`.
     /// Returns an error if this is not a directory.
-    async fn getdents(&self) -> VfsResult<Vec<(u64, String, u8)>> {
-        Err(super::VfsError::Other("Not a directory".to_string()))
+    async fn getdents(&self) -> VfsResult<Vec<DirEntry>> {
+        Err(super::VfsError::NotADirectory)
     }
 }
 
@@ -0,0 +1,378 @@
+//! Advisory file locks for virtual files
+//!
+//! Virtual files have no kernel file behind them, so `flock` and `fcntl`
+//! record locks taken on them are kept in a [`LockTable`] instead, keyed by
+//! device and inode so that every process in the sandbox sees the same locks.
+//! As on Linux, the two kinds don't interact: `flock` locks belong to an open
+//! file description and cover the whole file, while `fcntl` locks belong to a
+//! process and cover byte ranges.
+
+use super::file::{BoxedFileOps, FileOps};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// How often a blocked lock request looks again even without a wakeup
+///
+/// Files dropped by exiting processes release their `flock` locks without
+/// anyone calling into the table, so waiters can't rely on a notification.
+const RECHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Identifies a file by device and inode number, as reported by `fstat`
+pub type FileKey = (u64, u64);
+
+/// The lock key of an open virtual file
+pub async fn file_key(file: &BoxedFileOps) -> super::VfsResult<FileKey> {
+    let stat = file.fstat().await?;
+    Ok((stat.st_dev, stat.st_ino))
+}
+
+/// Shared (read) or exclusive (write) lock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockKind {
+    Shared,
+    Exclusive,
+}
+
+/// Who holds a lock
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LockOwner {
+    /// A `flock` lock, held by an open file description
+    OpenFile(usize),
+    /// An `fcntl` record lock, held by a process
+    Process(i32),
+}
+
+impl LockOwner {
+    /// The `flock` owner for an open file, shared by its duplicates
+    pub fn open_file(file: &BoxedFileOps) -> Self {
+        LockOwner::OpenFile(Arc::as_ptr(file) as *const () as usize)
+    }
+
+    /// Whether locks of the two owners can conflict at all
+    fn same_kind(&self, other: &LockOwner) -> bool {
+        matches!(
+            (self, other),
+            (LockOwner::OpenFile(_), LockOwner::OpenFile(_))
+                | (LockOwner::Process(_), LockOwner::Process(_))
+        )
+    }
+}
+
+/// Bytes `start..end` of a file; an `end` of `u64::MAX` reaches past any size
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl LockRange {
+    /// The whole file, as locked by `flock`
+    pub const WHOLE: LockRange = LockRange {
+        start: 0,
+        end: u64::MAX,
+    };
+
+    fn overlaps(&self, other: &LockRange) -> bool {
+        self.start < other.end && other.start < self.end
+    }
+}
+
+/// A lock held on a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileLock {
+    pub owner: LockOwner,
+    pub kind: LockKind,
+    pub range: LockRange,
+}
+
+impl FileLock {
+    fn conflicts_with(&self, owner: LockOwner, kind: LockKind, range: &LockRange) -> bool {
+        self.owner != owner
+            && self.owner.same_kind(&owner)
+            && self.range.overlaps(range)
+            && (self.kind == LockKind::Exclusive || kind == LockKind::Exclusive)
+    }
+}
+
+#[derive(Clone)]
+struct HeldLock {
+    lock: FileLock,
+    /// The open file of a `flock` lock, which goes away with its last FD
+    file: Option<Weak<dyn FileOps>>,
+}
+
+impl HeldLock {
+    fn is_live(&self) -> bool {
+        self.file
+            .as_ref()
+            .is_none_or(|file| file.strong_count() > 0)
+    }
+}
+
+/// Advisory locks held on virtual files by processes in the sandbox
+#[derive(Default)]
+pub struct LockTable {
+    locks: Mutex<HashMap<FileKey, Vec<HeldLock>>>,
+    released: Notify,
+}
+
+impl LockTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether any lock is held, to skip lock bookkeeping on close
+    pub fn is_empty(&self) -> bool {
+        self.locks.lock().unwrap().is_empty()
+    }
+
+    /// A lock that keeps `owner` from taking `kind` over `range` (`F_GETLK`)
+    pub fn conflict(
+        &self,
+        file: FileKey,
+        owner: LockOwner,
+        kind: LockKind,
+        range: LockRange,
+    ) -> Option<FileLock> {
+        let mut locks = self.locks.lock().unwrap();
+        let held = locks.get_mut(&file)?;
+        held.retain(HeldLock::is_live);
+        held.iter()
+            .map(|held| held.lock)
+            .find(|lock| lock.conflicts_with(owner, kind, &range))
+    }
+
+    /// Take a lock unless another owner holds a conflicting one
+    ///
+    /// Whatever `owner` already held over `range` is replaced, so a lock can be
+    /// converted between shared and exclusive. `handle` is the open file of a
+    /// `flock` lock. On conflict, returns the lock in the way.
+    pub fn try_lock(
+        &self,
+        file: FileKey,
+        owner: LockOwner,
+        kind: LockKind,
+        range: LockRange,
+        handle: Option<&BoxedFileOps>,
+    ) -> Result<(), FileLock> {
+        let mut locks = self.locks.lock().unwrap();
+        let held = locks.entry(file).or_default();
+        held.retain(HeldLock::is_live);
+        if let Some(conflict) = held
+            .iter()
+            .find(|held| held.lock.conflicts_with(owner, kind, &range))
+        {
+            return Err(conflict.lock);
+        }
+
+        carve(held, owner, range);
+        held.push(HeldLock {
+            lock: FileLock { owner, kind, range },
+            file: handle.map(Arc::downgrade),
+        });
+        Ok(())
+    }
+
+    /// Wait until nothing conflicts, then take the lock (`F_SETLKW`, `LOCK_EX`)
+    ///
+    /// There is no deadlock detection: two processes each waiting for a lock
+    /// the other holds wait forever.
+    pub async fn lock(
+        &self,
+        file: FileKey,
+        owner: LockOwner,
+        kind: LockKind,
+        range: LockRange,
+        handle: Option<&BoxedFileOps>,
+    ) {
+        loop {
+            // Register before trying, so a release in between isn't missed
+            let released = self.released.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+
+            if self.try_lock(file, owner, kind, range, handle).is_ok() {
+                return;
+            }
+            let _ = tokio::time::timeout(RECHECK_INTERVAL, released).await;
+        }
+    }
+
+    /// Release what `owner` holds over `range`, splitting locks that extend past it
+    pub fn unlock(&self, file: FileKey, owner: LockOwner, range: LockRange) {
+        let mut locks = self.locks.lock().unwrap();
+        if let Some(held) = locks.get_mut(&file) {
+            carve(held, owner, range);
+            if held.is_empty() {
+                locks.remove(&file);
+            }
+        }
+        self.released.notify_waiters();
+    }
+
+    /// Release every lock `owner` holds on `file`
+    pub fn release(&self, file: FileKey, owner: LockOwner) {
+        self.unlock(file, owner, LockRange::WHOLE);
+    }
+
+    /// Release every record lock of an exiting process
+    pub fn release_process(&self, pid: i32) {
+        let mut locks = self.locks.lock().unwrap();
+        for held in locks.values_mut() {
+            held.retain(|held| held.lock.owner != LockOwner::Process(pid));
+        }
+        locks.retain(|_, held| !held.is_empty());
+        self.released.notify_waiters();
+    }
+}
+
+/// Remove the parts of `owner`'s locks that fall inside `range`
+fn carve(held: &mut Vec<HeldLock>, owner: LockOwner, range: LockRange) {
+    let mut kept = Vec::with_capacity(held.len());
+    for lock in held.drain(..) {
+        if lock.lock.owner != owner || !lock.lock.range.overlaps(&range) {
+            kept.push(lock);
+            continue;
+        }
+        let held_range = lock.lock.range;
+        if held_range.start < range.start {
+            let mut before = lock.clone();
+            before.lock.range.end = range.start;
+            kept.push(before);
+        }
+        if range.end < held_range.end {
+            let mut after = lock;
+            after.lock.range.start = range.end;
+            kept.push(after);
+        }
+    }
+    *held = kept;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: FileKey = (1, 42);
+
+    fn range(start: u64, end: u64) -> LockRange {
+        LockRange { start, end }
+    }
+
+    #[test]
+    fn test_shared_and_exclusive() {
+        let table = LockTable::new();
+        let (a, b) = (LockOwner::Process(1), LockOwner::Process(2));
+
+        table
+            .try_lock(FILE, a, LockKind::Shared, LockRange::WHOLE, None)
+            .unwrap();
+        table
+            .try_lock(FILE, b, LockKind::Shared, LockRange::WHOLE, None)
+            .unwrap();
+        let conflict = table
+            .try_lock(FILE, b, LockKind::Exclusive, range(0, 10), None)
+            .unwrap_err();
+        assert_eq!(conflict.owner, a);
+
+        // Releasing the other reader lets the upgrade through
+        table.release(FILE, a);
+        table
+            .try_lock(FILE, b, LockKind::Exclusive, range(0, 10), None)
+            .unwrap();
+        assert!(table
+            .conflict(FILE, a, LockKind::Shared, range(5, 6))
+            .is_some());
+        assert!(table
+            .conflict(FILE, a, LockKind::Shared, range(10, 20))
+            .is_none());
+    }
+
+    #[test]
+    fn test_unlock_splits_ranges() {
+        let table = LockTable::new();
+        let (a, b) = (LockOwner::Process(1), LockOwner::Process(2));
+
+        table
+            .try_lock(FILE, a, LockKind::Exclusive, range(0, 100), None)
+            .unwrap();
+        table.unlock(FILE, a, range(40, 60));
+        table
+            .try_lock(FILE, b, LockKind::Exclusive, range(40, 60), None)
+            .unwrap();
+        assert!(table
+            .conflict(FILE, b, LockKind::Shared, range(30, 41))
+            .is_some());
+        assert!(table
+            .conflict(FILE, b, LockKind::Shared, range(59, 70))
+            .is_some());
+
+        table.release_process(1);
+        assert!(table
+            .conflict(FILE, b, LockKind::Exclusive, range(0, 100))
+            .is_none());
+    }
+
+    #[test]
+    fn test_flock_and_fcntl_are_independent() {
+        let table = LockTable::new();
+        table
+            .try_lock(
+                FILE,
+                LockOwner::OpenFile(7),
+                LockKind::Exclusive,
+                LockRange::WHOLE,
+                None,
+            )
+            .unwrap();
+        table
+            .try_lock(
+                FILE,
+                LockOwner::Process(1),
+                LockKind::Exclusive,
+                LockRange::WHOLE,
+                None,
+            )
+            .unwrap();
+        assert!(table
+            .try_lock(
+                FILE,
+                LockOwner::OpenFile(8),
+                LockKind::Shared,
+                LockRange::WHOLE,
+                None
+            )
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocked_lock_waits_for_release() {
+        let table = Arc::new(LockTable::new());
+        let (a, b) = (LockOwner::Process(1), LockOwner::Process(2));
+        table
+            .try_lock(FILE, a, LockKind::Exclusive, LockRange::WHOLE, None)
+            .unwrap();
+
+        let waiter = tokio::spawn({
+            let table = table.clone();
+            async move {
+                table
+                    .lock(FILE, b, LockKind::Exclusive, LockRange::WHOLE, None)
+                    .await
+            }
+        });
+        tokio::task::yield_now().await;
+        assert!(!waiter.is_finished());
+
+        table.release(FILE, a);
+        waiter.await.unwrap();
+        assert_eq!(
+            table
+                .conflict(FILE, a, LockKind::Shared, range(0, 1))
+                .map(|lock| lock.owner),
+            Some(b)
+        );
+    }
+}
@@ -1,8 +1,13 @@
 pub mod bind;
+#[cfg(target_os = "linux")]
+pub mod dev;
 pub mod fdtable;
 pub mod file;
+pub mod lock;
 pub mod mount;
 #[cfg(target_os = "linux")]
+pub mod proc;
+#[cfg(target_os = "linux")]
 pub mod sqlite;
 
 use async_trait::async_trait;
@@ -15,11 +20,36 @@ pub enum VfsError {
     NotFound,
     PermissionDenied,
     AlreadyExists,
+    /// Too many symbolic links were followed (`ELOOP`)
+    SymlinkLoop,
+    /// A path component or the target is not a directory (`ENOTDIR`)
+    NotADirectory,
+    /// The target is a directory where a file is required (`EISDIR`)
+    IsADirectory,
     InvalidInput(String),
     IoError(std::io::Error),
     Other(String),
 }
 
+impl VfsError {
+    /// Convert to the libc errno code a failing syscall returns to the guest
+    ///
+    /// [`VfsError::Other`] and I/O errors without an OS error code map to `EIO`.
+    pub fn to_errno(&self) -> i32 {
+        match self {
+            VfsError::NotFound => libc::ENOENT,
+            VfsError::PermissionDenied => libc::EACCES,
+            VfsError::AlreadyExists => libc::EEXIST,
+            VfsError::SymlinkLoop => libc::ELOOP,
+            VfsError::NotADirectory => libc::ENOTDIR,
+            VfsError::IsADirectory => libc::EISDIR,
+            VfsError::InvalidInput(_) => libc::EINVAL,
+            VfsError::IoError(err) => err.raw_os_error().unwrap_or(libc::EIO),
+            VfsError::Other(_) => libc::EIO,
+        }
+    }
+}
+
 impl From<std::io::Error> for VfsError {
     fn from(err: std::io::Error) -> Self {
         VfsError::IoError(err)
@@ -32,6 +62,9 @@ impl std::fmt::Display for VfsError {
             VfsError::NotFound => write!(f, "Not found"),
             VfsError::PermissionDenied => write!(f, "Permission denied"),
             VfsError::AlreadyExists => write!(f, "Already exists"),
+            VfsError::SymlinkLoop => write!(f, "Too many levels of symbolic links"),
+            VfsError::NotADirectory => write!(f, "Not a directory"),
+            VfsError::IsADirectory => write!(f, "Is a directory"),
             VfsError::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
             VfsError::IoError(err) => write!(f, "IO error: {}", err),
             VfsError::Other(msg) => write!(f, "{}", msg),
@@ -43,6 +76,38 @@ impl std::error::Error for VfsError {}
 
 pub type VfsResult<T> = StdResult<T, VfsError>;
 
+/// A directory entry, as reported by `getdents64`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    /// Inode number
+    pub ino: u64,
+    /// Entry name (without path)
+    pub name: String,
+    /// Entry type (`libc::DT_*`)
+    pub d_type: u8,
+}
+
+impl DirEntry {
+    /// Create a directory entry, deriving the `DT_*` type from a `st_mode` value
+    pub fn new(ino: u64, name: impl Into<String>, mode: u32) -> Self {
+        let d_type = match mode & libc::S_IFMT {
+            libc::S_IFDIR => libc::DT_DIR,
+            libc::S_IFREG => libc::DT_REG,
+            libc::S_IFLNK => libc::DT_LNK,
+            libc::S_IFIFO => libc::DT_FIFO,
+            libc::S_IFSOCK => libc::DT_SOCK,
+            libc::S_IFCHR => libc::DT_CHR,
+            libc::S_IFBLK => libc::DT_BLK,
+            _ => libc::DT_UNKNOWN,
+        };
+        Self {
+            ino,
+            name: name.into(),
+            d_type,
+        }
+    }
+}
+
 use file::BoxedFileOps;
 
 /// Virtual file system trait.
@@ -57,6 +122,16 @@ pub trait Vfs: Send + Sync {
     /// It maps a guest/sandbox path to the real path that should be used.
     fn translate_path(&self, path: &Path) -> VfsResult<PathBuf>;
 
+    /// Short name of this kind of VFS, as shown in mount listings
+    fn kind(&self) -> &'static str {
+        "custom"
+    }
+
+    /// Host path or database file the VFS serves, as shown in mount listings
+    fn source(&self) -> Option<PathBuf> {
+        None
+    }
+
     /// Check if this VFS is purely virtual (no kernel file descriptors)
     ///
     /// Returns true if files are stored entirely in the VFS (like SQLite),
@@ -65,6 +140,55 @@ pub trait Vfs: Send + Sync {
         false
     }
 
+    /// Check if this VFS rejects writes
+    ///
+    /// Used to answer `access(W_OK)` for virtual filesystems, and to fail
+    /// syscalls that would change the mount with `EROFS`.
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    /// Ownership reported for every file in this VFS, as `(uid, gid)`
+    ///
+    /// `None` keeps the real owner. When either is set, the syscall handlers
+    /// serve stat through `stat()`/`lstat()` and restrict chown.
+    fn owner_override(&self) -> (Option<u32>, Option<u32>) {
+        (None, None)
+    }
+
+    /// Check if this VFS rewrites file ownership
+    fn remaps_ownership(&self) -> bool {
+        let (uid, gid) = self.owner_override();
+        uid.is_some() || gid.is_some()
+    }
+
+    /// Rewrite the ownership fields of a stat buffer according to `owner_override()`
+    fn apply_owner_override(&self, stat: &mut libc::stat) {
+        let (uid, gid) = self.owner_override();
+        if let Some(uid) = uid {
+            stat.st_uid = uid;
+        }
+        if let Some(gid) = gid {
+            stat.st_gid = gid;
+        }
+    }
+
+    /// Check if `check_path()` can refuse paths
+    ///
+    /// The sandbox only reads path arguments for checking when some mount
+    /// needs it.
+    fn checks_paths(&self) -> bool {
+        false
+    }
+
+    /// Check that a path in this VFS may be used, before its syscall runs
+    ///
+    /// An error fails the syscall with the matching errno. The default accepts
+    /// every path.
+    fn check_path(&self, _path: &Path) -> VfsResult<()> {
+        Ok(())
+    }
+
     /// Open a file directly in the VFS (for virtual filesystems)
     ///
     /// This is only called for virtual VFS implementations. For passthrough
@@ -77,8 +201,9 @@ pub trait Vfs: Send + Sync {
     /// Get file status directly from the VFS (for virtual filesystems)
     /// This follows symlinks.
     ///
-    /// This is only called for virtual VFS implementations. For passthrough
-    /// VFS, the kernel handles stat operations.
+    /// This is only called for virtual VFS implementations and for VFS that
+    /// rewrite ownership. For other passthrough VFS, the kernel handles stat
+    /// operations.
     async fn stat(&self, _path: &Path) -> VfsResult<libc::stat> {
         Err(VfsError::Other(
             "stat() not supported by this VFS".to_string(),
@@ -87,13 +212,23 @@ pub trait Vfs: Send + Sync {
 
     /// Get file status without following symlinks (for virtual filesystems)
     ///
-    /// This is only called for virtual VFS implementations.
+    /// This is only called for virtual VFS implementations and for VFS that
+    /// rewrite ownership.
     async fn lstat(&self, _path: &Path) -> VfsResult<libc::stat> {
         Err(VfsError::Other(
             "lstat() not supported by this VFS".to_string(),
         ))
     }
 
+    /// List a directory, including the `.` and `..` entries (for virtual filesystems)
+    ///
+    /// This is only called for virtual VFS implementations.
+    async fn readdir(&self, _path: &Path) -> VfsResult<Vec<DirEntry>> {
+        Err(VfsError::Other(
+            "readdir() not supported by this VFS".to_string(),
+        ))
+    }
+
     /// Create a symbolic link (for virtual filesystems)
     ///
     /// This is only called for virtual VFS implementations.
@@ -121,6 +256,47 @@ pub trait Vfs: Send + Sync {
             "link() not supported by this VFS".to_string(),
         ))
     }
+
+    /// Create a directory entry `newpath` for an open file (for virtual filesystems)
+    ///
+    /// This backs `linkat` with `AT_EMPTY_PATH` or a `/proc/self/fd` path, which is
+    /// how files opened with `O_TMPFILE` get a name. `file` must have been opened
+    /// by this VFS. This is only called for virtual VFS implementations.
+    async fn link_fd(&self, _file: &BoxedFileOps, _newpath: &Path) -> VfsResult<()> {
+        Err(VfsError::Other(
+            "link_fd() not supported by this VFS".to_string(),
+        ))
+    }
+
+    /// Change the permission bits of a file (for virtual filesystems)
+    ///
+    /// The file type bits of the stored mode are kept.
+    /// This is only called for virtual VFS implementations.
+    async fn chmod(&self, _path: &Path, _mode: u32) -> VfsResult<()> {
+        Err(VfsError::Other(
+            "chmod() not supported by this VFS".to_string(),
+        ))
+    }
+
+    /// Change the owner and group of a file (for virtual filesystems)
+    ///
+    /// `None` leaves the uid or gid unchanged.
+    /// This is only called for virtual VFS implementations.
+    async fn chown(&self, _path: &Path, _uid: Option<u32>, _gid: Option<u32>) -> VfsResult<()> {
+        Err(VfsError::Other(
+            "chown() not supported by this VFS".to_string(),
+        ))
+    }
+
+    /// Report the capacity and usage of the filesystem (for virtual filesystems)
+    ///
+    /// Answers `statfs`, `fstatfs` and thereby `statvfs` for paths and files on
+    /// the mount. This is only called for virtual VFS implementations.
+    async fn statfs(&self) -> VfsResult<libc::statvfs> {
+        Err(VfsError::Other(
+            "statfs() not supported by this VFS".to_string(),
+        ))
+    }
 }
 
 /// A boxed VFS trait object for dynamic dispatch
@@ -1,4 +1,4 @@
-use super::Vfs;
+use super::{Vfs, VfsResult};
 use serde::{Deserialize, Serialize};
 use std::{
     cmp::Reverse,
@@ -58,10 +58,108 @@ impl MountTable {
         None
     }
 
+    /// Check if two paths are served by the same mount
+    ///
+    /// Paths outside every mount count as one mount, the host's. Like the
+    /// kernel's, a rename or hard link between two mounts fails with `EXDEV`.
+    pub fn same_mount(&self, a: &Path, b: &Path) -> bool {
+        match (self.resolve(a), self.resolve(b)) {
+            (Some((a, _)), Some((b, _))) => Arc::ptr_eq(&a, &b),
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    /// Check if any mount refuses some paths in `check_path()`
+    pub fn checks_paths(&self) -> bool {
+        self.mounts.iter().any(|mount| mount.vfs.checks_paths())
+    }
+
+    /// Check if any mount refuses writes
+    pub fn has_read_only(&self) -> bool {
+        self.mounts.iter().any(|mount| mount.vfs.is_read_only())
+    }
+
+    /// Check if `path` lies in a read-only mount
+    pub fn is_read_only(&self, path: &Path) -> bool {
+        self.resolve(path)
+            .is_some_and(|(vfs, _)| vfs.is_read_only())
+    }
+
+    /// Check a path with the VFS of the mount it lies under
+    ///
+    /// Unlike [`MountTable::resolve`], the mount is chosen by its mount point
+    /// alone, so a path its VFS refuses to translate is still checked by it.
+    pub fn check_path(&self, path: &Path) -> VfsResult<()> {
+        match self
+            .mounts
+            .iter()
+            .find(|mount| path.starts_with(&mount.sandbox_path))
+        {
+            Some(mount) => mount.vfs.check_path(path),
+            None => Ok(()),
+        }
+    }
+
     /// Get all mount points
     pub fn mounts(&self) -> &[MountPoint] {
         &self.mounts
     }
+
+    /// Summarize every mount, in the order paths are matched against them
+    pub fn describe(&self) -> Vec<MountInfo> {
+        self.mounts
+            .iter()
+            .map(|mount| {
+                let (uid_map, gid_map) = mount.vfs.owner_override();
+                MountInfo {
+                    dst: mount.sandbox_path.clone(),
+                    kind: mount.vfs.kind().to_string(),
+                    src: mount.vfs.source(),
+                    read_only: mount.vfs.is_read_only(),
+                    is_virtual: mount.vfs.is_virtual(),
+                    uid_map,
+                    gid_map,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Summary of a mount point, from [`MountTable::describe`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MountInfo {
+    /// Path in the sandbox the mount is seen at
+    pub dst: PathBuf,
+    /// Kind of VFS: `bind`, `sqlite`, `tmpfs`, `dev`, `proc` or `custom`
+    pub kind: String,
+    /// Host path or database file behind the mount, if any
+    pub src: Option<PathBuf>,
+    /// Whether writes are refused
+    pub read_only: bool,
+    /// Whether files live only in the VFS, with no host file descriptors
+    pub is_virtual: bool,
+    /// Owner uid reported for every file in the mount
+    pub uid_map: Option<u32>,
+    /// Owner gid reported for every file in the mount
+    pub gid_map: Option<u32>,
+}
+
+impl std::fmt::Display for MountInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} type={}", self.dst.display(), self.kind)?;
+        if let Some(src) = &self.src {
+            write!(f, " src={}", src.display())?;
+        }
+        write!(f, " {}", if self.read_only { "ro" } else { "rw" })?;
+        if let Some(uid) = self.uid_map {
+            write!(f, " uid_map={}", uid)?;
+        }
+        if let Some(gid) = self.gid_map {
+            write!(f, " gid_map={}", gid)?;
+        }
+        Ok(())
+    }
 }
 
 impl Default for MountTable {
@@ -88,9 +186,10 @@ pub enum MountType {
     ///
     /// Similar to Linux bind mounts, this makes a host directory tree
     /// available at a path within the sandbox. The source path is
-    /// canonicalized (symlinks are resolved) during parsing.
+    /// canonicalized (symlinks are resolved) during parsing, or when the
+    /// sandbox starts for mounts built with [`MountConfig::bind`].
     Bind {
-        /// Source path on the host (canonicalized).
+        /// Source path on the host.
         src: PathBuf,
     },
     /// SQLite-backed virtual filesystem.
@@ -101,6 +200,21 @@ pub enum MountType {
         /// Path to the SQLite database file.
         src: PathBuf,
     },
+    /// Minimal virtual `/dev`.
+    ///
+    /// Serves `null`, `zero`, `full`, `random`, `urandom` and `tty` without
+    /// exposing the host's devices.
+    Dev,
+    /// Skeletal `/proc`.
+    ///
+    /// Passes `/proc/self` and a few system files through to the host's
+    /// `/proc`, and nothing else.
+    Proc,
+    /// Empty in-memory filesystem, discarded when the sandbox exits.
+    Tmpfs {
+        /// Most bytes the files in the mount may hold together.
+        size: Option<u64>,
+    },
 }
 
 /// Configuration for a mount point (used for CLI parsing).
@@ -109,12 +223,125 @@ pub enum MountType {
 /// `type=bind,src=/host/path,dst=/sandbox/path`
 ///
 /// Aliases are supported: `source` for `src`, `target` for `dst`.
+///
+/// Bind mounts also accept `uid_map=<uid>` and `gid_map=<gid>` (aliases `uid`
+/// and `gid`) to make every file appear owned by the given ids. Any mount can
+/// be made read-only with `readonly=true` (alias `ro`), and `tmpfs` mounts take
+/// a `size` in bytes, optionally suffixed with `k`, `m` or `g`.
+///
+/// In Rust code, start from one of the constructors instead:
+///
+/// ```ignore
+/// let config = MountConfig::bind("/srv/data", "/data").read_only();
+/// ```
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MountConfig {
     /// Type of mount.
     pub mount_type: MountType,
     /// Destination path in the sandbox (must be absolute).
     pub dst: PathBuf,
+    /// Owner uid reported for every file in the mount.
+    #[serde(default)]
+    pub uid_map: Option<u32>,
+    /// Owner gid reported for every file in the mount.
+    #[serde(default)]
+    pub gid_map: Option<u32>,
+    /// Refuse writes to the mount.
+    #[serde(default)]
+    pub read_only: bool,
+}
+
+impl MountConfig {
+    fn new(mount_type: MountType, dst: impl Into<PathBuf>) -> Self {
+        Self {
+            mount_type,
+            dst: dst.into(),
+            uid_map: None,
+            gid_map: None,
+            read_only: false,
+        }
+    }
+
+    /// Bind mount the host directory `src` at `dst`
+    ///
+    /// Unlike a parsed specification, `src` is used as given; it is not
+    /// canonicalized or checked to exist until the sandbox starts.
+    pub fn bind(src: impl Into<PathBuf>, dst: impl Into<PathBuf>) -> Self {
+        Self::new(MountType::Bind { src: src.into() }, dst)
+    }
+
+    /// Mount the AgentFS database `src` at `dst`, creating it if needed
+    pub fn sqlite(src: impl Into<PathBuf>, dst: impl Into<PathBuf>) -> Self {
+        Self::new(MountType::Sqlite { src: src.into() }, dst)
+    }
+
+    /// Mount an empty in-memory filesystem at `dst`, holding at most `size` bytes
+    pub fn tmpfs(dst: impl Into<PathBuf>, size: Option<u64>) -> Self {
+        Self::new(MountType::Tmpfs { size }, dst)
+    }
+
+    /// Mount a minimal virtual `/dev` at `dst`
+    pub fn dev(dst: impl Into<PathBuf>) -> Self {
+        Self::new(MountType::Dev, dst)
+    }
+
+    /// Mount a skeletal `/proc` at `dst`
+    pub fn proc(dst: impl Into<PathBuf>) -> Self {
+        Self::new(MountType::Proc, dst)
+    }
+
+    /// Refuse writes to the mount
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Report every file in a bind mount as owned by the given uid and/or gid
+    pub fn with_owner(mut self, uid: Option<u32>, gid: Option<u32>) -> Self {
+        self.uid_map = uid;
+        self.gid_map = gid;
+        self
+    }
+}
+
+/// Parse a byte count such as `65536`, `64k`, `16M` or `1g`
+fn parse_size(value: &str) -> Result<u64, String> {
+    let invalid = || {
+        format!(
+            "Invalid size '{}'. Expected a number of bytes, optionally suffixed with k, m or g.",
+            value
+        )
+    };
+    let (digits, unit) = match value.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&value[..i], c.to_ascii_lowercase()),
+        _ => (value, 'b'),
+    };
+    let shift = match unit {
+        'b' => 0,
+        'k' => 10,
+        'm' => 20,
+        'g' => 30,
+        _ => return Err(invalid()),
+    };
+    let n: u64 = digits.parse().map_err(|_| invalid())?;
+    n.checked_mul(1 << shift).ok_or_else(invalid)
+}
+
+/// Parse an optional numeric id from the mount options, accepting an alias.
+fn parse_id_option(
+    options: &std::collections::HashMap<String, String>,
+    key: &str,
+    alias: &str,
+) -> Result<Option<u32>, String> {
+    options
+        .get(key)
+        .or_else(|| options.get(alias))
+        .map(|value| {
+            value
+                .parse::<u32>()
+                .map_err(|_| format!("Invalid {} '{}'. Expected a numeric id.", key, value))
+        })
+        .transpose()
 }
 
 impl std::str::FromStr for MountConfig {
@@ -149,6 +376,25 @@ impl std::str::FromStr for MountConfig {
                 .to_string()
         })?;
 
+        let uid_map = parse_id_option(&options, "uid_map", "uid")?;
+        let gid_map = parse_id_option(&options, "gid_map", "gid")?;
+        let read_only = match options.get("readonly").or_else(|| options.get("ro")) {
+            None => false,
+            Some(value) => match value.as_str() {
+                "true" | "1" => true,
+                "false" | "0" => false,
+                _ => {
+                    return Err(format!(
+                        "Invalid readonly '{}'. Expected true or false.",
+                        value
+                    ))
+                }
+            },
+        };
+        if options.contains_key("size") && mount_type.as_str() != "tmpfs" {
+            return Err("size is only supported for tmpfs mounts.".to_string());
+        }
+
         match mount_type.as_str() {
             "bind" => {
                 // Get src (or source as alias)
@@ -179,6 +425,9 @@ impl std::str::FromStr for MountConfig {
                 Ok(MountConfig {
                     mount_type: MountType::Bind { src },
                     dst,
+                    uid_map,
+                    gid_map,
+                    read_only,
                 })
             }
             "sqlite" => {
@@ -202,16 +451,72 @@ impl std::str::FromStr for MountConfig {
                     return Err(format!("Destination path '{}' must be absolute.", dst_str));
                 }
 
+                // SQLite mounts store their own ownership, so there is nothing to remap
+                if uid_map.is_some() || gid_map.is_some() {
+                    return Err(
+                        "uid_map and gid_map are only supported for bind mounts.".to_string()
+                    );
+                }
+
                 // For SQLite, we use the path as-is (may be relative or absolute)
                 let src = PathBuf::from(src_str);
 
                 Ok(MountConfig {
                     mount_type: MountType::Sqlite { src },
                     dst,
+                    uid_map: None,
+                    gid_map: None,
+                    read_only,
+                })
+            }
+            "dev" | "proc" | "tmpfs" => {
+                // Get dst (or target as alias)
+                let dst_str = options
+                    .get("dst")
+                    .or_else(|| options.get("target"))
+                    .ok_or_else(|| {
+                        format!(
+                            "{} mount requires 'dst' field. Example: type={},dst=/{}.",
+                            mount_type, mount_type, mount_type
+                        )
+                    })?;
+
+                // Validate destination is absolute
+                let dst = PathBuf::from(dst_str);
+                if !dst.is_absolute() {
+                    return Err(format!("Destination path '{}' must be absolute.", dst_str));
+                }
+
+                // These are served by the sandbox, not a host path or database
+                if options.contains_key("src") || options.contains_key("source") {
+                    return Err(format!("{} mounts do not take a 'src' field.", mount_type));
+                }
+                if uid_map.is_some() || gid_map.is_some() {
+                    return Err(
+                        "uid_map and gid_map are only supported for bind mounts.".to_string()
+                    );
+                }
+
+                let mount_type = match mount_type.as_str() {
+                    "dev" => MountType::Dev,
+                    "proc" => MountType::Proc,
+                    _ => MountType::Tmpfs {
+                        size: options
+                            .get("size")
+                            .map(|size| parse_size(size))
+                            .transpose()?,
+                    },
+                };
+                Ok(MountConfig {
+                    mount_type,
+                    dst,
+                    uid_map: None,
+                    gid_map: None,
+                    read_only,
                 })
             }
             _ => Err(format!(
-                "Unsupported mount type '{}'. Supported types: bind, sqlite.",
+                "Unsupported mount type '{}'. Supported types: bind, sqlite, tmpfs, dev, proc.",
                 mount_type
             )),
         }
@@ -259,6 +564,67 @@ mod tests {
         assert_eq!(translated, PathBuf::from("/tmp/agent/normal"));
     }
 
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_same_mount() {
+        use crate::vfs::sqlite::SqliteVfs;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut table = MountTable::new();
+        table.add_mount(
+            PathBuf::from("/work"),
+            Arc::new(BindVfs::new(
+                PathBuf::from("/tmp/work"),
+                PathBuf::from("/work"),
+            )),
+        );
+        let sqlite = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/work/agent"))
+            .await
+            .unwrap();
+        table.add_mount(PathBuf::from("/work/agent"), Arc::new(sqlite));
+
+        assert!(table.same_mount(Path::new("/work/a"), Path::new("/work/b/c")));
+        assert!(table.same_mount(Path::new("/work/agent/a"), Path::new("/work/agent/b")));
+        assert!(table.same_mount(Path::new("/etc/a"), Path::new("/usr/b")));
+
+        // Bind mount to the SQLite mount nested in it, and either to the host
+        assert!(!table.same_mount(Path::new("/work/a"), Path::new("/work/agent/a")));
+        assert!(!table.same_mount(Path::new("/work/agent/a"), Path::new("/work/a")));
+        assert!(!table.same_mount(Path::new("/work/a"), Path::new("/tmp/a")));
+        assert!(!table.same_mount(Path::new("/tmp/a"), Path::new("/work/agent/a")));
+    }
+
+    #[test]
+    fn test_describe() {
+        let mut table = MountTable::new();
+        table.add_mount(
+            PathBuf::from("/data"),
+            Arc::new(
+                BindVfs::new(PathBuf::from("/tmp/data"), PathBuf::from("/data"))
+                    .with_owner(Some(1000), None),
+            ),
+        );
+        table.add_mount(
+            PathBuf::from("/data/cache"),
+            Arc::new(BindVfs::new(
+                PathBuf::from("/tmp/cache"),
+                PathBuf::from("/data/cache"),
+            )),
+        );
+
+        // Deeper mounts come first, as they are matched first
+        let mounts = table.describe();
+        assert_eq!(mounts.len(), 2);
+        assert_eq!(mounts[0].dst, PathBuf::from("/data/cache"));
+        assert_eq!(mounts[1].kind, "bind");
+        assert_eq!(mounts[1].src, Some(PathBuf::from("/tmp/data")));
+        assert!(!mounts[1].read_only && !mounts[1].is_virtual);
+        assert_eq!(
+            mounts[1].to_string(),
+            "/data type=bind src=/tmp/data rw uid_map=1000"
+        );
+    }
+
     #[test]
     fn test_mount_table_no_match() {
         let mut table = MountTable::new();
@@ -287,7 +653,7 @@ mod tests {
                 assert_eq!(src, std::fs::canonicalize("/tmp").unwrap());
                 assert_eq!(config.dst, PathBuf::from("/data"));
             }
-            MountType::Sqlite { .. } => panic!("Expected Bind mount, got Sqlite"),
+            other => panic!("Expected Bind mount, got {:?}", other),
         }
     }
 
@@ -303,8 +669,99 @@ mod tests {
                 assert_eq!(src, std::fs::canonicalize("/tmp").unwrap());
                 assert_eq!(config.dst, PathBuf::from("/data"));
             }
-            MountType::Sqlite { .. } => panic!("Expected Bind mount, got Sqlite"),
+            other => panic!("Expected Bind mount, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_bind_mount_with_owner_maps() {
+        let config: MountConfig = "type=bind,src=/tmp,dst=/data,uid_map=1000,gid=1000"
+            .parse()
+            .unwrap();
+        assert_eq!(config.uid_map, Some(1000));
+        assert_eq!(config.gid_map, Some(1000));
+
+        let config: MountConfig = "type=bind,src=/tmp,dst=/data".parse().unwrap();
+        assert_eq!(config.uid_map, None);
+        assert_eq!(config.gid_map, None);
+    }
+
+    #[test]
+    fn test_parse_invalid_owner_maps() {
+        let config: Result<MountConfig, _> = "type=bind,src=/tmp,dst=/data,uid_map=alice".parse();
+        assert!(config.unwrap_err().contains("Invalid uid_map"));
+
+        let config: Result<MountConfig, _> = "type=sqlite,src=agent.db,dst=/agent,uid=1000".parse();
+        assert!(config
+            .unwrap_err()
+            .contains("only supported for bind mounts"));
+    }
+
+    #[test]
+    fn test_parse_dev_and_proc_mounts() {
+        let config: MountConfig = "type=dev,dst=/dev".parse().unwrap();
+        assert!(matches!(config.mount_type, MountType::Dev));
+        assert_eq!(config.dst, PathBuf::from("/dev"));
+
+        let config: MountConfig = "type=proc,target=/proc".parse().unwrap();
+        assert!(matches!(config.mount_type, MountType::Proc));
+
+        let config: Result<MountConfig, _> = "type=dev,src=/dev,dst=/dev".parse();
+        assert!(config.unwrap_err().contains("do not take a 'src'"));
+        let config: Result<MountConfig, _> = "type=proc".parse();
+        assert!(config.unwrap_err().contains("requires 'dst' field"));
+    }
+
+    #[test]
+    fn test_parse_tmpfs_and_readonly() {
+        let config: MountConfig = "type=tmpfs,dst=/scratch,size=16M".parse().unwrap();
+        assert!(matches!(
+            config.mount_type,
+            MountType::Tmpfs {
+                size: Some(0x100_0000)
+            }
+        ));
+        assert!(!config.read_only);
+
+        let config: MountConfig = "type=tmpfs,dst=/scratch".parse().unwrap();
+        assert!(matches!(config.mount_type, MountType::Tmpfs { size: None }));
+
+        let config: MountConfig = "type=sqlite,src=agent.db,dst=/agent,ro=true"
+            .parse()
+            .unwrap();
+        assert!(config.read_only);
+
+        let config: Result<MountConfig, _> = "type=tmpfs,dst=/scratch,size=lots".parse();
+        assert!(config.unwrap_err().contains("Invalid size"));
+        let config: Result<MountConfig, _> = "type=sqlite,src=a.db,dst=/a,size=1k".parse();
+        assert!(config.unwrap_err().contains("only supported for tmpfs"));
+        let config: Result<MountConfig, _> = "type=bind,src=/tmp,dst=/a,readonly=yes".parse();
+        assert!(config.unwrap_err().contains("Invalid readonly"));
+    }
+
+    #[test]
+    fn test_mount_config_builders() {
+        let config = MountConfig::bind("/srv/data", "/data")
+            .read_only()
+            .with_owner(Some(1000), None);
+        match &config.mount_type {
+            MountType::Bind { src } => assert_eq!(src, &PathBuf::from("/srv/data")),
+            other => panic!("Expected Bind mount, got {:?}", other),
         }
+        assert_eq!(config.dst, PathBuf::from("/data"));
+        assert!(config.read_only);
+        assert_eq!((config.uid_map, config.gid_map), (Some(1000), None));
+
+        let config = MountConfig::sqlite("agent.db", "/agent");
+        assert!(matches!(config.mount_type, MountType::Sqlite { .. }));
+        assert!(!config.read_only);
+
+        let config = MountConfig::tmpfs("/scratch", Some(4096));
+        assert!(matches!(
+            config.mount_type,
+            MountType::Tmpfs { size: Some(4096) }
+        ));
+        assert_eq!(config.dst, PathBuf::from("/scratch"));
     }
 
     #[test]
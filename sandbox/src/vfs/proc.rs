@@ -0,0 +1,89 @@
+use super::bind::BindVfs;
+use super::{Vfs, VfsError, VfsResult};
+use std::path::{Component, Path, PathBuf};
+
+/// Top-level `/proc` entries a [`ProcVfs`] passes through
+const PROC_ENTRIES: &[&str] = &[
+    "self",
+    "thread-self",
+    "cpuinfo",
+    "meminfo",
+    "loadavg",
+    "uptime",
+    "version",
+];
+
+/// A skeletal `/proc`, limited to the calling process and a few system files
+///
+/// Paths are passed through to the host's `/proc`, so `/proc/self` refers to
+/// the sandboxed process itself. Only `self`, `thread-self` and a few files
+/// describing the system resolve to this mount; anything else, like other
+/// processes' directories, is left to the mounts below it (under a virtual
+/// root, it doesn't exist).
+#[derive(Debug, Clone)]
+pub struct ProcVfs {
+    bind: BindVfs,
+}
+
+impl ProcVfs {
+    /// Create a proc VFS mounted at `mount_point` (usually `/proc`)
+    pub fn new(mount_point: PathBuf) -> Self {
+        Self {
+            bind: BindVfs::new(PathBuf::from("/proc"), mount_point),
+        }
+    }
+
+    /// Get the mount point path
+    pub fn mount_point(&self) -> &Path {
+        self.bind.sandbox_root()
+    }
+}
+
+#[async_trait::async_trait]
+impl Vfs for ProcVfs {
+    fn translate_path(&self, path: &Path) -> VfsResult<PathBuf> {
+        let relative = path
+            .strip_prefix(self.mount_point())
+            .map_err(|_| VfsError::NotFound)?;
+        match relative.components().next() {
+            None => {}
+            Some(Component::Normal(name))
+                if PROC_ENTRIES.contains(&name.to_str().unwrap_or("")) => {}
+            Some(_) => return Err(VfsError::NotFound),
+        }
+        self.bind.translate_path(path)
+    }
+
+    fn kind(&self) -> &'static str {
+        "proc"
+    }
+
+    fn source(&self) -> Option<PathBuf> {
+        self.bind.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_path() {
+        let vfs = ProcVfs::new(PathBuf::from("/proc"));
+
+        assert_eq!(
+            vfs.translate_path(Path::new("/proc/self/status")).unwrap(),
+            PathBuf::from("/proc/self/status")
+        );
+        assert_eq!(
+            vfs.translate_path(Path::new("/proc")).unwrap(),
+            PathBuf::from("/proc")
+        );
+        assert!(vfs.translate_path(Path::new("/proc/cpuinfo")).is_ok());
+
+        // Other processes and unlisted files are not part of the mount
+        assert!(vfs.translate_path(Path::new("/proc/1/environ")).is_err());
+        assert!(vfs.translate_path(Path::new("/proc/kcore")).is_err());
+        assert!(vfs.translate_path(Path::new("/process")).is_err());
+    }
+}
@@ -1,10 +1,144 @@
 use super::file::{BoxedFileOps, FileOps};
-use super::{Vfs, VfsError, VfsResult};
-use agentfs_sdk::{filesystem::AgentFS, FileSystem};
+use super::{DirEntry, Vfs, VfsError, VfsResult};
+use agentfs_sdk::{filesystem::AgentFS, validate_path, AgentFsError, FileSystem, FsError};
+use lru::LruCache;
+use std::ffi::CString;
+use std::num::NonZeroUsize;
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::io::RawFd;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
 
+/// Device number handed out to the next [`SqliteVfs`]
+///
+/// Inode numbers are only unique within one database, so each mount reports
+/// its own `st_dev` to keep `(st_dev, st_ino)` distinct across sqlite mounts.
+/// Numbering starts above the 32-bit range used by host devices.
+static NEXT_DEV: AtomicU64 = AtomicU64::new(1 << 32);
+
+/// Allocate a device number for a new [`SqliteVfs`]
+fn next_dev() -> u64 {
+    NEXT_DEV.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Convert an SDK error, keeping the errno of filesystem errors
+fn fs_error(context: &str, err: impl Into<AgentFsError>) -> VfsError {
+    match err.into() {
+        AgentFsError::NotFound(_) => VfsError::NotFound,
+        AgentFsError::AlreadyExists(_) => VfsError::AlreadyExists,
+        AgentFsError::Fs(FsError::SymlinkLoop) => VfsError::SymlinkLoop,
+        AgentFsError::Fs(FsError::NotADirectory) => VfsError::NotADirectory,
+        AgentFsError::Fs(FsError::IsADirectory) => VfsError::IsADirectory,
+        // EFBIG, ENOSPC and the like; only errors without one become EIO
+        err if err.to_errno() != libc::EIO => {
+            VfsError::IoError(std::io::Error::from_raw_os_error(err.to_errno()))
+        }
+        err => VfsError::Other(format!("{}: {}", context, err)),
+    }
+}
+
+/// Convert an SDK error from `link`, where Linux reports a directory source
+/// as `EPERM`
+fn link_error(err: impl Into<AgentFsError>) -> VfsError {
+    match err.into() {
+        AgentFsError::Fs(FsError::IsADirectory) => {
+            VfsError::IoError(std::io::Error::from_raw_os_error(libc::EPERM))
+        }
+        err => fs_error("Failed to create hard link", err),
+    }
+}
+
+/// Block size reported by `statfs`, the same as the `st_blksize` of files
+const BLOCK_SIZE: u64 = 4096;
+
+/// `statvfs` of the host filesystem containing `path`
+fn host_statvfs(path: &Path) -> VfsResult<libc::statvfs> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| VfsError::InvalidInput("Path contains NUL byte".to_string()))?;
+    let mut buf: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut buf) } != 0 {
+        return Err(VfsError::IoError(std::io::Error::last_os_error()));
+    }
+    Ok(buf)
+}
+
+/// Parent of an SDK path, or `None` for the root
+fn parent_path(path: &str) -> Option<&str> {
+    match path.rfind('/')? {
+        0 if path.len() > 1 => Some("/"),
+        0 => None,
+        i => Some(&path[..i]),
+    }
+}
+
+/// LRU cache of path lookups and file status for a [`SqliteVfs`]
+///
+/// Maps (path, follow final symlink) -> inode and inode -> stat, so that paths
+/// leading to the same inode share its status. Entries are dropped whenever
+/// the VFS changes the path they were looked up by. Access times are not
+/// refreshed by reads while an entry is cached.
+struct StatCache {
+    // Mutex required because LruCache::get() mutates internal order
+    inodes: Mutex<LruCache<(String, bool), u64>>,
+    stats: Mutex<LruCache<u64, libc::stat>>,
+}
+
+impl StatCache {
+    fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            inodes: Mutex::new(LruCache::new(capacity)),
+            stats: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Look up the cached status of `path` (updates LRU order)
+    fn get(&self, path: &str, follow: bool) -> Option<libc::stat> {
+        let ino = *self
+            .inodes
+            .lock()
+            .unwrap()
+            .get(&(path.to_string(), follow))?;
+        self.stats.lock().unwrap().get(&ino).copied()
+    }
+
+    /// Cache the status of `path` (evicts LRU entries if full)
+    fn insert(&self, path: &str, follow: bool, stat: &libc::stat) {
+        self.inodes
+            .lock()
+            .unwrap()
+            .put((path.to_string(), follow), stat.st_ino);
+        self.stats.lock().unwrap().put(stat.st_ino, *stat);
+    }
+
+    /// Forget `path` and the status of the inode it leads to
+    ///
+    /// Without a cached `stat` lookup of `path`, the changed inode is unknown
+    /// and could be cached under another name (a hard link or a symlink), so
+    /// every cached status is dropped.
+    fn invalidate(&self, path: &str) {
+        let mut inodes = self.inodes.lock().unwrap();
+        let mut stats = self.stats.lock().unwrap();
+        if let Some(ino) = inodes.pop(&(path.to_string(), false)) {
+            stats.pop(&ino);
+        }
+        match inodes.pop(&(path.to_string(), true)) {
+            Some(ino) => {
+                stats.pop(&ino);
+            }
+            None => stats.clear(),
+        }
+    }
+
+    /// Forget `path` and its parent directory, after an entry was added or removed
+    fn invalidate_entry(&self, path: &str) {
+        self.invalidate(path);
+        if let Some(parent) = parent_path(path) {
+            self.invalidate(parent);
+        }
+    }
+}
+
 /// A SQLite-backed virtual filesystem using the AgentFS SDK
 ///
 /// This implements a full POSIX-like filesystem stored in a SQLite database,
@@ -15,6 +149,18 @@ pub struct SqliteVfs {
     fs: Arc<dyn FileSystem>,
     /// The virtual path as seen by the sandboxed process
     mount_point: PathBuf,
+    /// Reject opens for writing
+    read_only: bool,
+    /// Cache of path lookups and stats, if enabled
+    cache: Option<Arc<StatCache>>,
+    /// Device number reported in `st_dev`, unique to this VFS
+    dev: u64,
+    /// Database file, if the VFS opened it itself
+    db_path: Option<PathBuf>,
+    /// Whether the database lives only in memory, as for a tmpfs mount
+    in_memory: bool,
+    /// Most bytes the files may hold together, if limited
+    capacity: Option<u64>,
 }
 
 impl SqliteVfs {
@@ -31,19 +177,118 @@ impl SqliteVfs {
 
         let fs = AgentFS::new(db_path_str)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to create filesystem: {}", e)))?;
+            .map_err(|e| fs_error("Failed to create filesystem", e))?;
 
         Ok(Self {
             fs: Arc::new(fs) as Arc<dyn FileSystem>,
             mount_point,
+            read_only: false,
+            cache: None,
+            dev: next_dev(),
+            db_path: Some(db_path.as_ref().to_path_buf()),
+            in_memory: false,
+            capacity: None,
         })
     }
 
+    /// Create a SQLite VFS on a new in-memory database
+    ///
+    /// The filesystem starts empty and is dropped with the last reference to
+    /// the VFS, like a tmpfs.
+    pub async fn in_memory(mount_point: PathBuf) -> VfsResult<Self> {
+        let fs = AgentFS::new(":memory:")
+            .await
+            .map_err(|e| fs_error("Failed to create filesystem", e))?;
+
+        Ok(Self {
+            in_memory: true,
+            db_path: None,
+            ..Self::from_fs(Arc::new(fs), mount_point)
+        })
+    }
+
+    /// Create a SQLite VFS on top of an already opened filesystem
+    ///
+    /// Lets the caller keep using the filesystem (e.g. a clone of the same
+    /// `AgentFS`) alongside the sandbox.
+    pub fn from_fs(fs: Arc<dyn FileSystem>, mount_point: PathBuf) -> Self {
+        Self {
+            fs,
+            mount_point,
+            read_only: false,
+            cache: None,
+            dev: next_dev(),
+            db_path: None,
+            in_memory: false,
+            capacity: None,
+        }
+    }
+
+    /// Name the database file behind a VFS created with [`SqliteVfs::from_fs`]
+    ///
+    /// Only used to describe the mount, e.g. in `MountTable::describe`.
+    pub fn with_source(mut self, db_path: impl Into<PathBuf>) -> Self {
+        self.db_path = Some(db_path.into());
+        self
+    }
+
+    /// Make the mount read-only
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// Limit the total size of the files in the mount to `capacity` bytes
+    ///
+    /// Writes that would go over it fail with `ENOSPC` when the file is
+    /// flushed, and `statfs` reports the capacity instead of the host's disk.
+    pub fn with_capacity(mut self, capacity: Option<u64>) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Cache up to `capacity` path lookups and stats in memory
+    ///
+    /// Speeds up workloads that stat the same paths over and over. The cache
+    /// is kept in sync with changes made through this VFS, but not with other
+    /// users of the filesystem, so it is off by default (a `capacity` of 0).
+    pub fn with_stat_cache(mut self, capacity: usize) -> Self {
+        self.cache = NonZeroUsize::new(capacity).map(|capacity| Arc::new(StatCache::new(capacity)));
+        self
+    }
+
     /// Get the mount point path
     pub fn mount_point(&self) -> &Path {
         &self.mount_point
     }
 
+    /// Device number reported for every file in this VFS
+    ///
+    /// Each `SqliteVfs` gets its own, even when several are opened on the
+    /// same database, so inode numbers from different mounts never collide.
+    pub fn dev(&self) -> u64 {
+        self.dev
+    }
+
+    /// Look up the cached status of `path`, if caching is enabled
+    fn cached_stat(&self, path: &str, follow: bool) -> Option<libc::stat> {
+        self.cache.as_ref()?.get(path, follow)
+    }
+
+    /// Drop cached lookups of `path`
+    fn invalidate(&self, path: &str) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate(path);
+        }
+    }
+
+    /// Drop cached lookups of `path` and its parent directory
+    fn invalidate_entry(&self, path: &str) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate_entry(path);
+        }
+    }
+
     /// Translate a sandbox path to a relative path for the SDK
     fn translate_to_relative(&self, path: &Path) -> VfsResult<String> {
         let path_str = path
@@ -64,6 +309,9 @@ impl SqliteVfs {
             return Err(VfsError::NotFound);
         };
 
+        // Reject paths that would climb out of the mount or contain NUL bytes
+        validate_path(relative).map_err(|e| VfsError::InvalidInput(e.to_string()))?;
+
         Ok(relative.to_string())
     }
 }
@@ -88,20 +336,72 @@ impl Vfs for SqliteVfs {
         }
     }
 
+    fn kind(&self) -> &'static str {
+        if self.in_memory {
+            "tmpfs"
+        } else {
+            "sqlite"
+        }
+    }
+
+    fn source(&self) -> Option<PathBuf> {
+        self.db_path.clone()
+    }
+
     fn is_virtual(&self) -> bool {
         true
     }
 
-    async fn open(&self, path: &Path, flags: i32, _mode: u32) -> VfsResult<BoxedFileOps> {
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    async fn open(&self, path: &Path, flags: i32, mode: u32) -> VfsResult<BoxedFileOps> {
         let relative_path = self.translate_to_relative(path)?;
 
+        let writes = flags & libc::O_ACCMODE != libc::O_RDONLY
+            || flags & (libc::O_CREAT | libc::O_TRUNC) != 0;
+        if self.read_only && writes {
+            return Err(VfsError::PermissionDenied);
+        }
+
         let stats = self
             .fs
             .stat(&relative_path)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?;
+            .map_err(|e| fs_error("Failed to stat", e))?;
+
+        // O_TMPFILE creates an unnamed file in the directory at `path`
+        if flags & libc::O_TMPFILE == libc::O_TMPFILE {
+            if flags & libc::O_ACCMODE == libc::O_RDONLY {
+                return Err(VfsError::InvalidInput(
+                    "O_TMPFILE requires write access".to_string(),
+                ));
+            }
+            match stats {
+                Some(stats) if stats.is_directory() => {}
+                Some(_) => return Err(VfsError::NotADirectory),
+                None => return Err(VfsError::NotFound),
+            }
+            return Ok(Arc::new(SqliteFileOps {
+                fs: self.fs.clone(),
+                path: Mutex::new(None),
+                mode: mode & 0o7777,
+                data: Arc::new(Mutex::new(Vec::new())),
+                offset: Arc::new(Mutex::new(0)),
+                flags: Mutex::new(flags),
+                dirty: Arc::new(Mutex::new(false)),
+                cache: self.cache.clone(),
+                dev: self.dev,
+                capacity: self.capacity,
+            }));
+        }
 
         match stats {
+            Some(_) if flags & libc::O_CREAT != 0 && flags & libc::O_EXCL != 0 => {
+                // O_CREAT|O_EXCL guarantees the caller created the file
+                Err(VfsError::AlreadyExists)
+            }
             Some(stats) => {
                 if stats.is_directory() {
                     Ok(Arc::new(SqliteDirectoryOps {
@@ -110,40 +410,61 @@ impl Vfs for SqliteVfs {
                         flags: Mutex::new(flags),
                         entries: Arc::new(Mutex::new(None)),
                         position: Arc::new(Mutex::new(0)),
+                        dev: self.dev,
                     }))
                 } else {
-                    // If O_TRUNC is set, skip reading the file and use empty data
+                    // If O_TRUNC is set, truncate the backing inode right away so the
+                    // file is empty for everyone, not just once this FD is closed
                     let data = if flags & libc::O_TRUNC != 0 {
+                        self.fs
+                            .write_file(&relative_path, &[])
+                            .await
+                            .map_err(|e| fs_error("Failed to truncate file", e))?;
+                        self.invalidate(&relative_path);
                         Vec::new()
                     } else {
                         self.fs
                             .read_file(&relative_path)
                             .await
-                            .map_err(|e| VfsError::Other(format!("Failed to read file: {}", e)))?
+                            .map_err(|e| fs_error("Failed to read file", e))?
                             .ok_or(VfsError::NotFound)?
                     };
                     Ok(Arc::new(SqliteFileOps {
                         fs: self.fs.clone(),
-                        path: relative_path,
+                        path: Mutex::new(Some(relative_path)),
+                        mode: stats.mode & 0o7777,
                         data: Arc::new(Mutex::new(data)),
                         offset: Arc::new(Mutex::new(0)),
                         flags: Mutex::new(flags),
-                        dirty: Arc::new(Mutex::new(flags & libc::O_TRUNC != 0)),
+                        dirty: Arc::new(Mutex::new(false)),
+                        cache: self.cache.clone(),
+                        dev: self.dev,
+                        capacity: self.capacity,
                     }))
                 }
             }
             None => {
                 // File doesn't exist - check if O_CREAT is set
                 if flags & libc::O_CREAT != 0 {
-                    let data = Vec::new();
+                    // Create the file now so that it is visible to other opens
+                    // (and to a racing O_EXCL) before this FD is closed
+                    self.fs
+                        .write_file_mode(&relative_path, &[], mode & 0o7777)
+                        .await
+                        .map_err(|e| fs_error("Failed to create file", e))?;
+                    self.invalidate_entry(&relative_path);
 
                     Ok(Arc::new(SqliteFileOps {
                         fs: self.fs.clone(),
-                        path: relative_path,
-                        data: Arc::new(Mutex::new(data)),
+                        path: Mutex::new(Some(relative_path)),
+                        mode: mode & 0o7777,
+                        data: Arc::new(Mutex::new(Vec::new())),
                         offset: Arc::new(Mutex::new(0)),
                         flags: Mutex::new(flags),
-                        dirty: Arc::new(Mutex::new(true)), // Mark as dirty so it gets written on close
+                        dirty: Arc::new(Mutex::new(false)),
+                        cache: self.cache.clone(),
+                        dev: self.dev,
+                        capacity: self.capacity,
                     }))
                 } else {
                     // File doesn't exist and O_CREAT not set
@@ -155,19 +476,22 @@ impl Vfs for SqliteVfs {
 
     async fn stat(&self, path: &Path) -> VfsResult<libc::stat> {
         let relative_path = self.translate_to_relative(path)?;
+        if let Some(stat) = self.cached_stat(&relative_path, true) {
+            return Ok(stat);
+        }
 
         let stats = self
             .fs
             .stat(&relative_path)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?
+            .map_err(|e| fs_error("Failed to stat", e))?
             .ok_or(VfsError::NotFound)?;
 
         // Use MaybeUninit to construct libc::stat safely
         let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
-        unsafe {
+        let stat = unsafe {
             let stat_ptr = stat.as_mut_ptr();
-            (*stat_ptr).st_dev = 0;
+            (*stat_ptr).st_dev = self.dev;
             (*stat_ptr).st_ino = stats.ino as u64;
             (*stat_ptr).st_nlink = stats.nlink.into();
             (*stat_ptr).st_mode = stats.mode;
@@ -183,25 +507,32 @@ impl Vfs for SqliteVfs {
             (*stat_ptr).st_mtime_nsec = 0;
             (*stat_ptr).st_ctime = stats.ctime;
             (*stat_ptr).st_ctime_nsec = 0;
-            Ok(stat.assume_init())
+            stat.assume_init()
+        };
+        if let Some(cache) = &self.cache {
+            cache.insert(&relative_path, true, &stat);
         }
+        Ok(stat)
     }
 
     async fn lstat(&self, path: &Path) -> VfsResult<libc::stat> {
         let relative_path = self.translate_to_relative(path)?;
+        if let Some(stat) = self.cached_stat(&relative_path, false) {
+            return Ok(stat);
+        }
 
         let stats = self
             .fs
             .lstat(&relative_path)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to lstat: {}", e)))?
+            .map_err(|e| fs_error("Failed to lstat", e))?
             .ok_or(VfsError::NotFound)?;
 
         // Use MaybeUninit to construct libc::stat safely
         let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
-        unsafe {
+        let stat = unsafe {
             let stat_ptr = stat.as_mut_ptr();
-            (*stat_ptr).st_dev = 0;
+            (*stat_ptr).st_dev = self.dev;
             (*stat_ptr).st_ino = stats.ino as u64;
             (*stat_ptr).st_nlink = stats.nlink.into();
             (*stat_ptr).st_mode = stats.mode;
@@ -217,8 +548,17 @@ impl Vfs for SqliteVfs {
             (*stat_ptr).st_mtime_nsec = 0;
             (*stat_ptr).st_ctime = stats.ctime;
             (*stat_ptr).st_ctime_nsec = 0;
-            Ok(stat.assume_init())
+            stat.assume_init()
+        };
+        if let Some(cache) = &self.cache {
+            cache.insert(&relative_path, false, &stat);
         }
+        Ok(stat)
+    }
+
+    async fn readdir(&self, path: &Path) -> VfsResult<Vec<DirEntry>> {
+        let relative_path = self.translate_to_relative(path)?;
+        read_dir_entries(self.fs.as_ref(), &relative_path).await
     }
 
     async fn symlink(&self, target: &Path, linkpath: &Path) -> VfsResult<()> {
@@ -230,14 +570,9 @@ impl Vfs for SqliteVfs {
         self.fs
             .symlink(target_str, &linkpath_rel)
             .await
-            .map_err(|e| {
-                let err_msg = e.to_string();
-                if err_msg.contains("already exists") {
-                    VfsError::AlreadyExists
-                } else {
-                    VfsError::Other(format!("Failed to create symlink: {}", e))
-                }
-            })
+            .map_err(|e| fs_error("Failed to create symlink", e))?;
+        self.invalidate_entry(&linkpath_rel);
+        Ok(())
     }
 
     async fn readlink(&self, path: &Path) -> VfsResult<PathBuf> {
@@ -247,12 +582,46 @@ impl Vfs for SqliteVfs {
             .fs
             .readlink(&relative_path)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to read symlink: {}", e)))?
+            .map_err(|e| fs_error("Failed to read symlink", e))?
             .ok_or(VfsError::NotFound)?;
 
         Ok(PathBuf::from(target))
     }
 
+    async fn link_fd(&self, file: &BoxedFileOps, newpath: &Path) -> VfsResult<()> {
+        let newpath_rel = self.translate_to_relative(newpath)?;
+        if self.read_only {
+            return Err(VfsError::PermissionDenied);
+        }
+        file.link(&newpath_rel).await
+    }
+
+    async fn chmod(&self, path: &Path, mode: u32) -> VfsResult<()> {
+        let relative_path = self.translate_to_relative(path)?;
+        if self.read_only {
+            return Err(VfsError::PermissionDenied);
+        }
+        self.fs
+            .chmod(&relative_path, mode)
+            .await
+            .map_err(|e| fs_error("Failed to chmod", e))?;
+        self.invalidate(&relative_path);
+        Ok(())
+    }
+
+    async fn chown(&self, path: &Path, uid: Option<u32>, gid: Option<u32>) -> VfsResult<()> {
+        let relative_path = self.translate_to_relative(path)?;
+        if self.read_only {
+            return Err(VfsError::PermissionDenied);
+        }
+        self.fs
+            .chown(&relative_path, uid, gid)
+            .await
+            .map_err(|e| fs_error("Failed to chown", e))?;
+        self.invalidate(&relative_path);
+        Ok(())
+    }
+
     async fn link(&self, oldpath: &Path, newpath: &Path) -> VfsResult<()> {
         let oldpath_rel = self.translate_to_relative(oldpath)?;
         let newpath_rel = self.translate_to_relative(newpath)?;
@@ -260,29 +629,66 @@ impl Vfs for SqliteVfs {
         self.fs
             .link(&oldpath_rel, &newpath_rel)
             .await
-            .map_err(|e| {
-                let err_msg = e.to_string();
-                if err_msg.contains("does not exist") {
-                    VfsError::NotFound
-                } else if err_msg.contains("already exists") {
-                    VfsError::AlreadyExists
-                } else if err_msg.contains("directory") {
-                    VfsError::PermissionDenied
-                } else {
-                    VfsError::Other(format!("Failed to create hard link: {}", e))
-                }
-            })
+            .map_err(link_error)?;
+        self.invalidate(&oldpath_rel);
+        self.invalidate_entry(&newpath_rel);
+        Ok(())
+    }
+
+    /// Usage comes from the database. The database can grow until the disk
+    /// holding it is full, so free space and inodes are those of that disk.
+    async fn statfs(&self) -> VfsResult<libc::statvfs> {
+        let usage = self
+            .fs
+            .statfs()
+            .await
+            .map_err(|e| fs_error("Failed to statfs", e))?;
+        let host_dir = self
+            .db_path
+            .as_deref()
+            .and_then(Path::parent)
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let host = host_statvfs(host_dir)?;
+
+        let used = usage.bytes_used.div_ceil(BLOCK_SIZE);
+        let free = match self.capacity {
+            Some(capacity) => capacity.saturating_sub(usage.bytes_used) / BLOCK_SIZE,
+            None => (host.f_bavail as u64).saturating_mul(host.f_frsize as u64) / BLOCK_SIZE,
+        };
+        let mut buf: libc::statvfs = unsafe { std::mem::zeroed() };
+        buf.f_bsize = BLOCK_SIZE as _;
+        buf.f_frsize = BLOCK_SIZE as _;
+        buf.f_blocks = (used + free) as _;
+        buf.f_bfree = free as _;
+        buf.f_bavail = free as _;
+        buf.f_files = (usage.inodes + host.f_favail as u64) as _;
+        buf.f_ffree = host.f_favail;
+        buf.f_favail = host.f_favail;
+        buf.f_fsid = self.dev as _;
+        buf.f_flag = if self.read_only { libc::ST_RDONLY } else { 0 };
+        buf.f_namemax = 255;
+        Ok(buf)
     }
 }
 
 /// File operations for SQLite VFS files
 struct SqliteFileOps {
     fs: Arc<dyn FileSystem>,
-    path: String,
+    /// Path in the SDK filesystem, or None for an `O_TMPFILE` that hasn't been linked
+    path: Mutex<Option<String>>,
+    /// Permission bits, reported by fstat while the file has no path
+    mode: u32,
     data: Arc<Mutex<Vec<u8>>>,
     offset: Arc<Mutex<i64>>,
     flags: Mutex<i32>,
     dirty: Arc<Mutex<bool>>,
+    /// The VFS's cache of path lookups and stats, if enabled
+    cache: Option<Arc<StatCache>>,
+    /// The VFS's device number
+    dev: u64,
+    /// The VFS's limit on the total size of its files
+    capacity: Option<u64>,
 }
 
 #[async_trait::async_trait]
@@ -305,11 +711,34 @@ impl FileOps for SqliteFileOps {
     }
 
     async fn write(&self, buf: &[u8]) -> VfsResult<usize> {
+        // Fail the write itself rather than the flush on close, whose errors
+        // most programs ignore
+        if self.capacity.is_some() {
+            let len = {
+                let data = self.data.lock().unwrap();
+                let offset = *self.offset.lock().unwrap();
+                let flags = *self.flags.lock().unwrap();
+                let start = if flags & libc::O_APPEND != 0 {
+                    data.len()
+                } else {
+                    offset as usize
+                };
+                (start + buf.len()).max(data.len())
+            };
+            self.check_capacity(self.path().as_deref(), len).await?;
+        }
+
+        if self.get_flags() & libc::O_APPEND != 0 {
+            if let Some(path) = self.path() {
+                return self.append(&path, buf).await;
+            }
+        }
+
         let mut data = self.data.lock().unwrap();
         let mut offset = self.offset.lock().unwrap();
         let flags = *self.flags.lock().unwrap();
 
-        // Handle O_APPEND: always write at the end of the file
+        // Handle O_APPEND: always write at the end of the file, whatever the offset
         let start = if flags & libc::O_APPEND != 0 {
             data.len()
         } else {
@@ -338,11 +767,11 @@ impl FileOps for SqliteFileOps {
             libc::SEEK_SET => offset,
             libc::SEEK_CUR => *current_offset + offset,
             libc::SEEK_END => data.len() as i64 + offset,
-            _ => return Err(VfsError::Other("Invalid whence".to_string())),
+            _ => return Err(VfsError::InvalidInput("Invalid whence".to_string())),
         };
 
         if new_offset < 0 {
-            return Err(VfsError::Other("Invalid offset".to_string()));
+            return Err(VfsError::InvalidInput("Invalid offset".to_string()));
         }
 
         *current_offset = new_offset;
@@ -350,12 +779,16 @@ impl FileOps for SqliteFileOps {
     }
 
     async fn fstat(&self) -> VfsResult<libc::stat> {
+        let Some(path) = self.path() else {
+            return Ok(self.unlinked_stat());
+        };
+
         // Get the actual file stats from the filesystem
         let stats = self
             .fs
-            .stat(&self.path)
+            .stat(&path)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?
+            .map_err(|e| fs_error("Failed to stat", e))?
             .ok_or(VfsError::NotFound)?;
 
         let data = self.data.lock().unwrap();
@@ -364,7 +797,7 @@ impl FileOps for SqliteFileOps {
         let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
         unsafe {
             let stat_ptr = stat.as_mut_ptr();
-            (*stat_ptr).st_dev = 0;
+            (*stat_ptr).st_dev = self.dev;
             (*stat_ptr).st_ino = stats.ino as u64;
             (*stat_ptr).st_nlink = stats.nlink.into();
             (*stat_ptr).st_mode = stats.mode;
@@ -391,13 +824,20 @@ impl FileOps for SqliteFileOps {
             return Ok(());
         }
 
+        // An unlinked O_TMPFILE has nowhere to go; its data is dropped on close
+        let Some(path) = self.path() else {
+            return Ok(());
+        };
+
         let data = self.data.lock().unwrap().clone();
+        self.check_capacity(Some(&path), data.len()).await?;
 
         // Write the data to the database
         self.fs
-            .write_file(&self.path, &data)
+            .write_file(&path, &data)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to write file: {}", e)))?;
+            .map_err(|e| fs_error("Failed to write file", e))?;
+        self.invalidate(&path);
 
         // Clear dirty flag after successful write
         *self.dirty.lock().unwrap() = false;
@@ -447,10 +887,124 @@ impl FileOps for SqliteFileOps {
         *self.flags.lock().unwrap() = flags;
         Ok(())
     }
+
+    async fn link(&self, path: &str) -> VfsResult<()> {
+        if let Some(existing) = self.path() {
+            self.fs.link(&existing, path).await.map_err(link_error)?;
+            self.invalidate(&existing);
+            self.invalidate_entry(path);
+            return Ok(());
+        }
+
+        // O_TMPFILE|O_EXCL files can never be linked
+        if self.get_flags() & libc::O_EXCL != 0 {
+            return Err(VfsError::NotFound);
+        }
+        let exists = self
+            .fs
+            .lstat(path)
+            .await
+            .map_err(|e| fs_error("Failed to stat", e))?
+            .is_some();
+        if exists {
+            return Err(VfsError::AlreadyExists);
+        }
+
+        // Materialize the anonymous file under its new name; from here on it
+        // behaves like any other file opened from the database
+        let data = self.data.lock().unwrap().clone();
+        self.check_capacity(None, data.len()).await?;
+        self.fs
+            .write_file_mode(path, &data, self.mode)
+            .await
+            .map_err(|e| fs_error("Failed to create file", e))?;
+        self.invalidate_entry(path);
+        *self.path.lock().unwrap() = Some(path.to_string());
+        *self.dirty.lock().unwrap() = false;
+        Ok(())
+    }
 }
 
-/// Type alias for directory entry list: (inode, name, type)
-type DirEntryList = Vec<(u64, String, u8)>;
+impl SqliteFileOps {
+    /// Append `buf` to the file in the database right away
+    ///
+    /// The SDK finds the end of the file in the same transaction as the write,
+    /// so appends through other handles to the same file are never lost. Earlier
+    /// writes, made before `O_APPEND` was set, are flushed first.
+    async fn append(&self, path: &str, buf: &[u8]) -> VfsResult<usize> {
+        self.fsync().await?;
+        self.fs
+            .append_file(path, buf)
+            .await
+            .map_err(|e| fs_error("Failed to append to file", e))?;
+        self.invalidate(path);
+
+        let mut data = self.data.lock().unwrap();
+        data.extend_from_slice(buf);
+        *self.offset.lock().unwrap() = data.len() as i64;
+        Ok(buf.len())
+    }
+
+    /// Current path of the file, if it has one
+    fn path(&self) -> Option<String> {
+        self.path.lock().unwrap().clone()
+    }
+
+    /// Drop cached lookups of `path`
+    fn invalidate(&self, path: &str) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate(path);
+        }
+    }
+
+    /// Drop cached lookups of `path` and its parent directory
+    fn invalidate_entry(&self, path: &str) {
+        if let Some(cache) = &self.cache {
+            cache.invalidate_entry(path);
+        }
+    }
+
+    /// Fail with `ENOSPC` if storing `len` bytes in place of the contents of
+    /// `path` would take the VFS over its capacity
+    async fn check_capacity(&self, path: Option<&str>, len: usize) -> VfsResult<()> {
+        let Some(capacity) = self.capacity else {
+            return Ok(());
+        };
+        let usage = self
+            .fs
+            .statfs()
+            .await
+            .map_err(|e| fs_error("Failed to statfs", e))?;
+        let current = match path {
+            Some(path) => self
+                .fs
+                .stat(path)
+                .await
+                .map_err(|e| fs_error("Failed to stat", e))?
+                .map_or(0, |stats| stats.size as u64),
+            None => 0,
+        };
+        if usage.bytes_used.saturating_sub(current) + len as u64 > capacity {
+            return Err(VfsError::IoError(std::io::Error::from_raw_os_error(
+                libc::ENOSPC,
+            )));
+        }
+        Ok(())
+    }
+
+    /// Status of an `O_TMPFILE` that hasn't been linked into the filesystem yet
+    fn unlinked_stat(&self) -> libc::stat {
+        let size = self.data.lock().unwrap().len() as i64;
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        stat.st_dev = self.dev;
+        stat.st_mode = libc::S_IFREG | self.mode;
+        stat.st_nlink = 0;
+        stat.st_size = size;
+        stat.st_blksize = 4096;
+        stat.st_blocks = (size + 4095) / 4096;
+        stat
+    }
+}
 
 /// Directory operations for SQLite VFS directories
 struct SqliteDirectoryOps {
@@ -458,26 +1012,46 @@ struct SqliteDirectoryOps {
     path: String,
     flags: Mutex<i32>,
     /// Cached directory entries
-    entries: Arc<Mutex<Option<DirEntryList>>>,
+    entries: Arc<Mutex<Option<Vec<DirEntry>>>>,
     /// Current position in the directory listing
     position: Arc<Mutex<usize>>,
+    /// The VFS's device number
+    dev: u64,
 }
 
 #[async_trait::async_trait]
 impl FileOps for SqliteDirectoryOps {
     async fn read(&self, _buf: &mut [u8]) -> VfsResult<usize> {
         // Cannot read from a directory
-        Err(VfsError::Other("Is a directory".to_string()))
+        Err(VfsError::IsADirectory)
     }
 
     async fn write(&self, _buf: &[u8]) -> VfsResult<usize> {
         // Cannot write to a directory
-        Err(VfsError::Other("Is a directory".to_string()))
+        Err(VfsError::IsADirectory)
     }
 
-    async fn seek(&self, _offset: i64, _whence: i32) -> VfsResult<i64> {
-        // Cannot seek in a directory
-        Err(VfsError::Other("Is a directory".to_string()))
+    async fn seek(&self, offset: i64, whence: i32) -> VfsResult<i64> {
+        // The directory offset is the index of the next entry to return
+        let mut position = self.position.lock().unwrap();
+
+        let new_position = match whence {
+            libc::SEEK_SET => offset,
+            libc::SEEK_CUR => *position as i64 + offset,
+            _ => return Err(VfsError::InvalidInput("Invalid whence".to_string())),
+        };
+
+        if new_position < 0 {
+            return Err(VfsError::InvalidInput("Invalid offset".to_string()));
+        }
+
+        // Rewinding re-reads the directory, like rewinddir()
+        if whence == libc::SEEK_SET && new_position == 0 {
+            *self.entries.lock().unwrap() = None;
+        }
+
+        *position = new_position as usize;
+        Ok(new_position)
     }
 
     async fn fstat(&self) -> VfsResult<libc::stat> {
@@ -486,14 +1060,14 @@ impl FileOps for SqliteDirectoryOps {
             .fs
             .stat(&self.path)
             .await
-            .map_err(|e| VfsError::Other(format!("Failed to stat: {}", e)))?
+            .map_err(|e| fs_error("Failed to stat", e))?
             .ok_or(VfsError::NotFound)?;
 
         // Use MaybeUninit to construct libc::stat safely
         let mut stat: std::mem::MaybeUninit<libc::stat> = std::mem::MaybeUninit::zeroed();
         unsafe {
             let stat_ptr = stat.as_mut_ptr();
-            (*stat_ptr).st_dev = 0;
+            (*stat_ptr).st_dev = self.dev;
             (*stat_ptr).st_ino = stats.ino as u64;
             (*stat_ptr).st_nlink = stats.nlink.into();
             (*stat_ptr).st_mode = stats.mode;
@@ -561,7 +1135,7 @@ impl FileOps for SqliteDirectoryOps {
         Ok(())
     }
 
-    async fn getdents(&self) -> VfsResult<DirEntryList> {
+    async fn getdents(&self) -> VfsResult<Vec<DirEntry>> {
         // Check if we need to populate the entries cache
         let needs_populate = {
             let entries_lock = self.entries.lock().unwrap();
@@ -570,85 +1144,475 @@ impl FileOps for SqliteDirectoryOps {
 
         if needs_populate {
             // Read directory entries from the filesystem (without holding lock)
-            let dir_entries = self
-                .fs
-                .readdir(&self.path)
-                .await
-                .map_err(|e| VfsError::Other(format!("Failed to read directory: {}", e)))?
-                .ok_or(VfsError::NotFound)?;
+            let result = read_dir_entries(self.fs.as_ref(), &self.path).await?;
+            let mut entries_lock = self.entries.lock().unwrap();
+            *entries_lock = Some(result);
+        }
 
-            // Convert to the format expected by getdents64
-            let mut result = Vec::new();
+        // Return the entries from the current position onward; the caller
+        // advances the position by the number it consumed
+        let position = *self.position.lock().unwrap();
+        let entries_lock = self.entries.lock().unwrap();
+        let all_entries = entries_lock.as_ref().unwrap();
 
-            // Add . and .. entries with correct inode numbers
-            // Get current directory inode
-            let current_stats = self
-                .fs
-                .stat(&self.path)
-                .await
-                .map_err(|e| VfsError::Other(format!("Failed to stat current dir: {}", e)))?
-                .ok_or(VfsError::NotFound)?;
-            let current_ino = current_stats.ino as u64;
-
-            // Get parent directory inode
-            let parent_path = if self.path == "/" {
-                "/".to_string()
-            } else {
-                Path::new(&self.path)
-                    .parent()
-                    .map(|p| p.to_str().unwrap_or("/").to_string())
-                    .unwrap_or("/".to_string())
-            };
-            let parent_stats = self
-                .fs
-                .stat(&parent_path)
-                .await
-                .map_err(|e| VfsError::Other(format!("Failed to stat parent dir: {}", e)))?
-                .ok_or(VfsError::NotFound)?;
-            let parent_ino = parent_stats.ino as u64;
+        Ok(all_entries.get(position..).unwrap_or_default().to_vec())
+    }
+}
 
-            result.push((current_ino, ".".to_string(), libc::DT_DIR));
-            result.push((parent_ino, "..".to_string(), libc::DT_DIR));
+/// List a directory in the SDK filesystem, including `.` and `..`
+async fn read_dir_entries(fs: &dyn FileSystem, path: &str) -> VfsResult<Vec<DirEntry>> {
+    let dir_entries = fs
+        .readdir_stats(path)
+        .await
+        .map_err(|e| fs_error("Failed to read directory", e))?
+        .ok_or(VfsError::NotFound)?;
+
+    // Get current directory inode
+    let current_stats = fs
+        .stat(path)
+        .await
+        .map_err(|e| fs_error("Failed to stat current dir", e))?
+        .ok_or(VfsError::NotFound)?;
+    if !current_stats.is_directory() {
+        return Err(VfsError::NotADirectory);
+    }
 
-            for name in dir_entries {
-                // Construct the full path for this entry
-                let entry_path = if self.path == "/" {
-                    format!("/{}", name)
-                } else {
-                    format!("{}/{}", self.path, name)
-                };
+    // Get parent directory inode
+    let parent_path = Path::new(path)
+        .parent()
+        .and_then(|p| p.to_str())
+        .unwrap_or("/");
+    let parent_stats = fs
+        .stat(parent_path)
+        .await
+        .map_err(|e| fs_error("Failed to stat parent dir", e))?
+        .ok_or(VfsError::NotFound)?;
+
+    let mut result = Vec::with_capacity(dir_entries.len() + 2);
+    result.push(DirEntry::new(
+        current_stats.ino as u64,
+        ".",
+        current_stats.mode,
+    ));
+    result.push(DirEntry::new(
+        parent_stats.ino as u64,
+        "..",
+        parent_stats.mode,
+    ));
+    result.extend(
+        dir_entries
+            .into_iter()
+            .map(|(name, stats)| DirEntry::new(stats.ino as u64, name, stats.mode)),
+    );
+
+    Ok(result)
+}
 
-                // Get stats to determine type and inode
-                if let Ok(Some(stats)) = self.fs.stat(&entry_path).await {
-                    let d_type = if stats.is_directory() {
-                        libc::DT_DIR
-                    } else if stats.is_symlink() {
-                        libc::DT_LNK
-                    } else {
-                        libc::DT_REG
-                    };
-                    result.push((stats.ino as u64, name, d_type));
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            // Store the results
-            let mut entries_lock = self.entries.lock().unwrap();
-            *entries_lock = Some(result);
-        }
+    async fn vfs() -> (SqliteVfs, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let vfs = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap();
+        (vfs, dir)
+    }
 
-        // Get the current position and return entries
-        let mut position = self.position.lock().unwrap();
-        let entries_lock = self.entries.lock().unwrap();
-        let all_entries = entries_lock.as_ref().unwrap();
+    async fn read_all(vfs: &SqliteVfs, path: &str) -> Vec<u8> {
+        let file = vfs.open(Path::new(path), libc::O_RDONLY, 0).await.unwrap();
+        let mut buf = vec![0u8; 64];
+        let n = file.read(&mut buf).await.unwrap();
+        buf.truncate(n);
+        buf
+    }
 
-        if *position >= all_entries.len() {
-            // No more entries - return empty to signal EOF
-            Ok(Vec::new())
-        } else {
-            // Return remaining entries and update position
-            let remaining = all_entries[*position..].to_vec();
-            *position = all_entries.len();
-            Ok(remaining)
+    #[tokio::test]
+    async fn test_symlink_loop() {
+        let (vfs, _dir) = vfs().await;
+        let link = Path::new("/agent/loop");
+        vfs.symlink(Path::new("/loop"), link).await.unwrap();
+
+        assert!(matches!(vfs.stat(link).await, Err(VfsError::SymlinkLoop)));
+        assert!(vfs.lstat(link).await.is_ok());
+        let result = vfs.open(link, libc::O_RDONLY, 0).await;
+        assert!(matches!(result, Err(VfsError::SymlinkLoop)));
+    }
+
+    #[tokio::test]
+    async fn test_stat_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("agent.db");
+        let fs = Arc::new(AgentFS::new(db_path.to_str().unwrap()).await.unwrap());
+        let vfs = SqliteVfs::from_fs(fs.clone(), PathBuf::from("/agent")).with_stat_cache(16);
+        let path = Path::new("/agent/data.txt");
+        let link = Path::new("/agent/link");
+
+        fs.write_file("/data.txt", b"x").await.unwrap();
+        vfs.symlink(Path::new("data.txt"), link).await.unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_size, 1);
+        assert_eq!(vfs.stat(link).await.unwrap().st_size, 1);
+
+        // Changes made behind the VFS's back are not seen while cached
+        fs.write_file("/data.txt", b"xyz").await.unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_size, 1);
+
+        // Changes made through the VFS drop the entry, and paths leading to
+        // the same inode share its status
+        let file = vfs
+            .open(path, libc::O_WRONLY | libc::O_APPEND, 0)
+            .await
+            .unwrap();
+        file.write(b"!").await.unwrap();
+        file.close().await.unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_size, 4);
+        assert_eq!(vfs.stat(link).await.unwrap().st_size, 4);
+        assert_eq!(
+            vfs.lstat(link).await.unwrap().st_mode & libc::S_IFMT,
+            libc::S_IFLNK
+        );
+
+        vfs.chmod(path, 0o600).await.unwrap();
+        assert_eq!(vfs.stat(link).await.unwrap().st_mode & 0o7777, 0o600);
+
+        // A capacity of 0 leaves caching off
+        assert!(vfs.with_stat_cache(0).cache.is_none());
+        assert_eq!(parent_path("/a/b"), Some("/a"));
+        assert_eq!(parent_path("/a"), Some("/"));
+        assert_eq!(parent_path("/"), None);
+    }
+
+    #[tokio::test]
+    async fn test_chmod_and_chown() {
+        let (vfs, _dir) = vfs().await;
+        let path = Path::new("/agent/run.sh");
+        let flags = libc::O_WRONLY | libc::O_CREAT;
+        drop(vfs.open(path, flags, 0o644).await.unwrap());
+
+        vfs.chmod(path, 0o755).await.unwrap();
+        let stat = vfs.stat(path).await.unwrap();
+        assert_eq!(stat.st_mode, libc::S_IFREG | 0o755);
+
+        vfs.chown(path, Some(1000), None).await.unwrap();
+        let stat = vfs.stat(path).await.unwrap();
+        assert_eq!(stat.st_uid, 1000);
+
+        let missing = Path::new("/agent/missing");
+        assert!(matches!(
+            vfs.chmod(missing, 0o755).await,
+            Err(VfsError::NotFound)
+        ));
+        assert!(matches!(
+            vfs.chown(missing, None, Some(0)).await,
+            Err(VfsError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_open_create_mode() {
+        let (vfs, _dir) = vfs().await;
+        let path = Path::new("/agent/run.sh");
+
+        let file = vfs
+            .open(path, libc::O_WRONLY | libc::O_CREAT, 0o755)
+            .await
+            .unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_mode, libc::S_IFREG | 0o755);
+
+        // Writing the contents back on close keeps the mode
+        file.write(b"#!/bin/sh\n").await.unwrap();
+        file.close().await.unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_mode, libc::S_IFREG | 0o755);
+    }
+
+    #[tokio::test]
+    async fn test_open_create_excl() {
+        let (vfs, _dir) = vfs().await;
+        let path = Path::new("/agent/lock");
+        let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL;
+
+        // The file exists as soon as it is created, before the FD is closed
+        let _file = vfs.open(path, flags, 0o644).await.unwrap();
+        assert!(vfs.stat(path).await.is_ok());
+
+        let result = vfs.open(path, flags, 0o644).await;
+        assert!(matches!(result, Err(VfsError::AlreadyExists)));
+    }
+
+    #[tokio::test]
+    async fn test_statfs() {
+        let (vfs, dir) = vfs().await;
+        let empty = vfs.statfs().await.unwrap();
+        assert_eq!(empty.f_bsize, BLOCK_SIZE as _);
+        assert_eq!(empty.f_flag & libc::ST_RDONLY, 0);
+
+        let file = vfs
+            .open(
+                Path::new("/agent/data"),
+                libc::O_WRONLY | libc::O_CREAT,
+                0o644,
+            )
+            .await
+            .unwrap();
+        file.write(&[1u8; 10000]).await.unwrap();
+        file.close().await.unwrap();
+
+        let stats = vfs.statfs().await.unwrap();
+        // 10000 bytes take three 4 KiB blocks
+        assert_eq!(stats.f_blocks - stats.f_bfree, 3);
+        assert_eq!(stats.f_bavail, stats.f_bfree);
+        assert!(stats.f_files - stats.f_ffree >= 2);
+
+        let read_only = SqliteVfs::new(dir.path().join("agent.db"), PathBuf::from("/agent"))
+            .await
+            .unwrap()
+            .with_read_only(true);
+        let stats = read_only.statfs().await.unwrap();
+        assert_ne!(stats.f_flag & libc::ST_RDONLY, 0);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_capacity() {
+        let vfs = SqliteVfs::in_memory(PathBuf::from("/scratch"))
+            .await
+            .unwrap()
+            .with_capacity(Some(3 * BLOCK_SIZE));
+        assert_eq!(vfs.kind(), "tmpfs");
+        assert_eq!(vfs.statfs().await.unwrap().f_blocks, 3);
+
+        let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC;
+        let file = vfs
+            .open(Path::new("/scratch/a"), flags, 0o644)
+            .await
+            .unwrap();
+        file.write(&[1u8; 8192]).await.unwrap();
+        file.close().await.unwrap();
+
+        // Rewriting a file only counts the difference in size
+        let file = vfs
+            .open(Path::new("/scratch/a"), flags, 0o644)
+            .await
+            .unwrap();
+        file.write(&[2u8; 10000]).await.unwrap();
+        file.close().await.unwrap();
+
+        let file = vfs
+            .open(Path::new("/scratch/b"), flags, 0o644)
+            .await
+            .unwrap();
+        let err = file.write(&[3u8; 4096]).await.unwrap_err();
+        assert!(
+            matches!(&err, VfsError::IoError(e) if e.raw_os_error() == Some(libc::ENOSPC)),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_open_trunc_and_append() {
+        let (vfs, _dir) = vfs().await;
+        let path = Path::new("/agent/log.txt");
+
+        let file = vfs
+            .open(path, libc::O_WRONLY | libc::O_CREAT, 0o644)
+            .await
+            .unwrap();
+        file.write(b"hello").await.unwrap();
+        file.close().await.unwrap();
+
+        // O_APPEND writes land at the end even after seeking back
+        let file = vfs
+            .open(path, libc::O_WRONLY | libc::O_APPEND, 0)
+            .await
+            .unwrap();
+        file.seek(0, libc::SEEK_SET).await.unwrap();
+        file.write(b" world").await.unwrap();
+        file.close().await.unwrap();
+        assert_eq!(read_all(&vfs, "/agent/log.txt").await, b"hello world");
+
+        // Appends through two handles to the same file both land
+        let first = vfs
+            .open(path, libc::O_WRONLY | libc::O_APPEND, 0)
+            .await
+            .unwrap();
+        let second = vfs
+            .open(path, libc::O_WRONLY | libc::O_APPEND, 0)
+            .await
+            .unwrap();
+        first.write(b"\n1").await.unwrap();
+        second.write(b"\n2").await.unwrap();
+        first.write(b"\n3").await.unwrap();
+        first.close().await.unwrap();
+        second.close().await.unwrap();
+        assert_eq!(
+            read_all(&vfs, "/agent/log.txt").await,
+            b"hello world\n1\n2\n3"
+        );
+
+        // O_TRUNC empties the file on open, without waiting for close
+        let _file = vfs
+            .open(path, libc::O_WRONLY | libc::O_TRUNC, 0)
+            .await
+            .unwrap();
+        assert_eq!(vfs.stat(path).await.unwrap().st_size, 0);
+    }
+
+    #[tokio::test]
+    async fn test_open_tmpfile() {
+        let (vfs, _dir) = vfs().await;
+        let dir = Path::new("/agent");
+
+        let result = vfs.open(dir, libc::O_RDONLY | libc::O_TMPFILE, 0o600).await;
+        assert!(matches!(result, Err(VfsError::InvalidInput(_))));
+
+        // An unlinked temp file has no name and vanishes on close
+        let file = vfs
+            .open(dir, libc::O_RDWR | libc::O_TMPFILE, 0o600)
+            .await
+            .unwrap();
+        file.write(b"scratch").await.unwrap();
+        let stat = file.fstat().await.unwrap();
+        assert_eq!(stat.st_nlink, 0);
+        assert_eq!(stat.st_size, 7);
+        file.close().await.unwrap();
+        let names: Vec<_> = vfs
+            .readdir(dir)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, [".", ".."]);
+
+        // Linking it gives it a name, and later writes land in the linked file
+        let file = vfs
+            .open(dir, libc::O_WRONLY | libc::O_TMPFILE, 0o640)
+            .await
+            .unwrap();
+        file.write(b"draft").await.unwrap();
+        vfs.link_fd(&file, Path::new("/agent/out.txt"))
+            .await
+            .unwrap();
+        file.write(b" final").await.unwrap();
+        file.close().await.unwrap();
+        assert_eq!(read_all(&vfs, "/agent/out.txt").await, b"draft final");
+        let stat = vfs.stat(Path::new("/agent/out.txt")).await.unwrap();
+        assert_eq!(stat.st_mode, libc::S_IFREG | 0o640);
+
+        // O_EXCL temp files can never be linked
+        let file = vfs
+            .open(dir, libc::O_RDWR | libc::O_TMPFILE | libc::O_EXCL, 0o600)
+            .await
+            .unwrap();
+        let result = vfs.link_fd(&file, Path::new("/agent/other.txt")).await;
+        assert!(matches!(result, Err(VfsError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_errors_map_to_errno() {
+        let (vfs, _dir) = vfs().await;
+
+        let err = vfs
+            .open(Path::new("/agent/missing.txt"), libc::O_RDONLY, 0)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_errno(), libc::ENOENT);
+
+        let flags = libc::O_WRONLY | libc::O_CREAT | libc::O_EXCL;
+        let file = vfs
+            .open(Path::new("/agent/a.txt"), flags, 0o644)
+            .await
+            .unwrap();
+        file.close().await.unwrap();
+        let err = vfs
+            .open(Path::new("/agent/a.txt"), flags, 0o644)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_errno(), libc::EEXIST);
+
+        // readlink of a regular file is EINVAL, as on Linux
+        let err = vfs.readlink(Path::new("/agent/a.txt")).await.unwrap_err();
+        assert_eq!(err.to_errno(), libc::EINVAL);
+
+        let file = vfs
+            .open(Path::new("/agent/a.txt"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        assert_eq!(file.getdents().await.unwrap_err().to_errno(), libc::ENOTDIR);
+        let err = file.seek(-1, libc::SEEK_SET).await.unwrap_err();
+        assert_eq!(err.to_errno(), libc::EINVAL);
+        let err = file.seek(0, 42).await.unwrap_err();
+        assert_eq!(err.to_errno(), libc::EINVAL);
+
+        let dir = vfs
+            .open(Path::new("/agent"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        let err = dir.read(&mut [0u8; 16]).await.unwrap_err();
+        assert_eq!(err.to_errno(), libc::EISDIR);
+    }
+
+    #[tokio::test]
+    async fn test_multiple_sqlite_mounts() {
+        use crate::vfs::mount::MountTable;
+
+        let dir = tempfile::tempdir().unwrap();
+        let cache = SqliteVfs::new(dir.path().join("a.db"), PathBuf::from("/cache"))
+            .await
+            .unwrap();
+        let work = SqliteVfs::new(dir.path().join("b.db"), PathBuf::from("/work"))
+            .await
+            .unwrap();
+        let mut table = MountTable::new();
+        table.add_mount(PathBuf::from("/cache"), Arc::new(cache.clone()));
+        table.add_mount(PathBuf::from("/work"), Arc::new(work.clone()));
+
+        for (path, contents) in [("/cache/f", b"cache"), ("/work/f", b"work!")] {
+            let (vfs, translated) = table.resolve(Path::new(path)).unwrap();
+            let file = vfs
+                .open(&translated, libc::O_WRONLY | libc::O_CREAT, 0o644)
+                .await
+                .unwrap();
+            file.write(contents).await.unwrap();
+            file.close().await.unwrap();
         }
+
+        // Each mount writes to its own database
+        assert_eq!(read_all(&cache, "/cache/f").await, b"cache");
+        assert_eq!(read_all(&work, "/work/f").await, b"work!");
+        assert!(matches!(
+            work.stat(Path::new("/work/missing")).await,
+            Err(VfsError::NotFound)
+        ));
+
+        // Inode numbers are per database, so the device tells the files apart
+        let a = cache.stat(Path::new("/cache/f")).await.unwrap();
+        let b = work.stat(Path::new("/work/f")).await.unwrap();
+        assert_eq!(a.st_ino, b.st_ino);
+        assert_ne!(a.st_dev, b.st_dev);
+        assert_eq!(a.st_dev, cache.dev());
+        let file = work
+            .open(Path::new("/work/f"), libc::O_RDONLY, 0)
+            .await
+            .unwrap();
+        assert_eq!(file.fstat().await.unwrap().st_dev, work.dev());
+    }
+
+    #[test]
+    fn test_fs_error_errno() {
+        let too_large = fs_error("Failed to write file", FsError::FileTooLarge);
+        assert_eq!(too_large.to_errno(), libc::EFBIG);
+        let missing = fs_error("Failed to lstat", FsError::NotFound);
+        assert_eq!(missing.to_errno(), libc::ENOENT);
+        let not_empty = fs_error("Failed to remove", FsError::NotEmpty);
+        assert_eq!(not_empty.to_errno(), libc::ENOTEMPTY);
+        let full = fs_error(
+            "Failed to write file",
+            AgentFsError::QuotaExceeded("quota".to_string()),
+        );
+        assert_eq!(full.to_errno(), libc::ENOSPC);
+        let other = fs_error("Failed to read file", anyhow::anyhow!("disk on fire"));
+        assert_eq!(other.to_errno(), libc::EIO);
     }
 }
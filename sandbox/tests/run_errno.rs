@@ -0,0 +1,47 @@
+//! Checks that failing syscalls on a SQLite mount report the right errno
+//!
+//! The sandbox keeps its state in process globals and can only run once per
+//! process, so this scenario has its own test binary.
+
+#![cfg(target_os = "linux")]
+
+use agentfs_sandbox::{Sandbox, SandboxConfig};
+use agentfs_sdk::{AgentFS, AgentFSOptions};
+use reverie_process::ExitStatus;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_failed_syscalls_report_errno() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("agent.db");
+    let options = AgentFSOptions::with_path(db_path.to_str().unwrap());
+    {
+        let agentfs = AgentFS::open(options.clone()).await.unwrap();
+        agentfs.fs.mkdir("/dir").await.unwrap();
+    }
+
+    // Each failing cat appends strerror() of its errno to err.txt
+    let config = SandboxConfig::new("/bin/sh")
+        .arg("-c")
+        .arg("cat /work/missing.txt 2>> /work/err.txt; cat /work/dir 2>> /work/err.txt; exit 0")
+        .mount(
+            format!("type=sqlite,src={},dst=/work", db_path.display())
+                .parse()
+                .unwrap(),
+        );
+    let status = Sandbox::run(config).await.unwrap();
+    assert!(
+        matches!(status, ExitStatus::Exited(0)),
+        "sandboxed command failed: {:?}",
+        status
+    );
+
+    let agentfs = AgentFS::open(options).await.unwrap();
+    let err = agentfs.fs.read_file("/err.txt").await.unwrap().unwrap();
+    let err = String::from_utf8_lossy(&err);
+    let lines: Vec<&str> = err.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", err);
+    // ENOENT
+    assert!(lines[0].contains("No such file or directory"), "{}", err);
+    // EISDIR
+    assert!(lines[1].contains("Is a directory"), "{}", err);
+}
@@ -0,0 +1,37 @@
+//! Runs a real command in the sandbox against a SQLite mount
+//!
+//! The sandbox keeps its state in process globals and can only run once per
+//! process, so each scenario here needs its own test binary.
+
+#![cfg(target_os = "linux")]
+
+use agentfs_sandbox::{Sandbox, SandboxConfig};
+use agentfs_sdk::{AgentFS, AgentFSOptions};
+use reverie_process::ExitStatus;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_shell_writes_to_sqlite_mount() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("agent.db");
+
+    let config = SandboxConfig::new("/bin/sh")
+        .arg("-c")
+        .arg("echo hi > /work/out.txt")
+        .mount(
+            format!("type=sqlite,src={},dst=/work", db_path.display())
+                .parse()
+                .unwrap(),
+        );
+    let status = Sandbox::run(config).await.unwrap();
+    assert!(
+        matches!(status, ExitStatus::Exited(0)),
+        "sandboxed command failed: {:?}",
+        status
+    );
+
+    let agentfs = AgentFS::open(AgentFSOptions::with_path(db_path.to_str().unwrap()))
+        .await
+        .unwrap();
+    let contents = agentfs.fs.read_file("/out.txt").await.unwrap();
+    assert_eq!(contents.as_deref(), Some(&b"hi\n"[..]));
+}
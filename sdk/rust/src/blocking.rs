@@ -0,0 +1,407 @@
+//! Blocking API for synchronous callers
+//!
+//! [`AgentFS`] wraps the async [`crate::AgentFS`] and runs every call to
+//! completion on a single-threaded Tokio runtime it owns, so the SDK can be
+//! used from synchronous CLIs and build scripts. Its `kv`, `fs` and `tools`
+//! fields mirror the main methods of their async counterparts; anything else
+//! is reachable through [`AgentFS::block_on`] and [`AgentFS::inner`].
+//!
+//! Enabled by the `blocking` feature.
+//!
+//! # Panics
+//!
+//! Nothing here may be called from within an async context: blocking on the
+//! runtime from inside another Tokio runtime panics. Async code should use
+//! [`crate::AgentFS`] directly.
+//!
+//! ```no_run
+//! use agentfs_sdk::{blocking::AgentFS, AgentFSOptions};
+//!
+//! # fn example() -> anyhow::Result<()> {
+//! let agent = AgentFS::open(AgentFSOptions::with_id("my-agent"))?;
+//! agent.kv.set("answer", &42)?;
+//! agent.fs.write_file("/notes.txt", b"hello")?;
+//! let notes = agent.fs.read_file("/notes.txt")?;
+//! agent.close()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::Result;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+use crate::filesystem::{self, FilesystemStats, Stats};
+use crate::toolcalls::{self, ToolCall, ToolCallStats};
+use crate::{kvstore, AgentFSOptions};
+
+/// Blocking counterpart of [`crate::AgentFS`]
+pub struct AgentFS {
+    pub kv: KvStore,
+    pub fs: Filesystem,
+    pub tools: ToolCalls,
+    // Dropped after the components above, and the runtime after everything
+    inner: crate::AgentFS,
+    runtime: Arc<Runtime>,
+}
+
+impl AgentFS {
+    /// Open an agent, see [`crate::AgentFS::open`]
+    pub fn open(options: AgentFSOptions) -> anyhow::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let inner = runtime.block_on(crate::AgentFS::open(options))?;
+        Ok(Self::new(inner, Arc::new(runtime)))
+    }
+
+    fn new(inner: crate::AgentFS, runtime: Arc<Runtime>) -> Self {
+        Self {
+            kv: KvStore {
+                inner: inner.kv.clone(),
+                runtime: runtime.clone(),
+            },
+            fs: Filesystem {
+                inner: inner.fs.clone(),
+                runtime: runtime.clone(),
+            },
+            tools: ToolCalls {
+                inner: inner.tools.clone(),
+                runtime: runtime.clone(),
+            },
+            inner,
+            runtime,
+        }
+    }
+
+    /// The async agent this wraps
+    pub fn inner(&self) -> &crate::AgentFS {
+        &self.inner
+    }
+
+    /// Run a future on the agent's runtime, for async APIs with no blocking wrapper
+    ///
+    /// ```no_run
+    /// # fn example(agent: &agentfs_sdk::blocking::AgentFS) -> anyhow::Result<()> {
+    /// let export = agent.block_on(agent.inner().export_logical())?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Whether the agent is stored in a database file
+    pub fn is_persistent(&self) -> bool {
+        self.inner.is_persistent()
+    }
+
+    /// Absolute path of the database file, or `None` for in-memory agents
+    pub fn db_path(&self) -> Option<PathBuf> {
+        self.inner.db_path()
+    }
+
+    /// Copy the agent to a new database file, see [`crate::AgentFS::backup_to`]
+    pub fn backup_to(&self, path: &Path) -> anyhow::Result<()> {
+        self.block_on(self.inner.backup_to(path))
+    }
+
+    /// Checkpoint and close the agent, see [`crate::AgentFS::close`]
+    pub fn close(self) -> anyhow::Result<()> {
+        let Self {
+            kv,
+            fs,
+            tools,
+            inner,
+            runtime,
+        } = self;
+        drop((kv, fs, tools));
+        runtime.block_on(inner.close())
+    }
+}
+
+/// Blocking counterpart of [`kvstore::KvStore`]
+pub struct KvStore {
+    inner: kvstore::KvStore,
+    runtime: Arc<Runtime>,
+}
+
+impl KvStore {
+    /// Set a value, see [`kvstore::KvStore::set`]
+    pub fn set<V: Serialize>(&self, key: &str, value: &V) -> Result<()> {
+        self.runtime.block_on(self.inner.set(key, value))
+    }
+
+    /// Set a value with metadata, see [`kvstore::KvStore::set_with_meta`]
+    pub fn set_with_meta<V: Serialize>(
+        &self,
+        key: &str,
+        value: &V,
+        meta: serde_json::Value,
+    ) -> Result<()> {
+        self.runtime
+            .block_on(self.inner.set_with_meta(key, value, meta))
+    }
+
+    /// Get a value, or `None` if the key is missing
+    pub fn get<V: for<'de> Deserialize<'de>>(&self, key: &str) -> Result<Option<V>> {
+        self.runtime.block_on(self.inner.get(key))
+    }
+
+    /// Get a value and its metadata, see [`kvstore::KvStore::get_with_meta`]
+    pub fn get_with_meta<V: for<'de> Deserialize<'de>>(
+        &self,
+        key: &str,
+    ) -> Result<Option<(V, serde_json::Value)>> {
+        self.runtime.block_on(self.inner.get_with_meta(key))
+    }
+
+    /// Get a value, failing if the key is missing
+    pub fn try_get<V: for<'de> Deserialize<'de>>(&self, key: &str) -> Result<V> {
+        self.runtime.block_on(self.inner.try_get(key))
+    }
+
+    /// Get a value, or `default` if the key is missing
+    pub fn get_or<V: for<'de> Deserialize<'de>>(&self, key: &str, default: V) -> Result<V> {
+        self.runtime.block_on(self.inner.get_or(key, default))
+    }
+
+    /// Delete a key
+    pub fn delete(&self, key: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.delete(key))
+    }
+
+    /// List all keys
+    pub fn keys(&self) -> Result<Vec<String>> {
+        self.runtime.block_on(self.inner.keys())
+    }
+}
+
+/// Blocking counterpart of [`filesystem::AgentFS`]
+pub struct Filesystem {
+    inner: filesystem::AgentFS,
+    runtime: Arc<Runtime>,
+}
+
+impl Filesystem {
+    /// Get file statistics, following symlinks
+    pub fn stat(&self, path: &str) -> Result<Option<Stats>> {
+        self.runtime.block_on(self.inner.stat(path))
+    }
+
+    /// Get file statistics without following symlinks
+    pub fn lstat(&self, path: &str) -> Result<Option<Stats>> {
+        self.runtime.block_on(self.inner.lstat(path))
+    }
+
+    /// Read a whole file, or `None` if it doesn't exist
+    pub fn read_file(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        self.runtime.block_on(self.inner.read_file(path))
+    }
+
+    /// Read up to `size` bytes at `offset`
+    pub fn pread(&self, path: &str, offset: u64, size: u64) -> Result<Option<Vec<u8>>> {
+        self.runtime.block_on(self.inner.pread(path, offset, size))
+    }
+
+    /// Create or replace a file
+    pub fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
+        self.runtime.block_on(self.inner.write_file(path, data))
+    }
+
+    /// Create or replace a file with the permission bits of `mode`
+    pub fn write_file_mode(&self, path: &str, data: &[u8], mode: u32) -> Result<()> {
+        self.runtime
+            .block_on(self.inner.write_file_mode(path, data, mode))
+    }
+
+    /// Write `data` at `offset`, extending the file as needed
+    pub fn pwrite(&self, path: &str, offset: u64, data: &[u8]) -> Result<()> {
+        self.runtime.block_on(self.inner.pwrite(path, offset, data))
+    }
+
+    /// Append to a file, creating it if needed
+    pub fn append_file(&self, path: &str, data: &[u8]) -> Result<()> {
+        self.runtime.block_on(self.inner.append_file(path, data))
+    }
+
+    /// Truncate or extend a file to `new_size`
+    pub fn truncate(&self, path: &str, new_size: u64) -> Result<()> {
+        self.runtime.block_on(self.inner.truncate(path, new_size))
+    }
+
+    /// Create a directory
+    pub fn mkdir(&self, path: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.mkdir(path))
+    }
+
+    /// Create a directory and any missing parents
+    pub fn create_dir_all(&self, path: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.create_dir_all(path))
+    }
+
+    /// List a directory, or `None` if it doesn't exist
+    pub fn readdir(&self, path: &str) -> Result<Option<Vec<String>>> {
+        self.runtime.block_on(self.inner.readdir(path))
+    }
+
+    /// Remove a file, symlink or empty directory
+    pub fn remove(&self, path: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.remove(path))
+    }
+
+    /// Remove every file matching a glob pattern, returning how many were removed
+    pub fn remove_glob(&self, pattern: &str, directories: bool) -> Result<u64> {
+        self.runtime
+            .block_on(self.inner.remove_glob(pattern, directories))
+    }
+
+    /// Rename a file or directory, replacing the destination
+    pub fn rename(&self, from: &str, to: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.rename(from, to))
+    }
+
+    /// Create a symlink at `linkpath` pointing to `target`
+    pub fn symlink(&self, target: &str, linkpath: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.symlink(target, linkpath))
+    }
+
+    /// Read a symlink's target, or `None` if it doesn't exist
+    pub fn readlink(&self, path: &str) -> Result<Option<String>> {
+        self.runtime.block_on(self.inner.readlink(path))
+    }
+
+    /// Change the permission bits of a file
+    pub fn chmod(&self, path: &str, mode: u32) -> Result<()> {
+        self.runtime.block_on(self.inner.chmod(path, mode))
+    }
+
+    /// Count inodes and bytes used
+    pub fn statfs(&self) -> Result<FilesystemStats> {
+        self.runtime.block_on(self.inner.statfs())
+    }
+}
+
+/// Blocking counterpart of [`toolcalls::ToolCalls`]
+pub struct ToolCalls {
+    inner: toolcalls::ToolCalls,
+    runtime: Arc<Runtime>,
+}
+
+impl ToolCalls {
+    /// Record the start of a tool call, returning its ID
+    pub fn start(&self, name: &str, parameters: Option<serde_json::Value>) -> Result<i64> {
+        self.runtime.block_on(self.inner.start(name, parameters))
+    }
+
+    /// Mark a tool call as successful
+    pub fn success(&self, id: i64, result: Option<serde_json::Value>) -> Result<()> {
+        self.runtime.block_on(self.inner.success(id, result))
+    }
+
+    /// Mark a tool call as failed
+    pub fn error(&self, id: i64, error: &str) -> Result<()> {
+        self.runtime.block_on(self.inner.error(id, error))
+    }
+
+    /// Record a completed tool call, see [`toolcalls::ToolCalls::record`]
+    pub fn record(
+        &self,
+        name: &str,
+        started_at: i64,
+        completed_at: i64,
+        parameters: Option<serde_json::Value>,
+        result: Option<serde_json::Value>,
+        error: Option<&str>,
+    ) -> Result<i64> {
+        self.runtime.block_on(self.inner.record(
+            name,
+            started_at,
+            completed_at,
+            parameters,
+            result,
+            error,
+        ))
+    }
+
+    /// Cancel an unfinished tool call, see [`toolcalls::ToolCalls::cancel`]
+    pub fn cancel(&self, id: i64, reason: Option<&str>) -> Result<bool> {
+        self.runtime.block_on(self.inner.cancel(id, reason))
+    }
+
+    /// Tool calls that were started but haven't finished, oldest first
+    pub fn in_flight(&self) -> Result<Vec<ToolCall>> {
+        self.runtime.block_on(self.inner.in_flight())
+    }
+
+    /// Get a tool call by ID
+    pub fn get(&self, id: i64) -> Result<Option<ToolCall>> {
+        self.runtime.block_on(self.inner.get(id))
+    }
+
+    /// The most recent tool calls, newest first
+    pub fn recent(&self, limit: Option<i64>) -> Result<Vec<ToolCall>> {
+        self.runtime.block_on(self.inner.recent(limit))
+    }
+
+    /// Statistics for every tool
+    pub fn stats(&self) -> Result<Vec<ToolCallStats>> {
+        self.runtime.block_on(self.inner.stats())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocking_agent() {
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).unwrap();
+
+        agent.kv.set("answer", &42).unwrap();
+        assert_eq!(agent.kv.get::<i32>("answer").unwrap(), Some(42));
+        assert_eq!(agent.kv.get_or("missing", 7).unwrap(), 7);
+
+        agent.fs.create_dir_all("/notes/old").unwrap();
+        agent.fs.write_file("/notes/a.txt", b"hello").unwrap();
+        agent.fs.append_file("/notes/a.txt", b", world").unwrap();
+        assert_eq!(
+            agent.fs.read_file("/notes/a.txt").unwrap(),
+            Some(b"hello, world".to_vec())
+        );
+        agent.fs.rename("/notes/a.txt", "/notes/old/a.txt").unwrap();
+        assert_eq!(
+            agent.fs.readdir("/notes/old").unwrap(),
+            Some(vec!["a.txt".to_string()])
+        );
+
+        let id = agent.tools.start("search", None).unwrap();
+        agent.tools.success(id, None).unwrap();
+        assert_eq!(agent.tools.get(id).unwrap().unwrap().name, "search");
+
+        let export = agent.block_on(agent.inner().export_logical()).unwrap();
+        assert!(!export.is_empty());
+        agent.close().unwrap();
+    }
+
+    #[test]
+    fn test_blocking_agent_persists() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.db");
+        let options = || AgentFSOptions::with_path(path.to_str().unwrap());
+
+        let agent = AgentFS::open(options()).unwrap();
+        assert!(agent.is_persistent());
+        agent.fs.write_file("/a.txt", b"kept").unwrap();
+        agent.close().unwrap();
+
+        let agent = AgentFS::open(options()).unwrap();
+        assert_eq!(
+            agent.fs.read_file("/a.txt").unwrap(),
+            Some(b"kept".to_vec())
+        );
+    }
+}
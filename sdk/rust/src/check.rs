@@ -0,0 +1,522 @@
+//! Health checks for agent databases
+//!
+//! `PRAGMA integrity_check` only knows about SQLite's own structures. A
+//! database can pass it and still have a broken filesystem: directory entries
+//! naming inodes that no longer exist, inodes nothing refers to, or data blocks
+//! left behind by a deleted file. [`AgentFS::check`] runs the pragma and then
+//! cross-checks the filesystem tables against each other, and
+//! [`AgentFS::repair`] fixes what can be fixed without guessing.
+
+use anyhow::Result;
+use serde::Serialize;
+use turso::Value;
+
+use crate::{
+    filesystem::agentfs::fold_name, storage::StorageBackend, AgentFS, DEFAULT_DIR_MODE, S_IFDIR,
+    S_IFMT,
+};
+
+const ROOT_INO: i64 = 1;
+/// Directory in the root that [`AgentFS::repair`] moves unreferenced inodes to
+pub const LOST_AND_FOUND: &str = "lost+found";
+
+const ORPHANED_INODES: &str = "SELECT i.ino FROM fs_inode i
+    LEFT JOIN fs_dentry d ON d.ino = i.ino
+    WHERE d.ino IS NULL AND i.ino != ?
+    ORDER BY i.ino";
+const DANGLING_DENTRIES: &str = "SELECT d.parent_ino, d.name, d.ino FROM fs_dentry d
+    LEFT JOIN fs_inode i ON i.ino = d.ino
+    WHERE i.ino IS NULL
+    ORDER BY d.id";
+
+/// A directory entry found by [`AgentFS::check`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DentryRef {
+    pub parent_ino: i64,
+    pub name: String,
+    pub ino: i64,
+}
+
+/// A file whose link count disagrees with the entries naming it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LinkCountMismatch {
+    pub ino: i64,
+    /// Link count stored in the inode
+    pub nlink: i64,
+    /// Number of directory entries naming the inode
+    pub dentries: i64,
+}
+
+/// What [`AgentFS::check`] found
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct CheckReport {
+    /// Messages from `PRAGMA integrity_check`, empty if it reported `ok`
+    pub integrity_errors: Vec<String>,
+    pub inodes: u64,
+    pub dentries: u64,
+    pub data_blocks: u64,
+    /// Inodes other than the root that no directory entry names
+    pub orphaned_inodes: Vec<i64>,
+    /// Directory entries naming an inode that does not exist
+    pub dangling_dentries: Vec<DentryRef>,
+    /// Directory entries whose parent does not exist or is not a directory
+    pub misplaced_dentries: Vec<DentryRef>,
+    /// Inodes that do not exist but still have data blocks
+    pub orphaned_data: Vec<i64>,
+    /// Inodes that do not exist but still have a symlink target
+    pub orphaned_symlinks: Vec<i64>,
+    /// Files and symlinks whose link count is off
+    pub link_count_mismatches: Vec<LinkCountMismatch>,
+}
+
+impl CheckReport {
+    /// Number of problems found
+    pub fn problems(&self) -> usize {
+        self.integrity_errors.len()
+            + self.orphaned_inodes.len()
+            + self.dangling_dentries.len()
+            + self.misplaced_dentries.len()
+            + self.orphaned_data.len()
+            + self.orphaned_symlinks.len()
+            + self.link_count_mismatches.len()
+    }
+
+    /// Check if no problems were found
+    pub fn is_ok(&self) -> bool {
+        self.problems() == 0
+    }
+}
+
+impl AgentFS {
+    /// Check the database and the filesystem stored in it for corruption
+    ///
+    /// Nothing is repaired; the report lists every problem found along with
+    /// the number of inodes, directory entries and data blocks.
+    pub async fn check(&self) -> Result<CheckReport> {
+        let conn: &dyn StorageBackend = self.conn.as_ref();
+        let mut report = CheckReport::default();
+
+        let mut rows = conn.query("PRAGMA integrity_check", Vec::new()).await?;
+        while let Some(row) = rows.next().await? {
+            if let Some(Value::Text(message)) = row.first() {
+                if message != "ok" {
+                    report.integrity_errors.push(message.clone());
+                }
+            }
+        }
+
+        report.inodes = count(conn, "SELECT COUNT(*) FROM fs_inode").await?;
+        report.dentries = count(conn, "SELECT COUNT(*) FROM fs_dentry").await?;
+        report.data_blocks = count(conn, "SELECT COUNT(*) FROM fs_data").await?;
+
+        report.orphaned_inodes =
+            integers(conn, ORPHANED_INODES, vec![Value::Integer(ROOT_INO)]).await?;
+        report.dangling_dentries = dentries(conn, DANGLING_DENTRIES, Vec::new()).await?;
+
+        let directory = vec![
+            Value::Integer(S_IFMT as i64),
+            Value::Integer(S_IFDIR as i64),
+        ];
+        report.misplaced_dentries = dentries(
+            conn,
+            "SELECT d.parent_ino, d.name, d.ino FROM fs_dentry d
+            LEFT JOIN fs_inode p ON p.ino = d.parent_ino
+            WHERE p.ino IS NULL OR (p.mode & ?) != ?
+            ORDER BY d.id",
+            directory.clone(),
+        )
+        .await?;
+
+        report.orphaned_data = integers(conn, &orphaned_rows("fs_data"), Vec::new()).await?;
+        report.orphaned_symlinks = integers(conn, &orphaned_rows("fs_symlink"), Vec::new()).await?;
+
+        // Inodes without any entry are already reported as orphaned
+        report.link_count_mismatches = conn
+            .query_all(
+                "SELECT i.ino, i.nlink, COUNT(d.id) FROM fs_inode i
+                JOIN fs_dentry d ON d.ino = i.ino
+                WHERE (i.mode & ?) != ?
+                GROUP BY i.ino, i.nlink
+                HAVING COUNT(d.id) != i.nlink
+                ORDER BY i.ino",
+                directory,
+            )
+            .await?
+            .iter()
+            .filter_map(|row| {
+                Some(LinkCountMismatch {
+                    ino: integer(row, 0)?,
+                    nlink: integer(row, 1)?,
+                    dentries: integer(row, 2)?,
+                })
+            })
+            .collect();
+
+        Ok(report)
+    }
+
+    /// Fix the problems [`AgentFS::check`] can find without guessing
+    ///
+    /// Removes directory entries naming missing inodes, and data blocks and
+    /// symlink targets of missing inodes. With
+    /// [`RepairOptions::lost_and_found`], inodes nothing refers to are linked
+    /// into `/lost+found` as `#<ino>`, like `fsck` does.
+    ///
+    /// Everything runs in one transaction. Unless [`RepairOptions::apply`] is
+    /// set, it is rolled back and the report describes what would change.
+    pub async fn repair(&self, options: RepairOptions) -> Result<RepairReport> {
+        let conn: &dyn StorageBackend = self.conn.as_ref();
+        let now = self.fs.clock().unix_time();
+
+        conn.begin().await?;
+        let result = repair_tables(conn, options, now).await;
+        // Entries created or removed above may be cached
+        self.fs.clear_dentry_cache();
+        match result {
+            Ok(report) if options.apply => {
+                conn.commit().await?;
+                Ok(RepairReport {
+                    applied: true,
+                    ..report
+                })
+            }
+            Ok(report) => {
+                conn.rollback().await?;
+                Ok(report)
+            }
+            Err(e) => {
+                let _ = conn.rollback().await;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Options for [`AgentFS::repair`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RepairOptions {
+    /// Commit the repairs. Otherwise nothing is changed.
+    pub apply: bool,
+    /// Link inodes nothing refers to into `/lost+found`.
+    /// Otherwise they are left as they are.
+    pub lost_and_found: bool,
+}
+
+/// What [`AgentFS::repair`] changed, or would change
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RepairReport {
+    /// Whether the changes were committed
+    pub applied: bool,
+    /// Directory entries removed because their inode does not exist
+    pub removed_dentries: Vec<DentryRef>,
+    /// Data blocks removed because their inode does not exist
+    pub removed_data_blocks: u64,
+    /// Symlink targets removed because their inode does not exist
+    pub removed_symlinks: u64,
+    /// Inodes linked into `/lost+found`
+    pub recovered_inodes: Vec<i64>,
+}
+
+impl RepairReport {
+    /// Number of changes made, or that would be made
+    pub fn changes(&self) -> usize {
+        self.removed_dentries.len()
+            + self.removed_data_blocks as usize
+            + self.removed_symlinks as usize
+            + self.recovered_inodes.len()
+    }
+}
+
+/// Repair the filesystem tables inside an open transaction
+async fn repair_tables(
+    conn: &dyn StorageBackend,
+    options: RepairOptions,
+    now: i64,
+) -> Result<RepairReport> {
+    let mut report = RepairReport {
+        removed_dentries: dentries(conn, DANGLING_DENTRIES, Vec::new()).await?,
+        ..Default::default()
+    };
+    for dentry in &report.removed_dentries {
+        conn.execute(
+            "DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?",
+            vec![
+                Value::Integer(dentry.parent_ino),
+                Value::Text(dentry.name.clone()),
+            ],
+        )
+        .await?;
+    }
+
+    // Count the deleted rows themselves: the change count reported for a
+    // DELETE from fs_data is not the number of rows removed
+    for ino in integers(conn, &orphaned_rows("fs_data"), Vec::new()).await? {
+        let mut rows = conn
+            .query(
+                "DELETE FROM fs_data WHERE ino = ? RETURNING chunk_index",
+                vec![Value::Integer(ino)],
+            )
+            .await?;
+        while rows.next().await?.is_some() {
+            report.removed_data_blocks += 1;
+        }
+    }
+    for ino in integers(conn, &orphaned_rows("fs_symlink"), Vec::new()).await? {
+        report.removed_symlinks += conn
+            .execute(
+                "DELETE FROM fs_symlink WHERE ino = ?",
+                vec![Value::Integer(ino)],
+            )
+            .await?;
+    }
+
+    if options.lost_and_found {
+        let orphans = integers(conn, ORPHANED_INODES, vec![Value::Integer(ROOT_INO)]).await?;
+        if !orphans.is_empty() {
+            let dir = lost_and_found_dir(conn, now).await?;
+            for ino in orphans {
+                let name = format!("#{}", ino);
+                conn.execute(
+                    "INSERT INTO fs_dentry (name, parent_ino, ino, name_key) VALUES (?, ?, ?, ?)",
+                    vec![
+                        Value::Text(name.clone()),
+                        Value::Integer(dir),
+                        Value::Integer(ino),
+                        Value::Text(fold_name(&name)),
+                    ],
+                )
+                .await?;
+                conn.execute(
+                    "UPDATE fs_inode SET nlink = 1, ctime = ? WHERE ino = ?",
+                    vec![Value::Integer(now), Value::Integer(ino)],
+                )
+                .await?;
+                report.recovered_inodes.push(ino);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Find `/lost+found`, creating it if needed
+async fn lost_and_found_dir(conn: &dyn StorageBackend, now: i64) -> Result<i64> {
+    let existing = conn
+        .query_one(
+            "SELECT i.ino, i.mode FROM fs_dentry d JOIN fs_inode i ON i.ino = d.ino
+            WHERE d.parent_ino = ? AND d.name = ?",
+            vec![
+                Value::Integer(ROOT_INO),
+                Value::Text(LOST_AND_FOUND.to_string()),
+            ],
+        )
+        .await?;
+    if let Some(row) = existing {
+        let (Some(ino), Some(mode)) = (integer(&row, 0), integer(&row, 1)) else {
+            anyhow::bail!("Invalid /{} inode", LOST_AND_FOUND);
+        };
+        if (mode as u32 & S_IFMT) != S_IFDIR {
+            anyhow::bail!("/{} exists and is not a directory", LOST_AND_FOUND);
+        }
+        return Ok(ino);
+    }
+
+    let row = conn
+        .query_one(
+            "INSERT INTO fs_inode (mode, nlink, uid, gid, size, atime, mtime, ctime)
+            VALUES (?, 1, 0, 0, 0, ?, ?, ?) RETURNING ino",
+            vec![
+                Value::Integer(DEFAULT_DIR_MODE as i64),
+                Value::Integer(now),
+                Value::Integer(now),
+                Value::Integer(now),
+            ],
+        )
+        .await?;
+    let ino = row
+        .as_deref()
+        .and_then(|row| integer(row, 0))
+        .ok_or_else(|| anyhow::anyhow!("Failed to create /{}", LOST_AND_FOUND))?;
+    conn.execute(
+        "INSERT INTO fs_dentry (name, parent_ino, ino, name_key) VALUES (?, ?, ?, ?)",
+        vec![
+            Value::Text(LOST_AND_FOUND.to_string()),
+            Value::Integer(ROOT_INO),
+            Value::Integer(ino),
+            Value::Text(fold_name(LOST_AND_FOUND)),
+        ],
+    )
+    .await?;
+    Ok(ino)
+}
+
+/// Query selecting the inodes that have rows in `table` but do not exist
+fn orphaned_rows(table: &str) -> String {
+    format!(
+        "SELECT DISTINCT t.ino FROM {} t
+        LEFT JOIN fs_inode i ON i.ino = t.ino
+        WHERE i.ino IS NULL
+        ORDER BY t.ino",
+        table
+    )
+}
+
+/// Run a query and return the first column of every row as an integer
+async fn integers(conn: &dyn StorageBackend, sql: &str, params: Vec<Value>) -> Result<Vec<i64>> {
+    Ok(conn
+        .query_all(sql, params)
+        .await?
+        .iter()
+        .filter_map(|row| integer(row, 0))
+        .collect())
+}
+
+async fn count(conn: &dyn StorageBackend, sql: &str) -> Result<u64> {
+    let rows = integers(conn, sql, Vec::new()).await?;
+    Ok(rows.first().copied().unwrap_or(0) as u64)
+}
+
+/// Run a query selecting `parent_ino, name, ino` from `fs_dentry`
+async fn dentries(
+    conn: &dyn StorageBackend,
+    sql: &str,
+    params: Vec<Value>,
+) -> Result<Vec<DentryRef>> {
+    Ok(conn
+        .query_all(sql, params)
+        .await?
+        .iter()
+        .filter_map(|row| {
+            let name = match row.get(1) {
+                Some(Value::Text(name)) => name.clone(),
+                _ => return None,
+            };
+            Some(DentryRef {
+                parent_ino: integer(row, 0)?,
+                name,
+                ino: integer(row, 2)?,
+            })
+        })
+        .collect())
+}
+
+fn integer(row: &[Value], idx: usize) -> Option<i64> {
+    match row.get(idx) {
+        Some(Value::Integer(i)) => Some(*i),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AgentFSOptions;
+
+    #[tokio::test]
+    async fn test_check_healthy() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        agentfs.fs.mkdir("/dir").await.unwrap();
+        agentfs.fs.write_file("/dir/a.txt", b"hello").await.unwrap();
+        agentfs.fs.link("/dir/a.txt", "/b.txt").await.unwrap();
+        agentfs.fs.symlink("/dir/a.txt", "/link").await.unwrap();
+
+        let report = agentfs.check().await.unwrap();
+        assert!(report.is_ok(), "{:?}", report);
+        assert_eq!(report.inodes, 4);
+        assert_eq!(report.dentries, 4);
+        assert_eq!(report.data_blocks, 1);
+    }
+
+    #[tokio::test]
+    async fn test_check_finds_broken_tree() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        agentfs.fs.write_file("/a.txt", b"hello").await.unwrap();
+        agentfs.fs.write_file("/b.txt", b"world").await.unwrap();
+        let a = agentfs.fs.lstat("/a.txt").await.unwrap().unwrap().ino;
+        let b = agentfs.fs.lstat("/b.txt").await.unwrap().unwrap().ino;
+
+        let conn = agentfs.get_connection();
+        for sql in [
+            // Orphan /a.txt's inode
+            format!("DELETE FROM fs_dentry WHERE ino = {}", a),
+            // Entries for an inode that does not exist, and under a file
+            "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES ('ghost', 1, 999)".to_string(),
+            format!(
+                "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES ('child', {}, {})",
+                b, b
+            ),
+            // Leftovers of a deleted inode
+            "INSERT INTO fs_data (ino, chunk_index, data) VALUES (998, 0, x'00')".to_string(),
+            "INSERT INTO fs_symlink (ino, target) VALUES (997, '/nowhere')".to_string(),
+        ] {
+            conn.execute(&sql, ()).await.unwrap();
+        }
+
+        let report = agentfs.check().await.unwrap();
+        assert!(report.integrity_errors.is_empty());
+        assert_eq!(report.orphaned_inodes, vec![a]);
+        assert_eq!(report.dangling_dentries.len(), 1);
+        assert_eq!(report.dangling_dentries[0].name, "ghost");
+        assert_eq!(report.misplaced_dentries.len(), 1);
+        assert_eq!(report.misplaced_dentries[0].parent_ino, b);
+        assert_eq!(report.orphaned_data, vec![998]);
+        assert_eq!(report.orphaned_symlinks, vec![997]);
+        // /b.txt now has two entries but a link count of one
+        assert_eq!(report.link_count_mismatches.len(), 1);
+        assert_eq!(report.link_count_mismatches[0].ino, b);
+        assert_eq!(report.problems(), 6);
+    }
+
+    #[tokio::test]
+    async fn test_repair() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        agentfs.fs.write_file("/a.txt", b"hello").await.unwrap();
+        agentfs.fs.write_file("/b.txt", b"world").await.unwrap();
+        let a = agentfs.fs.lstat("/a.txt").await.unwrap().unwrap().ino;
+
+        let conn = agentfs.get_connection();
+        for sql in [
+            format!("DELETE FROM fs_dentry WHERE ino = {}", a),
+            "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES ('ghost', 1, 999)".to_string(),
+            "INSERT INTO fs_data (ino, chunk_index, data) VALUES (998, 0, x'00')".to_string(),
+            "INSERT INTO fs_data (ino, chunk_index, data) VALUES (998, 1, x'00')".to_string(),
+            "INSERT INTO fs_symlink (ino, target) VALUES (997, '/nowhere')".to_string(),
+        ] {
+            conn.execute(&sql, ()).await.unwrap();
+        }
+        let before = agentfs.check().await.unwrap();
+        assert_eq!(before.problems(), 4);
+
+        // A dry run reports the changes without making them
+        let options = RepairOptions {
+            apply: false,
+            lost_and_found: true,
+        };
+        let dry_run = agentfs.repair(options).await.unwrap();
+        assert!(!dry_run.applied);
+        assert_eq!(dry_run.removed_dentries.len(), 1);
+        assert_eq!(dry_run.removed_data_blocks, 2);
+        assert_eq!(dry_run.removed_symlinks, 1);
+        assert_eq!(dry_run.recovered_inodes, vec![a]);
+        assert_eq!(agentfs.check().await.unwrap(), before);
+        assert!(agentfs.fs.lstat("/lost+found").await.unwrap().is_none());
+
+        let report = agentfs
+            .repair(RepairOptions {
+                apply: true,
+                ..options
+            })
+            .await
+            .unwrap();
+        assert!(report.applied);
+        assert_eq!(report.changes(), dry_run.changes());
+        assert!(agentfs.check().await.unwrap().is_ok());
+        let recovered = format!("/lost+found/#{}", a);
+        assert_eq!(
+            agentfs.fs.read_file(&recovered).await.unwrap().unwrap(),
+            b"hello"
+        );
+        assert!(agentfs.fs.lstat("/ghost").await.unwrap().is_none());
+        assert_eq!(agentfs.repair(options).await.unwrap().changes(), 0);
+    }
+}
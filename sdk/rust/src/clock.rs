@@ -0,0 +1,90 @@
+//! Sources of the current time for timestamps written by the SDK.
+
+use std::{
+    fmt,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Provides the current time to the filesystem, key-value store and tool
+/// call tracker
+///
+/// File times, key-value `created_at`/`updated_at` and tool call start and
+/// completion times all come from the agent's clock. The default is
+/// [`SystemClock`]; tests can use [`MockClock`] to control time.
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Get the current time
+    fn now(&self) -> SystemTime;
+
+    /// Get the current time in whole seconds since the Unix epoch
+    ///
+    /// Times before the epoch are reported as negative seconds.
+    fn unix_time(&self) -> i64 {
+        match self.now().duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        }
+    }
+}
+
+/// The operating system's wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to
+///
+/// ```
+/// use agentfs_sdk::{Clock, MockClock};
+/// use std::time::Duration;
+///
+/// let clock = MockClock::from_unix_time(1_700_000_000);
+/// clock.advance(Duration::from_secs(60));
+/// assert_eq!(clock.unix_time(), 1_700_000_060);
+/// ```
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<SystemTime>,
+}
+
+impl MockClock {
+    /// Create a clock stopped at `now`
+    pub fn new(now: SystemTime) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// Create a clock stopped at the given number of seconds since the Unix epoch
+    pub fn from_unix_time(secs: u64) -> Self {
+        Self::new(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    /// Move the clock to `now`, which may be in the past
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Move the clock forward by `duration`
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Default for MockClock {
+    /// A clock stopped at the Unix epoch
+    fn default() -> Self {
+        Self::new(UNIX_EPOCH)
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+}
@@ -0,0 +1,179 @@
+use crate::filesystem::FsError;
+use std::time::SystemTimeError;
+use thiserror::Error;
+
+/// Result type returned by the structured SDK APIs
+pub type Result<T> = std::result::Result<T, AgentFsError>;
+
+/// Structured errors returned by the AgentFS SDK
+///
+/// Library consumers can match on the variant instead of inspecting error
+/// strings. Converts into `anyhow::Error` via `?` for callers that don't care.
+///
+/// Returned by the kv, tool call and [`crate::filesystem::AgentFS`] methods.
+/// The [`crate::FileSystem`] trait keeps returning `anyhow::Error` with an
+/// `FsError` root, so it can be implemented by other filesystems.
+#[derive(Debug, Error)]
+pub enum AgentFsError {
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Already exists: {0}")]
+    AlreadyExists(String),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
+    #[error("File too large: {0}")]
+    FileTooLarge(String),
+
+    #[error("Wrong type: {0}")]
+    WrongType(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Database error: {0}")]
+    Database(#[from] turso::Error),
+
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+
+    /// Any other filesystem error, kept as is so its errno survives
+    /// (including `FsError::InvalidPath` and `FsError::PathTraversal`)
+    #[error(transparent)]
+    Fs(FsError),
+
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl AgentFsError {
+    /// Convert to libc errno code
+    pub fn to_errno(&self) -> i32 {
+        match self {
+            AgentFsError::NotFound(_) => libc::ENOENT,
+            AgentFsError::AlreadyExists(_) => libc::EEXIST,
+            AgentFsError::QuotaExceeded(_) => libc::ENOSPC,
+            AgentFsError::FileTooLarge(_) => libc::EFBIG,
+            AgentFsError::WrongType(_) => libc::EINVAL,
+            AgentFsError::Io(e) => e.raw_os_error().unwrap_or(libc::EIO),
+            AgentFsError::Fs(e) => e.to_errno(),
+            AgentFsError::Database(_) | AgentFsError::Serialization(_) | AgentFsError::Other(_) => {
+                libc::EIO
+            }
+        }
+    }
+
+    /// Convert to an `anyhow::Error` for the [`crate::FileSystem`] trait
+    ///
+    /// Filesystem errors get their `FsError` root back, so trait callers can
+    /// keep downcasting to it.
+    pub(crate) fn into_fs_error(self) -> anyhow::Error {
+        match self {
+            AgentFsError::NotFound(_) => FsError::NotFound.into(),
+            AgentFsError::AlreadyExists(_) => FsError::AlreadyExists.into(),
+            AgentFsError::FileTooLarge(_) => FsError::FileTooLarge.into(),
+            AgentFsError::Fs(e) => e.into(),
+            AgentFsError::Other(e) => e,
+            err => err.into(),
+        }
+    }
+}
+
+impl From<FsError> for AgentFsError {
+    fn from(err: FsError) -> Self {
+        let msg = err.to_string();
+        match err {
+            FsError::NotFound => AgentFsError::NotFound(msg),
+            FsError::AlreadyExists => AgentFsError::AlreadyExists(msg),
+            FsError::FileTooLarge => AgentFsError::FileTooLarge(msg),
+            _ => AgentFsError::Fs(err),
+        }
+    }
+}
+
+impl From<SystemTimeError> for AgentFsError {
+    fn from(err: SystemTimeError) -> Self {
+        AgentFsError::Other(err.into())
+    }
+}
+
+/// Classify an `anyhow::Error` coming out of the filesystem layer.
+///
+/// `FsError` and `std::io::Error` roots are mapped onto the matching variant;
+/// anything else is kept as `Other`.
+impl From<anyhow::Error> for AgentFsError {
+    fn from(err: anyhow::Error) -> Self {
+        let err = match err.downcast::<AgentFsError>() {
+            Ok(e) => return e,
+            Err(err) => err,
+        };
+        let err = match err.downcast::<FsError>() {
+            Ok(e) => return e.into(),
+            Err(err) => err,
+        };
+        match err.downcast::<std::io::Error>() {
+            Ok(e) => match e.raw_os_error() {
+                Some(libc::ENOSPC) | Some(libc::EDQUOT) => {
+                    AgentFsError::QuotaExceeded(e.to_string())
+                }
+                _ => match e.kind() {
+                    std::io::ErrorKind::NotFound => AgentFsError::NotFound(e.to_string()),
+                    std::io::ErrorKind::AlreadyExists => AgentFsError::AlreadyExists(e.to_string()),
+                    _ => AgentFsError::Io(e),
+                },
+            },
+            Err(err) => AgentFsError::Other(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_anyhow_classifies_fs_errors() {
+        let err: anyhow::Error = FsError::NotFound.into();
+        assert!(matches!(AgentFsError::from(err), AgentFsError::NotFound(_)));
+
+        let err: anyhow::Error = FsError::IsADirectory.into();
+        let err = AgentFsError::from(err);
+        assert!(matches!(err, AgentFsError::Fs(FsError::IsADirectory)));
+        assert_eq!(err.to_errno(), libc::EISDIR);
+
+        let err: anyhow::Error = std::io::Error::from_raw_os_error(libc::ENOSPC).into();
+        assert!(matches!(
+            AgentFsError::from(err),
+            AgentFsError::QuotaExceeded(_)
+        ));
+
+        let err: anyhow::Error = FsError::FileTooLarge.into();
+        assert!(matches!(
+            AgentFsError::from(err),
+            AgentFsError::FileTooLarge(_)
+        ));
+
+        let err = anyhow::anyhow!("something else");
+        assert!(matches!(AgentFsError::from(err), AgentFsError::Other(_)));
+    }
+
+    #[test]
+    fn test_into_fs_error_restores_fs_error() {
+        let err = AgentFsError::from(FsError::NotEmpty).into_fs_error();
+        assert!(matches!(err.downcast_ref(), Some(FsError::NotEmpty)));
+
+        let err = AgentFsError::NotFound("/missing".to_string()).into_fs_error();
+        assert!(matches!(err.downcast_ref(), Some(FsError::NotFound)));
+    }
+
+    #[test]
+    fn test_into_anyhow_roundtrip() {
+        let err: anyhow::Error = AgentFsError::AlreadyExists("key".to_string()).into();
+        assert!(matches!(
+            AgentFsError::from(err),
+            AgentFsError::AlreadyExists(_)
+        ));
+    }
+}
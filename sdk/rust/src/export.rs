@@ -0,0 +1,229 @@
+//! Logical export of an agent's state
+//!
+//! [`AgentFS::export_logical`] dumps the key-value store, the filesystem tables
+//! and the tool call history as JSON. The output is canonical: rows are sorted
+//! by their key, inode or id, columns are written in a fixed order and file
+//! blocks are stored decompressed, so two agents in the same state export the
+//! same bytes however their blocks were compressed. That makes exports suitable
+//! for diffing and hashing.
+//!
+//! Inode numbers and timestamps are part of the state, so a tree rebuilt in a
+//! different order or at a different time exports differently. Directory entries
+//! are keyed by `(parent_ino, name)`; their row ids are left out. The filesystem
+//! journal is history rather than state and is not exported.
+
+use anyhow::Result;
+use serde_json::{json, Map, Value as Json};
+use turso::Value;
+
+use crate::{storage::StorageBackend, AgentFS};
+
+/// Version of the export format, bumped on incompatible changes
+pub const EXPORT_VERSION: u32 = 1;
+
+/// An exported table: name, columns in output order and the sort order of rows
+struct Table {
+    name: &'static str,
+    columns: &'static [&'static str],
+    order_by: &'static str,
+}
+
+const TABLES: &[Table] = &[
+    Table {
+        name: "kv_store",
+        columns: &["key", "value", "format", "meta", "created_at", "updated_at"],
+        order_by: "key",
+    },
+    Table {
+        name: "fs_config",
+        columns: &["key", "value"],
+        order_by: "key",
+    },
+    Table {
+        name: "fs_inode",
+        columns: &[
+            "ino", "mode", "nlink", "uid", "gid", "size", "atime", "mtime", "ctime",
+        ],
+        order_by: "ino",
+    },
+    Table {
+        name: "fs_dentry",
+        columns: &["parent_ino", "name", "ino"],
+        order_by: "parent_ino, name",
+    },
+    Table {
+        name: "fs_symlink",
+        columns: &["ino", "target"],
+        order_by: "ino",
+    },
+    Table {
+        name: "fs_data",
+        columns: &["ino", "chunk_index", "data"],
+        order_by: "ino, chunk_index",
+    },
+    Table {
+        name: "tool_calls",
+        columns: &[
+            "id",
+            "name",
+            "parameters",
+            "result",
+            "error",
+            "status",
+            "started_at",
+            "completed_at",
+            "duration_ms",
+        ],
+        order_by: "id",
+    },
+];
+
+impl AgentFS {
+    /// Export the agent's state as canonical JSON
+    ///
+    /// The result is an object with the format `version` and a `tables` object
+    /// holding one array of rows per table. Blobs are written as `{"hex": ...}`
+    /// to keep them apart from text. Identical state always gives identical
+    /// bytes; see the [module documentation](crate::export) for what that covers.
+    pub async fn export_logical(&self) -> Result<Vec<u8>> {
+        let conn: &dyn StorageBackend = self.conn.as_ref();
+        let mut tables = Map::new();
+        for table in TABLES {
+            // Blocks are exported decompressed, so read their compression flag too
+            let mut columns = table.columns.join(", ");
+            if table.name == "fs_data" {
+                columns.push_str(", compressed");
+            }
+            let sql = format!(
+                "SELECT {} FROM {} ORDER BY {}",
+                columns, table.name, table.order_by
+            );
+
+            let mut rows = Vec::new();
+            let mut stream = conn.query(&sql, Vec::new()).await?;
+            while let Some(row) = stream.next().await? {
+                let mut row = Vec::from(row);
+                if table.name == "fs_data" {
+                    let compressed = matches!(row.pop(), Some(Value::Integer(1)));
+                    if let (true, Some(Value::Blob(data))) = (compressed, row.get_mut(2)) {
+                        *data = zstd::bulk::decompress(data, self.fs.chunk_size())?;
+                    }
+                }
+                let object: Map<String, Json> = table
+                    .columns
+                    .iter()
+                    .zip(row)
+                    .map(|(column, value)| (column.to_string(), canonical_value(value)))
+                    .collect();
+                rows.push(Json::Object(object));
+            }
+            tables.insert(table.name.to_string(), Json::Array(rows));
+        }
+
+        let export = json!({
+            "version": EXPORT_VERSION,
+            "tables": tables,
+        });
+        Ok(serde_json::to_vec(&export)?)
+    }
+}
+
+/// Encode a column value, writing blobs as hex so they can't pass for text
+fn canonical_value(value: Value) -> Json {
+    match value {
+        Value::Null => Json::Null,
+        Value::Integer(i) => i.into(),
+        Value::Real(f) => serde_json::Number::from_f64(f).map_or(Json::Null, Json::Number),
+        Value::Text(text) => text.into(),
+        Value::Blob(bytes) => {
+            let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+            json!({ "hex": hex })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AgentFSOptions, Compression};
+
+    /// Build the same state in a fresh agent, in an order given by `reversed`
+    async fn populate(reversed: bool, compression: Compression) -> AgentFS {
+        let mut agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        agentfs.fs.set_compression(compression);
+        let mut keys = vec!["alpha", "beta", "gamma"];
+        if reversed {
+            keys.reverse();
+        }
+        for key in keys {
+            agentfs.kv.set(key, &key.len()).await.unwrap();
+        }
+        agentfs.fs.mkdir("/dir").await.unwrap();
+        let mut files = vec!["/dir/a.txt", "/dir/b.txt"];
+        if reversed {
+            files.reverse();
+        }
+        for file in files {
+            let data = vec![b'x'; agentfs.fs.chunk_size() + 10];
+            agentfs.fs.write_file(file, &data).await.unwrap();
+        }
+        agentfs
+    }
+
+    /// Zero out timestamps, which depend on when the state was built
+    async fn freeze_times(agentfs: &AgentFS) {
+        let conn = agentfs.get_connection();
+        for sql in [
+            "UPDATE fs_inode SET atime = 0, mtime = 0, ctime = 0",
+            "UPDATE kv_store SET created_at = 0, updated_at = 0",
+        ] {
+            conn.execute(sql, ()).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_is_deterministic() {
+        let agentfs = populate(false, Compression::None).await;
+        let id = agentfs.tools.start("search", None).await.unwrap();
+        agentfs.tools.success(id, None).await.unwrap();
+        let first = agentfs.export_logical().await.unwrap();
+        let second = agentfs.export_logical().await.unwrap();
+        assert_eq!(first, second);
+
+        let export: Json = serde_json::from_slice(&first).unwrap();
+        assert_eq!(export["version"], EXPORT_VERSION);
+        let keys: Vec<&str> = export["tables"]["kv_store"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|row| row["key"].as_str().unwrap())
+            .collect();
+        assert_eq!(keys, ["alpha", "beta", "gamma"]);
+        assert_eq!(export["tables"]["tool_calls"][0]["name"], "search");
+    }
+
+    #[tokio::test]
+    async fn test_export_ignores_compression() {
+        let a = populate(false, Compression::None).await;
+        let b = populate(false, Compression::Zstd(3)).await;
+        freeze_times(&a).await;
+        freeze_times(&b).await;
+        assert_eq!(
+            a.export_logical().await.unwrap(),
+            b.export_logical().await.unwrap()
+        );
+
+        // Rows are sorted, so creating entries in another order changes only
+        // the inode numbers they were given
+        let c = populate(true, Compression::None).await;
+        freeze_times(&c).await;
+        let export: Json = serde_json::from_slice(&c.export_logical().await.unwrap()).unwrap();
+        let names: Vec<&str> = export["tables"]["fs_dentry"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|row| row["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["dir", "a.txt", "b.txt"]);
+    }
+}
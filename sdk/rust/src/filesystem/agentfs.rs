@@ -1,20 +1,139 @@
-use anyhow::Result;
+use crate::error::{AgentFsError, Result};
 use async_trait::async_trait;
 use lru::LruCache;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::num::NonZeroUsize;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
-use turso::{Builder, Connection, Value};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use turso::{Connection, Value};
+use unicode_normalization::UnicodeNormalization;
 
+use crate::clock::{Clock, SystemClock};
+use crate::storage::{Row, StorageBackend};
+use crate::Synchronous;
+
+use super::glob::component_match;
 use super::{
-    BoxedFile, DirEntry, File, FileSystem, FilesystemStats, FsError, Stats, DEFAULT_DIR_MODE,
-    DEFAULT_FILE_MODE, S_IFLNK, S_IFMT,
+    BlockInfo, BoxedFile, Compression, DirEntry, ExportOptions, ExportSummary, File, FileSystem,
+    FilenameOptions, FilesystemStats, FsError, Glob, ImportOptions, ImportSummary, JournalEntry,
+    JournalOp, RootOptions, Stats, UsageSummary, DEFAULT_DIR_MODE, DEFAULT_FILE_MODE,
+    DEFAULT_MAX_SYMLINK_DEPTH, S_IFDIR, S_IFLNK, S_IFMT, S_IFREG,
 };
 
 const ROOT_INO: i64 = 1;
 const DEFAULT_CHUNK_SIZE: usize = 4096;
 const DENTRY_CACHE_MAX_SIZE: usize = 10000;
+/// Number of entries `import_dir` writes per transaction
+const IMPORT_BATCH_SIZE: usize = 256;
+/// Blocks shorter than this are always stored raw
+const MIN_COMPRESSED_BLOCK: usize = 64;
+
+/// Encode a block for `fs_data`, returning the blob and its `compressed` flag
+///
+/// Short blocks and blocks that do not shrink are stored raw, so a file can
+/// mix compressed and raw blocks.
+fn encode_block(data: &[u8], compression: Compression) -> anyhow::Result<(Value, i64)> {
+    if let Compression::Zstd(level) = compression {
+        if data.len() >= MIN_COMPRESSED_BLOCK {
+            let compressed = zstd::bulk::compress(data, level)?;
+            if compressed.len() < data.len() {
+                return Ok((Value::Blob(compressed), 1));
+            }
+        }
+    }
+    Ok((Value::Blob(data.to_vec()), 0))
+}
+
+/// Read the block in a row's `data` and `compressed` columns, starting at `col`
+///
+/// Returns `Ok(None)` if the column does not hold a blob.
+fn block_from_row(row: &Row, col: usize, chunk_size: usize) -> anyhow::Result<Option<Vec<u8>>> {
+    let data = match row.get_value(col) {
+        Ok(Value::Blob(data)) => data,
+        _ => return Ok(None),
+    };
+    let compressed = matches!(row.get_value(col + 1), Ok(Value::Integer(1)));
+    if compressed {
+        Ok(Some(zstd::bulk::decompress(&data, chunk_size)?))
+    } else {
+        Ok(Some(data))
+    }
+}
+
+/// Write zeros from offset `from` up to `to`, returning the new offset
+async fn write_zeros<W>(writer: &mut W, zeros: &[u8], from: u64, to: u64) -> anyhow::Result<u64>
+where
+    W: AsyncWrite + Unpin + Send,
+{
+    let mut offset = from;
+    while offset < to {
+        let len = (zeros.len() as u64).min(to - offset) as usize;
+        writer.write_all(&zeros[..len]).await?;
+        offset += len as u64;
+    }
+    Ok(offset)
+}
+
+/// Permission bits of a host file, if the platform has them
+#[cfg(unix)]
+fn host_permissions(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & 0o7777)
+}
+
+#[cfg(not(unix))]
+fn host_permissions(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Key that case-insensitive lookups match names by
+///
+/// Names are lowercased with Unicode rules and put in NFC, so `É` and `é`
+/// share a key whichever way either was composed. SQLite's `NOCASE` only
+/// folds ASCII letters.
+pub(crate) fn fold_name(name: &str) -> String {
+    name.to_lowercase().nfc().collect()
+}
+
+/// Apply permission bits to a host file, where the platform has them
+#[cfg(unix)]
+fn set_host_permissions(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))
+}
+
+#[cfg(not(unix))]
+fn set_host_permissions(_path: &Path, _mode: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Create a symlink on the host, replacing any file already at `link`
+#[cfg(unix)]
+fn create_host_symlink(target: &str, link: &Path) -> std::io::Result<()> {
+    if std::fs::symlink_metadata(link).is_ok_and(|m| !m.is_dir()) {
+        std::fs::remove_file(link)?;
+    }
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn create_host_symlink(_target: &str, _link: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "symlinks can only be exported on Unix; follow them instead",
+    ))
+}
+
+/// Fail with `FsError::FileTooLarge` if `size` exceeds the per-file limit
+fn check_file_size(max_file_size: Option<u64>, size: u64) -> anyhow::Result<()> {
+    match max_file_size {
+        Some(max) if size > max => Err(FsError::FileTooLarge.into()),
+        _ => Ok(()),
+    }
+}
 
 /// LRU cache for directory entry lookups.
 ///
@@ -24,14 +143,25 @@ const DENTRY_CACHE_MAX_SIZE: usize = 10000;
 struct DentryCache {
     // Mutex required because LruCache::get() mutates internal order
     entries: Mutex<LruCache<(i64, String), i64>>,
+    /// Fold names to lowercase so differently-cased lookups share an entry
+    case_insensitive: bool,
 }
 
 impl DentryCache {
-    fn new(max_size: usize) -> Self {
+    fn new(max_size: usize, case_insensitive: bool) -> Self {
         Self {
             entries: Mutex::new(LruCache::new(
                 NonZeroUsize::new(max_size).expect("cache size must be > 0"),
             )),
+            case_insensitive,
+        }
+    }
+
+    fn key(&self, parent_ino: i64, name: &str) -> (i64, String) {
+        if self.case_insensitive {
+            (parent_ino, fold_name(name))
+        } else {
+            (parent_ino, name.to_string())
         }
     }
 
@@ -40,7 +170,7 @@ impl DentryCache {
         self.entries
             .lock()
             .unwrap()
-            .get(&(parent_ino, name.to_string()))
+            .get(&self.key(parent_ino, name))
             .copied()
     }
 
@@ -49,7 +179,7 @@ impl DentryCache {
         self.entries
             .lock()
             .unwrap()
-            .put((parent_ino, name.to_string()), child_ino);
+            .put(self.key(parent_ino, name), child_ino);
     }
 
     /// Remove an entry from the cache
@@ -57,17 +187,117 @@ impl DentryCache {
         self.entries
             .lock()
             .unwrap()
-            .pop(&(parent_ino, name.to_string()));
+            .pop(&self.key(parent_ino, name));
+    }
+
+    /// Drop every cached entry
+    fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+/// Inodes with open handles, so that unlinking one keeps its data readable
+///
+/// When the last link to an open inode is removed, its rows stay in place with
+/// `nlink = 0` until the last handle is dropped, as on POSIX filesystems.
+/// Handles can't run queries when they are dropped, so the rows are deleted by
+/// the next `remove` or `rename` after that. If the process exits first, they
+/// are left for `check` to report as orphaned inodes.
+#[derive(Default)]
+struct OpenInodes {
+    state: Mutex<OpenInodesState>,
+}
+
+#[derive(Default)]
+struct OpenInodesState {
+    /// Number of live handles per open inode
+    handles: HashMap<i64, usize>,
+    /// Open inodes whose last link was removed
+    unlinked: HashSet<i64>,
+    /// Unlinked inodes whose last handle was dropped, waiting to be deleted
+    closed: Vec<i64>,
+}
+
+impl OpenInodes {
+    /// Register a handle on `ino`, released when the guard is dropped
+    fn acquire(self: &Arc<Self>, ino: i64) -> OpenInodeGuard {
+        *self.state.lock().unwrap().handles.entry(ino).or_default() += 1;
+        OpenInodeGuard {
+            inodes: self.clone(),
+            ino,
+        }
+    }
+
+    /// Record that the last link to `ino` is gone
+    ///
+    /// Returns true if a handle still has the inode open, in which case its
+    /// rows must be kept for now.
+    fn keep_if_open(&self, ino: i64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.handles.contains_key(&ino) {
+            state.unlinked.insert(ino);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Take the unlinked inodes that nothing has open any more
+    fn take_closed(&self) -> Vec<i64> {
+        std::mem::take(&mut self.state.lock().unwrap().closed)
+    }
+
+    /// Put back inodes whose rows could not be deleted
+    fn requeue(&self, inodes: &[i64]) {
+        self.state.lock().unwrap().closed.extend_from_slice(inodes);
+    }
+}
+
+/// Keeps an inode's rows alive while a handle has it open
+struct OpenInodeGuard {
+    inodes: Arc<OpenInodes>,
+    ino: i64,
+}
+
+impl Drop for OpenInodeGuard {
+    fn drop(&mut self) {
+        let mut state = self.inodes.state.lock().unwrap();
+        let Some(count) = state.handles.get_mut(&self.ino) else {
+            return;
+        };
+        *count -= 1;
+        if *count == 0 {
+            state.handles.remove(&self.ino);
+            if state.unlinked.remove(&self.ino) {
+                state.closed.push(self.ino);
+            }
+        }
     }
 }
 
 /// A filesystem backed by SQLite
 #[derive(Clone)]
 pub struct AgentFS {
-    conn: Arc<Connection>,
+    backend: Arc<dyn StorageBackend>,
     chunk_size: usize,
     /// Cache for directory entry lookups (shared across clones)
     dentry_cache: Arc<DentryCache>,
+    /// Filename matching behaviour
+    filenames: FilenameOptions,
+    /// Synchronous level restored after an explicit fsync
+    synchronous: Synchronous,
+    /// Maximum size of a single file in bytes (`None` for unlimited)
+    max_file_size: Option<u64>,
+    /// Maximum number of symlinks followed while resolving a path
+    max_symlink_depth: u32,
+    /// Compression applied to blocks as they are written
+    compression: Compression,
+    /// Source of inode timestamps
+    clock: Arc<dyn Clock>,
+    /// Whether mutations are recorded in `fs_journal`
+    journal: bool,
+    /// Inodes with open handles (shared across clones)
+    open_inodes: Arc<OpenInodes>,
 }
 
 /// An open file handle for AgentFS.
@@ -75,24 +305,34 @@ pub struct AgentFS {
 /// This struct holds the inode number resolved at open time, allowing
 /// efficient read/write/fsync operations without path lookups.
 pub struct AgentFSFile {
-    conn: Arc<Connection>,
+    backend: Arc<dyn StorageBackend>,
     ino: i64,
     chunk_size: usize,
+    synchronous: Synchronous,
+    max_file_size: Option<u64>,
+    compression: Compression,
+    clock: Arc<dyn Clock>,
+    /// Keeps the inode's data while the file is open, even if it is unlinked
+    _open: OpenInodeGuard,
 }
 
 #[async_trait]
 impl File for AgentFSFile {
-    async fn pread(&self, offset: u64, size: u64) -> Result<Vec<u8>> {
+    async fn pread(&self, offset: u64, size: u64) -> anyhow::Result<Vec<u8>> {
         let chunk_size = self.chunk_size as u64;
         let start_chunk = offset / chunk_size;
         let end_chunk = (offset + size).saturating_sub(1) / chunk_size;
 
-        let mut stmt = self
-            .conn
-            .prepare_cached("SELECT chunk_index, data FROM fs_data WHERE ino = ? AND chunk_index >= ? AND chunk_index <= ? ORDER BY chunk_index")
-            .await?;
-        let mut rows = stmt
-            .query((self.ino, start_chunk as i64, end_chunk as i64))
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT chunk_index, data, compressed FROM fs_data WHERE ino = ? AND chunk_index >= ? AND chunk_index <= ? ORDER BY chunk_index",
+                vec![
+                    self.ino.into(),
+                    (start_chunk as i64).into(),
+                    (end_chunk as i64).into(),
+                ],
+            )
             .await?;
 
         let mut result = Vec::with_capacity(size as usize);
@@ -119,7 +359,7 @@ impl File for AgentFSFile {
                 next_expected_chunk += 1;
             }
 
-            if let Ok(Value::Blob(chunk_data)) = row.get_value(1) {
+            if let Some(chunk_data) = block_from_row(&row, 1, self.chunk_size)? {
                 let skip = if chunk_index == start_chunk {
                     start_offset_in_chunk
                 } else {
@@ -157,17 +397,19 @@ impl File for AgentFSFile {
         Ok(result)
     }
 
-    async fn pwrite(&self, offset: u64, data: &[u8]) -> Result<()> {
+    async fn pwrite(&self, offset: u64, data: &[u8]) -> anyhow::Result<()> {
         if data.is_empty() {
             return Ok(());
         }
 
         // Get current file size
-        let mut stmt = self
-            .conn
-            .prepare_cached("SELECT size FROM fs_inode WHERE ino = ?")
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT size FROM fs_inode WHERE ino = ?",
+                vec![self.ino.into()],
+            )
             .await?;
-        let mut rows = stmt.query((self.ino,)).await?;
         let current_size = if let Some(row) = rows.next().await? {
             row.get_value(0)
                 .ok()
@@ -177,6 +419,9 @@ impl File for AgentFSFile {
             0
         };
 
+        let new_size = std::cmp::max(current_size, offset + data.len() as u64);
+        check_file_size(self.max_file_size, new_size)?;
+
         // If writing beyond current size, extend with zeros first
         if offset > current_size {
             let zeros = vec![0u8; (offset - current_size) as usize];
@@ -187,24 +432,26 @@ impl File for AgentFSFile {
         self.write_data_at_offset(offset, data).await?;
 
         // Update file size and mtime
-        let new_size = std::cmp::max(current_size, offset + data.len() as u64);
-        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-        let mut stmt = self
-            .conn
-            .prepare_cached("UPDATE fs_inode SET size = ?, mtime = ? WHERE ino = ?")
+        let now = self.clock.unix_time();
+        self.backend
+            .execute(
+                "UPDATE fs_inode SET size = ?, mtime = ? WHERE ino = ?",
+                vec![(new_size as i64).into(), now.into(), self.ino.into()],
+            )
             .await?;
-        stmt.execute((new_size as i64, now, self.ino)).await?;
 
         Ok(())
     }
 
-    async fn truncate(&self, new_size: u64) -> Result<()> {
+    async fn truncate(&self, new_size: u64) -> anyhow::Result<()> {
         // Get current size
-        let mut stmt = self
-            .conn
-            .prepare_cached("SELECT size FROM fs_inode WHERE ino = ?")
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT size FROM fs_inode WHERE ino = ?",
+                vec![self.ino.into()],
+            )
             .await?;
-        let mut rows = stmt.query((self.ino,)).await?;
         let current_size = if let Some(row) = rows.next().await? {
             row.get_value(0)
                 .ok()
@@ -214,48 +461,60 @@ impl File for AgentFSFile {
             0
         };
 
+        if new_size > current_size {
+            check_file_size(self.max_file_size, new_size)?;
+        }
+
         let chunk_size = self.chunk_size as u64;
 
-        self.conn.execute("BEGIN IMMEDIATE", ()).await?;
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
 
-        let result: Result<()> = async {
+        let result: anyhow::Result<()> = async {
             if new_size == 0 {
                 // Special case: truncate to zero - just delete all chunks
-                let mut stmt = self
-                    .conn
-                    .prepare_cached("DELETE FROM fs_data WHERE ino = ?")
+                self.backend
+                    .execute("DELETE FROM fs_data WHERE ino = ?", vec![self.ino.into()])
                     .await?;
-                stmt.execute((self.ino,)).await?;
             } else if new_size < current_size {
                 // Shrinking: delete excess chunks and truncate last chunk if needed
                 let last_chunk_idx = (new_size - 1) / chunk_size;
 
                 // Delete all chunks beyond the last one we need
-                self.conn
+                self.backend
                     .execute(
                         "DELETE FROM fs_data WHERE ino = ? AND chunk_index > ?",
-                        (self.ino, last_chunk_idx as i64),
+                        vec![self.ino.into(), (last_chunk_idx as i64).into()],
                     )
                     .await?;
 
                 // Truncate the last chunk if needed
                 let offset_in_chunk = (new_size % chunk_size) as usize;
                 if offset_in_chunk > 0 {
-                    let mut stmt = self
-                        .conn
-                        .prepare_cached("SELECT data FROM fs_data WHERE ino = ? AND chunk_index = ?")
+                    let mut rows = self
+                        .backend
+                        .query(
+                            "SELECT data, compressed FROM fs_data WHERE ino = ? AND chunk_index = ?",
+                            vec![self.ino.into(), (last_chunk_idx as i64).into()],
+                        )
                         .await?;
-                    let mut rows = stmt.query((self.ino, last_chunk_idx as i64)).await?;
 
                     if let Some(row) = rows.next().await? {
-                        if let Ok(Value::Blob(mut chunk_data)) = row.get_value(0) {
+                        if let Some(mut chunk_data) = block_from_row(&row, 0, self.chunk_size)? {
                             if chunk_data.len() > offset_in_chunk {
                                 chunk_data.truncate(offset_in_chunk);
-                                let mut stmt = self
-                                    .conn
-                                    .prepare_cached("UPDATE fs_data SET data = ? WHERE ino = ? AND chunk_index = ?")
+                                let (blob, compressed) =
+                                    encode_block(&chunk_data, self.compression)?;
+                                self.backend
+                                    .execute(
+                                        "UPDATE fs_data SET data = ?, compressed = ? WHERE ino = ? AND chunk_index = ?",
+                                        vec![
+                                            blob,
+                                            compressed.into(),
+                                            self.ino.into(),
+                                            (last_chunk_idx as i64).into(),
+                                        ],
+                                    )
                                     .await?;
-                                stmt.execute((Value::Blob(chunk_data), self.ino, last_chunk_idx as i64)).await?;
                             }
                         }
                     }
@@ -265,43 +524,53 @@ impl File for AgentFSFile {
             // The sparse regions will be handled by pread returning zeros
 
             // Update the inode size and mtime
-            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-            let mut stmt = self
-                .conn
-                .prepare_cached("UPDATE fs_inode SET size = ?, mtime = ? WHERE ino = ?")
+            let now = self.clock.unix_time();
+            self.backend
+                .execute(
+                    "UPDATE fs_inode SET size = ?, mtime = ? WHERE ino = ?",
+                    vec![(new_size as i64).into(), now.into(), self.ino.into()],
+                )
                 .await?;
-            stmt.execute((new_size as i64, now, self.ino)).await?;
 
             Ok(())
         }
         .await;
 
         if result.is_err() {
-            let _ = self.conn.execute("ROLLBACK", ()).await;
+            let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
             return result;
         }
 
-        self.conn.execute("COMMIT", ()).await?;
+        self.backend.execute("COMMIT", Vec::new()).await?;
         Ok(())
     }
 
-    async fn fsync(&self) -> Result<()> {
-        self.conn.execute("PRAGMA synchronous = FULL", ()).await?;
-        self.conn.execute("BEGIN", ()).await?;
-        self.conn.execute("COMMIT", ()).await?;
-        self.conn.execute("PRAGMA synchronous = OFF", ()).await?;
+    async fn fsync(&self) -> anyhow::Result<()> {
+        self.backend
+            .execute("PRAGMA synchronous = FULL", Vec::new())
+            .await?;
+        self.backend.execute("BEGIN", Vec::new()).await?;
+        self.backend.execute("COMMIT", Vec::new()).await?;
+        self.backend
+            .execute(
+                &format!("PRAGMA synchronous = {}", self.synchronous.as_pragma()),
+                Vec::new(),
+            )
+            .await?;
         Ok(())
     }
 
-    async fn fstat(&self) -> Result<Stats> {
-        let mut stmt = self
-            .conn
-            .prepare_cached("SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime FROM fs_inode WHERE ino = ?")
+    async fn fstat(&self) -> anyhow::Result<Stats> {
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime FROM fs_inode WHERE ino = ?",
+                vec![self.ino.into()],
+            )
             .await?;
-        let mut rows = stmt.query((self.ino,)).await?;
 
         if let Some(row) = rows.next().await? {
-            AgentFS::build_stats_from_row(&row)
+            Ok(AgentFS::build_stats_from_row(&row)?)
         } else {
             anyhow::bail!("File not found")
         }
@@ -310,7 +579,7 @@ impl File for AgentFSFile {
 
 impl AgentFSFile {
     /// Write data at a specific offset, handling chunk boundaries.
-    async fn write_data_at_offset(&self, offset: u64, data: &[u8]) -> Result<()> {
+    async fn write_data_at_offset(&self, offset: u64, data: &[u8]) -> anyhow::Result<()> {
         let chunk_size = self.chunk_size as u64;
         let mut written = 0usize;
 
@@ -325,23 +594,16 @@ impl AgentFSFile {
             let to_write = std::cmp::min(remaining_in_chunk, remaining_data);
 
             // Get existing chunk data (if any)
-            let mut stmt = self
-                .conn
-                .prepare_cached("SELECT data FROM fs_data WHERE ino = ? AND chunk_index = ?")
+            let mut rows = self
+                .backend
+                .query(
+                    "SELECT data, compressed FROM fs_data WHERE ino = ? AND chunk_index = ?",
+                    vec![self.ino.into(), chunk_index.into()],
+                )
                 .await?;
-            let mut rows = stmt.query((self.ino, chunk_index)).await?;
 
             let mut chunk_data = if let Some(row) = rows.next().await? {
-                row.get_value(0)
-                    .ok()
-                    .and_then(|v| {
-                        if let Value::Blob(b) = v {
-                            Some(b.clone())
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap_or_default()
+                block_from_row(&row, 0, self.chunk_size)?.unwrap_or_default()
             } else {
                 Vec::new()
             };
@@ -356,14 +618,13 @@ impl AgentFSFile {
                 .copy_from_slice(&data[written..written + to_write]);
 
             // Save chunk
-            let mut stmt = self
-                .conn
-                .prepare_cached(
-                    "INSERT OR REPLACE INTO fs_data (ino, chunk_index, data) VALUES (?, ?, ?)",
+            let (blob, compressed) = encode_block(&chunk_data, self.compression)?;
+            self.backend
+                .execute(
+                    "INSERT OR REPLACE INTO fs_data (ino, chunk_index, data, compressed) VALUES (?, ?, ?, ?)",
+                    vec![self.ino.into(), chunk_index.into(), blob, compressed.into()],
                 )
                 .await?;
-            stmt.execute((self.ino, chunk_index, Value::Blob(chunk_data)))
-                .await?;
 
             written += to_write;
         }
@@ -375,59 +636,336 @@ impl AgentFSFile {
 impl AgentFS {
     /// Create a new filesystem
     pub async fn new(db_path: &str) -> Result<Self> {
-        let db = Builder::new_local(db_path).build().await?;
-        let conn = Arc::new(db.connect()?);
-        Self::from_connection(conn).await
+        let db = crate::open_database(db_path).await?;
+        let conn = db.connect()?;
+        crate::set_busy_timeout(&conn).await?;
+        Self::from_connection(Arc::new(conn)).await
     }
 
     /// Create a filesystem from an existing connection
+    ///
+    /// The connection is used as it is; callers sharing the database with
+    /// other connections should set a busy timeout on it first.
     pub async fn from_connection(conn: Arc<Connection>) -> Result<Self> {
+        Self::from_backend(conn).await
+    }
+
+    /// Create a filesystem from an existing connection, enabling the given
+    /// filename options
+    ///
+    /// Options are persisted in the database. Options enabled by an earlier
+    /// open stay in effect even if they are not requested again.
+    pub async fn from_connection_with_filenames(
+        conn: Arc<Connection>,
+        filenames: FilenameOptions,
+    ) -> Result<Self> {
+        Self::from_backend_with_filenames(conn, filenames).await
+    }
+
+    /// Create a filesystem on top of an arbitrary storage backend
+    pub async fn from_backend(backend: Arc<dyn StorageBackend>) -> Result<Self> {
+        Self::from_backend_with_filenames(backend, FilenameOptions::default()).await
+    }
+
+    /// Create a filesystem on top of an arbitrary storage backend, enabling
+    /// the given filename options
+    ///
+    /// See [`AgentFS::from_connection_with_filenames`].
+    pub async fn from_backend_with_filenames(
+        backend: Arc<dyn StorageBackend>,
+        filenames: FilenameOptions,
+    ) -> Result<Self> {
         // Initialize schema first
-        Self::initialize_schema(&conn).await?;
+        Self::initialize_schema(backend.as_ref()).await?;
+        let filenames = Self::init_filename_options(backend.as_ref(), filenames).await?;
+        Self::init_name_keys(backend.as_ref(), filenames).await?;
 
         // Disable synchronous mode for filesystem fsync() semantics.
-        conn.execute("PRAGMA synchronous = OFF", ()).await?;
-
-        // Set busy timeout to handle concurrent access gracefully.
-        // Without this, concurrent transactions fail immediately with SQLITE_BUSY.
-        conn.execute("PRAGMA busy_timeout = 5000", ()).await?;
+        backend
+            .execute("PRAGMA synchronous = OFF", Vec::new())
+            .await?;
 
         // Get chunk_size from config (or use default)
-        let chunk_size = Self::read_chunk_size(&conn).await?;
+        let chunk_size = Self::read_chunk_size(backend.as_ref()).await?;
+        let journal = Self::read_journal_enabled(backend.as_ref()).await?;
 
         let fs = Self {
-            conn,
+            backend,
             chunk_size,
-            dentry_cache: Arc::new(DentryCache::new(DENTRY_CACHE_MAX_SIZE)),
+            dentry_cache: Arc::new(DentryCache::new(
+                DENTRY_CACHE_MAX_SIZE,
+                filenames.case_insensitive,
+            )),
+            filenames,
+            synchronous: Synchronous::Off,
+            max_file_size: None,
+            max_symlink_depth: DEFAULT_MAX_SYMLINK_DEPTH,
+            compression: Compression::None,
+            clock: Arc::new(SystemClock),
+            journal,
+            open_inodes: Arc::default(),
         };
         Ok(fs)
     }
 
+    /// A copy of this filesystem that runs its queries on `backend`
+    ///
+    /// Settings carry over, but the dentry cache does not: `backend` may see a
+    /// different state of the database.
+    pub(crate) fn with_backend(&self, backend: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            backend,
+            dentry_cache: Arc::new(DentryCache::new(
+                DENTRY_CACHE_MAX_SIZE,
+                self.filenames.case_insensitive,
+            )),
+            ..self.clone()
+        }
+    }
+
+    /// Set the maximum size of a single file in bytes
+    ///
+    /// Writes and truncations that would grow a file beyond the limit fail with
+    /// `FsError::FileTooLarge` before anything is committed. Defaults to `None`
+    /// (unlimited). Unlike a quota, this caps each file, not the total.
+    pub fn set_max_file_size(&mut self, max_file_size: Option<u64>) {
+        self.max_file_size = max_file_size;
+    }
+
+    /// Get the maximum size of a single file in bytes, if limited
+    pub fn max_file_size(&self) -> Option<u64> {
+        self.max_file_size
+    }
+
+    /// Set how many symlinks `stat` and `read_file` follow before failing
+    ///
+    /// Resolving a path through more links, or through a cycle, fails with
+    /// `FsError::SymlinkLoop`. Defaults to `DEFAULT_MAX_SYMLINK_DEPTH` (40).
+    pub fn set_max_symlink_depth(&mut self, depth: u32) {
+        self.max_symlink_depth = depth;
+    }
+
+    /// Get how many symlinks are followed while resolving a path
+    pub fn max_symlink_depth(&self) -> u32 {
+        self.max_symlink_depth
+    }
+
+    /// Set the compression applied to blocks as they are written
+    ///
+    /// Blocks already stored keep their encoding until they are rewritten.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Get the compression applied to blocks as they are written
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Set the clock file timestamps are taken from
+    ///
+    /// Defaults to [`SystemClock`]. The root directory's initial timestamps
+    /// are set when the schema is created and always use the system clock.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Get the clock file timestamps are taken from
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Start recording mutations in the journal
+    ///
+    /// Every path-based mutation (writes, appends, truncates, renames,
+    /// removals, links, permission and ownership changes, and imports) then
+    /// appends an entry to the `fs_journal` table in the same transaction as
+    /// the change, so the journal never disagrees with the filesystem. Writes
+    /// through open file handles are not journaled. Like the filename
+    /// options, journaling is persisted and stays enabled across reopens.
+    pub async fn enable_journal(&mut self) -> Result<()> {
+        self.backend
+            .execute(
+                "INSERT OR REPLACE INTO fs_config (key, value) VALUES ('journal', '1')",
+                Vec::new(),
+            )
+            .await?;
+        self.journal = true;
+        Ok(())
+    }
+
+    /// Whether mutations are recorded in the journal
+    pub fn journal_enabled(&self) -> bool {
+        self.journal
+    }
+
+    /// Get the journaled mutations made at or after `since`, oldest first
+    ///
+    /// Entries are kept when journaling is enabled; there is no retention limit.
+    pub async fn journal(&self, since: SystemTime) -> Result<Vec<JournalEntry>> {
+        let since = match since.duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        };
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT id, timestamp, op, path, target, size, hash FROM fs_journal
+                WHERE timestamp >= ? ORDER BY id",
+                vec![since.into()],
+            )
+            .await?;
+
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let integer = |idx| match row.get_value(idx) {
+                Ok(Value::Integer(i)) => Some(i),
+                _ => None,
+            };
+            let text = |idx| match row.get_value(idx) {
+                Ok(Value::Text(s)) => Some(s),
+                _ => None,
+            };
+            let op = text(2)
+                .and_then(|op| JournalOp::from_name(&op))
+                .ok_or_else(|| anyhow::anyhow!("Invalid journal operation"))?;
+            entries.push(JournalEntry {
+                id: integer(0).unwrap_or(0),
+                timestamp: integer(1).unwrap_or(0),
+                op,
+                path: text(3).unwrap_or_default(),
+                target: text(4),
+                size: integer(5).map(|size| size as u64),
+                hash: text(6),
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Append a mutation to the journal, if journaling is enabled
+    ///
+    /// `data` is what was written, for its size and hash. Paths are recorded
+    /// as given, so callers pass them normalized. The caller holds the write
+    /// transaction, so the entry commits or rolls back with the change.
+    async fn journal_locked(
+        &self,
+        op: JournalOp,
+        path: &str,
+        target: Option<&str>,
+        data: Option<&[u8]>,
+    ) -> Result<()> {
+        if !self.journal {
+            return Ok(());
+        }
+        let size = data.map(|data| data.len() as u64);
+        let hash = data.map(|data| format!("{:x}", Sha256::digest(data)));
+        self.journal_entry_locked(op, path, target, size, hash)
+            .await
+    }
+
+    /// Like [`AgentFS::journal_locked`], for a size and hash worked out by the caller
+    async fn journal_entry_locked(
+        &self,
+        op: JournalOp,
+        path: &str,
+        target: Option<&str>,
+        size: Option<u64>,
+        hash: Option<String>,
+    ) -> Result<()> {
+        if !self.journal {
+            return Ok(());
+        }
+        self.backend
+            .execute(
+                "INSERT INTO fs_journal (timestamp, op, path, target, size, hash)
+                VALUES (?, ?, ?, ?, ?, ?)",
+                vec![
+                    self.clock.unix_time().into(),
+                    op.as_str().into(),
+                    path.into(),
+                    target.into(),
+                    size.map(|size| size as i64).into(),
+                    hash.into(),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Set the permissions and ownership of the root directory
+    ///
+    /// Only the fields set in `root` are changed. The new values are stored in
+    /// the root inode, so they stick across reopens.
+    pub async fn set_root_options(&self, root: RootOptions) -> Result<()> {
+        let updates = [
+            ("mode", root.mode.map(|mode| S_IFDIR | (mode & 0o7777))),
+            ("uid", root.uid),
+            ("gid", root.gid),
+        ];
+        for (column, value) in updates {
+            if let Some(value) = value {
+                self.backend
+                    .execute(
+                        &format!("UPDATE fs_inode SET {} = ? WHERE ino = ?", column),
+                        vec![(value as i64).into(), ROOT_INO.into()],
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the synchronous level used outside of explicit fsync calls
+    ///
+    /// Defaults to `Synchronous::Off`; `fsync()` temporarily raises it to `Full`.
+    pub async fn set_synchronous(&mut self, synchronous: Synchronous) -> Result<()> {
+        self.backend
+            .execute(
+                &format!("PRAGMA synchronous = {}", synchronous.as_pragma()),
+                Vec::new(),
+            )
+            .await?;
+        self.synchronous = synchronous;
+        Ok(())
+    }
+
+    /// Forget every cached directory entry, after the tables were changed directly
+    pub(crate) fn clear_dentry_cache(&self) {
+        self.dentry_cache.clear();
+    }
+
     /// Get the configured chunk size
     pub fn chunk_size(&self) -> usize {
         self.chunk_size
     }
 
-    /// Get the underlying database connection
-    pub fn get_connection(&self) -> Arc<Connection> {
-        self.conn.clone()
+    /// Get the storage backend the filesystem runs its queries on
+    pub fn backend(&self) -> &Arc<dyn StorageBackend> {
+        &self.backend
+    }
+
+    /// Get the filename matching options in effect
+    pub fn filename_options(&self) -> FilenameOptions {
+        self.filenames
     }
 
     /// Initialize the database schema
-    async fn initialize_schema(conn: &Connection) -> Result<()> {
+    async fn initialize_schema(backend: &dyn StorageBackend) -> Result<()> {
         // Create config table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS fs_config (
+        backend
+            .execute(
+                "CREATE TABLE IF NOT EXISTS fs_config (
                 key TEXT PRIMARY KEY,
                 value TEXT NOT NULL
             )",
-            (),
-        )
-        .await?;
+                Vec::new(),
+            )
+            .await?;
 
         // Create inode table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS fs_inode (
+        backend
+            .execute(
+                "CREATE TABLE IF NOT EXISTS fs_inode (
                 ino INTEGER PRIMARY KEY AUTOINCREMENT,
                 mode INTEGER NOT NULL,
                 nlink INTEGER NOT NULL DEFAULT 0,
@@ -438,89 +976,287 @@ impl AgentFS {
                 mtime INTEGER NOT NULL,
                 ctime INTEGER NOT NULL
             )",
-            (),
-        )
-        .await?;
+                Vec::new(),
+            )
+            .await?;
 
         // Create directory entry table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS fs_dentry (
+        backend
+            .execute(
+                "CREATE TABLE IF NOT EXISTS fs_dentry (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
                 name TEXT NOT NULL,
                 parent_ino INTEGER NOT NULL,
                 ino INTEGER NOT NULL,
+                name_key TEXT,
                 UNIQUE(parent_ino, name)
             )",
-            (),
-        )
-        .await?;
+                Vec::new(),
+            )
+            .await?;
+
+        // Databases created before case-insensitive names lack the key column;
+        // keys are filled in once the filename options are known
+        if backend
+            .query("SELECT name_key FROM fs_dentry LIMIT 0", Vec::new())
+            .await
+            .is_err()
+        {
+            backend
+                .execute("ALTER TABLE fs_dentry ADD COLUMN name_key TEXT", Vec::new())
+                .await?;
+        }
 
         // Create index for efficient path lookups
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_fs_dentry_parent
+        backend
+            .execute(
+                "CREATE INDEX IF NOT EXISTS idx_fs_dentry_parent
             ON fs_dentry(parent_ino, name)",
-            (),
-        )
-        .await?;
+                Vec::new(),
+            )
+            .await?;
 
         // Create data chunks table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS fs_data (
+        backend
+            .execute(
+                "CREATE TABLE IF NOT EXISTS fs_data (
                 ino INTEGER NOT NULL,
                 chunk_index INTEGER NOT NULL,
                 data BLOB NOT NULL,
+                compressed INTEGER NOT NULL DEFAULT 0,
                 PRIMARY KEY (ino, chunk_index)
             )",
-            (),
-        )
-        .await?;
+                Vec::new(),
+            )
+            .await?;
+
+        // Databases created before block compression lack the `compressed` flag
+        if backend
+            .query("SELECT compressed FROM fs_data LIMIT 0", Vec::new())
+            .await
+            .is_err()
+        {
+            backend
+                .execute(
+                    "ALTER TABLE fs_data ADD COLUMN compressed INTEGER NOT NULL DEFAULT 0",
+                    Vec::new(),
+                )
+                .await?;
+        }
 
         // Create symlink table
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS fs_symlink (
+        backend
+            .execute(
+                "CREATE TABLE IF NOT EXISTS fs_symlink (
                 ino INTEGER PRIMARY KEY,
                 target TEXT NOT NULL
             )",
-            (),
-        )
-        .await?;
+                Vec::new(),
+            )
+            .await?;
 
-        // Ensure chunk_size config exists
-        let mut rows = conn
-            .query("SELECT value FROM fs_config WHERE key = 'chunk_size'", ())
+        // Create journal table, only written to once journaling is enabled
+        backend
+            .execute(
+                "CREATE TABLE IF NOT EXISTS fs_journal (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                op TEXT NOT NULL,
+                path TEXT NOT NULL,
+                target TEXT,
+                size INTEGER,
+                hash TEXT
+            )",
+                Vec::new(),
+            )
             .await?;
 
-        if rows.next().await?.is_none() {
-            conn.execute(
-                "INSERT INTO fs_config (key, value) VALUES ('chunk_size', ?)",
-                (DEFAULT_CHUNK_SIZE.to_string(),),
+        backend
+            .execute(
+                "CREATE INDEX IF NOT EXISTS idx_fs_journal_timestamp
+            ON fs_journal(timestamp)",
+                Vec::new(),
+            )
+            .await?;
+
+        // Ensure chunk_size config exists
+        let mut rows = backend
+            .query(
+                "SELECT value FROM fs_config WHERE key = 'chunk_size'",
+                Vec::new(),
             )
             .await?;
+
+        if rows.next().await?.is_none() {
+            backend
+                .execute(
+                    "INSERT INTO fs_config (key, value) VALUES ('chunk_size', ?)",
+                    vec![DEFAULT_CHUNK_SIZE.to_string().into()],
+                )
+                .await?;
         }
 
         // Ensure root directory exists
-        let mut rows = conn
-            .query("SELECT ino FROM fs_inode WHERE ino = ?", (ROOT_INO,))
+        let mut rows = backend
+            .query(
+                "SELECT ino FROM fs_inode WHERE ino = ?",
+                vec![ROOT_INO.into()],
+            )
             .await?;
 
         if rows.next().await?.is_none() {
             let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-            conn.execute(
-                "INSERT INTO fs_inode (ino, mode, nlink, uid, gid, size, atime, mtime, ctime)
+            backend
+                .execute(
+                    "INSERT INTO fs_inode (ino, mode, nlink, uid, gid, size, atime, mtime, ctime)
                 VALUES (?, ?, 1, 0, 0, 0, ?, ?, ?)",
-                (ROOT_INO, DEFAULT_DIR_MODE as i64, now, now, now),
-            )
-            .await?;
+                    vec![
+                        ROOT_INO.into(),
+                        (DEFAULT_DIR_MODE as i64).into(),
+                        now.into(),
+                        now.into(),
+                        now.into(),
+                    ],
+                )
+                .await?;
         }
 
         Ok(())
     }
 
-    /// Read chunk size from config
-    async fn read_chunk_size(conn: &Connection) -> Result<usize> {
-        let mut rows = conn
-            .query("SELECT value FROM fs_config WHERE key = 'chunk_size'", ())
-            .await?;
+    /// Persist requested filename options and return the effective ones
+    async fn init_filename_options(
+        backend: &dyn StorageBackend,
+        requested: FilenameOptions,
+    ) -> Result<FilenameOptions> {
+        let flags = [
+            ("case_insensitive", requested.case_insensitive),
+            ("unicode_nfc", requested.unicode_nfc),
+        ];
+        for (key, enabled) in flags {
+            if enabled {
+                backend
+                    .execute(
+                        "INSERT OR REPLACE INTO fs_config (key, value) VALUES (?, '1')",
+                        vec![key.into()],
+                    )
+                    .await?;
+            }
+        }
+
+        let mut options = FilenameOptions::default();
+        let mut rows = backend
+            .query(
+                "SELECT key FROM fs_config
+                 WHERE key IN ('case_insensitive', 'unicode_nfc') AND value = '1'",
+                Vec::new(),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            match row.get_value(0) {
+                Ok(Value::Text(key)) if key == "case_insensitive" => {
+                    options.case_insensitive = true
+                }
+                Ok(Value::Text(key)) if key == "unicode_nfc" => options.unicode_nfc = true,
+                _ => {}
+            }
+        }
+        Ok(options)
+    }
+
+    /// Bring every entry's `name_key` in line with the filename options and
+    /// index it
+    ///
+    /// The key is the folded name when names are case-insensitive and the name
+    /// itself otherwise, so the unique index rejects names that match an
+    /// existing entry in either mode. Entries written before the column existed
+    /// have no key yet. Enabling case-insensitive matching fails if a directory
+    /// holds names that differ only in case.
+    async fn init_name_keys(backend: &dyn StorageBackend, options: FilenameOptions) -> Result<()> {
+        if options.case_insensitive {
+            Self::fill_name_keys(backend).await.map_err(|e| {
+                anyhow::anyhow!(
+                    "Cannot enable case-insensitive filenames, names differ only in case: {}",
+                    e
+                )
+            })?;
+        } else {
+            backend
+                .execute(
+                    "UPDATE fs_dentry SET name_key = name
+                     WHERE name_key IS NULL OR name_key != name",
+                    Vec::new(),
+                )
+                .await?;
+        }
+
+        backend
+            .execute("DROP INDEX IF EXISTS idx_fs_dentry_name_key", Vec::new())
+            .await?;
+        backend
+            .execute(
+                "CREATE UNIQUE INDEX IF NOT EXISTS idx_fs_dentry_name_key_unique
+            ON fs_dentry(parent_ino, name_key)",
+                Vec::new(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Set the folded key of entries whose key is missing or unfolded
+    async fn fill_name_keys(backend: &dyn StorageBackend) -> Result<()> {
+        let mut rows = backend
+            .query("SELECT id, name, name_key FROM fs_dentry", Vec::new())
+            .await?;
+        let mut missing = Vec::new();
+        while let Some(row) = rows.next().await? {
+            if let (Ok(Value::Integer(id)), Ok(Value::Text(name))) =
+                (row.get_value(0), row.get_value(1))
+            {
+                let key = fold_name(&name);
+                if !matches!(row.get_value(2), Ok(Value::Text(current)) if current == key) {
+                    missing.push((id, key));
+                }
+            }
+        }
+        drop(rows);
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+        let result: Result<()> = async {
+            for (id, key) in missing {
+                backend
+                    .execute(
+                        "UPDATE fs_dentry SET name_key = ? WHERE id = ?",
+                        vec![key.into(), id.into()],
+                    )
+                    .await?;
+            }
+            Ok(())
+        }
+        .await;
+        match result {
+            Ok(()) => {
+                backend.execute("COMMIT", Vec::new()).await?;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = backend.execute("ROLLBACK", Vec::new()).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Read chunk size from config
+    async fn read_chunk_size(backend: &dyn StorageBackend) -> Result<usize> {
+        let mut rows = backend
+            .query(
+                "SELECT value FROM fs_config WHERE key = 'chunk_size'",
+                Vec::new(),
+            )
+            .await?;
 
         if let Some(row) = rows.next().await? {
             let value = row
@@ -538,19 +1274,48 @@ impl AgentFS {
         }
     }
 
+    /// Value to match `name` against and to store as its `name_key`: the
+    /// folded name when names are case-insensitive, the name itself otherwise
+    fn match_name(&self, name: &str) -> String {
+        if self.filenames.case_insensitive {
+            fold_name(name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Whether journaling was enabled for this database
+    async fn read_journal_enabled(backend: &dyn StorageBackend) -> Result<bool> {
+        let mut rows = backend
+            .query(
+                "SELECT value FROM fs_config WHERE key = 'journal'",
+                Vec::new(),
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => Ok(matches!(row.get_value(0), Ok(Value::Text(v)) if v == "1")),
+            None => Ok(false),
+        }
+    }
+
     /// Normalize a path
-    fn normalize_path(&self, path: &str) -> String {
-        let normalized = path.trim_end_matches('/');
-        let normalized = if normalized.is_empty() {
-            "/"
-        } else if normalized.starts_with('/') {
-            normalized
+    ///
+    /// Fails with [`FsError::InvalidPath`] if the path contains a NUL byte.
+    fn normalize_path(&self, path: &str) -> Result<String> {
+        if path.contains('\0') {
+            return Err(FsError::InvalidPath.into());
+        }
+        let nfc;
+        let path = if self.filenames.unicode_nfc {
+            nfc = path.nfc().collect::<String>();
+            nfc.as_str()
         } else {
-            return format!("/{}", normalized);
+            path
         };
 
-        // Handle . and .. components
-        let components: Vec<&str> = normalized.split('/').filter(|s| !s.is_empty()).collect();
+        // Relative paths are interpreted relative to root. `..` components are
+        // clamped at root so that no path can climb above it.
+        let components: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
         let mut result = Vec::new();
 
         for component in components {
@@ -572,23 +1337,23 @@ impl AgentFS {
         }
 
         if result.is_empty() {
-            "/".to_string()
+            Ok("/".to_string())
         } else {
-            format!("/{}", result.join("/"))
+            Ok(format!("/{}", result.join("/")))
         }
     }
 
     /// Split path into components
-    fn split_path(&self, path: &str) -> Vec<String> {
-        let normalized = self.normalize_path(path);
+    fn split_path(&self, path: &str) -> Result<Vec<String>> {
+        let normalized = self.normalize_path(path)?;
         if normalized == "/" {
-            return vec![];
+            return Ok(vec![]);
         }
-        normalized
+        Ok(normalized
             .split('/')
             .filter(|p| !p.is_empty())
             .map(|s| s.to_string())
-            .collect()
+            .collect())
     }
 
     /// Look up a child entry by parent inode and name.
@@ -597,10 +1362,14 @@ impl AgentFS {
     /// as it avoids re-resolving all parent path components.
     async fn lookup_child(&self, parent_ino: i64, name: &str) -> Result<Option<i64>> {
         let mut rows = self
-            .conn
+            .backend
             .query(
-                "SELECT ino FROM fs_dentry WHERE parent_ino = ? AND name = ?",
-                (parent_ino, name),
+                if self.filenames.case_insensitive {
+                    "SELECT ino FROM fs_dentry WHERE parent_ino = ? AND name_key = ?"
+                } else {
+                    "SELECT ino FROM fs_dentry WHERE parent_ino = ? AND name = ?"
+                },
+                vec![parent_ino.into(), self.match_name(name).into()],
             )
             .await?;
 
@@ -621,11 +1390,10 @@ impl AgentFS {
 
     /// Get link count for an inode
     async fn get_link_count(&self, ino: i64) -> Result<u32> {
-        let mut stmt = self
-            .conn
-            .prepare_cached("SELECT nlink FROM fs_inode WHERE ino = ?")
+        let mut rows = self
+            .backend
+            .query("SELECT nlink FROM fs_inode WHERE ino = ?", vec![ino.into()])
             .await?;
-        let mut rows = stmt.query((ino,)).await?;
 
         if let Some(row) = rows.next().await? {
             let nlink = row
@@ -643,7 +1411,7 @@ impl AgentFS {
     ///
     /// The row should contain columns in this order:
     /// ino, mode, nlink, uid, gid, size, atime, mtime, ctime
-    fn build_stats_from_row(row: &turso::Row) -> Result<Stats> {
+    fn build_stats_from_row(row: &Row) -> Result<Stats> {
         Ok(Stats {
             ino: row
                 .get_value(0)
@@ -695,7 +1463,7 @@ impl AgentFS {
 
     /// Resolve a path to an inode number
     async fn resolve_path(&self, path: &str) -> Result<Option<i64>> {
-        let components = self.split_path(path);
+        let components = self.split_path(path)?;
         if components.is_empty() {
             return Ok(Some(ROOT_INO));
         }
@@ -709,11 +1477,17 @@ impl AgentFS {
             }
 
             // Cache miss - query database
-            let mut statement = self
-                .conn
-                .prepare_cached("SELECT ino FROM fs_dentry WHERE parent_ino = ? AND name = ?")
+            let mut rows = self
+                .backend
+                .query(
+                    if self.filenames.case_insensitive {
+                        "SELECT ino FROM fs_dentry WHERE parent_ino = ? AND name_key = ?"
+                    } else {
+                        "SELECT ino FROM fs_dentry WHERE parent_ino = ? AND name = ?"
+                    },
+                    vec![current_ino.into(), self.match_name(&component).into()],
+                )
                 .await?;
-            let mut rows = statement.query((current_ino, component.as_str())).await?;
 
             let mut found_row = None;
             let mut row_count = 0;
@@ -745,19 +1519,44 @@ impl AgentFS {
         Ok(Some(current_ino))
     }
 
+    /// Resolve the directory a new entry is created in
+    ///
+    /// Fails with `FsError::NotFound` if it doesn't exist and with
+    /// `FsError::NotADirectory` if it isn't a directory.
+    async fn resolve_parent(&self, parent_path: &str) -> Result<i64> {
+        let ino = self
+            .resolve_path(parent_path)
+            .await?
+            .ok_or(FsError::NotFound)?;
+        if ino != ROOT_INO {
+            let row = self
+                .backend
+                .query_one("SELECT mode FROM fs_inode WHERE ino = ?", vec![ino.into()])
+                .await?;
+            let mode = row
+                .and_then(|row| row.get_value(0).ok())
+                .and_then(|v| v.as_integer().copied())
+                .ok_or(FsError::NotFound)? as u32;
+            if (mode & S_IFMT) != S_IFDIR {
+                return Err(FsError::NotADirectory.into());
+            }
+        }
+        Ok(ino)
+    }
+
     /// Get file statistics without following symlinks
     pub async fn lstat(&self, path: &str) -> Result<Option<Stats>> {
-        let path = self.normalize_path(path);
+        let path = self.normalize_path(path)?;
         let ino = match self.resolve_path(&path).await? {
             Some(ino) => ino,
             None => return Ok(None),
         };
 
         let mut rows = self
-            .conn
+            .backend
             .query(
                 "SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime FROM fs_inode WHERE ino = ?",
-                (ino,),
+                vec![ino.into()],
             )
             .await?;
 
@@ -771,74 +1570,146 @@ impl AgentFS {
 
     /// Get file statistics, following symlinks
     pub async fn stat(&self, path: &str) -> Result<Option<Stats>> {
-        let path = self.normalize_path(path);
-
-        // Follow symlinks with a maximum depth to prevent infinite loops
-        let mut current_path = path;
-        let max_symlink_depth = 40; // Standard limit for symlink following
+        match self.resolve_path_follow(path).await? {
+            Some(ino) => self.stat_ino(ino).await,
+            None => Ok(None),
+        }
+    }
 
-        for _ in 0..max_symlink_depth {
+    /// Resolve a path to an inode, following a symlink at the end of it
+    ///
+    /// Follows at most `max_symlink_depth` links; one more (including any
+    /// cycle) fails with `FsError::SymlinkLoop`. Returns `Ok(None)` if the path
+    /// or the final link target does not exist.
+    async fn resolve_path_follow(&self, path: &str) -> Result<Option<i64>> {
+        let mut current_path = self.normalize_path(path)?;
+        let mut followed = 0;
+
+        loop {
             let ino = match self.resolve_path(&current_path).await? {
                 Some(ino) => ino,
                 None => return Ok(None),
             };
 
             let mut rows = self
-                .conn
-                .query(
-                    "SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime FROM fs_inode WHERE ino = ?",
-                    (ino,),
-                )
+                .backend
+                .query("SELECT mode FROM fs_inode WHERE ino = ?", vec![ino.into()])
                 .await?;
-
-            if let Some(row) = rows.next().await? {
-                let mode = row
-                    .get_value(1)
+            let mode = match rows.next().await? {
+                Some(row) => row
+                    .get_value(0)
                     .ok()
                     .and_then(|v| v.as_integer().copied())
-                    .unwrap_or(0) as u32;
-
-                // Check if this is a symlink
-                if (mode & S_IFMT) == S_IFLNK {
-                    // Read the symlink target
-                    let target = self
-                        .readlink(&current_path)
-                        .await?
-                        .ok_or_else(|| anyhow::anyhow!("Symlink has no target"))?;
-
-                    // Resolve target path (handle both absolute and relative paths)
-                    current_path = if target.starts_with('/') {
-                        target
-                    } else {
-                        // Relative path - resolve relative to the symlink's directory
-                        let base_path = Path::new(&current_path);
-                        let parent = base_path.parent().unwrap_or(Path::new("/"));
-                        let joined = parent.join(&target);
-                        joined.to_string_lossy().into_owned()
-                    };
-                    current_path = self.normalize_path(&current_path);
-                    continue; // Follow the symlink
-                }
+                    .unwrap_or(0) as u32,
+                None => return Ok(None),
+            };
+            if (mode & S_IFMT) != S_IFLNK {
+                return Ok(Some(ino));
+            }
+
+            if followed == self.max_symlink_depth {
+                return Err(FsError::SymlinkLoop.into());
+            }
+            followed += 1;
+
+            let target = self
+                .readlink(&current_path)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Symlink has no target"))?;
 
-                // Not a symlink, return the stats
-                let stats = Self::build_stats_from_row(&row)?;
-                return Ok(Some(stats));
+            // Resolve target path (handle both absolute and relative paths)
+            current_path = if target.starts_with('/') {
+                target
             } else {
+                // Relative path - resolve relative to the symlink's directory
+                let base_path = Path::new(&current_path);
+                let parent = base_path.parent().unwrap_or(Path::new("/"));
+                let joined = parent.join(&target);
+                joined.to_string_lossy().into_owned()
+            };
+            current_path = self.normalize_path(&current_path)?;
+        }
+    }
+
+    /// Get file statistics by inode number, without following symlinks
+    ///
+    /// Returns `Ok(None)` if no inode with that number exists.
+    pub async fn stat_ino(&self, ino: i64) -> Result<Option<Stats>> {
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT ino, mode, nlink, uid, gid, size, atime, mtime, ctime FROM fs_inode WHERE ino = ?",
+                vec![ino.into()],
+            )
+            .await?;
+
+        match rows.next().await? {
+            Some(row) => Ok(Some(Self::build_stats_from_row(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Find a path that refers to an inode
+    ///
+    /// An inode with several hard links resolves to the oldest of them.
+    /// Returns `Ok(None)` if the inode is not linked into the tree.
+    pub async fn path_for_ino(&self, ino: i64) -> Result<Option<String>> {
+        let mut components = Vec::new();
+        let mut current = ino;
+        while current != ROOT_INO {
+            let mut rows = self
+                .backend
+                .query(
+                    "SELECT parent_ino, name FROM fs_dentry WHERE ino = ? ORDER BY id LIMIT 1",
+                    vec![current.into()],
+                )
+                .await?;
+            let Some(row) = rows.next().await? else {
                 return Ok(None);
+            };
+            current = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(ROOT_INO);
+            if let Ok(Value::Text(name)) = row.get_value(1) {
+                components.push(name);
+            }
+            // A cycle would mean a corrupt tree; don't loop forever on it
+            if components.len() > 4096 {
+                return Err(FsError::InvalidPath.into());
             }
         }
-
-        // Too many symlinks
-        anyhow::bail!("Too many levels of symbolic links")
+        components.reverse();
+        Ok(Some(format!("/{}", components.join("/"))))
     }
 
     /// Create a directory
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = path)))]
     pub async fn mkdir(&self, path: &str) -> Result<()> {
-        let path = self.normalize_path(path);
-        let components = self.split_path(&path);
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        match self.mkdir_locked(path).await {
+            Ok(()) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Create a directory
+    ///
+    /// The caller holds the write transaction.
+    async fn mkdir_locked(&self, path: &str) -> Result<()> {
+        let path = self.normalize_path(path)?;
+        let components = self.split_path(&path)?;
 
         if components.is_empty() {
-            anyhow::bail!("Cannot create root directory");
+            return Err(FsError::AlreadyExists.into());
         }
 
         let parent_path = if components.len() == 1 {
@@ -847,50 +1718,59 @@ impl AgentFS {
             format!("/{}", components[..components.len() - 1].join("/"))
         };
 
-        let parent_ino = self
-            .resolve_path(&parent_path)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+        let parent_ino = self.resolve_parent(&parent_path).await?;
 
         let name = components.last().unwrap();
 
         // Check if already exists (single query using parent_ino we already have)
         if self.lookup_child(parent_ino, name).await?.is_some() {
-            anyhow::bail!("Directory already exists");
+            return Err(FsError::AlreadyExists.into());
         }
 
         // Create inode
-        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-        let mut stmt = self
-            .conn
-            .prepare(
+        let now = self.clock.unix_time();
+        let row = self
+            .backend
+            .query_one(
                 "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
                 VALUES (?, 0, 0, 0, ?, ?, ?) RETURNING ino",
+                vec![
+                    (DEFAULT_DIR_MODE as i64).into(),
+                    now.into(),
+                    now.into(),
+                    now.into(),
+                ],
             )
             .await?;
-        let row = stmt
-            .query_row((DEFAULT_DIR_MODE as i64, now, now, now))
-            .await?;
 
         let ino = row
-            .get_value(0)
-            .ok()
+            .and_then(|row| row.get_value(0).ok())
             .and_then(|v| v.as_integer().copied())
             .ok_or_else(|| anyhow::anyhow!("Failed to get inode"))?;
 
         // Create directory entry
-        let mut stmt = self
-            .conn
-            .prepare_cached("INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)")
+        self.backend
+            .execute(
+                "INSERT INTO fs_dentry (name, parent_ino, ino, name_key) VALUES (?, ?, ?, ?)",
+                vec![
+                    name.as_str().into(),
+                    parent_ino.into(),
+                    ino.into(),
+                    self.match_name(name).into(),
+                ],
+            )
             .await?;
-        stmt.execute((name.as_str(), parent_ino, ino)).await?;
 
         // Increment link count
-        let mut stmt = self
-            .conn
-            .prepare_cached("UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?")
+        self.backend
+            .execute(
+                "UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?",
+                vec![ino.into()],
+            )
+            .await?;
+
+        self.journal_locked(JournalOp::Mkdir, &path, None, None)
             .await?;
-        stmt.execute((ino,)).await?;
 
         // Populate dentry cache
         self.dentry_cache.insert(parent_ino, name, ino);
@@ -898,13 +1778,13 @@ impl AgentFS {
         Ok(())
     }
 
-    /// Write data to a file
-    pub async fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
-        let path = self.normalize_path(path);
-        let components = self.split_path(&path);
+    /// Resolve the parent directory inode and final name of a file to write
+    async fn resolve_write_target(&self, path: &str) -> Result<(i64, String)> {
+        let path = self.normalize_path(path)?;
+        let components = self.split_path(&path)?;
 
         if components.is_empty() {
-            anyhow::bail!("Cannot write to root directory");
+            return Err(FsError::IsADirectory.into());
         }
 
         let parent_path = if components.len() == 1 {
@@ -913,133 +1793,786 @@ impl AgentFS {
             format!("/{}", components[..components.len() - 1].join("/"))
         };
 
-        let parent_ino = self
-            .resolve_path(&parent_path)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+        let parent_ino = self.resolve_parent(&parent_path).await?;
 
-        let name = components.last().unwrap();
+        let name = components.last().unwrap().to_string();
+        Ok((parent_ino, name))
+    }
+
+    /// Get an empty file to write into, creating it with `mode` if it does not exist
+    ///
+    /// Existing data is deleted. Must be called inside a transaction.
+    async fn create_or_clear_file(
+        &self,
+        parent_ino: i64,
+        name: &str,
+        size: u64,
+        mode: u32,
+    ) -> Result<i64> {
+        // Check if file exists (single query using parent_ino we already have)
+        if let Some(ino) = self.lookup_child(parent_ino, name).await? {
+            // Delete existing data
+            self.backend
+                .execute("DELETE FROM fs_data WHERE ino = ?", vec![ino.into()])
+                .await?;
+            return Ok(ino);
+        }
 
-        self.conn.execute("BEGIN IMMEDIATE", ()).await?;
+        // Create new inode
+        let now = self.clock.unix_time();
+        let row = self
+            .backend
+            .query_one(
+                "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
+                VALUES (?, 0, 0, ?, ?, ?, ?) RETURNING ino",
+                vec![
+                    (mode as i64).into(),
+                    (size as i64).into(),
+                    now.into(),
+                    now.into(),
+                    now.into(),
+                ],
+            )
+            .await?;
 
-        let result: Result<()> = async {
-            // Check if file exists (single query using parent_ino we already have)
-            let ino = if let Some(ino) = self.lookup_child(parent_ino, name).await? {
-                // Delete existing data
-                let mut stmt = self
-                    .conn
-                    .prepare_cached("DELETE FROM fs_data WHERE ino = ?")
-                    .await?;
-                stmt.execute((ino,)).await?;
-                ino
-            } else {
-                // Create new inode
-                let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-                let mut stmt = self
-                    .conn
-                    .prepare(
-                        "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
-                        VALUES (?, 0, 0, ?, ?, ?, ?) RETURNING ino",
+        let ino = row
+            .and_then(|row| row.get_value(0).ok())
+            .and_then(|v| v.as_integer().copied())
+            .ok_or_else(|| anyhow::anyhow!("Failed to get inode"))?;
+
+        // Create directory entry
+        self.backend
+            .execute(
+                "INSERT INTO fs_dentry (name, parent_ino, ino, name_key) VALUES (?, ?, ?, ?)",
+                vec![
+                    name.into(),
+                    parent_ino.into(),
+                    ino.into(),
+                    self.match_name(name).into(),
+                ],
+            )
+            .await?;
+
+        // Increment link count
+        self.backend
+            .execute(
+                "UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?",
+                vec![ino.into()],
+            )
+            .await?;
+
+        // Populate dentry cache for new file
+        self.dentry_cache.insert(parent_ino, name, ino);
+
+        Ok(ino)
+    }
+
+    /// Mark a freshly written file as a regular file of `size` bytes
+    ///
+    /// With a `mode`, the file gets it. Otherwise a regular file keeps its
+    /// permissions, and anything else written through (a symlink) becomes a
+    /// regular file with [`DEFAULT_FILE_MODE`].
+    async fn finish_file_write(&self, ino: i64, size: u64, mode: Option<u32>) -> Result<()> {
+        let now = self.clock.unix_time();
+        match mode {
+            Some(mode) => {
+                self.backend
+                    .execute(
+                        "UPDATE fs_inode SET mode = ?, size = ?, mtime = ? WHERE ino = ?",
+                        vec![
+                            (mode as i64).into(),
+                            (size as i64).into(),
+                            now.into(),
+                            ino.into(),
+                        ],
                     )
                     .await?;
-                let row = stmt
-                    .query_row((DEFAULT_FILE_MODE as i64, data.len() as i64, now, now, now))
+            }
+            None => {
+                self.backend
+                    .execute(
+                        "UPDATE fs_inode
+                        SET mode = CASE WHEN (mode & ?) = ? THEN mode ELSE ? END, size = ?, mtime = ?
+                        WHERE ino = ?",
+                        vec![
+                            (S_IFMT as i64).into(),
+                            (S_IFREG as i64).into(),
+                            (DEFAULT_FILE_MODE as i64).into(),
+                            (size as i64).into(),
+                            now.into(),
+                            ino.into(),
+                        ],
+                    )
                     .await?;
+            }
+        }
+        Ok(())
+    }
 
-                let ino = row
-                    .get_value(0)
-                    .ok()
-                    .and_then(|v| v.as_integer().copied())
-                    .ok_or_else(|| anyhow::anyhow!("Failed to get inode"))?;
+    /// Write data to a file
+    ///
+    /// A new file gets [`DEFAULT_FILE_MODE`]; an existing regular file keeps
+    /// its permissions. Use [`AgentFS::write_file_mode`] to choose them.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = path, size = data.len())))]
+    pub async fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
+        self.write_file_with(path, data, None).await
+    }
 
-                // Create directory entry
-                let mut stmt = self
-                    .conn
-                    .prepare_cached(
-                        "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)",
-                    )
-                    .await?;
-                stmt.execute((name.as_str(), parent_ino, ino)).await?;
+    /// Write data to a file, giving it the permission bits of `mode`
+    ///
+    /// The permissions (`mode & 0o7777`) are set whether the file is created
+    /// or overwritten, e.g. to create an executable script; the file type is
+    /// always a regular file.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = path, size = data.len(), mode = mode)))]
+    pub async fn write_file_mode(&self, path: &str, data: &[u8], mode: u32) -> Result<()> {
+        self.write_file_with(path, data, Some(S_IFREG | (mode & 0o7777)))
+            .await
+    }
 
-                // Increment link count
-                let mut stmt = self
-                    .conn
-                    .prepare_cached("UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?")
-                    .await?;
-                stmt.execute((ino,)).await?;
+    async fn write_file_with(&self, path: &str, data: &[u8], mode: Option<u32>) -> Result<()> {
+        let path = self.normalize_path(path)?;
+        let (parent_ino, name) = self.resolve_write_target(&path).await?;
 
-                // Populate dentry cache for new file
-                self.dentry_cache.insert(parent_ino, name, ino);
+        check_file_size(self.max_file_size, data.len() as u64)?;
 
-                ino
-            };
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        let result: Result<()> = async {
+            let ino = self
+                .create_or_clear_file(
+                    parent_ino,
+                    &name,
+                    data.len() as u64,
+                    mode.unwrap_or(DEFAULT_FILE_MODE),
+                )
+                .await?;
 
             // Write data in chunks
             for (chunk_index, chunk) in data.chunks(self.chunk_size).enumerate() {
-                self.conn
+                let (blob, compressed) = encode_block(chunk, self.compression)?;
+                self.backend
                     .execute(
-                        "INSERT INTO fs_data (ino, chunk_index, data) VALUES (?, ?, ?)",
-                        (ino, chunk_index as i64, chunk),
+                        "INSERT INTO fs_data (ino, chunk_index, data, compressed) VALUES (?, ?, ?, ?)",
+                        vec![
+                            ino.into(),
+                            (chunk_index as i64).into(),
+                            blob,
+                            compressed.into(),
+                        ],
                     )
                     .await?;
             }
 
-            // Update mode (to regular file), size and mtime
-            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-            let mut stmt = self
-                .conn
-                .prepare_cached("UPDATE fs_inode SET mode = ?, size = ?, mtime = ? WHERE ino = ?")
-                .await?;
-            stmt.execute((DEFAULT_FILE_MODE as i64, data.len() as i64, now, ino))
-                .await?;
-
-            Ok(())
+            self.finish_file_write(ino, data.len() as u64, mode).await?;
+            self.journal_locked(JournalOp::Write, &path, None, Some(data))
+                .await
         }
         .await;
 
         match result {
             Ok(()) => {
-                self.conn.execute("COMMIT", ()).await?;
+                self.backend.execute("COMMIT", Vec::new()).await?;
                 Ok(())
             }
             Err(e) => {
-                let _ = self.conn.execute("ROLLBACK", ()).await;
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                self.dentry_cache.remove(parent_ino, &name);
                 Err(e)
             }
         }
     }
 
-    /// Read data from a file
-    pub async fn read_file(&self, path: &str) -> Result<Option<Vec<u8>>> {
-        let ino = match self.resolve_path(path).await? {
-            Some(ino) => ino,
-            None => return Ok(None),
-        };
+    /// Create a new file with `data`, failing if the path already exists
+    ///
+    /// Like `open` with `O_CREAT | O_EXCL`, this is safe to use for lock and
+    /// sentinel files: the directory entry is inserted with `ON CONFLICT DO
+    /// NOTHING` against the `UNIQUE(parent_ino, name)` constraint of
+    /// `fs_dentry`, so of several racing creators exactly one succeeds. The
+    /// others get [`FsError::AlreadyExists`], as does any existing entry,
+    /// whatever its type.
+    pub async fn create_new(&self, path: &str, data: &[u8]) -> Result<()> {
+        let path = self.normalize_path(path)?;
+        let (parent_ino, name) = self.resolve_write_target(&path).await?;
+
+        check_file_size(self.max_file_size, data.len() as u64)?;
+
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        let result: Result<i64> = async {
+            let now = self.clock.unix_time();
+            let row = self
+                .backend
+                .query_one(
+                    "INSERT INTO fs_inode (mode, nlink, uid, gid, size, atime, mtime, ctime)
+                    VALUES (?, 1, 0, 0, ?, ?, ?, ?) RETURNING ino",
+                    vec![
+                        (DEFAULT_FILE_MODE as i64).into(),
+                        (data.len() as i64).into(),
+                        now.into(),
+                        now.into(),
+                        now.into(),
+                    ],
+                )
+                .await?;
+            let ino = row
+                .and_then(|row| row.get_value(0).ok())
+                .and_then(|v| v.as_integer().copied())
+                .ok_or_else(|| anyhow::anyhow!("Failed to get inode"))?;
 
-        let mut rows = self
-            .conn
-            .query(
-                "SELECT data FROM fs_data WHERE ino = ? ORDER BY chunk_index",
-                (ino,),
-            )
-            .await?;
+            // No row comes back if the name is taken. Either unique index can
+            // reject it: the exact name, or the name key when names are matched
+            // case-insensitively.
+            let mut rows = self
+                .backend
+                .query(
+                    "INSERT INTO fs_dentry (name, parent_ino, ino, name_key) VALUES (?, ?, ?, ?)
+                    ON CONFLICT DO NOTHING RETURNING id",
+                    vec![
+                        name.as_str().into(),
+                        parent_ino.into(),
+                        ino.into(),
+                        self.match_name(&name).into(),
+                    ],
+                )
+                .await?;
+            let inserted = rows.next().await?.is_some();
+            drop(rows);
+            if !inserted {
+                return Err(FsError::AlreadyExists.into());
+            }
 
-        let mut data = Vec::new();
-        while let Some(row) = rows.next().await? {
-            if let Ok(Value::Blob(chunk)) = row.get_value(0) {
-                data.extend_from_slice(&chunk);
+            for (chunk_index, chunk) in data.chunks(self.chunk_size).enumerate() {
+                let (blob, compressed) = encode_block(chunk, self.compression)?;
+                self.backend
+                    .execute(
+                        "INSERT INTO fs_data (ino, chunk_index, data, compressed) VALUES (?, ?, ?, ?)",
+                        vec![
+                            ino.into(),
+                            (chunk_index as i64).into(),
+                            blob,
+                            compressed.into(),
+                        ],
+                    )
+                    .await?;
             }
+            self.journal_locked(JournalOp::Write, &path, None, Some(data))
+                .await?;
+            Ok(ino)
         }
+        .await;
 
-        Ok(Some(data))
-    }
-
-    /// Reads from a file at a given offset.
-    ///
-    /// Similar to POSIX `pread`, this reads up to `size` bytes from the file
+        match result {
+            Ok(ino) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                self.dentry_cache.insert(parent_ino, &name, ino);
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Write a file from a reader, replacing any existing contents
+    ///
+    /// The reader is consumed one chunk at a time and each chunk is inserted as
+    /// soon as it is read, so the payload is never held in memory as a whole.
+    /// The write runs in a single transaction: if reading or writing fails, the
+    /// file is left as it was before the call.
+    ///
+    /// Returns the number of bytes written.
+    pub async fn write_file_from<R>(&self, path: &str, mut reader: R) -> Result<u64>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let path = self.normalize_path(path)?;
+        let (parent_ino, name) = self.resolve_write_target(&path).await?;
+
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        let result: Result<u64> = async {
+            let ino = self
+                .create_or_clear_file(parent_ino, &name, 0, DEFAULT_FILE_MODE)
+                .await?;
+            let mut hasher = self.journal.then(Sha256::new);
+            let total = self
+                .write_chunks_from(ino, &mut reader, hasher.as_mut())
+                .await?;
+            self.finish_file_write(ino, total, None).await?;
+            self.journal_written_locked(&path, total, hasher).await?;
+            Ok(total)
+        }
+        .await;
+
+        match result {
+            Ok(total) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                Ok(total)
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                self.dentry_cache.remove(parent_ino, &name);
+                Err(e)
+            }
+        }
+    }
+
+    /// Insert the contents of `reader` as the chunks of an empty file
+    ///
+    /// Must be called inside a transaction. Each chunk is also fed to
+    /// `hasher`, if given. Returns the number of bytes written.
+    async fn write_chunks_from<R>(
+        &self,
+        ino: i64,
+        reader: &mut R,
+        mut hasher: Option<&mut Sha256>,
+    ) -> Result<u64>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        let mut chunk = vec![0u8; self.chunk_size];
+        let mut chunk_index = 0i64;
+        let mut total = 0u64;
+        loop {
+            // Fill the whole chunk (readers may return short reads) so that
+            // chunk boundaries stay aligned with chunk_size
+            let mut filled = 0;
+            while filled < chunk.len() {
+                let n = reader.read(&mut chunk[filled..]).await?;
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+            }
+            if filled == 0 {
+                break;
+            }
+
+            total += filled as u64;
+            check_file_size(self.max_file_size, total)?;
+            if let Some(hasher) = hasher.as_mut() {
+                hasher.update(&chunk[..filled]);
+            }
+
+            let (blob, compressed) = encode_block(&chunk[..filled], self.compression)?;
+            self.backend
+                .execute(
+                    "INSERT INTO fs_data (ino, chunk_index, data, compressed) VALUES (?, ?, ?, ?)",
+                    vec![ino.into(), chunk_index.into(), blob, compressed.into()],
+                )
+                .await?;
+            chunk_index += 1;
+
+            if filled < chunk.len() {
+                break;
+            }
+        }
+        Ok(total)
+    }
+
+    /// Journal a streamed write of `size` bytes, hashed into `hasher`
+    ///
+    /// The caller holds the write transaction.
+    async fn journal_written_locked(
+        &self,
+        path: &str,
+        size: u64,
+        hasher: Option<Sha256>,
+    ) -> Result<()> {
+        let hash = hasher.map(|hasher| format!("{:x}", hasher.finalize()));
+        self.journal_entry_locked(JournalOp::Write, path, None, Some(size), hash)
+            .await
+    }
+
+    /// Create a directory and any missing parents
+    ///
+    /// Fails with `FsError::NotADirectory` if a component exists but is not a
+    /// directory.
+    pub async fn create_dir_all(&self, path: &str) -> Result<()> {
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        match self.create_dir_all_locked(path).await {
+            Ok(()) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                // Directories created before the failure may be cached
+                self.dentry_cache.clear();
+                Err(e)
+            }
+        }
+    }
+
+    /// Create a directory and any missing parents
+    ///
+    /// The caller holds the write transaction.
+    async fn create_dir_all_locked(&self, path: &str) -> Result<()> {
+        let path = self.normalize_path(path)?;
+        let mut current = String::new();
+        for component in self.split_path(&path)? {
+            current.push('/');
+            current.push_str(&component);
+            match self.lstat(&current).await? {
+                Some(stats) if stats.is_directory() => {}
+                Some(_) => return Err(FsError::NotADirectory.into()),
+                None => self.mkdir_locked(&current).await?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Recursively copy a host directory into the filesystem at `dst`
+    ///
+    /// Same as [`AgentFS::import_dir_with`] with default options.
+    pub async fn import_dir(&self, host_dir: &Path, dst: &str) -> Result<ImportSummary> {
+        self.import_dir_with(host_dir, dst, ImportOptions::default())
+            .await
+    }
+
+    /// Recursively copy a host directory into the filesystem at `dst`
+    ///
+    /// `dst` and any missing parents are created, and existing files are
+    /// overwritten. Symlinks are recreated with their original target; sockets,
+    /// FIFOs and device files are skipped. File contents are streamed chunk by
+    /// chunk.
+    ///
+    /// Entries are written in transactions of a few hundred at a time. If an
+    /// entry fails, the batch it belongs to is rolled back and the error is
+    /// returned; earlier batches stay imported.
+    pub async fn import_dir_with(
+        &self,
+        host_dir: &Path,
+        dst: &str,
+        options: ImportOptions,
+    ) -> Result<ImportSummary> {
+        if !std::fs::metadata(host_dir)?.is_dir() {
+            return Err(FsError::NotADirectory.into());
+        }
+        let dst = self.normalize_path(dst)?;
+        self.create_dir_all(&dst).await?;
+
+        let mut summary = ImportSummary::default();
+        let mut stack = vec![(host_dir.to_path_buf(), dst)];
+
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        let result: Result<()> = async {
+            let mut batched = 0;
+            while let Some((host_path, path)) = stack.pop() {
+                let mut entries =
+                    std::fs::read_dir(&host_path)?.collect::<std::io::Result<Vec<_>>>()?;
+                entries.sort_by_key(|entry| entry.file_name());
+
+                for entry in entries {
+                    let name = entry.file_name();
+                    let name = name.to_str().ok_or_else(|| {
+                        anyhow::anyhow!("File name is not valid UTF-8: {}", entry.path().display())
+                    })?;
+                    let child = if path == "/" {
+                        format!("/{}", name)
+                    } else {
+                        format!("{}/{}", path, name)
+                    };
+                    let file_type = entry.file_type()?;
+
+                    // Files and symlinks replace whatever non-directory is in their way
+                    if file_type.is_file() || file_type.is_symlink() {
+                        match self.lstat(&child).await? {
+                            Some(stats) if stats.is_directory() => {
+                                return Err(FsError::IsADirectory.into())
+                            }
+                            Some(stats) if stats.is_symlink() || file_type.is_symlink() => {
+                                self.remove_locked(&child).await?
+                            }
+                            _ => {}
+                        }
+                    }
+
+                    if file_type.is_dir() {
+                        self.create_dir_all_locked(&child).await?;
+                        summary.directories += 1;
+                        stack.push((entry.path(), child.clone()));
+                    } else if file_type.is_file() {
+                        let mut file = tokio::fs::File::open(entry.path()).await?;
+                        let (parent_ino, name) = self.resolve_write_target(&child).await?;
+                        let ino = self
+                            .create_or_clear_file(parent_ino, &name, 0, DEFAULT_FILE_MODE)
+                            .await?;
+                        let mut hasher = self.journal.then(Sha256::new);
+                        let size = self
+                            .write_chunks_from(ino, &mut file, hasher.as_mut())
+                            .await?;
+                        self.finish_file_write(ino, size, None).await?;
+                        self.journal_written_locked(&child, size, hasher).await?;
+                        summary.files += 1;
+                        summary.bytes += size;
+                    } else if file_type.is_symlink() {
+                        let target = std::fs::read_link(entry.path())?;
+                        let target = target.to_str().ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "Symlink target is not valid UTF-8: {}",
+                                target.display()
+                            )
+                        })?;
+                        self.symlink_locked(target, &child).await?;
+                        summary.symlinks += 1;
+                    } else {
+                        continue;
+                    }
+
+                    // chmod would change the link itself, which has no meaningful mode
+                    if options.preserve_mode && !file_type.is_symlink() {
+                        if let Some(mode) = host_permissions(&entry.metadata()?) {
+                            self.chmod_locked(&child, mode).await?;
+                        }
+                    }
+
+                    batched += 1;
+                    if batched >= IMPORT_BATCH_SIZE {
+                        self.backend.execute("COMMIT", Vec::new()).await?;
+                        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+                        batched = 0;
+                    }
+                }
+            }
+            Ok(())
+        }
+        .await;
+
+        match result {
+            Ok(()) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                Ok(summary)
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                // Entries created in the rolled back batch may be cached
+                self.dentry_cache.clear();
+                Err(e)
+            }
+        }
+    }
+
+    /// Recursively write the subtree at `src` out to `host_dir`
+    ///
+    /// Same as [`AgentFS::export_dir_with`] with default options.
+    pub async fn export_dir(&self, src: &str, host_dir: &Path) -> Result<ExportSummary> {
+        self.export_dir_with(src, host_dir, ExportOptions::default())
+            .await
+    }
+
+    /// Recursively write the subtree at `src` out to `host_dir`
+    ///
+    /// `host_dir` and any missing parents are created, and existing host files
+    /// are overwritten. Files keep their permission bits and are streamed a
+    /// chunk at a time. Symlinks are recreated as they are unless
+    /// `follow_symlinks` is set, in which case what they point to is written
+    /// instead; dangling links and links back into a directory being exported
+    /// are skipped.
+    pub async fn export_dir_with(
+        &self,
+        src: &str,
+        host_dir: &Path,
+        options: ExportOptions,
+    ) -> Result<ExportSummary> {
+        let src = self.normalize_path(src)?;
+        let root = self.stat(&src).await?.ok_or(FsError::NotFound)?;
+        if !root.is_directory() {
+            return Err(FsError::NotADirectory.into());
+        }
+        std::fs::create_dir_all(host_dir)?;
+
+        let mut summary = ExportSummary::default();
+        // Directories on the path from `src` to the current one, to stop
+        // followed symlinks from recursing forever
+        let mut stack = vec![(src, host_dir.to_path_buf(), vec![root.ino])];
+
+        while let Some((path, host_path, ancestors)) = stack.pop() {
+            let entries = self.readdir_plus(&path).await?.unwrap_or_default();
+            for entry in entries {
+                let child = if path == "/" {
+                    format!("/{}", entry.name)
+                } else {
+                    format!("{}/{}", path, entry.name)
+                };
+                let host_child = host_path.join(&entry.name);
+
+                let stats = if entry.stats.is_symlink() {
+                    if !options.follow_symlinks {
+                        let target = self.readlink(&child).await?.unwrap_or_default();
+                        create_host_symlink(&target, &host_child)?;
+                        summary.symlinks += 1;
+                        continue;
+                    }
+                    match self.stat(&child).await? {
+                        Some(stats) => stats,
+                        None => {
+                            summary.skipped += 1;
+                            continue;
+                        }
+                    }
+                } else {
+                    entry.stats
+                };
+
+                if stats.is_directory() {
+                    if ancestors.contains(&stats.ino) {
+                        summary.skipped += 1;
+                        continue;
+                    }
+                    std::fs::create_dir_all(&host_child)?;
+                    summary.directories += 1;
+                    let mut ancestors = ancestors.clone();
+                    ancestors.push(stats.ino);
+                    stack.push((child, host_child, ancestors));
+                } else if stats.is_file() {
+                    summary.bytes += self.export_file(&stats, &host_child).await?;
+                    summary.files += 1;
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Stream the contents of a regular file to a host path
+    async fn export_file(&self, stats: &Stats, host_path: &Path) -> Result<u64> {
+        // A symlink left at the destination would redirect the write
+        if std::fs::symlink_metadata(host_path).is_ok_and(|m| m.file_type().is_symlink()) {
+            std::fs::remove_file(host_path)?;
+        }
+
+        let size = stats.size.max(0) as u64;
+        let mut out = tokio::fs::File::create(host_path).await?;
+        let written = self.write_blocks_to(stats.ino, size, &mut out).await?;
+        set_host_permissions(host_path, stats.permissions())?;
+        Ok(written)
+    }
+
+    /// Stream the first `size` bytes of inode `ino` to `writer`
+    ///
+    /// Blocks are fetched with a single query and each one is written out as
+    /// its row arrives, so memory use stays at about one block whatever the
+    /// file size. Holes and a short tail are written as zeros. Returns the
+    /// number of bytes written.
+    async fn write_blocks_to<W>(&self, ino: i64, size: u64, writer: &mut W) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let zeros = vec![0u8; self.chunk_size];
+        let mut written = 0u64;
+
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT chunk_index, data, compressed FROM fs_data WHERE ino = ? ORDER BY chunk_index",
+                vec![ino.into()],
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let chunk_index = row
+                .get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0);
+            let Some(block) = block_from_row(&row, 1, self.chunk_size)? else {
+                continue;
+            };
+            let start = (chunk_index as u64 * self.chunk_size as u64).min(size);
+            written = write_zeros(writer, &zeros, written, start).await?;
+            let len = (block.len() as u64).min(size - written) as usize;
+            writer.write_all(&block[..len]).await?;
+            written += len as u64;
+        }
+        written = write_zeros(writer, &zeros, written, size).await?;
+
+        writer.flush().await?;
+        Ok(written)
+    }
+
+    /// Read data from a file, following a symlink at the end of the path
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = path, size = tracing::field::Empty)))]
+    pub async fn read_file(&self, path: &str) -> Result<Option<Vec<u8>>> {
+        let mut attempts = 0;
+        let data = loop {
+            let ino = match self.resolve_path_follow(path).await? {
+                Some(ino) => ino,
+                None => return Ok(None),
+            };
+            // Keep the blocks from being deleted if the file is replaced by a
+            // rename while they are read
+            let _open = self.open_inodes.acquire(ino);
+
+            // The join yields no rows if the inode was replaced and deleted
+            // between the lookup and now, in which case look the path up again
+            let mut rows = self
+                .backend
+                .query(
+                    "SELECT d.data, d.compressed FROM fs_inode i
+                     LEFT JOIN fs_data d ON d.ino = i.ino
+                     WHERE i.ino = ? ORDER BY d.chunk_index",
+                    vec![ino.into()],
+                )
+                .await?;
+
+            let mut data = Vec::new();
+            let mut found = false;
+            while let Some(row) = rows.next().await? {
+                found = true;
+                if let Some(chunk) = block_from_row(&row, 0, self.chunk_size)? {
+                    data.extend_from_slice(&chunk);
+                }
+            }
+            if found {
+                break data;
+            }
+            // A damaged entry naming a missing inode never resolves
+            attempts += 1;
+            if attempts == 3 {
+                return Ok(None);
+            }
+        };
+
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("size", data.len());
+
+        Ok(Some(data))
+    }
+
+    /// Stream a file's contents to a writer, following a symlink at the end
+    /// of the path
+    ///
+    /// Unlike [`read_file`](Self::read_file), the contents are never collected
+    /// in memory: each block is written as it is read from the database, so
+    /// large files can be copied out with about one block of memory.
+    ///
+    /// Returns the number of bytes written, or `Ok(None)` if the file does not
+    /// exist.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = path)))]
+    pub async fn read_file_to<W>(&self, path: &str, writer: &mut W) -> Result<Option<u64>>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let Some(ino) = self.resolve_path_follow(path).await? else {
+            return Ok(None);
+        };
+        let Some(stats) = self.stat_ino(ino).await? else {
+            return Ok(None);
+        };
+        let size = stats.size.max(0) as u64;
+        Ok(Some(self.write_blocks_to(ino, size, writer).await?))
+    }
+
+    /// Reads from a file at a given offset.
+    ///
+    /// Similar to POSIX `pread`, this reads up to `size` bytes from the file
     /// starting at `offset`, without modifying any file cursor.
     ///
     /// Returns `Ok(None)` if the file does not exist.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = path, offset = offset, size = size)))]
     pub async fn pread(&self, path: &str, offset: u64, size: u64) -> Result<Option<Vec<u8>>> {
         let ino = match self.resolve_path(path).await? {
             Some(ino) => ino,
@@ -1052,10 +2585,14 @@ impl AgentFS {
         let end_chunk = (offset + size).saturating_sub(1) / chunk_size;
 
         let mut rows = self
-            .conn
+            .backend
             .query(
-                "SELECT chunk_index, data FROM fs_data WHERE ino = ? AND chunk_index >= ? AND chunk_index <= ? ORDER BY chunk_index",
-                (ino, start_chunk as i64, end_chunk as i64),
+                "SELECT chunk_index, data, compressed FROM fs_data WHERE ino = ? AND chunk_index >= ? AND chunk_index <= ? ORDER BY chunk_index",
+                vec![
+                    ino.into(),
+                    (start_chunk as i64).into(),
+                    (end_chunk as i64).into(),
+                ],
             )
             .await?;
 
@@ -1063,7 +2600,7 @@ impl AgentFS {
         let start_offset_in_chunk = (offset % chunk_size) as usize;
 
         while let Some(row) = rows.next().await? {
-            if let Ok(Value::Blob(chunk_data)) = row.get_value(1) {
+            if let Some(chunk_data) = block_from_row(&row, 1, self.chunk_size)? {
                 let skip = if result.is_empty() {
                     start_offset_in_chunk
                 } else {
@@ -1088,12 +2625,176 @@ impl AgentFS {
     ///
     /// If the offset is beyond the current file size, the file is extended with zeros.
     /// If the file does not exist, it will be created.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = path, offset = offset, size = data.len())))]
     pub async fn pwrite(&self, path: &str, offset: u64, data: &[u8]) -> Result<()> {
-        let path = self.normalize_path(path);
-        let components = self.split_path(&path);
+        self.write_at(path, Some(offset), data).await
+    }
+
+    /// Appends data to the end of a file.
+    ///
+    /// The end of the file is looked up in the same transaction as the write,
+    /// so concurrent appends never overwrite each other. If the file does not
+    /// exist, it will be created.
+    pub async fn append_file(&self, path: &str, data: &[u8]) -> Result<()> {
+        self.write_at(path, None, data).await
+    }
+
+    /// Appends data to a log file, rotating it once it would grow past `max_bytes`.
+    ///
+    /// Before an append that would take a non-empty `path` over `max_bytes`,
+    /// `path` becomes `path.1`, `path.1` becomes `path.2` and so on, and
+    /// whatever would land beyond `path.{keep}` is deleted. With `keep == 0`
+    /// the old contents are simply discarded. Rotation and the append happen
+    /// in a single transaction, so a crash never leaves a half-rotated set of
+    /// files behind.
+    pub async fn append_rotating(
+        &self,
+        path: &str,
+        data: &[u8],
+        max_bytes: u64,
+        keep: usize,
+    ) -> Result<()> {
+        let (parent_ino, name) = self.resolve_write_target(path).await?;
+        let path = self.normalize_path(path)?;
+        let rotated: Vec<String> = (1..=keep).map(|i| format!("{}.{}", name, i)).collect();
+
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        let result: Result<()> = async {
+            if let Some(ino) = self.lookup_child(parent_ino, &name).await? {
+                let size = self.stat_ino(ino).await?.map_or(0, |stats| stats.size);
+                if size > 0 && size as u64 + data.len() as u64 > max_bytes {
+                    // Shift from the oldest generation down so no name is
+                    // ever occupied twice
+                    let generation = |i: usize| match i {
+                        0 => path.clone(),
+                        i => format!("{}.{}", path, i),
+                    };
+                    let oldest = rotated.last().unwrap_or(&name);
+                    if self.unlink_file_locked(parent_ino, oldest).await? {
+                        self.journal_locked(JournalOp::Remove, &generation(keep), None, None)
+                            .await?;
+                    }
+                    for i in (0..rotated.len()).rev() {
+                        let from = if i == 0 { &name } else { &rotated[i - 1] };
+                        if self
+                            .rename_entry_locked(parent_ino, from, &rotated[i])
+                            .await?
+                        {
+                            self.journal_locked(
+                                JournalOp::Rename,
+                                &generation(i),
+                                Some(&generation(i + 1)),
+                                None,
+                            )
+                            .await?;
+                        }
+                    }
+                    self.forget_rotated(parent_ino, &name, &rotated);
+                }
+            }
+            self.write_at_locked(&path, parent_ino, &name, None, data)
+                .await
+        }
+        .await;
+
+        match result {
+            Ok(()) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                self.forget_rotated(parent_ino, &name, &rotated);
+                Err(e)
+            }
+        }
+    }
+
+    /// Drop cached lookups for a log file and its rotated generations
+    fn forget_rotated(&self, parent_ino: i64, name: &str, rotated: &[String]) {
+        self.dentry_cache.remove(parent_ino, name);
+        for rotated_name in rotated {
+            self.dentry_cache.remove(parent_ino, rotated_name);
+        }
+    }
+
+    /// Remove the non-directory entry `name` from `parent_ino`, if present
+    ///
+    /// Returns whether there was an entry. The caller holds the write transaction.
+    async fn unlink_file_locked(&self, parent_ino: i64, name: &str) -> Result<bool> {
+        let Some(ino) = self.lookup_child(parent_ino, name).await? else {
+            return Ok(false);
+        };
+        if matches!(self.stat_ino(ino).await?, Some(stats) if stats.is_directory()) {
+            return Err(FsError::IsADirectory.into());
+        }
+
+        self.backend
+            .execute(
+                if self.filenames.case_insensitive {
+                    "DELETE FROM fs_dentry WHERE parent_ino = ? AND name_key = ?"
+                } else {
+                    "DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?"
+                },
+                vec![parent_ino.into(), self.match_name(name).into()],
+            )
+            .await?;
+        self.backend
+            .execute(
+                "UPDATE fs_inode SET nlink = nlink - 1 WHERE ino = ?",
+                vec![ino.into()],
+            )
+            .await?;
+
+        if self.get_link_count(ino).await? == 0 && !self.open_inodes.keep_if_open(ino) {
+            self.delete_inode(ino).await?;
+        }
+        Ok(true)
+    }
+
+    /// Rename the entry `from` to `to` within `parent_ino`, if present
+    ///
+    /// `to` must be free. Returns whether there was an entry. The caller holds
+    /// the write transaction.
+    async fn rename_entry_locked(&self, parent_ino: i64, from: &str, to: &str) -> Result<bool> {
+        let Some(ino) = self.lookup_child(parent_ino, from).await? else {
+            return Ok(false);
+        };
+        self.backend
+            .execute(
+                if self.filenames.case_insensitive {
+                    "UPDATE fs_dentry SET name = ?, name_key = ?
+                     WHERE parent_ino = ? AND name_key = ?"
+                } else {
+                    "UPDATE fs_dentry SET name = ?, name_key = ?
+                     WHERE parent_ino = ? AND name = ?"
+                },
+                vec![
+                    to.into(),
+                    self.match_name(to).into(),
+                    parent_ino.into(),
+                    self.match_name(from).into(),
+                ],
+            )
+            .await?;
+        let now = self.clock.unix_time();
+        self.backend
+            .execute(
+                "UPDATE fs_inode SET ctime = ? WHERE ino = ?",
+                vec![now.into(), ino.into()],
+            )
+            .await?;
+        Ok(true)
+    }
+
+    /// Write `data` at `offset`, or at the end of the file if `offset` is None
+    async fn write_at(&self, path: &str, offset: Option<u64>, data: &[u8]) -> Result<()> {
+        let path = self.normalize_path(path)?;
+        let components = self.split_path(&path)?;
 
         if components.is_empty() {
-            anyhow::bail!("Cannot write to root directory");
+            return Err(FsError::IsADirectory.into());
         }
 
         let parent_path = if components.len() == 1 {
@@ -1102,188 +2803,217 @@ impl AgentFS {
             format!("/{}", components[..components.len() - 1].join("/"))
         };
 
-        let parent_ino = self
-            .resolve_path(&parent_path)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+        let parent_ino = self.resolve_parent(&parent_path).await?;
 
         let name = components.last().unwrap();
 
-        self.conn.execute("BEGIN IMMEDIATE", ()).await?;
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
 
-        let result: Result<()> = async {
-            // Get or create the inode
-            let (ino, current_size) = if let Some(ino) = self.resolve_path(&path).await? {
-                // Get current file size
-                let mut stmt = self
-                    .conn
-                    .prepare_cached("SELECT size FROM fs_inode WHERE ino = ?")
-                    .await?;
-                let mut rows = stmt.query((ino,)).await?;
-                let size = if let Some(row) = rows.next().await? {
-                    row.get_value(0)
-                        .ok()
-                        .and_then(|v| v.as_integer().copied())
-                        .unwrap_or(0) as u64
-                } else {
-                    0
-                };
-                (ino, size)
-            } else {
-                // Create new inode
-                let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-                let mut stmt = self
-                    .conn
-                    .prepare(
-                        "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
-                        VALUES (?, 0, 0, 0, ?, ?, ?) RETURNING ino",
-                    )
-                    .await?;
-                let row = stmt
-                    .query_row((DEFAULT_FILE_MODE as i64, now, now, now))
-                    .await?;
+        let result = self
+            .write_at_locked(&path, parent_ino, name, offset, data)
+            .await;
 
-                let ino = row
-                    .get_value(0)
+        match result {
+            Ok(()) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Body of [`AgentFS::write_at`]; the caller holds the write transaction
+    async fn write_at_locked(
+        &self,
+        path: &str,
+        parent_ino: i64,
+        name: &str,
+        offset: Option<u64>,
+        data: &[u8],
+    ) -> Result<()> {
+        // Get or create the inode
+        let (ino, current_size) = if let Some(ino) = self.resolve_path(path).await? {
+            // Get current file size
+            let mut rows = self
+                .backend
+                .query("SELECT size FROM fs_inode WHERE ino = ?", vec![ino.into()])
+                .await?;
+            let size = if let Some(row) = rows.next().await? {
+                row.get_value(0)
                     .ok()
                     .and_then(|v| v.as_integer().copied())
-                    .ok_or_else(|| anyhow::anyhow!("Failed to get inode"))?;
-
-                // Create directory entry
-                let mut stmt = self
-                    .conn
-                    .prepare_cached(
-                        "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)",
-                    )
-                    .await?;
-                stmt.execute((name.as_str(), parent_ino, ino)).await?;
+                    .unwrap_or(0) as u64
+            } else {
+                0
+            };
+            (ino, size)
+        } else {
+            // Create new inode
+            let now = self.clock.unix_time();
+            let row = self
+                .backend
+                .query_one(
+                    "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
+                    VALUES (?, 0, 0, 0, ?, ?, ?) RETURNING ino",
+                    vec![
+                        (DEFAULT_FILE_MODE as i64).into(),
+                        now.into(),
+                        now.into(),
+                        now.into(),
+                    ],
+                )
+                .await?;
 
-                // Increment link count
-                let mut stmt = self
-                    .conn
-                    .prepare_cached("UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?")
-                    .await?;
-                stmt.execute((ino,)).await?;
+            let ino = row
+                .and_then(|row| row.get_value(0).ok())
+                .and_then(|v| v.as_integer().copied())
+                .ok_or_else(|| anyhow::anyhow!("Failed to get inode"))?;
+
+            // Create directory entry
+            self.backend
+                .execute(
+                    "INSERT INTO fs_dentry (name, parent_ino, ino, name_key) VALUES (?, ?, ?, ?)",
+                    vec![
+                        name.into(),
+                        parent_ino.into(),
+                        ino.into(),
+                        self.match_name(name).into(),
+                    ],
+                )
+                .await?;
 
-                (ino, 0)
-            };
+            // Increment link count
+            self.backend
+                .execute(
+                    "UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?",
+                    vec![ino.into()],
+                )
+                .await?;
 
-            // Handle empty writes - just update mtime
-            if data.is_empty() {
-                let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-                self.conn
-                    .execute("UPDATE fs_inode SET mtime = ? WHERE ino = ?", (now, ino))
-                    .await?;
-                return Ok(());
-            }
+            (ino, 0)
+        };
 
-            let chunk_size = self.chunk_size as u64;
-            let write_end = offset + data.len() as u64;
+        let op = if offset.is_some() {
+            JournalOp::Pwrite
+        } else {
+            JournalOp::Append
+        };
+        self.journal_locked(op, path, None, Some(data)).await?;
 
-            // Calculate affected chunk range
-            let start_chunk = offset / chunk_size;
-            let end_chunk = (write_end - 1) / chunk_size;
+        // Handle empty writes - just update mtime
+        if data.is_empty() {
+            let now = self.clock.unix_time();
+            self.backend
+                .execute(
+                    "UPDATE fs_inode SET mtime = ? WHERE ino = ?",
+                    vec![now.into(), ino.into()],
+                )
+                .await?;
+            return Ok(());
+        }
 
-            // Process each affected chunk
-            for chunk_idx in start_chunk..=end_chunk {
-                let chunk_start = chunk_idx * chunk_size;
+        let offset = offset.unwrap_or(current_size);
+        let chunk_size = self.chunk_size as u64;
+        let write_end = offset + data.len() as u64;
+        check_file_size(self.max_file_size, std::cmp::max(current_size, write_end))?;
 
-                // Calculate what part of data goes into this chunk
-                let data_start = if offset > chunk_start {
-                    (offset - chunk_start) as usize
-                } else {
-                    0
-                };
-                let data_end =
-                    std::cmp::min(chunk_size as usize, (write_end - chunk_start) as usize);
+        // Calculate affected chunk range
+        let start_chunk = offset / chunk_size;
+        let end_chunk = (write_end - 1) / chunk_size;
 
-                // Calculate what part of data to copy
-                let src_start = if chunk_start > offset {
-                    (chunk_start - offset) as usize
-                } else {
-                    0
-                };
-                let src_end = std::cmp::min(data.len(), src_start + (data_end - data_start));
+        // Process each affected chunk
+        for chunk_idx in start_chunk..=end_chunk {
+            let chunk_start = chunk_idx * chunk_size;
 
-                // Read existing chunk if we need to preserve some data
-                let needs_read = data_start > 0 || data_end < chunk_size as usize;
-                let mut chunk_data = if needs_read {
-                    let mut rows = self
-                        .conn
-                        .query(
-                            "SELECT data FROM fs_data WHERE ino = ? AND chunk_index = ?",
-                            (ino, chunk_idx as i64),
-                        )
-                        .await?;
-                    if let Some(row) = rows.next().await? {
-                        if let Ok(Value::Blob(data)) = row.get_value(0) {
-                            let mut v = data.clone();
-                            v.resize(chunk_size as usize, 0);
-                            v
-                        } else {
-                            vec![0u8; chunk_size as usize]
-                        }
-                    } else {
-                        vec![0u8; chunk_size as usize]
+            // Calculate what part of data goes into this chunk
+            let data_start = if offset > chunk_start {
+                (offset - chunk_start) as usize
+            } else {
+                0
+            };
+            let data_end = std::cmp::min(chunk_size as usize, (write_end - chunk_start) as usize);
+
+            // Calculate what part of data to copy
+            let src_start = if chunk_start > offset {
+                (chunk_start - offset) as usize
+            } else {
+                0
+            };
+            let src_end = std::cmp::min(data.len(), src_start + (data_end - data_start));
+
+            // Read existing chunk if we need to preserve some data
+            let needs_read = data_start > 0 || data_end < chunk_size as usize;
+            let mut chunk_data = if needs_read {
+                let mut rows = self
+                    .backend
+                    .query(
+                        "SELECT data, compressed FROM fs_data WHERE ino = ? AND chunk_index = ?",
+                        vec![ino.into(), (chunk_idx as i64).into()],
+                    )
+                    .await?;
+                match rows.next().await? {
+                    Some(row) => {
+                        let mut v = block_from_row(&row, 0, self.chunk_size)?.unwrap_or_default();
+                        v.resize(chunk_size as usize, 0);
+                        v
                     }
-                } else {
-                    vec![0u8; chunk_size as usize]
-                };
+                    None => vec![0u8; chunk_size as usize],
+                }
+            } else {
+                vec![0u8; chunk_size as usize]
+            };
 
-                // Copy the new data into the chunk
-                chunk_data[data_start..data_end].copy_from_slice(&data[src_start..src_end]);
+            // Copy the new data into the chunk
+            chunk_data[data_start..data_end].copy_from_slice(&data[src_start..src_end]);
 
-                // Trim trailing zeros for the last chunk
-                let actual_len = if chunk_idx == end_chunk {
-                    let file_end_in_chunk = (write_end - chunk_start) as usize;
-                    let old_end_in_chunk = if current_size > chunk_start {
-                        std::cmp::min((current_size - chunk_start) as usize, chunk_size as usize)
-                    } else {
-                        0
-                    };
-                    std::cmp::max(file_end_in_chunk, old_end_in_chunk)
+            // Trim trailing zeros for the last chunk
+            let actual_len = if chunk_idx == end_chunk {
+                let file_end_in_chunk = (write_end - chunk_start) as usize;
+                let old_end_in_chunk = if current_size > chunk_start {
+                    std::cmp::min((current_size - chunk_start) as usize, chunk_size as usize)
                 } else {
-                    chunk_size as usize
+                    0
                 };
+                std::cmp::max(file_end_in_chunk, old_end_in_chunk)
+            } else {
+                chunk_size as usize
+            };
 
-                // Write the chunk - delete existing then insert
-                self.conn
-                    .execute(
-                        "DELETE FROM fs_data WHERE ino = ? AND chunk_index = ?",
-                        (ino, chunk_idx as i64),
-                    )
-                    .await?;
-                self.conn
-                    .execute(
-                        "INSERT INTO fs_data (ino, chunk_index, data) VALUES (?, ?, ?)",
-                        (ino, chunk_idx as i64, &chunk_data[..actual_len]),
-                    )
-                    .await?;
-            }
-
-            // Update size and mtime
-            let new_size = std::cmp::max(current_size, write_end);
-            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-            let mut stmt = self
-                .conn
-                .prepare_cached("UPDATE fs_inode SET size = ?, mtime = ? WHERE ino = ?")
+            // Write the chunk - delete existing then insert
+            self.backend
+                .execute(
+                    "DELETE FROM fs_data WHERE ino = ? AND chunk_index = ?",
+                    vec![ino.into(), (chunk_idx as i64).into()],
+                )
+                .await?;
+            let (blob, compressed) = encode_block(&chunk_data[..actual_len], self.compression)?;
+            self.backend
+                .execute(
+                    "INSERT INTO fs_data (ino, chunk_index, data, compressed) VALUES (?, ?, ?, ?)",
+                    vec![
+                        ino.into(),
+                        (chunk_idx as i64).into(),
+                        blob,
+                        compressed.into(),
+                    ],
+                )
                 .await?;
-            stmt.execute((new_size as i64, now, ino)).await?;
-
-            Ok(())
         }
-        .await;
 
-        match result {
-            Ok(()) => {
-                self.conn.execute("COMMIT", ()).await?;
-                Ok(())
-            }
-            Err(e) => {
-                let _ = self.conn.execute("ROLLBACK", ()).await;
-                Err(e)
-            }
-        }
+        // Update size and mtime
+        let new_size = std::cmp::max(current_size, write_end);
+        let now = self.clock.unix_time();
+        self.backend
+            .execute(
+                "UPDATE fs_inode SET size = ?, mtime = ? WHERE ino = ?",
+                vec![(new_size as i64).into(), now.into(), ino.into()],
+            )
+            .await?;
+
+        Ok(())
     }
 
     /// Truncate a file to a specific size.
@@ -1292,18 +3022,14 @@ impl AgentFS {
     /// - Shrinking: deletes chunks beyond new size, truncates the last chunk if needed
     /// - Extending: pads with zeros up to the new size
     pub async fn truncate(&self, path: &str, new_size: u64) -> Result<()> {
-        let path = self.normalize_path(path);
-        let ino = self
-            .resolve_path(&path)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("File not found"))?;
+        let path = self.normalize_path(path)?;
+        let ino = self.resolve_path(&path).await?.ok_or(FsError::NotFound)?;
 
         // Get current size
-        let mut stmt = self
-            .conn
-            .prepare_cached("SELECT size FROM fs_inode WHERE ino = ?")
+        let mut rows = self
+            .backend
+            .query("SELECT size FROM fs_inode WHERE ino = ?", vec![ino.into()])
             .await?;
-        let mut rows = stmt.query((ino,)).await?;
         let current_size = if let Some(row) = rows.next().await? {
             row.get_value(0)
                 .ok()
@@ -1313,27 +3039,29 @@ impl AgentFS {
             0
         };
 
+        if new_size > current_size {
+            check_file_size(self.max_file_size, new_size)?;
+        }
+
         let chunk_size = self.chunk_size as u64;
 
-        self.conn.execute("BEGIN IMMEDIATE", ()).await?;
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
 
         let result: Result<()> = async {
             if new_size == 0 {
                 // Special case: truncate to zero - just delete all chunks
-                let mut stmt = self
-                    .conn
-                    .prepare_cached("DELETE FROM fs_data WHERE ino = ?")
+                self.backend
+                    .execute("DELETE FROM fs_data WHERE ino = ?", vec![ino.into()])
                     .await?;
-                stmt.execute((ino,)).await?;
             } else if new_size < current_size {
                 // Shrinking: delete excess chunks and truncate last chunk if needed
                 let last_chunk_idx = (new_size - 1) / chunk_size;
 
                 // Delete all chunks beyond the last one we need
-                self.conn
+                self.backend
                     .execute(
                         "DELETE FROM fs_data WHERE ino = ? AND chunk_index > ?",
-                        (ino, last_chunk_idx as i64),
+                        vec![ino.into(), (last_chunk_idx as i64).into()],
                     )
                     .await?;
 
@@ -1343,21 +3071,30 @@ impl AgentFS {
                 // If the last chunk needs to be truncated (not a full chunk),
                 // read it, truncate, and rewrite
                 if end_in_last_chunk < chunk_size {
-                    let mut stmt = self
-                        .conn
-                        .prepare_cached("SELECT data FROM fs_data WHERE ino = ? AND chunk_index = ?")
+                    let mut rows = self
+                        .backend
+                        .query(
+                            "SELECT data, compressed FROM fs_data WHERE ino = ? AND chunk_index = ?",
+                            vec![ino.into(), (last_chunk_idx as i64).into()],
+                        )
                         .await?;
-                    let mut rows = stmt.query((ino, last_chunk_idx as i64)).await?;
 
                     if let Some(row) = rows.next().await? {
-                        if let Ok(Value::Blob(chunk_data)) = row.get_value(0) {
+                        if let Some(chunk_data) = block_from_row(&row, 0, self.chunk_size)? {
                             if chunk_data.len() > end_in_last_chunk as usize {
                                 let truncated = &chunk_data[..end_in_last_chunk as usize];
-                                let mut stmt = self
-                                    .conn
-                                    .prepare_cached("UPDATE fs_data SET data = ? WHERE ino = ? AND chunk_index = ?")
+                                let (blob, compressed) = encode_block(truncated, self.compression)?;
+                                self.backend
+                                    .execute(
+                                        "UPDATE fs_data SET data = ?, compressed = ? WHERE ino = ? AND chunk_index = ?",
+                                        vec![
+                                            blob,
+                                            compressed.into(),
+                                            ino.into(),
+                                            (last_chunk_idx as i64).into(),
+                                        ],
+                                    )
                                     .await?;
-                                stmt.execute((truncated, ino, last_chunk_idx as i64)).await?;
                             }
                         }
                     }
@@ -1373,14 +3110,16 @@ impl AgentFS {
 
                 // Pad the last existing chunk with zeros if it's not full
                 if let Some(last_idx) = last_existing_chunk {
-                    let mut stmt = self
-                        .conn
-                        .prepare_cached("SELECT data FROM fs_data WHERE ino = ? AND chunk_index = ?")
+                    let mut rows = self
+                        .backend
+                        .query(
+                            "SELECT data, compressed FROM fs_data WHERE ino = ? AND chunk_index = ?",
+                            vec![ino.into(), (last_idx as i64).into()],
+                        )
                         .await?;
-                    let mut rows = stmt.query((ino, last_idx as i64)).await?;
 
                     if let Some(row) = rows.next().await? {
-                        if let Ok(Value::Blob(chunk_data)) = row.get_value(0) {
+                        if let Some(chunk_data) = block_from_row(&row, 0, self.chunk_size)? {
                             let current_chunk_len = chunk_data.len();
                             let needed_len = if last_idx == last_new_chunk {
                                 // Last existing chunk is also the last new chunk
@@ -1391,13 +3130,20 @@ impl AgentFS {
                             };
 
                             if needed_len > current_chunk_len {
-                                let mut padded = chunk_data.clone();
+                                let mut padded = chunk_data;
                                 padded.resize(needed_len, 0);
-                                let mut stmt = self
-                                    .conn
-                                    .prepare_cached("UPDATE fs_data SET data = ? WHERE ino = ? AND chunk_index = ?")
+                                let (blob, compressed) = encode_block(&padded, self.compression)?;
+                                self.backend
+                                    .execute(
+                                        "UPDATE fs_data SET data = ?, compressed = ? WHERE ino = ? AND chunk_index = ?",
+                                        vec![
+                                            blob,
+                                            compressed.into(),
+                                            ino.into(),
+                                            (last_idx as i64).into(),
+                                        ],
+                                    )
                                     .await?;
-                                stmt.execute((&padded[..], ino, last_idx as i64)).await?;
                             }
                         }
                     }
@@ -1412,10 +3158,16 @@ impl AgentFS {
                         chunk_size as usize
                     };
                     let zeros = vec![0u8; chunk_len];
-                    self.conn
+                    let (blob, compressed) = encode_block(&zeros, self.compression)?;
+                    self.backend
                         .execute(
-                            "INSERT INTO fs_data (ino, chunk_index, data) VALUES (?, ?, ?)",
-                            (ino, chunk_idx as i64, &zeros[..]),
+                            "INSERT INTO fs_data (ino, chunk_index, data, compressed) VALUES (?, ?, ?, ?)",
+                            vec![
+                                ino.into(),
+                                (chunk_idx as i64).into(),
+                                blob,
+                                compressed.into(),
+                            ],
                         )
                         .await?;
                 }
@@ -1423,24 +3175,26 @@ impl AgentFS {
             // else: new_size == current_size, nothing to do for data
 
             // Update size and mtime
-            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
-            let mut stmt = self
-                .conn
-                .prepare_cached("UPDATE fs_inode SET size = ?, mtime = ? WHERE ino = ?")
+            let now = self.clock.unix_time();
+            self.backend
+                .execute(
+                    "UPDATE fs_inode SET size = ?, mtime = ? WHERE ino = ?",
+                    vec![(new_size as i64).into(), now.into(), ino.into()],
+                )
                 .await?;
-            stmt.execute((new_size as i64, now, ino)).await?;
 
-            Ok(())
+            self.journal_entry_locked(JournalOp::Truncate, &path, None, Some(new_size), None)
+                .await
         }
         .await;
 
         match result {
             Ok(()) => {
-                self.conn.execute("COMMIT", ()).await?;
+                self.backend.execute("COMMIT", Vec::new()).await?;
                 Ok(())
             }
             Err(e) => {
-                let _ = self.conn.execute("ROLLBACK", ()).await;
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
                 Err(e)
             }
         }
@@ -1454,10 +3208,10 @@ impl AgentFS {
         };
 
         let mut rows = self
-            .conn
+            .backend
             .query(
                 "SELECT name FROM fs_dentry WHERE parent_ino = ? ORDER BY name",
-                (ino,),
+                vec![ino.into()],
             )
             .await?;
 
@@ -1482,6 +3236,40 @@ impl AgentFS {
         Ok(Some(entries))
     }
 
+    /// List up to `limit` directory entries that sort after `after`
+    ///
+    /// Only the requested page is read from the database, so large directories
+    /// can be walked without loading every entry at once.
+    pub async fn readdir_paged(
+        &self,
+        path: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Option<Vec<String>>> {
+        let ino = match self.resolve_path(path).await? {
+            Some(ino) => ino,
+            None => return Ok(None),
+        };
+
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT name FROM fs_dentry WHERE parent_ino = ? AND name > ? ORDER BY name LIMIT ?",
+                vec![ino.into(), after.unwrap_or("").into(), limit.into()],
+            )
+            .await?;
+
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next().await? {
+            if let Ok(Value::Text(name)) = row.get_value(0) {
+                entries.push(name);
+            }
+        }
+
+        Ok(Some(entries))
+    }
+
     /// List directory contents with full statistics (optimized batch query)
     ///
     /// Returns entries with their stats in a single JOIN query, avoiding N+1 queries.
@@ -1493,14 +3281,14 @@ impl AgentFS {
 
         // Single JOIN query to get all entry names and their stats (including link count)
         let mut rows = self
-            .conn
+            .backend
             .query(
                 "SELECT d.name, i.ino, i.mode, i.nlink, i.uid, i.gid, i.size, i.atime, i.mtime, i.ctime
                  FROM fs_dentry d
                  JOIN fs_inode i ON d.ino = i.ino
                  WHERE d.parent_ino = ?
                  ORDER BY d.name",
-                (ino,),
+                vec![ino.into()],
             )
             .await?;
 
@@ -1582,11 +3370,27 @@ impl AgentFS {
 
     /// Create a symbolic link
     pub async fn symlink(&self, target: &str, linkpath: &str) -> Result<()> {
-        let linkpath = self.normalize_path(linkpath);
-        let components = self.split_path(&linkpath);
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        match self.symlink_locked(target, linkpath).await {
+            Ok(()) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Body of [`AgentFS::symlink`]; the caller holds the write transaction
+    async fn symlink_locked(&self, target: &str, linkpath: &str) -> Result<()> {
+        let linkpath = self.normalize_path(linkpath)?;
+        let components = self.split_path(&linkpath)?;
 
         if components.is_empty() {
-            anyhow::bail!("Cannot create symlink at root");
+            return Err(FsError::AlreadyExists.into());
         }
 
         // Get parent directory
@@ -1596,67 +3400,68 @@ impl AgentFS {
             format!("/{}", components[..components.len() - 1].join("/"))
         };
 
-        let parent_ino = self
-            .resolve_path(&parent_path)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+        let parent_ino = self.resolve_parent(&parent_path).await?;
 
         let name = components.last().unwrap();
 
         // Check if entry already exists (single query using parent_ino we already have)
         if self.lookup_child(parent_ino, name).await?.is_some() {
-            anyhow::bail!("Path already exists");
+            return Err(FsError::AlreadyExists.into());
         }
 
         // Create inode for symlink
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs() as i64;
+        let now = self.clock.unix_time();
 
         let mode = S_IFLNK | 0o777; // Symlinks typically have 777 permissions
         let size = target.len() as i64;
 
-        let mut stmt = self
-            .conn
-            .prepare(
+        let row = self
+            .backend
+            .query_one(
                 "INSERT INTO fs_inode (mode, uid, gid, size, atime, mtime, ctime)
                  VALUES (?, 0, 0, ?, ?, ?, ?) RETURNING ino",
+                vec![mode.into(), size.into(), now.into(), now.into(), now.into()],
             )
             .await?;
-        let row = stmt.query_row((mode, size, now, now, now)).await?;
 
         // Get the newly created inode
         let ino = row
-            .get_value(0)
-            .ok()
+            .and_then(|row| row.get_value(0).ok())
             .and_then(|v| v.as_integer().copied())
             .unwrap_or(0);
 
         // Store symlink target
-        self.conn
+        self.backend
             .execute(
                 "INSERT INTO fs_symlink (ino, target) VALUES (?, ?)",
-                (ino, target),
+                vec![ino.into(), target.into()],
             )
             .await?;
 
         // Create directory entry
-        self.conn
+        self.backend
             .execute(
-                "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)",
-                (name.as_str(), parent_ino, ino),
+                "INSERT INTO fs_dentry (name, parent_ino, ino, name_key) VALUES (?, ?, ?, ?)",
+                vec![
+                    name.as_str().into(),
+                    parent_ino.into(),
+                    ino.into(),
+                    self.match_name(name).into(),
+                ],
             )
             .await?;
 
         // Increment link count
-        self.conn
+        self.backend
             .execute(
                 "UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?",
-                (ino,),
+                vec![ino.into()],
             )
             .await?;
 
+        self.journal_locked(JournalOp::Symlink, &linkpath, Some(target), None)
+            .await?;
+
         // Populate dentry cache
         self.dentry_cache.insert(parent_ino, name, ino);
 
@@ -1669,24 +3474,40 @@ impl AgentFS {
     /// Both paths will share the same file data and metadata (except for the name).
     /// The link count (nlink) of the inode is incremented.
     pub async fn link(&self, oldpath: &str, newpath: &str) -> Result<()> {
-        let oldpath = self.normalize_path(oldpath);
-        let newpath = self.normalize_path(newpath);
-        let components = self.split_path(&newpath);
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        match self.link_locked(oldpath, newpath).await {
+            Ok(()) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Body of [`AgentFS::link`]; the caller holds the write transaction
+    async fn link_locked(&self, oldpath: &str, newpath: &str) -> Result<()> {
+        let oldpath = self.normalize_path(oldpath)?;
+        let newpath = self.normalize_path(newpath)?;
+        let components = self.split_path(&newpath)?;
 
         if components.is_empty() {
-            anyhow::bail!("Cannot create hard link at root");
+            return Err(FsError::AlreadyExists.into());
         }
 
         // Resolve old path to get its inode
         let ino = self
             .resolve_path(&oldpath)
             .await?
-            .ok_or_else(|| anyhow::anyhow!("Source path does not exist"))?;
+            .ok_or(FsError::NotFound)?;
 
         // Check if source is a directory (hard links to directories are not allowed)
         let mut rows = self
-            .conn
-            .query("SELECT mode FROM fs_inode WHERE ino = ?", (ino,))
+            .backend
+            .query("SELECT mode FROM fs_inode WHERE ino = ?", vec![ino.into()])
             .await?;
 
         if let Some(row) = rows.next().await? {
@@ -1697,10 +3518,10 @@ impl AgentFS {
                 .unwrap_or(0) as u32;
 
             if (mode & S_IFMT) == super::S_IFDIR {
-                anyhow::bail!("Cannot create hard link to directory");
+                return Err(FsError::IsADirectory.into());
             }
         } else {
-            anyhow::bail!("Source inode not found");
+            return Err(FsError::NotFound.into());
         }
 
         // Get parent directory of new path
@@ -1710,34 +3531,39 @@ impl AgentFS {
             format!("/{}", components[..components.len() - 1].join("/"))
         };
 
-        let parent_ino = self
-            .resolve_path(&parent_path)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+        let parent_ino = self.resolve_parent(&parent_path).await?;
 
         let name = components.last().unwrap();
 
         // Check if new path already exists (single query using parent_ino we already have)
         if self.lookup_child(parent_ino, name).await?.is_some() {
-            anyhow::bail!("Path already exists");
+            return Err(FsError::AlreadyExists.into());
         }
 
         // Create directory entry pointing to the same inode
-        self.conn
+        self.backend
             .execute(
-                "INSERT INTO fs_dentry (name, parent_ino, ino) VALUES (?, ?, ?)",
-                (name.as_str(), parent_ino, ino),
+                "INSERT INTO fs_dentry (name, parent_ino, ino, name_key) VALUES (?, ?, ?, ?)",
+                vec![
+                    name.as_str().into(),
+                    parent_ino.into(),
+                    ino.into(),
+                    self.match_name(name).into(),
+                ],
             )
             .await?;
 
         // Increment link count
-        self.conn
+        self.backend
             .execute(
                 "UPDATE fs_inode SET nlink = nlink + 1 WHERE ino = ?",
-                (ino,),
+                vec![ino.into()],
             )
             .await?;
 
+        self.journal_locked(JournalOp::Link, &oldpath, Some(&newpath), None)
+            .await?;
+
         // Populate dentry cache
         self.dentry_cache.insert(parent_ino, name, ino);
 
@@ -1746,7 +3572,7 @@ impl AgentFS {
 
     /// Read the target of a symbolic link
     pub async fn readlink(&self, path: &str) -> Result<Option<String>> {
-        let path = self.normalize_path(path);
+        let path = self.normalize_path(path)?;
 
         let ino = match self.resolve_path(&path).await? {
             Some(ino) => ino,
@@ -1755,8 +3581,8 @@ impl AgentFS {
 
         // Check if it's a symlink by querying the inode
         let mut rows = self
-            .conn
-            .query("SELECT mode FROM fs_inode WHERE ino = ?", (ino,))
+            .backend
+            .query("SELECT mode FROM fs_inode WHERE ino = ?", vec![ino.into()])
             .await?;
 
         if let Some(row) = rows.next().await? {
@@ -1768,7 +3594,7 @@ impl AgentFS {
 
             // Check if it's a symlink
             if (mode & S_IFMT) != S_IFLNK {
-                anyhow::bail!("Not a symbolic link");
+                return Err(FsError::NotASymlink.into());
             }
         } else {
             return Ok(None);
@@ -1776,8 +3602,11 @@ impl AgentFS {
 
         // Read target from fs_symlink table
         let mut rows = self
-            .conn
-            .query("SELECT target FROM fs_symlink WHERE ino = ?", (ino,))
+            .backend
+            .query(
+                "SELECT target FROM fs_symlink WHERE ino = ?",
+                vec![ino.into()],
+            )
             .await?;
 
         if let Some(row) = rows.next().await? {
@@ -1796,29 +3625,48 @@ impl AgentFS {
     }
 
     /// Remove a file or empty directory
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(path = path)))]
     pub async fn remove(&self, path: &str) -> Result<()> {
-        let path = self.normalize_path(path);
-        let components = self.split_path(&path);
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        match self.remove_locked(path).await {
+            Ok(()) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                self.reclaim_closed_inodes().await;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Remove a file or empty directory
+    ///
+    /// The caller holds the write transaction.
+    async fn remove_locked(&self, path: &str) -> Result<()> {
+        let path = self.normalize_path(path)?;
+        let components = self.split_path(&path)?;
 
         if components.is_empty() {
-            anyhow::bail!("Cannot remove root directory");
+            return Err(FsError::RootOperation.into());
         }
 
-        let ino = self
-            .resolve_path(&path)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Path does not exist"))?;
+        let ino = self.resolve_path(&path).await?.ok_or(FsError::NotFound)?;
 
         if ino == ROOT_INO {
-            anyhow::bail!("Cannot remove root directory");
+            return Err(FsError::RootOperation.into());
         }
 
         // Check if directory is empty
-        let mut stmt = self
-            .conn
-            .prepare_cached("SELECT COUNT(*) FROM fs_dentry WHERE parent_ino = ?")
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT COUNT(*) FROM fs_dentry WHERE parent_ino = ?",
+                vec![ino.into()],
+            )
             .await?;
-        let mut rows = stmt.query((ino,)).await?;
 
         if let Some(row) = rows.next().await? {
             let count = row
@@ -1827,7 +3675,7 @@ impl AgentFS {
                 .and_then(|v| v.as_integer().copied())
                 .unwrap_or(0);
             if count > 0 {
-                anyhow::bail!("Directory not empty");
+                return Err(FsError::NotEmpty.into());
             }
         }
 
@@ -1838,76 +3686,263 @@ impl AgentFS {
             format!("/{}", components[..components.len() - 1].join("/"))
         };
 
-        let parent_ino = self
-            .resolve_path(&parent_path)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Parent directory does not exist"))?;
+        let parent_ino = self.resolve_parent(&parent_path).await?;
 
         let name = components.last().unwrap();
 
         // Delete the specific directory entry (not all entries pointing to this inode)
-        let mut stmt = self
-            .conn
-            .prepare_cached("DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?")
+        self.backend
+            .execute(
+                if self.filenames.case_insensitive {
+                    "DELETE FROM fs_dentry WHERE parent_ino = ? AND name_key = ?"
+                } else {
+                    "DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?"
+                },
+                vec![parent_ino.into(), self.match_name(name).into()],
+            )
             .await?;
-        stmt.execute((parent_ino, name.as_str())).await?;
 
         // Invalidate cache for this entry
         self.dentry_cache.remove(parent_ino, name);
 
         // Decrement link count
-        let mut stmt = self
-            .conn
-            .prepare_cached("UPDATE fs_inode SET nlink = nlink - 1 WHERE ino = ?")
+        self.backend
+            .execute(
+                "UPDATE fs_inode SET nlink = nlink - 1 WHERE ino = ?",
+                vec![ino.into()],
+            )
             .await?;
-        stmt.execute((ino,)).await?;
 
         // Check if this was the last link to the inode
         let link_count = self.get_link_count(ino).await?;
-        if link_count == 0 {
-            // Manually handle cascading deletes since we don't use foreign keys
-            // Delete data blocks
-            let mut stmt = self
-                .conn
-                .prepare_cached("DELETE FROM fs_data WHERE ino = ?")
-                .await?;
-            stmt.execute((ino,)).await?;
+        if link_count == 0 && !self.open_inodes.keep_if_open(ino) {
+            self.delete_inode(ino).await?;
+        }
 
-            // Delete symlink if exists
-            let mut stmt = self
-                .conn
-                .prepare_cached("DELETE FROM fs_symlink WHERE ino = ?")
-                .await?;
-            stmt.execute((ino,)).await?;
+        self.journal_locked(JournalOp::Remove, &path, None, None)
+            .await
+    }
 
-            // Delete inode
-            let mut stmt = self
-                .conn
-                .prepare_cached("DELETE FROM fs_inode WHERE ino = ?")
-                .await?;
-            stmt.execute((ino,)).await?;
+    /// Remove every file matching a glob pattern
+    ///
+    /// See [`Glob`] for the pattern syntax; symlinks are removed, not followed.
+    /// Matching directories are skipped unless `directories` is set, in which
+    /// case they are removed with everything below them. All removals happen
+    /// in one transaction, so if any of them fails nothing is removed.
+    ///
+    /// Returns the number of matching paths removed.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(pattern = pattern)))]
+    pub async fn remove_glob(&self, pattern: &str, directories: bool) -> Result<u64> {
+        let glob = Glob::new(pattern)?;
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        match self.remove_glob_locked(&glob, directories).await {
+            Ok(removed) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                self.reclaim_closed_inodes().await;
+                Ok(removed)
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Remove every path matching `glob`
+    ///
+    /// The caller holds the write transaction.
+    async fn remove_glob_locked(&self, glob: &Glob, directories: bool) -> Result<u64> {
+        let mut removed = 0;
+        let mut removed_dirs: Vec<String> = Vec::new();
+        // Sorted, so a directory always comes before the paths below it
+        for (path, is_dir) in self.glob_locked(glob).await? {
+            if is_dir && !directories {
+                continue;
+            }
+            if removed_dirs
+                .iter()
+                .any(|dir| path.starts_with(&format!("{}/", dir)))
+            {
+                continue;
+            }
+            if is_dir {
+                self.remove_tree_locked(&path).await?;
+                removed_dirs.push(path);
+            } else {
+                self.remove_locked(&path).await?;
+            }
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// Paths matching `glob`, excluding the root, mapped to whether they are directories
+    ///
+    /// The caller holds the write transaction.
+    async fn glob_locked(&self, glob: &Glob) -> Result<BTreeMap<String, bool>> {
+        let patterns = glob.components();
+        let mut matches = BTreeMap::new();
+        let mut pending = vec![("/".to_string(), ROOT_INO, true, 0)];
+
+        while let Some((path, ino, is_dir, next)) = pending.pop() {
+            let Some(pattern) = patterns.get(next) else {
+                if ino != ROOT_INO {
+                    matches.insert(path, is_dir);
+                }
+                continue;
+            };
+            // `**` matches no components here, or one more component below
+            if pattern == "**" {
+                pending.push((path.clone(), ino, is_dir, next + 1));
+            }
+            if !is_dir {
+                continue;
+            }
+
+            for (name, child_ino, mode) in self.children_locked(ino).await? {
+                let child_path = if path == "/" {
+                    format!("/{}", name)
+                } else {
+                    format!("{}/{}", path, name)
+                };
+                let child_is_dir = (mode & S_IFMT) == S_IFDIR;
+                if pattern == "**" {
+                    pending.push((child_path, child_ino, child_is_dir, next));
+                } else if self.filenames.case_insensitive {
+                    if component_match(&fold_name(pattern), &fold_name(&name)) {
+                        pending.push((child_path, child_ino, child_is_dir, next + 1));
+                    }
+                } else if component_match(pattern, &name) {
+                    pending.push((child_path, child_ino, child_is_dir, next + 1));
+                }
+            }
+        }
+
+        Ok(matches)
+    }
+
+    /// Names, inode numbers and modes of the entries in a directory
+    async fn children_locked(&self, ino: i64) -> Result<Vec<(String, i64, u32)>> {
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT d.name, d.ino, i.mode
+                 FROM fs_dentry d
+                 JOIN fs_inode i ON d.ino = i.ino
+                 WHERE d.parent_ino = ?",
+                vec![ino.into()],
+            )
+            .await?;
+
+        let mut children = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let name = match row.get_value(0)? {
+                Value::Text(name) if !name.is_empty() => name,
+                _ => continue,
+            };
+            let child_ino = row
+                .get_value(1)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0);
+            let mode = row
+                .get_value(2)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u32;
+            children.push((name, child_ino, mode));
+        }
+        Ok(children)
+    }
+
+    /// Remove `path` and, if it is a directory, everything below it
+    ///
+    /// The caller holds the write transaction.
+    async fn remove_tree_locked(&self, path: &str) -> Result<()> {
+        let ino = self.resolve_path(path).await?.ok_or(FsError::NotFound)?;
+
+        for (name, _, mode) in self.children_locked(ino).await? {
+            let child = format!("{}/{}", path.trim_end_matches('/'), name);
+            if (mode & S_IFMT) == S_IFDIR {
+                Box::pin(self.remove_tree_locked(&child)).await?;
+            } else {
+                self.remove_locked(&child).await?;
+            }
         }
 
+        self.remove_locked(path).await
+    }
+
+    /// Delete the rows of an inode that has no links left
+    async fn delete_inode(&self, ino: i64) -> Result<()> {
+        // Manually handle cascading deletes since we don't use foreign keys
+        // Delete data blocks
+        self.backend
+            .execute("DELETE FROM fs_data WHERE ino = ?", vec![ino.into()])
+            .await?;
+
+        // Delete symlink if exists
+        self.backend
+            .execute("DELETE FROM fs_symlink WHERE ino = ?", vec![ino.into()])
+            .await?;
+
+        // Delete inode
+        self.backend
+            .execute("DELETE FROM fs_inode WHERE ino = ?", vec![ino.into()])
+            .await?;
         Ok(())
     }
 
+    /// Delete inodes that were unlinked while open and have since been closed
+    ///
+    /// Runs after the operation that triggered it has committed, so a failure
+    /// only postpones the cleanup to the next call. Inodes that have links
+    /// again, because the unlink was rolled back, are left alone.
+    async fn reclaim_closed_inodes(&self) {
+        let closed = self.open_inodes.take_closed();
+        for (i, &ino) in closed.iter().enumerate() {
+            let result = match self.get_link_count(ino).await {
+                Ok(0) => self.delete_inode(ino).await,
+                Ok(_) => Ok(()),
+                Err(e) => Err(e),
+            };
+            if result.is_err() {
+                self.open_inodes.requeue(&closed[i..]);
+                return;
+            }
+        }
+    }
+
     /// Change file mode/permissions.
     ///
     /// Only modifies the permission bits (lower 12 bits), preserving the file type.
     pub async fn chmod(&self, path: &str, mode: u32) -> Result<()> {
-        let path = self.normalize_path(path);
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        match self.chmod_locked(path, mode).await {
+            Ok(()) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                Err(e)
+            }
+        }
+    }
 
-        let ino = self
-            .resolve_path(&path)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("Path does not exist"))?;
+    /// Body of [`AgentFS::chmod`]; the caller holds the write transaction
+    async fn chmod_locked(&self, path: &str, mode: u32) -> Result<()> {
+        let path = self.normalize_path(path)?;
+
+        let ino = self.resolve_path(&path).await?.ok_or(FsError::NotFound)?;
 
         // Get current mode to preserve file type bits
-        let mut stmt = self
-            .conn
-            .prepare_cached("SELECT mode FROM fs_inode WHERE ino = ?")
+        let mut rows = self
+            .backend
+            .query("SELECT mode FROM fs_inode WHERE ino = ?", vec![ino.into()])
             .await?;
-        let mut rows = stmt.query((ino,)).await?;
 
         let current_mode = if let Some(row) = rows.next().await? {
             row.get_value(0)
@@ -1915,27 +3950,77 @@ impl AgentFS {
                 .and_then(|v| v.as_integer().copied())
                 .unwrap_or(0) as u32
         } else {
-            anyhow::bail!("Inode not found");
+            return Err(FsError::NotFound.into());
         };
 
         // Preserve file type bits (upper bits), replace permission bits (lower 12 bits)
-        let new_mode = (current_mode & S_IFMT) | (mode & 0o7777);
+        let new_mode = super::mode::with_permissions(current_mode, mode);
 
-        let mut stmt = self
-            .conn
-            .prepare_cached("UPDATE fs_inode SET mode = ? WHERE ino = ?")
+        let now = self.clock.unix_time();
+        self.backend
+            .execute(
+                "UPDATE fs_inode SET mode = ?, ctime = ? WHERE ino = ?",
+                vec![(new_mode as i64).into(), now.into(), ino.into()],
+            )
             .await?;
-        stmt.execute((new_mode as i64, ino)).await?;
 
-        Ok(())
+        self.journal_locked(JournalOp::Chmod, &path, None, None)
+            .await
+    }
+
+    /// Change file ownership.
+    ///
+    /// `None` leaves the uid or gid unchanged.
+    pub async fn chown(&self, path: &str, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        match self.chown_locked(path, uid, gid).await {
+            Ok(()) => {
+                self.backend.execute("COMMIT", Vec::new()).await?;
+                Ok(())
+            }
+            Err(e) => {
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Body of [`AgentFS::chown`]; the caller holds the write transaction
+    async fn chown_locked(&self, path: &str, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        let path = self.normalize_path(path)?;
+
+        let ino = self.resolve_path(&path).await?.ok_or(FsError::NotFound)?;
+
+        let now = self.clock.unix_time();
+        self.backend
+            .execute(
+                "UPDATE fs_inode SET uid = COALESCE(?, uid), gid = COALESCE(?, gid), ctime = ?
+                 WHERE ino = ?",
+                vec![
+                    uid.map(i64::from).into(),
+                    gid.map(i64::from).into(),
+                    now.into(),
+                    ino.into(),
+                ],
+            )
+            .await?;
+
+        self.journal_locked(JournalOp::Chown, &path, None, None)
+            .await
     }
 
     /// Rename/move a file or directory.
     ///
     /// This operation is atomic - either all changes succeed or none do.
+    /// Renaming over an existing file swaps the destination entry to the new
+    /// inode in one statement, so a reader of `to` sees either the old or the
+    /// new file, never neither. Files already open on the replaced inode keep
+    /// reading its old contents, as on POSIX filesystems.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(from = from, to = to)))]
     pub async fn rename(&self, from: &str, to: &str) -> Result<()> {
-        let from_path = self.normalize_path(from);
-        let to_path = self.normalize_path(to);
+        let from_path = self.normalize_path(from)?;
+        let to_path = self.normalize_path(to)?;
 
         // Cannot rename root
         if from_path == "/" {
@@ -1960,7 +4045,7 @@ impl AgentFS {
         }
 
         // Parse source path to get parent and name
-        let from_components = self.split_path(&from_path);
+        let from_components = self.split_path(&from_path)?;
         let src_name = from_components.last().ok_or(FsError::InvalidPath)?;
         let src_parent_path = if from_components.len() == 1 {
             "/".to_string()
@@ -1976,7 +4061,7 @@ impl AgentFS {
             .ok_or(FsError::NotFound)?;
 
         // Parse destination path to get parent and name
-        let to_components = self.split_path(&to_path);
+        let to_components = self.split_path(&to_path)?;
         if to_components.is_empty() {
             return Err(FsError::RootOperation.into());
         }
@@ -1995,11 +4080,22 @@ impl AgentFS {
         let src_name = src_name.clone();
         let dst_name = dst_name.clone();
 
-        self.conn.execute("BEGIN IMMEDIATE", ()).await?;
+        self.backend.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+
+        // With case-insensitive names, renaming to a differently-cased version of
+        // the same name only changes the stored name.
+        let case_only_rename = self.filenames.case_insensitive
+            && src_parent_ino == dst_parent_ino
+            && fold_name(&src_name) == fold_name(&dst_name);
 
         let result: Result<()> = async {
             // Check if destination exists (inside transaction for atomicity)
-            if let Some(dst_ino) = self.resolve_path(&to_path).await? {
+            let dst_ino = if case_only_rename {
+                None
+            } else {
+                self.resolve_path(&to_path).await?
+            };
+            if let Some(dst_ino) = dst_ino {
                 let dst_stats = self.stat(&to_path).await?.ok_or(FsError::NotFound)?;
 
                 // Can't replace directory with non-directory
@@ -2014,11 +4110,13 @@ impl AgentFS {
 
                 // If destination is directory, it must be empty
                 if dst_stats.is_directory() {
-                    let mut stmt = self
-                        .conn
-                        .prepare_cached("SELECT COUNT(*) FROM fs_dentry WHERE parent_ino = ?")
+                    let mut rows = self
+                        .backend
+                        .query(
+                            "SELECT COUNT(*) FROM fs_dentry WHERE parent_ino = ?",
+                            vec![dst_ino.into()],
+                        )
                         .await?;
-                    let mut rows = stmt.query((dst_ino,)).await?;
 
                     if let Some(row) = rows.next().await? {
                         let count = row
@@ -2032,75 +4130,96 @@ impl AgentFS {
                     }
                 }
 
-                // Remove destination entry
-                let mut stmt = self
-                    .conn
-                    .prepare_cached("DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?")
+                // Point the destination entry at the source inode in a single
+                // statement, so the destination never goes missing or shows a
+                // mix of old and new data to a reader. Drop the cached entry
+                // first so readers look it up again.
+                self.dentry_cache.remove(dst_parent_ino, &dst_name);
+                self.backend
+                    .execute(
+                        if self.filenames.case_insensitive {
+                            "UPDATE fs_dentry SET ino = ?, name = ?, name_key = ?
+                         WHERE parent_ino = ? AND name_key = ?"
+                        } else {
+                            "UPDATE fs_dentry SET ino = ?, name = ?, name_key = ?
+                         WHERE parent_ino = ? AND name = ?"
+                        },
+                        vec![
+                            src_ino.into(),
+                            dst_name.as_str().into(),
+                            self.match_name(&dst_name).into(),
+                            dst_parent_ino.into(),
+                            self.match_name(&dst_name).into(),
+                        ],
+                    )
+                    .await?;
+
+                // Then drop the source entry
+                self.backend
+                    .execute(
+                        if self.filenames.case_insensitive {
+                            "DELETE FROM fs_dentry WHERE parent_ino = ? AND name_key = ?"
+                        } else {
+                            "DELETE FROM fs_dentry WHERE parent_ino = ? AND name = ?"
+                        },
+                        vec![src_parent_ino.into(), self.match_name(&src_name).into()],
+                    )
                     .await?;
-                stmt.execute((dst_parent_ino, dst_name.as_str())).await?;
 
                 // Decrement link count
-                let mut stmt = self
-                    .conn
-                    .prepare_cached("UPDATE fs_inode SET nlink = nlink - 1 WHERE ino = ?")
+                self.backend
+                    .execute(
+                        "UPDATE fs_inode SET nlink = nlink - 1 WHERE ino = ?",
+                        vec![dst_ino.into()],
+                    )
                     .await?;
-                stmt.execute((dst_ino,)).await?;
 
-                // Clean up destination inode if no more links
+                // Clean up the replaced inode if no more links, unless a
+                // handle still has it open
                 let link_count = self.get_link_count(dst_ino).await?;
-                if link_count == 0 {
-                    let mut stmt = self
-                        .conn
-                        .prepare_cached("DELETE FROM fs_data WHERE ino = ?")
-                        .await?;
-                    stmt.execute((dst_ino,)).await?;
-                    let mut stmt = self
-                        .conn
-                        .prepare_cached("DELETE FROM fs_symlink WHERE ino = ?")
-                        .await?;
-                    stmt.execute((dst_ino,)).await?;
-                    let mut stmt = self
-                        .conn
-                        .prepare_cached("DELETE FROM fs_inode WHERE ino = ?")
-                        .await?;
-                    stmt.execute((dst_ino,)).await?;
+                if link_count == 0 && !self.open_inodes.keep_if_open(dst_ino) {
+                    self.delete_inode(dst_ino).await?;
                 }
+            } else {
+                // Update the dentry: change parent and/or name
+                self.backend
+                    .execute(
+                        if self.filenames.case_insensitive {
+                            "UPDATE fs_dentry SET parent_ino = ?, name = ?, name_key = ?
+                         WHERE parent_ino = ? AND name_key = ?"
+                        } else {
+                            "UPDATE fs_dentry SET parent_ino = ?, name = ?, name_key = ?
+                         WHERE parent_ino = ? AND name = ?"
+                        },
+                        vec![
+                            dst_parent_ino.into(),
+                            dst_name.as_str().into(),
+                            self.match_name(&dst_name).into(),
+                            src_parent_ino.into(),
+                            self.match_name(&src_name).into(),
+                        ],
+                    )
+                    .await?;
             }
 
-            // Update the dentry: change parent and/or name
-            let mut stmt = self
-                .conn
-                .prepare_cached(
-                    "UPDATE fs_dentry SET parent_ino = ?, name = ? WHERE parent_ino = ? AND name = ?",
-                )
-                .await?;
-            stmt.execute((
-                dst_parent_ino,
-                dst_name.as_str(),
-                src_parent_ino,
-                src_name.as_str(),
-            ))
-            .await?;
-
             // Update ctime of the inode
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs() as i64;
-
-            let mut stmt = self
-                .conn
-                .prepare_cached("UPDATE fs_inode SET ctime = ? WHERE ino = ?")
+            let now = self.clock.unix_time();
+
+            self.backend
+                .execute(
+                    "UPDATE fs_inode SET ctime = ? WHERE ino = ?",
+                    vec![now.into(), src_ino.into()],
+                )
                 .await?;
-            stmt.execute((now, src_ino)).await?;
 
-            Ok(())
+            self.journal_locked(JournalOp::Rename, &from_path, Some(&to_path), None)
+                .await
         }
         .await;
 
         match result {
             Ok(()) => {
-                self.conn.execute("COMMIT", ()).await?;
+                self.backend.execute("COMMIT", Vec::new()).await?;
 
                 // Invalidate cache for source and destination
                 self.dentry_cache.remove(src_parent_ino, &src_name);
@@ -2109,10 +4228,11 @@ impl AgentFS {
                 // Add new entry to cache (source inode is now at destination)
                 self.dentry_cache.insert(dst_parent_ino, &dst_name, src_ino);
 
+                self.reclaim_closed_inodes().await;
                 Ok(())
             }
             Err(e) => {
-                let _ = self.conn.execute("ROLLBACK", ()).await;
+                let _ = self.backend.execute("ROLLBACK", Vec::new()).await;
                 Err(e)
             }
         }
@@ -2123,11 +4243,10 @@ impl AgentFS {
     /// Returns the total number of inodes and bytes used by file contents.
     pub async fn statfs(&self) -> Result<FilesystemStats> {
         // Count total inodes
-        let mut stmt = self
-            .conn
-            .prepare_cached("SELECT COUNT(*) FROM fs_inode")
+        let mut rows = self
+            .backend
+            .query("SELECT COUNT(*) FROM fs_inode", Vec::new())
             .await?;
-        let mut rows = stmt.query(()).await?;
 
         let inodes = if let Some(row) = rows.next().await? {
             row.get_value(0)
@@ -2139,11 +4258,10 @@ impl AgentFS {
         };
 
         // Sum total bytes used (from file sizes in inodes)
-        let mut stmt = self
-            .conn
-            .prepare_cached("SELECT COALESCE(SUM(size), 0) FROM fs_inode")
+        let mut rows = self
+            .backend
+            .query("SELECT COALESCE(SUM(size), 0) FROM fs_inode", Vec::new())
             .await?;
-        let mut rows = stmt.query(()).await?;
 
         let bytes_used = if let Some(row) = rows.next().await? {
             row.get_value(0)
@@ -2157,18 +4275,121 @@ impl AgentFS {
         Ok(FilesystemStats { inodes, bytes_used })
     }
 
+    /// Summarize how much space file contents take, and how well they compress
+    pub async fn usage_summary(&self) -> Result<UsageSummary> {
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT COALESCE(SUM(size), 0) FROM fs_inode WHERE (mode & ?) = ?",
+                vec![(S_IFMT as i64).into(), (S_IFREG as i64).into()],
+            )
+            .await?;
+
+        let logical_bytes = if let Some(row) = rows.next().await? {
+            row.get_value(0)
+                .ok()
+                .and_then(|v| v.as_integer().copied())
+                .unwrap_or(0) as u64
+        } else {
+            0
+        };
+
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT COUNT(*), COALESCE(SUM(compressed), 0), COALESCE(SUM(length(data)), 0) FROM fs_data",
+                Vec::new(),
+            )
+            .await?;
+
+        let mut summary = UsageSummary {
+            logical_bytes,
+            ..UsageSummary::default()
+        };
+        if let Some(row) = rows.next().await? {
+            let column = |i| {
+                row.get_value(i)
+                    .ok()
+                    .and_then(|v| v.as_integer().copied())
+                    .unwrap_or(0) as u64
+            };
+            summary.blocks = column(0);
+            summary.compressed_blocks = column(1);
+            summary.stored_bytes = column(2);
+        }
+
+        Ok(summary)
+    }
+
+    /// List the stored blocks of a file, following a symlink at the end of the path
+    ///
+    /// Reports where each `fs_data` row sits in the file and how it is stored,
+    /// without returning the data, to check that writes and truncates leave a
+    /// sane layout. Blocks are in file order; a chunk index with no block is a
+    /// hole that reads as zeros. Blocks belong to a single file and are never
+    /// shared, so there is nothing to report about deduplication.
+    pub async fn block_map(&self, path: &str) -> Result<Vec<BlockInfo>> {
+        let ino = match self.stat(path).await? {
+            Some(stats) if stats.is_directory() => return Err(FsError::IsADirectory.into()),
+            Some(stats) => stats.ino,
+            None => return Err(FsError::NotFound.into()),
+        };
+
+        // Only compressed blocks are fetched, to find their decoded length
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT chunk_index, length(data), compressed, CASE WHEN compressed = 1 THEN data END FROM fs_data WHERE ino = ? ORDER BY chunk_index",
+                vec![ino.into()],
+            )
+            .await?;
+        let mut blocks = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let column = |i| {
+                row.get_value(i)
+                    .ok()
+                    .and_then(|v| v.as_integer().copied())
+                    .unwrap_or(0) as u64
+            };
+            let chunk_index = column(0);
+            let stored_length = column(1);
+            let compressed = column(2) == 1;
+            let length = match row.get_value(3) {
+                Ok(Value::Blob(data)) => {
+                    zstd::bulk::decompress(&data, self.chunk_size)?.len() as u64
+                }
+                _ => stored_length,
+            };
+            blocks.push(BlockInfo {
+                chunk_index,
+                offset: chunk_index * self.chunk_size as u64,
+                length,
+                stored_length,
+                compressed,
+            });
+        }
+        Ok(blocks)
+    }
+
     /// Synchronize file data to persistent storage
     ///
     /// Temporarily enables FULL synchronous mode, runs a transaction to force
-    /// a checkpoint, then restores OFF mode. This ensures durability while
-    /// maintaining high performance for normal operations.
+    /// a checkpoint, then restores the configured mode (OFF by default). This
+    /// ensures durability while maintaining high performance for normal operations.
     ///
     /// Note: The path parameter is ignored since all data is in a single database.
     pub async fn fsync(&self, _path: &str) -> Result<()> {
-        self.conn.execute("PRAGMA synchronous = FULL", ()).await?;
-        self.conn.execute("BEGIN", ()).await?;
-        self.conn.execute("COMMIT", ()).await?;
-        self.conn.execute("PRAGMA synchronous = OFF", ()).await?;
+        self.backend
+            .execute("PRAGMA synchronous = FULL", Vec::new())
+            .await?;
+        self.backend.execute("BEGIN", Vec::new()).await?;
+        self.backend.execute("COMMIT", Vec::new()).await?;
+        self.backend
+            .execute(
+                &format!("PRAGMA synchronous = {}", self.synchronous.as_pragma()),
+                Vec::new(),
+            )
+            .await?;
         Ok(())
     }
 
@@ -2177,16 +4398,18 @@ impl AgentFS {
     /// The returned handle can be used for efficient read/write/fsync operations
     /// without requiring path lookups on each operation.
     pub async fn open(&self, path: &str) -> Result<BoxedFile> {
-        let path = self.normalize_path(path);
-        let ino = self
-            .resolve_path(&path)
-            .await?
-            .ok_or_else(|| anyhow::anyhow!("File not found: {}", path))?;
+        let path = self.normalize_path(path)?;
+        let ino = self.resolve_path(&path).await?.ok_or(FsError::NotFound)?;
 
         Ok(Arc::new(AgentFSFile {
-            conn: self.conn.clone(),
+            backend: self.backend.clone(),
             ino,
             chunk_size: self.chunk_size,
+            synchronous: self.synchronous,
+            max_file_size: self.max_file_size,
+            compression: self.compression,
+            clock: self.clock.clone(),
+            _open: self.open_inodes.acquire(ino),
         }))
     }
 
@@ -2194,8 +4417,11 @@ impl AgentFS {
     #[cfg(test)]
     async fn get_chunk_count(&self, ino: i64) -> Result<i64> {
         let mut rows = self
-            .conn
-            .query("SELECT COUNT(*) FROM fs_data WHERE ino = ?", (ino,))
+            .backend
+            .query(
+                "SELECT COUNT(*) FROM fs_data WHERE ino = ?",
+                vec![ino.into()],
+            )
             .await?;
 
         if let Some(row) = rows.next().await? {
@@ -2212,71 +4438,168 @@ impl AgentFS {
 
 #[async_trait]
 impl FileSystem for AgentFS {
-    async fn stat(&self, path: &str) -> Result<Option<Stats>> {
-        AgentFS::stat(self, path).await
+    async fn stat(&self, path: &str) -> anyhow::Result<Option<Stats>> {
+        AgentFS::stat(self, path)
+            .await
+            .map_err(AgentFsError::into_fs_error)
+    }
+
+    async fn lstat(&self, path: &str) -> anyhow::Result<Option<Stats>> {
+        AgentFS::lstat(self, path)
+            .await
+            .map_err(AgentFsError::into_fs_error)
+    }
+
+    async fn read_file(&self, path: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        AgentFS::read_file(self, path)
+            .await
+            .map_err(AgentFsError::into_fs_error)
+    }
+
+    async fn write_file(&self, path: &str, data: &[u8]) -> anyhow::Result<()> {
+        AgentFS::write_file(self, path, data)
+            .await
+            .map_err(AgentFsError::into_fs_error)
+    }
+
+    async fn write_file_mode(&self, path: &str, data: &[u8], mode: u32) -> anyhow::Result<()> {
+        AgentFS::write_file_mode(self, path, data, mode)
+            .await
+            .map_err(AgentFsError::into_fs_error)
+    }
+
+    async fn readdir(&self, path: &str) -> anyhow::Result<Option<Vec<String>>> {
+        AgentFS::readdir(self, path)
+            .await
+            .map_err(AgentFsError::into_fs_error)
+    }
+
+    async fn readdir_paged(
+        &self,
+        path: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        AgentFS::readdir_paged(self, path, after, limit)
+            .await
+            .map_err(AgentFsError::into_fs_error)
+    }
+
+    async fn readdir_plus(&self, path: &str) -> anyhow::Result<Option<Vec<DirEntry>>> {
+        AgentFS::readdir_plus(self, path)
+            .await
+            .map_err(AgentFsError::into_fs_error)
+    }
+
+    async fn mkdir(&self, path: &str) -> anyhow::Result<()> {
+        AgentFS::mkdir(self, path)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn lstat(&self, path: &str) -> Result<Option<Stats>> {
-        AgentFS::lstat(self, path).await
+    async fn remove(&self, path: &str) -> anyhow::Result<()> {
+        AgentFS::remove(self, path)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn read_file(&self, path: &str) -> Result<Option<Vec<u8>>> {
-        AgentFS::read_file(self, path).await
+    async fn chmod(&self, path: &str, mode: u32) -> anyhow::Result<()> {
+        AgentFS::chmod(self, path, mode)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
-        AgentFS::write_file(self, path, data).await
+    async fn chown(&self, path: &str, uid: Option<u32>, gid: Option<u32>) -> anyhow::Result<()> {
+        AgentFS::chown(self, path, uid, gid)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn readdir(&self, path: &str) -> Result<Option<Vec<String>>> {
-        AgentFS::readdir(self, path).await
+    async fn create_new(&self, path: &str, data: &[u8]) -> anyhow::Result<()> {
+        AgentFS::create_new(self, path, data)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn readdir_plus(&self, path: &str) -> Result<Option<Vec<DirEntry>>> {
-        AgentFS::readdir_plus(self, path).await
+    async fn append_file(&self, path: &str, data: &[u8]) -> anyhow::Result<()> {
+        AgentFS::append_file(self, path, data)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn mkdir(&self, path: &str) -> Result<()> {
-        AgentFS::mkdir(self, path).await
+    async fn append_rotating(
+        &self,
+        path: &str,
+        data: &[u8],
+        max_bytes: u64,
+        keep: usize,
+    ) -> anyhow::Result<()> {
+        AgentFS::append_rotating(self, path, data, max_bytes, keep)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn remove(&self, path: &str) -> Result<()> {
-        AgentFS::remove(self, path).await
+    async fn rename(&self, from: &str, to: &str) -> anyhow::Result<()> {
+        AgentFS::rename(self, from, to)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn chmod(&self, path: &str, mode: u32) -> Result<()> {
-        AgentFS::chmod(self, path, mode).await
+    async fn symlink(&self, target: &str, linkpath: &str) -> anyhow::Result<()> {
+        AgentFS::symlink(self, target, linkpath)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn rename(&self, from: &str, to: &str) -> Result<()> {
-        AgentFS::rename(self, from, to).await
+    async fn link(&self, oldpath: &str, newpath: &str) -> anyhow::Result<()> {
+        AgentFS::link(self, oldpath, newpath)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn symlink(&self, target: &str, linkpath: &str) -> Result<()> {
-        AgentFS::symlink(self, target, linkpath).await
+    async fn readlink(&self, path: &str) -> anyhow::Result<Option<String>> {
+        AgentFS::readlink(self, path)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn link(&self, oldpath: &str, newpath: &str) -> Result<()> {
-        AgentFS::link(self, oldpath, newpath).await
+    async fn journal(&self, since: SystemTime) -> anyhow::Result<Vec<JournalEntry>> {
+        AgentFS::journal(self, since)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn readlink(&self, path: &str) -> Result<Option<String>> {
-        AgentFS::readlink(self, path).await
+    async fn statfs(&self) -> anyhow::Result<FilesystemStats> {
+        AgentFS::statfs(self)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn statfs(&self) -> Result<FilesystemStats> {
-        AgentFS::statfs(self).await
+    async fn stat_ino(&self, ino: i64) -> anyhow::Result<Option<Stats>> {
+        AgentFS::stat_ino(self, ino)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 
-    async fn open(&self, path: &str) -> Result<BoxedFile> {
-        AgentFS::open(self, path).await
+    async fn path_for_ino(&self, ino: i64) -> anyhow::Result<Option<String>> {
+        AgentFS::path_for_ino(self, ino)
+            .await
+            .map_err(AgentFsError::into_fs_error)
+    }
+
+    async fn open(&self, path: &str) -> anyhow::Result<BoxedFile> {
+        AgentFS::open(self, path)
+            .await
+            .map_err(AgentFsError::into_fs_error)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clock::MockClock;
     use tempfile::tempdir;
+    use turso::Builder;
 
     async fn create_test_fs() -> Result<(AgentFS, tempfile::TempDir)> {
         let dir = tempdir()?;
@@ -2583,8 +4906,11 @@ mod tests {
 
         // Query fs_config table directly
         let mut rows = fs
-            .conn
-            .query("SELECT value FROM fs_config WHERE key = 'chunk_size'", ())
+            .backend
+            .query(
+                "SELECT value FROM fs_config WHERE key = 'chunk_size'",
+                Vec::new(),
+            )
             .await?;
 
         let row = rows.next().await?.expect("chunk_size config should exist");
@@ -2617,10 +4943,10 @@ mod tests {
 
         // Try to insert a duplicate chunk - should fail due to PRIMARY KEY constraint
         let result = fs
-            .conn
+            .backend
             .execute(
                 "INSERT INTO fs_data (ino, chunk_index, data) VALUES (?, 0, ?)",
-                (ino, vec![1u8; 10]),
+                vec![ino.into(), vec![1u8; 10].into()],
             )
             .await;
 
@@ -2643,10 +4969,10 @@ mod tests {
 
         // Query chunks in order
         let mut rows = fs
-            .conn
+            .backend
             .query(
                 "SELECT chunk_index FROM fs_data WHERE ino = ? ORDER BY chunk_index",
-                (ino,),
+                vec![ino.into()],
             )
             .await?;
 
@@ -2684,8 +5010,11 @@ mod tests {
 
         // Verify all chunks are gone
         let mut rows = fs
-            .conn
-            .query("SELECT COUNT(*) FROM fs_data WHERE ino = ?", (ino,))
+            .backend
+            .query(
+                "SELECT COUNT(*) FROM fs_data WHERE ino = ?",
+                vec![ino.into()],
+            )
             .await?;
 
         let count = rows
@@ -2870,6 +5199,414 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_append_file() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let chunk_size = fs.chunk_size();
+
+        // Appending to a missing file creates it
+        fs.append_file("/log.txt", b"hello").await?;
+        fs.append_file("/log.txt", b" world").await?;
+        assert_eq!(fs.read_file("/log.txt").await?.unwrap(), b"hello world");
+
+        // Appends that cross chunk boundaries keep earlier data intact
+        let big: Vec<u8> = (0..chunk_size + 7).map(|i| (i % 251) as u8).collect();
+        fs.append_file("/log.txt", &big).await?;
+        let result = fs.read_file("/log.txt").await?.unwrap();
+        assert_eq!(result.len(), 11 + big.len());
+        assert_eq!(&result[..11], b"hello world");
+        assert_eq!(&result[11..], &big[..]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_journal() -> Result<()> {
+        let (mut fs, _dir) = create_test_fs().await?;
+
+        // Nothing is recorded until journaling is enabled
+        fs.write_file("/before.txt", b"x").await?;
+        assert!(!fs.journal_enabled());
+        fs.enable_journal().await?;
+
+        let clock = Arc::new(MockClock::from_unix_time(1_700_000_000));
+        fs.set_clock(clock.clone());
+        fs.mkdir("/dir").await?;
+        fs.write_file("/dir/a.txt", b"hello").await?;
+        clock.advance(std::time::Duration::from_secs(60));
+        fs.rename("/dir/a.txt", "/dir/b.txt").await?;
+        fs.remove("/dir/b.txt").await?;
+
+        // Failed mutations leave no entry
+        assert!(fs.mkdir("/dir").await.is_err());
+        assert!(fs.remove("/missing").await.is_err());
+
+        let entries = fs.journal(UNIX_EPOCH).await?;
+        let ops: Vec<_> = entries
+            .iter()
+            .map(|e| (e.op, e.path.as_str(), e.target.as_deref()))
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                (JournalOp::Mkdir, "/dir", None),
+                (JournalOp::Write, "/dir/a.txt", None),
+                (JournalOp::Rename, "/dir/a.txt", Some("/dir/b.txt")),
+                (JournalOp::Remove, "/dir/b.txt", None),
+            ]
+        );
+        assert_eq!(entries[1].size, Some(5));
+        assert_eq!(
+            entries[1].hash.as_deref(),
+            Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824")
+        );
+        assert_eq!(entries[0].timestamp, 1_700_000_000);
+
+        let since = UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_060);
+        assert_eq!(fs.journal(since).await?.len(), 2);
+
+        // Journaling stays enabled across reopens
+        let reopened = AgentFS::from_backend(fs.backend().clone()).await?;
+        assert!(reopened.journal_enabled());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_journal_every_mutation() -> Result<()> {
+        let (mut fs, _dir) = create_test_fs().await?;
+        fs.enable_journal().await?;
+        let host = tempdir()?;
+        std::fs::write(host.path().join("f.txt"), b"hello")?;
+
+        fs.mkdir("/d").await?;
+        fs.write_file("/d/../a.txt", b"abc").await?;
+        fs.create_new("/b.txt", b"b").await?;
+        fs.write_file_from("/c.txt", &b"hello"[..]).await?;
+        fs.append_file("/a.txt", b"de").await?;
+        fs.pwrite("/a.txt", 0, b"x").await?;
+        fs.truncate("/a.txt", 2).await?;
+        fs.symlink("a.txt", "/s").await?;
+        fs.link("/a.txt", "/h").await?;
+        fs.chmod("/a.txt", 0o600).await?;
+        fs.chown("/a.txt", Some(1000), None).await?;
+        fs.rename("/h", "/h2").await?;
+        fs.remove("/h2").await?;
+        fs.append_rotating("/log", b"aaaa", 4, 1).await?;
+        fs.append_rotating("/log", b"bb", 4, 1).await?;
+        fs.append_rotating("/log", b"ccc", 4, 1).await?;
+        fs.import_dir(host.path(), "/imp").await?;
+
+        // Failed mutations leave no entry
+        assert!(fs.create_new("/b.txt", b"b").await.is_err());
+        assert!(fs.link("/a.txt", "/b.txt").await.is_err());
+        assert!(fs.symlink("a.txt", "/s").await.is_err());
+        assert!(fs.chmod("/missing", 0o600).await.is_err());
+        assert!(fs.truncate("/missing", 0).await.is_err());
+
+        let entries = fs.journal(UNIX_EPOCH).await?;
+        let ops: Vec<_> = entries
+            .iter()
+            .map(|e| (e.op, e.path.as_str(), e.target.as_deref()))
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                (JournalOp::Mkdir, "/d", None),
+                (JournalOp::Write, "/a.txt", None),
+                (JournalOp::Write, "/b.txt", None),
+                (JournalOp::Write, "/c.txt", None),
+                (JournalOp::Append, "/a.txt", None),
+                (JournalOp::Pwrite, "/a.txt", None),
+                (JournalOp::Truncate, "/a.txt", None),
+                (JournalOp::Symlink, "/s", Some("a.txt")),
+                (JournalOp::Link, "/a.txt", Some("/h")),
+                (JournalOp::Chmod, "/a.txt", None),
+                (JournalOp::Chown, "/a.txt", None),
+                (JournalOp::Rename, "/h", Some("/h2")),
+                (JournalOp::Remove, "/h2", None),
+                (JournalOp::Append, "/log", None),
+                (JournalOp::Rename, "/log", Some("/log.1")),
+                (JournalOp::Append, "/log", None),
+                (JournalOp::Remove, "/log.1", None),
+                (JournalOp::Rename, "/log", Some("/log.1")),
+                (JournalOp::Append, "/log", None),
+                (JournalOp::Mkdir, "/imp", None),
+                (JournalOp::Write, "/imp/f.txt", None),
+            ]
+        );
+
+        // Streamed writes are hashed like buffered ones
+        let hello = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        for entry in [&entries[3], &entries[20]] {
+            assert_eq!(entry.size, Some(5));
+            assert_eq!(entry.hash.as_deref(), Some(hello));
+        }
+        assert_eq!(entries[4].size, Some(2));
+        assert_eq!(entries[6].size, Some(2));
+        assert_eq!(entries[6].hash, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_append_rotating() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        fs.append_rotating("/app.log", b"aaaa", 8, 2).await?;
+        fs.append_rotating("/app.log", b"bbbb", 8, 2).await?;
+        assert_eq!(fs.read_file("/app.log").await?.unwrap(), b"aaaabbbb");
+
+        // Going over the cap rotates first
+        fs.append_rotating("/app.log", b"cccc", 8, 2).await?;
+        assert_eq!(fs.read_file("/app.log").await?.unwrap(), b"cccc");
+        assert_eq!(fs.read_file("/app.log.1").await?.unwrap(), b"aaaabbbb");
+
+        fs.append_rotating("/app.log", b"ddddd", 8, 2).await?;
+        fs.append_rotating("/app.log", b"eeeeeeeee", 8, 2).await?;
+        assert_eq!(fs.read_file("/app.log").await?.unwrap(), b"eeeeeeeee");
+        assert_eq!(fs.read_file("/app.log.1").await?.unwrap(), b"ddddd");
+        assert_eq!(fs.read_file("/app.log.2").await?.unwrap(), b"cccc");
+        assert!(fs.stat("/app.log.3").await?.is_none());
+
+        // A failed rotation leaves every generation in place
+        fs.remove("/app.log.2").await?;
+        fs.mkdir("/app.log.2").await?;
+        assert!(fs.append_rotating("/app.log", b"f", 8, 2).await.is_err());
+        assert_eq!(fs.read_file("/app.log").await?.unwrap(), b"eeeeeeeee");
+        assert_eq!(fs.read_file("/app.log.1").await?.unwrap(), b"ddddd");
+
+        // keep = 0 discards the old contents
+        fs.append_rotating("/other.log", b"12345", 8, 0).await?;
+        fs.append_rotating("/other.log", b"6789", 8, 0).await?;
+        assert_eq!(fs.read_file("/other.log").await?.unwrap(), b"6789");
+        assert!(fs.stat("/other.log.1").await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_file_size() -> Result<()> {
+        let (mut fs, _dir) = create_test_fs().await?;
+        fs.set_max_file_size(Some(100));
+
+        fs.write_file("/test.txt", &[1; 100]).await?;
+        let err = fs.write_file("/test.txt", &[2; 101]).await.unwrap_err();
+        assert!(matches!(err, AgentFsError::FileTooLarge(_)));
+
+        // Failed writes leave the file untouched
+        let err = fs.pwrite("/test.txt", 90, &[3; 20]).await.unwrap_err();
+        assert!(matches!(err, AgentFsError::FileTooLarge(_)));
+        assert_eq!(fs.read_file("/test.txt").await?.unwrap(), vec![1; 100]);
+
+        let file = fs.open("/test.txt").await?;
+        assert!(file.pwrite(100, &[4]).await.is_err());
+        assert!(file.truncate(101).await.is_err());
+        file.pwrite(99, &[5]).await?;
+        assert_eq!(file.fstat().await?.size, 100);
+
+        Ok(())
+    }
+
+    /// Reader that yields `remaining` bytes and then fails
+    struct FailingReader {
+        remaining: usize,
+    }
+
+    impl AsyncRead for FailingReader {
+        fn poll_read(
+            mut self: std::pin::Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            if self.remaining == 0 {
+                return std::task::Poll::Ready(Err(std::io::Error::other("read failed")));
+            }
+            let n = self.remaining.min(buf.remaining());
+            buf.put_slice(&vec![7u8; n]);
+            self.remaining -= n;
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_file_from_reader() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        // Spans several chunks and ends with a partial one
+        let data: Vec<u8> = (0..fs.chunk_size() * 3 + 17)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let written = fs.write_file_from("/big.bin", data.as_slice()).await?;
+        assert_eq!(written, data.len() as u64);
+        assert_eq!(fs.read_file("/big.bin").await?.unwrap(), data);
+
+        let ino = fs.resolve_path("/big.bin").await?.unwrap();
+        assert_eq!(fs.get_chunk_count(ino).await?, 4);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_file_to_writer() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let chunk_size = fs.chunk_size();
+
+        let data: Vec<u8> = (0..chunk_size * 2 + 17).map(|i| (i % 251) as u8).collect();
+        fs.write_file("/big.bin", &data).await?;
+        let mut out = Vec::new();
+        assert_eq!(
+            fs.read_file_to("/big.bin", &mut out).await?,
+            Some(data.len() as u64)
+        );
+        assert_eq!(out, data);
+
+        // Gaps left by pwrite and truncate read back as zeros
+        fs.write_file("/sparse.bin", b"head").await?;
+        fs.pwrite("/sparse.bin", chunk_size as u64 * 2, b"tail")
+            .await?;
+        fs.truncate("/sparse.bin", chunk_size as u64 * 3).await?;
+        let mut out = Vec::new();
+        fs.read_file_to("/sparse.bin", &mut out).await?;
+        let mut expected = vec![0u8; chunk_size * 3];
+        expected[..4].copy_from_slice(b"head");
+        expected[chunk_size * 2..chunk_size * 2 + 4].copy_from_slice(b"tail");
+        assert_eq!(out, expected);
+
+        assert_eq!(fs.read_file_to("/missing", &mut out).await?, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_block_map() -> Result<()> {
+        let (mut fs, _dir) = create_test_fs().await?;
+        fs.set_compression(Compression::Zstd(3));
+        let chunk_size = fs.chunk_size() as u64;
+
+        // The short tail isn't worth compressing and is stored raw
+        fs.write_file("/a.txt", &vec![b'a'; chunk_size as usize * 2 + 17])
+            .await?;
+        let blocks = fs.block_map("/a.txt").await?;
+        let layout: Vec<_> = blocks
+            .iter()
+            .map(|b| (b.chunk_index, b.offset, b.length, b.compressed))
+            .collect();
+        assert_eq!(
+            layout,
+            [
+                (0, 0, chunk_size, true),
+                (1, chunk_size, chunk_size, true),
+                (2, chunk_size * 2, 17, false),
+            ]
+        );
+        assert!(blocks[0].stored_length < chunk_size);
+        assert_eq!(blocks[2].stored_length, 17);
+
+        // Holes have no block
+        fs.write_file("/sparse.bin", b"head").await?;
+        fs.pwrite("/sparse.bin", chunk_size * 2, b"tail").await?;
+        let indexes: Vec<u64> = fs
+            .block_map("/sparse.bin")
+            .await?
+            .iter()
+            .map(|b| b.chunk_index)
+            .collect();
+        assert_eq!(indexes, [0, 2]);
+
+        fs.mkdir("/dir").await?;
+        let err = fs.block_map("/dir").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::Fs(FsError::IsADirectory)));
+        let err = fs.block_map("/missing").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::NotFound(_)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_write_file_from_rolls_back_on_error() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let remaining = fs.chunk_size() * 2 + 5;
+
+        fs.write_file("/existing.txt", b"original").await?;
+        let reader = FailingReader { remaining };
+        assert!(fs.write_file_from("/existing.txt", reader).await.is_err());
+        assert_eq!(
+            fs.read_file("/existing.txt").await?.unwrap(),
+            b"original".to_vec()
+        );
+
+        let reader = FailingReader { remaining };
+        assert!(fs.write_file_from("/new.txt", reader).await.is_err());
+        assert!(fs.stat("/new.txt").await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_open_with_async_handle() -> Result<()> {
+        use crate::filesystem::OpenOptions;
+        use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+        let (fs, _dir) = create_test_fs().await?;
+        let missing = fs.open_with("/log.txt", OpenOptions::default()).await;
+        assert!(matches!(
+            missing.err().unwrap().downcast_ref::<FsError>(),
+            Some(FsError::NotFound)
+        ));
+
+        // Stream more than a chunk in and read it back through the same handle
+        let data: Vec<u8> = (0..fs.chunk_size() + 10).map(|i| (i % 251) as u8).collect();
+        let options = OpenOptions {
+            write: true,
+            create: true,
+            ..Default::default()
+        };
+        let mut file = fs.open_with("/log.txt", options).await?;
+        file.write_all(&data).await?;
+        file.flush().await?;
+        assert_eq!(file.offset(), data.len() as u64);
+        assert_eq!(file.seek(std::io::SeekFrom::Start(2)).await?, 2);
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).await?;
+        assert_eq!(contents, data[2..]);
+        assert_eq!(
+            file.seek(std::io::SeekFrom::End(-4)).await?,
+            data.len() as u64 - 4
+        );
+        file.write_all(b"tail").await?;
+        assert_eq!(
+            file.seek(std::io::SeekFrom::Current(0)).await?,
+            data.len() as u64
+        );
+
+        // Appends land at the end whatever the offset, and truncation empties the file
+        let options = OpenOptions {
+            read: false,
+            append: true,
+            ..Default::default()
+        };
+        let mut file = fs.open_with("/log.txt", options).await?;
+        file.write_all(b"!").await?;
+        let mut buf = [0u8; 1];
+        assert!(file.read(&mut buf).await.is_err());
+        assert_eq!(fs.read_file("/log.txt").await?.unwrap().last(), Some(&b'!'));
+
+        let options = OpenOptions {
+            truncate: true,
+            write: true,
+            ..Default::default()
+        };
+        let mut file = fs.open_with("/log.txt", options).await?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).await?;
+        assert!(contents.is_empty());
+
+        let options = OpenOptions {
+            create_new: true,
+            ..Default::default()
+        };
+        assert!(fs.open_with("/log.txt", options).await.is_err());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_pwrite_across_chunks() -> Result<()> {
         let (fs, _dir) = create_test_fs().await?;
@@ -3107,11 +5844,77 @@ mod tests {
         // Rename src to dst (overwrites dst)
         fs.rename("/src.txt", "/dst.txt").await?;
 
-        // Only dst should exist with src's content
-        assert!(fs.stat("/src.txt").await?.is_none());
-        let result = fs.read_file("/dst.txt").await?.unwrap();
-        assert_eq!(result, b"source");
+        // Only dst should exist with src's content
+        assert!(fs.stat("/src.txt").await?.is_none());
+        let result = fs.read_file("/dst.txt").await?.unwrap();
+        assert_eq!(result, b"source");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rename_over_open_file_keeps_old_data() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let old = vec![b'o'; fs.chunk_size() * 2 + 5];
+        let new = vec![b'n'; fs.chunk_size() + 3];
+
+        fs.write_file("/config", &old).await?;
+        let file = fs.open("/config").await?;
+        let old_ino = file.fstat().await?.ino;
+
+        // Write-then-rename replaces the path, but the open file keeps the
+        // inode it was opened on
+        fs.write_file("/config.tmp", &new).await?;
+        fs.rename("/config.tmp", "/config").await?;
+        assert_eq!(fs.read_file("/config").await?.unwrap(), new);
+        assert_eq!(file.pread(0, old.len() as u64).await?, old);
+        assert_eq!(file.fstat().await?.nlink, 0);
+
+        // The same holds for a removed file
+        let again = fs.open("/config").await?;
+        fs.remove("/config").await?;
+        assert_eq!(again.pread(0, new.len() as u64).await?, new);
+
+        // Once closed, the next remove or rename deletes the old inodes
+        drop(file);
+        drop(again);
+        fs.write_file("/other", b"x").await?;
+        fs.remove("/other").await?;
+        assert!(fs.stat_ino(old_ino).await?.is_none());
+        assert_eq!(fs.get_chunk_count(old_ino).await?, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rename_replace_is_atomic_for_readers() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let len = fs.chunk_size() * 3;
+        fs.write_file("/data", &vec![0u8; len]).await?;
+
+        let writer = async {
+            for version in 1..=20u8 {
+                fs.write_file("/data.tmp", &vec![version; len]).await?;
+                fs.rename("/data.tmp", "/data").await?;
+                tokio::task::yield_now().await;
+            }
+            Result::<()>::Ok(())
+        };
+        let reader = async {
+            for _ in 0..40 {
+                // Every read sees one complete version, never a mix or nothing
+                let data = fs.read_file("/data").await?.expect("file went missing");
+                assert_eq!(data.len(), len);
+                assert!(data.iter().all(|&byte| byte == data[0]));
+                tokio::task::yield_now().await;
+            }
+            Result::<()>::Ok(())
+        };
+        let (written, read) = tokio::join!(writer, reader);
+        written?;
+        read?;
 
+        assert_eq!(fs.read_file("/data").await?.unwrap(), vec![20u8; len]);
         Ok(())
     }
 
@@ -3296,9 +6099,39 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_chmod_regular_file() -> Result<()> {
+    async fn test_write_file_mode() -> Result<()> {
         let (fs, _dir) = create_test_fs().await?;
 
+        // Only the permission bits of the mode are used
+        fs.write_file_mode("/run.sh", b"#!/bin/sh\n", S_IFLNK | 0o755)
+            .await?;
+        let stats = fs.stat("/run.sh").await?.unwrap();
+        assert_eq!(stats.mode, S_IFREG | 0o755);
+
+        // Overwriting keeps the permissions...
+        fs.write_file("/run.sh", b"#!/bin/sh\nexit 0\n").await?;
+        let stats = fs.stat("/run.sh").await?.unwrap();
+        assert_eq!(stats.mode, S_IFREG | 0o755);
+        assert_eq!(stats.size, 17);
+
+        // ...unless new ones are given
+        fs.write_file_mode("/run.sh", b"", 0o600).await?;
+        let stats = fs.stat("/run.sh").await?.unwrap();
+        assert_eq!(stats.mode, S_IFREG | 0o600);
+
+        fs.write_file("/plain.txt", b"text").await?;
+        let stats = fs.stat("/plain.txt").await?.unwrap();
+        assert_eq!(stats.mode, DEFAULT_FILE_MODE);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chmod_regular_file() -> Result<()> {
+        let (mut fs, _dir) = create_test_fs().await?;
+        let clock = Arc::new(MockClock::from_unix_time(1_700_000_000));
+        fs.set_clock(clock.clone());
+
         // Create a file with default permissions
         fs.write_file("/test.txt", b"content").await?;
 
@@ -3310,6 +6143,7 @@ mod tests {
         );
 
         // Change to executable
+        clock.advance(std::time::Duration::from_secs(60));
         fs.chmod("/test.txt", 0o755).await?;
 
         let stats = fs.stat("/test.txt").await?.unwrap();
@@ -3319,6 +6153,8 @@ mod tests {
             "Mode should be 0o755 after chmod"
         );
         assert!(stats.is_file(), "Should still be a regular file");
+        assert_eq!(stats.ctime, 1_700_000_060, "ctime should be updated");
+        assert_eq!(stats.mtime, 1_700_000_000, "mtime should be unchanged");
 
         // Change to read-only
         fs.chmod("/test.txt", 0o444).await?;
@@ -3356,6 +6192,89 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_chown() -> Result<()> {
+        let (mut fs, _dir) = create_test_fs().await?;
+        let clock = Arc::new(MockClock::from_unix_time(1_700_000_000));
+        fs.set_clock(clock.clone());
+
+        fs.write_file("/file.txt", b"content").await?;
+        let before = fs.stat("/file.txt").await?.unwrap();
+
+        clock.advance(std::time::Duration::from_secs(60));
+        fs.chown("/file.txt", Some(1000), None).await?;
+        let stats = fs.stat("/file.txt").await?.unwrap();
+        assert_eq!(stats.uid, 1000);
+        assert_eq!(stats.gid, before.gid, "gid should be unchanged");
+        assert_eq!(stats.mode, before.mode, "mode should be unchanged");
+        assert_eq!(stats.ctime, 1_700_000_060, "ctime should be updated");
+        assert_eq!(stats.mtime, before.mtime, "mtime should be unchanged");
+
+        fs.chown("/file.txt", None, Some(2000)).await?;
+        let stats = fs.stat("/file.txt").await?.unwrap();
+        assert_eq!((stats.uid, stats.gid), (1000, 2000));
+
+        assert!(fs.chown("/missing", Some(0), Some(0)).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_new() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        fs.create_new("/lock", b"pid 1").await?;
+        let data = fs.read_file("/lock").await?.unwrap();
+        assert_eq!(data, b"pid 1");
+
+        // A second creator loses and the original contents survive
+        let err = fs.create_new("/lock", b"pid 2").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::AlreadyExists(_)));
+        let data = fs.read_file("/lock").await?.unwrap();
+        assert_eq!(data, b"pid 1");
+        assert_eq!(fs.stat("/lock").await?.unwrap().nlink, 1);
+
+        // Directories count as existing entries too
+        fs.mkdir("/dir").await?;
+        let err = fs.create_new("/dir", b"").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::AlreadyExists(_)));
+
+        // Once removed, the name can be created again
+        fs.remove("/lock").await?;
+        fs.create_new("/lock", b"pid 3").await?;
+        let data = fs.read_file("/lock").await?.unwrap();
+        assert_eq!(data, b"pid 3");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_new_case_insensitive() -> Result<()> {
+        let dir = tempdir()?;
+        let db_path = dir.path().join("test.db");
+        let db = Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await?;
+        let options = FilenameOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let first =
+            AgentFS::from_connection_with_filenames(Arc::new(db.connect()?), options).await?;
+        let second =
+            AgentFS::from_connection_with_filenames(Arc::new(db.connect()?), options).await?;
+
+        // The name key index rejects a differently-cased creator, even one
+        // that hasn't seen the first entry yet
+        first.create_new("/Lock", b"pid 1").await?;
+        let err = second.create_new("/LOCK", b"pid 2").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::AlreadyExists(_)));
+        assert_eq!(second.readdir("/").await?.unwrap(), vec!["Lock"]);
+        assert_eq!(second.read_file("/lock").await?, Some(b"pid 1".to_vec()));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_chmod_nonexistent_fails() -> Result<()> {
         let (fs, _dir) = create_test_fs().await?;
@@ -3366,6 +6285,64 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_stat_follows_symlinks_lstat_does_not() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        fs.mkdir("/dir").await?;
+        fs.write_file("/dir/target.txt", b"target content").await?;
+        fs.symlink("target.txt", "/dir/relative").await?;
+        fs.symlink("/dir/relative", "/chain").await?;
+        fs.symlink("/missing", "/dangling").await?;
+        fs.symlink("/loop-b", "/loop-a").await?;
+        fs.symlink("/loop-a", "/loop-b").await?;
+
+        let target = fs.stat("/dir/target.txt").await?.unwrap();
+        for link in ["/dir/relative", "/chain"] {
+            let stats = fs.stat(link).await?.unwrap();
+            assert!(stats.is_file());
+            assert_eq!(stats.ino, target.ino);
+            let stats = fs.lstat(link).await?.unwrap();
+            assert!(stats.is_symlink());
+            assert_ne!(stats.ino, target.ino);
+        }
+
+        assert!(fs.stat("/dangling").await?.is_none());
+        assert!(fs.lstat("/dangling").await?.unwrap().is_symlink());
+
+        let err = fs.stat("/loop-a").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::Fs(FsError::SymlinkLoop)));
+        assert!(fs.lstat("/loop-a").await?.unwrap().is_symlink());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_max_symlink_depth() -> Result<()> {
+        let (mut fs, _dir) = create_test_fs().await?;
+        assert_eq!(fs.max_symlink_depth(), DEFAULT_MAX_SYMLINK_DEPTH);
+        fs.set_max_symlink_depth(3);
+
+        fs.write_file("/target.txt", b"data").await?;
+        fs.symlink("/self", "/self").await?;
+        // /link3 -> /link2 -> /link1 -> /target.txt, and /link4 -> /link3
+        fs.symlink("/target.txt", "/link1").await?;
+        for i in 2..=4 {
+            fs.symlink(&format!("/link{}", i - 1), &format!("/link{}", i))
+                .await?;
+        }
+
+        let is_loop = |err: AgentFsError| matches!(err, AgentFsError::Fs(FsError::SymlinkLoop));
+        assert!(is_loop(fs.stat("/self").await.unwrap_err()));
+        assert!(is_loop(fs.read_file("/self").await.unwrap_err()));
+
+        // A chain exactly as long as the limit still resolves
+        assert!(fs.stat("/link3").await?.unwrap().is_file());
+        assert_eq!(fs.read_file("/link3").await?.unwrap(), b"data");
+        // One link more than the limit does not
+        assert!(is_loop(fs.stat("/link4").await.unwrap_err()));
+        assert!(is_loop(fs.read_file("/link4").await.unwrap_err()));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_chmod_symlink() -> Result<()> {
         let (fs, _dir) = create_test_fs().await?;
@@ -3382,4 +6359,528 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_readdir_stats() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        fs.mkdir("/dir").await?;
+        fs.write_file("/dir/b.txt", b"hello").await?;
+        fs.mkdir("/dir/a").await?;
+
+        let entries = FileSystem::readdir_stats(&fs, "/dir").await?.unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, "a");
+        assert!(entries[0].1.is_directory());
+        assert_eq!(entries[1].0, "b.txt");
+        assert!(entries[1].1.is_file());
+        assert_eq!(entries[1].1.size, 5);
+
+        assert!(FileSystem::readdir_stats(&fs, "/missing").await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_readdir_paged() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        fs.mkdir("/dir").await?;
+        for name in ["e", "a", "d", "c", "b"] {
+            fs.write_file(&format!("/dir/{name}"), b"").await?;
+        }
+
+        let mut pages = Vec::new();
+        let mut after: Option<String> = None;
+        loop {
+            let page = fs
+                .readdir_paged("/dir", after.as_deref(), 2)
+                .await?
+                .unwrap();
+            if page.is_empty() {
+                break;
+            }
+            after = page.last().cloned();
+            pages.push(page);
+        }
+        assert_eq!(pages, vec![vec!["a", "b"], vec!["c", "d"], vec!["e"]]);
+
+        assert!(fs.readdir_paged("/missing", None, 10).await?.is_none());
+        assert_eq!(fs.readdir_paged("/dir", Some("e"), 10).await?, Some(vec![]));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_relative_parent_components_clamped_at_root() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        fs.write_file("/file.txt", b"root").await?;
+
+        // `..` in relative paths is resolved and cannot escape root
+        let data = fs.read_file("a/../../file.txt").await?;
+        assert_eq!(data, Some(b"root".to_vec()));
+        assert!(fs.stat("../../file.txt").await?.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_structured_errors() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        fs.mkdir("/dir").await?;
+        fs.write_file("/file.txt", b"data").await?;
+
+        let missing = |err| matches!(err, AgentFsError::NotFound(_));
+        assert!(missing(fs.mkdir("/missing/dir").await.unwrap_err()));
+        assert!(missing(
+            fs.write_file("/missing/f", b"x").await.unwrap_err()
+        ));
+        assert!(missing(fs.chmod("/missing", 0o644).await.unwrap_err()));
+        assert!(missing(
+            fs.chown("/missing", Some(1), None).await.unwrap_err()
+        ));
+        assert!(missing(fs.truncate("/missing", 0).await.unwrap_err()));
+        assert!(missing(fs.remove("/missing").await.unwrap_err()));
+        assert!(missing(fs.link("/missing", "/new").await.unwrap_err()));
+        assert!(missing(fs.open("/missing").await.err().unwrap()));
+
+        let exists = |err| matches!(err, AgentFsError::AlreadyExists(_));
+        assert!(exists(fs.mkdir("/dir").await.unwrap_err()));
+        assert!(exists(fs.link("/file.txt", "/dir").await.unwrap_err()));
+
+        let err = fs.mkdir("/file.txt/sub").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::Fs(FsError::NotADirectory)));
+        let err = fs.link("/dir", "/dir2").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::Fs(FsError::IsADirectory)));
+        let err = fs.remove("/").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::Fs(FsError::RootOperation)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_nul_byte_in_path_rejected() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+
+        let err = fs.write_file("/a\0b", b"data").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::Fs(FsError::InvalidPath)));
+        assert!(fs.mkdir("/dir\0").await.is_err());
+        assert!(fs.stat("/a\0b").await.is_err());
+        assert!(fs.readdir("/").await?.unwrap().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_case_insensitive_filenames() -> Result<()> {
+        let dir = tempdir()?;
+        let db_path = dir.path().join("test.db");
+        let db = Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await?;
+        let options = FilenameOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let fs = AgentFS::from_connection_with_filenames(Arc::new(db.connect()?), options).await?;
+
+        fs.mkdir("/Docs").await?;
+        fs.write_file("/Docs/ReadMe.txt", b"hello").await?;
+
+        // Lookups ignore case
+        let data = fs.read_file("/docs/README.TXT").await?;
+        assert_eq!(data, Some(b"hello".to_vec()));
+        assert!(fs.mkdir("/DOCS").await.is_err());
+
+        // Writing with different case updates the existing entry
+        fs.write_file("/DOCS/readme.txt", b"updated").await?;
+        let entries = fs.readdir("/docs").await?.unwrap();
+        assert_eq!(entries, vec!["ReadMe.txt"]);
+        assert_eq!(
+            fs.read_file("/Docs/ReadMe.txt").await?,
+            Some(b"updated".to_vec())
+        );
+
+        // Case-only rename changes the displayed name
+        fs.rename("/docs/readme.txt", "/docs/README.txt").await?;
+        let entries = fs.readdir("/Docs").await?.unwrap();
+        assert_eq!(entries, vec!["README.txt"]);
+
+        // The option is persisted for later opens
+        let fs = AgentFS::from_connection(Arc::new(db.connect()?)).await?;
+        assert!(fs.filename_options().case_insensitive);
+        assert!(fs.stat("/docs/readme.TXT").await?.is_some());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_case_insensitive_non_ascii_filenames() -> Result<()> {
+        let dir = tempdir()?;
+        let db_path = dir.path().join("test.db");
+        let db = Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await?;
+        let conn = Arc::new(db.connect()?);
+        let fs = AgentFS::from_connection(conn.clone()).await?;
+        fs.write_file("/Été.txt", b"summer").await?;
+        assert!(fs.stat("/été.txt").await?.is_none());
+
+        // Entries written before the key column existed have no key
+        conn.execute("UPDATE fs_dentry SET name_key = NULL", ())
+            .await?;
+
+        let options = FilenameOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        let fs = AgentFS::from_connection_with_filenames(conn, options).await?;
+        assert_eq!(fs.read_file("/été.txt").await?, Some(b"summer".to_vec()));
+        // Decomposed É matches too
+        assert!(fs.stat("/E\u{301}TÉ.TXT").await?.is_some());
+        assert!(fs.create_new("/ÉTÉ.txt", b"winter").await.is_err());
+
+        fs.rename("/été.txt", "/ÉTÉ.txt").await?;
+        assert_eq!(fs.readdir("/").await?.unwrap(), vec!["ÉTÉ.txt"]);
+        fs.remove("/été.TXT").await?;
+        assert!(fs.readdir("/").await?.unwrap().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_name_key_unique_index() -> Result<()> {
+        let dir = tempdir()?;
+        let db_path = dir.path().join("test.db");
+        let db = Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await?;
+        let conn = Arc::new(db.connect()?);
+
+        // Case-sensitive names may differ only in case
+        let fs = AgentFS::from_connection(conn.clone()).await?;
+        fs.write_file("/a.txt", b"lower").await?;
+        fs.write_file("/A.txt", b"upper").await?;
+
+        // Such a directory can't switch to case-insensitive matching
+        let options = FilenameOptions {
+            case_insensitive: true,
+            ..Default::default()
+        };
+        assert!(
+            AgentFS::from_connection_with_filenames(conn.clone(), options)
+                .await
+                .is_err()
+        );
+        fs.remove("/A.txt").await?;
+        let fs = AgentFS::from_connection_with_filenames(conn.clone(), options).await?;
+
+        // The index rejects a second entry with the same folded name
+        let root = fs.stat("/").await?.unwrap().ino;
+        let ino = fs.stat("/a.txt").await?.unwrap().ino;
+        let result = conn
+            .execute(
+                "INSERT INTO fs_dentry (name, parent_ino, ino, name_key) VALUES (?, ?, ?, ?)",
+                ("A.TXT", root, ino, "a.txt"),
+            )
+            .await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unicode_nfc_filenames() -> Result<()> {
+        let dir = tempdir()?;
+        let db_path = dir.path().join("test.db");
+        let db = Builder::new_local(db_path.to_str().unwrap())
+            .build()
+            .await?;
+        let options = FilenameOptions {
+            unicode_nfc: true,
+            ..Default::default()
+        };
+        let fs = AgentFS::from_connection_with_filenames(Arc::new(db.connect()?), options).await?;
+
+        // "e" + combining acute accent (NFD) and precomposed "é" (NFC)
+        fs.write_file("/cafe\u{301}.txt", b"coffee").await?;
+        let data = fs.read_file("/caf\u{e9}.txt").await?;
+        assert_eq!(data, Some(b"coffee".to_vec()));
+
+        let entries = fs.readdir("/").await?.unwrap();
+        assert_eq!(entries, vec!["caf\u{e9}.txt"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stat_ino_and_path_for_ino() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        fs.mkdir("/a").await?;
+        fs.write_file("/a/file.txt", b"data").await?;
+        let ino = fs.stat("/a/file.txt").await?.unwrap().ino();
+
+        assert_eq!(fs.stat_ino(ino).await?.unwrap().size, 4);
+        assert_eq!(fs.path_for_ino(ino).await?.as_deref(), Some("/a/file.txt"));
+        assert_eq!(fs.path_for_ino(ROOT_INO).await?.as_deref(), Some("/"));
+
+        // Identity survives renames, and hard links resolve to the first name
+        fs.mkdir("/b").await?;
+        fs.rename("/a/file.txt", "/b/moved.txt").await?;
+        fs.link("/b/moved.txt", "/a/link.txt").await?;
+        assert_eq!(fs.stat("/b/moved.txt").await?.unwrap().ino(), ino);
+        assert_eq!(fs.path_for_ino(ino).await?.as_deref(), Some("/b/moved.txt"));
+
+        fs.remove("/b/moved.txt").await?;
+        assert_eq!(fs.path_for_ino(ino).await?.as_deref(), Some("/a/link.txt"));
+        fs.remove("/a/link.txt").await?;
+        assert!(fs.stat_ino(ino).await?.is_none());
+        assert!(fs.path_for_ino(ino).await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_dir() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        let host = tempdir()?;
+        std::fs::create_dir_all(host.path().join("src/nested"))?;
+        std::fs::write(host.path().join("README.md"), b"readme")?;
+        std::fs::write(host.path().join("src/main.rs"), b"fn main() {}")?;
+        std::fs::write(host.path().join("src/nested/data.bin"), vec![7u8; 10_000])?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::os::unix::fs::symlink("src/main.rs", host.path().join("link"))?;
+            std::fs::set_permissions(
+                host.path().join("README.md"),
+                std::fs::Permissions::from_mode(0o600),
+            )?;
+        }
+
+        fs.write_file("/existing.txt", b"keep").await?;
+        let options = ImportOptions {
+            preserve_mode: true,
+        };
+        let summary = fs
+            .import_dir_with(host.path(), "/work/seed", options)
+            .await?;
+
+        assert_eq!(summary.files, 3);
+        assert_eq!(summary.directories, 2);
+        assert_eq!(summary.bytes, 6 + 12 + 10_000);
+        assert_eq!(
+            fs.read_file("/work/seed/src/main.rs").await?,
+            Some(b"fn main() {}".to_vec())
+        );
+        assert_eq!(
+            fs.read_file("/work/seed/src/nested/data.bin").await?,
+            Some(vec![7u8; 10_000])
+        );
+        assert_eq!(fs.read_file("/existing.txt").await?, Some(b"keep".to_vec()));
+        #[cfg(unix)]
+        {
+            assert_eq!(summary.symlinks, 1);
+            assert_eq!(
+                fs.readlink("/work/seed/link").await?.as_deref(),
+                Some("src/main.rs")
+            );
+            let readme = fs.stat("/work/seed/README.md").await?.unwrap();
+            assert_eq!(readme.permissions(), 0o600);
+        }
+
+        // Importing again overwrites files in place
+        std::fs::write(host.path().join("README.md"), b"updated")?;
+        fs.import_dir(host.path(), "/work/seed").await?;
+        assert_eq!(
+            fs.read_file("/work/seed/README.md").await?,
+            Some(b"updated".to_vec())
+        );
+
+        // A file in the way of the destination directory is an error
+        assert!(fs.import_dir(host.path(), "/existing.txt").await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_export_dir() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        fs.mkdir("/out").await?;
+        fs.mkdir("/out/sub").await?;
+        fs.write_file("/out/a.txt", b"hello").await?;
+        fs.write_file("/out/sub/big.bin", &vec![3u8; 10_000])
+            .await?;
+        fs.chmod("/out/a.txt", 0o600).await?;
+        fs.symlink("a.txt", "/out/link").await?;
+        fs.symlink("/out", "/out/sub/loop").await?;
+        fs.symlink("/missing", "/out/dangling").await?;
+
+        let host = tempdir()?;
+        let summary = fs.export_dir("/out", host.path()).await?;
+        assert_eq!(summary.files, 2);
+        assert_eq!(summary.directories, 1);
+        assert_eq!(summary.symlinks, 3);
+        assert_eq!(summary.bytes, 10_005);
+        assert_eq!(std::fs::read(host.path().join("a.txt"))?, b"hello");
+        assert_eq!(
+            std::fs::read(host.path().join("sub/big.bin"))?,
+            vec![3u8; 10_000]
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(host.path().join("a.txt"))?
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+            assert_eq!(
+                std::fs::read_link(host.path().join("link"))?,
+                Path::new("a.txt")
+            );
+        }
+
+        // Following symlinks writes their targets and skips loops and dangling links
+        let host = tempdir()?;
+        let options = ExportOptions {
+            follow_symlinks: true,
+        };
+        let summary = fs.export_dir_with("/out", host.path(), options).await?;
+        assert_eq!(summary.files, 3);
+        assert_eq!(summary.symlinks, 0);
+        assert_eq!(summary.skipped, 2);
+        assert!(!std::fs::symlink_metadata(host.path().join("link"))?
+            .file_type()
+            .is_symlink());
+        assert_eq!(std::fs::read(host.path().join("link"))?, b"hello");
+
+        assert!(fs.export_dir("/out/a.txt", host.path()).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_zstd_compression() -> Result<()> {
+        let (mut fs, _dir) = create_test_fs().await?;
+        fs.set_compression(Compression::Zstd(3));
+        let chunk_size = fs.chunk_size();
+
+        let text: Vec<u8> = b"compressible text "
+            .iter()
+            .copied()
+            .cycle()
+            .take(chunk_size * 3 + 100)
+            .collect();
+        fs.write_file("/text.txt", &text).await?;
+        // Pseudo-random bytes do not shrink and are stored raw
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let noise: Vec<u8> = (0..chunk_size)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        fs.write_file("/noise.bin", &noise).await?;
+        fs.write_file("/small.txt", b"tiny").await?;
+
+        assert_eq!(fs.read_file("/text.txt").await?.unwrap(), text);
+        assert_eq!(fs.read_file("/noise.bin").await?.unwrap(), noise);
+        assert_eq!(fs.read_file("/small.txt").await?.unwrap(), b"tiny");
+        assert_eq!(
+            fs.pread("/text.txt", chunk_size as u64 - 5, 10)
+                .await?
+                .unwrap(),
+            text[chunk_size - 5..chunk_size + 5]
+        );
+
+        // Partial writes and truncation rewrite compressed blocks
+        fs.pwrite("/text.txt", 10, b"XYZ").await?;
+        let mut expected = text.clone();
+        expected[10..13].copy_from_slice(b"XYZ");
+        fs.truncate("/text.txt", chunk_size as u64 + 700).await?;
+        expected.truncate(chunk_size + 700);
+        assert_eq!(fs.read_file("/text.txt").await?.unwrap(), expected);
+
+        let summary = fs.usage_summary().await?;
+        assert_eq!(summary.blocks, 4);
+        // Both text blocks compress; the noise and tiny blocks stay raw
+        assert_eq!(summary.compressed_blocks, 2);
+        assert_eq!(
+            summary.logical_bytes,
+            (expected.len() + noise.len() + 4) as u64
+        );
+        assert!(summary.stored_bytes < summary.logical_bytes);
+        assert!(summary.compression_ratio() > 1.0);
+
+        // Reads do not depend on the current mode
+        fs.set_compression(Compression::None);
+        assert_eq!(fs.read_file("/text.txt").await?.unwrap(), expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_uncompressed_schema_is_upgraded() -> Result<()> {
+        let dir = tempdir()?;
+        let db_path = dir.path().join("old.db");
+        {
+            let db = Builder::new_local(db_path.to_str().unwrap())
+                .build()
+                .await?;
+            let conn = db.connect()?;
+            conn.execute(
+                "CREATE TABLE fs_data (
+                    ino INTEGER NOT NULL,
+                    chunk_index INTEGER NOT NULL,
+                    data BLOB NOT NULL,
+                    PRIMARY KEY (ino, chunk_index)
+                )",
+                (),
+            )
+            .await?;
+        }
+
+        let fs = AgentFS::new(db_path.to_str().unwrap()).await?;
+        fs.write_file("/a.txt", b"hello").await?;
+        assert_eq!(fs.read_file("/a.txt").await?.unwrap(), b"hello");
+        let summary = fs.usage_summary().await?;
+        assert_eq!(summary.blocks, 1);
+        assert_eq!(summary.compressed_blocks, 0);
+        assert_eq!(summary.compression_ratio(), 1.0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_remove_glob() -> Result<()> {
+        let (fs, _dir) = create_test_fs().await?;
+        fs.mkdir("/tmp").await?;
+        fs.mkdir("/tmp/sub").await?;
+        fs.mkdir("/tmp/old.log").await?;
+        fs.write_file("/tmp/a.log", b"a").await?;
+        fs.write_file("/tmp/b.log", b"b").await?;
+        fs.write_file("/tmp/keep.txt", b"k").await?;
+        fs.write_file("/tmp/sub/c.log", b"c").await?;
+        fs.write_file("/tmp/old.log/d.txt", b"d").await?;
+
+        // Only files directly in /tmp, directories are left alone
+        assert_eq!(fs.remove_glob("/tmp/*.log", false).await?, 2);
+        assert!(fs.stat("/tmp/a.log").await?.is_none());
+        assert!(fs.stat("/tmp/b.log").await?.is_none());
+        assert!(fs.stat("/tmp/keep.txt").await?.is_some());
+        assert!(fs.stat("/tmp/sub/c.log").await?.is_some());
+        assert!(fs.stat("/tmp/old.log").await?.is_some());
+
+        // Matching directories are removed with their contents when asked
+        assert_eq!(fs.remove_glob("/tmp/**/*.log", true).await?, 2);
+        assert!(fs.stat("/tmp/sub/c.log").await?.is_none());
+        assert!(fs.stat("/tmp/old.log").await?.is_none());
+        assert!(fs.stat("/tmp/sub").await?.is_some());
+        assert!(fs.stat("/tmp/keep.txt").await?.is_some());
+
+        assert_eq!(fs.remove_glob("/nothing/*", false).await?, 0);
+        assert!(fs.remove_glob("tmp/*", false).await.is_err());
+        Ok(())
+    }
 }
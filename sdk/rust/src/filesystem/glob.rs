@@ -0,0 +1,92 @@
+//! Glob patterns over filesystem paths
+//!
+//! Patterns are absolute paths matched component by component: `*` and `?`
+//! match within a single component and `**` matches any number of components,
+//! including none. There are no character classes or escapes.
+
+use anyhow::Result;
+
+/// A parsed glob pattern, e.g. `/tmp/**/*.log`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    components: Vec<String>,
+}
+
+impl Glob {
+    /// Parse an absolute glob pattern
+    pub fn new(pattern: &str) -> Result<Self> {
+        if !pattern.starts_with('/') {
+            anyhow::bail!("Glob pattern '{}' must be absolute", pattern);
+        }
+        let components = pattern
+            .split('/')
+            .filter(|part| !part.is_empty() && *part != ".")
+            .map(str::to_string)
+            .collect();
+        Ok(Self { components })
+    }
+
+    /// Pattern components, one per path component
+    pub(crate) fn components(&self) -> &[String] {
+        &self.components
+    }
+
+    /// Check if an absolute path matches the pattern
+    pub fn matches(&self, path: &str) -> bool {
+        let path: Vec<&str> = path
+            .split('/')
+            .filter(|part| !part.is_empty() && *part != ".")
+            .collect();
+        glob_match(&self.components, &path)
+    }
+}
+
+/// Match path components against glob components
+fn glob_match(glob: &[String], path: &[&str]) -> bool {
+    match glob.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| glob_match(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((name, path_rest)) => component_match(first, name) && glob_match(rest, path_rest),
+            None => false,
+        },
+    }
+}
+
+/// Match one path component against a pattern with `*` and `?` wildcards
+pub(crate) fn component_match(pattern: &str, name: &str) -> bool {
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match pattern.split_first() {
+            None => name.is_empty(),
+            Some(('*', rest)) => (0..=name.len()).any(|skip| matches(rest, &name[skip..])),
+            Some(('?', rest)) => !name.is_empty() && matches(rest, &name[1..]),
+            Some((c, rest)) => name.first() == Some(c) && matches(rest, &name[1..]),
+        }
+    }
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    matches(&pattern, &name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        let glob = Glob::new("/tmp/*.log").unwrap();
+        assert!(glob.matches("/tmp/a.log"));
+        assert!(glob.matches("/tmp/.log"));
+        assert!(!glob.matches("/tmp/a.txt"));
+        assert!(!glob.matches("/tmp/sub/a.log"));
+
+        let glob = Glob::new("/**/cache/?.bin").unwrap();
+        assert!(glob.matches("/cache/a.bin"));
+        assert!(glob.matches("/x/y/cache/é.bin"));
+        assert!(!glob.matches("/x/cache/ab.bin"));
+
+        assert!(Glob::new("tmp/*.log").is_err());
+    }
+}
@@ -0,0 +1,237 @@
+//! Async I/O handles for open files
+//!
+//! [`FileHandle`] wraps a [`BoxedFile`] with a file offset and implements
+//! tokio's `AsyncRead`, `AsyncWrite` and `AsyncSeek`, so stored files can be
+//! used with `tokio::io::copy`, `BufReader` and the other tokio utilities
+//! instead of reading or writing whole files at once.
+
+use super::{BoxedFile, FsError};
+use std::future::Future;
+use std::io::{self, SeekFrom};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncWrite, ReadBuf};
+
+/// How [`FileSystem::open_with`](super::FileSystem::open_with) opens a file
+///
+/// The default opens an existing file for reading only.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenOptions {
+    /// Allow reads through the handle
+    pub read: bool,
+    /// Allow writes through the handle
+    pub write: bool,
+    /// Write at the end of the file, whatever the offset. Implies `write`.
+    pub append: bool,
+    /// Create the file if it doesn't exist
+    pub create: bool,
+    /// Create the file, failing with [`FsError::AlreadyExists`] if anything
+    /// exists at the path (like `O_CREAT | O_EXCL`)
+    pub create_new: bool,
+    /// Truncate the file to zero length when it is opened
+    pub truncate: bool,
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self {
+            read: true,
+            write: false,
+            append: false,
+            create: false,
+            create_new: false,
+            truncate: false,
+        }
+    }
+}
+
+impl OpenOptions {
+    fn writable(&self) -> bool {
+        self.write || self.append
+    }
+}
+
+type IoFuture<T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send>>;
+
+/// Operation in progress on a [`FileHandle`]
+enum State {
+    Idle,
+    /// Reading at the offset; resolves to the bytes read
+    Reading(IoFuture<Vec<u8>>),
+    /// Writing; resolves to the offset after the written bytes
+    Writing(IoFuture<u64>),
+    /// Seeking; resolves to the new offset
+    Seeking(IoFuture<u64>),
+}
+
+/// Convert an SDK error, keeping the errno of filesystem errors
+fn io_error(err: anyhow::Error) -> io::Error {
+    match err.downcast_ref::<FsError>() {
+        Some(fs_err) => io::Error::from_raw_os_error(fs_err.to_errno()),
+        None => io::Error::other(err),
+    }
+}
+
+/// An open file with a current offset, usable as an async stream
+///
+/// Each read or write is a positioned [`File::pread`](super::File::pread) or
+/// [`File::pwrite`](super::File::pwrite) at the offset, so nothing is
+/// buffered and `flush` has nothing to do. Wrap the handle in a
+/// `tokio::io::BufReader` or `BufWriter` to batch small reads and writes.
+pub struct FileHandle {
+    file: BoxedFile,
+    options: OpenOptions,
+    offset: u64,
+    state: State,
+}
+
+impl FileHandle {
+    /// Wrap an open file, starting at offset 0
+    pub fn new(file: BoxedFile, options: OpenOptions) -> Self {
+        Self {
+            file,
+            options,
+            offset: 0,
+            state: State::Idle,
+        }
+    }
+
+    /// The underlying file
+    pub fn file(&self) -> &BoxedFile {
+        &self.file
+    }
+
+    /// Current offset of the handle
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Options the handle was opened with
+    pub fn options(&self) -> OpenOptions {
+        self.options
+    }
+
+    /// Wait for a started write or seek to finish, updating the offset
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let result = match &mut self.state {
+            State::Idle | State::Reading(_) => return Poll::Ready(Ok(())),
+            State::Writing(future) | State::Seeking(future) => ready!(future.as_mut().poll(cx)),
+        };
+        self.state = State::Idle;
+        self.offset = result?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for FileHandle {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        if !this.options.read {
+            return Poll::Ready(Err(io::Error::from_raw_os_error(libc::EBADF)));
+        }
+        ready!(this.poll_pending(cx))?;
+
+        if let State::Idle = this.state {
+            let file = this.file.clone();
+            let offset = this.offset;
+            let len = buf.remaining() as u64;
+            this.state = State::Reading(Box::pin(async move {
+                // pread() pads with zeros past the end, so stop at the size
+                let size = file.fstat().await.map_err(io_error)?.size as u64;
+                let len = len.min(size.saturating_sub(offset));
+                if len == 0 {
+                    return Ok(Vec::new());
+                }
+                file.pread(offset, len).await.map_err(io_error)
+            }));
+        }
+
+        let State::Reading(future) = &mut this.state else {
+            unreachable!("pending writes and seeks were completed above");
+        };
+        let result = ready!(future.as_mut().poll(cx));
+        this.state = State::Idle;
+        let data = result?;
+
+        // The buffer may have shrunk since the read started
+        let n = data.len().min(buf.remaining());
+        buf.put_slice(&data[..n]);
+        this.offset += n as u64;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for FileHandle {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        if !this.options.writable() {
+            return Poll::Ready(Err(io::Error::from_raw_os_error(libc::EBADF)));
+        }
+
+        // A pending write is the one for `buf`, resubmitted after Pending
+        if !matches!(this.state, State::Writing(_)) {
+            ready!(this.poll_pending(cx))?;
+            let file = this.file.clone();
+            let offset = this.offset;
+            let append = this.options.append;
+            let data = buf.to_vec();
+            this.state = State::Writing(Box::pin(async move {
+                let offset = if append {
+                    file.fstat().await.map_err(io_error)?.size as u64
+                } else {
+                    offset
+                };
+                file.pwrite(offset, &data).await.map_err(io_error)?;
+                Ok(offset + data.len() as u64)
+            }));
+        }
+
+        ready!(this.poll_pending(cx))?;
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_pending(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_pending(cx)
+    }
+}
+
+impl AsyncSeek for FileHandle {
+    fn start_seek(mut self: Pin<&mut Self>, position: SeekFrom) -> io::Result<()> {
+        // An unfinished read was abandoned by its caller and can be dropped
+        if matches!(self.state, State::Writing(_) | State::Seeking(_)) {
+            return Err(io::Error::other(
+                "other file operation is pending, call poll_complete before start_seek",
+            ));
+        }
+
+        let file = self.file.clone();
+        let current = self.offset;
+        self.state = State::Seeking(Box::pin(async move {
+            let (base, delta) = match position {
+                SeekFrom::Start(offset) => return Ok(offset),
+                SeekFrom::Current(delta) => (current, delta),
+                SeekFrom::End(delta) => (file.fstat().await.map_err(io_error)?.size as u64, delta),
+            };
+            base.checked_add_signed(delta)
+                .ok_or_else(|| io::Error::from_raw_os_error(libc::EINVAL))
+        }));
+        Ok(())
+    }
+
+    fn poll_complete(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<u64>> {
+        ready!(self.poll_pending(cx))?;
+        Poll::Ready(Ok(self.offset))
+    }
+}
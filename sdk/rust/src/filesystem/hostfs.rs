@@ -8,7 +8,9 @@ use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 #[cfg(unix)]
 use libc;
 
-use super::{BoxedFile, DirEntry, File, FileSystem, FilesystemStats, Stats};
+use super::{
+    validate_path, BoxedFile, DirEntry, File, FileSystem, FilesystemStats, FsError, Stats,
+};
 use std::sync::Arc;
 
 /// A filesystem backed by a host directory (passthrough)
@@ -119,12 +121,15 @@ impl HostFS {
     }
 
     /// Resolve a virtual path to a host path
-    fn resolve_path(&self, path: &str) -> PathBuf {
+    ///
+    /// Fails for paths that would escape the root directory.
+    fn resolve_path(&self, path: &str) -> Result<PathBuf> {
+        validate_path(path)?;
         let normalized = path.trim_start_matches('/');
         if normalized.is_empty() {
-            self.root.clone()
+            Ok(self.root.clone())
         } else {
-            self.root.join(normalized)
+            Ok(self.root.join(normalized))
         }
     }
 
@@ -155,7 +160,7 @@ impl HostFS {
 #[async_trait]
 impl FileSystem for HostFS {
     async fn stat(&self, path: &str) -> Result<Option<Stats>> {
-        let full_path = self.resolve_path(path);
+        let full_path = self.resolve_path(path)?;
         match fs::metadata(&full_path).await {
             Ok(metadata) => Ok(Some(Self::metadata_to_stats(&metadata, path))),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
@@ -164,7 +169,7 @@ impl FileSystem for HostFS {
     }
 
     async fn lstat(&self, path: &str) -> Result<Option<Stats>> {
-        let full_path = self.resolve_path(path);
+        let full_path = self.resolve_path(path)?;
         match fs::symlink_metadata(&full_path).await {
             Ok(metadata) => Ok(Some(Self::metadata_to_stats(&metadata, path))),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
@@ -173,7 +178,7 @@ impl FileSystem for HostFS {
     }
 
     async fn read_file(&self, path: &str) -> Result<Option<Vec<u8>>> {
-        let full_path = self.resolve_path(path);
+        let full_path = self.resolve_path(path)?;
         match fs::read(&full_path).await {
             Ok(data) => Ok(Some(data)),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
@@ -182,13 +187,31 @@ impl FileSystem for HostFS {
     }
 
     async fn write_file(&self, path: &str, data: &[u8]) -> Result<()> {
-        let full_path = self.resolve_path(path);
+        let full_path = self.resolve_path(path)?;
         fs::write(&full_path, data).await?;
         Ok(())
     }
 
+    async fn create_new(&self, path: &str, data: &[u8]) -> Result<()> {
+        let full_path = self.resolve_path(path)?;
+        let mut file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&full_path)
+            .await
+        {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(FsError::AlreadyExists.into())
+            }
+            Err(e) => return Err(e.into()),
+        };
+        file.write_all(data).await?;
+        Ok(())
+    }
+
     async fn readdir(&self, path: &str) -> Result<Option<Vec<String>>> {
-        let full_path = self.resolve_path(path);
+        let full_path = self.resolve_path(path)?;
         let mut entries = Vec::new();
 
         let mut dir = match fs::read_dir(&full_path).await {
@@ -219,7 +242,7 @@ impl FileSystem for HostFS {
     }
 
     async fn readdir_plus(&self, path: &str) -> Result<Option<Vec<DirEntry>>> {
-        let full_path = self.resolve_path(path);
+        let full_path = self.resolve_path(path)?;
         let mut entries = Vec::new();
 
         let mut dir = match fs::read_dir(&full_path).await {
@@ -263,13 +286,13 @@ impl FileSystem for HostFS {
     }
 
     async fn mkdir(&self, path: &str) -> Result<()> {
-        let full_path = self.resolve_path(path);
+        let full_path = self.resolve_path(path)?;
         fs::create_dir(&full_path).await?;
         Ok(())
     }
 
     async fn remove(&self, path: &str) -> Result<()> {
-        let full_path = self.resolve_path(path);
+        let full_path = self.resolve_path(path)?;
         let metadata = fs::symlink_metadata(&full_path).await?;
 
         if metadata.is_dir() {
@@ -283,34 +306,40 @@ impl FileSystem for HostFS {
     async fn chmod(&self, path: &str, mode: u32) -> Result<()> {
         use std::os::unix::fs::PermissionsExt;
 
-        let full_path = self.resolve_path(path);
+        let full_path = self.resolve_path(path)?;
         let permissions = std::fs::Permissions::from_mode(mode);
         fs::set_permissions(&full_path, permissions).await?;
         Ok(())
     }
 
+    async fn chown(&self, path: &str, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        let full_path = self.resolve_path(path)?;
+        std::os::unix::fs::chown(&full_path, uid, gid)?;
+        Ok(())
+    }
+
     async fn rename(&self, from: &str, to: &str) -> Result<()> {
-        let from_path = self.resolve_path(from);
-        let to_path = self.resolve_path(to);
+        let from_path = self.resolve_path(from)?;
+        let to_path = self.resolve_path(to)?;
         fs::rename(&from_path, &to_path).await?;
         Ok(())
     }
 
     async fn symlink(&self, target: &str, linkpath: &str) -> Result<()> {
-        let full_path = self.resolve_path(linkpath);
+        let full_path = self.resolve_path(linkpath)?;
         tokio::fs::symlink(target, &full_path).await?;
         Ok(())
     }
 
     async fn link(&self, oldpath: &str, newpath: &str) -> Result<()> {
-        let old_full_path = self.resolve_path(oldpath);
-        let new_full_path = self.resolve_path(newpath);
+        let old_full_path = self.resolve_path(oldpath)?;
+        let new_full_path = self.resolve_path(newpath)?;
         tokio::fs::hard_link(&old_full_path, &new_full_path).await?;
         Ok(())
     }
 
     async fn readlink(&self, path: &str) -> Result<Option<String>> {
-        let full_path = self.resolve_path(path);
+        let full_path = self.resolve_path(path)?;
         match fs::read_link(&full_path).await {
             Ok(target) => Ok(Some(target.to_string_lossy().to_string())),
             Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
@@ -349,7 +378,7 @@ impl FileSystem for HostFS {
     }
 
     async fn open(&self, path: &str) -> Result<BoxedFile> {
-        let full_path = self.resolve_path(path);
+        let full_path = self.resolve_path(path)?;
         // Verify the file exists
         if !full_path.exists() {
             anyhow::bail!("File not found: {}", path);
@@ -386,6 +415,37 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_hostfs_create_new() -> Result<()> {
+        let dir = tempdir()?;
+        let fs = HostFS::new(dir.path())?;
+
+        fs.create_new("/lock", b"first").await?;
+        let err = fs.create_new("/lock", b"second").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FsError>(),
+            Some(FsError::AlreadyExists)
+        ));
+        assert_eq!(fs.read_file("/lock").await?.unwrap(), b"first");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hostfs_append_rotating() -> Result<()> {
+        let dir = tempdir()?;
+        let fs = HostFS::new(dir.path())?;
+
+        for line in [&b"one\n"[..], b"two\n", b"three\n"] {
+            fs.append_rotating("/app.log", line, 8, 1).await?;
+        }
+        assert_eq!(fs.read_file("/app.log").await?.unwrap(), b"three\n");
+        assert_eq!(fs.read_file("/app.log.1").await?.unwrap(), b"one\ntwo\n");
+        assert!(fs.stat("/app.log.2").await?.is_none());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_hostfs_mkdir_readdir() -> Result<()> {
         let dir = tempdir()?;
@@ -402,6 +462,15 @@ mod tests {
         let entries = fs.readdir("/subdir").await?.unwrap();
         assert_eq!(entries, vec!["a.txt", "b.txt"]);
 
+        // Page through it
+        let page = fs.readdir_paged("/subdir", None, 1).await?.unwrap();
+        assert_eq!(page, vec!["a.txt"]);
+        let page = fs
+            .readdir_paged("/subdir", Some("a.txt"), 10)
+            .await?
+            .unwrap();
+        assert_eq!(page, vec!["b.txt"]);
+
         Ok(())
     }
 
@@ -476,4 +545,27 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_hostfs_rejects_path_traversal() -> Result<()> {
+        let dir = tempdir()?;
+        std::fs::create_dir(dir.path().join("root"))?;
+        std::fs::write(dir.path().join("secret.txt"), b"secret")?;
+        let fs = HostFS::new(dir.path().join("root"))?;
+
+        let err = fs.read_file("/../secret.txt").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FsError>(),
+            Some(FsError::PathTraversal)
+        ));
+        assert!(fs.write_file("a/../../escape.txt", b"x").await.is_err());
+        assert!(fs.stat("/a\0b").await.is_err());
+
+        // `..` that stays inside the root is fine
+        fs.mkdir("/sub").await?;
+        fs.write_file("/sub/../inside.txt", b"ok").await?;
+        assert_eq!(fs.read_file("/inside.txt").await?.unwrap(), b"ok");
+
+        Ok(())
+    }
 }
@@ -1,17 +1,24 @@
 pub mod agentfs;
+pub mod glob;
+pub mod handle;
 #[cfg(unix)]
 pub mod hostfs;
+pub mod mode;
 pub mod overlayfs;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use std::sync::Arc;
+use std::time::SystemTime;
 use thiserror::Error;
 
 // Re-export implementations
 pub use agentfs::AgentFS;
+pub use glob::Glob;
+pub use handle::{FileHandle, OpenOptions};
 #[cfg(unix)]
 pub use hostfs::HostFS;
+pub use mode::{FileType, S_IFDIR, S_IFLNK, S_IFMT, S_IFREG};
 pub use overlayfs::OverlayFS;
 
 /// Filesystem-specific errors with errno semantics
@@ -46,6 +53,12 @@ pub enum FsError {
 
     #[error("Cannot rename directory into its own subdirectory")]
     InvalidRename,
+
+    #[error("Path escapes the root directory")]
+    PathTraversal,
+
+    #[error("File too large")]
+    FileTooLarge,
 }
 
 impl FsError {
@@ -62,20 +75,85 @@ impl FsError {
             FsError::RootOperation => libc::EPERM,
             FsError::SymlinkLoop => libc::ELOOP,
             FsError::InvalidRename => libc::EINVAL,
+            FsError::PathTraversal => libc::EACCES,
+            FsError::FileTooLarge => libc::EFBIG,
         }
     }
 }
 
-// File types for mode field
-pub const S_IFMT: u32 = 0o170000; // File type mask
-pub const S_IFREG: u32 = 0o100000; // Regular file
-pub const S_IFDIR: u32 = 0o040000; // Directory
-pub const S_IFLNK: u32 = 0o120000; // Symbolic link
+/// Validate an untrusted filesystem path.
+///
+/// Rejects paths containing NUL bytes (`FsError::InvalidPath`) and paths whose
+/// `..` components would climb above the root (`FsError::PathTraversal`).
+/// Paths are interpreted relative to the filesystem root whether or not they
+/// start with `/`.
+pub fn validate_path(path: &str) -> std::result::Result<(), FsError> {
+    if path.contains('\0') {
+        return Err(FsError::InvalidPath);
+    }
+
+    let mut depth = 0usize;
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                depth = depth.checked_sub(1).ok_or(FsError::PathTraversal)?;
+            }
+            _ => depth += 1,
+        }
+    }
+    Ok(())
+}
 
 // Default permissions
 pub const DEFAULT_FILE_MODE: u32 = S_IFREG | 0o644; // Regular file, rw-r--r--
 pub const DEFAULT_DIR_MODE: u32 = S_IFDIR | 0o755; // Directory, rwxr-xr-x
 
+/// Symlinks followed while resolving a path before failing with `FsError::SymlinkLoop`
+pub const DEFAULT_MAX_SYMLINK_DEPTH: u32 = 40; // Same as Linux's MAXSYMLINKS
+
+/// How file blocks are compressed when they are written
+///
+/// Each stored block records whether it is compressed, so reads never depend
+/// on this setting and databases written with any mode stay readable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    /// Store blocks as they are
+    #[default]
+    None,
+    /// Compress blocks with zstd at the given level (1-22; 3 is zstd's default).
+    /// Small blocks and blocks that do not shrink are still stored raw.
+    Zstd(i32),
+}
+
+/// Filename matching behaviour for a filesystem
+///
+/// Both options are opt-in and, once enabled for an agent, are persisted in
+/// its database so later opens keep honoring them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FilenameOptions {
+    /// Match names case-insensitively, comparing their Unicode lowercase
+    /// NFC forms. Entries keep the case they were created with.
+    pub case_insensitive: bool,
+    /// Normalize names to Unicode NFC before storing or looking them up.
+    pub unicode_nfc: bool,
+}
+
+/// Permissions and ownership of the root directory
+///
+/// Applied each time the filesystem is opened and persisted in the root inode.
+/// Fields left as `None` keep their stored value; a new filesystem's root starts
+/// out as `0755`, owned by `0:0`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RootOptions {
+    /// Permission bits of the root directory (file type bits are ignored).
+    pub mode: Option<u32>,
+    /// Owner of the root directory.
+    pub uid: Option<u32>,
+    /// Group of the root directory.
+    pub gid: Option<u32>,
+}
+
 /// File statistics
 #[derive(Debug, Clone)]
 pub struct Stats {
@@ -99,6 +177,165 @@ pub struct FilesystemStats {
     pub bytes_used: u64,
 }
 
+/// Space taken by file contents, before and after compression
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsageSummary {
+    /// Apparent size of all regular files
+    pub logical_bytes: u64,
+    /// Bytes of block data stored in the database
+    pub stored_bytes: u64,
+    /// Number of stored blocks
+    pub blocks: u64,
+    /// Number of stored blocks that are compressed
+    pub compressed_blocks: u64,
+}
+
+impl UsageSummary {
+    /// Ratio of logical to stored bytes (1.0 when nothing is stored)
+    pub fn compression_ratio(&self) -> f64 {
+        if self.stored_bytes == 0 {
+            1.0
+        } else {
+            self.logical_bytes as f64 / self.stored_bytes as f64
+        }
+    }
+}
+
+/// How one stored block of a file is laid out, as reported by [`AgentFS::block_map`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockInfo {
+    /// Index of the block in the file, in units of the chunk size
+    pub chunk_index: u64,
+    /// Offset in the file of the block's first byte
+    pub offset: u64,
+    /// Bytes of file data the block holds
+    pub length: u64,
+    /// Bytes the block takes in the database
+    pub stored_length: u64,
+    /// Whether the block is stored compressed
+    pub compressed: bool,
+}
+
+/// Options for [`AgentFS::import_dir_with`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportOptions {
+    /// Copy the permission bits of host files and directories.
+    /// Otherwise imported entries get the default modes.
+    pub preserve_mode: bool,
+}
+
+/// What an import copied into the filesystem
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportSummary {
+    pub files: u64,
+    pub directories: u64,
+    pub symlinks: u64,
+    /// Total size of the imported files
+    pub bytes: u64,
+}
+
+/// Options for [`AgentFS::export_dir_with`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportOptions {
+    /// Write the files and directories symlinks point to instead of
+    /// recreating the symlinks
+    pub follow_symlinks: bool,
+}
+
+/// What an export wrote to the host
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportSummary {
+    pub files: u64,
+    pub directories: u64,
+    pub symlinks: u64,
+    /// Total size of the exported files
+    pub bytes: u64,
+    /// Dangling symlinks and directory cycles skipped while following symlinks
+    pub skipped: u64,
+}
+
+/// Kind of mutation recorded in the filesystem journal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalOp {
+    /// A file's contents were replaced, by `write_file`, `write_file_from`,
+    /// `create_new` or an import
+    Write,
+    /// Data was appended to a file
+    Append,
+    /// Data was written at an offset within a file
+    Pwrite,
+    /// A file was truncated or extended; `size` is its new length
+    Truncate,
+    /// An entry was renamed; the new path is in `target`
+    Rename,
+    /// A file, symlink or empty directory was removed
+    Remove,
+    /// A directory was created
+    Mkdir,
+    /// A symlink was created; what it points to is in `target`
+    Symlink,
+    /// A hard link was created; the new path is in `target`
+    Link,
+    /// Permissions were changed
+    Chmod,
+    /// Ownership was changed
+    Chown,
+}
+
+impl JournalOp {
+    /// Name stored in the `op` column of `fs_journal`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JournalOp::Write => "write",
+            JournalOp::Append => "append",
+            JournalOp::Pwrite => "pwrite",
+            JournalOp::Truncate => "truncate",
+            JournalOp::Rename => "rename",
+            JournalOp::Remove => "remove",
+            JournalOp::Mkdir => "mkdir",
+            JournalOp::Symlink => "symlink",
+            JournalOp::Link => "link",
+            JournalOp::Chmod => "chmod",
+            JournalOp::Chown => "chown",
+        }
+    }
+
+    /// Parse a name stored in `fs_journal`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "write" => Some(JournalOp::Write),
+            "append" => Some(JournalOp::Append),
+            "pwrite" => Some(JournalOp::Pwrite),
+            "truncate" => Some(JournalOp::Truncate),
+            "rename" => Some(JournalOp::Rename),
+            "remove" => Some(JournalOp::Remove),
+            "mkdir" => Some(JournalOp::Mkdir),
+            "symlink" => Some(JournalOp::Symlink),
+            "link" => Some(JournalOp::Link),
+            "chmod" => Some(JournalOp::Chmod),
+            "chown" => Some(JournalOp::Chown),
+            _ => None,
+        }
+    }
+}
+
+/// A mutation recorded in the filesystem journal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JournalEntry {
+    /// Position in the journal; later entries have larger ids
+    pub id: i64,
+    /// When the mutation happened, in seconds since the Unix epoch
+    pub timestamp: i64,
+    pub op: JournalOp,
+    pub path: String,
+    /// Destination of a rename or link, or the target of a symlink
+    pub target: Option<String>,
+    /// Size of the data written, or the new length after a truncate
+    pub size: Option<u64>,
+    /// Hex SHA-256 of the data written
+    pub hash: Option<String>,
+}
+
 /// Directory entry with full statistics
 #[derive(Debug, Clone)]
 pub struct DirEntry {
@@ -109,16 +346,36 @@ pub struct DirEntry {
 }
 
 impl Stats {
+    /// Inode number, stable across renames
+    pub fn ino(&self) -> i64 {
+        self.ino
+    }
+
+    /// Type of the file, or None for types AgentFS doesn't store
+    pub fn kind(&self) -> Option<FileType> {
+        FileType::from_mode(self.mode)
+    }
+
     pub fn is_file(&self) -> bool {
-        (self.mode & S_IFMT) == S_IFREG
+        self.kind() == Some(FileType::File)
     }
 
     pub fn is_directory(&self) -> bool {
-        (self.mode & S_IFMT) == S_IFDIR
+        self.kind() == Some(FileType::Directory)
     }
 
     pub fn is_symlink(&self) -> bool {
-        (self.mode & S_IFMT) == S_IFLNK
+        self.kind() == Some(FileType::Symlink)
+    }
+
+    /// File type name: "file", "directory", "symlink" or "other"
+    pub fn file_type(&self) -> &'static str {
+        self.kind().map_or("other", FileType::name)
+    }
+
+    /// Permission bits of the mode, without the file type
+    pub fn permissions(&self) -> u32 {
+        mode::permissions(self.mode)
     }
 }
 
@@ -165,11 +422,114 @@ pub trait FileSystem: Send + Sync {
     /// Write data to a file (creates or overwrites)
     async fn write_file(&self, path: &str, data: &[u8]) -> Result<()>;
 
+    /// Write data to a file, giving it the permission bits of `mode`
+    ///
+    /// The permissions (`mode & 0o7777`) are set whether the file is created
+    /// or overwritten; the file type is always a regular file.
+    ///
+    /// The default implementation is [`FileSystem::write_file`] followed by
+    /// [`FileSystem::chmod`] and is not atomic. `AgentFS` overrides it to
+    /// create the file with its mode in one transaction.
+    async fn write_file_mode(&self, path: &str, data: &[u8], mode: u32) -> Result<()> {
+        self.write_file(path, data).await?;
+        self.chmod(path, mode & 0o7777).await
+    }
+
+    /// Create a new file with `data`, like `open` with `O_CREAT | O_EXCL`
+    ///
+    /// Fails with [`FsError::AlreadyExists`] if anything exists at `path`.
+    /// Of several concurrent creators of the same path, exactly one succeeds,
+    /// which makes this suitable for lock files.
+    async fn create_new(&self, path: &str, data: &[u8]) -> Result<()>;
+
+    /// Append `data` to the end of a file, creating it if it doesn't exist
+    ///
+    /// The default implementation writes at the size reported by
+    /// [`FileSystem::stat`] and is not atomic. `AgentFS` overrides it to find
+    /// the end of the file in the same transaction as the write, so concurrent
+    /// appends never overwrite each other.
+    async fn append_file(&self, path: &str, data: &[u8]) -> Result<()> {
+        match self.stat(path).await? {
+            Some(stats) => {
+                let file = self.open(path).await?;
+                file.pwrite(stats.size as u64, data).await
+            }
+            None => self.write_file(path, data).await,
+        }
+    }
+
+    /// Append `data` to a log file, rotating it once it would exceed `max_bytes`
+    ///
+    /// Before an append that would take a non-empty `path` past `max_bytes`,
+    /// `path` is renamed to `path.1`, `path.1` to `path.2` and so on, and
+    /// whatever would land beyond `path.{keep}` is deleted.
+    ///
+    /// The default implementation is built from [`FileSystem::remove`],
+    /// [`FileSystem::rename`] and [`File::pwrite`] and is not atomic.
+    /// `AgentFS` overrides it to rotate and append in one transaction.
+    async fn append_rotating(
+        &self,
+        path: &str,
+        data: &[u8],
+        max_bytes: u64,
+        keep: usize,
+    ) -> Result<()> {
+        if let Some(stats) = self.stat(path).await? {
+            let size = stats.size as u64;
+            if size == 0 || size + data.len() as u64 <= max_bytes {
+                let file = self.open(path).await?;
+                return file.pwrite(size, data).await;
+            }
+
+            let rotated = |i: usize| {
+                if i == 0 {
+                    path.to_string()
+                } else {
+                    format!("{}.{}", path, i)
+                }
+            };
+            if self.lstat(&rotated(keep)).await?.is_some() {
+                self.remove(&rotated(keep)).await?;
+            }
+            for i in (1..=keep).rev() {
+                if self.lstat(&rotated(i - 1)).await?.is_some() {
+                    self.rename(&rotated(i - 1), &rotated(i)).await?;
+                }
+            }
+        }
+        self.write_file(path, data).await
+    }
+
     /// List directory contents
     ///
     /// Returns `Ok(None)` if the directory does not exist.
     async fn readdir(&self, path: &str) -> Result<Option<Vec<String>>>;
 
+    /// List up to `limit` directory entries that sort after `after`
+    ///
+    /// Entries are returned in the same sorted order as [`FileSystem::readdir`].
+    /// Pass the last name of one page as `after` to fetch the next; a page
+    /// shorter than `limit` is the last one.
+    ///
+    /// Returns `Ok(None)` if the directory does not exist.
+    async fn readdir_paged(
+        &self,
+        path: &str,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Option<Vec<String>>> {
+        Ok(self.readdir(path).await?.map(|entries| {
+            entries
+                .into_iter()
+                .filter(|name| match after {
+                    Some(after) => name.as_str() > after,
+                    None => true,
+                })
+                .take(limit)
+                .collect()
+        }))
+    }
+
     /// List directory contents with full statistics for each entry
     ///
     /// This is an optimized version of readdir that returns both entry names
@@ -178,6 +538,21 @@ pub trait FileSystem: Send + Sync {
     /// Returns `Ok(None)` if the directory does not exist.
     async fn readdir_plus(&self, path: &str) -> Result<Option<Vec<DirEntry>>>;
 
+    /// List directory contents as `(name, stats)` pairs
+    ///
+    /// Convenience wrapper around [`FileSystem::readdir_plus`] for callers that
+    /// prefer tuples. Use [`FileSystem::readdir`] when only names are needed.
+    ///
+    /// Returns `Ok(None)` if the directory does not exist.
+    async fn readdir_stats(&self, path: &str) -> Result<Option<Vec<(String, Stats)>>> {
+        Ok(self.readdir_plus(path).await?.map(|entries| {
+            entries
+                .into_iter()
+                .map(|entry| (entry.name, entry.stats))
+                .collect()
+        }))
+    }
+
     /// Create a directory
     async fn mkdir(&self, path: &str) -> Result<()>;
 
@@ -190,6 +565,11 @@ pub trait FileSystem: Send + Sync {
     /// but only the permission bits (lower 12 bits) will be modified.
     async fn chmod(&self, path: &str, mode: u32) -> Result<()>;
 
+    /// Change file ownership
+    ///
+    /// `None` leaves the uid or gid unchanged, like `-1` in `chown(2)`.
+    async fn chown(&self, path: &str, uid: Option<u32>, gid: Option<u32>) -> Result<()>;
+
     /// Rename/move a file or directory
     async fn rename(&self, from: &str, to: &str) -> Result<()>;
 
@@ -208,6 +588,30 @@ pub trait FileSystem: Send + Sync {
     /// Returns `Ok(None)` if the path does not exist.
     async fn readlink(&self, path: &str) -> Result<Option<String>>;
 
+    /// Get file statistics by inode number, without following symlinks
+    ///
+    /// Returns `Ok(None)` if the inode doesn't exist or the filesystem can't
+    /// look up inodes.
+    async fn stat_ino(&self, _ino: i64) -> Result<Option<Stats>> {
+        Ok(None)
+    }
+
+    /// Find a path that refers to an inode
+    ///
+    /// Returns `Ok(None)` if the inode doesn't exist or the filesystem can't
+    /// map inodes back to paths.
+    async fn path_for_ino(&self, _ino: i64) -> Result<Option<String>> {
+        Ok(None)
+    }
+
+    /// Get the journaled mutations made at or after `since`, oldest first
+    ///
+    /// Returns nothing if the filesystem doesn't keep a journal or journaling
+    /// is disabled.
+    async fn journal(&self, _since: SystemTime) -> Result<Vec<JournalEntry>> {
+        Ok(Vec::new())
+    }
+
     /// Get filesystem statistics
     async fn statfs(&self) -> Result<FilesystemStats>;
 
@@ -216,4 +620,29 @@ pub trait FileSystem: Send + Sync {
     /// The returned file handle can be used for efficient read/write/fsync
     /// operations without requiring path lookups on each operation.
     async fn open(&self, path: &str) -> Result<BoxedFile>;
+
+    /// Open a file as an async stream
+    ///
+    /// The returned [`FileHandle`] implements tokio's `AsyncRead`, `AsyncWrite`
+    /// and `AsyncSeek` on top of [`FileSystem::open`], so large files can be
+    /// streamed without holding them in memory. `options` decide whether the
+    /// file is created or truncated and which operations the handle allows.
+    async fn open_with(&self, path: &str, options: OpenOptions) -> Result<FileHandle> {
+        if options.create_new {
+            self.create_new(path, &[]).await?;
+        } else {
+            match self.stat(path).await? {
+                Some(stats) if stats.is_directory() => return Err(FsError::IsADirectory.into()),
+                Some(_) => {}
+                None if options.create => self.write_file(path, &[]).await?,
+                None => return Err(FsError::NotFound.into()),
+            }
+        }
+
+        let file = self.open(path).await?;
+        if options.truncate {
+            file.truncate(0).await?;
+        }
+        Ok(FileHandle::new(file, options))
+    }
 }
@@ -0,0 +1,117 @@
+//! Decoding of POSIX mode words
+//!
+//! Modes are stored as Linux encodes them in `st_mode`: the file type in the
+//! `S_IFMT` bits and the permissions, including setuid, setgid and sticky, in
+//! the low 12 bits.
+
+// File types for mode field
+pub const S_IFMT: u32 = 0o170000; // File type mask
+pub const S_IFREG: u32 = 0o100000; // Regular file
+pub const S_IFDIR: u32 = 0o040000; // Directory
+pub const S_IFLNK: u32 = 0o120000; // Symbolic link
+
+/// Permission bits of a mode, including setuid, setgid and sticky
+pub const PERMISSION_BITS: u32 = 0o7777;
+
+/// Type of file encoded in a mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileType {
+    File,
+    Directory,
+    Symlink,
+}
+
+impl FileType {
+    /// Decode the file type bits of `mode`
+    ///
+    /// Returns `None` for types AgentFS doesn't store, like FIFOs or devices.
+    pub fn from_mode(mode: u32) -> Option<Self> {
+        match mode & S_IFMT {
+            S_IFREG => Some(FileType::File),
+            S_IFDIR => Some(FileType::Directory),
+            S_IFLNK => Some(FileType::Symlink),
+            _ => None,
+        }
+    }
+
+    /// The `S_IFMT` bits of this type
+    pub fn to_mode(self) -> u32 {
+        match self {
+            FileType::File => S_IFREG,
+            FileType::Directory => S_IFDIR,
+            FileType::Symlink => S_IFLNK,
+        }
+    }
+
+    /// Name of this type: "file", "directory" or "symlink"
+    pub fn name(self) -> &'static str {
+        match self {
+            FileType::File => "file",
+            FileType::Directory => "directory",
+            FileType::Symlink => "symlink",
+        }
+    }
+
+    /// Type character in `ls -l` output
+    pub fn ls_char(self) -> char {
+        match self {
+            FileType::File => '-',
+            FileType::Directory => 'd',
+            FileType::Symlink => 'l',
+        }
+    }
+}
+
+/// Permission bits of `mode`, without the file type
+pub fn permissions(mode: u32) -> u32 {
+    mode & PERMISSION_BITS
+}
+
+/// `mode` with its permission bits replaced by those of `perm`
+///
+/// The file type of `mode` is kept; any type bits in `perm` are ignored.
+pub fn with_permissions(mode: u32, perm: u32) -> u32 {
+    (mode & S_IFMT) | permissions(perm)
+}
+
+/// Format a mode as in `ls -l`, e.g. `drwxr-xr-x`
+///
+/// Unknown file types are shown as `?`.
+pub fn format_mode(mode: u32) -> String {
+    let mut out = String::with_capacity(10);
+    out.push(FileType::from_mode(mode).map_or('?', FileType::ls_char));
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        out.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        out.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        out.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_type_round_trip() {
+        for file_type in [FileType::File, FileType::Directory, FileType::Symlink] {
+            assert_eq!(
+                FileType::from_mode(file_type.to_mode() | 0o644),
+                Some(file_type)
+            );
+        }
+        // FIFOs and devices
+        assert_eq!(FileType::from_mode(0o010644), None);
+        assert_eq!(FileType::from_mode(0o020644), None);
+    }
+
+    #[test]
+    fn test_permissions() {
+        assert_eq!(permissions(0o104755), 0o4755);
+        assert_eq!(with_permissions(0o100644, 0o40700), 0o100700);
+        assert_eq!(format_mode(0o100644), "-rw-r--r--");
+        assert_eq!(format_mode(0o040750), "drwxr-x---");
+        assert_eq!(format_mode(0o010600), "?rw-------");
+    }
+}
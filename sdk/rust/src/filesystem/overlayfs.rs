@@ -3,12 +3,15 @@ use async_trait::async_trait;
 use std::{
     collections::{HashMap, HashSet},
     sync::{Arc, RwLock},
-    time::{SystemTime, UNIX_EPOCH},
+    time::SystemTime,
 };
-use turso::{Connection, Value};
+use turso::Value;
+
+use crate::storage::StorageBackend;
 
 use super::{
-    agentfs::AgentFS, BoxedFile, DirEntry, File, FileSystem, FilesystemStats, FsError, Stats,
+    agentfs::AgentFS, BoxedFile, DirEntry, File, FileSystem, FilesystemStats, FsError,
+    JournalEntry, Stats,
 };
 
 /// A path-component trie for efficient whiteout lookups.
@@ -240,6 +243,11 @@ pub struct OverlayFile {
 }
 
 impl OverlayFile {
+    /// The delta layer through the [`FileSystem`] trait, see [`OverlayFS::delta_fs`]
+    fn delta_fs(&self) -> &dyn FileSystem {
+        &self.delta
+    }
+
     /// Ensure parent directories exist in the delta layer.
     ///
     /// This is needed for copy-on-write: when a file exists only in the base layer
@@ -252,9 +260,9 @@ impl OverlayFile {
             current = format!("{}/{}", current, component);
 
             // Check if directory exists in delta
-            if self.delta.stat(&current).await?.is_none() {
+            if self.delta_fs().stat(&current).await?.is_none() {
                 // Create it in delta
-                self.delta.mkdir(&current).await?;
+                self.delta_fs().mkdir(&current).await?;
             }
         }
         Ok(())
@@ -293,16 +301,16 @@ impl File for OverlayFile {
             if let Some(ref base_file) = self.base_file {
                 let stats = base_file.fstat().await?;
                 let base_data = base_file.pread(0, stats.size as u64).await?;
-                self.delta.write_file(&self.path, &base_data).await?;
+                self.delta_fs().write_file(&self.path, &base_data).await?;
             } else {
-                self.delta.write_file(&self.path, &[]).await?;
+                self.delta_fs().write_file(&self.path, &[]).await?;
             }
             self.copied_to_delta
                 .store(true, std::sync::atomic::Ordering::Release);
         }
 
         // Open file in delta and write using the file handle
-        let delta_file = self.delta.open(&self.path).await?;
+        let delta_file = self.delta_fs().open(&self.path).await?;
         delta_file.pwrite(offset, data).await
     }
 
@@ -323,16 +331,16 @@ impl File for OverlayFile {
             if let Some(ref base_file) = self.base_file {
                 let stats = base_file.fstat().await?;
                 let base_data = base_file.pread(0, stats.size as u64).await?;
-                self.delta.write_file(&self.path, &base_data).await?;
+                self.delta_fs().write_file(&self.path, &base_data).await?;
             } else {
-                self.delta.write_file(&self.path, &[]).await?;
+                self.delta_fs().write_file(&self.path, &[]).await?;
             }
             self.copied_to_delta
                 .store(true, std::sync::atomic::Ordering::Release);
         }
 
         // Open file in delta and truncate using the file handle
-        let delta_file = self.delta.open(&self.path).await?;
+        let delta_file = self.delta_fs().open(&self.path).await?;
         delta_file.truncate(size).await
     }
 
@@ -347,7 +355,7 @@ impl File for OverlayFile {
             .copied_to_delta
             .load(std::sync::atomic::Ordering::Acquire)
         {
-            let delta_file = self.delta.open(&self.path).await?;
+            let delta_file = self.delta_fs().open(&self.path).await?;
             return delta_file.fsync().await;
         }
 
@@ -383,19 +391,19 @@ impl OverlayFS {
     /// This is called during init() to populate the cache. The cache is then
     /// kept in sync via create_whiteout() and remove_whiteout().
     async fn load_whiteouts_into_cache(&self) -> Result<()> {
-        let conn = self.delta.get_connection();
-
         // Query all whiteouts from the database
-        let result = conn.prepare_cached("SELECT path FROM fs_whiteout").await;
+        let result = self
+            .delta
+            .backend()
+            .query("SELECT path FROM fs_whiteout", Vec::new())
+            .await;
 
         // Handle case where table doesn't exist yet (fresh database)
-        let mut stmt = match result {
-            Ok(stmt) => stmt,
+        let mut rows = match result {
+            Ok(rows) => rows,
             Err(_) => return Ok(()), // Table doesn't exist, nothing to load
         };
 
-        let mut rows = stmt.query(()).await?;
-
         while let Some(row) = rows.next().await? {
             if let Ok(Value::Text(path)) = row.get_value(0) {
                 self.whiteout_cache.insert(&path);
@@ -413,47 +421,52 @@ impl OverlayFS {
     /// The `base_path` parameter specifies the actual filesystem path that the
     /// base layer represents. This is stored in the delta database so that
     /// tools like `agentfs diff` can determine what files were modified.
-    pub async fn init_schema(conn: &Connection, base_path: &str) -> Result<()> {
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS fs_whiteout (
+    pub async fn init_schema(backend: &dyn StorageBackend, base_path: &str) -> Result<()> {
+        backend
+            .execute(
+                "CREATE TABLE IF NOT EXISTS fs_whiteout (
                 path TEXT PRIMARY KEY,
                 parent_path TEXT NOT NULL,
                 created_at INTEGER NOT NULL
             )",
-            (),
-        )
-        .await?;
+                Vec::new(),
+            )
+            .await?;
         // Index on parent_path for efficient child lookups (avoids LIKE regex compilation)
-        conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_fs_whiteout_parent ON fs_whiteout(parent_path)",
-            (),
-        )
-        .await?;
+        backend
+            .execute(
+                "CREATE INDEX IF NOT EXISTS idx_fs_whiteout_parent ON fs_whiteout(parent_path)",
+                Vec::new(),
+            )
+            .await?;
         // Store overlay configuration so tools can identify this as an overlay database
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS fs_overlay_config (
+        backend
+            .execute(
+                "CREATE TABLE IF NOT EXISTS fs_overlay_config (
                 key TEXT PRIMARY KEY,
                 value TEXT NOT NULL
             )",
-            (),
-        )
-        .await?;
-        conn.execute(
-            "INSERT OR REPLACE INTO fs_overlay_config (key, value) VALUES ('base_path', ?1)",
-            [Value::Text(base_path.to_string())],
-        )
-        .await?;
+                Vec::new(),
+            )
+            .await?;
+        backend
+            .execute(
+                "INSERT OR REPLACE INTO fs_overlay_config (key, value) VALUES ('base_path', ?1)",
+                vec![Value::Text(base_path.to_string())],
+            )
+            .await?;
         // Track origin inodes for copy-up operations (like Linux overlayfs "trusted.overlay.origin")
         // When a file is copied from base to delta, we store the mapping so stat() returns
         // the original base inode, maintaining consistency with kernel inode cache.
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS fs_origin (
+        backend
+            .execute(
+                "CREATE TABLE IF NOT EXISTS fs_origin (
                 delta_ino INTEGER PRIMARY KEY,
                 base_ino INTEGER NOT NULL
             )",
-            (),
-        )
-        .await?;
+                Vec::new(),
+            )
+            .await?;
         Ok(())
     }
 
@@ -466,7 +479,7 @@ impl OverlayFS {
     /// base layer represents. This is stored in the delta database so that
     /// tools like `agentfs diff` can determine what files were modified.
     pub async fn init(&self, base_path: &str) -> Result<()> {
-        Self::init_schema(&self.delta.get_connection(), base_path).await?;
+        Self::init_schema(self.delta.backend().as_ref(), base_path).await?;
         // Load existing whiteouts into the in-memory cache
         self.load_whiteouts_into_cache().await?;
         Ok(())
@@ -494,6 +507,12 @@ impl OverlayFS {
         &self.delta
     }
 
+    /// The delta layer through the [`FileSystem`] trait, whose errors keep
+    /// their `FsError` root
+    fn delta_fs(&self) -> &dyn FileSystem {
+        &self.delta
+    }
+
     /// Normalize a path
     fn normalize_path(&self, path: &str) -> NormalizedPath {
         let normalized = path.trim_end_matches('/');
@@ -521,17 +540,16 @@ impl OverlayFS {
     async fn create_whiteout(&self, path: &str) -> Result<()> {
         let normalized = self.normalize_path(path);
         let parent = Self::parent_path(&normalized);
-        let conn = self.delta.get_connection();
-        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        let now = self.delta.clock().unix_time();
 
-        let mut stmt = conn
-            .prepare_cached(
+        self.delta
+            .backend()
+            .execute(
                 "INSERT INTO fs_whiteout (path, parent_path, created_at) VALUES (?, ?, ?)
              ON CONFLICT(path) DO UPDATE SET created_at = excluded.created_at",
+                vec![normalized.as_str().into(), parent.into(), now.into()],
             )
             .await?;
-        stmt.execute((normalized.as_str(), parent.as_str(), now))
-            .await?;
 
         // Update in-memory cache
         self.whiteout_cache.insert(&normalized);
@@ -541,12 +559,13 @@ impl OverlayFS {
     /// Remove a whiteout (un-delete a path)
     async fn remove_whiteout(&self, path: &str) -> Result<()> {
         let normalized = self.normalize_path(path);
-        let conn = self.delta.get_connection();
-
-        let mut stmt = conn
-            .prepare_cached("DELETE FROM fs_whiteout WHERE path = ?")
+        self.delta
+            .backend()
+            .execute(
+                "DELETE FROM fs_whiteout WHERE path = ?",
+                vec![normalized.as_str().into()],
+            )
             .await?;
-        stmt.execute((normalized.as_str(),)).await?;
 
         // Update in-memory cache
         self.whiteout_cache.remove(&normalized);
@@ -580,7 +599,7 @@ impl OverlayFS {
             }
 
             // Check if it exists in delta
-            let delta_stats = self.delta.stat(&current).await?;
+            let delta_stats = self.delta_fs().stat(&current).await?;
 
             if let Some(s) = &delta_stats {
                 if s.is_directory() {
@@ -599,7 +618,7 @@ impl OverlayFS {
             match base_stats {
                 Some(s) if s.is_directory() => {
                     // Directory exists in base but not delta, create in delta
-                    self.delta.mkdir(&current).await?;
+                    self.delta_fs().mkdir(&current).await?;
                     self.delta_dir_cache.insert(&current);
                 }
                 Some(_) => {
@@ -608,7 +627,7 @@ impl OverlayFS {
                 }
                 None => {
                     // Doesn't exist anywhere, create it
-                    self.delta.mkdir(&current).await?;
+                    self.delta_fs().mkdir(&current).await?;
                     self.delta_dir_cache.insert(&current);
                 }
             }
@@ -616,9 +635,50 @@ impl OverlayFS {
         Ok(())
     }
 
+    /// Make sure a path exists in the delta layer before changing its metadata.
+    ///
+    /// Base directories are recreated, symlinks and regular files are copied.
+    /// Returns `false` if a base entry could not be read back, in which case
+    /// there is nothing in delta to update.
+    async fn copy_up_for_metadata(&self, normalized: &NormalizedPath) -> Result<bool> {
+        // Check if whited-out
+        if self.is_whiteout(normalized) {
+            return Err(FsError::NotFound.into());
+        }
+
+        // If file exists in delta, it can be changed there directly
+        if self.exists_in_delta(normalized).await? {
+            return Ok(true);
+        }
+
+        let Some(stats) = self.base.lstat(normalized).await? else {
+            return Err(FsError::NotFound.into());
+        };
+        if stats.is_directory() {
+            // For directories, just create in delta
+            self.ensure_parent_dirs(normalized).await?;
+            self.delta_fs().mkdir(normalized).await?;
+        } else if stats.is_symlink() {
+            // For symlinks, copy the symlink to delta
+            let Some(target) = self.base.readlink(normalized).await? else {
+                return Ok(false);
+            };
+            self.ensure_parent_dirs(normalized).await?;
+            self.delta_fs().symlink(&target, normalized).await?;
+        } else {
+            // For regular files, copy content to delta
+            let Some(data) = self.base.read_file(normalized).await? else {
+                return Ok(false);
+            };
+            self.ensure_parent_dirs(normalized).await?;
+            self.delta_fs().write_file(normalized, &data).await?;
+        }
+        Ok(true)
+    }
+
     /// Check if a path exists in delta layer
     async fn exists_in_delta(&self, path: &str) -> Result<bool> {
-        Ok(self.delta.stat(path).await?.is_some())
+        Ok(self.delta_fs().stat(path).await?.is_some())
     }
 
     /// Check if a path is traversable (all parent components are directories, not whited out)
@@ -637,7 +697,7 @@ impl OverlayFS {
             }
 
             // Check if it's a directory (check delta first, then base)
-            let stats = if let Some(s) = self.delta.stat(&current).await? {
+            let stats = if let Some(s) = self.delta_fs().stat(&current).await? {
                 Some(s)
             } else {
                 self.base.stat(&current).await?
@@ -656,29 +716,33 @@ impl OverlayFS {
     /// This records that a delta inode originated from a base inode,
     /// so stat() can return the original inode number (like Linux overlayfs).
     async fn add_origin_mapping(&self, delta_ino: i64, base_ino: i64) -> Result<()> {
-        let conn = self.delta.get_connection();
-        let mut stmt = conn
-            .prepare_cached("INSERT OR REPLACE INTO fs_origin (delta_ino, base_ino) VALUES (?, ?)")
+        self.delta
+            .backend()
+            .execute(
+                "INSERT OR REPLACE INTO fs_origin (delta_ino, base_ino) VALUES (?, ?)",
+                vec![delta_ino.into(), base_ino.into()],
+            )
             .await?;
-        stmt.execute((delta_ino, base_ino)).await?;
         Ok(())
     }
 
     /// Get the origin (base) inode for a delta inode, if it was copied up.
     async fn get_origin_inode(&self, delta_ino: i64) -> Result<Option<i64>> {
-        let conn = self.delta.get_connection();
-        let result = conn
-            .prepare_cached("SELECT base_ino FROM fs_origin WHERE delta_ino = ?")
+        let result = self
+            .delta
+            .backend()
+            .query(
+                "SELECT base_ino FROM fs_origin WHERE delta_ino = ?",
+                vec![delta_ino.into()],
+            )
             .await;
 
         // Handle case where fs_origin table doesn't exist yet (for existing databases)
-        let mut stmt = match result {
-            Ok(stmt) => stmt,
+        let mut rows = match result {
+            Ok(rows) => rows,
             Err(_) => return Ok(None),
         };
 
-        let mut rows = stmt.query((delta_ino,)).await?;
-
         if let Some(row) = rows.next().await? {
             let base_ino = row.get_value(0).ok().and_then(|v| v.as_integer().copied());
             Ok(base_ino)
@@ -691,9 +755,13 @@ impl OverlayFS {
     ///
     /// Called when a file is deleted from the delta layer to clean up stale mappings.
     async fn remove_origin_mapping(&self, delta_ino: i64) -> Result<()> {
-        let conn = self.delta.get_connection();
-        let result = conn
-            .execute("DELETE FROM fs_origin WHERE delta_ino = ?", (delta_ino,))
+        let result = self
+            .delta
+            .backend()
+            .execute(
+                "DELETE FROM fs_origin WHERE delta_ino = ?",
+                vec![delta_ino.into()],
+            )
             .await;
 
         // Ignore errors for existing databases without fs_origin table
@@ -715,7 +783,7 @@ impl FileSystem for OverlayFS {
         }
 
         // Check delta first for the file content/stats
-        let delta_stats = self.delta.stat(&normalized).await?;
+        let delta_stats = self.delta_fs().stat(&normalized).await?;
 
         // Check base for the stable inode
         // If file exists in base, use base's inode to maintain consistency
@@ -756,7 +824,7 @@ impl FileSystem for OverlayFS {
         }
 
         // Check delta first for the file content/stats
-        let delta_stats = self.delta.lstat(&normalized).await?;
+        let delta_stats = self.delta_fs().lstat(&normalized).await?;
 
         // Check base for the stable inode
         // If file exists in base, use base's inode to maintain consistency
@@ -802,7 +870,7 @@ impl FileSystem for OverlayFS {
         }
 
         // Check delta first
-        if let Some(data) = self.delta.read_file(&normalized).await? {
+        if let Some(data) = self.delta_fs().read_file(&normalized).await? {
             return Ok(Some(data));
         }
 
@@ -823,7 +891,25 @@ impl FileSystem for OverlayFS {
         self.ensure_parent_dirs(&normalized).await?;
 
         // Write to delta
-        self.delta.write_file(&normalized, data).await
+        self.delta_fs().write_file(&normalized, data).await
+    }
+
+    async fn create_new(&self, path: &str, data: &[u8]) -> Result<()> {
+        let normalized = self.normalize_path(path);
+
+        // Entries in the base layer exist unless they were deleted
+        if !self.is_whiteout(&normalized) && self.base.lstat(&normalized).await?.is_some() {
+            return Err(FsError::AlreadyExists.into());
+        }
+
+        self.delta_dir_cache.remove(&normalized);
+
+        // Ensure parent directories exist in delta
+        self.ensure_parent_dirs(&normalized).await?;
+
+        // The delta layer decides between racing creators
+        self.delta_fs().create_new(&normalized, data).await?;
+        self.remove_whiteout(&normalized).await
     }
 
     async fn readdir(&self, path: &str) -> Result<Option<Vec<String>>> {
@@ -840,7 +926,7 @@ impl FileSystem for OverlayFS {
         let mut entries = HashSet::new();
 
         // Get entries from delta
-        if let Some(delta_entries) = self.delta.readdir(&normalized).await? {
+        if let Some(delta_entries) = self.delta_fs().readdir(&normalized).await? {
             entries.extend(delta_entries);
         }
 
@@ -854,7 +940,7 @@ impl FileSystem for OverlayFS {
         }
 
         // Check if directory exists in either layer
-        let delta_exists = self.delta.stat(&normalized).await?.is_some();
+        let delta_exists = self.delta_fs().stat(&normalized).await?.is_some();
         let base_exists = self.base.stat(&normalized).await?.is_some();
 
         if !delta_exists && !base_exists {
@@ -894,7 +980,7 @@ impl FileSystem for OverlayFS {
         // Get entries from delta (these take precedence for content/metadata)
         // But we need to use the base inode if the file exists in base,
         // to maintain consistency with stat() which returns base inodes
-        if let Some(delta_entries) = self.delta.readdir_plus(&normalized).await? {
+        if let Some(delta_entries) = self.delta_fs().readdir_plus(&normalized).await? {
             for mut entry in delta_entries {
                 // If file exists in base, use base inode for consistency with stat()
                 if let Some(&base_ino) = base_inodes.get(&entry.name) {
@@ -910,7 +996,7 @@ impl FileSystem for OverlayFS {
         }
 
         // Check if directory exists in either layer
-        let delta_exists = self.delta.stat(&normalized).await?.is_some();
+        let delta_exists = self.delta_fs().stat(&normalized).await?.is_some();
         let base_exists = self.base.stat(&normalized).await?.is_some();
 
         if !delta_exists && !base_exists {
@@ -927,7 +1013,7 @@ impl FileSystem for OverlayFS {
 
         // Check if already exists (in either layer, not whiteout)
         if !self.is_whiteout(&normalized)
-            && (self.delta.stat(&normalized).await?.is_some()
+            && (self.delta_fs().stat(&normalized).await?.is_some()
                 || self.base.stat(&normalized).await?.is_some())
         {
             return Err(FsError::AlreadyExists.into());
@@ -940,7 +1026,7 @@ impl FileSystem for OverlayFS {
         self.ensure_parent_dirs(&normalized).await?;
 
         // Create in delta
-        self.delta.mkdir(&normalized).await?;
+        self.delta_fs().mkdir(&normalized).await?;
 
         // Update cache
         self.delta_dir_cache.insert(&normalized);
@@ -960,7 +1046,7 @@ impl FileSystem for OverlayFS {
         // Only check for children if not a symlink (directories need to be empty)
         if !is_symlink {
             // Check if directory has children in delta - if so, can't remove
-            if let Some(children) = self.delta.readdir(&normalized).await? {
+            if let Some(children) = self.delta_fs().readdir(&normalized).await? {
                 if !children.is_empty() {
                     return Err(FsError::NotEmpty.into());
                 }
@@ -979,10 +1065,10 @@ impl FileSystem for OverlayFS {
         }
 
         // Get delta inode before removal to clean up origin mapping
-        let delta_ino = self.delta.lstat(&normalized).await?.map(|s| s.ino);
+        let delta_ino = self.delta_fs().lstat(&normalized).await?.map(|s| s.ino);
 
         // Try to remove from delta
-        let removed_from_delta = self.delta.remove(&normalized).await.is_ok();
+        let removed_from_delta = self.delta_fs().remove(&normalized).await.is_ok();
 
         // Clean up origin mapping if file was removed from delta
         if removed_from_delta {
@@ -1012,45 +1098,18 @@ impl FileSystem for OverlayFS {
 
     async fn chmod(&self, path: &str, mode: u32) -> Result<()> {
         let normalized = self.normalize_path(path);
-
-        // Check if whited-out
-        if self.is_whiteout(&normalized) {
-            return Err(FsError::NotFound.into());
+        if self.copy_up_for_metadata(&normalized).await? {
+            self.delta_fs().chmod(&normalized, mode).await?;
         }
+        Ok(())
+    }
 
-        // If file exists in delta, chmod there directly
-        if self.exists_in_delta(&normalized).await? {
-            return self.delta.chmod(&normalized, mode).await;
-        }
-
-        // Check if exists in base
-        let base_stats = self.base.lstat(&normalized).await?;
-        if let Some(stats) = base_stats {
-            // Need to copy to delta first, then chmod
-            if stats.is_directory() {
-                // For directories, just create in delta and chmod
-                self.ensure_parent_dirs(&normalized).await?;
-                self.delta.mkdir(&normalized).await?;
-                self.delta.chmod(&normalized, mode).await?;
-            } else if stats.is_symlink() {
-                // For symlinks, copy the symlink to delta
-                if let Some(target) = self.base.readlink(&normalized).await? {
-                    self.ensure_parent_dirs(&normalized).await?;
-                    self.delta.symlink(&target, &normalized).await?;
-                    self.delta.chmod(&normalized, mode).await?;
-                }
-            } else {
-                // For regular files, copy content to delta
-                if let Some(data) = self.base.read_file(&normalized).await? {
-                    self.ensure_parent_dirs(&normalized).await?;
-                    self.delta.write_file(&normalized, &data).await?;
-                    self.delta.chmod(&normalized, mode).await?;
-                }
-            }
-            Ok(())
-        } else {
-            Err(FsError::NotFound.into())
+    async fn chown(&self, path: &str, uid: Option<u32>, gid: Option<u32>) -> Result<()> {
+        let normalized = self.normalize_path(path);
+        if self.copy_up_for_metadata(&normalized).await? {
+            self.delta_fs().chown(&normalized, uid, gid).await?;
         }
+        Ok(())
     }
 
     async fn rename(&self, from: &str, to: &str) -> Result<()> {
@@ -1080,7 +1139,7 @@ impl FileSystem for OverlayFS {
                     // Copy file to delta
                     if let Some(data) = self.base.read_file(&from_normalized).await? {
                         self.ensure_parent_dirs(&from_normalized).await?;
-                        self.delta.write_file(&from_normalized, &data).await?;
+                        self.delta_fs().write_file(&from_normalized, &data).await?;
                     }
                 }
             } else {
@@ -1095,7 +1154,9 @@ impl FileSystem for OverlayFS {
         self.ensure_parent_dirs(&to_normalized).await?;
 
         // Perform rename in delta
-        self.delta.rename(&from_normalized, &to_normalized).await?;
+        self.delta_fs()
+            .rename(&from_normalized, &to_normalized)
+            .await?;
 
         // Invalidate delta directory cache for both source and destination (and their children)
         self.delta_dir_cache.remove(&from_normalized);
@@ -1119,7 +1180,7 @@ impl FileSystem for OverlayFS {
         self.ensure_parent_dirs(&normalized).await?;
 
         // Create in delta
-        self.delta.symlink(target, &normalized).await
+        self.delta_fs().symlink(target, &normalized).await
     }
 
     async fn link(&self, oldpath: &str, newpath: &str) -> Result<()> {
@@ -1151,11 +1212,11 @@ impl FileSystem for OverlayFS {
                 // Copy-up: read from base and write to delta
                 if let Some(data) = self.base.read_file(&old_normalized).await? {
                     self.ensure_parent_dirs(&old_normalized).await?;
-                    self.delta.write_file(&old_normalized, &data).await?;
+                    self.delta_fs().write_file(&old_normalized, &data).await?;
 
                     // Store origin mapping: delta_ino -> base_ino
                     // This ensures stat() returns the original base inode after copy-up
-                    if let Some(delta_stats) = self.delta.lstat(&old_normalized).await? {
+                    if let Some(delta_stats) = self.delta_fs().lstat(&old_normalized).await? {
                         self.add_origin_mapping(delta_stats.ino, stats.ino).await?;
                     }
                 } else {
@@ -1167,7 +1228,7 @@ impl FileSystem for OverlayFS {
         }
 
         // Create hard link in delta
-        self.delta.link(&old_normalized, &new_normalized).await
+        self.delta_fs().link(&old_normalized, &new_normalized).await
     }
 
     async fn readlink(&self, path: &str) -> Result<Option<String>> {
@@ -1178,7 +1239,7 @@ impl FileSystem for OverlayFS {
         }
 
         // Check delta first
-        if let Some(target) = self.delta.readlink(&normalized).await? {
+        if let Some(target) = self.delta_fs().readlink(&normalized).await? {
             return Ok(Some(target));
         }
 
@@ -1186,9 +1247,14 @@ impl FileSystem for OverlayFS {
         self.base.readlink(&normalized).await
     }
 
+    async fn journal(&self, since: SystemTime) -> Result<Vec<JournalEntry>> {
+        // Changes land in the delta; whiteouts for base entries aren't journaled
+        self.delta_fs().journal(since).await
+    }
+
     async fn statfs(&self) -> Result<FilesystemStats> {
         // Return delta stats (base stats would be misleading for overlay)
-        self.delta.statfs().await
+        self.delta_fs().statfs().await
     }
 
     async fn open(&self, path: &str) -> Result<BoxedFile> {
@@ -1201,7 +1267,7 @@ impl FileSystem for OverlayFS {
 
         // Try to open from delta
         let delta_file = if self.exists_in_delta(&normalized).await? {
-            Some(self.delta.open(&normalized).await?)
+            Some(self.delta_fs().open(&normalized).await?)
         } else {
             None
         };
@@ -1227,7 +1293,7 @@ impl FileSystem for OverlayFS {
 impl OverlayFS {
     /// Recursively copy a directory from base to delta
     async fn copy_dir_to_delta(&self, path: &str) -> Result<()> {
-        self.delta.mkdir(path).await?;
+        self.delta_fs().mkdir(path).await?;
 
         if let Some(entries) = self.base.readdir(path).await? {
             for entry in entries {
@@ -1242,10 +1308,10 @@ impl OverlayFS {
                         Box::pin(self.copy_dir_to_delta(&entry_path)).await?;
                     } else if stats.is_symlink() {
                         if let Some(target) = self.base.readlink(&entry_path).await? {
-                            self.delta.symlink(&target, &entry_path).await?;
+                            self.delta_fs().symlink(&target, &entry_path).await?;
                         }
                     } else if let Some(data) = self.base.read_file(&entry_path).await? {
-                        self.delta.write_file(&entry_path, &data).await?;
+                        self.delta_fs().write_file(&entry_path, &data).await?;
                     }
                 }
             }
@@ -1482,6 +1548,69 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_overlay_chown_base_file_copies_to_delta() -> Result<()> {
+        let (overlay, base_dir, _delta_dir) = create_test_overlay().await?;
+
+        let original_stats = overlay.stat("/base.txt").await?.unwrap();
+
+        // chown the base file - should copy to delta, leaving the gid alone
+        overlay.chown("/base.txt", Some(4242), None).await?;
+
+        let stats = overlay.stat("/base.txt").await?.unwrap();
+        assert_eq!(stats.uid, 4242, "uid should be 4242 after chown");
+
+        // Verify base file is unchanged
+        use std::os::unix::fs::MetadataExt;
+        let base_meta = std::fs::metadata(base_dir.path().join("base.txt"))?;
+        assert_eq!(
+            base_meta.uid(),
+            original_stats.uid,
+            "Base file should be unchanged"
+        );
+
+        let data = overlay.read_file("/base.txt").await?.unwrap();
+        assert_eq!(data, b"base content", "Content should be preserved");
+
+        // Whited-out files can't be chowned
+        overlay.remove("/base.txt").await?;
+        assert!(overlay.chown("/base.txt", Some(0), Some(0)).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_overlay_create_new() -> Result<()> {
+        let (overlay, _base_dir, _delta_dir) = create_test_overlay().await?;
+
+        // Files in the base layer already exist
+        let err = overlay.create_new("/base.txt", b"new").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FsError>(),
+            Some(FsError::AlreadyExists)
+        ));
+
+        overlay.create_new("/fresh.txt", b"fresh").await?;
+        let err = overlay
+            .create_new("/fresh.txt", b"again")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<FsError>(),
+            Some(FsError::AlreadyExists)
+        ));
+        let data = overlay.read_file("/fresh.txt").await?.unwrap();
+        assert_eq!(data, b"fresh");
+
+        // A deleted base file can be created anew
+        overlay.remove("/base.txt").await?;
+        overlay.create_new("/base.txt", b"replaced").await?;
+        let data = overlay.read_file("/base.txt").await?.unwrap();
+        assert_eq!(data, b"replaced");
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_overlay_link_base_file_preserves_inode() -> Result<()> {
         let (overlay, _base_dir, _delta_dir) = create_test_overlay().await?;
@@ -1,12 +1,51 @@
-use anyhow::Result;
+use crate::clock::{Clock, SystemClock};
+use crate::error::{AgentFsError, Result};
+use crate::storage::StorageBackend;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
-use turso::{Builder, Connection};
+use turso::{Builder, Connection, Value};
+
+/// Serialization format of stored values
+///
+/// Every row records the format it was written in, so values written in
+/// different formats can be read back from the same store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValueFormat {
+    /// JSON text, readable by any SQLite client (the default)
+    #[default]
+    Json,
+    /// CBOR, stored as a BLOB
+    Cbor,
+    /// MessagePack, stored as a BLOB
+    MessagePack,
+}
+
+impl ValueFormat {
+    /// Tag stored in the `format` column
+    pub(crate) fn as_tag(self) -> &'static str {
+        match self {
+            ValueFormat::Json => "json",
+            ValueFormat::Cbor => "cbor",
+            ValueFormat::MessagePack => "msgpack",
+        }
+    }
+
+    pub(crate) fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "json" => Some(ValueFormat::Json),
+            "cbor" => Some(ValueFormat::Cbor),
+            "msgpack" => Some(ValueFormat::MessagePack),
+            _ => None,
+        }
+    }
+}
 
 /// A key-value store backed by SQLite
 #[derive(Clone)]
 pub struct KvStore {
-    conn: Arc<Connection>,
+    backend: Arc<dyn StorageBackend>,
+    clock: Arc<dyn Clock>,
+    format: ValueFormat,
 }
 
 impl KvStore {
@@ -14,109 +53,334 @@ impl KvStore {
     pub async fn new(db_path: &str) -> Result<Self> {
         let db = Builder::new_local(db_path).build().await?;
         let conn = db.connect()?;
-        let kv = Self {
-            conn: Arc::new(conn),
-        };
-        kv.initialize().await?;
-        Ok(kv)
+        Self::from_connection(Arc::new(conn)).await
     }
 
     /// Create a KV store from an existing connection
     pub async fn from_connection(conn: Arc<Connection>) -> Result<Self> {
-        let kv = Self { conn };
+        Self::from_backend(conn).await
+    }
+
+    /// Create a KV store on top of an arbitrary storage backend
+    pub async fn from_backend(backend: Arc<dyn StorageBackend>) -> Result<Self> {
+        let kv = Self {
+            backend,
+            clock: Arc::new(SystemClock),
+            format: ValueFormat::default(),
+        };
         kv.initialize().await?;
         Ok(kv)
     }
 
     /// Initialize the database schema
     async fn initialize(&self) -> Result<()> {
-        self.conn
+        self.backend
             .execute(
                 "CREATE TABLE IF NOT EXISTS kv_store (
                     key TEXT PRIMARY KEY,
                     value TEXT NOT NULL,
+                    format TEXT NOT NULL DEFAULT 'json',
+                    meta TEXT,
                     created_at INTEGER DEFAULT (unixepoch()),
                     updated_at INTEGER DEFAULT (unixepoch())
                 )",
-                (),
+                Vec::new(),
             )
             .await?;
 
-        self.conn
+        // Stores created before value formats hold only JSON
+        if self
+            .backend
+            .query("SELECT format FROM kv_store LIMIT 0", Vec::new())
+            .await
+            .is_err()
+        {
+            self.backend
+                .execute(
+                    "ALTER TABLE kv_store ADD COLUMN format TEXT NOT NULL DEFAULT 'json'",
+                    Vec::new(),
+                )
+                .await?;
+        }
+
+        // Stores created before metadata have no place for it
+        if self
+            .backend
+            .query("SELECT meta FROM kv_store LIMIT 0", Vec::new())
+            .await
+            .is_err()
+        {
+            self.backend
+                .execute("ALTER TABLE kv_store ADD COLUMN meta TEXT", Vec::new())
+                .await?;
+        }
+
+        self.backend
             .execute(
                 "CREATE INDEX IF NOT EXISTS idx_kv_store_created_at
                 ON kv_store(created_at)",
-                (),
+                Vec::new(),
             )
             .await?;
 
         Ok(())
     }
 
+    /// A copy of this store that runs its queries on `backend`
+    pub(crate) fn with_backend(&self, backend: Arc<dyn StorageBackend>) -> Self {
+        Self {
+            backend,
+            ..self.clone()
+        }
+    }
+
+    /// Set the clock `created_at` and `updated_at` are taken from
+    ///
+    /// Defaults to [`SystemClock`].
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
+    /// Set the format values are serialized in from now on
+    ///
+    /// Defaults to [`ValueFormat::Json`]. Existing values keep their format and
+    /// stay readable.
+    pub fn set_format(&mut self, format: ValueFormat) {
+        self.format = format;
+    }
+
     /// Set a key-value pair
+    ///
+    /// Any metadata stored with the previous value is cleared.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(key = key)))]
     pub async fn set<V: Serialize>(&self, key: &str, value: &V) -> Result<()> {
-        let serialized = serde_json::to_string(value)?;
-        self.conn
-            .execute(
-                "INSERT INTO kv_store (key, value, updated_at)
-                VALUES (?, ?, unixepoch())
-                ON CONFLICT(key) DO UPDATE SET
-                    value = excluded.value,
-                    updated_at = unixepoch()",
-                (key, serialized.as_str()),
-            )
-            .await?;
-        Ok(())
+        self.set_value(
+            Value::Text(key.to_string()),
+            value,
+            &serde_json::Value::Null,
+        )
+        .await
+    }
+
+    /// Set a key-value pair along with metadata describing the value
+    ///
+    /// `meta` is kept as JSON in the same row as the value, e.g. the content
+    /// type, ETag and fetch time of a cached HTTP response, so the two are
+    /// always written and deleted together. Read it back with
+    /// [`KvStore::get_with_meta`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(key = key)))]
+    pub async fn set_with_meta<V: Serialize>(
+        &self,
+        key: &str,
+        value: &V,
+        meta: serde_json::Value,
+    ) -> Result<()> {
+        self.set_value(Value::Text(key.to_string()), value, &meta)
+            .await
     }
 
     /// Get a value by key
+    ///
+    /// Returns `Ok(None)` only when the key doesn't exist. A stored value that
+    /// can't be read as `V` fails with [`AgentFsError::WrongType`], so a schema
+    /// mismatch is never mistaken for an empty slot.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(key = key)))]
     pub async fn get<V: for<'de> Deserialize<'de>>(&self, key: &str) -> Result<Option<V>> {
-        let mut rows = self
-            .conn
-            .query("SELECT value FROM kv_store WHERE key = ?", (key,))
-            .await?;
+        let entry = self.get_value(Value::Text(key.to_string()), key).await?;
+        Ok(entry.map(|(value, _)| value))
+    }
 
-        if let Some(row) = rows.next().await? {
-            if let Some(value_str) = row.get_value(0).ok().and_then(|v| {
-                if let turso::Value::Text(s) = v {
-                    Some(s.clone())
-                } else {
-                    None
-                }
-            }) {
-                let value: V = serde_json::from_str(&value_str)?;
-                Ok(Some(value))
-            } else {
-                Ok(None)
-            }
-        } else {
-            Ok(None)
-        }
+    /// Get a value and its metadata by key
+    ///
+    /// The metadata is `null` for values stored without any, e.g. with
+    /// [`KvStore::set`].
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(key = key)))]
+    pub async fn get_with_meta<V: for<'de> Deserialize<'de>>(
+        &self,
+        key: &str,
+    ) -> Result<Option<(V, serde_json::Value)>> {
+        self.get_value(Value::Text(key.to_string()), key).await
+    }
+
+    /// Get a value by key, failing with [`AgentFsError::NotFound`] if it is missing
+    pub async fn try_get<V: for<'de> Deserialize<'de>>(&self, key: &str) -> Result<V> {
+        self.get(key)
+            .await?
+            .ok_or_else(|| AgentFsError::NotFound(format!("key '{key}'")))
+    }
+
+    /// Get a value by key, or `default` if it is missing
+    ///
+    /// A stored value of the wrong type is still an error rather than `default`.
+    pub async fn get_or<V: for<'de> Deserialize<'de>>(&self, key: &str, default: V) -> Result<V> {
+        Ok(self.get(key).await?.unwrap_or(default))
     }
 
     /// Delete a key
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(key = key)))]
     pub async fn delete(&self, key: &str) -> Result<()> {
-        self.conn
-            .execute("DELETE FROM kv_store WHERE key = ?", (key,))
-            .await?;
-        Ok(())
+        self.delete_value(Value::Text(key.to_string())).await
     }
 
     /// List all keys
+    ///
+    /// Binary keys are not included; see [`KvStore::raw_keys`].
     pub async fn keys(&self) -> Result<Vec<String>> {
-        let mut rows = self.conn.query("SELECT key FROM kv_store", ()).await?;
+        let mut rows = self
+            .backend
+            .query("SELECT key FROM kv_store", Vec::new())
+            .await?;
+        let mut keys = Vec::new();
+        while let Some(row) = rows.next().await? {
+            if let Some(Value::Text(key)) = row.into_iter().next() {
+                keys.push(key);
+            }
+        }
+        Ok(keys)
+    }
+
+    /// Set a key-value pair with a binary key, such as a content hash
+    ///
+    /// Binary keys are stored as BLOBs, so they never collide with string keys,
+    /// even ones with the same bytes. They are not tracked by sync conflict
+    /// detection.
+    pub async fn set_raw_key<V: Serialize>(&self, key: &[u8], value: &V) -> Result<()> {
+        self.set_value(Value::Blob(key.to_vec()), value, &serde_json::Value::Null)
+            .await
+    }
+
+    /// Get a value by binary key
+    pub async fn get_raw_key<V: for<'de> Deserialize<'de>>(&self, key: &[u8]) -> Result<Option<V>> {
+        let entry = self.get_value(Value::Blob(key.to_vec()), &hex(key)).await?;
+        Ok(entry.map(|(value, _)| value))
+    }
+
+    /// Delete a binary key
+    pub async fn delete_raw_key(&self, key: &[u8]) -> Result<()> {
+        self.delete_value(Value::Blob(key.to_vec())).await
+    }
+
+    /// List all binary keys
+    pub async fn raw_keys(&self) -> Result<Vec<Vec<u8>>> {
+        let mut rows = self
+            .backend
+            .query("SELECT key FROM kv_store", Vec::new())
+            .await?;
         let mut keys = Vec::new();
         while let Some(row) = rows.next().await? {
-            if let Some(key) = row.get_value(0).ok().and_then(|v| {
-                if let turso::Value::Text(s) = v {
-                    Some(s.clone())
-                } else {
-                    None
-                }
-            }) {
+            if let Some(Value::Blob(key)) = row.into_iter().next() {
                 keys.push(key);
             }
         }
         Ok(keys)
     }
+
+    async fn set_value<V: Serialize>(
+        &self,
+        key: Value,
+        value: &V,
+        meta: &serde_json::Value,
+    ) -> Result<()> {
+        let serialized = match self.format {
+            ValueFormat::Json => Value::Text(serde_json::to_string(value)?),
+            ValueFormat::Cbor => {
+                let mut buf = Vec::new();
+                ciborium::into_writer(value, &mut buf)
+                    .map_err(|e| AgentFsError::Other(anyhow::anyhow!("CBOR: {e}")))?;
+                Value::Blob(buf)
+            }
+            ValueFormat::MessagePack => Value::Blob(
+                rmp_serde::to_vec_named(value)
+                    .map_err(|e| AgentFsError::Other(anyhow::anyhow!("MessagePack: {e}")))?,
+            ),
+        };
+        let meta = match meta {
+            serde_json::Value::Null => Value::Null,
+            meta => Value::Text(meta.to_string()),
+        };
+        let now = self.clock.unix_time();
+        self.backend
+            .execute(
+                "INSERT INTO kv_store (key, value, format, meta, created_at, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?)
+                ON CONFLICT(key) DO UPDATE SET
+                    value = excluded.value,
+                    format = excluded.format,
+                    meta = excluded.meta,
+                    updated_at = excluded.updated_at",
+                vec![
+                    key,
+                    serialized,
+                    Value::Text(self.format.as_tag().to_string()),
+                    meta,
+                    Value::Integer(now),
+                    Value::Integer(now),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Get the value and metadata stored under `key`, naming it `display` in errors
+    async fn get_value<V: for<'de> Deserialize<'de>>(
+        &self,
+        key: Value,
+        display: &str,
+    ) -> Result<Option<(V, serde_json::Value)>> {
+        let row = self
+            .backend
+            .query_one(
+                "SELECT value, format, meta FROM kv_store WHERE key = ?",
+                vec![key],
+            )
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+
+        let format = match row.get(1) {
+            Some(Value::Text(tag)) => ValueFormat::from_tag(tag).ok_or_else(|| {
+                AgentFsError::WrongType(format!("{display}: unknown value format '{tag}'"))
+            })?,
+            _ => ValueFormat::Json,
+        };
+        let wrong_type =
+            |e: &dyn std::fmt::Display| AgentFsError::WrongType(format!("{display}: {e}"));
+        let value = match (format, row.first()) {
+            (ValueFormat::Json, Some(Value::Text(text))) => {
+                serde_json::from_str(text).map_err(|e| wrong_type(&e))?
+            }
+            (ValueFormat::Cbor, Some(Value::Blob(bytes))) => {
+                ciborium::from_reader(bytes.as_slice()).map_err(|e| wrong_type(&e))?
+            }
+            (ValueFormat::MessagePack, Some(Value::Blob(bytes))) => {
+                rmp_serde::from_slice(bytes).map_err(|e| wrong_type(&e))?
+            }
+            // The key exists, so a value that doesn't match its format is an
+            // error rather than a missing key
+            _ => {
+                return Err(wrong_type(&format_args!(
+                    "stored value doesn't match its '{}' format",
+                    format.as_tag()
+                )))
+            }
+        };
+        let meta = match row.get(2) {
+            Some(Value::Text(meta)) => serde_json::from_str(meta).map_err(|e| wrong_type(&e))?,
+            _ => serde_json::Value::Null,
+        };
+        Ok(Some((value, meta)))
+    }
+
+    async fn delete_value(&self, key: Value) -> Result<()> {
+        self.backend
+            .execute("DELETE FROM kv_store WHERE key = ?", vec![key])
+            .await?;
+        Ok(())
+    }
+}
+
+/// Format a binary key for error messages
+fn hex(key: &[u8]) -> String {
+    key.iter().map(|byte| format!("{:02x}", byte)).collect()
 }
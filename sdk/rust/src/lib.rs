@@ -1,28 +1,59 @@
+#[cfg(feature = "blocking")]
+pub mod blocking;
+pub mod check;
+pub mod clock;
+pub mod error;
+pub mod export;
 pub mod filesystem;
 pub mod kvstore;
+pub mod manager;
+pub mod storage;
+pub mod summary;
+pub mod sync;
 pub mod toolcalls;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::{
-    collections::{HashSet, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex, OnceLock, RwLock, Weak,
+    },
 };
-use turso::{Builder, Connection, Value};
+use turso::{Builder, Connection, Database, Value};
+
+pub use check::{CheckReport, DentryRef, LinkCountMismatch, RepairOptions, RepairReport};
+pub use clock::{Clock, MockClock, SystemClock};
+pub use error::AgentFsError;
 
 // Re-export filesystem types
 #[cfg(unix)]
 pub use filesystem::HostFS;
 pub use filesystem::{
-    BoxedFile, DirEntry, File, FileSystem, FilesystemStats, FsError, OverlayFS, Stats,
-    DEFAULT_DIR_MODE, DEFAULT_FILE_MODE, S_IFDIR, S_IFLNK, S_IFMT, S_IFREG,
+    validate_path, BlockInfo, BoxedFile, Compression, DirEntry, ExportOptions, ExportSummary, File,
+    FileHandle, FileSystem, FileType, FilenameOptions, FilesystemStats, FsError, Glob,
+    ImportOptions, ImportSummary, JournalEntry, JournalOp, OpenOptions, OverlayFS, RootOptions,
+    Stats, UsageSummary, DEFAULT_DIR_MODE, DEFAULT_FILE_MODE, DEFAULT_MAX_SYMLINK_DEPTH, S_IFDIR,
+    S_IFLNK, S_IFMT, S_IFREG,
 };
-pub use kvstore::KvStore;
-pub use toolcalls::{ToolCall, ToolCallStats, ToolCallStatus, ToolCalls};
+pub use kvstore::{KvStore, ValueFormat};
+pub use manager::AgentManager;
+pub use storage::StorageBackend;
+pub use summary::{AgentSummary, ToolCallCounts};
+pub use sync::{ChangeKey, Conflict, ConflictPolicy};
+pub use toolcalls::{Span, SpanStatus, ToolCall, ToolCallStats, ToolCallStatus, ToolCalls};
 
 /// Directory containing agentfs databases
+///
+/// This is `$AGENTFS_HOME` if set, or `.agentfs` in the current directory.
+/// The variable is read once, on first use.
 pub fn agentfs_dir() -> &'static std::path::Path {
-    std::path::Path::new(".agentfs")
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| match std::env::var_os("AGENTFS_HOME") {
+        Some(home) if !home.is_empty() => PathBuf::from(home),
+        _ => PathBuf::from(".agentfs"),
+    })
 }
 
 /// Information about a mounted agentfs filesystem
@@ -72,6 +103,100 @@ pub fn get_mounts() -> Vec<Mount> {
     vec![]
 }
 
+/// How long a connection waits for another one to release the database lock
+/// before failing with SQLITE_BUSY
+pub(crate) const BUSY_TIMEOUT_MS: u64 = 5000;
+
+/// What turso reports when another process has the database file open
+///
+/// Its locking error reaches the public API flattened into
+/// `turso::Error::Error`, so the message is all there is to tell it apart.
+const FILE_LOCKED_MESSAGE: &str = "File is locked by another process";
+
+/// Open the database file at `path`
+///
+/// The engine locks the whole file exclusively for as long as a process has
+/// it open, and has no way to share it with other processes, not even for
+/// reading. A database another process has open is therefore rejected right
+/// away, with an error that says so.
+pub(crate) async fn open_database(path: &str) -> Result<Database> {
+    match Builder::new_local(path).build().await {
+        Err(turso::Error::Error(msg)) if msg.ends_with(FILE_LOCKED_MESSAGE) => {
+            anyhow::bail!(
+                "Database '{}' is open in another process. The database engine locks the file while it is open, so an agent can only be used by one process at a time.",
+                path
+            )
+        }
+        result => Ok(result?),
+    }
+}
+
+/// Make `conn` wait [`BUSY_TIMEOUT_MS`] for the database lock
+///
+/// Set once per connection, right after it is opened: even creating the
+/// schema is a write.
+pub(crate) async fn set_busy_timeout(conn: &Connection) -> Result<()> {
+    conn.execute(&format!("PRAGMA busy_timeout = {}", BUSY_TIMEOUT_MS), ())
+        .await?;
+    Ok(())
+}
+
+/// Journal mode of the agent database
+///
+/// - `Wal`: readers and the writer don't block each other, which suits concurrent,
+///   read-heavy workloads. Relies on shared memory, so avoid it on network filesystems.
+/// - `Delete`: classic rollback journal, deleted after every transaction. The writer
+///   blocks readers, but the database is always a single self-contained file.
+/// - `Memory`: rollback journal kept in memory. Fastest, but a crash in the middle
+///   of a transaction can leave the database corrupt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalMode {
+    Wal,
+    Delete,
+    Memory,
+}
+
+impl JournalMode {
+    fn as_pragma(&self) -> &'static str {
+        match self {
+            JournalMode::Wal => "WAL",
+            JournalMode::Delete => "DELETE",
+            JournalMode::Memory => "MEMORY",
+        }
+    }
+}
+
+/// How hard the database pushes writes to stable storage
+///
+/// - `Full`: sync on every commit. Commits survive power loss in every journal mode.
+/// - `Normal`: with `Wal`, commits survive crashes and the database never corrupts,
+///   but the latest transactions may be lost on power loss. With rollback journals
+///   a power loss at the wrong moment can corrupt the database.
+/// - `Off` (default): leave syncing to the OS. Fastest, but a power loss can lose or
+///   corrupt recent writes. An explicit filesystem `fsync` still forces a full sync,
+///   so applications that fsync what they care about keep POSIX semantics.
+///
+/// Engines that don't implement `Normal` run it as `Full` rather than `Off`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Synchronous {
+    Full,
+    Normal,
+    #[default]
+    Off,
+}
+
+impl Synchronous {
+    /// Numeric level, since an engine without `NORMAL` would read the name
+    /// as `OFF` but rounds the number up to `FULL`
+    pub(crate) fn as_pragma(&self) -> &'static str {
+        match self {
+            Synchronous::Full => "2",
+            Synchronous::Normal => "1",
+            Synchronous::Off => "0",
+        }
+    }
+}
+
 /// Configuration options for opening an AgentFS instance
 #[derive(Debug, Clone, Default)]
 pub struct AgentFSOptions {
@@ -85,6 +210,51 @@ pub struct AgentFSOptions {
     /// Optional base directory for overlay filesystem (copy-on-write).
     /// When set, the filesystem operates as an overlay on top of this directory.
     pub base: Option<PathBuf>,
+    /// Optional name of a shared in-memory database.
+    /// All agents opened with the same name in this process see the same data;
+    /// other processes can't attach to it. Ignored if `path` or `id` is set.
+    pub memory_name: Option<String>,
+    /// Filename matching behaviour (case-insensitivity, Unicode normalization).
+    /// Options are persisted in the database once enabled.
+    pub filenames: FilenameOptions,
+    /// Journal mode to switch the database to on open.
+    /// If None, databases stored in a file (`path` or `id`) are switched to
+    /// `Wal` so that read snapshots and backups don't block writes, and others
+    /// keep their current journal mode.
+    pub journal_mode: Option<JournalMode>,
+    /// Synchronous level for the connection (defaults to `Off`).
+    pub synchronous: Synchronous,
+    /// Maximum size of a single file in bytes.
+    /// If None (the default), file size is unlimited.
+    pub max_file_size: Option<u64>,
+    /// Maximum number of symlinks followed while resolving a path.
+    /// If None, `DEFAULT_MAX_SYMLINK_DEPTH` (40, like Linux) is used.
+    pub max_symlink_depth: Option<u32>,
+    /// Compression applied to file blocks as they are written (defaults to none).
+    pub compression: Compression,
+    /// Clock timestamps are taken from.
+    /// If None, the system clock is used.
+    pub clock: Option<Arc<dyn Clock>>,
+    /// How `pull` resolves entries changed both locally and on the remote.
+    pub sync_conflict_policy: ConflictPolicy,
+    /// Permissions and ownership to give the root directory on open.
+    pub root: RootOptions,
+    /// Extra `PRAGMA name = value` statements run right after connecting, in order.
+    /// See [`AgentFSOptions::with_pragma`].
+    pub pragmas: Vec<(String, String)>,
+    /// Directory `id`-named databases are stored in.
+    /// If None, [`agentfs_dir`] is used.
+    pub base_dir: Option<PathBuf>,
+    /// Store `id`-named databases in the system temp directory when the
+    /// database directory can't be created, instead of failing.
+    pub temp_dir_fallback: bool,
+    /// Record filesystem mutations in the `fs_journal` table.
+    /// Persisted in the database once enabled.
+    pub journal: bool,
+    /// Size in bytes past which an ephemeral agent moves from memory to a
+    /// temp file. See [`AgentFSOptions::ephemeral_spilling`]. Ignored if
+    /// `path`, `id` or `memory_name` is set.
+    pub spill_threshold: Option<u64>,
 }
 
 impl AgentFSOptions {
@@ -96,6 +266,11 @@ impl AgentFSOptions {
                 .chars()
                 .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
     }
+
+    /// Path of the database these options open
+    ///
+    /// A named in-memory database has no path; it is reported as
+    /// `:memory:{name}`, which tells databases apart but is never opened.
     pub fn db_path(&self) -> anyhow::Result<String> {
         // Determine database path: path takes precedence over id
         if let Some(path) = &self.path {
@@ -110,42 +285,144 @@ impl AgentFSOptions {
                 );
             }
 
-            // Ensure .agentfs directory exists
-            let agentfs_dir = agentfs_dir();
-            if !agentfs_dir.exists() {
-                std::fs::create_dir_all(agentfs_dir)?;
+            let dir = self.database_dir()?;
+            Ok(format!("{}/{}.db", dir.display(), id))
+        } else if let Some(name) = &self.memory_name {
+            if !Self::validate_agent_id(name) {
+                anyhow::bail!(
+                    "Invalid in-memory database name '{}'. Names must contain only alphanumeric characters, hyphens, and underscores.",
+                    name
+                );
             }
-            Ok(format!("{}/{}.db", agentfs_dir.display(), id))
+            Ok(format!(":memory:{}", name))
         } else {
             // No id or path = ephemeral in-memory database
             Ok(":memory:".to_string())
         }
     }
+    /// Directory `id`-named databases are stored in, created if needed
+    fn database_dir(&self) -> Result<PathBuf> {
+        let dir = match &self.base_dir {
+            Some(dir) => dir.clone(),
+            None => agentfs_dir().to_path_buf(),
+        };
+        let err = match std::fs::create_dir_all(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(err) => err,
+        };
+        if self.temp_dir_fallback {
+            let fallback = std::env::temp_dir().join("agentfs");
+            if std::fs::create_dir_all(&fallback).is_ok() {
+                return Ok(fallback);
+            }
+        }
+        let shown = absolute_path(&dir).unwrap_or(dir);
+        Err(anyhow::anyhow!(
+            "Cannot create database directory '{}': {}. Point AGENTFS_HOME or \
+             AgentFSOptions::with_base_dir at a writable directory{}",
+            shown.display(),
+            err,
+            if self.temp_dir_fallback {
+                ""
+            } else {
+                ", or use AgentFSOptions::with_temp_dir_fallback"
+            }
+        ))
+    }
+
     /// Create options for a persistent agent with the given ID
     pub fn with_id(id: impl Into<String>) -> Self {
         Self {
             id: Some(id.into()),
-            path: None,
-            base: None,
+            ..Default::default()
         }
     }
 
     /// Create options for an ephemeral in-memory agent
     pub fn ephemeral() -> Self {
+        Self::default()
+    }
+
+    /// Create options for an ephemeral agent that spills to disk when it grows
+    ///
+    /// Pure in-memory agents hold everything in memory and can run a process out
+    /// of it on large workloads. This one starts in memory too, but once the
+    /// database grows past `threshold` bytes its contents are copied into a
+    /// temp file, which serves every later statement from behind a page cache
+    /// of `threshold` bytes. The size is checked whenever a write completes
+    /// outside of a transaction, so a transaction in flight finishes in memory
+    /// first. The file is deleted once the agent is closed or dropped.
+    ///
+    /// Like other ephemeral agents, the result reports
+    /// [`AgentFS::is_persistent`] as false.
+    pub fn ephemeral_spilling(threshold: u64) -> Self {
         Self {
-            id: None,
-            path: None,
-            base: None,
+            spill_threshold: Some(threshold),
+            ..Self::ephemeral()
+        }
+    }
+
+    /// Create options for a named in-memory agent shared within the process
+    ///
+    /// Every `AgentFS::open` with the same name attaches to the same in-memory
+    /// database, so several handles (e.g. components of an integration test) can
+    /// observe each other's writes. The database lives as long as at least one
+    /// `AgentFS` opened with this name is alive; once the last one is dropped, its
+    /// contents are discarded and the next open starts from an empty database.
+    ///
+    /// Sharing is limited to the current process: the databases are kept in a
+    /// process-wide registry, not in SQLite's shared cache, so another process
+    /// opening the same name gets a database of its own.
+    pub fn ephemeral_named(name: impl Into<String>) -> Self {
+        Self {
+            memory_name: Some(name.into()),
+            ..Default::default()
         }
     }
 
     /// Create options with a custom database path
     pub fn with_path(path: impl Into<String>) -> Self {
         Self {
-            id: None,
             path: Some(path.into()),
-            base: None,
+            ..Default::default()
+        }
+    }
+
+    /// Create options for an existing database file at an arbitrary location
+    ///
+    /// Unlike `with_path`, which creates the database if needed, this bypasses the
+    /// `.agentfs/{id}.db` convention only for databases that already exist, e.g. one
+    /// handed over by another tool. Returns an error if the path doesn't exist, is
+    /// not a regular file, or is not valid UTF-8.
+    pub fn with_existing_path(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if !path.exists() {
+            anyhow::bail!("Database '{}' does not exist", path.display());
+        }
+        if !path.is_file() {
+            anyhow::bail!("Database path '{}' is not a file", path.display());
         }
+        let path = path.to_str().ok_or_else(|| {
+            anyhow::anyhow!("Database path '{}' is not valid UTF-8", path.display())
+        })?;
+        Ok(Self::with_path(path))
+    }
+
+    /// Store `id`-named databases in `dir` instead of [`agentfs_dir`]
+    pub fn with_base_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.base_dir = Some(dir.into());
+        self
+    }
+
+    /// Fall back to the system temp directory if the database directory can't
+    /// be created
+    ///
+    /// Useful in containers whose working directory is read-only. Databases in
+    /// the temp directory may not survive a restart; check
+    /// [`AgentFS::db_path`] to see where one ended up.
+    pub fn with_temp_dir_fallback(mut self) -> Self {
+        self.temp_dir_fallback = true;
+        self
     }
 
     /// Set the base directory for overlay filesystem (copy-on-write)
@@ -154,14 +431,119 @@ impl AgentFSOptions {
         self
     }
 
+    /// Match filenames case-insensitively, preserving the original case for display
+    pub fn with_case_insensitive(mut self) -> Self {
+        self.filenames.case_insensitive = true;
+        self
+    }
+
+    /// Normalize filenames to Unicode NFC
+    pub fn with_unicode_nfc(mut self) -> Self {
+        self.filenames.unicode_nfc = true;
+        self
+    }
+
+    /// Keep an audit journal of filesystem mutations
+    ///
+    /// See [`filesystem::AgentFS::enable_journal`]. Not to be confused with the
+    /// SQLite journal mode.
+    pub fn with_journal(mut self) -> Self {
+        self.journal = true;
+        self
+    }
+
+    /// Set the journal mode applied when the database is opened
+    pub fn with_journal_mode(mut self, mode: JournalMode) -> Self {
+        self.journal_mode = Some(mode);
+        self
+    }
+
+    /// Run `PRAGMA name = value` on the connection when the database is opened
+    ///
+    /// Meant for tuning knobs like `cache_size`, `mmap_size` and `temp_store`,
+    /// which apply to the connection and are set again on every open.
+    /// `page_size` only takes effect when the database is created; on an
+    /// existing database it is silently ignored. Pragmas that could corrupt the
+    /// database or that have a dedicated option (`journal_mode`, `synchronous`)
+    /// are rejected by `AgentFS::open`.
+    pub fn with_pragma(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.pragmas.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the synchronous level of the connection
+    pub fn with_synchronous(mut self, synchronous: Synchronous) -> Self {
+        self.synchronous = synchronous;
+        self
+    }
+
+    /// Limit the size of any single file to `bytes`
+    ///
+    /// Writes that would grow a file beyond the limit fail with
+    /// `AgentFsError::FileTooLarge`. This is a per-file cap, separate from quotas.
+    pub fn with_max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    /// Limit how many symlinks are followed while resolving a path
+    ///
+    /// Resolving a path through more links (or a cycle) fails with
+    /// `FsError::SymlinkLoop`, the equivalent of `ELOOP`.
+    pub fn with_max_symlink_depth(mut self, depth: u32) -> Self {
+        self.max_symlink_depth = Some(depth);
+        self
+    }
+
+    /// Compress file blocks as they are written
+    ///
+    /// Existing blocks are left as they are; reads handle both compressed and
+    /// raw blocks, so the mode can change between opens.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Take timestamps from `clock` instead of the system clock
+    ///
+    /// Applies to file times, key-value `created_at`/`updated_at` and tool call
+    /// times. Use a [`MockClock`] to make time-dependent behaviour deterministic
+    /// in tests.
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Set how `pull` resolves entries changed both locally and on the remote
+    pub fn with_sync_conflict_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.sync_conflict_policy = policy;
+        self
+    }
+
+    /// Set the permission bits of the root directory
+    pub fn with_root_mode(mut self, mode: u32) -> Self {
+        self.root.mode = Some(mode);
+        self
+    }
+
+    /// Set the owner and group of the root directory
+    pub fn with_root_owner(mut self, uid: u32, gid: u32) -> Self {
+        self.root.uid = Some(uid);
+        self.root.gid = Some(gid);
+        self
+    }
+
     /// Resolve an id-or-path string to AgentFSOptions
     ///
     /// Resolution order (first match wins):
     /// 1. `:memory:` -> ephemeral in-memory database
     /// 2. Valid agent ID with existing `.agentfs/{id}.db` -> uses that agent
-    /// 3. Existing file path -> uses that path directly
+    /// 3. Existing file path -> uses that path directly, with a leading `~`
+    ///    expanded to the home directory and relative paths made absolute
     ///
-    /// Returns an error if neither an agent nor a file exists.
+    /// Returns an error if neither an agent nor a file exists. Arguments that
+    /// look like paths (containing `/`, ending in `.db` or starting with `~`)
+    /// are reported as missing files rather than unknown agents.
     pub fn resolve(id_or_path: impl Into<String>) -> Result<Self> {
         let id_or_path = id_or_path.into();
 
@@ -180,9 +562,9 @@ impl AgentFSOptions {
         }
 
         // Fall back to treating as a direct file path
-        let path = Path::new(&id_or_path);
-        if path.is_file() {
-            Ok(Self::with_path(id_or_path))
+        let path = absolute_path(&expand_home(&id_or_path))?;
+        if path.is_file() || looks_like_path(&id_or_path) {
+            Self::with_existing_path(path)
         } else {
             // Not a valid agent and not an existing file
             if AgentFSOptions::validate_agent_id(&id_or_path) {
@@ -201,15 +583,318 @@ impl AgentFSOptions {
     }
 }
 
+/// Whether an id-or-path argument was meant as a path rather than an agent ID
+///
+/// Relative paths climbing out with `..` are paths like any other; they can
+/// never name an agent, since agent IDs don't contain `/`.
+fn looks_like_path(id_or_path: &str) -> bool {
+    id_or_path.ends_with(".db") || id_or_path.starts_with('~') || id_or_path.contains('/')
+}
+
+/// Expand a leading `~` to the home directory
+///
+/// `~user` forms are left alone, as is everything when `HOME` isn't set.
+fn expand_home(path: &str) -> PathBuf {
+    let rest = match path.strip_prefix("~/") {
+        Some(rest) => rest,
+        None if path == "~" => "",
+        None => return PathBuf::from(path),
+    };
+    match std::env::var_os("HOME") {
+        Some(home) => Path::new(&home).join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+/// Make a path absolute by joining it to the current directory
+fn absolute_path(path: &Path) -> Result<PathBuf> {
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+    let cwd = std::env::current_dir().context("Failed to get the current directory")?;
+    Ok(cwd.join(path))
+}
+
+/// The database file behind a path passed to `Builder::new_local`, if any
+fn database_file(db_path: &str) -> Result<Option<PathBuf>> {
+    let in_memory = db_path.is_empty()
+        || db_path.starts_with(":memory:")
+        || (db_path.starts_with("file:") && db_path.contains("mode=memory"));
+    if in_memory {
+        return Ok(None);
+    }
+    absolute_path(Path::new(db_path)).map(Some)
+}
+
+/// Named in-memory databases shared by every `AgentFS` opened in this process.
+///
+/// Entries are weak so that a database is released once the last agent using it
+/// is dropped.
+static SHARED_MEMORY_DBS: OnceLock<Mutex<HashMap<String, Weak<Database>>>> = OnceLock::new();
+
+/// Get the shared in-memory database for `name`, creating it if needed.
+async fn open_shared_memory_db(name: &str) -> Result<Arc<Database>> {
+    let registry = SHARED_MEMORY_DBS.get_or_init(|| Mutex::new(HashMap::new()));
+    let lookup = |map: &HashMap<String, Weak<Database>>| map.get(name).and_then(Weak::upgrade);
+
+    if let Some(db) = lookup(&registry.lock().unwrap_or_else(|p| p.into_inner())) {
+        return Ok(db);
+    }
+
+    let db = Arc::new(Builder::new_local(":memory:").build().await?);
+
+    // Another task may have created the database while we were building ours.
+    let mut map = registry.lock().unwrap_or_else(|p| p.into_inner());
+    if let Some(existing) = lookup(&map) {
+        return Ok(existing);
+    }
+    map.retain(|_, weak| weak.strong_count() > 0);
+    map.insert(name.to_string(), Arc::downgrade(&db));
+    Ok(db)
+}
+
+/// Temp file an agent opened with [`AgentFSOptions::ephemeral_spilling`] spills to
+///
+/// The file and its WAL are deleted when this is dropped. On Unix, connections
+/// still open at that point keep working on the unlinked file.
+struct SpillFile {
+    path: PathBuf,
+}
+
+impl SpillFile {
+    /// Pick an unused path in the system temp directory
+    fn create() -> Result<Self> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let name = format!(
+            "agentfs-spill-{}-{}.db",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        );
+        let file = Self {
+            path: std::env::temp_dir().join(name),
+        };
+        // Left behind by an earlier process with the same pid
+        file.remove();
+        Ok(file)
+    }
+
+    fn path_str(&self) -> Result<&str> {
+        self.path.to_str().ok_or_else(|| {
+            anyhow::anyhow!("Temp path '{}' is not valid UTF-8", self.path.display())
+        })
+    }
+
+    fn remove(&self) {
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = self.path.clone().into_os_string();
+            path.push(suffix);
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        self.remove();
+    }
+}
+
+/// Storage of an agent opened with [`AgentFSOptions::ephemeral_spilling`]
+///
+/// Statements go to an in-memory database until a write leaves it larger than
+/// the threshold outside of a transaction. Its contents are then copied into
+/// the temp file, and every later statement goes there instead.
+struct SpillingBackend {
+    /// Held shared while a statement runs and exclusively while spilling, so
+    /// no write to the in-memory database is missed by the copy
+    gate: tokio::sync::RwLock<()>,
+    /// Database and connection statements currently go to
+    current: RwLock<(Arc<Database>, Arc<Connection>)>,
+    spilled: AtomicBool,
+    threshold: u64,
+    /// Pragmas to run on the file's connection before the data is copied
+    pragmas: Vec<String>,
+    /// Dropped last, so the file is only removed once nothing uses it
+    file: SpillFile,
+}
+
+impl SpillingBackend {
+    fn new(
+        db: Arc<Database>,
+        conn: Arc<Connection>,
+        threshold: u64,
+        pragmas: Vec<String>,
+    ) -> Result<Self> {
+        Ok(Self {
+            gate: tokio::sync::RwLock::new(()),
+            current: RwLock::new((db, conn)),
+            spilled: AtomicBool::new(false),
+            threshold,
+            pragmas,
+            file: SpillFile::create()?,
+        })
+    }
+
+    fn database(&self) -> Arc<Database> {
+        self.current
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .0
+            .clone()
+    }
+
+    fn connection(&self) -> Arc<Connection> {
+        self.current
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .1
+            .clone()
+    }
+
+    /// Whether the data has moved to the temp file
+    fn spilled(&self) -> bool {
+        self.spilled.load(Ordering::Acquire)
+    }
+
+    /// Whether the in-memory database has outgrown the threshold and no
+    /// transaction is open on it
+    async fn should_spill(&self, conn: &Connection) -> Result<bool> {
+        if !conn.is_autocommit()? {
+            return Ok(false);
+        }
+        let pragma = |name: &'static str| async move {
+            let mut rows = conn.query(&format!("PRAGMA {}", name), ()).await?;
+            let value = match rows.next().await? {
+                Some(row) => match row.get_value(0)? {
+                    Value::Integer(value) => value as u64,
+                    _ => 0,
+                },
+                None => 0,
+            };
+            anyhow::Ok(value)
+        };
+        Ok(pragma("page_count").await? * pragma("page_size").await? > self.threshold)
+    }
+
+    /// Move the data to the temp file if the in-memory database outgrew the threshold
+    async fn spill_if_needed(&self) -> Result<()> {
+        if self.spilled() || !self.should_spill(&self.connection()).await? {
+            return Ok(());
+        }
+
+        let _gate = self.gate.write().await;
+        let memory = self.connection();
+        // Another statement may have spilled or opened a transaction meanwhile
+        if self.spilled() || !self.should_spill(&memory).await? {
+            return Ok(());
+        }
+
+        let db = Arc::new(open_database(self.file.path_str()?).await?);
+        let conn = db.connect()?;
+        for pragma in &self.pragmas {
+            let mut rows = conn.query(pragma, ()).await?;
+            while rows.next().await?.is_some() {}
+        }
+        set_busy_timeout(&conn).await?;
+        if let Err(e) = copy_database(&memory, &conn).await {
+            drop((conn, db));
+            self.file.remove();
+            return Err(e.context("Failed to spill the ephemeral database to disk"));
+        }
+
+        *self.current.write().unwrap_or_else(|p| p.into_inner()) = (db, Arc::new(conn));
+        self.spilled.store(true, Ordering::Release);
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageBackend for SpillingBackend {
+    async fn execute(&self, sql: &str, params: Vec<Value>) -> error::Result<u64> {
+        let changes = {
+            let _gate = self.gate.read().await;
+            StorageBackend::execute(&*self.connection(), sql, params).await?
+        };
+        // The statement has taken effect either way. If the copy fails, the
+        // data stays in memory and the next write tries again.
+        let _ = self.spill_if_needed().await;
+        Ok(changes)
+    }
+
+    async fn query(&self, sql: &str, params: Vec<Value>) -> error::Result<storage::Rows> {
+        let _gate = self.gate.read().await;
+        StorageBackend::query(&*self.connection(), sql, params).await
+    }
+}
+
+/// Pragmas `AgentFSOptions::with_pragma` refuses to set
+///
+/// These either break the guarantees the schema relies on, change how the
+/// file is locked or stored behind the SDK's back, or have a typed option.
+const DENIED_PRAGMAS: &[&str] = &[
+    "journal_mode",
+    "synchronous",
+    "writable_schema",
+    "ignore_check_constraints",
+    "foreign_keys",
+    "schema_version",
+    "user_version",
+    "application_id",
+    "locking_mode",
+    "query_only",
+    "key",
+    "rekey",
+];
+
+/// Check a pragma from `AgentFSOptions::pragmas` before it is run
+///
+/// Names and values are spliced into the statement, so both are limited to
+/// plain words and numbers.
+fn validate_pragma(name: &str, value: &str) -> Result<()> {
+    let is_word = |s: &str, extra: &[char]| {
+        !s.is_empty()
+            && s.chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || extra.contains(&c))
+    };
+    if !is_word(name, &[]) {
+        anyhow::bail!("Invalid pragma name '{}'", name);
+    }
+    if DENIED_PRAGMAS.contains(&name.to_ascii_lowercase().as_str()) {
+        anyhow::bail!("Pragma '{}' cannot be set through AgentFSOptions", name);
+    }
+    if !is_word(value, &['-']) {
+        anyhow::bail!("Invalid value '{}' for pragma '{}'", value, name);
+    }
+    Ok(())
+}
+
 /// The main AgentFS SDK struct
 ///
 /// This provides a unified interface to the filesystem, key-value store,
 /// and tool calls tracking backed by a SQLite database.
 pub struct AgentFS {
     conn: Arc<Connection>,
+    /// Database handle when opened through `open`.
+    /// Also keeps shared in-memory databases alive while this instance exists.
+    db: Option<Arc<Database>>,
+    /// Absolute path of the database file, or None for in-memory databases
+    path: Option<PathBuf>,
     pub kv: KvStore,
     pub fs: filesystem::AgentFS,
     pub tools: ToolCalls,
+    /// Storage of a spilling ephemeral agent, whose temp file is removed
+    /// after everything else is dropped
+    spill: Option<Arc<SpillingBackend>>,
+}
+
+/// A consistent read-only view of an agent, from [`AgentFS::begin_read_snapshot`]
+///
+/// `fs` and `kv` offer the usual read methods. Writes through them fail, since
+/// the snapshot's connection is inside a read transaction. The transaction
+/// ends when both are dropped.
+pub struct ReadSnapshot {
+    pub fs: filesystem::AgentFS,
+    pub kv: KvStore,
 }
 
 impl AgentFS {
@@ -241,10 +926,46 @@ impl AgentFS {
                 anyhow::bail!("Base path is not a directory: {}", path.display());
             }
         }
+        for (name, value) in &options.pragmas {
+            validate_pragma(name, value)?;
+        }
+        let spill_threshold = match (&options.path, &options.id, &options.memory_name) {
+            (None, None, None) => options.spill_threshold,
+            _ => None,
+        };
         let db_path = options.db_path()?;
-        let db = Builder::new_local(&db_path).build().await?;
+        let db = match (&options.path, &options.id, &options.memory_name) {
+            (None, None, Some(name)) => open_shared_memory_db(name).await?,
+            _ => Arc::new(open_database(&db_path).await?),
+        };
         let conn = db.connect()?;
 
+        // First of all: once anything else is set, including the busy timeout
+        // and the journal mode, page_size no longer takes effect
+        for (name, value) in &options.pragmas {
+            let mut rows = conn
+                .query(&format!("PRAGMA {} = {}", name, value), ())
+                .await
+                .with_context(|| format!("Failed to set pragma '{}'", name))?;
+            while rows.next().await?.is_some() {}
+        }
+
+        // Another connection, such as a read snapshot, may hold the write lock
+        // (even creating the schema takes it), so wait for it rather than
+        // failing with SQLITE_BUSY
+        set_busy_timeout(&conn).await?;
+
+        let persistent = database_file(&db_path)?.is_some();
+        let journal_mode = options
+            .journal_mode
+            .or_else(|| persistent.then_some(JournalMode::Wal));
+        if let Some(mode) = journal_mode {
+            let mut rows = conn
+                .query(&format!("PRAGMA journal_mode = {}", mode.as_pragma()), ())
+                .await?;
+            while rows.next().await?.is_some() {}
+        }
+
         // Initialize overlay schema if base is provided
         if let Some(base_path) = options.base {
             let canonical_base = std::fs::canonicalize(base_path)?;
@@ -252,21 +973,101 @@ impl AgentFS {
             OverlayFS::init_schema(&conn, &base_path_str).await?;
         }
 
-        Self::open_with(conn).await
+        let conn = Arc::new(conn);
+        let spill = match spill_threshold {
+            Some(threshold) => {
+                let mut pragmas: Vec<String> = options
+                    .pragmas
+                    .iter()
+                    .map(|(name, value)| format!("PRAGMA {} = {}", name, value))
+                    .collect();
+                // A cache_size pragma given in the options wins
+                let cache_size_set = options
+                    .pragmas
+                    .iter()
+                    .any(|(name, _)| name.eq_ignore_ascii_case("cache_size"));
+                if !cache_size_set {
+                    // A negative cache size is in KiB rather than pages
+                    let kib = threshold.div_ceil(1024).max(1);
+                    pragmas.push(format!("PRAGMA cache_size = -{}", kib));
+                }
+                pragmas.push(format!(
+                    "PRAGMA synchronous = {}",
+                    options.synchronous.as_pragma()
+                ));
+                if let Some(mode) = options.journal_mode {
+                    pragmas.push(format!("PRAGMA journal_mode = {}", mode.as_pragma()));
+                }
+                Some(Arc::new(SpillingBackend::new(
+                    db.clone(),
+                    conn.clone(),
+                    threshold,
+                    pragmas,
+                )?))
+            }
+            None => None,
+        };
+        let backend: Arc<dyn StorageBackend> = match &spill {
+            Some(spill) => spill.clone(),
+            None => conn.clone(),
+        };
+
+        let mut agent =
+            Self::open_with_options(conn, backend, options.filenames, options.synchronous).await?;
+        agent.fs.set_max_file_size(options.max_file_size);
+        if let Some(depth) = options.max_symlink_depth {
+            agent.fs.set_max_symlink_depth(depth);
+        }
+        agent.fs.set_compression(options.compression);
+        if options.journal {
+            agent.fs.enable_journal().await?;
+        }
+        if let Some(clock) = options.clock {
+            agent.set_clock(clock);
+        }
+        if options.root != RootOptions::default() {
+            agent.fs.set_root_options(options.root).await?;
+        }
+        agent.db = Some(db);
+        agent.path = database_file(&db_path)?;
+        agent.spill = spill;
+        Ok(agent)
     }
 
     pub async fn open_with(conn: Connection) -> Result<Self> {
+        set_busy_timeout(&conn).await?;
         let conn = Arc::new(conn);
+        Self::open_with_options(
+            conn.clone(),
+            conn,
+            FilenameOptions::default(),
+            Synchronous::default(),
+        )
+        .await
+    }
 
-        let kv = KvStore::from_connection(conn.clone()).await?;
-        let fs = filesystem::AgentFS::from_connection(conn.clone()).await?;
-        let tools = ToolCalls::from_connection(conn.clone()).await?;
+    /// Set up the components on `backend`, which runs its statements on `conn`
+    /// unless the agent spills
+    async fn open_with_options(
+        conn: Arc<Connection>,
+        backend: Arc<dyn StorageBackend>,
+        filenames: FilenameOptions,
+        synchronous: Synchronous,
+    ) -> Result<Self> {
+        let kv = KvStore::from_backend(backend.clone()).await?;
+        let mut fs =
+            filesystem::AgentFS::from_backend_with_filenames(backend.clone(), filenames).await?;
+        fs.set_synchronous(synchronous).await?;
+        let tools = ToolCalls::from_backend(backend).await?;
 
         Ok(Self {
             conn,
+            db: None,
+            path: None,
             kv,
             fs,
             tools,
+            spill: None,
         })
     }
 
@@ -279,8 +1080,9 @@ impl AgentFS {
         note = "Use AgentFS::open with AgentFSOptions instead"
     )]
     pub async fn new(db_path: &str) -> Result<Self> {
-        let db = Builder::new_local(db_path).build().await?;
+        let db = open_database(db_path).await?;
         let conn = db.connect()?;
+        set_busy_timeout(&conn).await?;
         let conn = Arc::new(conn);
 
         let kv = KvStore::from_connection(conn.clone()).await?;
@@ -289,15 +1091,128 @@ impl AgentFS {
 
         Ok(Self {
             conn,
+            db: None,
+            path: database_file(db_path)?,
             kv,
             fs,
             tools,
+            spill: None,
         })
     }
 
     /// Get the underlying database connection
+    ///
+    /// For an agent opened with [`AgentFSOptions::ephemeral_spilling`], this is
+    /// the temp file's connection once the agent has spilled, so call it again
+    /// rather than holding on to the result.
     pub fn get_connection(&self) -> Arc<Connection> {
-        self.conn.clone()
+        match &self.spill {
+            Some(spill) => spill.connection(),
+            None => self.conn.clone(),
+        }
+    }
+
+    /// Database handle when opened through `open`, following a spill
+    fn database(&self) -> Option<Arc<Database>> {
+        match &self.spill {
+            Some(spill) => Some(spill.database()),
+            None => self.db.clone(),
+        }
+    }
+
+    /// Take a consistent read-only view of the filesystem and key-value store
+    ///
+    /// Reads through the snapshot all see the database as of this call, no
+    /// matter what is written meanwhile, which keeps long walks like exports
+    /// from observing half-applied changes. The snapshot holds a read
+    /// transaction on a connection of its own until it is dropped; with a WAL
+    /// journal, writers carry on in the meantime.
+    ///
+    /// Only available for instances created with `open`.
+    pub async fn begin_read_snapshot(&self) -> Result<ReadSnapshot> {
+        let db = self
+            .database()
+            .ok_or_else(|| anyhow::anyhow!("Read snapshots need an AgentFS created with open"))?;
+        let conn = Arc::new(db.connect()?);
+        set_busy_timeout(&conn).await?;
+
+        // A deferred transaction pins its view of the database at the first read
+        conn.execute("BEGIN", ()).await?;
+        let mut rows = conn.query("SELECT 1 FROM fs_inode LIMIT 1", ()).await?;
+        while rows.next().await?.is_some() {}
+        drop(rows);
+
+        Ok(ReadSnapshot {
+            fs: self.fs.with_backend(conn.clone()),
+            kv: self.kv.with_backend(conn),
+        })
+    }
+
+    /// Whether this instance is backed by a database file
+    ///
+    /// False for in-memory databases, including named shared ones, and for
+    /// instances created with `open_with`, whose connection isn't inspected.
+    pub fn is_persistent(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Absolute path of the database file this instance was opened from
+    ///
+    /// Returns None whenever [`AgentFS::is_persistent`] is false.
+    pub fn db_path(&self) -> Option<PathBuf> {
+        self.path.clone()
+    }
+
+    /// Take timestamps in the filesystem, key-value store and tool call
+    /// tracker from `clock`
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.kv.set_clock(clock.clone());
+        self.fs.set_clock(clock.clone());
+        self.tools.set_clock(clock);
+    }
+
+    /// Checkpoint the database and close the agent
+    ///
+    /// Dropping an `AgentFS` without calling this is best-effort: the connection
+    /// goes away with the last `Arc<Connection>`, whenever that happens to be,
+    /// and any frames still in the WAL are only folded into the database file by
+    /// a later open. `close` gives applications a deterministic shutdown point
+    /// after which the database file is complete on its own.
+    pub async fn close(self) -> Result<()> {
+        let mut rows = self.conn.query("PRAGMA journal_mode", ()).await?;
+        let wal = match rows.next().await? {
+            Some(row) => match row.get_value(0)? {
+                Value::Text(mode) => mode.eq_ignore_ascii_case("wal"),
+                _ => false,
+            },
+            None => false,
+        };
+        drop(rows);
+
+        if wal {
+            let mut rows = self
+                .conn
+                .query("PRAGMA wal_checkpoint(TRUNCATE)", ())
+                .await?;
+            while rows.next().await?.is_some() {}
+        }
+
+        // Release the components before the connection they share, and the
+        // connection before the database handle
+        let Self {
+            conn,
+            db,
+            path: _,
+            kv,
+            fs,
+            tools,
+            spill,
+        } = self;
+        drop((kv, fs, tools));
+        drop(conn);
+        drop(db);
+        drop(spill);
+        Ok(())
     }
 
     /// Get all paths in the delta layer (files in fs_dentry)
@@ -320,7 +1235,7 @@ impl AgentFS {
                 parent_ino
             );
 
-            let mut rows = self.conn.query(&query, ()).await?;
+            let mut rows = self.get_connection().query(&query, ()).await?;
 
             while let Some(row) = rows.next().await? {
                 let name: String = row
@@ -404,7 +1319,7 @@ impl AgentFS {
                 current_ino, component
             );
 
-            let mut rows = self.conn.query(&query, ()).await?;
+            let mut rows = self.get_connection().query(&query, ()).await?;
 
             if let Some(row) = rows.next().await? {
                 current_ino = row
@@ -441,7 +1356,10 @@ impl AgentFS {
     pub async fn get_whiteouts(&self) -> Result<HashSet<String>> {
         let mut whiteouts = HashSet::new();
 
-        let result = self.conn.query("SELECT path FROM fs_whiteout", ()).await;
+        let result = self
+            .get_connection()
+            .query("SELECT path FROM fs_whiteout", ())
+            .await;
 
         if let Ok(mut rows) = result {
             while let Some(row) = rows.next().await? {
@@ -489,16 +1407,292 @@ impl AgentFS {
             Err(_) => Ok(None), // Table doesn't exist
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Write a consistent point-in-time copy of the database to `path`
+    ///
+    /// The copy is taken inside a read transaction on a separate connection, so
+    /// the agent can keep working while the backup runs. In-memory databases are
+    /// materialized to the file. The result is a regular database file that can
+    /// be opened with `AgentFSOptions::with_path`.
+    ///
+    /// Tables, indexes, views and triggers are all copied; note that turso only
+    /// creates triggers with its experimental triggers feature enabled.
+    ///
+    /// Fails if `path` already exists, or if the agent was created with
+    /// `open_with` on an in-memory connection.
+    pub async fn backup_to(&self, path: &Path) -> Result<()> {
+        if path.exists() {
+            anyhow::bail!("Backup destination already exists: {}", path.display());
+        }
+        let dest_path = path.to_str().ok_or_else(|| {
+            anyhow::anyhow!("Backup path '{}' is not valid UTF-8", path.display())
+        })?;
+
+        // Use a dedicated connection so the snapshot doesn't interfere with
+        // transactions running on the agent's own connection.
+        let src = match self.database() {
+            Some(db) => db.connect()?,
+            None => self.connect_again().await?,
+        };
+        let dest = Builder::new_local(dest_path).build().await?.connect()?;
+
+        src.execute("BEGIN", ()).await?;
+        let result: Result<()> = async {
+            copy_database(&src, &dest).await?;
+            src.execute("COMMIT", ()).await?;
+            Ok(())
+        }
+        .await;
 
-    #[tokio::test]
-    async fn test_agentfs_creation() {
-        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
-        // Just verify we can get the connection
+        if result.is_err() {
+            let _ = src.execute("ROLLBACK", ()).await;
+            let _ = std::fs::remove_file(path);
+        }
+        result
+    }
+
+    /// Open another connection to the database file behind `conn`
+    ///
+    /// For instances created with `open_with`, which don't hold the database
+    /// their connection came from. An in-memory database can't be reached
+    /// from a second connection this way.
+    async fn connect_again(&self) -> Result<Connection> {
+        let mut rows = self.conn.query("PRAGMA database_list", ()).await?;
+        let mut file = None;
+        while let Some(row) = rows.next().await? {
+            if let (Ok(Value::Text(name)), Ok(Value::Text(path))) =
+                (row.get_value(1), row.get_value(2))
+            {
+                if name == "main" && !path.is_empty() {
+                    file = Some(path);
+                }
+            }
+        }
+        drop(rows);
+
+        let file = file.ok_or_else(|| {
+            anyhow::anyhow!("Backing up an in-memory database needs an AgentFS created with open")
+        })?;
+        let conn = Builder::new_local(&file).build().await?.connect()?;
+        set_busy_timeout(&conn).await?;
+        Ok(conn)
+    }
+
+    /// Copy the agent `src_id` to a new agent `dst_id`
+    ///
+    /// Both agents live in `base_dir` as `{id}.db` (normally [`agentfs_dir`]).
+    /// The copy is taken with [`AgentFS::backup_to`], so the source agent may be
+    /// in use while it is cloned. Fails if the destination agent already exists,
+    /// unless `force` is set, in which case it is replaced.
+    pub async fn clone_agent(
+        src_id: &str,
+        dst_id: &str,
+        base_dir: &Path,
+        force: bool,
+    ) -> Result<()> {
+        if !AgentFSOptions::validate_agent_id(src_id) {
+            anyhow::bail!(
+                "Invalid agent ID '{}'. Agent IDs must contain only alphanumeric characters, hyphens, and underscores.",
+                src_id
+            );
+        }
+        if src_id == dst_id {
+            anyhow::bail!("Source and destination agent are the same: '{}'", src_id);
+        }
+        let src_path = base_dir.join(format!("{}.db", src_id));
+        if !src_path.is_file() {
+            anyhow::bail!("Agent '{}' not found at '{}'", src_id, src_path.display());
+        }
+
+        let src = AgentFS::open(AgentFSOptions::with_path(src_path.to_str().ok_or_else(
+            || anyhow::anyhow!("Database path '{}' is not valid UTF-8", src_path.display()),
+        )?))
+        .await?;
+        let dst_path = prepare_agent_destination(dst_id, base_dir, force)?;
+        src.backup_to(&dst_path).await
+    }
+
+    /// Create the agent `dst_id` in `base_dir` from a backup file and open it
+    ///
+    /// Fails if the destination agent already exists, unless `force` is set, in
+    /// which case it is replaced.
+    pub async fn restore_from(
+        path: &Path,
+        dst_id: &str,
+        base_dir: &Path,
+        force: bool,
+    ) -> Result<Self> {
+        if !path.is_file() {
+            anyhow::bail!("Backup file not found: {}", path.display());
+        }
+        let backup = AgentFS::open(AgentFSOptions::with_path(path.to_str().ok_or_else(
+            || anyhow::anyhow!("Backup path '{}' is not valid UTF-8", path.display()),
+        )?))
+        .await?;
+        let dst_path = prepare_agent_destination(dst_id, base_dir, force)?;
+        backup.backup_to(&dst_path).await?;
+        drop(backup);
+
+        AgentFS::open(AgentFSOptions::with_path(dst_path.to_string_lossy())).await
+    }
+}
+
+/// Validate `dst_id` and make room for its database in `base_dir`.
+///
+/// Returns the path of the destination database file.
+fn prepare_agent_destination(dst_id: &str, base_dir: &Path, force: bool) -> Result<PathBuf> {
+    if !AgentFSOptions::validate_agent_id(dst_id) {
+        anyhow::bail!(
+            "Invalid agent ID '{}'. Agent IDs must contain only alphanumeric characters, hyphens, and underscores.",
+            dst_id
+        );
+    }
+    let dst_path = base_dir.join(format!("{}.db", dst_id));
+    if dst_path.exists() {
+        if !force {
+            anyhow::bail!(
+                "Agent '{}' already exists at '{}'. Use force to overwrite.",
+                dst_id,
+                dst_path.display()
+            );
+        }
+        for suffix in ["", "-wal", "-shm"] {
+            let file = base_dir.join(format!("{}.db{}", dst_id, suffix));
+            if file.exists() {
+                std::fs::remove_file(&file)?;
+            }
+        }
+    }
+    std::fs::create_dir_all(base_dir)?;
+    Ok(dst_path)
+}
+
+/// Quote an SQL identifier.
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Copy the schema and contents of every user table from `src` into `dest`.
+///
+/// Indexes and views are copied along with the tables. Triggers are created
+/// once the rows are in, so they don't fire on the copied data.
+async fn copy_database(src: &Connection, dest: &Connection) -> Result<()> {
+    // Tables first so that indexes and views can be created on them
+    let mut rows = src
+        .query(
+            "SELECT type, name, sql FROM sqlite_schema
+             WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%'
+             ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'trigger' THEN 2 ELSE 1 END",
+            (),
+        )
+        .await?;
+
+    let mut schema = Vec::new();
+    while let Some(row) = rows.next().await? {
+        let text = |idx| match row.get_value(idx) {
+            Ok(Value::Text(s)) => s,
+            _ => String::new(),
+        };
+        schema.push((text(0), text(1), text(2)));
+    }
+    drop(rows);
+
+    dest.execute("BEGIN IMMEDIATE", ()).await?;
+
+    let result: Result<()> = async {
+        for (kind, _, sql) in &schema {
+            if kind != "trigger" {
+                dest.execute(sql, ()).await?;
+            }
+        }
+
+        for (kind, name, _) in &schema {
+            if kind != "table" {
+                continue;
+            }
+            let table = quote_ident(name);
+            let mut rows = src.query(&format!("SELECT * FROM {}", table), ()).await?;
+            let mut insert = None;
+            while let Some(row) = rows.next().await? {
+                let count = row.column_count();
+                let values = (0..count)
+                    .map(|i| row.get_value(i).unwrap_or(Value::Null))
+                    .collect::<Vec<Value>>();
+                if insert.is_none() {
+                    let placeholders = vec!["?"; count].join(", ");
+                    let sql = format!("INSERT INTO {} VALUES ({})", table, placeholders);
+                    insert = Some(dest.prepare(&sql).await?);
+                }
+                if let Some(stmt) = insert.as_mut() {
+                    stmt.execute(values).await?;
+                }
+            }
+        }
+
+        // AUTOINCREMENT counters live in sqlite_sequence, which the schema
+        // filter skips. Copy them too, or the backup would hand out ids of
+        // deleted rows again.
+        let autoincrement = schema
+            .iter()
+            .any(|(kind, _, sql)| kind == "table" && sql.to_uppercase().contains("AUTOINCREMENT"));
+        if autoincrement {
+            let mut rows = src
+                .query("SELECT name, seq FROM sqlite_sequence", ())
+                .await?;
+            let mut sequences = Vec::new();
+            while let Some(row) = rows.next().await? {
+                if let (Ok(Value::Text(name)), Ok(Value::Integer(seq))) =
+                    (row.get_value(0), row.get_value(1))
+                {
+                    sequences.push((name, seq));
+                }
+            }
+            drop(rows);
+            // Inserting the rows already created entries for non-empty tables
+            for (name, seq) in sequences {
+                let mut existing = dest
+                    .query(
+                        "SELECT 1 FROM sqlite_sequence WHERE name = ?",
+                        [name.as_str()],
+                    )
+                    .await?;
+                let exists = existing.next().await?.is_some();
+                drop(existing);
+                let sql = if exists {
+                    "UPDATE sqlite_sequence SET seq = ?2 WHERE name = ?1"
+                } else {
+                    "INSERT INTO sqlite_sequence (name, seq) VALUES (?1, ?2)"
+                };
+                dest.execute(sql, (name, seq)).await?;
+            }
+        }
+
+        for (kind, _, sql) in &schema {
+            if kind == "trigger" {
+                dest.execute(sql, ()).await?;
+            }
+        }
+        Ok(())
+    }
+    .await;
+
+    if result.is_ok() {
+        dest.execute("COMMIT", ()).await?;
+    } else {
+        let _ = dest.execute("ROLLBACK", ()).await;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_agentfs_creation() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        // Just verify we can get the connection
         let _conn = agentfs.get_connection();
     }
 
@@ -537,6 +1731,191 @@ mod tests {
         assert_eq!(value, None);
     }
 
+    #[tokio::test]
+    async fn test_kv_typed_getters() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        agentfs.kv.set("count", &3i64).await.unwrap();
+        agentfs.kv.set("name", &"agent").await.unwrap();
+
+        // Missing keys and wrong types are told apart
+        assert_eq!(agentfs.kv.get::<i64>("missing").await.unwrap(), None);
+        let err = agentfs.kv.get::<i64>("name").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::WrongType(_)));
+
+        assert_eq!(agentfs.kv.try_get::<i64>("count").await.unwrap(), 3);
+        let err = agentfs.kv.try_get::<i64>("missing").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::NotFound(_)));
+        let err = agentfs.kv.try_get::<i64>("name").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::WrongType(_)));
+
+        assert_eq!(agentfs.kv.get_or("count", 0i64).await.unwrap(), 3);
+        assert_eq!(agentfs.kv.get_or("missing", 7i64).await.unwrap(), 7);
+        let err = agentfs.kv.get_or("name", 0i64).await.unwrap_err();
+        assert!(matches!(err, AgentFsError::WrongType(_)));
+
+        // A value whose bytes don't match its recorded format is a wrong type too
+        agentfs
+            .get_connection()
+            .execute(
+                "UPDATE kv_store SET format = 'cbor' WHERE key = 'count'",
+                (),
+            )
+            .await
+            .unwrap();
+        let err = agentfs.kv.get::<i64>("count").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::WrongType(_)));
+    }
+
+    #[tokio::test]
+    async fn test_kv_raw_keys() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let hash = [0xde, 0xad, 0x00, 0xff];
+
+        agentfs.kv.set_raw_key(&hash, &"blob").await.unwrap();
+        // A string key with the same bytes is a different key
+        agentfs.kv.set("abc", &"text").await.unwrap();
+        agentfs.kv.set_raw_key(b"abc", &"bytes").await.unwrap();
+
+        let value: Option<String> = agentfs.kv.get_raw_key(&hash).await.unwrap();
+        assert_eq!(value.as_deref(), Some("blob"));
+        let value: Option<String> = agentfs.kv.get("abc").await.unwrap();
+        assert_eq!(value.as_deref(), Some("text"));
+        let value: Option<String> = agentfs.kv.get_raw_key(b"abc").await.unwrap();
+        assert_eq!(value.as_deref(), Some("bytes"));
+
+        assert_eq!(agentfs.kv.keys().await.unwrap(), vec!["abc".to_string()]);
+        let mut raw_keys = agentfs.kv.raw_keys().await.unwrap();
+        raw_keys.sort();
+        assert_eq!(raw_keys, vec![b"abc".to_vec(), hash.to_vec()]);
+
+        agentfs.kv.delete_raw_key(&hash).await.unwrap();
+        let value: Option<String> = agentfs.kv.get_raw_key(&hash).await.unwrap();
+        assert_eq!(value, None);
+        let err = agentfs.kv.get_raw_key::<i64>(b"abc").await.unwrap_err();
+        assert!(err.to_string().contains("616263"));
+    }
+
+    #[tokio::test]
+    async fn test_kv_value_formats() {
+        let mut agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let value = vec![("alpha".to_string(), 1u32), ("beta".to_string(), 2)];
+
+        agentfs.kv.set("json", &value).await.unwrap();
+        agentfs.kv.set_format(ValueFormat::Cbor);
+        agentfs.kv.set("cbor", &value).await.unwrap();
+        agentfs.kv.set_format(ValueFormat::MessagePack);
+        agentfs.kv.set("msgpack", &value).await.unwrap();
+        agentfs.kv.set_format(ValueFormat::Json);
+
+        // Each value is read with the format it was written in
+        for key in ["json", "cbor", "msgpack"] {
+            let read: Option<Vec<(String, u32)>> = agentfs.kv.get(key).await.unwrap();
+            assert_eq!(read.as_ref(), Some(&value), "{key}");
+        }
+
+        let mut rows = agentfs
+            .get_connection()
+            .query(
+                "SELECT key, typeof(value), format FROM kv_store ORDER BY key",
+                (),
+            )
+            .await
+            .unwrap();
+        let mut stored = Vec::new();
+        while let Some(row) = rows.next().await.unwrap() {
+            let text = |i| match row.get_value(i).unwrap() {
+                Value::Text(text) => text,
+                other => panic!("unexpected {other:?}"),
+            };
+            stored.push((text(0), text(1), text(2)));
+        }
+        let expected = [
+            ("cbor", "blob", "cbor"),
+            ("json", "text", "json"),
+            ("msgpack", "blob", "msgpack"),
+        ];
+        let expected: Vec<(String, String, String)> = expected
+            .iter()
+            .map(|(a, b, c)| (a.to_string(), b.to_string(), c.to_string()))
+            .collect();
+        assert_eq!(stored, expected);
+
+        // Overwriting a value switches its format
+        agentfs.kv.set("cbor", &"now json").await.unwrap();
+        let read: Option<String> = agentfs.kv.get("cbor").await.unwrap();
+        assert_eq!(read.as_deref(), Some("now json"));
+    }
+
+    #[tokio::test]
+    async fn test_kv_store_without_format_column() {
+        let db = Builder::new_local(":memory:").build().await.unwrap();
+        let conn = Arc::new(db.connect().unwrap());
+        conn.execute(
+            "CREATE TABLE kv_store (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                created_at INTEGER DEFAULT (unixepoch()),
+                updated_at INTEGER DEFAULT (unixepoch())
+            )",
+            (),
+        )
+        .await
+        .unwrap();
+        conn.execute("INSERT INTO kv_store (key, value) VALUES ('old', '7')", ())
+            .await
+            .unwrap();
+
+        let kv = KvStore::from_connection(conn).await.unwrap();
+        let value: Option<i64> = kv.get("old").await.unwrap();
+        assert_eq!(value, Some(7));
+        let entry: Option<(i64, serde_json::Value)> = kv.get_with_meta("old").await.unwrap();
+        assert_eq!(entry, Some((7, serde_json::Value::Null)));
+    }
+
+    #[tokio::test]
+    async fn test_kv_metadata() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let meta = serde_json::json!({
+            "content-type": "text/html",
+            "etag": "\"abc123\"",
+            "fetched-at": 1700000000,
+        });
+        agentfs
+            .kv
+            .set_with_meta("https://example.com/", &"<html></html>", meta.clone())
+            .await
+            .unwrap();
+
+        let (body, read): (String, serde_json::Value) = agentfs
+            .kv
+            .get_with_meta("https://example.com/")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(body, "<html></html>");
+        assert_eq!(read, meta);
+        // The value alone reads as usual
+        let body: Option<String> = agentfs.kv.get("https://example.com/").await.unwrap();
+        assert_eq!(body.as_deref(), Some("<html></html>"));
+
+        // A plain set replaces the metadata along with the value
+        agentfs
+            .kv
+            .set("https://example.com/", &"new")
+            .await
+            .unwrap();
+        let entry: Option<(String, serde_json::Value)> = agentfs
+            .kv
+            .get_with_meta("https://example.com/")
+            .await
+            .unwrap();
+        assert_eq!(entry, Some(("new".to_string(), serde_json::Value::Null)));
+
+        let missing: Option<(String, serde_json::Value)> =
+            agentfs.kv.get_with_meta("missing").await.unwrap();
+        assert!(missing.is_none());
+    }
+
     #[tokio::test]
     async fn test_filesystem_operations() {
         let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
@@ -600,6 +1979,756 @@ mod tests {
         assert_eq!(stats.successful, 1);
     }
 
+    #[tokio::test]
+    async fn test_tool_calls_get_many() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+
+        let first = agentfs.tools.start("first", None).await.unwrap();
+        let second = agentfs.tools.start("second", None).await.unwrap();
+        agentfs.tools.error(second, "failed").await.unwrap();
+
+        let calls = agentfs
+            .tools
+            .get_many(&[second, 9999, first, second])
+            .await
+            .unwrap();
+        let names: Vec<Option<&str>> = calls
+            .iter()
+            .map(|call| call.as_ref().map(|call| call.name.as_str()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![Some("second"), None, Some("first"), Some("second")]
+        );
+        assert_eq!(calls[0].as_ref().unwrap().status, ToolCallStatus::Error);
+
+        assert!(agentfs.tools.get_many(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_mock_clock() {
+        let clock = Arc::new(MockClock::from_unix_time(1_000_000));
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral().with_clock(clock.clone()))
+            .await
+            .unwrap();
+
+        agentfs.fs.write_file("/a.txt", b"one").await.unwrap();
+        let stats = agentfs.fs.stat("/a.txt").await.unwrap().unwrap();
+        assert_eq!(stats.mtime, 1_000_000);
+        assert_eq!(stats.ctime, 1_000_000);
+
+        clock.advance(std::time::Duration::from_secs(30));
+        agentfs.fs.write_file("/a.txt", b"two").await.unwrap();
+        let stats = agentfs.fs.stat("/a.txt").await.unwrap().unwrap();
+        assert_eq!(stats.mtime, 1_000_030);
+
+        let id = agentfs.tools.start("tool", None).await.unwrap();
+        clock.advance(std::time::Duration::from_secs(2));
+        agentfs.tools.success(id, None).await.unwrap();
+        let call = agentfs.tools.get(id).await.unwrap().unwrap();
+        assert_eq!(call.started_at, 1_000_030);
+        assert_eq!(call.completed_at, Some(1_000_032));
+        assert_eq!(call.duration_ms, Some(2000));
+
+        agentfs.kv.set("key", &"value").await.unwrap();
+        let mut rows = agentfs
+            .get_connection()
+            .query(
+                "SELECT created_at, updated_at FROM kv_store WHERE key = 'key'",
+                (),
+            )
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert!(matches!(
+            row.get_value(0).unwrap(),
+            Value::Integer(1_000_032)
+        ));
+        assert!(matches!(
+            row.get_value(1).unwrap(),
+            Value::Integer(1_000_032)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_tool_call_stats_percentiles() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+
+        // 1..=10 seconds, recorded as 1000..=10000 ms
+        for secs in 1..=10 {
+            agentfs
+                .tools
+                .record("slow", 0, secs, None, None, None)
+                .await
+                .unwrap();
+        }
+        agentfs.tools.start("slow", None).await.unwrap();
+        agentfs
+            .tools
+            .record("fast", 5, 5, None, None, None)
+            .await
+            .unwrap();
+
+        let stats = agentfs.tools.stats_for("slow").await.unwrap().unwrap();
+        assert_eq!(stats.total_calls, 11);
+        // The pending call has no duration and is left out
+        assert_eq!(stats.p50_duration_ms, Some(5000));
+        assert_eq!(stats.p95_duration_ms, Some(10000));
+        assert_eq!(stats.p99_duration_ms, Some(10000));
+
+        let all = agentfs.tools.stats().await.unwrap();
+        let fast = all.iter().find(|s| s.name == "fast").unwrap();
+        assert_eq!(fast.p50_duration_ms, Some(0));
+        assert_eq!(fast.p99_duration_ms, Some(0));
+        let slow = all.iter().find(|s| s.name == "slow").unwrap();
+        assert_eq!(slow.p95_duration_ms, Some(10000));
+
+        agentfs.tools.start("pending", None).await.unwrap();
+        let pending = agentfs.tools.stats_for("pending").await.unwrap().unwrap();
+        assert_eq!(pending.p50_duration_ms, None);
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_in_flight() {
+        let clock = Arc::new(MockClock::from_unix_time(1_000_000));
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral().with_clock(clock.clone()))
+            .await
+            .unwrap();
+
+        let first = agentfs.tools.start("first", None).await.unwrap();
+        clock.advance(std::time::Duration::from_secs(5));
+        let done = agentfs.tools.start("done", None).await.unwrap();
+        agentfs.tools.success(done, None).await.unwrap();
+        let second = agentfs.tools.start("second", None).await.unwrap();
+        clock.advance(std::time::Duration::from_secs(2));
+
+        let calls = agentfs.tools.in_flight().await.unwrap();
+        let ids: Vec<_> = calls.iter().map(|call| call.id).collect();
+        assert_eq!(ids, vec![first, second]);
+        assert_eq!(calls[0].duration_ms, Some(7000));
+        assert_eq!(calls[1].duration_ms, Some(2000));
+        assert_eq!(calls[1].completed_at, None);
+
+        assert!(agentfs
+            .tools
+            .cancel(first, Some("agent restarted"))
+            .await
+            .unwrap());
+        let call = agentfs.tools.get(first).await.unwrap().unwrap();
+        assert_eq!(call.status, ToolCallStatus::Error);
+        assert_eq!(call.error.as_deref(), Some("cancelled: agent restarted"));
+        assert_eq!(call.duration_ms, Some(7000));
+
+        // Finished calls are left alone
+        assert!(!agentfs.tools.cancel(done, None).await.unwrap());
+        let call = agentfs.tools.get(done).await.unwrap().unwrap();
+        assert_eq!(call.status, ToolCallStatus::Success);
+        assert!(matches!(
+            agentfs.tools.cancel(12345, None).await,
+            Err(AgentFsError::NotFound(_))
+        ));
+
+        let calls = agentfs.tools.in_flight().await.unwrap();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id, second);
+    }
+
+    #[tokio::test]
+    async fn test_export_spans() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+
+        let ok_id = agentfs
+            .tools
+            .record(
+                "search",
+                100,
+                102,
+                Some(serde_json::json!({"q": "rust"})),
+                Some(serde_json::json!(["a", "b"])),
+                None,
+            )
+            .await
+            .unwrap();
+        agentfs
+            .tools
+            .record("fetch", 103, 104, None, None, Some("timeout"))
+            .await
+            .unwrap();
+
+        let spans = agentfs.tools.export_spans().await.unwrap();
+        assert_eq!(spans.len(), 2);
+        assert!(spans.iter().all(|s| s.trace_id == spans[0].trace_id));
+        assert_eq!(spans[0].trace_id.len(), 32);
+
+        let search = &spans[0];
+        assert_eq!(search.name, "search");
+        assert_eq!(search.span_id, format!("{:016x}", ok_id));
+        assert_eq!(search.status, SpanStatus::Ok);
+        assert_eq!(search.start_time_unix_nano, 100_000_000_000);
+        assert_eq!(search.end_time_unix_nano, Some(102_000_000_000));
+        assert_eq!(search.attributes["tool.parameters"], r#"{"q":"rust"}"#);
+        assert_eq!(search.attributes["tool.result"], r#"["a","b"]"#);
+
+        let fetch = &spans[1];
+        assert_eq!(fetch.status, SpanStatus::Error);
+        assert_eq!(fetch.status_message.as_deref(), Some("timeout"));
+
+        let otlp = toolcalls::to_otlp_json(&spans, "agent");
+        let otlp_spans = &otlp["resourceSpans"][0]["scopeSpans"][0]["spans"];
+        assert_eq!(otlp_spans.as_array().unwrap().len(), 2);
+        assert_eq!(otlp_spans[0]["startTimeUnixNano"], "100000000000");
+        assert_eq!(otlp_spans[1]["status"]["code"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_journal_mode_and_synchronous() {
+        async fn pragma(agentfs: &AgentFS, name: &str) -> Value {
+            let conn = agentfs.get_connection();
+            let mut rows = conn.query(&format!("PRAGMA {}", name), ()).await.unwrap();
+            let row = rows.next().await.unwrap().unwrap();
+            row.get_value(0).unwrap()
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("pragmas.db");
+        let options = AgentFSOptions::with_path(db_path.to_str().unwrap())
+            .with_journal_mode(JournalMode::Wal)
+            .with_synchronous(Synchronous::Normal);
+        let agentfs = AgentFS::open(options).await.unwrap();
+
+        match pragma(&agentfs, "journal_mode").await {
+            Value::Text(mode) => assert_eq!(mode.to_lowercase(), "wal"),
+            other => panic!("unexpected journal_mode: {:?}", other),
+        }
+        // turso doesn't implement NORMAL and runs it as FULL
+        assert_eq!(pragma(&agentfs, "synchronous").await, Value::Integer(2));
+
+        // An explicit fsync restores the configured level afterwards
+        agentfs.fs.write_file("/file", b"data").await.unwrap();
+        agentfs.fs.fsync("/file").await.unwrap();
+        assert_eq!(pragma(&agentfs, "synchronous").await, Value::Integer(2));
+        drop(agentfs);
+
+        for (synchronous, level) in [(Synchronous::Full, 2), (Synchronous::Off, 0)] {
+            let options =
+                AgentFSOptions::with_path(db_path.to_str().unwrap()).with_synchronous(synchronous);
+            let agentfs = AgentFS::open(options).await.unwrap();
+            assert_eq!(
+                pragma(&agentfs, "synchronous").await,
+                Value::Integer(level),
+                "{:?}",
+                synchronous
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pragmas() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("tuned.db");
+        let options = AgentFSOptions::with_path(db_path.to_str().unwrap())
+            .with_pragma("page_size", "8192")
+            .with_pragma("cache_size", "-4000");
+        let agentfs = AgentFS::open(options).await.unwrap();
+
+        let conn = agentfs.get_connection();
+        let mut rows = conn.query("PRAGMA page_size", ()).await.unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert!(matches!(row.get_value(0).unwrap(), Value::Integer(8192)));
+        drop(rows);
+        drop(agentfs);
+
+        // Dangerous pragmas and anything that isn't a plain value are rejected
+        for (name, value) in [
+            ("writable_schema", "1"),
+            ("Journal_Mode", "wal"),
+            ("cache_size", "1; DROP TABLE fs_inode"),
+            ("cache_size(0)", "1"),
+        ] {
+            let options =
+                AgentFSOptions::with_path(db_path.to_str().unwrap()).with_pragma(name, value);
+            assert!(AgentFS::open(options).await.is_err(), "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_close_checkpoints_wal() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("close.db");
+        let options = AgentFSOptions::with_path(db_path.to_str().unwrap())
+            .with_journal_mode(JournalMode::Wal);
+        let agentfs = AgentFS::open(options).await.unwrap();
+        agentfs.fs.write_file("/file", b"data").await.unwrap();
+        agentfs.close().await.unwrap();
+
+        // Everything is in the main database file once closed
+        let wal_path = dir.path().join("close.db-wal");
+        let wal_len = std::fs::metadata(&wal_path).map(|m| m.len()).unwrap_or(0);
+        assert_eq!(wal_len, 0);
+
+        let agentfs = AgentFS::open(AgentFSOptions::with_path(db_path.to_str().unwrap()))
+            .await
+            .unwrap();
+        let data = agentfs.fs.read_file("/file").await.unwrap();
+        assert_eq!(data, Some(b"data".to_vec()));
+
+        // Ephemeral agents close cleanly too
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        agentfs.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_spilling() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral_spilling(256 * 1024))
+            .await
+            .unwrap();
+        assert!(!agentfs.is_persistent());
+        let spill = agentfs.spill.clone().unwrap();
+        let spill_path = spill.file.path.clone();
+
+        // Small agents stay in memory
+        agentfs.fs.write_file("/small", b"small").await.unwrap();
+        agentfs.kv.set("key", &"value").await.unwrap();
+        assert!(!spill.spilled());
+        assert!(!spill_path.exists());
+
+        // Growing past the threshold moves everything to the temp file
+        let data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        agentfs.fs.write_file("/file0", &data).await.unwrap();
+        assert!(spill.spilled());
+        assert!(spill_path.exists());
+        assert!(!Arc::ptr_eq(&agentfs.get_connection(), &agentfs.conn));
+
+        for i in 1..4 {
+            let path = format!("/file{}", i);
+            agentfs.fs.write_file(&path, &data).await.unwrap();
+        }
+        assert_eq!(
+            agentfs.fs.read_file("/small").await.unwrap(),
+            Some(b"small".to_vec())
+        );
+        for i in 0..4 {
+            let path = format!("/file{}", i);
+            assert_eq!(
+                agentfs.fs.read_file(&path).await.unwrap(),
+                Some(data.clone())
+            );
+        }
+        let value: Option<String> = agentfs.kv.get("key").await.unwrap();
+        assert_eq!(value.as_deref(), Some("value"));
+
+        drop(spill);
+        agentfs.close().await.unwrap();
+        assert!(!spill_path.exists());
+
+        // A cache_size pragma wins over the threshold
+        let agentfs = AgentFS::open(
+            AgentFSOptions::ephemeral_spilling(1024).with_pragma("cache_size", "5000"),
+        )
+        .await
+        .unwrap();
+        agentfs.fs.write_file("/a", b"a").await.unwrap();
+        assert!(agentfs.spill.as_ref().unwrap().spilled());
+        let mut rows = agentfs
+            .get_connection()
+            .query("PRAGMA cache_size", ())
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(0).unwrap(), Value::Integer(5000));
+        drop(rows);
+        drop(agentfs);
+
+        // Dropping removes the file too, and each agent gets its own
+        let a = AgentFS::open(AgentFSOptions::ephemeral_spilling(1024))
+            .await
+            .unwrap();
+        let b = AgentFS::open(AgentFSOptions::ephemeral_spilling(1024))
+            .await
+            .unwrap();
+        a.fs.write_file("/a", b"a").await.unwrap();
+        b.fs.write_file("/b", b"b").await.unwrap();
+        assert!(b.fs.stat("/a").await.unwrap().is_none());
+        let a_path = a.spill.as_ref().unwrap().file.path.clone();
+        assert!(a_path.exists());
+        drop(a);
+        assert!(!a_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_read_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("snapshot.db");
+        let options = AgentFSOptions::with_path(db_path.to_str().unwrap())
+            .with_journal_mode(JournalMode::Wal);
+        let agentfs = AgentFS::open(options).await.unwrap();
+        agentfs.fs.write_file("/a.txt", b"before").await.unwrap();
+        agentfs.kv.set("key", &1).await.unwrap();
+
+        let snapshot = agentfs.begin_read_snapshot().await.unwrap();
+
+        // Writes made after the snapshot was taken are invisible to it
+        agentfs.fs.write_file("/a.txt", b"after").await.unwrap();
+        agentfs.fs.write_file("/b.txt", b"new").await.unwrap();
+        agentfs.kv.set("key", &2).await.unwrap();
+
+        let data = snapshot.fs.read_file("/a.txt").await.unwrap();
+        assert_eq!(data, Some(b"before".to_vec()));
+        assert!(snapshot.fs.stat("/b.txt").await.unwrap().is_none());
+        assert_eq!(
+            snapshot.fs.readdir("/").await.unwrap().unwrap(),
+            vec!["a.txt"]
+        );
+        let value: Option<i64> = snapshot.kv.get("key").await.unwrap();
+        assert_eq!(value, Some(1));
+
+        drop(snapshot);
+        let snapshot = agentfs.begin_read_snapshot().await.unwrap();
+        let data = snapshot.fs.read_file("/a.txt").await.unwrap();
+        assert_eq!(data, Some(b"after".to_vec()));
+
+        // Instances without a database handle can't take snapshots
+        let db = Builder::new_local(":memory:").build().await.unwrap();
+        let other = AgentFS::open_with(db.connect().unwrap()).await.unwrap();
+        assert!(other.begin_read_snapshot().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_root_options() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("root.db");
+        let db_path = db_path.to_str().unwrap();
+
+        let agentfs = AgentFS::open(AgentFSOptions::with_path(db_path))
+            .await
+            .unwrap();
+        let stats = agentfs.fs.stat("/").await.unwrap().unwrap();
+        assert_eq!(stats.mode, S_IFDIR | 0o755);
+        assert_eq!((stats.uid, stats.gid), (0, 0));
+        drop(agentfs);
+
+        let options = AgentFSOptions::with_path(db_path)
+            .with_root_mode(0o750)
+            .with_root_owner(1000, 100);
+        let agentfs = AgentFS::open(options).await.unwrap();
+        let stats = agentfs.fs.stat("/").await.unwrap().unwrap();
+        assert_eq!(stats.mode, S_IFDIR | 0o750);
+        assert_eq!((stats.uid, stats.gid), (1000, 100));
+        drop(agentfs);
+
+        // The root keeps its attributes when reopened without options
+        let agentfs = AgentFS::open(AgentFSOptions::with_path(db_path))
+            .await
+            .unwrap();
+        let stats = agentfs.fs.stat("/").await.unwrap().unwrap();
+        assert_eq!(stats.mode, S_IFDIR | 0o750);
+        assert_eq!((stats.uid, stats.gid), (1000, 100));
+    }
+
+    #[tokio::test]
+    async fn test_is_persistent() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("persistent.db");
+        let agentfs = AgentFS::open(AgentFSOptions::with_path(db_path.to_str().unwrap()))
+            .await
+            .unwrap();
+        assert!(agentfs.is_persistent());
+        assert_eq!(agentfs.db_path(), Some(db_path));
+
+        for options in [
+            AgentFSOptions::ephemeral(),
+            AgentFSOptions::ephemeral_named("test-is-persistent"),
+            AgentFSOptions::with_path(":memory:"),
+        ] {
+            let agentfs = AgentFS::open(options).await.unwrap();
+            assert!(!agentfs.is_persistent());
+            assert_eq!(agentfs.db_path(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_structured_errors() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+
+        agentfs.kv.set("key", &"not a number").await.unwrap();
+        let err = agentfs.kv.get::<i64>("key").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::WrongType(_)));
+
+        let err = agentfs.tools.success(42, None).await.unwrap_err();
+        assert!(matches!(err, AgentFsError::NotFound(_)));
+
+        let err = agentfs.fs.rename("/missing", "/other").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::NotFound(_)));
+
+        agentfs.fs.mkdir("/dir").await.unwrap();
+        agentfs.fs.write_file("/dir/file", b"data").await.unwrap();
+        let err = agentfs.fs.remove("/dir").await.unwrap_err();
+        assert!(matches!(err, AgentFsError::Fs(FsError::NotEmpty)));
+        assert_eq!(err.to_errno(), libc::ENOTEMPTY);
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_named_shares_data() {
+        let first = AgentFS::open(AgentFSOptions::ephemeral_named("test-shared"))
+            .await
+            .unwrap();
+        let second = AgentFS::open(AgentFSOptions::ephemeral_named("test-shared"))
+            .await
+            .unwrap();
+        let other = AgentFS::open(AgentFSOptions::ephemeral_named("test-shared-other"))
+            .await
+            .unwrap();
+
+        first.fs.write_file("/shared.txt", b"hello").await.unwrap();
+        first.kv.set("key", &"value").await.unwrap();
+
+        let data = second.fs.read_file("/shared.txt").await.unwrap();
+        assert_eq!(data, Some(b"hello".to_vec()));
+        let value: Option<String> = second.kv.get("key").await.unwrap();
+        assert_eq!(value, Some("value".to_string()));
+
+        // Databases with different names are isolated
+        assert!(other.fs.read_file("/shared.txt").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_named_released_after_last_drop() {
+        let agent = AgentFS::open(AgentFSOptions::ephemeral_named("test-released"))
+            .await
+            .unwrap();
+        agent.fs.write_file("/gone.txt", b"bye").await.unwrap();
+        drop(agent);
+
+        let agent = AgentFS::open(AgentFSOptions::ephemeral_named("test-released"))
+            .await
+            .unwrap();
+        assert!(agent.fs.read_file("/gone.txt").await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_base_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let base_dir = dir.path().join("agents");
+        let db_path = AgentFSOptions::with_id("based")
+            .with_base_dir(&base_dir)
+            .db_path()
+            .unwrap();
+        assert_eq!(db_path, format!("{}/based.db", base_dir.display()));
+        assert!(base_dir.is_dir());
+
+        // A regular file in the way makes the directory impossible to create
+        let blocker = dir.path().join("blocker");
+        std::fs::write(&blocker, b"").unwrap();
+        let options = AgentFSOptions::with_id("blocked").with_base_dir(blocker.join("agents"));
+        let err = options.db_path().unwrap_err().to_string();
+        assert!(err.contains(&blocker.join("agents").display().to_string()));
+        assert!(err.contains("AGENTFS_HOME"));
+
+        let db_path = options.with_temp_dir_fallback().db_path().unwrap();
+        assert_eq!(
+            PathBuf::from(db_path),
+            std::env::temp_dir().join("agentfs").join("blocked.db")
+        );
+    }
+
+    #[test]
+    fn test_ephemeral_named_invalid_name() {
+        let result = AgentFSOptions::ephemeral_named("../evil").db_path();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_ephemeral_named_db_path() {
+        let path = AgentFSOptions::ephemeral_named("test-path")
+            .db_path()
+            .unwrap();
+        assert_eq!(path, ":memory:test-path");
+        assert_eq!(database_file(&path).unwrap(), None);
+        let other = AgentFSOptions::ephemeral_named("test-path-other")
+            .db_path()
+            .unwrap();
+        assert_ne!(path, other);
+    }
+
+    #[tokio::test]
+    async fn test_backup_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("backup.db");
+
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        agentfs.fs.mkdir("/dir").await.unwrap();
+        agentfs
+            .fs
+            .write_file("/dir/file.txt", b"backed up")
+            .await
+            .unwrap();
+        agentfs.kv.set("key", &"value").await.unwrap();
+
+        agentfs.backup_to(&backup_path).await.unwrap();
+
+        // Changes after the backup are not part of it
+        agentfs.fs.write_file("/later.txt", b"later").await.unwrap();
+
+        let restored = AgentFS::open(AgentFSOptions::with_path(backup_path.to_str().unwrap()))
+            .await
+            .unwrap();
+        let data = restored.fs.read_file("/dir/file.txt").await.unwrap();
+        assert_eq!(data, Some(b"backed up".to_vec()));
+        let value: Option<String> = restored.kv.get("key").await.unwrap();
+        assert_eq!(value, Some("value".to_string()));
+        assert!(restored.fs.read_file("/later.txt").await.unwrap().is_none());
+
+        // Refuses to overwrite an existing file
+        assert!(agentfs.backup_to(&backup_path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_backup_to_copies_views() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("backup.db");
+
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        agentfs.kv.set("key", &"value").await.unwrap();
+        agentfs
+            .get_connection()
+            .execute("CREATE VIEW kv_keys AS SELECT key FROM kv_store", ())
+            .await
+            .unwrap();
+        agentfs.backup_to(&backup_path).await.unwrap();
+
+        let restored = AgentFS::open(AgentFSOptions::with_path(backup_path.to_str().unwrap()))
+            .await
+            .unwrap();
+        let mut rows = restored
+            .get_connection()
+            .query("SELECT key FROM kv_keys", ())
+            .await
+            .unwrap();
+        let row = rows.next().await.unwrap().unwrap();
+        assert_eq!(row.get_value(0).unwrap(), Value::Text("key".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_backup_to_from_open_with() {
+        let dir = tempfile::tempdir().unwrap();
+        let src_path = dir.path().join("src.db");
+        let backup_path = dir.path().join("backup.db");
+
+        let conn = Builder::new_local(src_path.to_str().unwrap())
+            .build()
+            .await
+            .unwrap()
+            .connect()
+            .unwrap();
+        let agentfs = AgentFS::open_with(conn).await.unwrap();
+        agentfs.fs.write_file("/file.txt", b"data").await.unwrap();
+        agentfs.backup_to(&backup_path).await.unwrap();
+
+        let restored = AgentFS::open(AgentFSOptions::with_path(backup_path.to_str().unwrap()))
+            .await
+            .unwrap();
+        let data = restored.fs.read_file("/file.txt").await.unwrap();
+        assert_eq!(data, Some(b"data".to_vec()));
+
+        // An in-memory connection can't be snapshotted from the outside
+        let conn = Builder::new_local(":memory:")
+            .build()
+            .await
+            .unwrap()
+            .connect()
+            .unwrap();
+        let agentfs = AgentFS::open_with(conn).await.unwrap();
+        let other = dir.path().join("other.db");
+        assert!(agentfs.backup_to(&other).await.is_err());
+        assert!(!other.exists());
+    }
+
+    #[tokio::test]
+    async fn test_clone_agent() {
+        let dir = tempfile::tempdir().unwrap();
+        let src_path = dir.path().join("template.db");
+        let src = AgentFS::open(AgentFSOptions::with_path(src_path.to_str().unwrap()))
+            .await
+            .unwrap();
+        src.fs.write_file("/hello.txt", b"template").await.unwrap();
+
+        AgentFS::clone_agent("template", "fork", dir.path(), false)
+            .await
+            .unwrap();
+
+        let fork_path = dir.path().join("fork.db");
+        let fork = AgentFS::open(AgentFSOptions::with_path(fork_path.to_str().unwrap()))
+            .await
+            .unwrap();
+        let data = fork.fs.read_file("/hello.txt").await.unwrap();
+        assert_eq!(data, Some(b"template".to_vec()));
+
+        // The clone is independent of the source
+        fork.fs.write_file("/hello.txt", b"forked").await.unwrap();
+        let data = src.fs.read_file("/hello.txt").await.unwrap();
+        assert_eq!(data, Some(b"template".to_vec()));
+        drop(fork);
+
+        // Existing destinations require force
+        let result = AgentFS::clone_agent("template", "fork", dir.path(), false).await;
+        assert!(result.unwrap_err().to_string().contains("already exists"));
+        AgentFS::clone_agent("template", "fork", dir.path(), true)
+            .await
+            .unwrap();
+
+        // Invalid IDs are rejected
+        assert!(
+            AgentFS::clone_agent("template", "../evil", dir.path(), false)
+                .await
+                .is_err()
+        );
+        assert!(AgentFS::clone_agent("missing", "other", dir.path(), false)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_clone_keeps_autoincrement_counters() {
+        let dir = tempfile::tempdir().unwrap();
+        let src_path = dir.path().join("template.db");
+        let src = AgentFS::open(AgentFSOptions::with_path(src_path.to_str().unwrap()))
+            .await
+            .unwrap();
+        src.fs.write_file("/gone.txt", b"x").await.unwrap();
+        let gone = src.fs.stat("/gone.txt").await.unwrap().unwrap().ino;
+        src.fs.remove("/gone.txt").await.unwrap();
+
+        AgentFS::clone_agent("template", "fork", dir.path(), false)
+            .await
+            .unwrap();
+
+        // Inode numbers of deleted files are not handed out again
+        let fork_path = dir.path().join("fork.db");
+        let fork = AgentFS::open(AgentFSOptions::with_path(fork_path.to_str().unwrap()))
+            .await
+            .unwrap();
+        fork.fs.write_file("/new.txt", b"y").await.unwrap();
+        let ino = fork.fs.stat("/new.txt").await.unwrap().unwrap().ino;
+        assert!(ino > gone, "inode {} reused after clone", ino);
+    }
+
+    #[tokio::test]
+    async fn test_restore_from() {
+        let dir = tempfile::tempdir().unwrap();
+        let backup_path = dir.path().join("snapshot.db");
+
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        agentfs.kv.set("key", &"value").await.unwrap();
+        agentfs.backup_to(&backup_path).await.unwrap();
+
+        let restored = AgentFS::restore_from(&backup_path, "restored", dir.path(), false)
+            .await
+            .unwrap();
+        let value: Option<String> = restored.kv.get("key").await.unwrap();
+        assert_eq!(value, Some("value".to_string()));
+        assert!(dir.path().join("restored.db").exists());
+    }
+
     #[test]
     fn test_resolve_memory() {
         let opts = AgentFSOptions::resolve(":memory:").unwrap();
@@ -622,6 +2751,69 @@ mod tests {
         let _ = std::fs::remove_file(&temp_file);
     }
 
+    #[test]
+    fn test_resolve_relative_path() {
+        let temp_file = tempfile::Builder::new()
+            .suffix(".db")
+            .tempfile_in(".")
+            .unwrap();
+        let name = temp_file.path().file_name().unwrap().to_str().unwrap();
+
+        let opts = AgentFSOptions::resolve(format!("./{}", name)).unwrap();
+        let expected = std::env::current_dir().unwrap().join(".").join(name);
+        assert_eq!(opts.path, Some(expected.to_str().unwrap().to_string()));
+    }
+
+    #[test]
+    fn test_resolve_missing_path() {
+        // Arguments that look like paths are reported as missing files
+        for arg in [
+            "missing-dir/agent.db",
+            "nonexistent-12345.db",
+            "~/nonexistent-12345",
+        ] {
+            let err = AgentFSOptions::resolve(arg).unwrap_err().to_string();
+            assert!(err.contains("does not exist"), "{}: {}", arg, err);
+        }
+    }
+
+    #[test]
+    fn test_expand_home() {
+        assert_eq!(expand_home("agent.db"), PathBuf::from("agent.db"));
+        assert_eq!(
+            expand_home("~user/agent.db"),
+            PathBuf::from("~user/agent.db")
+        );
+        if let Some(home) = std::env::var_os("HOME") {
+            assert_eq!(expand_home("~"), PathBuf::from(&home));
+            assert_eq!(expand_home("~/a.db"), Path::new(&home).join("a.db"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_with_existing_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("elsewhere.db");
+
+        let err = AgentFSOptions::with_existing_path(&db_path).unwrap_err();
+        assert!(err.to_string().contains("does not exist"));
+
+        let err = AgentFSOptions::with_existing_path(dir.path()).unwrap_err();
+        assert!(err.to_string().contains("is not a file"));
+
+        let agentfs = AgentFS::open(AgentFSOptions::with_path(db_path.to_str().unwrap()))
+            .await
+            .unwrap();
+        agentfs.kv.set("key", &"value").await.unwrap();
+        drop(agentfs);
+
+        let opts = AgentFSOptions::with_existing_path(&db_path).unwrap();
+        assert_eq!(opts.path, Some(db_path.to_str().unwrap().to_string()));
+        let agentfs = AgentFS::open(opts).await.unwrap();
+        let value: Option<String> = agentfs.kv.get("key").await.unwrap();
+        assert_eq!(value, Some("value".to_string()));
+    }
+
     #[test]
     fn test_resolve_valid_agent_id_with_existing_db() {
         // Setup: create .agentfs directory and a test database
@@ -640,10 +2832,9 @@ mod tests {
 
     #[test]
     fn test_resolve_invalid_agent_id() {
-        // Agent IDs with path traversal should be rejected
+        // `..` makes it a relative path, never an agent ID
         let result = AgentFSOptions::resolve("../evil");
-        assert!(result.is_err());
-        assert!(result.unwrap_err().to_string().contains("Invalid agent ID"));
+        assert!(result.unwrap_err().to_string().contains("does not exist"));
 
         // Agent IDs with spaces should be rejected
         let result = AgentFSOptions::resolve("invalid agent");
@@ -654,6 +2845,100 @@ mod tests {
         assert!(result.is_err());
     }
 
+    /// Database the `multiprocess_worker` test works on, when run as a child
+    const WORKER_DB_ENV: &str = "AGENTFS_TEST_WORKER_DB";
+    const WORKER_KEYS: usize = 200;
+
+    /// Body of the child processes spawned by `test_concurrent_processes`
+    ///
+    /// Ignored so it only runs when requested explicitly, and does nothing
+    /// without a database to work on.
+    #[tokio::test]
+    #[ignore]
+    async fn multiprocess_worker() {
+        let Ok(db_path) = std::env::var(WORKER_DB_ENV) else {
+            return;
+        };
+        let mode = std::env::var("AGENTFS_TEST_WORKER").unwrap();
+        // Tells the parent this test actually ran
+        std::fs::write(format!("{}.{}.started", db_path, mode), b"").unwrap();
+
+        if mode == "locked" {
+            let err = AgentFS::open(AgentFSOptions::with_path(&db_path))
+                .await
+                .err()
+                .expect("opened a database another process has open");
+            assert!(
+                err.to_string().contains("is open in another process"),
+                "{}",
+                err
+            );
+            return;
+        }
+
+        for i in 0..WORKER_KEYS {
+            // A fresh agent each time, like a CLI invocation
+            let agentfs = AgentFS::open(AgentFSOptions::with_path(&db_path))
+                .await
+                .unwrap();
+            agentfs.kv.set(&format!("key-{}", i), &i).await.unwrap();
+            let value: Option<usize> = agentfs.kv.get(&format!("key-{}", i)).await.unwrap();
+            assert_eq!(value, Some(i));
+            agentfs.close().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_processes() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("shared.db");
+        let db_path = db_path.to_str().unwrap();
+
+        // The workers are this test binary re-run with a filter
+        let exe = std::env::current_exe().unwrap();
+        let run_worker = |mode: &str| {
+            let output = std::process::Command::new(&exe)
+                .args(["--exact", "tests::multiprocess_worker", "--ignored"])
+                .env(WORKER_DB_ENV, db_path)
+                .env("AGENTFS_TEST_WORKER", mode)
+                .output()
+                .unwrap();
+            let log = format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+            assert!(
+                Path::new(&format!("{}.{}.started", db_path, mode)).exists(),
+                "multiprocess_worker did not run: {}",
+                log
+            );
+            assert!(output.status.success(), "worker failed: {}", log);
+        };
+
+        // While this process has the agent open, another one is turned away
+        // with an explanation instead of corrupting or waiting on it
+        let agentfs = AgentFS::open(AgentFSOptions::with_path(db_path))
+            .await
+            .unwrap();
+        agentfs.kv.set("parent", &1).await.unwrap();
+        run_worker("locked");
+        agentfs.close().await.unwrap();
+
+        // Once it is closed, the other process opens and writes it in turn
+        run_worker("writer");
+        let agentfs = AgentFS::open(AgentFSOptions::with_path(db_path))
+            .await
+            .unwrap();
+        assert_eq!(agentfs.kv.keys().await.unwrap().len(), WORKER_KEYS + 1);
+        let parent: Option<usize> = agentfs.kv.get("parent").await.unwrap();
+        assert_eq!(parent, Some(1));
+        for i in 0..WORKER_KEYS {
+            let value: Option<usize> = agentfs.kv.get(&format!("key-{}", i)).await.unwrap();
+            assert_eq!(value, Some(i));
+        }
+    }
+
     #[test]
     fn test_resolve_nonexistent_agent() {
         let result = AgentFSOptions::resolve("nonexistent-agent-12345");
@@ -0,0 +1,282 @@
+//! A cache of open agents for processes that serve many of them.
+
+use crate::{AgentFS, AgentFSOptions};
+use anyhow::Result;
+use lru::LruCache;
+use std::{
+    num::NonZeroUsize,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+/// Builds the options an agent is opened with from its id
+type OptionsFn = dyn Fn(&str) -> AgentFSOptions + Send + Sync;
+
+/// An open agent and when it was last handed out
+struct Entry {
+    agent: Arc<AgentFS>,
+    last_used: Instant,
+}
+
+/// Keeps agents open across requests, keyed by agent id
+///
+/// [`AgentManager::get`] opens an agent on first use and returns the cached
+/// handle afterwards. Once more than `capacity` agents are open, the least
+/// recently used one is evicted; with an idle timeout, agents not used for
+/// that long are evicted as well, on the next call to
+/// [`AgentManager::get`] or [`AgentManager::evict_idle`].
+///
+/// Evicted agents are closed with [`AgentFS::close`], which checkpoints the
+/// database. An agent whose handle is still held elsewhere cannot be closed
+/// yet; it is dropped from the cache and its connection goes away with the
+/// last handle.
+///
+/// ```no_run
+/// use agentfs_sdk::AgentManager;
+/// use std::{num::NonZeroUsize, time::Duration};
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let manager = AgentManager::new(NonZeroUsize::new(32).unwrap())
+///     .with_idle_timeout(Duration::from_secs(300));
+/// let agent = manager.get("my-agent").await?;
+/// agent.kv.set("status", &"running").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AgentManager {
+    agents: Mutex<LruCache<String, Entry>>,
+    idle_timeout: Option<Duration>,
+    options: Box<OptionsFn>,
+}
+
+impl AgentManager {
+    /// Create a manager keeping at most `capacity` agents open
+    ///
+    /// Agents are opened with [`AgentFSOptions::with_id`].
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            agents: Mutex::new(LruCache::new(capacity)),
+            idle_timeout: None,
+            options: Box::new(|id: &str| AgentFSOptions::with_id(id)),
+        }
+    }
+
+    /// Close agents that have not been used for `timeout`
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Open agents with the options returned by `options` for their id
+    pub fn with_options(
+        mut self,
+        options: impl Fn(&str) -> AgentFSOptions + Send + Sync + 'static,
+    ) -> Self {
+        self.options = Box::new(options);
+        self
+    }
+
+    /// Get the agent with the given id, opening it if it is not open yet
+    ///
+    /// Errors closing agents evicted to make room are not reported here; use
+    /// [`AgentManager::evict_idle`] or [`AgentManager::close_all`] to observe
+    /// them.
+    pub async fn get(&self, id: &str) -> Result<Arc<AgentFS>> {
+        let mut evicted = Vec::new();
+        let agent = {
+            let mut agents = self.agents.lock().await;
+            evicted.extend(self.take_idle(&mut agents));
+
+            match agents.get_mut(id) {
+                Some(entry) => {
+                    entry.last_used = Instant::now();
+                    entry.agent.clone()
+                }
+                None => {
+                    // Opening under the lock keeps concurrent callers from
+                    // opening the same agent twice
+                    let agent = Arc::new(AgentFS::open((self.options)(id)).await?);
+                    let entry = Entry {
+                        agent: agent.clone(),
+                        last_used: Instant::now(),
+                    };
+                    if let Some((_, old)) = agents.push(id.to_string(), entry) {
+                        evicted.push(old.agent);
+                    }
+                    agent
+                }
+            }
+        };
+
+        for old in evicted {
+            let _ = close(old).await;
+        }
+        Ok(agent)
+    }
+
+    /// Close the agent with the given id, if it is open
+    ///
+    /// Returns whether the agent was open.
+    pub async fn remove(&self, id: &str) -> Result<bool> {
+        let entry = self.agents.lock().await.pop(id);
+        match entry {
+            Some(entry) => {
+                close(entry.agent).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Close every agent that has been idle for longer than the idle timeout
+    ///
+    /// Servers can call this periodically, e.g. from a [`tokio::time::interval`]
+    /// loop, so that idle agents are closed even when no new requests arrive.
+    /// Returns the number of agents closed.
+    pub async fn evict_idle(&self) -> Result<usize> {
+        let idle = {
+            let mut agents = self.agents.lock().await;
+            self.take_idle(&mut agents)
+        };
+        close_each(idle).await
+    }
+
+    /// Close every open agent
+    pub async fn close_all(&self) -> Result<()> {
+        let all: Vec<_> = {
+            let mut agents = self.agents.lock().await;
+            std::iter::from_fn(|| agents.pop_lru())
+                .map(|(_, entry)| entry.agent)
+                .collect()
+        };
+        close_each(all).await.map(|_| ())
+    }
+
+    /// Number of agents currently open
+    pub async fn len(&self) -> usize {
+        self.agents.lock().await.len()
+    }
+
+    /// Check if no agent is open
+    pub async fn is_empty(&self) -> bool {
+        self.agents.lock().await.is_empty()
+    }
+
+    /// Remove the agents idle for longer than the idle timeout from the cache
+    fn take_idle(&self, agents: &mut LruCache<String, Entry>) -> Vec<Arc<AgentFS>> {
+        let Some(timeout) = self.idle_timeout else {
+            return Vec::new();
+        };
+        let mut idle = Vec::new();
+        // The least recently used entry is also the one idle the longest
+        while let Some((_, entry)) = agents.peek_lru() {
+            if entry.last_used.elapsed() < timeout {
+                break;
+            }
+            if let Some((_, entry)) = agents.pop_lru() {
+                idle.push(entry.agent);
+            }
+        }
+        idle
+    }
+}
+
+/// Close an agent unless its handle is still held elsewhere
+async fn close(agent: Arc<AgentFS>) -> Result<()> {
+    match Arc::try_unwrap(agent) {
+        Ok(agent) => agent.close().await,
+        Err(_) => Ok(()),
+    }
+}
+
+/// Close several agents, returning how many there were or the first error
+async fn close_each(agents: Vec<Arc<AgentFS>>) -> Result<usize> {
+    let count = agents.len();
+    let mut result = Ok(count);
+    for agent in agents {
+        if let Err(e) = close(agent).await {
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manager(dir: &std::path::Path, capacity: usize) -> AgentManager {
+        let dir = dir.to_path_buf();
+        AgentManager::new(NonZeroUsize::new(capacity).unwrap()).with_options(move |id| {
+            AgentFSOptions::with_path(dir.join(format!("{}.db", id)).to_str().unwrap())
+        })
+    }
+
+    #[test]
+    fn test_manager_is_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<AgentManager>();
+    }
+
+    #[tokio::test]
+    async fn test_manager_caches_agents() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = manager(dir.path(), 4);
+
+        let a = manager.get("a").await.unwrap();
+        a.kv.set("key", &"value").await.unwrap();
+        let again = manager.get("a").await.unwrap();
+        assert!(Arc::ptr_eq(&a, &again));
+        manager.get("b").await.unwrap();
+        assert_eq!(manager.len().await, 2);
+
+        assert!(manager.remove("b").await.unwrap());
+        assert!(!manager.remove("b").await.unwrap());
+        assert_eq!(manager.len().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_manager_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = manager(dir.path(), 2);
+
+        let a = manager.get("a").await.unwrap();
+        a.kv.set("key", &"from a").await.unwrap();
+        drop(a);
+        manager.get("b").await.unwrap();
+        manager.get("a").await.unwrap();
+        // "b" is now the least recently used agent
+        manager.get("c").await.unwrap();
+        assert_eq!(manager.len().await, 2);
+
+        let b = manager.get("b").await.unwrap();
+        assert!(b.kv.get::<String>("key").await.unwrap().is_none());
+        // "a" was evicted and closed; reopening it sees its data
+        let a = manager.get("a").await.unwrap();
+        assert_eq!(
+            a.kv.get::<String>("key").await.unwrap().as_deref(),
+            Some("from a")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_manager_evicts_idle_agents() {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = manager(dir.path(), 4).with_idle_timeout(Duration::from_millis(50));
+
+        manager.get("a").await.unwrap();
+        manager.get("b").await.unwrap();
+        assert_eq!(manager.evict_idle().await.unwrap(), 0);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        manager.get("b").await.unwrap();
+        assert_eq!(manager.len().await, 1);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(manager.evict_idle().await.unwrap(), 1);
+        assert!(manager.is_empty().await);
+    }
+}
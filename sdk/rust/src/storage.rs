@@ -0,0 +1,257 @@
+use crate::error::{AgentFsError, Result};
+use async_trait::async_trait;
+use std::ops::Deref;
+use turso::{Connection, Value};
+
+/// A row returned by a storage backend, one value per selected column
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Row(Vec<Value>);
+
+impl Row {
+    /// Get the value of the column at `idx`
+    pub fn get_value(&self, idx: usize) -> Result<Value> {
+        self.0.get(idx).cloned().ok_or_else(|| {
+            AgentFsError::Other(anyhow::anyhow!("Column index {} out of range", idx))
+        })
+    }
+
+    /// Get the number of columns
+    pub fn column_count(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl From<Vec<Value>> for Row {
+    fn from(values: Vec<Value>) -> Self {
+        Self(values)
+    }
+}
+
+impl From<Row> for Vec<Value> {
+    fn from(row: Row) -> Self {
+        row.0
+    }
+}
+
+impl Deref for Row {
+    type Target = [Value];
+
+    fn deref(&self) -> &[Value] {
+        &self.0
+    }
+}
+
+impl IntoIterator for Row {
+    type Item = Value;
+    type IntoIter = std::vec::IntoIter<Value>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Rows produced by a query, fetched one at a time
+#[async_trait]
+pub trait RowStream: Send {
+    /// Fetch the next row, or `None` once the query is exhausted
+    async fn next(&mut self) -> Result<Option<Row>>;
+}
+
+/// Rows returned by [`StorageBackend::query`]
+pub type Rows = Box<dyn RowStream>;
+
+/// Storage operations needed by the KV store, the filesystem and the tool
+/// call tracker
+///
+/// The turso `Connection` is the default implementation. Other backends
+/// (e.g. an in-memory mock in unit tests) only need to understand the SQL
+/// statements issued by those modules.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Execute a statement, returning the number of affected rows
+    async fn execute(&self, sql: &str, params: Vec<Value>) -> Result<u64>;
+
+    /// Run a query, streaming its rows
+    async fn query(&self, sql: &str, params: Vec<Value>) -> Result<Rows>;
+
+    /// Begin a write transaction
+    async fn begin(&self) -> Result<()> {
+        self.execute("BEGIN IMMEDIATE", Vec::new()).await?;
+        Ok(())
+    }
+
+    /// Commit the current transaction
+    async fn commit(&self) -> Result<()> {
+        self.execute("COMMIT", Vec::new()).await?;
+        Ok(())
+    }
+
+    /// Roll back the current transaction
+    async fn rollback(&self) -> Result<()> {
+        self.execute("ROLLBACK", Vec::new()).await?;
+        Ok(())
+    }
+
+    /// Run a query and return its first row, if any
+    async fn query_one(&self, sql: &str, params: Vec<Value>) -> Result<Option<Row>> {
+        self.query(sql, params).await?.next().await
+    }
+
+    /// Run a query and collect all resulting rows
+    async fn query_all(&self, sql: &str, params: Vec<Value>) -> Result<Vec<Row>> {
+        let mut rows = self.query(sql, params).await?;
+        let mut result = Vec::new();
+        while let Some(row) = rows.next().await? {
+            result.push(row);
+        }
+        Ok(result)
+    }
+}
+
+/// Statements are prepared through the connection's statement cache, as the
+/// same few statements are run over and over.
+#[async_trait]
+impl StorageBackend for Connection {
+    async fn execute(&self, sql: &str, params: Vec<Value>) -> Result<u64> {
+        let mut stmt = prepare(self, sql).await?;
+        Ok(stmt.execute(params).await?)
+    }
+
+    async fn query(&self, sql: &str, params: Vec<Value>) -> Result<Rows> {
+        let mut stmt = prepare(self, sql).await?;
+        Ok(Box::new(stmt.query(params).await?))
+    }
+}
+
+/// Prepare a statement, reusing the cached one unless it is a pragma: turso
+/// sets and reads pragmas while preparing, so a cached one would do nothing
+async fn prepare(conn: &Connection, sql: &str) -> Result<turso::Statement> {
+    let is_pragma = sql
+        .trim_start()
+        .get(..6)
+        .is_some_and(|keyword| keyword.eq_ignore_ascii_case("PRAGMA"));
+    if is_pragma {
+        Ok(conn.prepare(sql).await?)
+    } else {
+        Ok(conn.prepare_cached(sql).await?)
+    }
+}
+
+#[async_trait]
+impl RowStream for turso::Rows {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        let Some(row) = turso::Rows::next(self).await? else {
+            return Ok(None);
+        };
+        let mut values = Vec::with_capacity(row.column_count());
+        for idx in 0..row.column_count() {
+            values.push(row.get_value(idx)?);
+        }
+        Ok(Some(Row(values)))
+    }
+}
+
+/// Rows already in memory, e.g. the canned answers of a mock backend
+#[async_trait]
+impl RowStream for std::vec::IntoIter<Row> {
+    async fn next(&mut self) -> Result<Option<Row>> {
+        Ok(Iterator::next(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KvStore;
+    use std::sync::{Arc, Mutex};
+
+    /// Backend that records statements and answers every query with canned rows
+    struct MockBackend {
+        statements: Mutex<Vec<String>>,
+        rows: Vec<Row>,
+    }
+
+    #[async_trait]
+    impl StorageBackend for MockBackend {
+        async fn execute(&self, sql: &str, _params: Vec<Value>) -> Result<u64> {
+            self.statements.lock().unwrap().push(sql.to_string());
+            Ok(1)
+        }
+
+        async fn query(&self, sql: &str, _params: Vec<Value>) -> Result<Rows> {
+            self.statements.lock().unwrap().push(sql.to_string());
+            Ok(Box::new(self.rows.clone().into_iter()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_kv_store_with_mock_backend() {
+        let backend = Arc::new(MockBackend {
+            statements: Mutex::new(Vec::new()),
+            rows: vec![Row::from(vec![Value::Text("42".to_string())])],
+        });
+        let kv = KvStore::from_backend(backend.clone()).await.unwrap();
+
+        let value: Option<i64> = kv.get("answer").await.unwrap();
+        assert_eq!(value, Some(42));
+
+        let statements = backend.statements.lock().unwrap();
+        assert!(statements[0].contains("CREATE TABLE IF NOT EXISTS kv_store"));
+        assert!(statements
+            .last()
+            .unwrap()
+            .contains("FROM kv_store WHERE key = ?"));
+    }
+
+    #[tokio::test]
+    async fn test_connection_query_streams_rows() {
+        let db = turso::Builder::new_local(":memory:").build().await.unwrap();
+        let conn = db.connect().unwrap();
+        let backend: &dyn StorageBackend = &conn;
+        backend
+            .execute("CREATE TABLE t (n INTEGER)", Vec::new())
+            .await
+            .unwrap();
+        for n in 0..3i64 {
+            backend
+                .execute("INSERT INTO t (n) VALUES (?)", vec![n.into()])
+                .await
+                .unwrap();
+        }
+
+        let mut rows = backend
+            .query("SELECT n FROM t ORDER BY n", Vec::new())
+            .await
+            .unwrap();
+        for n in 0..3i64 {
+            let row = rows.next().await.unwrap().unwrap();
+            assert_eq!(row.get_value(0).unwrap(), Value::Integer(n));
+        }
+        assert!(rows.next().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_connection_pragmas_are_not_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pragmas.db");
+        let db = turso::Builder::new_local(path.to_str().unwrap())
+            .build()
+            .await
+            .unwrap();
+        let conn = db.connect().unwrap();
+        let backend: &dyn StorageBackend = &conn;
+
+        for level in [0, 2, 0, 2] {
+            backend
+                .execute(&format!("PRAGMA synchronous = {}", level), Vec::new())
+                .await
+                .unwrap();
+            let row = backend
+                .query_one("PRAGMA synchronous", Vec::new())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(row.get_value(0).unwrap(), Value::Integer(level));
+        }
+    }
+}
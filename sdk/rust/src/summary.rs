@@ -0,0 +1,168 @@
+//! At-a-glance summary of what an agent holds
+//!
+//! [`AgentFS::summary`] counts files, key-value entries and tool calls with a
+//! few aggregate queries, without reading file contents.
+
+use anyhow::Result;
+use serde::Serialize;
+use turso::Value;
+
+use crate::{
+    filesystem::{S_IFDIR, S_IFLNK, S_IFMT, S_IFREG},
+    storage::StorageBackend,
+    AgentFS, ToolCallStatus,
+};
+
+/// Logical contents of an agent, from [`AgentFS::summary`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AgentSummary {
+    /// Regular files, counting each inode once however many links it has
+    pub files: u64,
+    /// Directories, including the root
+    pub directories: u64,
+    pub symlinks: u64,
+    /// Apparent size of all regular files
+    pub file_bytes: u64,
+    /// Entries in the key-value store
+    pub kv_keys: u64,
+    pub tool_calls: ToolCallCounts,
+}
+
+/// Number of tool calls in each status
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ToolCallCounts {
+    pub pending: u64,
+    pub success: u64,
+    pub error: u64,
+}
+
+impl ToolCallCounts {
+    /// All tool calls, whatever their status
+    pub fn total(&self) -> u64 {
+        self.pending + self.success + self.error
+    }
+}
+
+/// Read an integer column, treating NULL (e.g. `SUM` over no rows) as 0
+fn count(row: &[Value], col: usize) -> u64 {
+    match row.get(col) {
+        Some(Value::Integer(n)) => *n as u64,
+        _ => 0,
+    }
+}
+
+impl AgentFS {
+    /// Count the agent's files, directories, key-value entries and tool calls
+    pub async fn summary(&self) -> Result<AgentSummary> {
+        let conn: &dyn StorageBackend = self.conn.as_ref();
+        let mut summary = AgentSummary::default();
+
+        let kind = |mode: u32| Value::Integer(mode as i64);
+        let row = conn
+            .query_one(
+                "SELECT
+                    SUM(CASE WHEN (mode & ?) = ? THEN 1 ELSE 0 END),
+                    SUM(CASE WHEN (mode & ?) = ? THEN 1 ELSE 0 END),
+                    SUM(CASE WHEN (mode & ?) = ? THEN 1 ELSE 0 END),
+                    SUM(CASE WHEN (mode & ?) = ? THEN size ELSE 0 END)
+                FROM fs_inode",
+                vec![
+                    kind(S_IFMT),
+                    kind(S_IFREG),
+                    kind(S_IFMT),
+                    kind(S_IFDIR),
+                    kind(S_IFMT),
+                    kind(S_IFLNK),
+                    kind(S_IFMT),
+                    kind(S_IFREG),
+                ],
+            )
+            .await?
+            .unwrap_or_default();
+        summary.files = count(&row, 0);
+        summary.directories = count(&row, 1);
+        summary.symlinks = count(&row, 2);
+        summary.file_bytes = count(&row, 3);
+
+        let row = conn
+            .query_one("SELECT COUNT(*) FROM kv_store", Vec::new())
+            .await?
+            .unwrap_or_default();
+        summary.kv_keys = count(&row, 0);
+
+        let mut rows = conn
+            .query(
+                "SELECT status, COUNT(*) FROM tool_calls GROUP BY status",
+                Vec::new(),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let status = match row.first() {
+                Some(Value::Text(status)) => ToolCallStatus::from(status.as_str()),
+                _ => ToolCallStatus::Pending,
+            };
+            let counter = match status {
+                ToolCallStatus::Pending => &mut summary.tool_calls.pending,
+                ToolCallStatus::Success => &mut summary.tool_calls.success,
+                ToolCallStatus::Error => &mut summary.tool_calls.error,
+            };
+            *counter += count(&row, 1);
+        }
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AgentFSOptions;
+
+    #[tokio::test]
+    async fn test_summary() {
+        let agentfs = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        let empty = agentfs.summary().await.unwrap();
+        assert_eq!(
+            empty,
+            AgentSummary {
+                directories: 1,
+                ..AgentSummary::default()
+            }
+        );
+
+        agentfs.fs.mkdir("/docs").await.unwrap();
+        agentfs
+            .fs
+            .write_file("/docs/a.txt", b"hello")
+            .await
+            .unwrap();
+        agentfs.fs.write_file("/b.txt", b"abc").await.unwrap();
+        agentfs.fs.link("/b.txt", "/docs/b.txt").await.unwrap();
+        agentfs.fs.symlink("a.txt", "/docs/link").await.unwrap();
+        agentfs.kv.set("one", &1).await.unwrap();
+        agentfs.kv.set("two", &2).await.unwrap();
+        let ok = agentfs.tools.start("search", None).await.unwrap();
+        agentfs.tools.success(ok, None).await.unwrap();
+        let failed = agentfs.tools.start("fetch", None).await.unwrap();
+        agentfs.tools.error(failed, "timeout").await.unwrap();
+        agentfs.tools.start("fetch", None).await.unwrap();
+
+        let summary = agentfs.summary().await.unwrap();
+        assert_eq!(
+            summary,
+            AgentSummary {
+                files: 2,
+                directories: 2,
+                symlinks: 1,
+                file_bytes: 8,
+                kv_keys: 2,
+                tool_calls: ToolCallCounts {
+                    pending: 1,
+                    success: 1,
+                    error: 1,
+                },
+            }
+        );
+        assert_eq!(summary.tool_calls.total(), 3);
+    }
+}
@@ -0,0 +1,487 @@
+//! Conflict detection and resolution for synced agents
+//!
+//! A pull replaces local rows with whatever the remote has, so a kv key or file
+//! that was written on both sides between two syncs silently ends up with one of
+//! the two values. To make that explicit, the caller snapshots what changed
+//! locally before pulling ([`AgentFS::changes_since`]) and hands the snapshot to
+//! [`AgentFS::reconcile`] afterwards. Every entry whose pulled value differs from
+//! the local one is a conflict, resolved according to a [`ConflictPolicy`].
+//!
+//! Only kv entries and regular files are tracked; deletions made locally are not.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use turso::Value;
+
+use crate::{AgentFS, ValueFormat, S_IFMT, S_IFREG};
+
+/// How to resolve an entry that changed both locally and on the remote
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Keep whichever side was written last (the default)
+    ///
+    /// Ties are broken by comparing the values, so every device resolves the
+    /// same conflict the same way.
+    #[default]
+    LastWriterWins,
+    /// Always restore the local value
+    PreferLocal,
+    /// Always keep the pulled value
+    PreferRemote,
+    /// Keep the pulled value and report the conflict to the caller
+    Manual,
+}
+
+/// A kv key or file path
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ChangeKey {
+    Kv(String),
+    File(String),
+}
+
+/// The value of an entry on one side of a sync
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    /// Raw value: the serialized value of a kv entry (JSON text, or CBOR or
+    /// MessagePack bytes) or the contents of a file.
+    /// None if the entry doesn't exist on this side.
+    pub value: Option<Vec<u8>>,
+    /// Format of a kv value; None for files and missing entries
+    pub format: Option<ValueFormat>,
+    /// Metadata stored with a kv value, as JSON text
+    pub meta: Option<String>,
+    /// Unix timestamp of the last write (0 if the entry doesn't exist)
+    pub updated_at: i64,
+}
+
+impl Version {
+    /// Version of an entry that doesn't exist
+    fn missing() -> Self {
+        Version {
+            value: None,
+            format: None,
+            meta: None,
+            updated_at: 0,
+        }
+    }
+
+    /// Version of a file with `contents` last written at `mtime`
+    fn file(contents: Option<Vec<u8>>, mtime: i64) -> Self {
+        Version {
+            value: contents,
+            format: None,
+            meta: None,
+            updated_at: mtime,
+        }
+    }
+
+    /// Whether both sides hold the same value, whenever it was written
+    fn same_contents(&self, other: &Version) -> bool {
+        (&self.value, self.format, &self.meta) == (&other.value, other.format, &other.meta)
+    }
+
+    /// Version of a kv entry from the `value, format, meta, updated_at`
+    /// columns of `row`, starting at column `first`
+    ///
+    /// Returns None for values in a format this version doesn't know.
+    fn kv(row: &turso::Row, first: usize) -> Result<Option<Self>> {
+        let value = match row.get_value(first)? {
+            Value::Text(text) => text.into_bytes(),
+            Value::Blob(bytes) => bytes,
+            _ => return Ok(None),
+        };
+        let format = match row.get_value(first + 1)? {
+            Value::Text(tag) => match ValueFormat::from_tag(&tag) {
+                Some(format) => format,
+                None => return Ok(None),
+            },
+            _ => ValueFormat::Json,
+        };
+        let meta = match row.get_value(first + 2)? {
+            Value::Text(meta) => Some(meta),
+            _ => None,
+        };
+        Ok(Some(Version {
+            value: Some(value),
+            format: Some(format),
+            meta,
+            updated_at: row.get_value(first + 3)?.as_integer().copied().unwrap_or(0),
+        }))
+    }
+}
+
+/// An entry that changed both locally and on the remote
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub key: ChangeKey,
+    pub local: Version,
+    pub remote: Version,
+}
+
+/// Which side of a conflict to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Local,
+    Remote,
+}
+
+impl Conflict {
+    /// Decide which side wins under `policy`, or None if the caller has to decide
+    pub fn resolve(&self, policy: ConflictPolicy) -> Option<Resolution> {
+        match policy {
+            ConflictPolicy::PreferLocal => Some(Resolution::Local),
+            ConflictPolicy::PreferRemote => Some(Resolution::Remote),
+            ConflictPolicy::Manual => None,
+            ConflictPolicy::LastWriterWins => {
+                let local = (self.local.updated_at, &self.local.value);
+                let remote = (self.remote.updated_at, &self.remote.value);
+                if local > remote {
+                    Some(Resolution::Local)
+                } else {
+                    Some(Resolution::Remote)
+                }
+            }
+        }
+    }
+}
+
+impl AgentFS {
+    /// Collect every kv entry and regular file written at or after `since`
+    ///
+    /// `since` is a Unix timestamp, typically the time of the last pull.
+    pub async fn changes_since(&self, since: i64) -> Result<BTreeMap<ChangeKey, Version>> {
+        let mut changes = BTreeMap::new();
+
+        let mut rows = self
+            .conn
+            .query(
+                "SELECT key, value, format, meta, updated_at FROM kv_store
+                 WHERE updated_at >= ?",
+                (since,),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            // Binary keys aren't tracked
+            if let Ok(Value::Text(key)) = row.get_value(0) {
+                if let Some(version) = Version::kv(&row, 1)? {
+                    changes.insert(ChangeKey::Kv(key), version);
+                }
+            }
+        }
+
+        let mut inodes = Vec::new();
+        let mut rows = self
+            .conn
+            .query(
+                "SELECT ino, mtime FROM fs_inode WHERE (mode & ?) = ? AND mtime >= ?",
+                (S_IFMT as i64, S_IFREG as i64, since),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let ino = row.get_value(0)?.as_integer().copied().unwrap_or(0);
+            let mtime = row.get_value(1)?.as_integer().copied().unwrap_or(0);
+            inodes.push((ino, mtime));
+        }
+        for (ino, mtime) in inodes {
+            let Some(path) = self.fs.path_for_ino(ino).await? else {
+                continue;
+            };
+            let value = self.fs.read_file(&path).await?;
+            changes.insert(ChangeKey::File(path), Version::file(value, mtime));
+        }
+
+        Ok(changes)
+    }
+
+    /// Compare the current (pulled) state against the local changes captured
+    /// before the pull and resolve the differences under `policy`
+    ///
+    /// Conflicts resolved in favour of the local side are written back. Returns
+    /// the conflicts left for the caller, which is non-empty only for
+    /// [`ConflictPolicy::Manual`].
+    pub async fn reconcile(
+        &self,
+        policy: ConflictPolicy,
+        local: &BTreeMap<ChangeKey, Version>,
+    ) -> Result<Vec<Conflict>> {
+        let mut unresolved = Vec::new();
+        for (key, local) in local {
+            let remote = self.current_version(key).await?;
+            if remote.same_contents(local) {
+                continue;
+            }
+            let conflict = Conflict {
+                key: key.clone(),
+                local: local.clone(),
+                remote,
+            };
+            match conflict.resolve(policy) {
+                Some(Resolution::Remote) => {}
+                Some(Resolution::Local) => self.restore(&conflict.key, &conflict.local).await?,
+                None => unresolved.push(conflict),
+            }
+        }
+        Ok(unresolved)
+    }
+
+    async fn current_version(&self, key: &ChangeKey) -> Result<Version> {
+        match key {
+            ChangeKey::Kv(key) => {
+                let mut rows = self
+                    .conn
+                    .query(
+                        "SELECT value, format, meta, updated_at FROM kv_store WHERE key = ?",
+                        (key.as_str(),),
+                    )
+                    .await?;
+                match rows.next().await? {
+                    Some(row) => Ok(Version::kv(&row, 0)?.unwrap_or_else(Version::missing)),
+                    None => Ok(Version::missing()),
+                }
+            }
+            ChangeKey::File(path) => match self.fs.stat(path).await? {
+                Some(stats) if stats.is_file() => {
+                    Ok(Version::file(self.fs.read_file(path).await?, stats.mtime))
+                }
+                _ => Ok(Version::missing()),
+            },
+        }
+    }
+
+    async fn restore(&self, key: &ChangeKey, version: &Version) -> Result<()> {
+        let Some(value) = &version.value else {
+            return Ok(());
+        };
+        match key {
+            ChangeKey::Kv(key) => {
+                // JSON is stored as text, the binary formats as BLOBs
+                let format = version.format.unwrap_or_default();
+                let value = match format {
+                    ValueFormat::Json => match String::from_utf8(value.clone()) {
+                        Ok(text) => Value::Text(text),
+                        Err(e) => Value::Blob(e.into_bytes()),
+                    },
+                    ValueFormat::Cbor | ValueFormat::MessagePack => Value::Blob(value.clone()),
+                };
+                let meta = match &version.meta {
+                    Some(meta) => Value::Text(meta.clone()),
+                    None => Value::Null,
+                };
+                let now = self.fs.clock().unix_time();
+                self.conn
+                    .execute(
+                        "INSERT INTO kv_store (key, value, format, meta, created_at, updated_at)
+                        VALUES (?, ?, ?, ?, ?, ?)
+                        ON CONFLICT(key) DO UPDATE SET
+                            value = excluded.value,
+                            format = excluded.format,
+                            meta = excluded.meta,
+                            updated_at = excluded.updated_at",
+                        vec![
+                            Value::Text(key.clone()),
+                            value,
+                            Value::Text(format.as_tag().to_string()),
+                            meta,
+                            Value::Integer(now),
+                            Value::Integer(now),
+                        ],
+                    )
+                    .await?;
+            }
+            ChangeKey::File(path) => {
+                // The pull may have removed the parent directories
+                if let Some((parent, _)) = path.rsplit_once('/') {
+                    self.fs.create_dir_all(parent).await?;
+                }
+                self.fs.write_file(path, value).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AgentFSOptions;
+
+    /// Simulate a pull that wrote `value` to `key` at `updated_at`
+    async fn pull_kv(agent: &AgentFS, key: &str, value: &str, updated_at: i64) {
+        agent
+            .get_connection()
+            .execute(
+                "UPDATE kv_store SET value = ?, format = 'json', meta = NULL, updated_at = ?
+                 WHERE key = ?",
+                (value, updated_at, key),
+            )
+            .await
+            .unwrap();
+    }
+
+    async fn setup() -> (AgentFS, BTreeMap<ChangeKey, Version>) {
+        let agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        agent.kv.set("unchanged", &1).await.unwrap();
+        agent.kv.set("key", &"local").await.unwrap();
+        agent.fs.mkdir("/docs").await.unwrap();
+        agent
+            .fs
+            .write_file("/docs/notes.md", b"local notes")
+            .await
+            .unwrap();
+        let local = agent.changes_since(0).await.unwrap();
+        (agent, local)
+    }
+
+    #[tokio::test]
+    async fn test_changes_since() {
+        let (agent, local) = setup().await;
+        assert_eq!(local.len(), 3);
+        assert_eq!(
+            local[&ChangeKey::File("/docs/notes.md".to_string())].value,
+            Some(b"local notes".to_vec())
+        );
+        assert_eq!(
+            local[&ChangeKey::Kv("key".to_string())].value,
+            Some(b"\"local\"".to_vec())
+        );
+        assert!(agent.changes_since(i64::MAX).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_manual_reports_conflicts() {
+        let (agent, local) = setup().await;
+        pull_kv(&agent, "key", "\"remote\"", 1).await;
+        agent.fs.remove("/docs/notes.md").await.unwrap();
+
+        let conflicts = agent
+            .reconcile(ConflictPolicy::Manual, &local)
+            .await
+            .unwrap();
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].key, ChangeKey::Kv("key".to_string()));
+        assert_eq!(conflicts[0].remote.value, Some(b"\"remote\"".to_vec()));
+        assert_eq!(
+            conflicts[1].key,
+            ChangeKey::File("/docs/notes.md".to_string())
+        );
+        assert_eq!(conflicts[1].remote.value, None);
+
+        // Manual leaves the pulled state in place
+        let value: Option<String> = agent.kv.get("key").await.unwrap();
+        assert_eq!(value.as_deref(), Some("remote"));
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_prefer_local_and_remote() {
+        let (agent, local) = setup().await;
+        pull_kv(&agent, "key", "\"remote\"", 1).await;
+        let conflicts = agent
+            .reconcile(ConflictPolicy::PreferRemote, &local)
+            .await
+            .unwrap();
+        assert!(conflicts.is_empty());
+        let value: Option<String> = agent.kv.get("key").await.unwrap();
+        assert_eq!(value.as_deref(), Some("remote"));
+
+        agent.fs.remove("/docs/notes.md").await.unwrap();
+        let conflicts = agent
+            .reconcile(ConflictPolicy::PreferLocal, &local)
+            .await
+            .unwrap();
+        assert!(conflicts.is_empty());
+        let value: Option<String> = agent.kv.get("key").await.unwrap();
+        assert_eq!(value.as_deref(), Some("local"));
+        assert_eq!(
+            agent.fs.read_file("/docs/notes.md").await.unwrap(),
+            Some(b"local notes".to_vec())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_last_writer_wins() {
+        let (agent, local) = setup().await;
+
+        // Remote write is older than the local one: local wins
+        pull_kv(&agent, "key", "\"remote\"", 1).await;
+        agent
+            .reconcile(ConflictPolicy::LastWriterWins, &local)
+            .await
+            .unwrap();
+        let value: Option<String> = agent.kv.get("key").await.unwrap();
+        assert_eq!(value.as_deref(), Some("local"));
+
+        // Remote write is newer: remote wins
+        pull_kv(&agent, "key", "\"remote\"", i64::MAX).await;
+        agent
+            .reconcile(ConflictPolicy::LastWriterWins, &local)
+            .await
+            .unwrap();
+        let value: Option<String> = agent.kv.get("key").await.unwrap();
+        assert_eq!(value.as_deref(), Some("remote"));
+    }
+
+    #[tokio::test]
+    async fn test_reconcile_restores_binary_values_and_meta() {
+        let mut agent = AgentFS::open(AgentFSOptions::ephemeral()).await.unwrap();
+        agent.kv.set_format(ValueFormat::Cbor);
+        let meta = serde_json::json!({"etag": "abc"});
+        agent
+            .kv
+            .set_with_meta("key", &vec![1u32, 2, 3], meta.clone())
+            .await
+            .unwrap();
+        let local = agent.changes_since(0).await.unwrap();
+        let version = &local[&ChangeKey::Kv("key".to_string())];
+        assert_eq!(version.format, Some(ValueFormat::Cbor));
+        assert_eq!(version.meta.as_deref(), Some(r#"{"etag":"abc"}"#));
+
+        pull_kv(&agent, "key", "\"remote\"", 1).await;
+        let conflicts = agent
+            .reconcile(ConflictPolicy::Manual, &local)
+            .await
+            .unwrap();
+        assert_eq!(conflicts[0].remote.format, Some(ValueFormat::Json));
+
+        agent
+            .reconcile(ConflictPolicy::PreferLocal, &local)
+            .await
+            .unwrap();
+        let value: Option<(Vec<u32>, serde_json::Value)> =
+            agent.kv.get_with_meta("key").await.unwrap();
+        assert_eq!(value, Some((vec![1, 2, 3], meta)));
+
+        // Once restored, the entry no longer conflicts
+        let conflicts = agent
+            .reconcile(ConflictPolicy::Manual, &local)
+            .await
+            .unwrap();
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_last_writer_wins_tie_is_deterministic() {
+        let a = Version::file(Some(b"a".to_vec()), 10);
+        let b = Version::file(Some(b"b".to_vec()), 10);
+        let key = ChangeKey::Kv("key".to_string());
+        let here = Conflict {
+            key: key.clone(),
+            local: a.clone(),
+            remote: b.clone(),
+        };
+        let there = Conflict {
+            key,
+            local: b,
+            remote: a,
+        };
+        // Both devices keep "b"
+        assert_eq!(
+            here.resolve(ConflictPolicy::LastWriterWins),
+            Some(Resolution::Remote)
+        );
+        assert_eq!(
+            there.resolve(ConflictPolicy::LastWriterWins),
+            Some(Resolution::Local)
+        );
+    }
+}
@@ -1,12 +1,17 @@
-use anyhow::Result;
+use crate::clock::{Clock, SystemClock};
+use crate::error::{AgentFsError, Result};
+use crate::storage::StorageBackend;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::{BTreeMap, HashMap},
     fmt,
     sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
 };
 use turso::{Builder, Connection, Value};
 
+/// Maximum number of IDs looked up per query by [`ToolCalls::get_many`]
+const GET_MANY_BATCH_SIZE: usize = 500;
+
 /// Status of a tool call
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -63,12 +68,172 @@ pub struct ToolCallStats {
     pub successful: i64,
     pub failed: i64,
     pub avg_duration_ms: f64,
+    /// Median duration of completed calls, `None` if none have completed
+    #[serde(default)]
+    pub p50_duration_ms: Option<i64>,
+    /// 95th percentile duration of completed calls
+    #[serde(default)]
+    pub p95_duration_ms: Option<i64>,
+    /// 99th percentile duration of completed calls
+    #[serde(default)]
+    pub p99_duration_ms: Option<i64>,
+}
+
+impl ToolCallStats {
+    /// Fill in the percentiles from durations sorted in ascending order
+    ///
+    /// Uses the nearest-rank method, so every percentile is an observed
+    /// duration. With fewer than 100 calls the high percentiles are simply the
+    /// slowest call.
+    fn set_percentiles(&mut self, sorted: &[i64]) {
+        self.p50_duration_ms = percentile(sorted, 50);
+        self.p95_duration_ms = percentile(sorted, 95);
+        self.p99_duration_ms = percentile(sorted, 99);
+    }
+}
+
+/// Nearest-rank percentile of durations sorted in ascending order
+fn percentile(sorted: &[i64], p: usize) -> Option<i64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+/// Status of an exported span, following OpenTelemetry status codes
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SpanStatus {
+    /// The tool call has not completed yet
+    Unset,
+    Ok,
+    Error,
+}
+
+/// A tool call exported as an OpenTelemetry-style span
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Span {
+    /// Trace identifier (32 hex characters), shared by all spans of an agent
+    pub trace_id: String,
+    /// Span identifier (16 hex characters), derived from the tool call ID
+    pub span_id: String,
+    /// Parent span identifier. Tool calls are not nested yet, so this is
+    /// always `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_span_id: Option<String>,
+    pub name: String,
+    pub start_time_unix_nano: u64,
+    /// End time, or `None` for tool calls that are still pending
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_time_unix_nano: Option<u64>,
+    pub status: SpanStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_message: Option<String>,
+    /// Tool call ID, input, output and duration as string attributes
+    pub attributes: BTreeMap<String, String>,
+}
+
+impl Span {
+    fn from_tool_call(trace_id: &str, call: &ToolCall) -> Self {
+        const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+        let mut attributes = BTreeMap::new();
+        attributes.insert("tool.call_id".to_string(), call.id.to_string());
+        if let Some(parameters) = &call.parameters {
+            attributes.insert("tool.parameters".to_string(), parameters.to_string());
+        }
+        if let Some(result) = &call.result {
+            attributes.insert("tool.result".to_string(), result.to_string());
+        }
+        if let Some(duration_ms) = call.duration_ms {
+            attributes.insert("tool.duration_ms".to_string(), duration_ms.to_string());
+        }
+
+        let status = match call.status {
+            ToolCallStatus::Pending => SpanStatus::Unset,
+            ToolCallStatus::Success => SpanStatus::Ok,
+            ToolCallStatus::Error => SpanStatus::Error,
+        };
+
+        Span {
+            trace_id: trace_id.to_string(),
+            span_id: format!("{:016x}", call.id),
+            parent_span_id: None,
+            name: call.name.clone(),
+            start_time_unix_nano: call.started_at.max(0) as u64 * NANOS_PER_SEC,
+            end_time_unix_nano: call.completed_at.map(|t| t.max(0) as u64 * NANOS_PER_SEC),
+            status,
+            status_message: call.error.clone(),
+            attributes,
+        }
+    }
+}
+
+/// Serialize spans as an OTLP/JSON `ExportTraceServiceRequest` body
+///
+/// The output can be posted as-is to a collector's `/v1/traces` endpoint.
+pub fn to_otlp_json(spans: &[Span], service_name: &str) -> serde_json::Value {
+    let spans: Vec<serde_json::Value> = spans
+        .iter()
+        .map(|span| {
+            let attributes: Vec<serde_json::Value> = span
+                .attributes
+                .iter()
+                .map(|(key, value)| {
+                    serde_json::json!({ "key": key, "value": { "stringValue": value } })
+                })
+                .collect();
+            let code = match span.status {
+                SpanStatus::Unset => 0,
+                SpanStatus::Ok => 1,
+                SpanStatus::Error => 2,
+            };
+            let mut status = serde_json::json!({ "code": code });
+            if let Some(message) = &span.status_message {
+                status["message"] = message.clone().into();
+            }
+            let mut otlp_span = serde_json::json!({
+                "traceId": span.trace_id,
+                "spanId": span.span_id,
+                "name": span.name,
+                // SPAN_KIND_INTERNAL
+                "kind": 1,
+                "startTimeUnixNano": span.start_time_unix_nano.to_string(),
+                "endTimeUnixNano": span
+                    .end_time_unix_nano
+                    .unwrap_or(span.start_time_unix_nano)
+                    .to_string(),
+                "attributes": attributes,
+                "status": status,
+            });
+            if let Some(parent) = &span.parent_span_id {
+                otlp_span["parentSpanId"] = parent.clone().into();
+            }
+            otlp_span
+        })
+        .collect();
+
+    serde_json::json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [
+                    { "key": "service.name", "value": { "stringValue": service_name } }
+                ]
+            },
+            "scopeSpans": [{
+                "scope": { "name": "agentfs" },
+                "spans": spans,
+            }]
+        }]
+    })
 }
 
 /// Tool calls tracker backed by SQLite
 #[derive(Clone)]
 pub struct ToolCalls {
-    conn: Arc<Connection>,
+    backend: Arc<dyn StorageBackend>,
+    clock: Arc<dyn Clock>,
 }
 
 impl ToolCalls {
@@ -76,23 +241,27 @@ impl ToolCalls {
     pub async fn new(db_path: &str) -> Result<Self> {
         let db = Builder::new_local(db_path).build().await?;
         let conn = db.connect()?;
-        let tc = Self {
-            conn: Arc::new(conn),
-        };
-        tc.initialize().await?;
-        Ok(tc)
+        Self::from_connection(Arc::new(conn)).await
     }
 
     /// Create a tool calls tracker from an existing connection
     pub async fn from_connection(conn: Arc<Connection>) -> Result<Self> {
-        let tc = Self { conn };
+        Self::from_backend(conn).await
+    }
+
+    /// Create a tool calls tracker on top of an arbitrary storage backend
+    pub async fn from_backend(backend: Arc<dyn StorageBackend>) -> Result<Self> {
+        let tc = Self {
+            backend,
+            clock: Arc::new(SystemClock),
+        };
         tc.initialize().await?;
         Ok(tc)
     }
 
     /// Initialize the database schema
     async fn initialize(&self) -> Result<()> {
-        self.conn
+        self.backend
             .execute(
                 "CREATE TABLE IF NOT EXISTS tool_calls (
                     id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -105,87 +274,83 @@ impl ToolCalls {
                     completed_at INTEGER,
                     duration_ms INTEGER
                 )",
-                (),
+                Vec::new(),
             )
             .await?;
 
-        self.conn
+        self.backend
             .execute(
                 "CREATE INDEX IF NOT EXISTS idx_tool_calls_name
                 ON tool_calls(name)",
-                (),
+                Vec::new(),
             )
             .await?;
 
-        self.conn
+        self.backend
             .execute(
                 "CREATE INDEX IF NOT EXISTS idx_tool_calls_started_at
                 ON tool_calls(started_at)",
-                (),
+                Vec::new(),
             )
             .await?;
 
         Ok(())
     }
 
+    /// Set the clock start and completion times are taken from
+    ///
+    /// Defaults to [`SystemClock`]. Times passed to [`ToolCalls::record`] are
+    /// stored as given.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+
     /// Start a new tool call and mark it as pending
     /// Returns the ID of the created tool call record
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(name = name), ret))]
     pub async fn start(&self, name: &str, parameters: Option<serde_json::Value>) -> Result<i64> {
         let serialized_params = parameters.map(|p| serde_json::to_string(&p)).transpose()?;
-        let started_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        let started_at = self.clock.unix_time();
 
-        let mut stmt = self
-            .conn
-            .prepare(
+        let row = self
+            .backend
+            .query_one(
                 "INSERT INTO tool_calls (name, parameters, status, started_at)
                 VALUES (?, ?, 'pending', ?) RETURNING id",
+                vec![
+                    Value::Text(name.to_string()),
+                    Value::Text(serialized_params.unwrap_or_default()),
+                    Value::Integer(started_at),
+                ],
             )
             .await?;
-        let row = stmt
-            .query_row((name, serialized_params.as_deref().unwrap_or(""), started_at))
-            .await?;
 
-        let id = row
-            .get_value(0)
-            .ok()
-            .and_then(|v| v.as_integer().copied())
-            .ok_or_else(|| anyhow::anyhow!("Failed to get tool call ID"))?;
-        Ok(id)
+        row.as_deref()
+            .and_then(|row| integer_at(row, 0))
+            .ok_or_else(|| AgentFsError::Other(anyhow::anyhow!("Failed to get tool call ID")))
     }
 
     /// Mark a tool call as successful
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(id = id)))]
     pub async fn success(&self, id: i64, result: Option<serde_json::Value>) -> Result<()> {
         let serialized_result = result.map(|r| serde_json::to_string(&r)).transpose()?;
-        let completed_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        let completed_at = self.clock.unix_time();
 
         // Get the started_at time to calculate duration
-        let mut rows = self
-            .conn
-            .query("SELECT started_at FROM tool_calls WHERE id = ?", (id,))
-            .await?;
-
-        let started_at = if let Some(row) = rows.next().await? {
-            row.get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .ok_or_else(|| anyhow::anyhow!("Invalid started_at value"))?
-        } else {
-            anyhow::bail!("Tool call not found");
-        };
-
+        let started_at = self.started_at(id).await?;
         let duration_ms = (completed_at - started_at) * 1000;
 
-        self.conn
+        self.backend
             .execute(
                 "UPDATE tool_calls
                 SET result = ?, status = 'success', completed_at = ?, duration_ms = ?
                 WHERE id = ?",
-                (
-                    serialized_result.as_deref().unwrap_or(""),
-                    completed_at,
-                    duration_ms,
-                    id,
-                ),
+                vec![
+                    Value::Text(serialized_result.unwrap_or_default()),
+                    Value::Integer(completed_at),
+                    Value::Integer(duration_ms),
+                    Value::Integer(id),
+                ],
             )
             .await?;
 
@@ -195,6 +360,10 @@ impl ToolCalls {
     /// Record a completed tool call (spec-compliant insert-only method)
     /// Either result or error should be provided, not both
     /// Returns the ID of the created tool call record
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(name = name, error = error.is_some()), ret)
+    )]
     pub async fn record(
         &self,
         name: &str,
@@ -209,95 +378,183 @@ impl ToolCalls {
         let duration_ms = (completed_at - started_at) * 1000;
         let status = if error.is_some() { "error" } else { "success" };
 
-        let mut stmt = self.conn
-            .prepare(
+        let row = self
+            .backend
+            .query_one(
                 "INSERT INTO tool_calls (name, parameters, result, error, status, started_at, completed_at, duration_ms)
-                VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING id"
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?) RETURNING id",
+                vec![
+                    Value::Text(name.to_string()),
+                    Value::Text(serialized_params.unwrap_or_default()),
+                    Value::Text(serialized_result.unwrap_or_default()),
+                    Value::Text(error.unwrap_or("").to_string()),
+                    Value::Text(status.to_string()),
+                    Value::Integer(started_at),
+                    Value::Integer(completed_at),
+                    Value::Integer(duration_ms),
+                ],
             )
             .await?;
 
-        let row = stmt
-            .query_row((
-                name,
-                serialized_params.as_deref().unwrap_or(""),
-                serialized_result.as_deref().unwrap_or(""),
-                error.unwrap_or(""),
-                status,
-                started_at,
-                completed_at,
-                duration_ms,
-            ))
-            .await?;
-        let id = row
-            .get_value(0)
-            .ok()
-            .and_then(|v| v.as_integer().copied())
-            .ok_or_else(|| anyhow::anyhow!("Failed to get tool call ID"))?;
-        Ok(id)
+        row.as_deref()
+            .and_then(|row| integer_at(row, 0))
+            .ok_or_else(|| AgentFsError::Other(anyhow::anyhow!("Failed to get tool call ID")))
     }
 
     /// Mark a tool call as failed
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(id = id)))]
     pub async fn error(&self, id: i64, error: &str) -> Result<()> {
-        let completed_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        let completed_at = self.clock.unix_time();
 
         // Get the started_at time to calculate duration
-        let mut rows = self
-            .conn
-            .query("SELECT started_at FROM tool_calls WHERE id = ?", (id,))
+        let started_at = self.started_at(id).await?;
+        let duration_ms = (completed_at - started_at) * 1000;
+
+        self.backend
+            .execute(
+                "UPDATE tool_calls
+                SET error = ?, status = 'error', completed_at = ?, duration_ms = ?
+                WHERE id = ?",
+                vec![
+                    Value::Text(error.to_string()),
+                    Value::Integer(completed_at),
+                    Value::Integer(duration_ms),
+                    Value::Integer(id),
+                ],
+            )
             .await?;
 
-        let started_at = if let Some(row) = rows.next().await? {
-            row.get_value(0)
-                .ok()
-                .and_then(|v| v.as_integer().copied())
-                .ok_or_else(|| anyhow::anyhow!("Invalid started_at value"))?
-        } else {
-            anyhow::bail!("Tool call not found");
-        };
+        Ok(())
+    }
 
+    /// Cancel a tool call that hasn't finished
+    ///
+    /// The call is marked as failed with `cancelled` (and the reason, if any)
+    /// as its error, so it counts as failed in the stats. Returns false if
+    /// the call had already finished, in which case it is left as it was.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all, fields(id = id), ret))]
+    pub async fn cancel(&self, id: i64, reason: Option<&str>) -> Result<bool> {
+        let completed_at = self.clock.unix_time();
+        let started_at = self.started_at(id).await?;
         let duration_ms = (completed_at - started_at) * 1000;
+        let error = match reason {
+            Some(reason) => format!("cancelled: {reason}"),
+            None => "cancelled".to_string(),
+        };
 
-        self.conn
+        let updated = self
+            .backend
             .execute(
                 "UPDATE tool_calls
                 SET error = ?, status = 'error', completed_at = ?, duration_ms = ?
-                WHERE id = ?",
-                (error, completed_at, duration_ms, id),
+                WHERE id = ? AND status = 'pending'",
+                vec![
+                    Value::Text(error),
+                    Value::Integer(completed_at),
+                    Value::Integer(duration_ms),
+                    Value::Integer(id),
+                ],
             )
             .await?;
 
-        Ok(())
+        Ok(updated > 0)
     }
 
-    /// Get a tool call by ID
-    pub async fn get(&self, id: i64) -> Result<Option<ToolCall>> {
+    /// Get the tool calls that were started but haven't finished, oldest first
+    ///
+    /// These are the calls an agent runtime restarting after a crash finds
+    /// interrupted. Their `duration_ms` is how long they have been running so
+    /// far, while `completed_at` stays `None`.
+    pub async fn in_flight(&self) -> Result<Vec<ToolCall>> {
+        let now = self.clock.unix_time();
         let mut rows = self
-            .conn
+            .backend
             .query(
+                "SELECT id, name, parameters, result, error, status, started_at, completed_at, duration_ms
+                FROM tool_calls
+                WHERE status = 'pending'
+                ORDER BY started_at, id",
+                Vec::new(),
+            )
+            .await?;
+
+        let mut calls = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let mut call = self.row_to_tool_call(&row)?;
+            call.duration_ms = Some((now - call.started_at).max(0) * 1000);
+            calls.push(call);
+        }
+        Ok(calls)
+    }
+
+    /// Look up the start time of a tool call
+    async fn started_at(&self, id: i64) -> Result<i64> {
+        let row = self
+            .backend
+            .query_one(
+                "SELECT started_at FROM tool_calls WHERE id = ?",
+                vec![Value::Integer(id)],
+            )
+            .await?
+            .ok_or_else(|| AgentFsError::NotFound(format!("Tool call {id}")))?;
+
+        integer_at(&row, 0)
+            .ok_or_else(|| AgentFsError::WrongType("Invalid started_at value".to_string()))
+    }
+
+    /// Get a tool call by ID
+    pub async fn get(&self, id: i64) -> Result<Option<ToolCall>> {
+        let row = self
+            .backend
+            .query_one(
                 "SELECT id, name, parameters, result, error, status, started_at, completed_at, duration_ms
                 FROM tool_calls WHERE id = ?",
-                (id,),
+                vec![Value::Integer(id)],
             )
             .await?;
 
-        if let Some(row) = rows.next().await? {
-            Ok(Some(self.row_to_tool_call(&row)?))
-        } else {
-            Ok(None)
+        row.map(|row| self.row_to_tool_call(&row)).transpose()
+    }
+
+    /// Get several tool calls by ID in one round-trip
+    ///
+    /// The result lines up with `ids`: entry `i` is the call with `ids[i]`, or
+    /// `None` if there is no such call.
+    pub async fn get_many(&self, ids: &[i64]) -> Result<Vec<Option<ToolCall>>> {
+        let mut found = HashMap::new();
+        // Stay well below SQLite's limit on bound parameters
+        for batch in ids.chunks(GET_MANY_BATCH_SIZE) {
+            let placeholders = vec!["?"; batch.len()].join(", ");
+            let mut rows = self
+                .backend
+                .query(
+                    &format!(
+                        "SELECT id, name, parameters, result, error, status, started_at, completed_at, duration_ms
+                        FROM tool_calls WHERE id IN ({placeholders})"
+                    ),
+                    batch.iter().map(|id| Value::Integer(*id)).collect(),
+                )
+                .await?;
+            while let Some(row) = rows.next().await? {
+                let call = self.row_to_tool_call(&row)?;
+                found.insert(call.id, call);
+            }
         }
+
+        Ok(ids.iter().map(|id| found.get(id).cloned()).collect())
     }
 
     /// Get recent tool calls with optional limit
     pub async fn recent(&self, limit: Option<i64>) -> Result<Vec<ToolCall>> {
         let limit = limit.unwrap_or(100);
         let mut rows = self
-            .conn
+            .backend
             .query(
                 "SELECT id, name, parameters, result, error, status, started_at, completed_at, duration_ms
                 FROM tool_calls
                 ORDER BY started_at DESC
                 LIMIT ?",
-                (limit,),
+                vec![Value::Integer(limit)],
             )
             .await?;
 
@@ -305,15 +562,45 @@ impl ToolCalls {
         while let Some(row) = rows.next().await? {
             calls.push(self.row_to_tool_call(&row)?);
         }
-
         Ok(calls)
     }
 
-    /// Get statistics for a specific tool
-    pub async fn stats_for(&self, name: &str) -> Result<Option<ToolCallStats>> {
+    /// Export all tool calls as OpenTelemetry-style spans, oldest first
+    ///
+    /// All spans share one trace ID derived from the first recorded call, so
+    /// repeated exports of the same agent land in the same trace.
+    pub async fn export_spans(&self) -> Result<Vec<Span>> {
         let mut rows = self
-            .conn
+            .backend
             .query(
+                "SELECT id, name, parameters, result, error, status, started_at, completed_at, duration_ms
+                FROM tool_calls
+                ORDER BY started_at ASC, id ASC",
+                Vec::new(),
+            )
+            .await?;
+
+        let mut calls = Vec::new();
+        while let Some(row) = rows.next().await? {
+            calls.push(self.row_to_tool_call(&row)?);
+        }
+
+        let Some(first) = calls.first() else {
+            return Ok(Vec::new());
+        };
+        let trace_id = format!("{:016x}{:016x}", first.started_at, first.id);
+
+        Ok(calls
+            .iter()
+            .map(|call| Span::from_tool_call(&trace_id, call))
+            .collect())
+    }
+
+    /// Get statistics for a specific tool
+    pub async fn stats_for(&self, name: &str) -> Result<Option<ToolCallStats>> {
+        let row = self
+            .backend
+            .query_one(
                 "SELECT
                     name,
                     COUNT(*) as total_calls,
@@ -323,21 +610,36 @@ impl ToolCalls {
                 FROM tool_calls
                 WHERE name = ?
                 GROUP BY name",
-                (name,),
+                vec![Value::Text(name.to_string())],
             )
             .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let mut stats = self.row_to_stats(&row)?;
 
-        if let Some(row) = rows.next().await? {
-            Ok(Some(self.row_to_stats(&row)?))
-        } else {
-            Ok(None)
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT duration_ms FROM tool_calls
+                WHERE name = ? AND duration_ms IS NOT NULL
+                ORDER BY duration_ms",
+                vec![Value::Text(name.to_string())],
+            )
+            .await?;
+        let mut durations = Vec::new();
+        while let Some(row) = rows.next().await? {
+            durations.extend(integer_at(&row, 0));
         }
+        stats.set_percentiles(&durations);
+
+        Ok(Some(stats))
     }
 
     /// Get statistics for all tools
     pub async fn stats(&self) -> Result<Vec<ToolCallStats>> {
         let mut rows = self
-            .conn
+            .backend
             .query(
                 "SELECT
                     name,
@@ -348,94 +650,68 @@ impl ToolCalls {
                 FROM tool_calls
                 GROUP BY name
                 ORDER BY total_calls DESC",
-                (),
+                Vec::new(),
             )
             .await?;
 
-        let mut stats = Vec::new();
+        let mut all_stats = Vec::new();
         while let Some(row) = rows.next().await? {
-            stats.push(self.row_to_stats(&row)?);
+            all_stats.push(self.row_to_stats(&row)?);
         }
 
-        Ok(stats)
-    }
-
-    fn row_to_tool_call(&self, row: &turso::Row) -> Result<ToolCall> {
-        let id = row
-            .get_value(0)
-            .ok()
-            .and_then(|v| v.as_integer().copied())
-            .unwrap_or(0);
-
-        let name = row
-            .get_value(1)
-            .ok()
-            .and_then(|v| {
-                if let Value::Text(s) = v {
-                    Some(s.clone())
-                } else {
-                    None
-                }
-            })
-            .unwrap_or_default();
-
-        let parameters = row.get_value(2).ok().and_then(|v| {
-            if let Value::Text(s) = v {
-                if !s.is_empty() {
-                    serde_json::from_str(s.as_str()).ok()
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        });
-
-        let result = row.get_value(3).ok().and_then(|v| {
-            if let Value::Text(s) = v {
-                if !s.is_empty() {
-                    serde_json::from_str(s.as_str()).ok()
-                } else {
-                    None
-                }
-            } else {
-                None
+        let mut durations: HashMap<String, Vec<i64>> = HashMap::new();
+        let mut rows = self
+            .backend
+            .query(
+                "SELECT name, duration_ms FROM tool_calls
+                WHERE duration_ms IS NOT NULL
+                ORDER BY name, duration_ms",
+                Vec::new(),
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            if let (Some(name), Some(duration)) = (text_at(&row, 0), integer_at(&row, 1)) {
+                durations
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(duration);
             }
-        });
-
-        let error = row.get_value(4).ok().and_then(|v| {
-            if let Value::Text(s) = v {
-                if !s.is_empty() {
-                    Some(s.clone())
-                } else {
-                    None
-                }
-            } else {
-                None
+        }
+
+        for stats in &mut all_stats {
+            if let Some(durations) = durations.get(&stats.name) {
+                stats.set_percentiles(durations);
             }
-        });
-
-        let status = row
-            .get_value(5)
-            .ok()
-            .and_then(|v| {
-                if let Value::Text(s) = v {
-                    Some(ToolCallStatus::from(s.as_str()))
-                } else {
-                    None
-                }
-            })
+        }
+        Ok(all_stats)
+    }
+
+    fn row_to_tool_call(&self, row: &[Value]) -> Result<ToolCall> {
+        let id = integer_at(row, 0).unwrap_or(0);
+
+        let name = text_at(row, 1).map(str::to_string).unwrap_or_default();
+
+        let parameters = text_at(row, 2)
+            .filter(|s| !s.is_empty())
+            .and_then(|s| serde_json::from_str(s).ok());
+
+        let result = text_at(row, 3)
+            .filter(|s| !s.is_empty())
+            .and_then(|s| serde_json::from_str(s).ok());
+
+        let error = text_at(row, 4)
+            .filter(|s| !s.is_empty())
+            .map(str::to_string);
+
+        let status = text_at(row, 5)
+            .map(ToolCallStatus::from)
             .unwrap_or(ToolCallStatus::Pending);
 
-        let started_at = row
-            .get_value(6)
-            .ok()
-            .and_then(|v| v.as_integer().copied())
-            .unwrap_or(0);
+        let started_at = integer_at(row, 6).unwrap_or(0);
 
-        let completed_at = row.get_value(7).ok().and_then(|v| v.as_integer().copied());
+        let completed_at = integer_at(row, 7);
 
-        let duration_ms = row.get_value(8).ok().and_then(|v| v.as_integer().copied());
+        let duration_ms = integer_at(row, 8);
 
         Ok(ToolCall {
             id,
@@ -450,46 +726,20 @@ impl ToolCalls {
         })
     }
 
-    fn row_to_stats(&self, row: &turso::Row) -> Result<ToolCallStats> {
-        let name = row
-            .get_value(0)
-            .ok()
-            .and_then(|v| {
-                if let Value::Text(s) = v {
-                    Some(s.clone())
-                } else {
-                    None
-                }
-            })
-            .unwrap_or_default();
-
-        let total_calls = row
-            .get_value(1)
-            .ok()
-            .and_then(|v| v.as_integer().copied())
-            .unwrap_or(0);
-
-        let successful = row
-            .get_value(2)
-            .ok()
-            .and_then(|v| v.as_integer().copied())
-            .unwrap_or(0);
-
-        let failed = row
-            .get_value(3)
-            .ok()
-            .and_then(|v| v.as_integer().copied())
-            .unwrap_or(0);
-
-        let avg_duration_ms = row
-            .get_value(4)
-            .ok()
-            .and_then(|v| match v {
-                Value::Real(f) => Some(f),
-                Value::Integer(i) => Some(i as f64),
-                _ => None,
-            })
-            .unwrap_or(0.0);
+    fn row_to_stats(&self, row: &[Value]) -> Result<ToolCallStats> {
+        let name = text_at(row, 0).map(str::to_string).unwrap_or_default();
+
+        let total_calls = integer_at(row, 1).unwrap_or(0);
+
+        let successful = integer_at(row, 2).unwrap_or(0);
+
+        let failed = integer_at(row, 3).unwrap_or(0);
+
+        let avg_duration_ms = match row.get(4) {
+            Some(Value::Real(f)) => *f,
+            Some(Value::Integer(i)) => *i as f64,
+            _ => 0.0,
+        };
 
         Ok(ToolCallStats {
             name,
@@ -497,6 +747,23 @@ impl ToolCalls {
             successful,
             failed,
             avg_duration_ms,
+            p50_duration_ms: None,
+            p95_duration_ms: None,
+            p99_duration_ms: None,
         })
     }
 }
+
+fn integer_at(row: &[Value], idx: usize) -> Option<i64> {
+    match row.get(idx) {
+        Some(Value::Integer(i)) => Some(*i),
+        _ => None,
+    }
+}
+
+fn text_at(row: &[Value], idx: usize) -> Option<&str> {
+    match row.get(idx) {
+        Some(Value::Text(s)) => Some(s.as_str()),
+        _ => None,
+    }
+}